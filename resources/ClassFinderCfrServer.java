@@ -0,0 +1,107 @@
+import java.io.BufferedOutputStream;
+import java.io.BufferedReader;
+import java.io.FileDescriptor;
+import java.io.FileOutputStream;
+import java.io.IOException;
+import java.io.InputStreamReader;
+import java.io.OutputStream;
+import java.nio.charset.StandardCharsets;
+import java.util.Collection;
+import java.util.Collections;
+import java.util.HashMap;
+import java.util.List;
+import java.util.Map;
+
+import org.benf.cfr.reader.api.CfrDriver;
+import org.benf.cfr.reader.api.OutputSinkFactory;
+
+/**
+ * Persistent CFR helper used by class-finder's --cfr-server mode.
+ *
+ * Launched as {@code java -cp cfr.jar ClassFinderCfrServer.java [key=value...]}
+ * (JDK 11+ single-file source launcher). Arguments are extra CFR options.
+ * Each stdin line is a request, {@code <classpath>\t<class name>}, where the
+ * classpath is passed to CFR as extraclasspath; each reply is
+ * {@code OK <n>\n} or {@code ERR <n>\n} followed by n bytes of UTF-8.
+ */
+public class ClassFinderCfrServer {
+    public static void main(String[] args) throws IOException {
+        Map<String, String> baseOptions = new HashMap<>();
+        baseOptions.put("silent", "true");
+        baseOptions.put("comments", "false");
+        for (String arg : args) {
+            int eq = arg.indexOf('=');
+            if (eq > 0) {
+                baseOptions.put(arg.substring(0, eq), arg.substring(eq + 1));
+            }
+        }
+
+        BufferedReader in =
+                new BufferedReader(new InputStreamReader(System.in, StandardCharsets.UTF_8));
+        OutputStream out = new BufferedOutputStream(new FileOutputStream(FileDescriptor.out));
+        // Anything CFR prints to the console must not corrupt the reply stream.
+        System.setOut(System.err);
+
+        String line;
+        while ((line = in.readLine()) != null) {
+            int tab = line.indexOf('\t');
+            if (tab < 0) {
+                reply(out, "ERR", "malformed request: " + line);
+                continue;
+            }
+            String classpath = line.substring(0, tab);
+            String className = line.substring(tab + 1);
+            try {
+                reply(out, "OK", decompile(baseOptions, classpath, className));
+            } catch (Throwable t) {
+                reply(out, "ERR", String.valueOf(t));
+            }
+        }
+    }
+
+    private static String decompile(
+            Map<String, String> baseOptions, String classpath, String className) {
+        StringBuilder java = new StringBuilder();
+        StringBuilder errors = new StringBuilder();
+        OutputSinkFactory sinks = new OutputSinkFactory() {
+            @Override
+            public List<SinkClass> getSupportedSinks(
+                    SinkType sinkType, Collection<SinkClass> available) {
+                return Collections.singletonList(SinkClass.STRING);
+            }
+
+            @Override
+            public <T> Sink<T> getSink(SinkType sinkType, SinkClass sinkClass) {
+                switch (sinkType) {
+                    case JAVA:
+                        return value -> java.append(value).append('\n');
+                    case EXCEPTION:
+                        return value -> errors.append(value).append('\n');
+                    default:
+                        return value -> {};
+                }
+            }
+        };
+
+        Map<String, String> options = new HashMap<>(baseOptions);
+        options.put("extraclasspath", classpath);
+        CfrDriver driver = new CfrDriver.Builder()
+                .withOptions(options)
+                .withOutputSink(sinks)
+                .build();
+        driver.analyse(Collections.singletonList(className));
+
+        if (java.length() == 0) {
+            String detail = errors.length() > 0 ? errors.toString().trim() : "no output";
+            throw new IllegalStateException(detail + " (" + className + ")");
+        }
+        return java.toString();
+    }
+
+    private static void reply(OutputStream out, String status, String body) throws IOException {
+        byte[] bytes = body.getBytes(StandardCharsets.UTF_8);
+        out.write((status + " " + bytes.length + "\n").getBytes(StandardCharsets.UTF_8));
+        out.write(bytes);
+        out.flush();
+    }
+}
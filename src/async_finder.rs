@@ -0,0 +1,290 @@
+//! A tokio front end for [`ClassFinder`], behind the `async` feature.
+//!
+//! Decompiler JVMs run through `tokio::process`, so a find waiting on CFR
+//! does not hold a worker thread; cache, registry and jar reads run on the
+//! blocking pool. Dropping a [`AsyncClassFinder::find`] future kills the JVM
+//! it started, together with anything that JVM spawned. `find` only reads the
+//! cache, so a cancelled find leaves it exactly as it was.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::cache::CacheStats;
+use crate::deadline::{self, Phase};
+use crate::decompiler::{JavaInvocation, java_process_slots, kill_process_group, timeout_error};
+use crate::find_options::FindOptions;
+use crate::finder::{ClassFinder, ClassMatch, FindResult, FindVersion, IndexResult, LoadResult};
+
+/// Shares one [`ClassFinder`] between tasks; clones are cheap.
+#[derive(Clone)]
+pub struct AsyncClassFinder {
+    inner: Arc<ClassFinder>,
+}
+
+impl AsyncClassFinder {
+    pub fn new(finder: ClassFinder) -> Self {
+        Self {
+            inner: Arc::new(finder),
+        }
+    }
+
+    pub fn finder(&self) -> &ClassFinder {
+        &self.inner
+    }
+
+    /// [`ClassFinder::find`] without blocking the runtime.
+    pub async fn find(&self, class_name: &str, options: &FindOptions) -> Result<FindResult> {
+        let found = self.match_class(class_name, options).await?;
+        self.find_matched(found).await
+    }
+
+    pub async fn match_class(&self, class_name: &str, options: &FindOptions) -> Result<ClassMatch> {
+        let class_name = class_name.to_string();
+        let options = options.clone();
+        self.blocking(move |finder| finder.match_class(&class_name, &options))
+            .await
+    }
+
+    /// [`ClassFinder::find_matched`], running the decompiler as a child
+    /// process of the runtime.
+    pub async fn find_matched(&self, found: ClassMatch) -> Result<FindResult> {
+        self.inner.check_matched(&found)?;
+        let mut versions = Vec::new();
+        for jar_path in &found.jars {
+            let lookup = found.clone();
+            let jar = jar_path.clone();
+            let stored = self
+                .blocking(move |finder| {
+                    finder.stored_version_in(&mut finder.read_session(), &lookup, &jar)
+                })
+                .await?;
+            let version = match stored {
+                Some(version) => version,
+                None => self.decompile(&found, jar_path).await?,
+            };
+            versions.push(version);
+        }
+        Ok(found.into_result(versions))
+    }
+
+    /// [`ClassFinder::load_jar`] on the blocking pool. It writes the cache,
+    /// so it runs to completion even if the future is dropped.
+    pub async fn load_jar(&self, jar_path: PathBuf) -> Result<LoadResult> {
+        self.blocking(move |finder| finder.load_jar(&jar_path))
+            .await
+    }
+
+    /// [`ClassFinder::index`] on the blocking pool; like `load_jar` it runs
+    /// to completion once started.
+    pub async fn index(&self, root: PathBuf) -> Result<IndexResult> {
+        self.blocking(move |finder| finder.index(root)).await
+    }
+
+    pub async fn stats(&self) -> Result<CacheStats> {
+        self.blocking(ClassFinder::stats).await
+    }
+
+    async fn decompile(&self, found: &ClassMatch, jar_path: &Path) -> Result<FindVersion> {
+        deadline::enter(Phase::Decompile);
+        let started = Instant::now();
+        let decompiler = Arc::clone(
+            self.inner
+                .decompiler()
+                .context("No decompiler configured")?,
+        );
+        let class_name = found.class_name.clone();
+        let jar = jar_path.to_path_buf();
+        let invocation = {
+            let decompiler = Arc::clone(&decompiler);
+            self.blocking(move |_| decompiler.class_invocation(&jar, &class_name))
+                .await?
+        };
+        let output = match &invocation {
+            Some(invocation) => Some(run_invocation(invocation).await),
+            None => None,
+        };
+
+        let found = found.clone();
+        let jar = jar_path.to_path_buf();
+        self.blocking(move |finder| {
+            let decompiled = match (invocation, output) {
+                (Some(invocation), Some(output)) => {
+                    output.and_then(|output| decompiler.invocation_output(&invocation, output))
+                }
+                // No command to hand out (e.g. the CFR server): run it here.
+                _ => decompiler.decompile_class(&jar, &found.class_name),
+            };
+            finder.decompiled_version(&found, &jar, decompiled, started.elapsed())
+        })
+        .await
+    }
+
+    async fn blocking<R, F>(&self, f: F) -> Result<R>
+    where
+        R: Send + 'static,
+        F: FnOnce(&ClassFinder) -> Result<R> + Send + 'static,
+    {
+        let finder = Arc::clone(&self.inner);
+        tokio::task::spawn_blocking(move || f(&finder))
+            .await
+            .context("class-finder task panicked")?
+    }
+}
+
+async fn run_invocation(invocation: &JavaInvocation) -> Result<std::process::Output> {
+    run_command(invocation.command(), &invocation.target, invocation.timeout).await
+}
+
+/// Runs `command` in its own process group under a `java_process_slots`
+/// slot, killing the group on timeout or when the future is dropped.
+async fn run_command(
+    mut command: Command,
+    target: &str,
+    timeout: Duration,
+) -> Result<std::process::Output> {
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    let mut command = tokio::process::Command::from(command);
+    command.kill_on_drop(true);
+
+    let _slot = tokio::task::spawn_blocking(|| java_process_slots().acquire())
+        .await
+        .context("class-finder task panicked")?;
+    let child = command
+        .spawn()
+        .context("Failed to execute java (ensure JRE/JDK is installed)")?;
+    let mut group = ProcessGroupGuard(child.id());
+    let output = tokio::time::timeout(deadline::cap(timeout), child.wait_with_output()).await;
+    match output {
+        Ok(output) => {
+            group.0 = None;
+            output.context("Failed to wait for child process")
+        }
+        Err(_) => Err(timeout_error(target, timeout)),
+    }
+}
+
+/// Kills the process group of a child that has not been waited for.
+struct ProcessGroupGuard(Option<u32>);
+
+impl Drop for ProcessGroupGuard {
+    fn drop(&mut self) {
+        if let Some(pid) = self.0 {
+            kill_process_group(pid);
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn shell(script: &str) -> Command {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(script);
+        command
+    }
+
+    fn pid_file(name: &str) -> PathBuf {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        std::env::temp_dir().join(format!(
+            "class_finder_async_test_{}_{}_{}",
+            std::process::id(),
+            nanos,
+            name
+        ))
+    }
+
+    fn alive(pid: i32) -> bool {
+        // A killed orphan can linger as a zombie when pid 1 does not reap.
+        if let Ok(stat) = std::fs::read_to_string(format!("/proc/{pid}/stat"))
+            && stat
+                .rsplit(')')
+                .next()
+                .is_some_and(|rest| rest.trim_start().starts_with('Z'))
+        {
+            return false;
+        }
+        // SAFETY: signal 0 only checks that the process exists.
+        unsafe { libc::kill(pid, 0) == 0 }
+    }
+
+    async fn read_pid(path: &Path) -> i32 {
+        for _ in 0..200 {
+            if let Ok(raw) = std::fs::read_to_string(path)
+                && let Ok(pid) = raw.trim().parse()
+            {
+                return pid;
+            }
+            tokio::time::sleep(Duration::from_millis(25)).await;
+        }
+        panic!("child never wrote {}", path.display());
+    }
+
+    async fn wait_gone(pid: i32) -> bool {
+        for _ in 0..200 {
+            if !alive(pid) {
+                return true;
+            }
+            tokio::time::sleep(Duration::from_millis(25)).await;
+        }
+        false
+    }
+
+    #[tokio::test]
+    async fn run_command_collects_output() {
+        let output = run_command(
+            shell("echo out; echo err >&2"),
+            "Demo",
+            Duration::from_secs(10),
+        )
+        .await
+        .unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"out\n");
+        assert_eq!(output.stderr, b"err\n");
+    }
+
+    #[tokio::test]
+    async fn run_command_times_out_and_kills_the_group() {
+        let pid_file = pid_file("timeout");
+        let script = format!("sleep 30 & echo $! > {}; wait", pid_file.display());
+
+        let err = run_command(shell(&script), "Demo", Duration::from_millis(500))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Demo"), "{err}");
+        let pid = read_pid(&pid_file).await;
+        assert!(wait_gone(pid).await, "sleep {pid} survived the timeout");
+        let _ = std::fs::remove_file(pid_file);
+    }
+
+    #[tokio::test]
+    async fn dropping_the_future_kills_the_group() {
+        let pid_file = pid_file("drop");
+        let script = format!("sleep 30 & echo $! > {}; wait", pid_file.display());
+
+        let run = tokio::spawn(async move {
+            run_command(shell(&script), "Demo", Duration::from_secs(60)).await
+        });
+        let pid = read_pid(&pid_file).await;
+        assert!(alive(pid));
+        run.abort();
+        assert!(run.await.unwrap_err().is_cancelled());
+        assert!(wait_gone(pid).await, "sleep {pid} survived cancellation");
+        let _ = std::fs::remove_file(pid_file);
+    }
+}
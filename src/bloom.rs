@@ -0,0 +1,142 @@
+//! Bloom filter over the class paths of a cataloged jar, kept in its
+//! artifact manifest row so probes can rule a jar out without opening it.
+//!
+//! A filter never misses a class path it was built from; a class it was not
+//! built from is reported as possibly present about 1% of the time, and the
+//! probe then opens the jar as before.
+
+use crate::scan::class_name_to_class_path;
+
+/// Bits per class path; with [`HASHES`] this gives about 1% false
+/// positives.
+const BITS_PER_ITEM: usize = 10;
+const HASHES: u32 = 7;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassFilter {
+    words: Vec<u64>,
+    hashes: u32,
+}
+
+impl ClassFilter {
+    /// An empty filter sized for `items` class paths.
+    pub fn with_capacity(items: usize) -> Self {
+        let bits = (items * BITS_PER_ITEM).max(64);
+        Self {
+            words: vec![0; bits.div_ceil(64)],
+            hashes: HASHES,
+        }
+    }
+
+    /// A filter over the class paths (`a/b/C.class`) of the fully qualified
+    /// `classes`.
+    pub fn for_classes(classes: &[String]) -> Self {
+        let mut filter = Self::with_capacity(classes.len());
+        for class in classes {
+            filter.insert(&class_name_to_class_path(class));
+        }
+        filter
+    }
+
+    pub fn insert(&mut self, class_path: &str) {
+        let bits: Vec<usize> = self.bits(class_path).collect();
+        for bit in bits {
+            self.words[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+
+    /// False only when `class_path` was never inserted.
+    pub fn might_contain(&self, class_path: &str) -> bool {
+        self.bits(class_path)
+            .all(|bit| self.words[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    /// `<hashes>:<hex of the little-endian words>`.
+    pub fn encode(&self) -> String {
+        let bytes: Vec<u8> = self.words.iter().flat_map(|w| w.to_le_bytes()).collect();
+        format!("{}:{}", self.hashes, hex::encode(bytes))
+    }
+
+    /// `None` for anything [`Self::encode`] did not produce.
+    pub fn decode(encoded: &str) -> Option<Self> {
+        let (hashes, bits) = encoded.split_once(':')?;
+        let hashes = hashes.parse().ok().filter(|&h| h > 0)?;
+        let bytes = hex::decode(bits).ok()?;
+        if bytes.is_empty() || bytes.len() % 8 != 0 {
+            return None;
+        }
+        let words = bytes
+            .chunks_exact(8)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+        Some(Self { words, hashes })
+    }
+
+    /// Double hashing: bit `i` is `h1 + i * h2` modulo the filter size.
+    fn bits(&self, class_path: &str) -> impl Iterator<Item = usize> + '_ {
+        let len = (self.words.len() * 64) as u64;
+        let h1 = fnv1a(class_path.as_bytes());
+        let h2 = mix(h1) | 1;
+        (0..u64::from(self.hashes))
+            .map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % len) as usize)
+    }
+}
+
+/// Stable across builds and platforms, unlike `DefaultHasher`: filters are
+/// persisted.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// The splitmix64 finalizer.
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classes(prefix: &str, count: usize) -> Vec<String> {
+        (0..count)
+            .map(|i| format!("org.example.{prefix}{}.Type{i}", i % 17))
+            .collect()
+    }
+
+    #[test]
+    fn inserted_class_paths_are_never_missed() {
+        for count in [0, 1, 10, 1000, 5000] {
+            let inserted = classes("pkg", count);
+            let filter = ClassFilter::for_classes(&inserted);
+            for class in &inserted {
+                assert!(filter.might_contain(&class_name_to_class_path(class)));
+            }
+        }
+    }
+
+    #[test]
+    fn most_other_class_paths_are_ruled_out() {
+        let filter = ClassFilter::for_classes(&classes("pkg", 2000));
+        let others = classes("other", 10_000);
+        let false_positives = others
+            .iter()
+            .filter(|class| filter.might_contain(&class_name_to_class_path(class)))
+            .count();
+        assert!(false_positives < 300, "{false_positives} false positives");
+    }
+
+    #[test]
+    fn encoding_round_trips() {
+        let filter = ClassFilter::for_classes(&classes("pkg", 100));
+        let encoded = filter.encode();
+        assert!(encoded.starts_with("7:"));
+        assert_eq!(ClassFilter::decode(&encoded), Some(filter));
+        for invalid in ["1", "", "7:", "0:00000000000000ff", "7:zz", "7:00ff"] {
+            assert_eq!(ClassFilter::decode(invalid), None, "{invalid}");
+        }
+    }
+}
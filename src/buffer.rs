@@ -5,20 +5,30 @@
 //! improves write performance by reducing transaction overhead.
 //!
 //! A background thread handles the actual flushing, allowing the main thread
-//! to continue processing without blocking on database writes.
+//! to continue processing without blocking on database writes. The queue is
+//! bounded: once `capacity` writes are waiting, `enqueue` blocks until the
+//! flusher catches up, so a fast producer cannot buffer unbounded sources.
 
-use anyhow::Result;
+use anyhow::{Result, anyhow, bail};
 use heed::types::Str;
 use heed::{Database, Env};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
 use std::path::PathBuf;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, SyncSender};
+use std::sync::{Arc, Mutex};
 use std::thread::JoinHandle;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use crate::cache::{CLASS_SOURCES_DB, CachedClassSource, ClassContentSource};
+use crate::cache::{
+    ARTIFACT_MANIFEST_DB, CLASS_HOTSPOT_DB, CLASS_REGISTRY_DB, CLASS_SOURCES_DB, CachedClassSource,
+    ClassContentSource, JAR_HOTSPOT_DB, JARS_DB, META_DB, WARMUP_QUEUE_DB,
+    WRITE_BUFFER_METRICS_KEY, WriteBufferMetrics,
+};
+use crate::hotspot::{record_class_access, record_jar_access};
+use crate::registry::add_artifact;
 
 type StrDb = Database<Str, Str>;
 
@@ -27,37 +37,163 @@ pub struct PendingWrite {
     pub key: String,
     pub content: String,
     pub source: ClassContentSource,
+    pub options: Vec<String>,
+    pub decompiler_version: Option<String>,
+    pub encoding: Option<String>,
+    pub postprocessed: bool,
+}
+
+/// Tables the buffer can write to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Table {
+    ClassSources,
+    Jars,
+    ClassRegistry,
+    ArtifactManifest,
+    WarmupQueue,
+    JarHotspot,
+    ClassHotspot,
+}
+
+impl Table {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::ClassSources => CLASS_SOURCES_DB,
+            Self::Jars => JARS_DB,
+            Self::ClassRegistry => CLASS_REGISTRY_DB,
+            Self::ArtifactManifest => ARTIFACT_MANIFEST_DB,
+            Self::WarmupQueue => WARMUP_QUEUE_DB,
+            Self::JarHotspot => JAR_HOTSPOT_DB,
+            Self::ClassHotspot => CLASS_HOTSPOT_DB,
+        }
+    }
+}
+
+/// A change applied by the flusher in the order it was queued.
+#[derive(Debug, Clone)]
+pub enum Mutation {
+    /// Stores a class source in [`Table::ClassSources`].
+    Put(PendingWrite),
+    PutValue {
+        table: Table,
+        key: String,
+        value: String,
+    },
+    Delete {
+        table: Table,
+        key: String,
+    },
+    /// Deletes every key starting with `prefix`.
+    DeletePrefix {
+        table: Table,
+        prefix: String,
+    },
+    /// Adds `jar_key` to the artifacts listed for `class_name` in
+    /// [`Table::ClassRegistry`], reading the current list in the same
+    /// transaction.
+    AddArtifact {
+        class_name: String,
+        jar_key: String,
+    },
+    /// Counts an access to `class_name` in `jar_key` in
+    /// [`Table::ClassHotspot`], keeping the jar's tracked class count in
+    /// [`Table::JarHotspot`].
+    RecordClassAccess {
+        class_name: String,
+        jar_key: String,
+        at: u64,
+    },
+    /// Adds `count` accesses to `jar_key` in [`Table::JarHotspot`].
+    RecordJarAccess {
+        jar_key: String,
+        count: u32,
+        at: u64,
+    },
+}
+
+impl Mutation {
+    /// Table and key of a mutation that replaces one value outright; the
+    /// others are never coalesced.
+    fn key(&self) -> Option<(Table, &str)> {
+        match self {
+            Self::Put(entry) => Some((Table::ClassSources, &entry.key)),
+            Self::PutValue { table, key, .. } | Self::Delete { table, key } => Some((*table, key)),
+            Self::DeletePrefix { .. }
+            | Self::AddArtifact { .. }
+            | Self::RecordClassAccess { .. }
+            | Self::RecordJarAccess { .. } => None,
+        }
+    }
+}
+
+enum Message {
+    Write(Mutation),
+    /// Mutations that must commit in the same transaction.
+    Group(Vec<Mutation>),
+    /// Commit everything queued before this message, then acknowledge.
+    Flush(SyncSender<()>),
+}
+
+/// Outcome of [`WriteBuffer::flush`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FlushReport {
+    /// Writes committed while the flush was in progress.
+    pub written: usize,
+    /// Writes queued since the flush was requested and not yet committed.
+    pub pending: usize,
+    pub duration: Duration,
 }
 
 #[derive(Debug, Clone)]
 pub struct WriteBufferHandle {
-    tx: Sender<PendingWrite>,
-    pending: Arc<AtomicUsize>,
+    tx: SyncSender<Message>,
+    counters: Arc<Counters>,
     gauge_path: Option<PathBuf>,
 }
 
 impl WriteBufferHandle {
+    /// Queues `entry`, blocking while the buffer is at capacity. Fails if the
+    /// flusher thread has stopped.
     pub fn enqueue(&self, entry: PendingWrite) -> Result<()> {
-        let prev = self.pending.fetch_add(1, Ordering::Relaxed);
-        if prev == 0
-            && let Some(path) = self.gauge_path.as_deref()
-        {
-            let _ = write_gauge(path, 1);
-        }
+        self.mutate(Mutation::Put(entry))
+    }
 
-        if self.tx.send(entry).is_ok() {
-            return Ok(());
-        }
+    /// Queues any [`Mutation`], with the same blocking as `enqueue`.
+    pub fn mutate(&self, mutation: Mutation) -> Result<()> {
+        send(
+            &self.tx,
+            &self.counters,
+            self.gauge_path.as_deref(),
+            Message::Write(mutation),
+        )
+    }
 
-        self.pending.fetch_sub(1, Ordering::Relaxed);
-        Ok(())
+    /// See [`WriteBuffer::mutate_group`].
+    pub fn mutate_group(&self, mutations: Vec<Mutation>) -> Result<()> {
+        send(
+            &self.tx,
+            &self.counters,
+            self.gauge_path.as_deref(),
+            Message::Group(mutations),
+        )
+    }
+
+    /// See [`WriteBuffer::flush`].
+    pub fn flush(&self) -> Result<FlushReport> {
+        flush_through(&self.tx, &self.counters)
+    }
+
+    pub fn stats(&self) -> BufferStats {
+        self.counters.stats()
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
 pub struct BufferConfig {
     pub batch_size: usize,
     pub flush_interval_ms: u64,
+    /// Writes that may be queued before `enqueue` blocks.
+    pub capacity: usize,
 }
 
 impl Default for BufferConfig {
@@ -65,149 +201,477 @@ impl Default for BufferConfig {
         Self {
             batch_size: 100,
             flush_interval_ms: 50,
+            capacity: 1_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct BufferStats {
+    pub pending: usize,
+    /// Most writes that were queued at once.
+    pub high_water_mark: usize,
+    pub capacity: usize,
+    pub batch_size: usize,
+    pub flush_interval_ms: u64,
+    /// Writes dropped because their batch failed to commit.
+    pub failed_writes: usize,
+    /// Writes skipped because a later write in the same batch had the same key.
+    pub deduplicated: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    pending: AtomicUsize,
+    high_water_mark: AtomicUsize,
+    written: AtomicUsize,
+    failed: AtomicUsize,
+    deduplicated: AtomicUsize,
+    transactions: AtomicU64,
+    flush_time_us: AtomicU64,
+    last_batch_size: AtomicUsize,
+    last_error: Mutex<Option<String>>,
+    config: BufferConfig,
+}
+
+impl Counters {
+    fn stats(&self) -> BufferStats {
+        BufferStats {
+            pending: self.pending.load(Ordering::Relaxed),
+            high_water_mark: self.high_water_mark.load(Ordering::Relaxed),
+            capacity: self.config.capacity,
+            batch_size: self.config.batch_size,
+            flush_interval_ms: self.config.flush_interval_ms,
+            failed_writes: self.failed.load(Ordering::Relaxed),
+            deduplicated: self.deduplicated.load(Ordering::Relaxed),
+            last_error: self.last_error(),
+        }
+    }
+
+    fn metrics(&self) -> WriteBufferMetrics {
+        WriteBufferMetrics {
+            flushed_entries: self.written.load(Ordering::Relaxed) as u64,
+            flush_transactions: self.transactions.load(Ordering::Relaxed),
+            flush_time_ms: self.flush_time_us.load(Ordering::Relaxed) / 1000,
+            last_batch_size: self.last_batch_size.load(Ordering::Relaxed) as u64,
+        }
+    }
+
+    fn last_error(&self) -> Option<String> {
+        self.last_error
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    fn record_failure(&self, drained: usize, err: &anyhow::Error) {
+        self.failed.fetch_add(drained, Ordering::Relaxed);
+        *self.last_error.lock().unwrap_or_else(|e| e.into_inner()) = Some(format!("{err:#}"));
+    }
+
+    /// Error for the writes that failed once `failed_before` had been seen.
+    fn failures_since(&self, failed_before: usize) -> Result<()> {
+        let failed = self.failed.load(Ordering::Relaxed) - failed_before;
+        if failed == 0 {
+            return Ok(());
         }
+        bail!(
+            "{failed} buffered write(s) failed to commit: {}",
+            self.last_error().unwrap_or_default()
+        );
     }
 }
 
 pub struct WriteBuffer {
-    tx: Option<Sender<PendingWrite>>,
-    pending: Arc<AtomicUsize>,
+    tx: Option<SyncSender<Message>>,
+    counters: Arc<Counters>,
     handle: Option<JoinHandle<()>>,
     gauge_path: Option<PathBuf>,
 }
 
 impl WriteBuffer {
     pub fn new(db: Arc<Env>, config: BufferConfig, gauge_path: PathBuf) -> Self {
-        let (tx, rx) = std::sync::mpsc::channel::<PendingWrite>();
-        let pending = Arc::new(AtomicUsize::new(0));
-        let pending_for_thread = Arc::clone(&pending);
-        let handle = spawn_flusher(rx, db, config, pending_for_thread, Some(gauge_path.clone()));
+        let config = BufferConfig {
+            capacity: config.capacity.max(1),
+            ..config
+        };
+        let (tx, rx) = std::sync::mpsc::sync_channel::<Message>(config.capacity);
+        let counters = Arc::new(Counters {
+            config,
+            ..Counters::default()
+        });
+        let handle = spawn_flusher(
+            rx,
+            db,
+            config,
+            Arc::clone(&counters),
+            Some(gauge_path.clone()),
+        );
 
         Self {
             tx: Some(tx),
-            pending,
+            counters,
             handle: Some(handle),
             gauge_path: Some(gauge_path),
         }
     }
 
+    /// Queues `entry`, blocking while the buffer is at capacity. Fails if the
+    /// flusher thread has stopped.
     pub fn enqueue(&self, entry: PendingWrite) -> Result<()> {
-        if let Some(tx) = self.tx.as_ref() {
-            let prev = self.pending.fetch_add(1, Ordering::Relaxed);
-            if prev == 0
-                && let Some(path) = self.gauge_path.as_deref()
-            {
-                let _ = write_gauge(path, 1);
-            }
-            if tx.send(entry).is_ok() {
-                return Ok(());
-            }
-            self.pending.fetch_sub(1, Ordering::Relaxed);
-        }
-        Ok(())
+        self.mutate(Mutation::Put(entry))
+    }
+
+    /// Queues any [`Mutation`], with the same blocking as `enqueue`. Deletes
+    /// go through the same batches as puts instead of opening their own write
+    /// transactions.
+    pub fn mutate(&self, mutation: Mutation) -> Result<()> {
+        self.send(Message::Write(mutation))
+    }
+
+    /// Queues `mutations` to be committed in a single transaction, so either
+    /// all of them land or none do. The group may share its transaction with
+    /// other writes but is never split across two.
+    pub fn mutate_group(&self, mutations: Vec<Mutation>) -> Result<()> {
+        self.send(Message::Group(mutations))
+    }
+
+    fn send(&self, message: Message) -> Result<()> {
+        let Some(tx) = self.tx.as_ref() else {
+            bail!("write buffer is shut down");
+        };
+        send(tx, &self.counters, self.gauge_path.as_deref(), message)
+    }
+
+    /// Waits until everything enqueued before the call is committed, leaving
+    /// the buffer running. Gives long-running modes a durability point
+    /// without `shutdown_and_flush`.
+    pub fn flush(&self) -> Result<FlushReport> {
+        let Some(tx) = self.tx.as_ref() else {
+            bail!("write buffer is shut down");
+        };
+        flush_through(tx, &self.counters)
     }
 
     pub fn handle(&self) -> Option<WriteBufferHandle> {
         self.tx.as_ref().map(|tx| WriteBufferHandle {
             tx: tx.clone(),
-            pending: Arc::clone(&self.pending),
+            counters: Arc::clone(&self.counters),
             gauge_path: self.gauge_path.clone(),
         })
     }
 
     pub fn pending_count(&self) -> usize {
-        self.pending.load(Ordering::Relaxed)
+        self.counters.pending.load(Ordering::Relaxed)
+    }
+
+    /// Writes dropped so far because their batch failed to commit.
+    pub fn failed_count(&self) -> usize {
+        self.counters.failed.load(Ordering::Relaxed)
+    }
+
+    pub fn last_error(&self) -> Option<String> {
+        self.counters.last_error()
+    }
+
+    pub fn metrics(&self) -> WriteBufferMetrics {
+        self.counters.metrics()
+    }
+
+    pub fn stats(&self) -> BufferStats {
+        self.counters.stats()
     }
 
+    /// Stops the buffer once every queued write is handled. Fails if any
+    /// batch over the buffer's lifetime failed to commit. Calling it again,
+    /// or dropping the buffer afterwards, does nothing.
     pub fn shutdown_and_flush(&mut self) -> Result<()> {
         self.tx.take();
-        if let Some(handle) = self.handle.take() {
-            let _ = handle.join();
-        }
+        let Some(handle) = self.handle.take() else {
+            return Ok(());
+        };
+        let panicked = handle.join().is_err();
         if let Some(path) = self.gauge_path.as_deref() {
             let _ = std::fs::remove_file(path);
         }
-        Ok(())
+        if panicked {
+            bail!("write buffer flusher panicked");
+        }
+        self.counters.failures_since(0)
+    }
+}
+
+/// Writes still queued when a buffer goes out of scope without an explicit
+/// `shutdown_and_flush` (e.g. after an early `?` return) are committed here.
+impl Drop for WriteBuffer {
+    fn drop(&mut self) {
+        if let Err(err) = self.shutdown_and_flush() {
+            tracing::warn!("write buffer flush on drop failed: {err:#}");
+        }
+    }
+}
+
+fn send(
+    tx: &SyncSender<Message>,
+    counters: &Counters,
+    gauge_path: Option<&Path>,
+    message: Message,
+) -> Result<()> {
+    let count = match &message {
+        Message::Write(_) => 1,
+        Message::Group(mutations) => mutations.len(),
+        Message::Flush(_) => 0,
+    };
+    if count == 0 {
+        return Ok(());
     }
+    let prev = counters.pending.fetch_add(count, Ordering::Relaxed);
+    counters
+        .high_water_mark
+        .fetch_max(prev + count, Ordering::Relaxed);
+    if prev == 0
+        && let Some(path) = gauge_path
+    {
+        let _ = write_gauge(path, count, None);
+    }
+
+    if tx.send(message).is_ok() {
+        return Ok(());
+    }
+
+    counters.pending.fetch_sub(count, Ordering::Relaxed);
+    bail!("write buffer flusher has stopped");
+}
+
+fn flush_through(tx: &SyncSender<Message>, counters: &Counters) -> Result<FlushReport> {
+    let start = Instant::now();
+    let written_before = counters.written.load(Ordering::Relaxed);
+    let failed_before = counters.failed.load(Ordering::Relaxed);
+    let (ack_tx, ack_rx) = std::sync::mpsc::sync_channel(1);
+    tx.send(Message::Flush(ack_tx))
+        .map_err(|_| anyhow!("write buffer flusher has stopped"))?;
+    ack_rx
+        .recv()
+        .map_err(|_| anyhow!("write buffer flusher stopped before the flush completed"))?;
+    counters.failures_since(failed_before)?;
+    Ok(FlushReport {
+        written: counters.written.load(Ordering::Relaxed) - written_before,
+        pending: counters.pending.load(Ordering::Relaxed),
+        duration: start.elapsed(),
+    })
 }
 
 fn spawn_flusher(
-    rx: Receiver<PendingWrite>,
+    rx: Receiver<Message>,
     db: Arc<Env>,
     config: BufferConfig,
-    pending: Arc<AtomicUsize>,
+    counters: Arc<Counters>,
     gauge_path: Option<PathBuf>,
 ) -> JoinHandle<()> {
     std::thread::spawn(move || {
-        let mut batch = Vec::with_capacity(config.batch_size.max(1));
-
-        loop {
-            while let Ok(entry) = rx.try_recv() {
-                batch.push(entry);
-                if batch.len() >= config.batch_size.max(1) {
-                    break;
-                }
+        let batch_size = config.batch_size.max(1);
+        let mut batch = Vec::with_capacity(batch_size.min(config.capacity));
+        let write_out = |batch: &mut Vec<Mutation>| {
+            if batch.is_empty() {
+                return;
             }
-
-            if !batch.is_empty() {
-                let drained = batch.len();
-                let _ = batch_write(&db, &batch);
-                pending.fetch_sub(drained, Ordering::Relaxed);
-                if let Some(path) = gauge_path.as_deref() {
-                    let _ = write_gauge(path, pending.load(Ordering::Relaxed));
+            let drained = batch.len();
+            let superseded = coalesce_by_key(batch);
+            counters
+                .deduplicated
+                .fetch_add(superseded, Ordering::Relaxed);
+            let started = Instant::now();
+            match batch_write(&db, batch) {
+                Ok(()) => {
+                    counters.written.fetch_add(batch.len(), Ordering::Relaxed);
+                    counters.transactions.fetch_add(1, Ordering::Relaxed);
                 }
-                batch.clear();
+                Err(err) => counters.record_failure(batch.len(), &err),
+            }
+            counters
+                .flush_time_us
+                .fetch_add(started.elapsed().as_micros() as u64, Ordering::Relaxed);
+            counters
+                .last_batch_size
+                .store(batch.len(), Ordering::Relaxed);
+            let pending = counters.pending.fetch_sub(drained, Ordering::Relaxed) - drained;
+            if let Some(path) = gauge_path.as_deref() {
+                let _ = write_gauge(path, pending, Some(&counters.metrics()));
             }
+            batch.clear();
+        };
 
-            match rx.recv_timeout(Duration::from_millis(config.flush_interval_ms)) {
-                Ok(entry) => batch.push(entry),
-                Err(RecvTimeoutError::Timeout) => continue,
-                Err(RecvTimeoutError::Disconnected) => {
-                    while let Ok(entry) = rx.try_recv() {
-                        batch.push(entry);
-                        if batch.len() >= config.batch_size.max(1) {
-                            let drained = batch.len();
-                            let _ = batch_write(&db, &batch);
-                            pending.fetch_sub(drained, Ordering::Relaxed);
-                            batch.clear();
-                        }
-                    }
-                    if !batch.is_empty() {
-                        let drained = batch.len();
-                        let _ = batch_write(&db, &batch);
-                        pending.fetch_sub(drained, Ordering::Relaxed);
-                        batch.clear();
+        // A batch is committed once it is full, when a flush barrier arrives,
+        // or `flush_interval_ms` after its first write was received.
+        let interval = Duration::from_millis(config.flush_interval_ms);
+        let mut deadline: Option<Instant> = None;
+        loop {
+            let received = match deadline {
+                Some(deadline) => {
+                    rx.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                }
+                None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            match received {
+                Ok(message @ (Message::Write(_) | Message::Group(_))) => {
+                    deadline.get_or_insert_with(|| Instant::now() + interval);
+                    match message {
+                        Message::Group(mutations) => batch.extend(mutations),
+                        Message::Write(mutation) => batch.push(mutation),
+                        Message::Flush(_) => unreachable!(),
                     }
-                    if let Some(path) = gauge_path.as_deref() {
-                        let _ = write_gauge(path, 0);
-                        let _ = std::fs::remove_file(path);
+                    if batch.len() >= batch_size {
+                        write_out(&mut batch);
+                        deadline = None;
                     }
-                    break;
                 }
+                Ok(Message::Flush(ack)) => {
+                    write_out(&mut batch);
+                    deadline = None;
+                    let _ = ack.send(());
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    write_out(&mut batch);
+                    deadline = None;
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
             }
         }
+        write_out(&mut batch);
+
+        let metrics = counters.metrics();
+        if metrics.flush_transactions > 0 {
+            let _ = save_metrics(&db, &metrics);
+        }
+        if let Some(path) = gauge_path.as_deref() {
+            let _ = write_gauge(path, 0, None);
+            let _ = std::fs::remove_file(path);
+        }
     })
 }
 
-fn batch_write(env: &Env, batch: &[PendingWrite]) -> Result<()> {
+/// Drops every put or delete that a later one for the same key in `batch`
+/// overrides (last write wins), keeping the order of the rest. Prefix deletes
+/// are always kept. Returns how many were dropped.
+fn coalesce_by_key(batch: &mut Vec<Mutation>) -> usize {
+    let before = batch.len();
+    let mut seen = HashSet::with_capacity(before);
+    let mut kept: Vec<Mutation> = batch
+        .drain(..)
+        .rev()
+        .filter(|mutation| {
+            mutation
+                .key()
+                .is_none_or(|(table, key)| seen.insert((table, key.to_string())))
+        })
+        .collect();
+    kept.reverse();
+    *batch = kept;
+    before - batch.len()
+}
+
+fn batch_write(env: &Env, batch: &[Mutation]) -> Result<()> {
     if batch.is_empty() {
         return Ok(());
     }
     let mut wtxn = env.write_txn()?;
-    let table: StrDb = env.create_database::<Str, Str>(&mut wtxn, Some(CLASS_SOURCES_DB))?;
-    for entry in batch {
-        let payload = serde_json::to_string(&CachedClassSource {
-            content: entry.content.clone(),
-            source: entry.source.clone(),
-        })?;
-        table.put(&mut wtxn, entry.key.as_str(), payload.as_str())?;
+    let mut tables: HashMap<Table, StrDb> = HashMap::new();
+    for mutation in batch {
+        let target = match mutation {
+            Mutation::Put(_) => Table::ClassSources,
+            Mutation::AddArtifact { .. } => Table::ClassRegistry,
+            Mutation::RecordClassAccess { .. } => Table::ClassHotspot,
+            Mutation::RecordJarAccess { .. } => Table::JarHotspot,
+            Mutation::PutValue { table, .. }
+            | Mutation::Delete { table, .. }
+            | Mutation::DeletePrefix { table, .. } => *table,
+        };
+        let table = table_handle(env, &mut wtxn, &mut tables, target)?;
+        match mutation {
+            Mutation::Put(entry) => {
+                let payload = serde_json::to_string(&CachedClassSource {
+                    content: entry.content.clone(),
+                    source: entry.source.clone(),
+                    options: entry.options.clone(),
+                    decompiler_version: entry.decompiler_version.clone(),
+                    encoding: entry.encoding.clone(),
+                    postprocessed: entry.postprocessed,
+                })?;
+                table.put(&mut wtxn, entry.key.as_str(), payload.as_str())?;
+            }
+            Mutation::PutValue { key, value, .. } => {
+                table.put(&mut wtxn, key.as_str(), value.as_str())?;
+            }
+            Mutation::Delete { key, .. } => {
+                table.delete(&mut wtxn, key.as_str())?;
+            }
+            Mutation::DeletePrefix { prefix, .. } => {
+                let mut entries = table.prefix_iter_mut(&mut wtxn, prefix.as_str())?;
+                while let Some(entry) = entries.next() {
+                    entry?;
+                    // SAFETY: No key or value borrowed from this entry is held
+                    // across the deletion.
+                    unsafe { entries.del_current()? };
+                }
+            }
+            Mutation::AddArtifact {
+                class_name,
+                jar_key,
+            } => {
+                add_artifact(&table, &mut wtxn, class_name, jar_key)?;
+            }
+            Mutation::RecordClassAccess {
+                class_name,
+                jar_key,
+                at,
+            } => {
+                let jars = table_handle(env, &mut wtxn, &mut tables, Table::JarHotspot)?;
+                record_class_access(&table, &jars, &mut wtxn, class_name, jar_key, *at)?;
+            }
+            Mutation::RecordJarAccess { jar_key, count, at } => {
+                record_jar_access(&table, &mut wtxn, jar_key, *count, *at)?;
+            }
+        }
     }
     wtxn.commit()?;
     Ok(())
 }
 
-fn write_gauge(path: &Path, value: usize) -> Result<()> {
-    std::fs::write(path, format!("{value}\n"))?;
+fn table_handle(
+    env: &Env,
+    wtxn: &mut heed::RwTxn<'_>,
+    tables: &mut HashMap<Table, StrDb>,
+    target: Table,
+) -> Result<StrDb> {
+    if let Some(table) = tables.get(&target) {
+        return Ok(*table);
+    }
+    let table = env.create_database::<Str, Str>(wtxn, Some(target.name()))?;
+    Ok(*tables.entry(target).or_insert(table))
+}
+
+/// Persists the metrics of a finished run for `class-finder stats`.
+fn save_metrics(env: &Env, metrics: &WriteBufferMetrics) -> Result<()> {
+    let mut wtxn = env.write_txn()?;
+    let table: StrDb = env.create_database::<Str, Str>(&mut wtxn, Some(META_DB))?;
+    table.put(
+        &mut wtxn,
+        WRITE_BUFFER_METRICS_KEY,
+        &serde_json::to_string(metrics)?,
+    )?;
+    wtxn.commit()?;
+    Ok(())
+}
+
+fn write_gauge(path: &Path, value: usize, metrics: Option<&WriteBufferMetrics>) -> Result<()> {
+    let mut content = format!("{value}\n");
+    if let Some(metrics) = metrics {
+        content.push_str(&serde_json::to_string(metrics)?);
+        content.push('\n');
+    }
+    std::fs::write(path, content)?;
     Ok(())
 }
 
@@ -240,6 +704,7 @@ mod tests {
             BufferConfig {
                 batch_size: 2,
                 flush_interval_ms: 10_000,
+                ..BufferConfig::default()
             },
             gauge.clone(),
         );
@@ -249,6 +714,10 @@ mod tests {
             key: "a.A::jar1".to_string(),
             content: "class A {}".to_string(),
             source: ClassContentSource::Decompiled,
+            options: Vec::new(),
+            decompiler_version: None,
+            encoding: None,
+            postprocessed: false,
         })?;
 
         buffer.shutdown_and_flush()?;
@@ -261,4 +730,403 @@ mod tests {
         let _ = std::fs::remove_file(db_path);
         Ok(())
     }
+
+    fn pending_write(key: &str) -> PendingWrite {
+        PendingWrite {
+            key: key.to_string(),
+            content: format!("// {key}"),
+            source: ClassContentSource::Decompiled,
+            options: Vec::new(),
+            decompiler_version: None,
+            encoding: None,
+            postprocessed: false,
+        }
+    }
+
+    #[test]
+    fn enqueue_blocks_at_capacity_until_the_flusher_catches_up() -> Result<()> {
+        let db_path = temp_db_path("buffer_backpressure");
+        let cache = PersistentCache::open(db_path.clone())?;
+        let mut buffer = WriteBuffer::new(
+            cache.db(),
+            BufferConfig {
+                batch_size: 1,
+                flush_interval_ms: 5,
+                capacity: 2,
+            },
+            cache.pending_gauge_path(),
+        );
+        let handle = buffer.handle().unwrap();
+
+        // Holding the write transaction stalls the flusher.
+        let env = cache.db();
+        let wtxn = env.write_txn()?;
+        let producer = std::thread::spawn(move || {
+            for i in 0..10 {
+                handle.enqueue(pending_write(&format!("a.C{i}::jar")))?;
+            }
+            anyhow::Ok(())
+        });
+        std::thread::sleep(Duration::from_millis(200));
+        assert!(!producer.is_finished());
+        // Two queued, up to two in the flusher's batch, one blocked in send.
+        assert!(buffer.stats().pending <= 5, "{:?}", buffer.stats());
+        drop(wtxn);
+
+        producer.join().unwrap()?;
+        buffer.shutdown_and_flush()?;
+        let stats = buffer.stats();
+        assert_eq!(stats.capacity, 2);
+        assert!(stats.high_water_mark <= 5, "{stats:?}");
+        for i in 0..10 {
+            assert!(cache.get_class_source(&format!("a.C{i}::jar"))?.is_some());
+        }
+
+        drop(cache);
+        let _ = std::fs::remove_file(db_path);
+        Ok(())
+    }
+
+    #[test]
+    fn flush_commits_queued_writes_and_keeps_the_buffer_running() -> Result<()> {
+        let db_path = temp_db_path("buffer_barrier");
+        let cache = PersistentCache::open(db_path.clone())?;
+        let mut buffer = WriteBuffer::new(
+            cache.db(),
+            BufferConfig {
+                batch_size: 100,
+                flush_interval_ms: 10_000,
+                ..BufferConfig::default()
+            },
+            cache.pending_gauge_path(),
+        );
+
+        for i in 0..3 {
+            buffer.enqueue(pending_write(&format!("a.C{i}::jar")))?;
+        }
+        let report = buffer.flush()?;
+        assert!(report.written <= 3);
+        assert_eq!(report.pending, 0);
+        for i in 0..3 {
+            assert!(cache.get_class_source(&format!("a.C{i}::jar"))?.is_some());
+        }
+
+        let handle = buffer.handle().unwrap();
+        handle.enqueue(pending_write("a.Late::jar"))?;
+        handle.flush()?;
+        assert!(cache.get_class_source("a.Late::jar")?.is_some());
+        drop(handle);
+
+        buffer.shutdown_and_flush()?;
+        assert!(buffer.flush().is_err());
+
+        drop(cache);
+        let _ = std::fs::remove_file(db_path);
+        Ok(())
+    }
+
+    #[test]
+    fn failed_batches_are_reported_by_flush_and_shutdown() -> Result<()> {
+        let db_path = temp_db_path("buffer_map_full");
+        let mut options = heed::EnvOpenOptions::new();
+        options.map_size(64 * 1024).max_dbs(4);
+        // SAFETY: The env is private to this test and opened with default locking.
+        let env = unsafe { options.flags(heed::EnvFlags::NO_SUB_DIR).open(&db_path)? };
+        let mut buffer = WriteBuffer::new(
+            Arc::new(env),
+            BufferConfig::default(),
+            temp_db_path("buffer_map_full_gauge"),
+        );
+
+        buffer.enqueue(PendingWrite {
+            content: "x".repeat(1024 * 1024),
+            ..pending_write("a.Huge::jar")
+        })?;
+        let err = buffer.flush().unwrap_err().to_string();
+        assert!(
+            err.contains("1 buffered write(s) failed to commit"),
+            "{err}"
+        );
+        assert_eq!(buffer.failed_count(), 1);
+        assert!(buffer.stats().last_error.is_some());
+
+        assert!(buffer.shutdown_and_flush().is_err());
+        let _ = std::fs::remove_file(&db_path);
+        let _ = std::fs::remove_file(db_path.with_extension("lmdb-lock"));
+        Ok(())
+    }
+
+    #[test]
+    fn duplicate_keys_in_a_batch_keep_the_last_write() -> Result<()> {
+        let db_path = temp_db_path("buffer_dedupe");
+        let cache = PersistentCache::open(db_path.clone())?;
+        let mut buffer = WriteBuffer::new(
+            cache.db(),
+            BufferConfig::default(),
+            cache.pending_gauge_path(),
+        );
+
+        // Hold the write transaction so all three land in one batch.
+        let env = cache.db();
+        let wtxn = env.write_txn()?;
+        for content in ["first", "second", "third"] {
+            buffer.enqueue(PendingWrite {
+                content: content.to_string(),
+                ..pending_write("a.A::jar")
+            })?;
+        }
+        buffer.enqueue(pending_write("a.B::jar"))?;
+        drop(wtxn);
+        buffer.shutdown_and_flush()?;
+
+        assert_eq!(
+            cache.get_class_source("a.A::jar")?.unwrap().content,
+            "third"
+        );
+        assert!(cache.get_class_source("a.B::jar")?.is_some());
+        assert_eq!(buffer.pending_count(), 0);
+        assert!((1..=2).contains(&buffer.stats().deduplicated));
+
+        let mut batch = vec![
+            Mutation::Put(pending_write("k1")),
+            Mutation::Put(PendingWrite {
+                content: "new".to_string(),
+                ..pending_write("k2")
+            }),
+            Mutation::Put(pending_write("k3")),
+            Mutation::Put(PendingWrite {
+                content: "newest".to_string(),
+                ..pending_write("k2")
+            }),
+        ];
+        assert_eq!(coalesce_by_key(&mut batch), 1);
+        let keys: Vec<(&str, &str)> = batch
+            .iter()
+            .map(|m| match m {
+                Mutation::Put(e) => (e.key.as_str(), e.content.as_str()),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(keys, [("k1", "// k1"), ("k3", "// k3"), ("k2", "newest")]);
+
+        drop(cache);
+        let _ = std::fs::remove_file(db_path);
+        Ok(())
+    }
+
+    #[test]
+    fn dropping_the_buffer_commits_queued_writes() -> Result<()> {
+        let db_path = temp_db_path("buffer_drop");
+        let cache = PersistentCache::open(db_path.clone())?;
+        let gauge = cache.pending_gauge_path();
+        {
+            let buffer = WriteBuffer::new(
+                cache.db(),
+                BufferConfig {
+                    batch_size: 100,
+                    flush_interval_ms: 10_000,
+                    ..BufferConfig::default()
+                },
+                gauge.clone(),
+            );
+            for i in 0..5 {
+                buffer.enqueue(pending_write(&format!("a.C{i}::jar")))?;
+            }
+        }
+        for i in 0..5 {
+            assert!(cache.get_class_source(&format!("a.C{i}::jar"))?.is_some());
+        }
+        assert!(!gauge.exists());
+
+        let mut buffer = WriteBuffer::new(cache.db(), BufferConfig::default(), gauge);
+        buffer.enqueue(pending_write("a.Twice::jar"))?;
+        buffer.shutdown_and_flush()?;
+        buffer.shutdown_and_flush()?;
+        drop(buffer);
+        assert!(cache.get_class_source("a.Twice::jar")?.is_some());
+
+        drop(cache);
+        let _ = std::fs::remove_file(db_path);
+        Ok(())
+    }
+
+    #[test]
+    fn flush_metrics_are_live_while_running_and_persisted_on_shutdown() -> Result<()> {
+        let db_path = temp_db_path("buffer_metrics");
+        let cache = PersistentCache::open(db_path.clone())?;
+        let mut buffer = WriteBuffer::new(
+            cache.db(),
+            BufferConfig {
+                batch_size: 2,
+                ..BufferConfig::default()
+            },
+            cache.pending_gauge_path(),
+        );
+        assert_eq!(cache.stats()?.write_buffer_metrics, None);
+
+        for i in 0..3 {
+            buffer.enqueue(pending_write(&format!("a.C{i}::jar")))?;
+        }
+        buffer.flush()?;
+        let live = cache.stats()?;
+        assert!(live.write_buffer_metrics_live);
+        assert_eq!(live.write_buffer_metrics, Some(buffer.metrics()));
+        assert_eq!(buffer.metrics().flushed_entries, 3);
+
+        buffer.shutdown_and_flush()?;
+        let metrics = buffer.metrics();
+        assert!(metrics.flush_transactions >= 2);
+        assert!((1..=2).contains(&metrics.last_batch_size));
+        let last_run = cache.stats()?;
+        assert!(!last_run.write_buffer_metrics_live);
+        assert_eq!(last_run.write_buffer_metrics, Some(metrics));
+
+        drop(cache);
+        let _ = std::fs::remove_file(db_path);
+        Ok(())
+    }
+
+    #[test]
+    fn writes_accumulate_into_one_batch_until_it_is_full() -> Result<()> {
+        let db_path = temp_db_path("buffer_accumulate");
+        let cache = PersistentCache::open(db_path.clone())?;
+        let buffer = WriteBuffer::new(
+            cache.db(),
+            BufferConfig {
+                batch_size: 3,
+                flush_interval_ms: 10_000,
+                ..BufferConfig::default()
+            },
+            cache.pending_gauge_path(),
+        );
+        for key in ["a.A::jar", "a.B::jar", "a.C::jar"] {
+            buffer.enqueue(pending_write(key))?;
+        }
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while buffer.pending_count() > 0 && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+
+        assert_eq!(buffer.pending_count(), 0);
+        assert_eq!(buffer.metrics().flush_transactions, 1);
+        assert_eq!(buffer.metrics().last_batch_size, 3);
+        assert_eq!(buffer.stats().batch_size, 3);
+
+        drop(buffer);
+        drop(cache);
+        let _ = std::fs::remove_file(db_path);
+        Ok(())
+    }
+
+    #[test]
+    fn a_group_commits_in_one_transaction_or_not_at_all() -> Result<()> {
+        let db_path = temp_db_path("buffer_group");
+        let cache = PersistentCache::open(db_path.clone())?;
+        let buffer = WriteBuffer::new(
+            cache.db(),
+            BufferConfig {
+                batch_size: 1,
+                ..BufferConfig::default()
+            },
+            cache.pending_gauge_path(),
+        );
+        let marker = |key: &str| Mutation::PutValue {
+            table: Table::Jars,
+            key: key.to_string(),
+            value: "1".to_string(),
+        };
+        buffer.mutate_group(vec![
+            Mutation::Put(pending_write("a.A::one")),
+            Mutation::Put(pending_write("a.B::one")),
+            marker("one"),
+        ])?;
+        buffer.flush()?;
+        assert_eq!(buffer.metrics().flush_transactions, 1);
+        assert_eq!(buffer.metrics().last_batch_size, 3);
+        assert!(cache.get_class_source("a.B::one")?.is_some());
+        assert!(cache.is_jar_loaded("one")?);
+
+        // LMDB rejects keys over 511 bytes, failing the whole transaction.
+        let too_long = "x".repeat(600);
+        buffer.mutate_group(vec![
+            Mutation::Put(pending_write("a.A::two")),
+            marker(&too_long),
+        ])?;
+        assert!(buffer.flush().is_err());
+        assert!(cache.get_class_source("a.A::two")?.is_none());
+        assert_eq!(buffer.failed_count(), 2);
+
+        drop(buffer);
+        drop(cache);
+        let _ = std::fs::remove_file(db_path);
+        Ok(())
+    }
+
+    #[test]
+    fn deletes_apply_in_order_with_puts() -> Result<()> {
+        let db_path = temp_db_path("buffer_delete");
+        let cache = PersistentCache::open(db_path.clone())?;
+        let buffer = WriteBuffer::new(
+            cache.db(),
+            BufferConfig::default(),
+            cache.pending_gauge_path(),
+        );
+        for key in ["a.A::one", "a.B::one", "a.C::two", "b.D::one"] {
+            buffer.enqueue(pending_write(key))?;
+        }
+        buffer.mutate(Mutation::Delete {
+            table: Table::ClassSources,
+            key: "b.D::one".to_string(),
+        })?;
+        buffer.mutate(Mutation::DeletePrefix {
+            table: Table::ClassSources,
+            prefix: "a.".to_string(),
+        })?;
+        buffer.enqueue(pending_write("a.B::one"))?;
+        buffer.flush()?;
+
+        assert!(cache.get_class_source("a.A::one")?.is_none());
+        assert!(cache.get_class_source("a.C::two")?.is_none());
+        assert!(cache.get_class_source("b.D::one")?.is_none());
+        assert!(cache.get_class_source("a.B::one")?.is_some());
+        assert_eq!(buffer.pending_count(), 0);
+
+        let mut batch = vec![
+            Mutation::Put(pending_write("k1")),
+            Mutation::DeletePrefix {
+                table: Table::ClassSources,
+                prefix: "k".to_string(),
+            },
+            Mutation::Delete {
+                table: Table::ClassSources,
+                key: "k1".to_string(),
+            },
+        ];
+        assert_eq!(coalesce_by_key(&mut batch), 1);
+        assert!(matches!(batch[0], Mutation::DeletePrefix { .. }));
+        assert!(matches!(batch[1], Mutation::Delete { .. }));
+
+        drop(buffer);
+        drop(cache);
+        let _ = std::fs::remove_file(db_path);
+        Ok(())
+    }
+
+    #[test]
+    fn enqueue_fails_once_the_flusher_has_stopped() {
+        let (tx, rx) = std::sync::mpsc::sync_channel(1);
+        let counters = Counters::default();
+        drop(rx);
+
+        assert!(
+            send(
+                &tx,
+                &counters,
+                None,
+                Message::Write(Mutation::Put(pending_write("a.A::jar")))
+            )
+            .is_err()
+        );
+        assert_eq!(counters.pending.load(Ordering::Relaxed), 0);
+    }
 }
@@ -5,28 +5,85 @@
 //! artifact manifests, hotspot tracking, and modification times.
 
 use anyhow::{Context, Result};
+use clap::ValueEnum;
 use heed::types::Str;
-use heed::{Database, Env, EnvFlags, EnvOpenOptions, RoTxn};
+use heed::{CompactionOption, Database, Env, EnvFlags, EnvOpenOptions, RoTxn};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+use crate::buffer::{BufferConfig, WriteBufferHandle};
+use crate::decompiler::DecompilerKind;
+use crate::error::ClassFinderError;
+use crate::hotspot::{DEFAULT_WARMUP_THRESHOLD, GroupAccess, ranking_score, unix_now};
+use crate::parse::hash_content;
+use crate::session::ReadSession;
+use crate::structure::{CompilationUnit, parse_compilation_unit};
+use crate::warmup::WarmupMode;
+
 pub const CLASS_SOURCES_DB: &str = "class_sources";
 pub const JARS_DB: &str = "jars";
 pub const CLASS_REGISTRY_DB: &str = "class_registry";
 pub const ARTIFACT_MANIFEST_DB: &str = "artifact_manifest";
 pub const JAR_HOTSPOT_DB: &str = "jar_hotspot";
+pub const CLASS_HOTSPOT_DB: &str = "class_hotspot";
 pub const JAR_MTIME_DB: &str = "jar_mtime";
+pub const META_DB: &str = "meta";
+pub const WARMUP_QUEUE_DB: &str = "warmup_queue";
+pub const CLASS_STRUCTURE_DB: &str = "class_structure";
+pub const SCAN_CACHE_DB: &str = "scan_cache";
+
+/// Prefix of `CLASS_STRUCTURE_DB` keys. Bumped whenever [`CompilationUnit`]
+/// changes shape so rows parsed by an older build are never read back.
+const STRUCTURE_SCHEMA: &str = "s4";
+
+/// `META_DB` row holding the [`WriteBufferMetrics`] of the last write buffer
+/// that shut down.
+pub const WRITE_BUFFER_METRICS_KEY: &str = "write_buffer_metrics";
+
+/// `META_DB` row holding the cumulative [`BackgroundWarmupStats`].
+pub const BACKGROUND_WARMUP_KEY: &str = "background_warmup";
+
+/// `META_DB` row holding the [`SnapshotRecord`] of the last publication.
+pub const SNAPSHOT_KEY: &str = "snapshot";
 
 const DEFAULT_MAP_SIZE: usize = 1024 * 1024 * 1024;
 const DEFAULT_MAX_DBS: u32 = 32;
 
 type StrDb = Database<Str, Str>;
 
+/// Key for a class source in `CLASS_SOURCES_DB`. CFR keeps the original
+/// `class::jar` layout; other backends get a suffix so their output never
+/// shadows another backend's.
+pub fn class_source_key(class_name: &str, jar_key: &str, decompiler: DecompilerKind) -> String {
+    match decompiler {
+        DecompilerKind::Cfr => format!("{class_name}::{jar_key}"),
+        other => format!("{class_name}::{jar_key}::{}", other.as_str()),
+    }
+}
+
+/// Key for a jar's load status in `JARS_DB`, scoped by backend like
+/// [`class_source_key`].
+pub fn loaded_jar_key(jar_key: &str, decompiler: DecompilerKind) -> String {
+    match decompiler {
+        DecompilerKind::Cfr => jar_key.to_string(),
+        other => format!("{jar_key}::{}", other.as_str()),
+    }
+}
+
+/// SHA-256 of a class source, used to tell an unchanged entry from one that
+/// needs rewriting.
+pub fn content_hash(content: &str) -> [u8; 32] {
+    Sha256::digest(content.as_bytes()).into()
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "kebab-case")]
 pub enum ClassContentSource {
     SourcesJar,
     Decompiled,
+    Javap,
 }
 
 impl ClassContentSource {
@@ -34,6 +91,7 @@ impl ClassContentSource {
         match self {
             Self::SourcesJar => "sources-jar",
             Self::Decompiled => "decompiled",
+            Self::Javap => "javap",
         }
     }
 }
@@ -42,17 +100,121 @@ impl ClassContentSource {
 pub struct CachedClassSource {
     pub content: String,
     pub source: ClassContentSource,
+    /// Decompiler options in effect when the entry was produced (`key=value`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub options: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decompiler_version: Option<String>,
+    /// Fallback encoding used because the decompiler output was not UTF-8.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+    /// Rewritten by the configured `postprocess` command.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub postprocessed: bool,
+}
+
+/// Throughput counters of one write buffer, cumulative over its lifetime.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct WriteBufferMetrics {
+    pub flushed_entries: u64,
+    pub flush_transactions: u64,
+    pub flush_time_ms: u64,
+    pub last_batch_size: u64,
+}
+
+/// Warmup tasks run in the background of `find`, cumulative over all runs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct BackgroundWarmupStats {
+    pub submitted_tasks: u64,
+    pub completed_tasks: u64,
+    pub failed_tasks: u64,
+    /// Tasks still queued or running when the time budget ran out; their
+    /// jars stay unwarmed for a later run.
+    pub unfinished_tasks: u64,
+    pub failed_classes: u64,
+}
+
+/// A read-only copy of the db published by [`PersistentCache::publish_snapshot`].
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SnapshotRecord {
+    pub path: String,
+    pub published_at: u64,
+    /// [`PersistentCache::snapshot_entries`] at publication time.
+    pub entries: u64,
+    pub duration_ms: u64,
+}
+
+/// How a `--db` path is laid out on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DbLayout {
+    /// `<db>` is the data file, with `<db>-lock` and `<db>.<name>` side files
+    /// next to it (LMDB's `NO_SUB_DIR`).
+    File,
+    /// `<db>` is a directory holding `data.mdb`, `lock.mdb` and the side
+    /// files, for a mounted volume or a directory-level backup.
+    Directory,
+}
+
+impl DbLayout {
+    /// `Directory` for an existing directory or a path ending in a separator.
+    pub fn of(db_path: &Path) -> Self {
+        let trailing_separator = db_path
+            .as_os_str()
+            .to_string_lossy()
+            .ends_with(std::path::is_separator);
+        if db_path.is_dir() || trailing_separator {
+            Self::Directory
+        } else {
+            Self::File
+        }
+    }
+
+    pub fn data_path(self, db_path: &Path) -> PathBuf {
+        match self {
+            Self::File => db_path.to_path_buf(),
+            Self::Directory => db_path.join("data.mdb"),
+        }
+    }
+
+    pub fn lock_path(self, db_path: &Path) -> PathBuf {
+        match self {
+            Self::File => {
+                let mut os = db_path.as_os_str().to_os_string();
+                os.push("-lock");
+                PathBuf::from(os)
+            }
+            Self::Directory => db_path.join("lock.mdb"),
+        }
+    }
+
+    /// A file kept with the db: `<db>.<name>`, or `<name>` inside the
+    /// directory.
+    pub fn side_path(self, db_path: &Path, name: &str) -> PathBuf {
+        match self {
+            Self::File => {
+                let mut os = db_path.as_os_str().to_os_string();
+                os.push(".");
+                os.push(name);
+                PathBuf::from(os)
+            }
+            Self::Directory => db_path.join(name),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct PersistentCache {
     env: Arc<Env>,
     db_path: PathBuf,
+    layout: DbLayout,
     class_sources: StrDb,
     jars: StrDb,
     class_registry: StrDb,
     artifact_manifest: StrDb,
     jar_hotspot: StrDb,
+    meta: StrDb,
+    warmup_queue: StrDb,
+    class_structure: StrDb,
 }
 
 #[derive(Debug)]
@@ -62,13 +224,17 @@ pub struct ReadOnlyCache {
 
 impl PersistentCache {
     pub fn open(db_path: PathBuf) -> Result<Self> {
-        if let Some(parent) = db_path.parent() {
-            std::fs::create_dir_all(parent).with_context(|| {
-                format!("Failed to create cache directory: {}", parent.display())
-            })?;
+        let layout = DbLayout::of(&db_path);
+        let dir = match layout {
+            DbLayout::File => db_path.parent(),
+            DbLayout::Directory => Some(db_path.as_path()),
+        };
+        if let Some(dir) = dir {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create cache directory: {}", dir.display()))?;
         }
 
-        let env = open_env(&db_path)?;
+        let env = open_env(&db_path, layout)?;
         let env = Arc::new(env);
 
         let mut wtxn = env.write_txn()?;
@@ -78,17 +244,27 @@ impl PersistentCache {
         let artifact_manifest =
             env.create_database::<Str, Str>(&mut wtxn, Some(ARTIFACT_MANIFEST_DB))?;
         let jar_hotspot = env.create_database::<Str, Str>(&mut wtxn, Some(JAR_HOTSPOT_DB))?;
+        let _class_hotspot = env.create_database::<Str, Str>(&mut wtxn, Some(CLASS_HOTSPOT_DB))?;
         let _jar_mtime = env.create_database::<Str, Str>(&mut wtxn, Some(JAR_MTIME_DB))?;
+        let meta = env.create_database::<Str, Str>(&mut wtxn, Some(META_DB))?;
+        let warmup_queue = env.create_database::<Str, Str>(&mut wtxn, Some(WARMUP_QUEUE_DB))?;
+        let class_structure =
+            env.create_database::<Str, Str>(&mut wtxn, Some(CLASS_STRUCTURE_DB))?;
+        let _scan_cache = env.create_database::<Str, Str>(&mut wtxn, Some(SCAN_CACHE_DB))?;
         wtxn.commit()?;
 
         Ok(Self {
             env,
             db_path,
+            layout,
             class_sources,
             jars,
             class_registry,
             artifact_manifest,
             jar_hotspot,
+            meta,
+            warmup_queue,
+            class_structure,
         })
     }
 
@@ -96,18 +272,77 @@ impl PersistentCache {
         Arc::clone(&self.env)
     }
 
+    pub fn layout(&self) -> DbLayout {
+        self.layout
+    }
+
     pub fn pending_gauge_path(&self) -> PathBuf {
-        let mut os = self.db_path.clone().into_os_string();
-        os.push(".pending");
-        PathBuf::from(os)
+        self.layout.side_path(&self.db_path, "pending")
+    }
+
+    /// Where [`Self::publish_snapshot`] puts its copy, laid out like the db
+    /// itself: `<db>.snapshot`, or a `snapshot` directory inside it.
+    pub fn snapshot_path(&self) -> PathBuf {
+        self.layout.side_path(&self.db_path, "snapshot")
+    }
+
+    pub fn java_gauge_path(&self) -> PathBuf {
+        self.layout.side_path(&self.db_path, "java")
+    }
+
+    /// The env itself, for a [`ReadSession`] borrowing it.
+    pub fn env(&self) -> &Env {
+        &self.env
     }
 
     pub fn get_class_source(&self, key: &str) -> Result<Option<CachedClassSource>> {
-        let rtxn = self.env.read_txn()?;
-        let Some(value) = self.class_sources.get(&rtxn, key)? else {
+        self.get_class_source_in(&mut ReadSession::new(&self.env), key)
+    }
+
+    pub fn get_class_source_in(
+        &self,
+        session: &mut ReadSession<'_>,
+        key: &str,
+    ) -> Result<Option<CachedClassSource>> {
+        let Some(value) = session.get(CLASS_SOURCES_DB, key)? else {
             return Ok(None);
         };
-        Ok(Some(serde_json::from_str(value)?))
+        Ok(Some(serde_json::from_str(&value)?))
+    }
+
+    /// [`content_hash`] of each of `class_names` already cached for the jar
+    /// by `decompiler`, read in one transaction.
+    pub fn cached_class_hashes(
+        &self,
+        jar_key: &str,
+        decompiler: DecompilerKind,
+        class_names: &[String],
+    ) -> Result<HashMap<String, [u8; 32]>> {
+        Ok(self
+            .cached_class_sources(jar_key, decompiler, class_names)?
+            .into_iter()
+            .map(|(class_name, cached)| (class_name, content_hash(&cached.content)))
+            .collect())
+    }
+
+    /// Each of `class_names` cached for the jar by `decompiler`, read in one
+    /// transaction. Names without an entry are left out.
+    pub fn cached_class_sources(
+        &self,
+        jar_key: &str,
+        decompiler: DecompilerKind,
+        class_names: &[String],
+    ) -> Result<HashMap<String, CachedClassSource>> {
+        let rtxn = self.env.read_txn()?;
+        let mut sources = HashMap::new();
+        for class_name in class_names {
+            let key = class_source_key(class_name, jar_key, decompiler);
+            let Some(value) = self.class_sources.get(&rtxn, &key)? else {
+                continue;
+            };
+            sources.insert(class_name.clone(), serde_json::from_str(value)?);
+        }
+        Ok(sources)
     }
 
     pub fn put_class_sources(&self, entries: &[(String, CachedClassSource)]) -> Result<usize> {
@@ -125,11 +360,94 @@ impl PersistentCache {
         Ok(entries.len())
     }
 
+    /// Structures parsed earlier from a source whose
+    /// [`hash_content`](crate::parse::hash_content) is `content_hash`. A row
+    /// that no longer deserializes counts as a miss.
+    pub fn get_compilation_unit(&self, content_hash: &str) -> Result<Option<CompilationUnit>> {
+        let rtxn = self.env.read_txn()?;
+        let key = structure_key(content_hash);
+        Ok(self
+            .class_structure
+            .get(&rtxn, &key)?
+            .and_then(|value| serde_json::from_str(value).ok()))
+    }
+
+    /// Stores `unit` under the hash of the source it was parsed from. Rows
+    /// are content-addressed, so nothing ever invalidates them.
+    pub fn put_compilation_unit(&self, content_hash: &str, unit: &CompilationUnit) -> Result<()> {
+        let payload = serde_json::to_string(unit)?;
+        let mut wtxn = self.env.write_txn()?;
+        self.class_structure
+            .put(&mut wtxn, &structure_key(content_hash), payload.as_str())?;
+        wtxn.commit()?;
+        Ok(())
+    }
+
+    /// Visits the class sources cached by `decompiler` whose jar passes
+    /// `in_scope`, each with its parsed structures. A source with no entry in
+    /// `CLASS_STRUCTURE_DB` is parsed and the result stored when
+    /// `parse_missing` is set, and skipped otherwise. Member-type entries
+    /// (`Outer$Inner`) are skipped: the outer class's structure covers them.
+    pub fn for_each_compilation_unit(
+        &self,
+        decompiler: DecompilerKind,
+        in_scope: impl Fn(&str) -> bool,
+        parse_missing: bool,
+        mut visit: impl FnMut(&str, &str, &CompilationUnit),
+    ) -> Result<StructureScan> {
+        let mut scan = StructureScan::default();
+        let mut parsed = Vec::new();
+        {
+            let rtxn = self.env.read_txn()?;
+            for item in self.class_sources.iter(&rtxn)? {
+                let (key, value) = item?;
+                let Some((class_name, jar_key)) = split_class_source_key(key, decompiler) else {
+                    continue;
+                };
+                if class_name.contains('$') || !in_scope(jar_key) {
+                    continue;
+                }
+                let Ok(cached) = serde_json::from_str::<CachedClassSource>(value) else {
+                    continue;
+                };
+                let content_hash = hash_content(&cached.content);
+                let stored = self
+                    .class_structure
+                    .get(&rtxn, &structure_key(&content_hash))?
+                    .and_then(|v| serde_json::from_str::<CompilationUnit>(v).ok());
+                let unit = match stored {
+                    Some(unit) => unit,
+                    None if parse_missing => {
+                        let Some(unit) = parse_compilation_unit(&cached.content) else {
+                            continue;
+                        };
+                        scan.parsed_classes += 1;
+                        parsed.push((content_hash, unit.clone()));
+                        unit
+                    }
+                    None => continue,
+                };
+                scan.scanned_classes += 1;
+                visit(class_name, jar_key, &unit);
+            }
+        }
+        for (content_hash, unit) in &parsed {
+            self.put_compilation_unit(content_hash, unit)?;
+        }
+        Ok(scan)
+    }
+
     pub fn is_jar_loaded(&self, jar_key: &str) -> Result<bool> {
         let rtxn = self.env.read_txn()?;
         Ok(self.jars.get(&rtxn, jar_key)?.is_some())
     }
 
+    /// Mode the jar was last loaded in, if it was loaded at all.
+    pub fn loaded_mode(&self, jar_key: &str) -> Result<Option<WarmupMode>> {
+        let rtxn = self.env.read_txn()?;
+        Ok(self.jars.get(&rtxn, jar_key)?.map(WarmupMode::from_marker))
+    }
+
     pub fn mark_jar_loaded(&self, jar_key: &str) -> Result<()> {
         let mut wtxn = self.env.write_txn()?;
         self.jars.put(&mut wtxn, jar_key, "1")?;
@@ -137,6 +455,81 @@ impl PersistentCache {
         Ok(())
     }
 
+    /// Adds one run's counts to the persisted [`BackgroundWarmupStats`].
+    pub fn record_background_warmup(&self, run: &BackgroundWarmupStats) -> Result<()> {
+        let mut wtxn = self.env.write_txn()?;
+        let mut total: BackgroundWarmupStats = self
+            .meta
+            .get(&wtxn, BACKGROUND_WARMUP_KEY)?
+            .and_then(|v| serde_json::from_str(v).ok())
+            .unwrap_or_default();
+        total.submitted_tasks += run.submitted_tasks;
+        total.completed_tasks += run.completed_tasks;
+        total.failed_tasks += run.failed_tasks;
+        total.unfinished_tasks += run.unfinished_tasks;
+        total.failed_classes += run.failed_classes;
+        let payload = serde_json::to_string(&total)?;
+        self.meta
+            .put(&mut wtxn, BACKGROUND_WARMUP_KEY, payload.as_str())?;
+        wtxn.commit()?;
+        Ok(())
+    }
+
+    /// Class sources, registry entries and structures: the rows a snapshot
+    /// policy counts to decide whether enough is new.
+    pub fn snapshot_entries(&self) -> Result<u64> {
+        let rtxn = self.env.read_txn()?;
+        Ok(table_len(&self.class_sources, &rtxn)?
+            + table_len(&self.class_registry, &rtxn)?
+            + table_len(&self.class_structure, &rtxn)?)
+    }
+
+    pub fn last_snapshot(&self) -> Result<Option<SnapshotRecord>> {
+        let rtxn = self.env.read_txn()?;
+        Ok(self
+            .meta
+            .get(&rtxn, SNAPSHOT_KEY)?
+            .and_then(|v| serde_json::from_str(v).ok()))
+    }
+
+    /// Copies the db to [`Self::snapshot_path`] and records the publication.
+    /// `buffer` is flushed first so the copy holds every write queued before
+    /// the call; the copy goes to a temp file renamed into place, so readers
+    /// of the snapshot never see a partial one.
+    pub fn publish_snapshot(&self, buffer: Option<&WriteBufferHandle>) -> Result<SnapshotRecord> {
+        let started = std::time::Instant::now();
+        if let Some(buffer) = buffer {
+            buffer.flush()?;
+        }
+        let entries = self.snapshot_entries()?;
+        let target = self.snapshot_path();
+        if self.layout == DbLayout::Directory {
+            std::fs::create_dir_all(&target)?;
+        }
+        let data = self.layout.data_path(&target);
+        let mut tmp = data.clone().into_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        self.env
+            .copy_to_path(&tmp, CompactionOption::Enabled)
+            .with_context(|| format!("Failed to copy db to {}", tmp.display()))?
+            .sync_all()?;
+        std::fs::rename(&tmp, &data)
+            .with_context(|| format!("Failed to publish snapshot: {}", target.display()))?;
+
+        let record = SnapshotRecord {
+            path: target.to_string_lossy().to_string(),
+            published_at: unix_now(),
+            entries,
+            duration_ms: started.elapsed().as_millis() as u64,
+        };
+        let mut wtxn = self.env.write_txn()?;
+        let payload = serde_json::to_string(&record)?;
+        self.meta.put(&mut wtxn, SNAPSHOT_KEY, payload.as_str())?;
+        wtxn.commit()?;
+        Ok(record)
+    }
+
     pub fn stats(&self) -> Result<CacheStats> {
         let rtxn = self.env.read_txn()?;
 
@@ -145,8 +538,13 @@ impl PersistentCache {
         let indexed_classes = table_len(&self.class_registry, &rtxn)?;
         let cataloged_jars = table_len(&self.artifact_manifest, &rtxn)?;
         let hotspot_jars = table_len(&self.jar_hotspot, &rtxn)?;
+        let structure_entries = table_len(&self.class_structure, &rtxn)?;
         let mut warmed_jars = 0u64;
+        let mut warmed_classes = 0u64;
+        let mut last_warmup_at = None;
+        let warmup_pending_tasks = table_len(&self.warmup_queue, &rtxn)?;
         let mut hotspot_top = Vec::new();
+        let now = unix_now();
         for item in self.jar_hotspot.iter(&rtxn)? {
             let (k, v) = item?;
             let Ok(h) = serde_json::from_str::<JarHotspotRow>(v) else {
@@ -154,37 +552,70 @@ impl PersistentCache {
             };
             if h.warmed {
                 warmed_jars += 1;
+                warmed_classes += u64::from(h.class_count);
+            }
+            if h.warmed_at > 0 {
+                last_warmup_at = last_warmup_at.max(Some(h.warmed_at));
             }
             hotspot_top.push(HotspotTopEntry {
                 jar_path: k.to_string(),
+                score: ranking_score(h.access_count, &h.recent_accesses, now),
                 access_count: h.access_count,
                 last_access: h.last_access,
                 warmed: h.warmed,
             });
         }
         hotspot_top.sort_by(|a, b| {
-            b.access_count
-                .cmp(&a.access_count)
+            b.score
+                .cmp(&a.score)
                 .then_with(|| b.last_access.cmp(&a.last_access))
                 .then_with(|| a.jar_path.cmp(&b.jar_path))
         });
         hotspot_top.truncate(10);
-        let write_buffer_pending = std::fs::read_to_string(self.pending_gauge_path())
+        let (write_buffer_pending, live_metrics) = read_pending_gauge(&self.pending_gauge_path());
+        let write_buffer_metrics_live = live_metrics.is_some();
+        let write_buffer_metrics = match live_metrics {
+            Some(metrics) => Some(metrics),
+            None => self
+                .meta
+                .get(&rtxn, WRITE_BUFFER_METRICS_KEY)?
+                .and_then(|v| serde_json::from_str(v).ok()),
+        };
+        let java_processes_in_flight = std::fs::read_to_string(self.java_gauge_path())
             .ok()
             .and_then(|s| s.trim().parse::<u64>().ok())
             .unwrap_or(0);
+        let background_warmup = self
+            .meta
+            .get(&rtxn, BACKGROUND_WARMUP_KEY)?
+            .and_then(|v| serde_json::from_str(v).ok());
+        let last_snapshot = self
+            .meta
+            .get(&rtxn, SNAPSHOT_KEY)?
+            .and_then(|v| serde_json::from_str(v).ok());
         Ok(CacheStats {
             db_path: self.db_path.to_string_lossy().to_string(),
             source_entries,
             indexed_classes,
+            structure_entries,
             cataloged_jars,
             loaded_jars,
             write_buffer_pending,
+            write_buffer_metrics,
+            write_buffer_metrics_live,
+            write_buffer_config: None,
+            java_processes_in_flight,
+            cfr_version: None,
             hotspot_jars,
             warmed_jars,
-            warmup_threshold: 2,
-            warmup_pending_tasks: 0,
+            warmed_classes,
+            last_warmup_at,
+            warmup_threshold: DEFAULT_WARMUP_THRESHOLD,
+            warmup_pending_tasks,
+            background_warmup,
+            last_snapshot,
             hotspot_top,
+            group_top: Vec::new(),
         })
     }
 }
@@ -208,27 +639,172 @@ impl ReadOnlyCache {
     }
 }
 
-fn open_env(db_path: &Path) -> Result<Env> {
+/// A group of tables `clear` can empty without touching the rest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClearTarget {
+    Sources,
+    /// The class registry together with the manifest of cataloged jars, so
+    /// `index` catalogs every jar again.
+    Registry,
+    Hotspot,
+    LoadedFlags,
+    /// Recorded jar mtimes together with the stored jar lists of scanned
+    /// roots, so the next index and find look at the filesystem again.
+    Mtimes,
+}
+
+impl ClearTarget {
+    pub fn tables(self) -> &'static [&'static str] {
+        match self {
+            Self::Sources => &[CLASS_SOURCES_DB],
+            Self::Registry => &[CLASS_REGISTRY_DB, ARTIFACT_MANIFEST_DB],
+            Self::Hotspot => &[JAR_HOTSPOT_DB, CLASS_HOTSPOT_DB],
+            Self::LoadedFlags => &[JARS_DB],
+            Self::Mtimes => &[JAR_MTIME_DB, SCAN_CACHE_DB],
+        }
+    }
+}
+
+/// Empties the tables of `targets` in one transaction and returns how many
+/// entries each one held.
+pub fn clear_tables(env: &Env, targets: &[ClearTarget]) -> Result<BTreeMap<&'static str, u64>> {
+    let mut wtxn = env.write_txn()?;
+    let mut removed = BTreeMap::new();
+    for name in targets.iter().flat_map(|target| target.tables()) {
+        let table = env.create_database::<Str, Str>(&mut wtxn, Some(name))?;
+        removed.insert(*name, table.len(&wtxn)?);
+        table.clear(&mut wtxn)?;
+    }
+    wtxn.commit()?;
+    Ok(removed)
+}
+
+/// Deletes what is kept for `jar_keys` outside the class registry: class
+/// sources and load markers of every backend, hotspot rows, queued warmups
+/// and recorded mtimes. Returns how many rows were deleted.
+pub fn delete_jar_data(env: &Env, jar_keys: &[String]) -> Result<usize> {
+    if jar_keys.is_empty() {
+        return Ok(0);
+    }
+    let jars: HashSet<&str> = jar_keys.iter().map(String::as_str).collect();
+    // `jar` or `jar::<backend>`, as built by `loaded_jar_key`.
+    let names_jar = |scoped: &str| {
+        jars.contains(scoped)
+            || scoped
+                .rsplit_once("::")
+                .is_some_and(|(jar, _)| jars.contains(jar))
+    };
+    let in_class_key = |key: &str| {
+        key.split_once("::")
+            .is_some_and(|(_, rest)| names_jar(rest))
+    };
+
+    let mut wtxn = env.write_txn()?;
+    let mut deleted = 0usize;
+    for (name, matches) in [
+        (CLASS_SOURCES_DB, &in_class_key as &dyn Fn(&str) -> bool),
+        (JARS_DB, &names_jar),
+        (CLASS_HOTSPOT_DB, &|key: &str| {
+            key.split_once("::")
+                .is_some_and(|(_, jar)| jars.contains(jar))
+        }),
+        (JAR_HOTSPOT_DB, &|key: &str| jars.contains(key)),
+        (WARMUP_QUEUE_DB, &|key: &str| jars.contains(key)),
+        (JAR_MTIME_DB, &|key: &str| jars.contains(key)),
+    ] {
+        let table = env.create_database::<Str, Str>(&mut wtxn, Some(name))?;
+        let mut entries = table.iter_mut(&mut wtxn)?;
+        while let Some(entry) = entries.next() {
+            let (key, _) = entry?;
+            if !matches(key) {
+                continue;
+            }
+            // SAFETY: `key` is not used after the deletion.
+            unsafe { entries.del_current()? };
+            deleted += 1;
+        }
+    }
+    wtxn.commit()?;
+    Ok(deleted)
+}
+
+fn open_env(db_path: &Path, layout: DbLayout) -> Result<Env> {
     let mut options = EnvOpenOptions::new();
     options.map_size(DEFAULT_MAP_SIZE);
     options.max_dbs(DEFAULT_MAX_DBS);
 
-    unsafe {
-        if db_path.is_dir() {
-            return options
-                .open(db_path)
-                .with_context(|| format!("Failed to create/open db env: {}", db_path.display()));
+    // SAFETY: We do not use NO_LOCK and keep default LMDB locking guarantees.
+    // NO_SUB_DIR keeps --db a single data file in file layout.
+    let opened = unsafe {
+        if layout == DbLayout::File {
+            options.flags(EnvFlags::NO_SUB_DIR);
         }
+        options.open(db_path)
+    };
+    opened.map_err(|err| {
+        if is_busy(&err) {
+            return ClassFinderError::CacheBusy {
+                path: db_path.to_string_lossy().to_string(),
+                reason: err.to_string(),
+            }
+            .into();
+        }
+        anyhow::Error::new(err).context(format!(
+            "Failed to create/open db env: {}",
+            db_path.display()
+        ))
+    })
+}
 
-        // SAFETY: We do not use NO_LOCK and keep default LMDB locking guarantees.
-        // NO_SUB_DIR preserves current single-path CLI behavior for --db.
-        options.flags(EnvFlags::NO_SUB_DIR);
-        options
-            .open(db_path)
-            .with_context(|| format!("Failed to create/open db env: {}", db_path.display()))
+/// Whether opening the env failed because other processes hold it: every
+/// reader slot is taken or the lock file is locked.
+fn is_busy(err: &heed::Error) -> bool {
+    match err {
+        heed::Error::Mdb(heed::MdbError::ReadersFull) => true,
+        heed::Error::Io(err) => matches!(
+            err.kind(),
+            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::ResourceBusy
+        ),
+        _ => false,
     }
 }
 
+/// Reads the pending-writes gauge a running write buffer keeps next to the
+/// db: the pending count on the first line, then optionally its
+/// [`WriteBufferMetrics`] as JSON.
+fn read_pending_gauge(path: &Path) -> (u64, Option<WriteBufferMetrics>) {
+    let Ok(content) = std::fs::read_to_string(path) else {
+        return (0, None);
+    };
+    let mut lines = content.lines();
+    let pending = lines
+        .next()
+        .and_then(|line| line.trim().parse::<u64>().ok())
+        .unwrap_or(0);
+    let metrics = lines
+        .next()
+        .and_then(|line| serde_json::from_str(line).ok());
+    (pending, metrics)
+}
+
+/// Class name and jar of a `CLASS_SOURCES_DB` key written for `decompiler`,
+/// the inverse of [`class_source_key`].
+fn split_class_source_key(key: &str, decompiler: DecompilerKind) -> Option<(&str, &str)> {
+    let (class_name, rest) = key.split_once("::")?;
+    let backend = rest.rsplit_once("::").and_then(|(jar_key, suffix)| {
+        DecompilerKind::value_variants()
+            .iter()
+            .find(|kind| **kind != DecompilerKind::Cfr && kind.as_str() == suffix)
+            .map(|kind| (jar_key, *kind))
+    });
+    let (jar_key, kind) = backend.unwrap_or((rest, DecompilerKind::Cfr));
+    (kind == decompiler).then_some((class_name, jar_key))
+}
+
+fn structure_key(content_hash: &str) -> String {
+    format!("{STRUCTURE_SCHEMA}:{content_hash}")
+}
+
 fn table_len(db: &StrDb, rtxn: &RoTxn<'_>) -> Result<u64> {
     let mut count = 0u64;
     for item in db.iter(rtxn)? {
@@ -243,27 +819,63 @@ struct JarHotspotRow {
     access_count: u32,
     last_access: u64,
     warmed: bool,
+    #[serde(default)]
+    class_count: u32,
+    #[serde(default)]
+    warmed_at: u64,
+    #[serde(default)]
+    recent_accesses: Vec<u64>,
 }
 
 #[derive(Debug, serde::Serialize)]
 pub struct HotspotTopEntry {
     pub jar_path: String,
+    pub score: u64,
     pub access_count: u32,
     pub last_access: u64,
     pub warmed: bool,
 }
 
+/// Counts of one [`PersistentCache::for_each_compilation_unit`] pass.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct StructureScan {
+    /// Classes visited, with a cached or freshly parsed structure.
+    pub scanned_classes: u64,
+    /// Structures parsed during the pass because none was cached.
+    pub parsed_classes: u64,
+}
+
 #[derive(Debug, serde::Serialize)]
 pub struct CacheStats {
     pub db_path: String,
     pub source_entries: u64,
     pub indexed_classes: u64,
+    /// Parsed class structures kept by content hash.
+    pub structure_entries: u64,
     pub cataloged_jars: u64,
     pub loaded_jars: u64,
     pub write_buffer_pending: u64,
+    /// Live metrics of a running write buffer, or those of the last one that
+    /// shut down (`write_buffer_metrics_live` is false then).
+    pub write_buffer_metrics: Option<WriteBufferMetrics>,
+    pub write_buffer_metrics_live: bool,
+    /// Buffer tuning the current flags and environment resolve to.
+    pub write_buffer_config: Option<BufferConfig>,
+    pub java_processes_in_flight: u64,
+    pub cfr_version: Option<String>,
     pub hotspot_jars: u64,
     pub warmed_jars: u64,
+    /// Classes cached by the warmups of the warmed jars.
+    pub warmed_classes: u64,
+    /// Unix time of the most recent warmup of any jar.
+    pub last_warmup_at: Option<u64>,
     pub warmup_threshold: u32,
+    /// Jars in the persisted warmup queue, which `warmup --resume` drains.
     pub warmup_pending_tasks: u64,
+    pub background_warmup: Option<BackgroundWarmupStats>,
+    /// The last publication of `<db>.snapshot`.
+    pub last_snapshot: Option<SnapshotRecord>,
     pub hotspot_top: Vec<HotspotTopEntry>,
+    /// Filled in by the caller, which knows the m2 repository.
+    pub group_top: Vec<GroupAccess>,
 }
@@ -1,32 +1,32 @@
-use anyhow::{Context, Result};
-use memmap2::Mmap;
-use std::fs::File;
-use std::io::Cursor;
+use anyhow::Result;
 use std::path::Path;
-use zip::ZipArchive;
+
+use crate::listing::jar_listings;
 
 pub fn catalog(artifact_path: &Path) -> Result<Vec<String>> {
-    let file = File::open(artifact_path)
-        .with_context(|| format!("Failed to open jar: {}", artifact_path.display()))?;
-    // SAFETY: The file is opened read-only and remains valid for the lifetime of the mmap.
-    // The mmap is dropped before the file, ensuring memory safety.
-    let mmap = unsafe { Mmap::map(&file) }
-        .with_context(|| format!("mmap jar failed: {}", artifact_path.display()))?;
-    let mut archive = ZipArchive::new(Cursor::new(&mmap[..]))
-        .with_context(|| format!("Failed to parse zip(jar): {}", artifact_path.display()))?;
+    list_classes(artifact_path, false)
+}
+
+/// Member types of the jar (`a.Outer$Inner`), without anonymous and local
+/// classes, whose binary names have a segment starting with a digit.
+pub fn nested_classes(artifact_path: &Path) -> Result<Vec<String>> {
+    list_classes(artifact_path, true)
+}
 
+fn list_classes(artifact_path: &Path, nested: bool) -> Result<Vec<String>> {
+    let listing = jar_listings().get(artifact_path)?;
     let mut classes = Vec::new();
-    for i in 0..archive.len() {
-        let entry = archive.by_index(i)?;
-        let name = entry.name();
+    for name in listing.names() {
         if !name.ends_with(".class") {
             continue;
         }
-        if name.contains('$') {
-            continue;
-        }
         let class_name = name.trim_end_matches(".class").replace(['/', '\\'], ".");
-        classes.push(class_name);
+        let mut segments = class_name.split('$');
+        segments.next();
+        let member = segments.all(|s| s.chars().next().is_some_and(|c| !c.is_ascii_digit()));
+        if class_name.contains('$') == nested && (!nested || member) {
+            classes.push(class_name);
+        }
     }
     Ok(classes)
 }
@@ -73,6 +73,8 @@ mod tests {
             &[
                 ("org/example/A.class", b""),
                 ("org/example/A$Inner.class", b""),
+                ("org/example/A$1.class", b""),
+                ("org/example/A$Inner$1Local.class", b""),
                 ("META-INF/MANIFEST.MF", b""),
             ],
         )?;
@@ -80,6 +82,10 @@ mod tests {
         let classes = catalog(&jar)?;
         assert!(classes.contains(&"org.example.A".to_string()));
         assert!(!classes.iter().any(|c| c.contains('$')));
+        assert_eq!(
+            nested_classes(&jar)?,
+            vec!["org.example.A$Inner".to_string()]
+        );
         std::fs::remove_file(jar)?;
         Ok(())
     }
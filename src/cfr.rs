@@ -1,95 +1,334 @@
 use anyhow::{Context, Result, bail};
+use std::collections::HashSet;
 use std::path::Path;
-use std::process::Command;
-
-fn java_command(args: &[&str]) -> Result<std::process::Output> {
-    let java_bin = std::env::var("CLASS_FINDER_JAVA").unwrap_or_else(|_| "java".to_string());
-
-    #[cfg(windows)]
-    {
-        let lower = java_bin.to_ascii_lowercase();
-        if lower.ends_with(".cmd") || lower.ends_with(".bat") {
-            return Command::new("cmd")
-                .arg("/C")
-                .arg(&java_bin)
-                .args(args)
-                .output()
-                .context("Failed to execute java (ensure JRE/JDK is installed)");
-        }
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use crate::cfr_server::CfrServer;
+use crate::classpath::decompile_classpath;
+use crate::deadline::DeadlineExceeded;
+use crate::decompiler::{
+    BatchDecompileOutput, DecompileTimeout, Decompiler, DecompilerConfig, DecompilerKind,
+    JavaInvocation, WorkDir, decode_decompiler_output, decompiled_stream, for_each_java_source,
+    java_command, java_command_streaming, manifest_version,
+};
+use crate::encoding::DecodedOutput;
+use crate::error::ClassFinderError;
+use crate::parse::ParsedClass;
+use crate::postprocess::Postprocessor;
+
+/// CFR options class-finder controls itself; overriding them would change
+/// which inputs CFR reads or where it writes.
+const RESERVED_CFR_OPTIONS: [&str; 4] = ["extraclasspath", "outputdir", "outputpath", "jarfilter"];
+
+/// Parses a `--cfr-arg key=value` pass-through option.
+pub fn parse_cfr_arg(raw: &str) -> Result<(String, String)> {
+    let (key, value) = raw
+        .split_once('=')
+        .with_context(|| format!("Invalid CFR option (expected key=value): {raw}"))?;
+    let key = key.trim().trim_start_matches("--");
+    let value = value.trim();
+
+    if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        bail!("Invalid CFR option name: {raw}");
+    }
+    if value.is_empty() || value.starts_with('-') {
+        bail!("Invalid CFR option value: {raw}");
+    }
+    if RESERVED_CFR_OPTIONS.contains(&key.to_ascii_lowercase().as_str()) {
+        bail!("CFR option --{key} is managed by class-finder and cannot be overridden");
     }
 
-    Command::new(&java_bin)
-        .args(args)
-        .output()
-        .context("Failed to execute java (ensure JRE/JDK is installed)")
+    Ok((key.to_string(), value.to_string()))
+}
+
+/// Extracts `0.152` from CFR's `--version` output (`CFR 0.152`).
+fn parse_cfr_version(output: &str) -> Option<String> {
+    output.lines().find_map(|line| {
+        let version = line.trim().strip_prefix("CFR ")?.trim();
+        (!version.is_empty() && version.starts_with(|c: char| c.is_ascii_digit()))
+            .then(|| version.to_string())
+    })
 }
 
 #[derive(Debug, Clone)]
 pub struct Cfr {
     cfr_jar: std::path::PathBuf,
+    config: DecompilerConfig,
+    extra_args: Vec<(String, String)>,
+    version: OnceLock<Option<String>>,
+    server: Option<Arc<CfrServer>>,
 }
 
 impl Cfr {
     pub fn new(cfr_jar: std::path::PathBuf) -> Self {
-        Self { cfr_jar }
+        Self::with_config(cfr_jar, DecompilerConfig::default())
+    }
+
+    pub fn with_config(cfr_jar: std::path::PathBuf, config: DecompilerConfig) -> Self {
+        Self {
+            cfr_jar,
+            config,
+            extra_args: Vec::new(),
+            version: OnceLock::new(),
+            server: None,
+        }
+    }
+
+    /// Appends `--key value` pairs (see [`parse_cfr_arg`]) to every CFR run.
+    pub fn with_extra_args(mut self, extra_args: Vec<(String, String)>) -> Self {
+        self.extra_args = extra_args;
+        self
+    }
+
+    /// Routes single-class decompiles through a persistent [`CfrServer`].
+    /// Call after [`Cfr::with_extra_args`] so the server gets the same options.
+    pub fn with_server(mut self) -> Self {
+        self.server = Some(Arc::new(CfrServer::new(
+            self.cfr_jar.clone(),
+            self.config.java_opts.clone(),
+            self.options(),
+        )));
+        self
+    }
+
+    /// Reads the version from the CFR jar's manifest, falling back to
+    /// `java -jar cfr.jar --version`.
+    fn probe_version(&self) -> Option<String> {
+        manifest_version(&self.cfr_jar).or_else(|| {
+            let jar = self.cfr_jar.to_str()?;
+            let output = java_command(
+                &self.config.java_opts,
+                &["-jar", jar, "--version"],
+                jar,
+                self.config.class_timeout,
+            )
+            .ok()?;
+            if !output.status.success() {
+                return None;
+            }
+            parse_cfr_version(&String::from_utf8_lossy(&output.stdout))
+        })
     }
 
-    pub fn decompile_class(&self, jar_path: &Path, class_name: &str) -> Result<String> {
-        let output = java_command(&[
-            "-jar",
+    fn args(
+        &self,
+        classpath: Option<&str>,
+        inputs: &[&str],
+        output_dir: Option<&str>,
+    ) -> Result<Vec<String>> {
+        let mut args = vec![
+            "-jar".to_string(),
             self.cfr_jar
                 .to_str()
-                .context("cfr.jar path is not valid UTF-8")?,
-            "--extraclasspath",
-            jar_path.to_str().context("jar path is not valid UTF-8")?,
-            class_name,
-            "--silent",
-            "true",
-            "--comments",
-            "false",
-        ])?;
+                .context("cfr.jar path is not valid UTF-8")?
+                .to_string(),
+        ];
+        if let Some(classpath) = classpath {
+            args.extend(["--extraclasspath".to_string(), classpath.to_string()]);
+        }
+        args.extend(inputs.iter().map(|input| input.to_string()));
+        if let Some(output_dir) = output_dir {
+            args.extend(["--outputdir".to_string(), output_dir.to_string()]);
+        }
+        args.extend(["--silent", "true", "--comments", "false"].map(String::from));
+        for (key, value) in &self.extra_args {
+            args.extend([format!("--{key}"), value.clone()]);
+        }
+        Ok(args)
+    }
+
+    fn run(
+        &self,
+        classpath: Option<&str>,
+        inputs: &[&str],
+        output_dir: Option<&str>,
+        target: &str,
+        timeout: Duration,
+    ) -> Result<DecodedOutput> {
+        let args = self.args(classpath, inputs, output_dir)?;
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let output = java_command(&self.config.java_opts, &args, target, timeout)?;
+        self.decoded(output, target)
+    }
 
+    fn decoded(&self, output: std::process::Output, target: &str) -> Result<DecodedOutput> {
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
-            bail!("CFR decompilation failed: {}", stderr.trim());
+            return Err(ClassFinderError::decompile_failed(
+                target,
+                format!("CFR decompilation failed: {}", stderr.trim()),
+            )
+            .into());
         }
 
-        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+        Ok(decode_decompiler_output(&output.stdout, &self.config))
     }
+}
 
-    pub fn decompile_jar(&self, jar_path: &Path) -> Result<String> {
-        let output = java_command(&[
-            "-jar",
-            self.cfr_jar
-                .to_str()
-                .context("cfr.jar path is not valid UTF-8")?,
-            jar_path.to_str().context("jar path is not valid UTF-8")?,
-            "--silent",
-            "true",
-            "--comments",
-            "false",
-        ])?;
+impl Decompiler for Cfr {
+    fn kind(&self) -> DecompilerKind {
+        DecompilerKind::Cfr
+    }
+
+    fn options(&self) -> Vec<String> {
+        self.extra_args
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect()
+    }
+
+    fn version(&self) -> Option<String> {
+        self.version.get_or_init(|| self.probe_version()).clone()
+    }
+
+    fn postprocessor(&self) -> Option<&Postprocessor> {
+        self.config.postprocess.as_ref()
+    }
+
+    fn decompile_class(&self, jar_path: &Path, class_name: &str) -> Result<DecodedOutput> {
+        let classpath = decompile_classpath(jar_path, self.config.classpath)?;
+        if let Some(server) = self.server.as_ref().filter(|s| s.is_alive()) {
+            match server.decompile_class(&classpath, class_name, self.config.class_timeout) {
+                Ok(output) => return Ok(decode_decompiler_output(&output, &self.config)),
+                Err(err)
+                    if server.is_alive()
+                        || err.is::<DecompileTimeout>()
+                        || err.is::<DeadlineExceeded>() =>
+                {
+                    return Err(err);
+                }
+                Err(err) => {
+                    tracing::warn!("CFR server unavailable, running CFR directly: {err}")
+                }
+            }
+        }
+
+        let invocation = JavaInvocation::new(
+            &self.config.java_opts,
+            self.args(Some(&classpath), &[class_name], None)?,
+            class_name,
+            self.config.class_timeout,
+        );
+        let output = invocation.run()?;
+        self.invocation_output(&invocation, output)
+    }
 
+    /// A direct CFR run, even with a server configured.
+    fn class_invocation(
+        &self,
+        jar_path: &Path,
+        class_name: &str,
+    ) -> Result<Option<JavaInvocation>> {
+        let classpath = decompile_classpath(jar_path, self.config.classpath)?;
+        Ok(Some(JavaInvocation::new(
+            &self.config.java_opts,
+            self.args(Some(&classpath), &[class_name], None)?,
+            class_name,
+            self.config.class_timeout,
+        )))
+    }
+
+    fn invocation_output(
+        &self,
+        invocation: &JavaInvocation,
+        output: std::process::Output,
+    ) -> Result<DecodedOutput> {
+        self.decoded(output, &invocation.target)
+    }
+
+    /// Passes every requested class to a single CFR run.
+    fn decompile_classes(
+        &self,
+        jar_path: &Path,
+        class_names: &[String],
+    ) -> Result<BatchDecompileOutput> {
+        if class_names.is_empty() {
+            return Ok(BatchDecompileOutput::default());
+        }
+
+        let jar = jar_path.to_str().context("jar path is not valid UTF-8")?;
+        let inputs: Vec<&str> = class_names.iter().map(String::as_str).collect();
+        let timeout = self
+            .config
+            .class_timeout
+            .saturating_mul(u32::try_from(class_names.len()).unwrap_or(u32::MAX))
+            .min(self.config.jar_timeout.max(self.config.class_timeout));
+        let classpath = decompile_classpath(jar_path, self.config.classpath)?;
+        let output = self.run(Some(&classpath), &inputs, None, jar, timeout)?;
+
+        Ok(BatchDecompileOutput::from_decoded(&output, class_names))
+    }
+
+    fn decompile_jar(&self, jar_path: &Path) -> Result<DecodedOutput> {
+        let jar = jar_path.to_str().context("jar path is not valid UTF-8")?;
+        self.run(None, &[jar], None, jar, self.config.jar_timeout)
+    }
+
+    /// Writes one file per class with `--outputdir` and streams them back, so
+    /// class names come from file paths rather than from splitting stdout.
+    /// CFR builds that ignore `--outputdir` still print to stdout; that output
+    /// is parsed as it arrives instead of being collected first.
+    fn decompile_jar_each(
+        &self,
+        jar_path: &Path,
+        on_class: &mut dyn FnMut(ParsedClass),
+    ) -> Result<usize> {
+        let jar = jar_path.to_str().context("jar path is not valid UTF-8")?;
+        let work = WorkDir::create("cfr_jar")?;
+        let out_dir = work
+            .path()
+            .to_str()
+            .context("output path is not valid UTF-8")?;
+        let args = self.args(None, &[jar], Some(out_dir))?;
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+        let mut seen = HashSet::new();
+        let mut emit = |cls: ParsedClass| {
+            if seen.insert(cls.class_name.clone()) {
+                on_class(cls);
+            }
+        };
+        let output = java_command_streaming(
+            &self.config.java_opts,
+            &args,
+            jar,
+            self.config.jar_timeout,
+            &mut |stdout| {
+                for cls in decompiled_stream(stdout, &self.config) {
+                    emit(cls?);
+                }
+                Ok(())
+            },
+        )?;
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
-            bail!("CFR decompilation failed: {}", stderr.trim());
+            return Err(ClassFinderError::decompile_failed(
+                jar,
+                format!("CFR decompilation failed: {}", stderr.trim()),
+            )
+            .into());
         }
 
-        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+        for_each_java_source(work.path(), &self.config, &mut emit)?;
+        Ok(seen.len())
     }
 }
 
 #[cfg(all(test, unix))]
 mod tests {
     use super::*;
+    use crate::classpath::ClasspathMode;
+    use crate::decompiler::{DecompileTimeout, java_env_lock, split_java_opts};
+    use crate::encoding::FallbackEncoding;
     use std::fs;
     use std::path::PathBuf;
-    use std::sync::{Mutex, OnceLock};
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
     use std::time::{SystemTime, UNIX_EPOCH};
 
     fn path_env_lock() -> &'static Mutex<()> {
-        static LOCK: OnceLock<Mutex<()>> = OnceLock::new();
-        LOCK.get_or_init(|| Mutex::new(()))
+        java_env_lock()
     }
 
     fn temp_dir(name: &str) -> PathBuf {
@@ -121,6 +360,108 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn decompile_class_passes_sibling_jars_in_artifact_mode() -> Result<()> {
+        let _guard = path_env_lock().lock().expect("PATH test lock poisoned");
+        let base = temp_dir("classpath_mode");
+        let fake_cfr = base.join("tools/cfr.jar");
+        let jar = base.join("repo/demo.jar");
+        let sibling = base.join("repo/demo-tests.jar");
+        let fake_bin = base.join("bin");
+        let fake_java = fake_bin.join("java");
+
+        write_file(&fake_cfr, "stub")?;
+        write_file(&jar, "stub")?;
+        write_file(&sibling, "stub")?;
+        write_file(&base.join("repo/demo-sources.jar"), "stub")?;
+        write_file(
+            &fake_java,
+            r#"#!/bin/sh
+[ "$3" = "--extraclasspath" ] || exit 1
+printf 'package a;\n// cp=%s\npublic class B {\n}\n' "$4"
+"#,
+        )?;
+        make_executable(&fake_java)?;
+
+        let old_path = std::env::var("PATH").unwrap_or_default();
+        let new_path = format!("{}:{}", fake_bin.to_string_lossy(), old_path);
+        // SAFETY: Guarded by path_env_lock and restored before returning.
+        unsafe { std::env::set_var("PATH", &new_path) };
+
+        let result: Result<()> = {
+            let cfr = Cfr::new(fake_cfr.clone());
+            let out = cfr.decompile_class(&jar, "a.B")?.text;
+            let expected = format!("// cp={}:{}", jar.display(), sibling.display());
+            assert!(out.contains(&expected), "{out}");
+
+            let cfr = Cfr::with_config(
+                fake_cfr,
+                DecompilerConfig {
+                    classpath: ClasspathMode::None,
+                    ..DecompilerConfig::default()
+                },
+            );
+            let out = cfr.decompile_class(&jar, "a.B")?.text;
+            assert!(out.contains(&format!("// cp={}\n", jar.display())), "{out}");
+            Ok(())
+        };
+
+        // SAFETY: Guarded by path_env_lock and restored before returning.
+        unsafe { std::env::set_var("PATH", old_path) };
+        let _ = fs::remove_dir_all(base);
+        result
+    }
+
+    #[test]
+    fn non_utf8_output_is_decoded_with_the_fallback_encoding() -> Result<()> {
+        let _guard = path_env_lock().lock().expect("PATH test lock poisoned");
+        let base = temp_dir("non_utf8");
+        let fake_cfr = base.join("tools/cfr.jar");
+        let jar = base.join("repo/demo.jar");
+        let fake_bin = base.join("bin");
+        let fake_java = fake_bin.join("java");
+
+        write_file(&fake_cfr, "stub")?;
+        write_file(&jar, "stub")?;
+        write_file(
+            &fake_java,
+            r#"#!/bin/sh
+printf 'package a;\npublic class B {\n    String s = "caf\351";\n}\n'
+"#,
+        )?;
+        make_executable(&fake_java)?;
+
+        let old_path = std::env::var("PATH").unwrap_or_default();
+        let new_path = format!("{}:{}", fake_bin.to_string_lossy(), old_path);
+        // SAFETY: Guarded by path_env_lock and restored before returning.
+        unsafe { std::env::set_var("PATH", &new_path) };
+
+        let result: Result<()> = {
+            let cfr = Cfr::new(fake_cfr.clone());
+            let out = cfr.decompile_class(&jar, "a.B")?;
+            assert!(out.text.contains("\"caf\u{e9}\""), "{}", out.text);
+            assert_eq!(out.encoding, Some(FallbackEncoding::Latin1));
+
+            let cfr = Cfr::with_config(
+                fake_cfr,
+                DecompilerConfig {
+                    fallback_encoding: FallbackEncoding::Gbk,
+                    ..DecompilerConfig::default()
+                },
+            );
+            let mut classes = Vec::new();
+            cfr.decompile_jar_each(&jar, &mut |cls| classes.push(cls))?;
+            assert_eq!(classes.len(), 1);
+            assert_eq!(classes[0].encoding.as_deref(), Some("gbk"));
+            Ok(())
+        };
+
+        // SAFETY: Guarded by path_env_lock and restored before returning.
+        unsafe { std::env::set_var("PATH", old_path) };
+        let _ = fs::remove_dir_all(base);
+        result
+    }
+
     #[test]
     fn decompile_class_uses_extraclasspath() -> Result<()> {
         let _guard = path_env_lock().lock().expect("PATH test lock poisoned");
@@ -158,7 +499,7 @@ fi
 
         let result: Result<()> = {
             let cfr = Cfr::new(fake_cfr);
-            let out = cfr.decompile_class(&fake_jar, "org.example.Demo")?;
+            let out = cfr.decompile_class(&fake_jar, "org.example.Demo")?.text;
             assert!(out.contains("public class Demo"));
             Ok(())
         };
@@ -207,4 +548,345 @@ exit 1
         let _ = fs::remove_dir_all(base);
         result
     }
+
+    #[test]
+    fn decompile_jar_kills_hung_process_on_timeout() -> Result<()> {
+        let _guard = path_env_lock().lock().expect("PATH test lock poisoned");
+        let base = temp_dir("decompile_jar_timeout");
+        let fake_cfr = base.join("cfr.jar");
+        let fake_jar = base.join("demo.jar");
+        let fake_bin = base.join("bin");
+        let fake_java = fake_bin.join("java");
+
+        write_file(&fake_cfr, "stub")?;
+        write_file(&fake_jar, "stub")?;
+        write_file(
+            &fake_java,
+            r#"#!/bin/sh
+printf 'package a;\npublic class Early {\n}\n'
+sleep 30
+echo "should not be reached"
+"#,
+        )?;
+        make_executable(&fake_java)?;
+
+        let old_path = std::env::var("PATH").unwrap_or_default();
+        let new_path = format!("{}:{}", fake_bin.to_string_lossy(), old_path);
+        // SAFETY: Guarded by path_env_lock and restored before returning.
+        unsafe { std::env::set_var("PATH", &new_path) };
+
+        let result: Result<()> = {
+            let cfr = Cfr::with_config(
+                fake_cfr,
+                DecompilerConfig {
+                    class_timeout: Duration::from_millis(200),
+                    jar_timeout: Duration::from_millis(200),
+                    ..DecompilerConfig::default()
+                },
+            );
+            let start = Instant::now();
+            let err = cfr.decompile_jar(&fake_jar).unwrap_err();
+            assert!(start.elapsed() < Duration::from_secs(10));
+            let timeout = err
+                .downcast_ref::<DecompileTimeout>()
+                .expect("expected DecompileTimeout");
+            assert_eq!(timeout.timeout, Duration::from_millis(200));
+
+            // Streamed stdout is handed over before the process is killed.
+            let mut names = Vec::new();
+            let start = Instant::now();
+            let err = cfr
+                .decompile_jar_each(&fake_jar, &mut |cls| names.push(cls.class_name))
+                .unwrap_err();
+            assert!(start.elapsed() < Duration::from_secs(10));
+            assert!(err.downcast_ref::<DecompileTimeout>().is_some(), "{err:#}");
+            assert_eq!(names, ["a.Early"]);
+            Ok(())
+        };
+
+        // SAFETY: Guarded by path_env_lock and restored before returning.
+        unsafe { std::env::set_var("PATH", old_path) };
+        let _ = fs::remove_dir_all(base);
+        result
+    }
+
+    #[test]
+    fn decompile_class_places_java_opts_before_jar() -> Result<()> {
+        let _guard = path_env_lock().lock().expect("PATH test lock poisoned");
+        let base = temp_dir("decompile_class_java_opts");
+        let fake_cfr = base.join("cfr.jar");
+        let fake_jar = base.join("demo.jar");
+        let fake_bin = base.join("bin");
+        let fake_java = fake_bin.join("java");
+
+        write_file(&fake_cfr, "stub")?;
+        write_file(&fake_jar, "stub")?;
+        write_file(
+            &fake_java,
+            r#"#!/bin/sh
+set -e
+if [ "$1" = "-Xmx4g" ] && [ "$2" = "-Dname=a b" ] && [ "$3" = "-jar" ] && [ "$5" = "--extraclasspath" ]; then
+  echo "public class Demo {}"
+else
+  echo "unexpected args: $*" >&2
+  exit 1
+fi
+"#,
+        )?;
+        make_executable(&fake_java)?;
+
+        let old_path = std::env::var("PATH").unwrap_or_default();
+        let new_path = format!("{}:{}", fake_bin.to_string_lossy(), old_path);
+        // SAFETY: Guarded by path_env_lock and restored before returning.
+        unsafe { std::env::set_var("PATH", &new_path) };
+
+        let result: Result<()> = {
+            let cfr = Cfr::with_config(
+                fake_cfr,
+                DecompilerConfig {
+                    java_opts: split_java_opts(r#"-Xmx4g "-Dname=a b""#),
+                    ..DecompilerConfig::default()
+                },
+            );
+            let out = cfr.decompile_class(&fake_jar, "org.example.Demo")?.text;
+            assert!(out.contains("public class Demo"));
+            Ok(())
+        };
+
+        // SAFETY: Guarded by path_env_lock and restored before returning.
+        unsafe { std::env::set_var("PATH", old_path) };
+        let _ = fs::remove_dir_all(base);
+        result
+    }
+
+    #[test]
+    fn decompile_classes_maps_output_and_reports_missing() -> Result<()> {
+        let _guard = path_env_lock().lock().expect("PATH test lock poisoned");
+        let base = temp_dir("decompile_classes");
+        let fake_cfr = base.join("cfr.jar");
+        let fake_jar = base.join("demo.jar");
+        let fake_bin = base.join("bin");
+        let fake_java = fake_bin.join("java");
+
+        write_file(&fake_cfr, "stub")?;
+        write_file(&fake_jar, "stub")?;
+        write_file(
+            &fake_java,
+            r#"#!/bin/sh
+set -e
+[ "$3" = "--extraclasspath" ] || exit 1
+shift 4
+for cls in "$@"; do
+  case "$cls" in --*) break ;; esac
+  name="${cls##*.}"
+  [ "$name" = "Missing" ] && continue
+  printf '/*\n * Decompiled with CFR 0.152.\n */\npackage %s;\n\npublic class %s {\n}\n' "${cls%.*}" "$name"
+done
+"#,
+        )?;
+        make_executable(&fake_java)?;
+
+        let old_path = std::env::var("PATH").unwrap_or_default();
+        let new_path = format!("{}:{}", fake_bin.to_string_lossy(), old_path);
+        // SAFETY: Guarded by path_env_lock and restored before returning.
+        unsafe { std::env::set_var("PATH", &new_path) };
+
+        let result: Result<()> = {
+            let cfr = Cfr::new(fake_cfr);
+            let requested = vec![
+                "org.example.B".to_string(),
+                "org.example.Missing".to_string(),
+                "org.example.A".to_string(),
+            ];
+            let out = cfr.decompile_classes(&fake_jar, &requested)?;
+            let names: Vec<&str> = out.classes.iter().map(|c| c.class_name.as_str()).collect();
+            assert_eq!(names, vec!["org.example.B", "org.example.A"]);
+            assert!(out.classes[0].content.contains("public class B"));
+            assert_eq!(out.missing, vec!["org.example.Missing".to_string()]);
+            Ok(())
+        };
+
+        // SAFETY: Guarded by path_env_lock and restored before returning.
+        unsafe { std::env::set_var("PATH", old_path) };
+        let _ = fs::remove_dir_all(base);
+        result
+    }
+
+    #[test]
+    fn parse_cfr_arg_accepts_pairs_and_rejects_reserved_or_malformed() {
+        assert_eq!(
+            parse_cfr_arg("decodelambdas=false").unwrap(),
+            ("decodelambdas".to_string(), "false".to_string())
+        );
+        assert_eq!(
+            parse_cfr_arg("--sugarenums=false").unwrap(),
+            ("sugarenums".to_string(), "false".to_string())
+        );
+        assert!(parse_cfr_arg("decodelambdas").is_err());
+        assert!(parse_cfr_arg("=false").is_err());
+        assert!(parse_cfr_arg("renameillegalidents=").is_err());
+        assert!(parse_cfr_arg("silent=--extraclasspath").is_err());
+        assert!(parse_cfr_arg("extraclasspath=/tmp/other.jar").is_err());
+        assert!(parse_cfr_arg("outputdir=/tmp").is_err());
+    }
+
+    #[test]
+    fn decompile_jar_appends_extra_cfr_args_after_inputs() -> Result<()> {
+        let _guard = path_env_lock().lock().expect("PATH test lock poisoned");
+        let base = temp_dir("decompile_jar_extra_args");
+        let fake_cfr = base.join("cfr.jar");
+        let fake_jar = base.join("demo.jar");
+        let fake_bin = base.join("bin");
+        let fake_java = fake_bin.join("java");
+
+        write_file(&fake_cfr, "stub")?;
+        write_file(&fake_jar, "stub")?;
+        write_file(
+            &fake_java,
+            r#"#!/bin/sh
+set -e
+if [ "$8" = "--decodelambdas" ] && [ "$9" = "false" ]; then
+  echo "public class Demo {}"
+else
+  echo "unexpected args: $*" >&2
+  exit 1
+fi
+"#,
+        )?;
+        make_executable(&fake_java)?;
+
+        let old_path = std::env::var("PATH").unwrap_or_default();
+        let new_path = format!("{}:{}", fake_bin.to_string_lossy(), old_path);
+        // SAFETY: Guarded by path_env_lock and restored before returning.
+        unsafe { std::env::set_var("PATH", &new_path) };
+
+        let result: Result<()> = {
+            let cfr =
+                Cfr::new(fake_cfr).with_extra_args(vec![parse_cfr_arg("decodelambdas=false")?]);
+            assert_eq!(cfr.options(), vec!["decodelambdas=false".to_string()]);
+            let out = cfr.decompile_jar(&fake_jar)?.text;
+            assert!(out.contains("public class Demo"));
+            Ok(())
+        };
+
+        // SAFETY: Guarded by path_env_lock and restored before returning.
+        unsafe { std::env::set_var("PATH", old_path) };
+        let _ = fs::remove_dir_all(base);
+        result
+    }
+
+    #[test]
+    fn decompile_jar_each_reads_outputdir_files_or_falls_back_to_stdout() -> Result<()> {
+        let _guard = path_env_lock().lock().expect("PATH test lock poisoned");
+        let base = temp_dir("decompile_jar_each");
+        let fake_cfr = base.join("cfr.jar");
+        let fake_jar = base.join("demo.jar");
+        let legacy_jar = base.join("legacy.jar");
+        let fake_bin = base.join("bin");
+        let fake_java = fake_bin.join("java");
+
+        write_file(&fake_cfr, "stub")?;
+        write_file(&fake_jar, "stub")?;
+        write_file(&legacy_jar, "stub")?;
+        write_file(
+            &fake_java,
+            r#"#!/bin/sh
+set -e
+if [ "$4" != "--outputdir" ]; then
+  echo "unexpected args: $*" >&2
+  exit 1
+fi
+case "$3" in
+  *legacy.jar)
+    printf '/*\n * Decompiled with CFR 0.152.\n */\npackage a;\nclass Legacy {}\n'
+    ;;
+  *)
+    mkdir -p "$5/a"
+    printf 'package a;\nclass B {}\n' > "$5/a/B.java"
+    printf 'package a;\nclass Inner {}\n' > "$5/a/B\$Inner.java"
+    printf 'summary' > "$5/summary.txt"
+    ;;
+esac
+"#,
+        )?;
+        make_executable(&fake_java)?;
+
+        let old_path = std::env::var("PATH").unwrap_or_default();
+        let new_path = format!("{}:{}", fake_bin.to_string_lossy(), old_path);
+        // SAFETY: Guarded by path_env_lock and restored before returning.
+        unsafe { std::env::set_var("PATH", &new_path) };
+
+        let result: Result<()> = {
+            let cfr = Cfr::new(fake_cfr);
+            let mut names = Vec::new();
+            let count = cfr.decompile_jar_each(&fake_jar, &mut |cls| names.push(cls.class_name))?;
+            assert_eq!(count, 2);
+            assert_eq!(names, vec!["a.B$Inner", "a.B"]);
+
+            let mut legacy = Vec::new();
+            cfr.decompile_jar_each(&legacy_jar, &mut |cls| legacy.push(cls.class_name))?;
+            assert_eq!(legacy, vec!["a.Legacy"]);
+            Ok(())
+        };
+
+        // SAFETY: Guarded by path_env_lock and restored before returning.
+        unsafe { std::env::set_var("PATH", old_path) };
+        let _ = fs::remove_dir_all(base);
+        result
+    }
+
+    #[test]
+    fn version_reads_manifest_or_falls_back_to_version_flag() -> Result<()> {
+        use std::io::Write;
+
+        let _guard = path_env_lock().lock().expect("PATH test lock poisoned");
+        let base = temp_dir("version");
+        let manifest_cfr = base.join("manifest-cfr.jar");
+        let plain_cfr = base.join("plain-cfr.jar");
+        let fake_bin = base.join("bin");
+        let fake_java = fake_bin.join("java");
+        let calls = base.join("calls");
+
+        fs::create_dir_all(&base)?;
+        let mut zip = zip::ZipWriter::new(fs::File::create(&manifest_cfr)?);
+        zip.start_file("META-INF/MANIFEST.MF", zip::write::FileOptions::default())?;
+        zip.write_all(b"Manifest-Version: 1.0\r\nImplementation-Version: 0.151\r\n")?;
+        zip.finish()?;
+        write_file(&plain_cfr, "stub")?;
+        write_file(
+            &fake_java,
+            &format!(
+                r#"#!/bin/sh
+echo call >> "{}"
+if [ "$3" = "--version" ]; then
+  echo "CFR 0.152"
+fi
+"#,
+                calls.to_string_lossy()
+            ),
+        )?;
+        make_executable(&fake_java)?;
+
+        let old_path = std::env::var("PATH").unwrap_or_default();
+        let new_path = format!("{}:{}", fake_bin.to_string_lossy(), old_path);
+        // SAFETY: Guarded by path_env_lock and restored before returning.
+        unsafe { std::env::set_var("PATH", &new_path) };
+
+        let result: Result<()> = {
+            assert_eq!(Cfr::new(manifest_cfr).version().as_deref(), Some("0.151"));
+            assert!(!calls.exists());
+
+            let cfr = Cfr::new(plain_cfr);
+            assert_eq!(cfr.version().as_deref(), Some("0.152"));
+            assert_eq!(cfr.version().as_deref(), Some("0.152"));
+            assert_eq!(fs::read_to_string(&calls)?.lines().count(), 1);
+            assert_eq!(parse_cfr_version("Usage: cfr ..."), None);
+            Ok(())
+        };
+
+        // SAFETY: Guarded by path_env_lock and restored before returning.
+        unsafe { std::env::set_var("PATH", old_path) };
+        let _ = fs::remove_dir_all(base);
+        result
+    }
 }
@@ -0,0 +1,329 @@
+use anyhow::{Context, Result, bail};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, ChildStdout, Stdio};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::time::Duration;
+
+use crate::deadline;
+use crate::decompiler::{
+    WorkDir, java_process_slots, jdk_tool, kill_process_tree, timeout_error, tool_base_command,
+};
+use crate::error::ClassFinderError;
+
+/// Java side of the protocol, run with the JDK's single-file source launcher so
+/// no companion jar has to be built or downloaded.
+const HELPER_SOURCE: &str = include_str!("../resources/ClassFinderCfrServer.java");
+const HELPER_FILE: &str = "ClassFinderCfrServer.java";
+
+/// The first request also pays for JVM startup and compiling the helper source.
+const STARTUP_GRACE: Duration = Duration::from_secs(30);
+
+enum Reply {
+    Ok(Vec<u8>),
+    Err(String),
+}
+
+/// A persistent JVM running CFR, so single-class decompiles skip JVM startup.
+///
+/// Requests are serialized over the helper's stdin/stdout. If the helper cannot
+/// be started or stops responding it is killed and marked dead; callers then
+/// fall back to one-shot CFR runs.
+pub struct CfrServer {
+    cfr_jar: PathBuf,
+    java_opts: Vec<String>,
+    cfr_options: Vec<String>,
+    process: Mutex<Option<ServerProcess>>,
+    dead: AtomicBool,
+}
+
+struct ServerProcess {
+    child: Child,
+    stdin: ChildStdin,
+    replies: Receiver<Reply>,
+    fresh: bool,
+    _work: WorkDir,
+}
+
+impl std::fmt::Debug for CfrServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CfrServer")
+            .field("cfr_jar", &self.cfr_jar)
+            .field("alive", &self.is_alive())
+            .finish()
+    }
+}
+
+impl CfrServer {
+    /// `cfr_options` are `key=value` pairs applied to every request. The helper
+    /// is started lazily on the first request.
+    pub fn new(cfr_jar: PathBuf, java_opts: Vec<String>, cfr_options: Vec<String>) -> Self {
+        Self {
+            cfr_jar,
+            java_opts,
+            cfr_options,
+            process: Mutex::new(None),
+            dead: AtomicBool::new(false),
+        }
+    }
+
+    pub fn is_alive(&self) -> bool {
+        !self.dead.load(Ordering::Relaxed)
+    }
+
+    /// Returns CFR's raw output; decoding is left to the caller.
+    pub fn decompile_class(
+        &self,
+        classpath: &str,
+        class_name: &str,
+        timeout: Duration,
+    ) -> Result<Vec<u8>> {
+        if !self.is_alive() {
+            bail!("CFR server is not running");
+        }
+        if [classpath, class_name]
+            .iter()
+            .any(|s| s.contains(['\t', '\n']))
+        {
+            bail!("Cannot send {class_name} to the CFR server: unsupported characters");
+        }
+
+        let _slot = java_process_slots().acquire();
+        let mut guard = self.process.lock().unwrap_or_else(|e| e.into_inner());
+        if guard.is_none() {
+            match self.spawn() {
+                Ok(process) => *guard = Some(process),
+                Err(err) => {
+                    self.dead.store(true, Ordering::Relaxed);
+                    return Err(err);
+                }
+            }
+        }
+        let Some(process) = guard.as_mut() else {
+            bail!("CFR server is not running");
+        };
+
+        let wait = deadline::cap(if process.fresh {
+            timeout + STARTUP_GRACE
+        } else {
+            timeout
+        });
+        process.fresh = false;
+
+        let reply = writeln!(process.stdin, "{classpath}\t{class_name}")
+            .and_then(|_| process.stdin.flush())
+            .map_err(|err| anyhow::anyhow!("CFR server stopped: {err}"))
+            .and_then(|_| match process.replies.recv_timeout(wait) {
+                Ok(reply) => Ok(reply),
+                Err(RecvTimeoutError::Timeout) => Err(timeout_error(class_name, timeout)),
+                Err(RecvTimeoutError::Disconnected) => {
+                    Err(anyhow::anyhow!("CFR server exited unexpectedly"))
+                }
+            });
+
+        match reply {
+            Ok(Reply::Ok(output)) => Ok(output),
+            Ok(Reply::Err(message)) => Err(ClassFinderError::decompile_failed(
+                class_name,
+                format!("CFR decompilation failed: {}", message.trim()),
+            )
+            .into()),
+            Err(err) => {
+                if let Some(mut process) = guard.take() {
+                    kill_process_tree(&mut process.child);
+                    let _ = process.child.wait();
+                }
+                self.dead.store(true, Ordering::Relaxed);
+                Err(err)
+            }
+        }
+    }
+
+    fn spawn(&self) -> Result<ServerProcess> {
+        let work = WorkDir::create("cfr_server")?;
+        let script = work.path().join(HELPER_FILE);
+        std::fs::write(&script, HELPER_SOURCE)
+            .with_context(|| format!("Failed to write {}", script.display()))?;
+
+        let mut command = tool_base_command("CLASS_FINDER_JAVA", &jdk_tool("java"));
+        command
+            .args(&self.java_opts)
+            .arg("-cp")
+            .arg(&self.cfr_jar)
+            .arg(&script)
+            .args(&self.cfr_options)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null());
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            command.process_group(0);
+        }
+
+        let mut child = command
+            .spawn()
+            .context("Failed to start the CFR server (requires JDK 11+)")?;
+        let stdin = child.stdin.take().context("CFR server stdin unavailable")?;
+        let stdout = child
+            .stdout
+            .take()
+            .context("CFR server stdout unavailable")?;
+        let (tx, replies) = std::sync::mpsc::channel();
+        std::thread::spawn(move || read_replies(stdout, tx));
+
+        Ok(ServerProcess {
+            child,
+            stdin,
+            replies,
+            fresh: true,
+            _work: work,
+        })
+    }
+}
+
+impl Drop for CfrServer {
+    fn drop(&mut self) {
+        let process = self
+            .process
+            .get_mut()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        if let Some(mut process) = process {
+            kill_process_tree(&mut process.child);
+            let _ = process.child.wait();
+        }
+    }
+}
+
+/// Reads `OK <n>` / `ERR <n>` headers and their payloads until the helper's
+/// stdout closes or a reply is malformed; dropping `tx` signals either case.
+fn read_replies(stdout: ChildStdout, tx: Sender<Reply>) {
+    let mut reader = BufReader::new(stdout);
+    let mut header = String::new();
+    loop {
+        header.clear();
+        if reader.read_line(&mut header).unwrap_or(0) == 0 {
+            return;
+        }
+        let Some((status, len)) = header.trim_end().split_once(' ') else {
+            return;
+        };
+        let Ok(len) = len.parse::<usize>() else {
+            return;
+        };
+        let mut body = vec![0u8; len];
+        if reader.read_exact(&mut body).is_err() {
+            return;
+        }
+        let reply = match status {
+            "OK" => Reply::Ok(body),
+            "ERR" => Reply::Err(String::from_utf8_lossy(&body).into_owned()),
+            _ => return,
+        };
+        if tx.send(reply).is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::decompiler::java_env_lock;
+    use std::path::Path;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        std::env::temp_dir().join(format!(
+            "class_finder_cfr_server_test_{}_{}_{}",
+            std::process::id(),
+            nanos,
+            name
+        ))
+    }
+
+    fn write_executable(path: &Path, content: &str) -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, content)?;
+        let mut perms = std::fs::metadata(path)?.permissions();
+        perms.set_mode(0o755);
+        std::fs::set_permissions(path, perms)?;
+        Ok(())
+    }
+
+    #[test]
+    fn server_reuses_one_process_and_dies_cleanly() -> Result<()> {
+        let _guard = java_env_lock().lock().expect("PATH test lock poisoned");
+        let base = temp_dir("reuse");
+        let spawns = base.join("spawns");
+        let fake_bin = base.join("bin");
+        write_executable(
+            &fake_bin.join("java"),
+            &format!(
+                r#"#!/bin/sh
+[ "$1" = "-cp" ] || exit 1
+[ "$4" = "decodelambdas=false" ] || exit 1
+echo spawn >> "{}"
+while IFS= read -r line; do
+  cls=$(printf '%s' "$line" | cut -f2)
+  case "$cls" in
+    a.Bad) body="boom"; printf 'ERR %d\n%s' ${{#body}} "$body" ;;
+    a.Exit) exit 0 ;;
+    *) body="class $cls {{}}"; printf 'OK %d\n%s' ${{#body}} "$body" ;;
+  esac
+done
+"#,
+                spawns.to_string_lossy()
+            ),
+        )?;
+
+        let old_path = std::env::var("PATH").unwrap_or_default();
+        let new_path = format!("{}:{}", fake_bin.to_string_lossy(), old_path);
+        // SAFETY: Guarded by java_env_lock and restored before returning.
+        unsafe { std::env::set_var("PATH", &new_path) };
+
+        let result: Result<()> = {
+            let server = CfrServer::new(
+                base.join("cfr.jar"),
+                Vec::new(),
+                vec!["decodelambdas=false".to_string()],
+            );
+            let jar = base.join("demo.jar").to_string_lossy().to_string();
+            let timeout = Duration::from_secs(10);
+
+            assert_eq!(
+                server.decompile_class(&jar, "a.A", timeout)?,
+                b"class a.A {}"
+            );
+            assert_eq!(
+                server.decompile_class(&jar, "a.B", timeout)?,
+                b"class a.B {}"
+            );
+            let err = server.decompile_class(&jar, "a.Bad", timeout).unwrap_err();
+            assert!(err.to_string().contains("boom"));
+            assert!(server.is_alive());
+            assert_eq!(std::fs::read_to_string(&spawns)?.lines().count(), 1);
+
+            assert!(server.decompile_class(&jar, "a.Exit", timeout).is_err());
+            assert!(!server.is_alive());
+            assert!(server.decompile_class(&jar, "a.A", timeout).is_err());
+            assert_eq!(std::fs::read_to_string(&spawns)?.lines().count(), 1);
+            Ok(())
+        };
+
+        // SAFETY: Guarded by java_env_lock and restored before returning.
+        unsafe { std::env::set_var("PATH", old_path) };
+        let _ = std::fs::remove_dir_all(base);
+        result
+    }
+}
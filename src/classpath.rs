@@ -0,0 +1,430 @@
+//! Extra classpath handed to the decompiler for single-class runs, so it can
+//! resolve supertypes and generic signatures from related jars.
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+use crate::listing::jar_listings;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ClasspathMode {
+    /// Only the jar being decompiled
+    None,
+    /// Also other jars in the same version directory
+    #[default]
+    Artifact,
+    /// Also the artifact's direct dependencies from its embedded pom
+    Deps,
+}
+
+/// Keeps the joined classpath well inside command-line limits (32K on Windows).
+const MAX_CLASSPATH_CHARS: usize = 16 * 1024;
+
+/// Builds the classpath for decompiling classes from `jar_path`: the jar itself
+/// first, then related jars according to `mode`, joined with the platform path
+/// separator. Entries that would push it past [`MAX_CLASSPATH_CHARS`] are dropped.
+pub fn decompile_classpath(jar_path: &Path, mode: ClasspathMode) -> Result<String> {
+    let mut entries = vec![jar_path.to_path_buf()];
+    if mode != ClasspathMode::None {
+        entries.extend(sibling_jars(jar_path));
+    }
+    if mode == ClasspathMode::Deps {
+        entries.extend(dependency_jars(jar_path));
+    }
+
+    let mut joined = String::new();
+    let mut seen = Vec::new();
+    for entry in entries {
+        if seen.contains(&entry) {
+            continue;
+        }
+        let next = std::env::join_paths([entry.as_path()])
+            .ok()
+            .and_then(|p| p.into_string().ok());
+        let Some(next) = next else {
+            continue;
+        };
+        if joined.is_empty() {
+            joined = next;
+        } else if joined.len() + 1 + next.len() <= MAX_CLASSPATH_CHARS {
+            joined.push(if cfg!(windows) { ';' } else { ':' });
+            joined.push_str(&next);
+        } else {
+            break;
+        }
+        seen.push(entry);
+    }
+
+    if joined.is_empty() {
+        anyhow::bail!(
+            "jar path cannot be used on a classpath: {}",
+            jar_path.display()
+        );
+    }
+    Ok(joined)
+}
+
+/// Other class jars in the same directory (classifier variants such as
+/// `-tests.jar`), excluding sources and javadoc jars.
+fn sibling_jars(jar_path: &Path) -> Vec<PathBuf> {
+    let Some(dir) = jar_path.parent() else {
+        return Vec::new();
+    };
+    let Ok(read_dir) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut jars: Vec<PathBuf> = read_dir
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.as_path() != jar_path)
+        .filter(|path| {
+            path.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|name| {
+                    name.ends_with(".jar")
+                        && !name.ends_with("-sources.jar")
+                        && !name.ends_with("-javadoc.jar")
+                })
+        })
+        .collect();
+    jars.sort();
+    jars
+}
+
+/// Direct compile/runtime dependencies declared in the jar's embedded pom that
+/// exist in the same local Maven repository.
+fn dependency_jars(jar_path: &Path) -> Vec<PathBuf> {
+    let Ok(Some(pom)) = read_embedded_pom(jar_path) else {
+        return Vec::new();
+    };
+    let pom = PomInfo::parse(&pom);
+    let Some(repo_root) = maven_repo_root(jar_path, &pom) else {
+        return Vec::new();
+    };
+
+    pom.dependencies
+        .iter()
+        .filter_map(|dep| {
+            let artifact_dir = dep
+                .group_id
+                .split('.')
+                .fold(repo_root.clone(), |dir, part| dir.join(part))
+                .join(&dep.artifact_id);
+            let version = match dep.version.as_deref() {
+                Some(v) => v.to_string(),
+                // Managed by a parent pom we don't read; take what's installed.
+                None => std::fs::read_dir(&artifact_dir)
+                    .ok()?
+                    .filter_map(|e| e.ok()?.file_name().into_string().ok())
+                    .max()?,
+            };
+            let jar = artifact_dir
+                .join(&version)
+                .join(format!("{}-{version}.jar", dep.artifact_id));
+            jar.is_file().then_some(jar)
+        })
+        .collect()
+}
+
+/// Walks up from `<root>/<group path>/<artifact>/<version>/x.jar` to `<root>`.
+fn maven_repo_root(jar_path: &Path, pom: &PomInfo) -> Option<PathBuf> {
+    let version_dir = jar_path.parent()?;
+    let artifact_dir = version_dir.parent()?;
+    if artifact_dir.file_name()?.to_str()? != pom.artifact_id.as_deref()? {
+        return None;
+    }
+    let mut dir = artifact_dir.parent()?;
+    for part in pom.group_id.as_deref()?.split('.').rev() {
+        if dir.file_name()?.to_str()? != part {
+            return None;
+        }
+        dir = dir.parent()?;
+    }
+    Some(dir.to_path_buf())
+}
+
+fn read_embedded_pom(jar_path: &Path) -> Result<Option<String>> {
+    let artifact_hint = jar_path
+        .parent()
+        .and_then(|p| p.parent())
+        .and_then(|p| p.file_name())
+        .and_then(|n| n.to_str())
+        .map(|a| format!("/{a}/pom.xml"));
+    // Most jars have no embedded pom; those are never opened past the
+    // (shared) listing.
+    let mut candidates: Vec<String> = jar_listings()
+        .get(jar_path)?
+        .names()
+        .filter(|name| name.starts_with("META-INF/maven/") && name.ends_with("/pom.xml"))
+        .map(str::to_string)
+        .collect();
+    candidates.sort_by_key(|name| {
+        !artifact_hint
+            .as_deref()
+            .is_some_and(|hint| name.ends_with(hint))
+    });
+    let Some(name) = candidates.into_iter().next() else {
+        return Ok(None);
+    };
+
+    let file = File::open(jar_path)
+        .with_context(|| format!("Failed to open jar: {}", jar_path.display()))?;
+    let mut archive = ZipArchive::new(file)
+        .with_context(|| format!("Failed to parse zip(jar): {}", jar_path.display()))?;
+    let mut pom = String::new();
+    archive.by_name(&name)?.read_to_string(&mut pom)?;
+    Ok(Some(pom))
+}
+
+#[derive(Debug, Default)]
+struct PomInfo {
+    group_id: Option<String>,
+    artifact_id: Option<String>,
+    dependencies: Vec<PomDependency>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct PomDependency {
+    group_id: String,
+    artifact_id: String,
+    version: Option<String>,
+}
+
+impl PomInfo {
+    /// Small tag-level reader for the handful of pom fields needed here; not a
+    /// general XML parser.
+    fn parse(pom: &str) -> Self {
+        let pom = strip_between(pom, "<!--", "-->");
+        let parent = element(&pom, "parent").unwrap_or_default().to_string();
+        let mut project = strip_element(&pom, "parent");
+        for section in ["dependencyManagement", "build", "profiles", "reporting"] {
+            project = strip_element(&project, section);
+        }
+        let dependencies_block = element(&project, "dependencies")
+            .unwrap_or_default()
+            .to_string();
+        let head = strip_element(&project, "dependencies");
+
+        let group_id = element(&head, "groupId").or_else(|| element(&parent, "groupId"));
+        let artifact_id = element(&head, "artifactId");
+        let version = element(&head, "version").or_else(|| element(&parent, "version"));
+
+        let mut properties: HashMap<String, String> = HashMap::new();
+        if let Some(block) = element(&head, "properties") {
+            let mut rest = block;
+            while let Some(open) = rest.find('<') {
+                let after = &rest[open + 1..];
+                let Some(close) = after.find('>') else { break };
+                let name = &after[..close];
+                let body = &after[close + 1..];
+                if name.is_empty() || name.ends_with('/') {
+                    rest = body;
+                    continue;
+                }
+                let end_tag = format!("</{name}>");
+                let Some(end) = body.find(&end_tag) else {
+                    break;
+                };
+                properties.insert(name.to_string(), body[..end].trim().to_string());
+                rest = &body[end + end_tag.len()..];
+            }
+        }
+        if let Some(v) = version {
+            properties.insert("project.version".to_string(), v.to_string());
+        }
+        if let Some(g) = group_id {
+            properties.insert("project.groupId".to_string(), g.to_string());
+        }
+        let resolve = |value: &str| -> Option<String> {
+            match value.strip_prefix("${").and_then(|v| v.strip_suffix('}')) {
+                Some(key) => properties.get(key).cloned(),
+                None => Some(value.to_string()),
+            }
+        };
+
+        let mut dependencies = Vec::new();
+        let mut rest = dependencies_block.as_str();
+        while let Some(dep) = element(rest, "dependency") {
+            let end = rest.find("</dependency>").map_or(rest.len(), |i| i + 13);
+            rest = &rest[end..];
+
+            let scope = element(dep, "scope").unwrap_or("compile");
+            let kind = element(dep, "type").unwrap_or("jar");
+            if matches!(scope, "test" | "system" | "import") || kind != "jar" {
+                continue;
+            }
+            let (Some(group), Some(artifact)) = (
+                element(dep, "groupId").and_then(resolve),
+                element(dep, "artifactId").and_then(resolve),
+            ) else {
+                continue;
+            };
+            dependencies.push(PomDependency {
+                group_id: group,
+                artifact_id: artifact,
+                version: element(dep, "version").and_then(resolve),
+            });
+        }
+
+        Self {
+            group_id: group_id.and_then(resolve),
+            artifact_id: artifact_id.map(str::to_string),
+            dependencies,
+        }
+    }
+}
+
+/// Trimmed text of the first `<tag>...</tag>` in `xml`.
+fn element<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let open = format!("<{tag}>");
+    let close = format!("</{tag}>");
+    let start = xml.find(&open)? + open.len();
+    let end = xml[start..].find(&close)? + start;
+    Some(xml[start..end].trim())
+}
+
+fn strip_element(xml: &str, tag: &str) -> String {
+    strip_between(xml, &format!("<{tag}>"), &format!("</{tag}>"))
+}
+
+fn strip_between(text: &str, open: &str, close: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(open) {
+        out.push_str(&rest[..start]);
+        match rest[start..].find(close) {
+            Some(end) => rest = &rest[start + end + close.len()..],
+            None => {
+                rest = "";
+                break;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use zip::write::FileOptions;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        std::env::temp_dir().join(format!(
+            "class_finder_classpath_test_{}_{}_{}",
+            std::process::id(),
+            nanos,
+            name
+        ))
+    }
+
+    fn write_jar(path: &Path, entries: &[(&str, &[u8])]) -> Result<()> {
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        let mut zip = zip::ZipWriter::new(File::create(path)?);
+        for (name, bytes) in entries {
+            zip.start_file(*name, FileOptions::default())?;
+            zip.write_all(bytes)?;
+        }
+        zip.finish()?;
+        Ok(())
+    }
+
+    const POM: &str = r#"<project>
+  <parent>
+    <groupId>org.example</groupId>
+    <artifactId>parent</artifactId>
+    <version>9</version>
+  </parent>
+  <artifactId>app</artifactId>
+  <version>1.0</version>
+  <properties>
+    <lib.version>2.1</lib.version>
+  </properties>
+  <dependencyManagement>
+    <dependencies>
+      <dependency><groupId>org.managed</groupId><artifactId>bom</artifactId><version>1</version></dependency>
+    </dependencies>
+  </dependencyManagement>
+  <dependencies>
+    <dependency><groupId>org.lib</groupId><artifactId>core</artifactId><version>${lib.version}</version></dependency>
+    <dependency><groupId>${project.groupId}</groupId><artifactId>api</artifactId></dependency>
+    <!-- <dependency><groupId>org.commented</groupId><artifactId>out</artifactId></dependency> -->
+    <dependency><groupId>junit</groupId><artifactId>junit</artifactId><version>4</version><scope>test</scope></dependency>
+  </dependencies>
+</project>"#;
+
+    #[test]
+    fn pom_info_reads_direct_dependencies() {
+        let pom = PomInfo::parse(POM);
+        assert_eq!(pom.group_id.as_deref(), Some("org.example"));
+        assert_eq!(pom.artifact_id.as_deref(), Some("app"));
+        assert_eq!(
+            pom.dependencies,
+            vec![
+                PomDependency {
+                    group_id: "org.lib".to_string(),
+                    artifact_id: "core".to_string(),
+                    version: Some("2.1".to_string()),
+                },
+                PomDependency {
+                    group_id: "org.example".to_string(),
+                    artifact_id: "api".to_string(),
+                    version: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn decompile_classpath_adds_siblings_and_dependencies() -> Result<()> {
+        let repo = temp_dir("repo");
+        let app = repo.join("org/example/app/1.0/app-1.0.jar");
+        write_jar(
+            &app,
+            &[("META-INF/maven/org.example/app/pom.xml", POM.as_bytes())],
+        )?;
+        write_jar(&repo.join("org/example/app/1.0/app-1.0-tests.jar"), &[])?;
+        write_jar(&repo.join("org/example/app/1.0/app-1.0-sources.jar"), &[])?;
+        let core = repo.join("org/lib/core/2.1/core-2.1.jar");
+        write_jar(&core, &[])?;
+        let api = repo.join("org/example/api/3.0/api-3.0.jar");
+        write_jar(&api, &[])?;
+
+        let sep = if cfg!(windows) { ";" } else { ":" };
+        let tests = repo.join("org/example/app/1.0/app-1.0-tests.jar");
+        let join = |paths: &[&Path]| {
+            paths
+                .iter()
+                .map(|p| p.to_string_lossy().to_string())
+                .collect::<Vec<_>>()
+                .join(sep)
+        };
+
+        assert_eq!(
+            decompile_classpath(&app, ClasspathMode::None)?,
+            join(&[&app])
+        );
+        assert_eq!(
+            decompile_classpath(&app, ClasspathMode::Artifact)?,
+            join(&[&app, &tests])
+        );
+        assert_eq!(
+            decompile_classpath(&app, ClasspathMode::Deps)?,
+            join(&[&app, &tests, &core, &api])
+        );
+
+        let _ = std::fs::remove_dir_all(repo);
+        Ok(())
+    }
+}
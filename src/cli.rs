@@ -1,5 +1,16 @@
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::{ArgAction, Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::classpath::ClasspathMode;
+use crate::config::FileConfig;
+use crate::decompiler::DecompilerKind;
+use crate::encoding::FallbackEncoding;
+use crate::export::InnerClassLayout;
+use crate::highlight::ColorChoice;
+use crate::javap::JavapView;
+use crate::logging::LogFormat;
+use crate::warmup::{WarmupMode, WarmupOrder};
 
 #[derive(Debug, Clone, Parser)]
 #[command(name = "class-finder")]
@@ -18,12 +29,88 @@ pub struct Cli {
 
     #[arg(long, value_name = "FILE")]
     pub db: Option<PathBuf>,
+
+    #[arg(long = "cfr-arg", value_name = "KEY=VALUE")]
+    pub cfr_args: Vec<String>,
+
+    #[arg(long, value_enum)]
+    pub decompiler: Option<DecompilerKind>,
+
+    #[arg(long, value_name = "FILE")]
+    pub vineflower: Option<PathBuf>,
+
+    #[arg(long)]
+    pub cfr_server: bool,
+
+    #[arg(long)]
+    pub no_download: bool,
+
+    #[arg(long, value_enum, default_value_t = ClasspathMode::Artifact)]
+    pub classpath: ClasspathMode,
+
+    #[arg(long, value_enum, default_value_t = FallbackEncoding::Latin1)]
+    pub fallback_encoding: FallbackEncoding,
+
+    #[arg(long)]
+    pub keep_warnings: bool,
+
+    #[arg(long)]
+    pub cache_package_info: bool,
+
+    #[arg(long)]
+    pub cache_structures: bool,
+
+    #[arg(long, value_name = "SECS")]
+    pub class_timeout: Option<u64>,
+
+    #[arg(long, value_name = "SECS")]
+    pub jar_timeout: Option<u64>,
+
+    #[arg(long, value_name = "OPTS", allow_hyphen_values = true)]
+    pub java_opts: Option<String>,
+
+    #[arg(long, value_name = "N")]
+    pub jobs: Option<usize>,
+
+    #[arg(long, value_name = "N")]
+    pub buffer_batch_size: Option<usize>,
+
+    #[arg(long, value_name = "MS")]
+    pub buffer_flush_ms: Option<u64>,
+
+    #[arg(long, value_name = "N")]
+    pub warmup_threshold: Option<u32>,
+
+    #[arg(short = 'v', long, action = ArgAction::Count)]
+    pub verbose: u8,
+
+    #[arg(short = 'q', long, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    #[arg(long, value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
+    #[arg(long)]
+    pub progress: bool,
+
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+
+    #[arg(long, value_name = "N")]
+    pub compat: Option<u32>,
+
+    #[arg(long, value_name = "SECS")]
+    pub timeout: Option<u64>,
+
+    #[arg(skip)]
+    pub file_config: FileConfig,
 }
 
 #[derive(Debug, Clone, Subcommand)]
 pub enum Commands {
     Find {
-        class_name: String,
+        #[arg(required_unless_present = "with_method")]
+        class_name: Option<String>,
 
         #[arg(short = 'f', long, value_enum, default_value_t = OutputFormat::Json)]
         format: OutputFormat,
@@ -31,15 +118,90 @@ pub enum Commands {
         #[arg(long)]
         code_only: bool,
 
+        #[arg(long, conflicts_with = "code_only")]
+        constants: bool,
+
+        #[arg(long, conflicts_with_all = ["count", "code_only", "constants", "format", "output"])]
+        exists: bool,
+
+        #[arg(long, conflicts_with_all = ["code_only", "constants", "format"])]
+        count: bool,
+
+        #[arg(long, value_name = "GROUP")]
+        exclude_group: Vec<String>,
+
+        #[arg(long, value_name = "ARTIFACT")]
+        exclude_artifact: Vec<String>,
+
+        #[arg(long, value_name = "GLOB")]
+        exclude_jar_glob: Vec<String>,
+
         #[arg(short = 'v', long, value_name = "VER")]
         version: Option<String>,
 
+        #[arg(long)]
+        latest: bool,
+
+        #[arg(long, value_name = "N")]
+        max_versions: Option<usize>,
+
+        #[arg(long, value_name = "N")]
+        max_matches: Option<usize>,
+
+        #[arg(long)]
+        fresh_scan: bool,
+
+        #[arg(long)]
+        verify_jars: bool,
+
         #[arg(short = 'o', long, value_name = "FILE")]
         output: Option<PathBuf>,
+
+        #[arg(long)]
+        no_fallback: bool,
+
+        #[arg(long, value_enum, default_value_t = JavapView::Bytecode)]
+        fallback_view: JavapView,
+
+        #[arg(long, value_name = "MS")]
+        background_warmup_ms: Option<u64>,
+
+        #[arg(long)]
+        with_javadoc: bool,
+
+        #[arg(long, value_name = "METHOD", conflicts_with = "class_name")]
+        with_method: Option<String>,
+
+        #[arg(long, value_name = "GROUP", requires = "with_method")]
+        group: Option<String>,
+    },
+    Diff {
+        class_name: String,
+
+        #[arg(value_name = "FROM_VER")]
+        from: String,
+
+        #[arg(value_name = "TO_VER")]
+        to: String,
+
+        #[arg(short = 'f', long, value_enum, default_value_t = DiffFormat::Json)]
+        format: DiffFormat,
     },
     Load {
         jar_path: PathBuf,
     },
+    ExportSources {
+        jar_path: PathBuf,
+
+        #[arg(long, value_name = "DIR")]
+        out: PathBuf,
+
+        #[arg(long)]
+        complete: bool,
+
+        #[arg(long, value_enum, default_value_t = InnerClassLayout::Merge)]
+        inner: InnerClassLayout,
+    },
     Warmup {
         #[arg(value_name = "JAR")]
         jar_path: Option<PathBuf>,
@@ -47,27 +209,179 @@ pub enum Commands {
         #[arg(long)]
         hot: bool,
 
+        #[arg(long)]
+        group_level: bool,
+
         #[arg(long, value_name = "GROUP")]
         group: Option<String>,
 
+        #[arg(long, value_name = "FILE")]
+        from_file: Option<PathBuf>,
+
         #[arg(long, value_name = "N", default_value_t = 20)]
         top: usize,
 
         #[arg(long, value_name = "N")]
         limit: Option<usize>,
+
+        #[arg(long)]
+        resume: bool,
+
+        #[arg(long, value_enum, default_value_t = WarmupMode::AllClasses)]
+        mode: WarmupMode,
+
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        max_duration: Option<Duration>,
+
+        #[arg(long, value_name = "N")]
+        max_classes: Option<usize>,
+
+        #[arg(long, value_name = "GROUP")]
+        exclude_group: Vec<String>,
+
+        #[arg(long, value_name = "ARTIFACT")]
+        exclude_artifact: Vec<String>,
+
+        #[arg(long, value_name = "GLOB")]
+        exclude_jar_glob: Vec<String>,
+
+        #[arg(long, value_name = "N", default_value_t = 1)]
+        jobs: usize,
+
+        #[arg(long, value_enum)]
+        order: Option<WarmupOrder>,
+
+        #[arg(long, value_enum, default_value_t = ProgressFormat::Text)]
+        format: ProgressFormat,
     },
     Index {
         #[arg(long, value_name = "DIR")]
         path: Option<PathBuf>,
+
+        #[arg(long, value_name = "NAME", conflicts_with = "path")]
+        repo: Option<String>,
+
+        #[arg(long)]
+        incremental: bool,
+
+        #[arg(long)]
+        purge_removed: bool,
+    },
+    Watch {
+        #[arg(long, value_name = "DIR")]
+        path: Option<PathBuf>,
+
+        #[arg(long, value_name = "NAME", conflicts_with = "path")]
+        repo: Option<String>,
+
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration, default_value = "5m")]
+        interval: Duration,
+
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration, default_value = "2s")]
+        debounce: Duration,
+
+        #[arg(long)]
+        purge_removed: bool,
     },
     Stats,
-    Clear,
+    Snapshot,
+    Doctor,
+    Hot {
+        #[command(subcommand)]
+        action: Option<HotAction>,
+
+        #[arg(long)]
+        classes: bool,
+
+        #[arg(long, value_name = "JAR")]
+        jar: Option<PathBuf>,
+
+        #[arg(long)]
+        by_group: bool,
+
+        #[arg(long)]
+        by_artifact: bool,
+
+        #[arg(long, value_name = "N", default_value_t = 20)]
+        top: usize,
+    },
+    Clear {
+        #[arg(long, conflicts_with_all = ["sources", "registry", "hotspot", "loaded_flags", "mtimes"])]
+        all: bool,
+
+        #[arg(long)]
+        sources: bool,
+
+        #[arg(long)]
+        registry: bool,
+
+        #[arg(long)]
+        hotspot: bool,
+
+        #[arg(long)]
+        loaded_flags: bool,
+
+        #[arg(long)]
+        mtimes: bool,
+    },
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum ConfigAction {
+    Show,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum HotAction {
+    Reset {
+        #[arg(long, value_name = "JAR")]
+        jar: Option<PathBuf>,
+    },
+    Trim {
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        older_than: Duration,
+    },
+}
+
+/// `90`, `90s`, `10m`, `2h` or `30d`; a bare number is seconds.
+pub fn parse_duration(raw: &str) -> Result<Duration, String> {
+    let raw = raw.trim();
+    let (digits, unit_secs) = match raw.char_indices().last() {
+        Some((idx, 's')) => (&raw[..idx], 1),
+        Some((idx, 'm')) => (&raw[..idx], 60),
+        Some((idx, 'h')) => (&raw[..idx], 3600),
+        Some((idx, 'd')) => (&raw[..idx], 86400),
+        _ => (raw, 1),
+    };
+    let value: u64 = digits
+        .parse()
+        .map_err(|_| format!("invalid duration {raw:?}, expected e.g. 90s, 10m, 2h or 30d"))?;
+    Ok(Duration::from_secs(value.saturating_mul(unit_secs)))
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum ProgressFormat {
+    Text,
+    Jsonl,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum DiffFormat {
+    Json,
+    Text,
 }
 
-#[derive(Debug, Copy, Clone, ValueEnum)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
 pub enum OutputFormat {
     Json,
     Text,
     Code,
     Structure,
+    Constants,
+    Stub,
+    Paths,
 }
@@ -1,51 +1,856 @@
 use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::env;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
+use crate::buffer::BufferConfig;
+use crate::cache::DbLayout;
+use crate::cfr::{Cfr, parse_cfr_arg};
 use crate::cli::Cli;
-use crate::scan::default_m2_repository;
+use crate::decompiler::{
+    Decompiler, DecompilerConfig, DecompilerKind, ToolCandidate, default_java_jobs,
+    java_candidates_with, split_java_opts,
+};
+use crate::download::download_verified;
+use crate::hotspot::DEFAULT_WARMUP_THRESHOLD;
+use crate::listing::DEFAULT_LISTING_CACHE_SIZE;
+use crate::postprocess::{DEFAULT_POSTPROCESS_TIMEOUT, Postprocessor};
+use crate::repository::{Repository, merge_repositories};
+use crate::scan::{JarExclusions, default_m2_repository};
+use crate::scan_cache::DEFAULT_SCAN_CACHE_MAX_AGE;
+use crate::snapshot::SnapshotPolicy;
+use crate::vineflower::Vineflower;
 
+const CFR_URL: &str = "https://github.com/leibnitz27/cfr/releases/download/0.152/cfr-0.152.jar";
+const VINEFLOWER_URL: &str =
+    "https://github.com/Vineflower/vineflower/releases/download/1.11.1/vineflower-1.11.1.jar";
+// SHA-256 of the release jars above, checked before a download is installed.
+// Update together with the URLs.
+// TODO: replace the zero placeholders with the digests of the release jars.
+const CFR_SHA256: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+const VINEFLOWER_SHA256: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Looks up an environment variable; tests pass a fixed map instead.
+type EnvLookup<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+fn process_env(name: &str) -> Option<String> {
+    env::var(name).ok()
+}
+
+/// Settings from the TOML config file. Every key is optional and named after
+/// the global flag it stands in for; a flag or environment variable given
+/// for the same setting wins over it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FileConfig {
+    /// The file these settings were read from, if any.
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+    pub m2: Option<PathBuf>,
+    pub db: Option<PathBuf>,
+    pub cfr: Option<PathBuf>,
+    /// Mirror of the CFR release jar, for networks that cannot reach GitHub.
+    pub cfr_download_url: Option<String>,
+    /// SHA-256 the downloaded CFR jar must have, or `skip`.
+    pub cfr_sha256: Option<String>,
+    /// Proxy for https downloads when `HTTPS_PROXY` is not set.
+    pub https_proxy: Option<String>,
+    /// Fail instead of downloading a missing decompiler jar.
+    pub no_download: bool,
+    pub vineflower: Option<PathBuf>,
+    pub decompiler: Option<DecompilerKind>,
+    pub java_opts: Option<String>,
+    pub jobs: Option<usize>,
+    pub class_timeout: Option<u64>,
+    pub jar_timeout: Option<u64>,
+    /// Formatter command each decompiled class is piped through before
+    /// caching, e.g. `["google-java-format", "-"]`.
+    pub postprocess: Vec<String>,
+    /// Seconds one `postprocess` run may take.
+    pub postprocess_timeout: Option<u64>,
+    pub buffer_batch_size: Option<usize>,
+    pub buffer_flush_ms: Option<u64>,
+    pub warmup_threshold: Option<u32>,
+    /// Seconds the jar list of a scanned root is reused; 0 walks every time.
+    pub scan_cache_max_age: Option<u64>,
+    /// Jars whose entry listings are kept in memory; 0 keeps none.
+    pub listing_cache_size: Option<usize>,
+    /// Default `--exclude-*` filters of bulk warmups.
+    pub exclude_group: Vec<String>,
+    pub exclude_artifact: Vec<String>,
+    pub exclude_jar_glob: Vec<String>,
+    /// `[[repository]]` tables, searched together with `m2`.
+    #[serde(rename = "repository")]
+    pub repositories: Vec<Repository>,
+    /// When `watch` republishes `<db>.snapshot`.
+    pub snapshot: SnapshotPolicy,
+}
+
+impl FileConfig {
+    pub fn parse(raw: &str) -> Result<Self> {
+        Ok(toml::from_str(raw)?)
+    }
+}
+
+/// `CLASS_FINDER_CONFIG`, or `~/.config/class-finder/config.toml`.
+pub fn config_file_path() -> Option<PathBuf> {
+    config_file_path_with(&process_env)
+}
+
+fn config_file_path_with(env: EnvLookup) -> Option<PathBuf> {
+    if let Some(p) = env("CLASS_FINDER_CONFIG") {
+        return Some(PathBuf::from(p));
+    }
+    dirs::home_dir().map(|home| {
+        home.join(".config")
+            .join("class-finder")
+            .join("config.toml")
+    })
+}
+
+/// Reads the config file. A missing default file gives empty settings; a
+/// missing `CLASS_FINDER_CONFIG` file or one that does not parse is an error.
+pub fn load_file_config() -> Result<FileConfig> {
+    let explicit = env::var_os("CLASS_FINDER_CONFIG").is_some();
+    let Some(path) = config_file_path() else {
+        return Ok(FileConfig::default());
+    };
+    if !explicit && !path.exists() {
+        return Ok(FileConfig::default());
+    }
+    let raw = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+    let mut config = FileConfig::parse(&raw)
+        .with_context(|| format!("Invalid config file: {}", path.display()))?;
+    config.path = Some(path);
+    Ok(config)
+}
+
+/// Where an effective setting came from, highest precedence first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigSource {
+    Flag,
+    Env,
+    File,
+    Default,
+}
+
+impl ConfigSource {
+    const PRECEDENCE: [Self; 4] = [Self::Flag, Self::Env, Self::File, Self::Default];
+
+    fn of(flag: bool, env: bool, file: bool) -> Self {
+        if flag {
+            Self::Flag
+        } else if env {
+            Self::Env
+        } else if file {
+            Self::File
+        } else {
+            Self::Default
+        }
+    }
+}
+
+/// The configuration a command runs with, for `config show`.
+#[derive(Debug, Serialize)]
+pub struct EffectiveConfig {
+    pub config_file: Option<PathBuf>,
+    /// Order in which the layers in `sources` override each other.
+    pub precedence: [ConfigSource; 4],
+    /// The layer each setting below was taken from.
+    pub sources: BTreeMap<&'static str, ConfigSource>,
+    pub m2: PathBuf,
+    pub db: PathBuf,
+    /// Holds the default database and the downloaded decompiler jars.
+    pub home: PathBuf,
+    /// `None` until the jar is installed; it is downloaded on first use.
+    pub cfr: Option<PathBuf>,
+    pub cfr_download_url: String,
+    pub no_download: bool,
+    pub vineflower: Option<PathBuf>,
+    pub decompiler: DecompilerKind,
+    pub java_opts: Vec<String>,
+    pub jobs: usize,
+    pub class_timeout_secs: u64,
+    pub jar_timeout_secs: u64,
+    pub postprocess: Vec<String>,
+    pub postprocess_timeout_secs: u64,
+    pub write_buffer: BufferConfig,
+    pub warmup_threshold: u32,
+    pub scan_cache_max_age_secs: u64,
+    pub listing_cache_size: usize,
+    pub exclude_group: Vec<String>,
+    pub exclude_artifact: Vec<String>,
+    pub exclude_jar_glob: Vec<String>,
+    /// In search order.
+    pub repositories: Vec<Repository>,
+    pub snapshot: SnapshotPolicy,
+}
+
+pub fn resolve_effective_config(cli: &Cli) -> Result<EffectiveConfig> {
+    resolve_effective_config_with(cli, &process_env)
+}
+
+fn resolve_effective_config_with(cli: &Cli, env: EnvLookup) -> Result<EffectiveConfig> {
+    let decompiler_config = resolve_decompiler_config_with(cli, env);
+    let file = &cli.file_config;
+    let (m2, m2_source) = m2_repo_with(cli, env)?;
+    let (db, db_source) = db_path_with(cli, env)?;
+    let set = |name: &str| env(name).is_some();
+    let sources = BTreeMap::from([
+        ("m2", m2_source),
+        ("db", db_source),
+        (
+            "home",
+            ConfigSource::of(false, set("CLASS_FINDER_HOME"), false),
+        ),
+        (
+            "cfr",
+            ConfigSource::of(cli.cfr.is_some(), set("CFR_JAR"), file.cfr.is_some()),
+        ),
+        (
+            "cfr_download_url",
+            ConfigSource::of(
+                false,
+                set("CLASS_FINDER_CFR_DOWNLOAD_URL"),
+                file.cfr_download_url.is_some(),
+            ),
+        ),
+        (
+            "no_download",
+            ConfigSource::of(cli.no_download, false, file.no_download),
+        ),
+        (
+            "vineflower",
+            ConfigSource::of(
+                cli.vineflower.is_some(),
+                set("VINEFLOWER_JAR"),
+                file.vineflower.is_some(),
+            ),
+        ),
+        (
+            "decompiler",
+            ConfigSource::of(cli.decompiler.is_some(), false, file.decompiler.is_some()),
+        ),
+        (
+            "java_opts",
+            ConfigSource::of(
+                cli.java_opts.is_some(),
+                set("CLASS_FINDER_JAVA_OPTS"),
+                file.java_opts.is_some(),
+            ),
+        ),
+        (
+            "jobs",
+            ConfigSource::of(cli.jobs.is_some(), false, file.jobs.is_some()),
+        ),
+        (
+            "class_timeout_secs",
+            ConfigSource::of(
+                cli.class_timeout.is_some(),
+                false,
+                file.class_timeout.is_some(),
+            ),
+        ),
+        (
+            "jar_timeout_secs",
+            ConfigSource::of(cli.jar_timeout.is_some(), false, file.jar_timeout.is_some()),
+        ),
+        (
+            "postprocess",
+            ConfigSource::of(false, false, !file.postprocess.is_empty()),
+        ),
+        (
+            "postprocess_timeout_secs",
+            ConfigSource::of(false, false, file.postprocess_timeout.is_some()),
+        ),
+        (
+            "write_buffer.batch_size",
+            ConfigSource::of(
+                cli.buffer_batch_size.is_some(),
+                set("CLASS_FINDER_BUFFER_BATCH_SIZE"),
+                file.buffer_batch_size.is_some(),
+            ),
+        ),
+        (
+            "write_buffer.flush_interval_ms",
+            ConfigSource::of(
+                cli.buffer_flush_ms.is_some(),
+                set("CLASS_FINDER_BUFFER_FLUSH_MS"),
+                file.buffer_flush_ms.is_some(),
+            ),
+        ),
+        (
+            "warmup_threshold",
+            ConfigSource::of(
+                cli.warmup_threshold.is_some(),
+                set("CLASS_FINDER_WARMUP_THRESHOLD"),
+                file.warmup_threshold.is_some(),
+            ),
+        ),
+        (
+            "scan_cache_max_age_secs",
+            ConfigSource::of(false, false, file.scan_cache_max_age.is_some()),
+        ),
+        (
+            "listing_cache_size",
+            ConfigSource::of(false, false, file.listing_cache_size.is_some()),
+        ),
+    ]);
+    Ok(EffectiveConfig {
+        config_file: file.path.clone(),
+        precedence: ConfigSource::PRECEDENCE,
+        sources,
+        db,
+        home: class_finder_home_with(env)?,
+        cfr: existing_cfr_path_with(cli, env)?,
+        cfr_download_url: cfr_download_url_with(cli, env),
+        no_download: cli.no_download || file.no_download,
+        vineflower: existing_vineflower_path_with(cli, env)?,
+        decompiler: resolve_decompiler_kind(cli),
+        java_opts: decompiler_config.java_opts,
+        jobs: resolve_java_jobs(cli),
+        class_timeout_secs: decompiler_config.class_timeout.as_secs(),
+        jar_timeout_secs: decompiler_config.jar_timeout.as_secs(),
+        postprocess: file.postprocess.clone(),
+        postprocess_timeout_secs: file
+            .postprocess_timeout
+            .map_or(DEFAULT_POSTPROCESS_TIMEOUT, Duration::from_secs)
+            .as_secs(),
+        write_buffer: resolve_buffer_config_with(cli, env)?,
+        warmup_threshold: resolve_warmup_threshold_with(cli, env)?,
+        scan_cache_max_age_secs: resolve_scan_cache_max_age(cli).as_secs(),
+        listing_cache_size: resolve_listing_cache_size(cli),
+        exclude_group: file.exclude_group.clone(),
+        exclude_artifact: file.exclude_artifact.clone(),
+        exclude_jar_glob: file.exclude_jar_glob.clone(),
+        repositories: merge_repositories(&file.repositories, Repository::local(m2.clone()))?,
+        snapshot: file.snapshot,
+        m2,
+    })
+}
+
+/// `--m2`, then `CLASS_FINDER_M2`, then the config file, then
+/// `~/.m2/repository`. A `CLASS_FINDER_M2` that does not exist is an error
+/// rather than an empty search.
 pub fn resolve_m2_repo(cli: &Cli) -> Result<PathBuf> {
+    let (path, source) = m2_repo_with(cli, &process_env)?;
+    if source == ConfigSource::Env && !path.exists() {
+        return Err(missing_env_path("CLASS_FINDER_M2", &path));
+    }
+    Ok(path)
+}
+
+/// The `[[repository]]` tables of the config file plus the `local` one made
+/// from [`resolve_m2_repo`], in search order.
+pub fn resolve_repositories(cli: &Cli) -> Result<Vec<Repository>> {
+    merge_repositories(
+        &cli.file_config.repositories,
+        Repository::local(resolve_m2_repo(cli)?),
+    )
+}
+
+fn m2_repo_with(cli: &Cli, env: EnvLookup) -> Result<(PathBuf, ConfigSource)> {
     if let Some(p) = cli.m2.clone() {
-        return Ok(p);
+        return Ok((p, ConfigSource::Flag));
     }
-    default_m2_repository()
+    if let Some(p) = env("CLASS_FINDER_M2") {
+        return Ok((PathBuf::from(p), ConfigSource::Env));
+    }
+    if let Some(p) = cli.file_config.m2.clone() {
+        return Ok((p, ConfigSource::File));
+    }
+    Ok((default_m2_repository()?, ConfigSource::Default))
 }
 
+/// `--db`, then `CLASS_FINDER_DB`, then the config file, then `db.lmdb`
+/// under [`class_finder_home`]. Commands that write create it when missing.
 pub fn resolve_db_path(cli: &Cli) -> Result<PathBuf> {
+    Ok(db_path_with(cli, &process_env)?.0)
+}
+
+/// [`resolve_db_path`] for commands that only read the cache: a database
+/// named by `CLASS_FINDER_DB` or `CLASS_FINDER_HOME` must already exist.
+pub fn resolve_existing_db_path(cli: &Cli) -> Result<PathBuf> {
+    let (path, source) = db_path_with(cli, &process_env)?;
+    if !path.exists() {
+        if source == ConfigSource::Env {
+            return Err(missing_env_path("CLASS_FINDER_DB", &path));
+        }
+        if source == ConfigSource::Default && process_env("CLASS_FINDER_HOME").is_some() {
+            return Err(missing_env_path("CLASS_FINDER_HOME", &path));
+        }
+    }
+    Ok(path)
+}
+
+fn db_path_with(cli: &Cli, env: EnvLookup) -> Result<(PathBuf, ConfigSource)> {
     if let Some(p) = cli.db.clone() {
-        return Ok(p);
+        return Ok((p, ConfigSource::Flag));
+    }
+    if let Some(p) = env("CLASS_FINDER_DB") {
+        return Ok((PathBuf::from(p), ConfigSource::Env));
+    }
+    if let Some(p) = cli.file_config.db.clone() {
+        return Ok((p, ConfigSource::File));
     }
+    Ok((
+        class_finder_home_with(env)?.join("db.lmdb"),
+        ConfigSource::Default,
+    ))
+}
 
-    Ok(class_finder_home()?.join("db.lmdb"))
+fn missing_env_path(var: &str, path: &Path) -> anyhow::Error {
+    anyhow::anyhow!(
+        "{var} points to {}, which does not exist; unset it or fix the path",
+        path.display()
+    )
 }
 
 pub fn resolve_cfr_path(cli: &Cli) -> Result<PathBuf> {
-    if let Some(p) = cli.cfr.clone() {
+    if let Some(p) = existing_cfr_path_with(cli, &process_env)? {
         return Ok(p);
     }
 
-    if let Ok(p) = env::var("CFR_JAR") {
-        return Ok(PathBuf::from(p));
+    let default_path = class_finder_home_with(&process_env)?
+        .join("tools")
+        .join("cfr.jar");
+    install_tool_if_missing(&default_path, &cfr_install_with(cli, &process_env)?)?;
+    Ok(default_path)
+}
+
+/// How a decompiler jar that is not installed yet is fetched.
+#[derive(Debug)]
+struct ToolInstall {
+    name: &'static str,
+    flag: &'static str,
+    env_var: &'static str,
+    config_key: &'static str,
+    url: String,
+    sha256: Option<String>,
+    https_proxy: Option<String>,
+    no_download: bool,
+}
+
+/// `CLASS_FINDER_CFR_DOWNLOAD_URL`, then `cfr_download_url`, then the
+/// GitHub release.
+fn cfr_download_url_with(cli: &Cli, env: EnvLookup) -> String {
+    env("CLASS_FINDER_CFR_DOWNLOAD_URL")
+        .or_else(|| cli.file_config.cfr_download_url.clone())
+        .unwrap_or_else(|| CFR_URL.to_string())
+}
+
+/// The CFR download. A `CLASS_FINDER_CFR_SHA256`/`cfr_sha256` digest
+/// replaces the pinned one, mirrors included; only `skip` turns verification
+/// off, so installing an unverified or patched jar is always a conscious
+/// choice.
+fn cfr_install_with(cli: &Cli, env: EnvLookup) -> Result<ToolInstall> {
+    let file = &cli.file_config;
+    let url = cfr_download_url_with(cli, env);
+    let sha256 = match env("CLASS_FINDER_CFR_SHA256").or_else(|| file.cfr_sha256.clone()) {
+        Some(raw) if raw.trim().eq_ignore_ascii_case("skip") => None,
+        Some(raw) => {
+            let digest = raw.trim().to_ascii_lowercase();
+            if digest.len() != 64 || !digest.bytes().all(|b| b.is_ascii_hexdigit()) {
+                anyhow::bail!("cfr_sha256 must be a hex SHA-256 digest or \"skip\", got {raw:?}");
+            }
+            Some(digest)
+        }
+        None => Some(CFR_SHA256.to_string()),
+    };
+    Ok(ToolInstall {
+        name: "CFR",
+        flag: "--cfr",
+        env_var: "CFR_JAR",
+        config_key: "cfr",
+        url,
+        sha256,
+        https_proxy: file.https_proxy.clone(),
+        no_download: cli.no_download || file.no_download,
+    })
+}
+
+/// The configured CFR jar, without downloading it when missing.
+fn existing_cfr_path_with(cli: &Cli, env: EnvLookup) -> Result<Option<PathBuf>> {
+    if let Some(p) = cli.cfr.clone() {
+        return Ok(Some(p));
+    }
+
+    if let Some(p) = env("CFR_JAR") {
+        return Ok(Some(PathBuf::from(p)));
+    }
+
+    if let Some(p) = cli.file_config.cfr.clone() {
+        return Ok(Some(p));
+    }
+
+    Ok(cfr_install_candidates_with(env)?
+        .into_iter()
+        .find(|candidate| candidate.exists)
+        .map(|candidate| candidate.path))
+}
+
+/// Where an installed CFR jar is looked for when none is configured, in
+/// order: the download location under `CLASS_FINDER_HOME`, the legacy
+/// `~/.class-finder/tools`, `$JAVA_HOME/lib`, then sdkman, Homebrew and
+/// system package locations.
+fn cfr_install_candidates_with(env: EnvLookup) -> Result<Vec<ToolCandidate>> {
+    let mut candidates = vec![ToolCandidate::new(
+        "CLASS_FINDER_HOME",
+        class_finder_home_with(env)?.join("tools").join("cfr.jar"),
+    )];
+    let home = env("HOME").map(PathBuf::from).or_else(dirs::home_dir);
+    if let Some(home) = &home {
+        candidates.push(ToolCandidate::new(
+            "legacy",
+            home.join(".class-finder").join("tools").join("cfr.jar"),
+        ));
+    }
+    if let Some(java_home) = env("JAVA_HOME").filter(|v| !v.trim().is_empty()) {
+        candidates.push(ToolCandidate::new(
+            "JAVA_HOME",
+            Path::new(&java_home).join("lib").join("cfr.jar"),
+        ));
     }
+    if let Some(sdkman) = env("SDKMAN_DIR")
+        .map(PathBuf::from)
+        .or_else(|| home.as_ref().map(|home| home.join(".sdkman")))
+    {
+        candidates.push(cfr_jar_in(
+            "sdkman",
+            &sdkman.join("candidates").join("cfr").join("current"),
+        ));
+    }
+    let mut prefixes: Vec<PathBuf> = env("HOMEBREW_PREFIX")
+        .map(PathBuf::from)
+        .into_iter()
+        .collect();
+    for default in ["/opt/homebrew", "/usr/local", "/home/linuxbrew/.linuxbrew"] {
+        if !prefixes.iter().any(|p| p == Path::new(default)) {
+            prefixes.push(PathBuf::from(default));
+        }
+    }
+    for prefix in prefixes {
+        candidates.push(cfr_jar_in(
+            "homebrew",
+            &prefix.join("opt").join("cfr-decompiler").join("libexec"),
+        ));
+    }
+    if cfg!(unix) {
+        candidates.push(ToolCandidate::new(
+            "system",
+            PathBuf::from("/usr/share/java/cfr.jar"),
+        ));
+    }
+    Ok(candidates)
+}
+
+/// The newest `cfr*.jar` in `dir`, which package managers name after the
+/// version; `dir/cfr.jar` (missing) when there is none.
+fn cfr_jar_in(source: &str, dir: &Path) -> ToolCandidate {
+    let newest = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with("cfr") && n.ends_with(".jar"))
+        })
+        .max();
+    ToolCandidate::new(source, newest.unwrap_or_else(|| dir.join("cfr.jar")))
+}
+
+/// Tool lookup printed by `doctor`.
+#[derive(Debug, Serialize)]
+pub struct DoctorReport {
+    pub cfr: ToolReport,
+    pub java: ToolReport,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ToolReport {
+    pub candidates: Vec<ToolCandidate>,
+    /// `None` for CFR means it will be downloaded on first use.
+    pub chosen: Option<PathBuf>,
+}
 
-    let default_path = class_finder_home()?.join("tools").join("cfr.jar");
-    if default_path.exists() {
-        return Ok(default_path);
+pub fn doctor_report(cli: &Cli) -> Result<DoctorReport> {
+    doctor_report_with(cli, &process_env)
+}
+
+fn doctor_report_with(cli: &Cli, env: EnvLookup) -> Result<DoctorReport> {
+    let mut cfr_candidates = Vec::new();
+    for (source, path) in [
+        ("--cfr", cli.cfr.clone()),
+        ("CFR_JAR", env("CFR_JAR").map(PathBuf::from)),
+        ("config file", cli.file_config.cfr.clone()),
+    ] {
+        if let Some(path) = path {
+            cfr_candidates.push(ToolCandidate::new(source, path));
+        }
+    }
+    cfr_candidates.extend(cfr_install_candidates_with(env)?);
+
+    let java_candidates = java_candidates_with(env);
+    let java = java_candidates
+        .iter()
+        .find(|c| c.source == "CLASS_FINDER_JAVA" || c.exists)
+        .map(|c| c.path.clone());
+    Ok(DoctorReport {
+        cfr: ToolReport {
+            candidates: cfr_candidates,
+            chosen: existing_cfr_path_with(cli, env)?,
+        },
+        java: ToolReport {
+            candidates: java_candidates,
+            chosen: java,
+        },
+    })
+}
+
+/// Version of the configured CFR jar, if one is installed.
+pub fn configured_cfr_version(cli: &Cli) -> Option<String> {
+    let path = existing_cfr_path_with(cli, &process_env).ok()??;
+    Cfr::with_config(path, resolve_decompiler_config(cli)).version()
+}
+
+pub fn resolve_vineflower_path(cli: &Cli) -> Result<PathBuf> {
+    if let Some(p) = existing_vineflower_path_with(cli, &process_env)? {
+        return Ok(p);
     }
 
-    install_cfr_if_missing(&default_path)?;
+    let default_path = class_finder_home_with(&process_env)?
+        .join("tools")
+        .join("vineflower.jar");
+    install_tool_if_missing(&default_path, &vineflower_install(cli))?;
     Ok(default_path)
 }
 
+/// The Vineflower download, always checked against the pinned digest.
+fn vineflower_install(cli: &Cli) -> ToolInstall {
+    let file = &cli.file_config;
+    ToolInstall {
+        name: "Vineflower",
+        flag: "--vineflower",
+        env_var: "VINEFLOWER_JAR",
+        config_key: "vineflower",
+        url: VINEFLOWER_URL.to_string(),
+        sha256: Some(VINEFLOWER_SHA256.to_string()),
+        https_proxy: file.https_proxy.clone(),
+        no_download: cli.no_download || file.no_download,
+    }
+}
+
+/// The configured Vineflower jar, without downloading it when missing.
+fn existing_vineflower_path_with(cli: &Cli, env: EnvLookup) -> Result<Option<PathBuf>> {
+    if let Some(p) = cli.vineflower.clone() {
+        return Ok(Some(p));
+    }
+
+    if let Some(p) = env("VINEFLOWER_JAR") {
+        return Ok(Some(PathBuf::from(p)));
+    }
+
+    if let Some(p) = cli.file_config.vineflower.clone() {
+        return Ok(Some(p));
+    }
+
+    let default_path = class_finder_home_with(env)?
+        .join("tools")
+        .join("vineflower.jar");
+    Ok(default_path.exists().then_some(default_path))
+}
+
+/// `--decompiler`, then the config file, then CFR.
+pub fn resolve_decompiler_kind(cli: &Cli) -> DecompilerKind {
+    cli.decompiler
+        .or(cli.file_config.decompiler)
+        .unwrap_or_default()
+}
+
+pub fn resolve_decompiler(cli: &Cli) -> Result<Arc<dyn Decompiler>> {
+    let config = resolve_decompiler_config(cli);
+    Ok(match resolve_decompiler_kind(cli) {
+        DecompilerKind::Cfr => {
+            let extra_args = cli
+                .cfr_args
+                .iter()
+                .map(|raw| parse_cfr_arg(raw))
+                .collect::<Result<Vec<_>>>()?;
+            let cfr = Cfr::with_config(resolve_cfr_path(cli)?, config).with_extra_args(extra_args);
+            Arc::new(if cli.cfr_server {
+                cfr.with_server()
+            } else {
+                cfr
+            })
+        }
+        DecompilerKind::Vineflower => Arc::new(Vineflower::with_config(
+            resolve_vineflower_path(cli)?,
+            config,
+        )),
+    })
+}
+
+pub fn resolve_decompiler_config(cli: &Cli) -> DecompilerConfig {
+    resolve_decompiler_config_with(cli, &process_env)
+}
+
+fn resolve_decompiler_config_with(cli: &Cli, env: EnvLookup) -> DecompilerConfig {
+    let file = &cli.file_config;
+    let mut config = DecompilerConfig {
+        classpath: cli.classpath,
+        fallback_encoding: cli.fallback_encoding,
+        keep_warnings: cli.keep_warnings,
+        ..DecompilerConfig::default()
+    };
+    if let Some(secs) = cli.class_timeout.or(file.class_timeout) {
+        config.class_timeout = Duration::from_secs(secs);
+    }
+    if let Some(secs) = cli.jar_timeout.or(file.jar_timeout) {
+        config.jar_timeout = Duration::from_secs(secs);
+    }
+    let java_opts = cli
+        .java_opts
+        .clone()
+        .or_else(|| env("CLASS_FINDER_JAVA_OPTS"))
+        .or_else(|| file.java_opts.clone());
+    if let Some(raw) = java_opts {
+        config.java_opts = split_java_opts(&raw);
+    }
+    config.postprocess = Postprocessor::new(
+        file.postprocess.clone(),
+        file.postprocess_timeout
+            .map_or(DEFAULT_POSTPROCESS_TIMEOUT, Duration::from_secs),
+    );
+    config
+}
+
+pub fn resolve_java_jobs(cli: &Cli) -> usize {
+    cli.jobs
+        .or(cli.file_config.jobs)
+        .unwrap_or_else(default_java_jobs)
+        .max(1)
+}
+
+/// Write-buffer tuning from `--buffer-*` flags, then `CLASS_FINDER_BUFFER_*`
+/// variables, then the config file, then the defaults. The queue is grown to
+/// hold at least one full batch.
+pub fn resolve_buffer_config(cli: &Cli) -> Result<BufferConfig> {
+    resolve_buffer_config_with(cli, &process_env)
+}
+
+fn resolve_buffer_config_with(cli: &Cli, env: EnvLookup) -> Result<BufferConfig> {
+    let defaults = BufferConfig::default();
+    let file = &cli.file_config;
+    let batch_size = match cli.buffer_batch_size {
+        Some(n) => n,
+        None => env_number(env, "CLASS_FINDER_BUFFER_BATCH_SIZE")?
+            .or(file.buffer_batch_size)
+            .unwrap_or(defaults.batch_size),
+    }
+    .max(1);
+    let flush_interval_ms = match cli.buffer_flush_ms {
+        Some(ms) => ms,
+        None => env_number(env, "CLASS_FINDER_BUFFER_FLUSH_MS")?
+            .or(file.buffer_flush_ms)
+            .unwrap_or(defaults.flush_interval_ms),
+    };
+    Ok(BufferConfig {
+        batch_size,
+        flush_interval_ms,
+        capacity: defaults.capacity.max(batch_size),
+    })
+}
+
+/// Accesses after which `find` warms a jar in full, from
+/// `--warmup-threshold`, then `CLASS_FINDER_WARMUP_THRESHOLD`, then the
+/// config file, then [`DEFAULT_WARMUP_THRESHOLD`]. 0 is taken as 1: warm on
+/// the first access.
+pub fn resolve_warmup_threshold(cli: &Cli) -> Result<u32> {
+    resolve_warmup_threshold_with(cli, &process_env)
+}
+
+fn resolve_warmup_threshold_with(cli: &Cli, env: EnvLookup) -> Result<u32> {
+    let threshold = match cli.warmup_threshold {
+        Some(n) => n,
+        None => env_number(env, "CLASS_FINDER_WARMUP_THRESHOLD")?
+            .or(cli.file_config.warmup_threshold)
+            .unwrap_or(DEFAULT_WARMUP_THRESHOLD),
+    };
+    Ok(threshold.max(1))
+}
+
+/// How long `find` reuses the jar list of a scanned root, from the config
+/// file's `scan_cache_max_age`, else [`DEFAULT_SCAN_CACHE_MAX_AGE`].
+pub fn resolve_scan_cache_max_age(cli: &Cli) -> Duration {
+    cli.file_config
+        .scan_cache_max_age
+        .map_or(DEFAULT_SCAN_CACHE_MAX_AGE, Duration::from_secs)
+}
+
+/// Jars whose entry listings [`jar_listings`](crate::listing::jar_listings)
+/// keeps, from the config file's `listing_cache_size`, else
+/// [`DEFAULT_LISTING_CACHE_SIZE`].
+pub fn resolve_listing_cache_size(cli: &Cli) -> usize {
+    cli.file_config
+        .listing_cache_size
+        .unwrap_or(DEFAULT_LISTING_CACHE_SIZE)
+}
+
+/// Warmup exclusions from the `--exclude-*` flags; a filter given no flag
+/// takes the config file's list.
+pub fn resolve_jar_exclusions(
+    cli: &Cli,
+    groups: &[String],
+    artifacts: &[String],
+    jar_globs: &[String],
+) -> Result<JarExclusions> {
+    let file = &cli.file_config;
+    let pick = |flags: &[String], configured: &[String]| {
+        if flags.is_empty() {
+            configured.to_vec()
+        } else {
+            flags.to_vec()
+        }
+    };
+    JarExclusions::new(
+        &pick(groups, &file.exclude_group),
+        &pick(artifacts, &file.exclude_artifact),
+        &pick(jar_globs, &file.exclude_jar_glob),
+    )
+}
+
+fn env_number<T: std::str::FromStr>(env: EnvLookup, name: &str) -> Result<Option<T>> {
+    let Some(raw) = env(name) else {
+        return Ok(None);
+    };
+    raw.trim()
+        .parse()
+        .map(Some)
+        .map_err(|_| anyhow::anyhow!("{name} must be a non-negative integer, got {raw:?}"))
+}
+
+/// Removes the data and lock files of the db. In directory layout the
+/// directory itself stays, since it is often a mount point.
 pub fn clear_db(db_path: &Path) -> Result<()> {
-    remove_file_if_exists(db_path, "db")?;
-    remove_file_if_exists(&lmdb_lock_path(db_path), "db lock")?;
+    let layout = DbLayout::of(db_path);
+    remove_file_if_exists(&layout.data_path(db_path), "db")?;
+    remove_file_if_exists(&layout.lock_path(db_path), "db lock")?;
 
     Ok(())
 }
 
-fn class_finder_home() -> Result<PathBuf> {
+/// `CLASS_FINDER_HOME`, or `class-finder` under the local data directory.
+fn class_finder_home_with(env: EnvLookup) -> Result<PathBuf> {
+    if let Some(p) = env("CLASS_FINDER_HOME") {
+        return Ok(PathBuf::from(p));
+    }
     let base = dirs::data_local_dir()
         .or_else(dirs::cache_dir)
         .or_else(dirs::home_dir)
@@ -53,12 +858,6 @@ fn class_finder_home() -> Result<PathBuf> {
     Ok(base.join("class-finder"))
 }
 
-fn lmdb_lock_path(db_path: &Path) -> PathBuf {
-    let mut os = db_path.as_os_str().to_os_string();
-    os.push("-lock");
-    PathBuf::from(os)
-}
-
 fn remove_file_if_exists(path: &Path, kind: &str) -> Result<()> {
     if path.exists() {
         std::fs::remove_file(path)
@@ -67,62 +866,415 @@ fn remove_file_if_exists(path: &Path, kind: &str) -> Result<()> {
     Ok(())
 }
 
-fn install_cfr_if_missing(target_path: &Path) -> Result<()> {
+fn install_tool_if_missing(target_path: &Path, install: &ToolInstall) -> Result<()> {
     if target_path.exists() {
         return Ok(());
     }
+    let ToolInstall {
+        name,
+        flag,
+        env_var,
+        config_key,
+        url,
+        ..
+    } = install;
+    if install.no_download {
+        anyhow::bail!(
+            "{name} is not installed and downloads are disabled (--no-download). \
+             Download {url} and save it as {}, or point {flag}, {env_var} or \
+             `{config_key}` in the config file at a local jar",
+            target_path.display()
+        );
+    }
 
-    let url = "https://github.com/leibnitz27/cfr/releases/download/0.152/cfr-0.152.jar";
     if let Some(parent) = target_path.parent() {
         std::fs::create_dir_all(parent)
             .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
     }
 
-    eprintln!(
-        "[class-finder] CFR not found, downloading to {}",
-        target_path.display()
-    );
-    let status = std::process::Command::new("curl")
-        .args([
-            "-L",
-            "--fail",
-            "--silent",
-            "--show-error",
-            "-o",
-            target_path
-                .to_str()
-                .context("cfr.jar target path is not valid UTF-8")?,
-            url,
-        ])
-        .status()
-        .context(
-            "Failed to execute curl (ensure curl is installed, or use --cfr to specify cfr.jar)",
+    tracing::info!("{name} not found, downloading to {}", target_path.display());
+    download_verified(
+        url,
+        target_path,
+        install.sha256.as_deref(),
+        install.https_proxy.as_deref(),
+    )
+    .with_context(|| {
+        format!("Failed to download {name}. You can use {flag} to specify a local jar")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::RepositoryLayout;
+    use clap::Parser;
+    use std::collections::HashMap;
+
+    const FILE: &str = r#"
+m2 = "/file/m2"
+db = "/file/db.lmdb"
+cfr = "/file/cfr.jar"
+decompiler = "vineflower"
+java_opts = "-Xmx1g"
+jobs = 3
+class_timeout = 30
+buffer_batch_size = 300
+buffer_flush_ms = 3000
+warmup_threshold = 30
+exclude_group = ["com.mycorp"]
+postprocess = ["google-java-format", "-"]
+scan_cache_max_age = 60
+listing_cache_size = 16
+"#;
+
+    fn cli_with(args: &[&str], file: &str) -> Cli {
+        let mut cli = Cli::parse_from(
+            std::iter::once("class-finder")
+                .chain(args.iter().copied())
+                .chain(["stats"]),
+        );
+        cli.file_config = FileConfig::parse(file).expect("config file");
+        cli
+    }
+
+    fn env_of<'a>(vars: &'a [(&'a str, &'a str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        let vars: HashMap<&str, &str> = vars.iter().copied().collect();
+        move |name| vars.get(name).map(|v| v.to_string())
+    }
+
+    #[test]
+    fn file_config_rejects_unknown_keys() {
+        let config = FileConfig::parse(FILE).expect("parses");
+        assert_eq!(config.decompiler, Some(DecompilerKind::Vineflower));
+        assert_eq!(config.exclude_group, vec!["com.mycorp"]);
+        assert!(FileConfig::parse("buffer_batch = 1\n").is_err());
+        assert!(FileConfig::parse("decompiler = \"procyon\"\n").is_err());
+    }
+
+    #[test]
+    fn flag_wins_over_env_and_file() -> Result<()> {
+        let cli = cli_with(
+            &[
+                "--m2",
+                "/flag/m2",
+                "--decompiler",
+                "cfr",
+                "--java-opts",
+                "-Xmx4g",
+                "--jobs",
+                "1",
+                "--buffer-batch-size",
+                "100",
+                "--warmup-threshold",
+                "10",
+            ],
+            FILE,
+        );
+        let env = env_of(&[
+            ("CLASS_FINDER_JAVA_OPTS", "-Xmx2g"),
+            ("CLASS_FINDER_BUFFER_BATCH_SIZE", "200"),
+            ("CLASS_FINDER_WARMUP_THRESHOLD", "20"),
+        ]);
+        let config = resolve_effective_config_with(&cli, &env)?;
+        assert_eq!(config.m2, PathBuf::from("/flag/m2"));
+        assert_eq!(config.decompiler, DecompilerKind::Cfr);
+        assert_eq!(config.java_opts, vec!["-Xmx4g"]);
+        assert_eq!(config.jobs, 1);
+        assert_eq!(config.write_buffer.batch_size, 100);
+        assert_eq!(config.warmup_threshold, 10);
+        Ok(())
+    }
+
+    #[test]
+    fn env_wins_over_file() -> Result<()> {
+        let cli = cli_with(&[], FILE);
+        let env = env_of(&[
+            ("CFR_JAR", "/env/cfr.jar"),
+            ("CLASS_FINDER_JAVA_OPTS", "-Xmx2g"),
+            ("CLASS_FINDER_BUFFER_BATCH_SIZE", "200"),
+            ("CLASS_FINDER_WARMUP_THRESHOLD", "20"),
+        ]);
+        let config = resolve_effective_config_with(&cli, &env)?;
+        assert_eq!(config.cfr, Some(PathBuf::from("/env/cfr.jar")));
+        assert_eq!(config.java_opts, vec!["-Xmx2g"]);
+        assert_eq!(config.write_buffer.batch_size, 200);
+        assert_eq!(config.write_buffer.flush_interval_ms, 3000);
+        assert_eq!(config.warmup_threshold, 20);
+        Ok(())
+    }
+
+    #[test]
+    fn file_wins_over_defaults() -> Result<()> {
+        let cli = cli_with(&[], FILE);
+        let config = resolve_effective_config_with(&cli, &env_of(&[]))?;
+        assert_eq!(config.m2, PathBuf::from("/file/m2"));
+        assert_eq!(config.db, PathBuf::from("/file/db.lmdb"));
+        assert_eq!(config.cfr, Some(PathBuf::from("/file/cfr.jar")));
+        assert_eq!(config.decompiler, DecompilerKind::Vineflower);
+        assert_eq!(config.java_opts, vec!["-Xmx1g"]);
+        assert_eq!(config.jobs, 3);
+        assert_eq!(config.class_timeout_secs, 30);
+        assert_eq!(
+            config.jar_timeout_secs,
+            DecompilerConfig::default().jar_timeout.as_secs()
+        );
+        assert_eq!(config.write_buffer.batch_size, 300);
+        assert_eq!(config.warmup_threshold, 30);
+        assert_eq!(config.postprocess, ["google-java-format", "-"]);
+        assert_eq!(config.sources["postprocess"], ConfigSource::File);
+        assert_eq!(config.scan_cache_max_age_secs, 60);
+        assert_eq!(config.listing_cache_size, 16);
+        assert_eq!(
+            config.postprocess_timeout_secs,
+            DEFAULT_POSTPROCESS_TIMEOUT.as_secs()
+        );
+        let decompiler = resolve_decompiler_config_with(&cli, &env_of(&[]));
+        assert_eq!(
+            decompiler.postprocess.as_ref().map(Postprocessor::command),
+            Some(&["google-java-format".to_string(), "-".to_string()][..])
+        );
+
+        let defaults = resolve_effective_config_with(&cli_with(&[], ""), &env_of(&[]))?;
+        assert_eq!(defaults.decompiler, DecompilerKind::Cfr);
+        assert_eq!(defaults.write_buffer, BufferConfig::default());
+        assert_eq!(defaults.warmup_threshold, DEFAULT_WARMUP_THRESHOLD);
+        assert!(defaults.java_opts.is_empty());
+        assert!(defaults.postprocess.is_empty());
+        assert_eq!(
+            defaults.scan_cache_max_age_secs,
+            DEFAULT_SCAN_CACHE_MAX_AGE.as_secs()
+        );
+        assert_eq!(defaults.listing_cache_size, DEFAULT_LISTING_CACHE_SIZE);
+        assert_eq!(
+            resolve_decompiler_config_with(&cli_with(&[], ""), &env_of(&[])).postprocess,
+            None
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn path_env_vars_sit_between_flags_and_the_file() -> Result<()> {
+        let env = env_of(&[
+            ("CLASS_FINDER_M2", "/env/m2"),
+            ("CLASS_FINDER_DB", "/env/db.lmdb"),
+            ("CLASS_FINDER_HOME", "/env/home"),
+        ]);
+        let config = resolve_effective_config_with(&cli_with(&[], FILE), &env)?;
+        assert_eq!(config.m2, PathBuf::from("/env/m2"));
+        assert_eq!(config.db, PathBuf::from("/env/db.lmdb"));
+        assert_eq!(config.home, PathBuf::from("/env/home"));
+        assert_eq!(config.sources["m2"], ConfigSource::Env);
+        assert_eq!(config.sources["cfr"], ConfigSource::File);
+
+        let flagged = cli_with(&["--m2", "/flag/m2", "--db", "/flag/db.lmdb"], FILE);
+        let config = resolve_effective_config_with(&flagged, &env)?;
+        assert_eq!(config.m2, PathBuf::from("/flag/m2"));
+        assert_eq!(config.db, PathBuf::from("/flag/db.lmdb"));
+        assert_eq!(config.sources["db"], ConfigSource::Flag);
+
+        let home_only = env_of(&[("CLASS_FINDER_HOME", "/env/home")]);
+        let config = resolve_effective_config_with(&cli_with(&[], ""), &home_only)?;
+        assert_eq!(config.db, PathBuf::from("/env/home/db.lmdb"));
+        assert_eq!(config.sources["db"], ConfigSource::Default);
+        assert_eq!(config.sources["home"], ConfigSource::Env);
+        assert_eq!(config.precedence, ConfigSource::PRECEDENCE);
+        Ok(())
+    }
+
+    #[test]
+    fn repositories_come_from_the_file_in_priority_order() -> Result<()> {
+        let file = r#"
+m2 = "/file/m2"
+
+[[repository]]
+name = "mirror"
+path = "/mnt/mirror"
+layout = "flat"
+priority = -5
+readonly = true
+
+[[repository]]
+name = "corp"
+path = "/corp/m2"
+priority = 10
+"#;
+        let cli = cli_with(&[], file);
+        let config = resolve_effective_config_with(&cli, &env_of(&[]))?;
+        let names: Vec<&str> = config
+            .repositories
+            .iter()
+            .map(|r| r.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["corp", "local", "mirror"]);
+        assert_eq!(config.repositories[1].path, PathBuf::from("/file/m2"));
+        assert_eq!(config.repositories[2].layout, RepositoryLayout::Flat);
+        assert!(config.repositories[2].readonly);
+        assert!(FileConfig::parse("[[repository]]\nname = \"x\"\n").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn exclusion_flags_replace_the_file_lists() -> Result<()> {
+        let cli = cli_with(&[], FILE);
+        let m2 = PathBuf::from("/m2");
+        let jar = m2.join("com/mycorp/app/1.0/app-1.0.jar");
+
+        let mut jars = vec![jar.clone()];
+        resolve_jar_exclusions(&cli, &[], &[], &[])?.apply(std::slice::from_ref(&m2), &mut jars);
+        assert!(jars.is_empty());
+
+        let mut jars = vec![jar.clone()];
+        resolve_jar_exclusions(&cli, &["org.other".to_string()], &[], &[])?
+            .apply(std::slice::from_ref(&m2), &mut jars);
+        assert_eq!(jars, vec![jar]);
+        Ok(())
+    }
+
+    #[test]
+    fn default_tool_installs_carry_the_pinned_digest() -> Result<()> {
+        let cli = cli_with(&[], "");
+        let cfr = cfr_install_with(&cli, &env_of(&[]))?;
+        let vineflower = vineflower_install(&cli);
+        assert_eq!(cfr.sha256.as_deref(), Some(CFR_SHA256));
+        assert_eq!(vineflower.sha256.as_deref(), Some(VINEFLOWER_SHA256));
+        for digest in [CFR_SHA256, VINEFLOWER_SHA256] {
+            assert_eq!(digest.len(), 64);
+            assert!(digest.bytes().all(|b| b.is_ascii_hexdigit()));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn cfr_mirror_is_verified_unless_skipped() -> Result<()> {
+        let install = cfr_install_with(&cli_with(&[], ""), &env_of(&[]))?;
+        assert_eq!(install.url, CFR_URL);
+        assert!(!install.no_download);
+
+        let mirror = "cfr_download_url = \"https://mirror.corp/cfr.jar\"\n";
+        let install = cfr_install_with(&cli_with(&[], mirror), &env_of(&[]))?;
+        assert_eq!(install.url, "https://mirror.corp/cfr.jar");
+        assert_eq!(install.sha256.as_deref(), Some(CFR_SHA256));
+
+        let digest = "AB".repeat(32);
+        let file = format!("{mirror}cfr_sha256 = \"skip\"\nhttps_proxy = \"http://proxy:3128\"\n");
+        let install = cfr_install_with(
+            &cli_with(&["--no-download"], &file),
+            &env_of(&[
+                ("CLASS_FINDER_CFR_DOWNLOAD_URL", "https://env.corp/cfr.jar"),
+                ("CLASS_FINDER_CFR_SHA256", &digest),
+            ]),
         )?;
+        assert_eq!(install.url, "https://env.corp/cfr.jar");
+        assert_eq!(install.sha256, Some(digest.to_ascii_lowercase()));
+        assert_eq!(install.https_proxy.as_deref(), Some("http://proxy:3128"));
+        assert!(install.no_download);
+
+        let install = cfr_install_with(&cli_with(&[], &file), &env_of(&[]))?;
+        assert_eq!(install.sha256, None);
+        let bad = format!("{mirror}cfr_sha256 = \"abc\"\n");
+        assert!(cfr_install_with(&cli_with(&[], &bad), &env_of(&[])).is_err());
+        Ok(())
+    }
 
-    if !status.success() {
-        if cfg!(windows) {
-            let ps_status = std::process::Command::new("powershell")
-                .args([
-                    "-NoProfile",
-                    "-ExecutionPolicy",
-                    "Bypass",
-                    "-Command",
-                    &format!(
-                        "Invoke-WebRequest -Uri '{url}' -OutFile '{}'",
-                        target_path.display()
-                    ),
-                ])
-                .status();
-
-            if let Ok(s) = ps_status
-                && s.success()
-            {
-                return Ok(());
+    #[test]
+    fn default_cfr_install_rejects_a_jar_with_the_wrong_digest() -> Result<()> {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let port = listener.local_addr()?.port();
+        std::thread::spawn(move || {
+            if let Some(Ok(mut stream)) = listener.incoming().next() {
+                let _ = stream.read(&mut [0u8; 4096]);
+                let _ = stream.write_all(
+                    b"HTTP/1.1 200 OK\r\nContent-Length: 7\r\nConnection: close\r\n\r\npatched",
+                );
             }
-        }
+        });
+        let base = std::env::temp_dir().join(format!(
+            "class_finder_config_test_{}_wrong_digest",
+            std::process::id()
+        ));
+        let target = base.join("cfr.jar");
+        let url = format!("http://127.0.0.1:{port}/cfr.jar");
+        let vars = [("CLASS_FINDER_CFR_DOWNLOAD_URL", url.as_str())];
+
+        let install = cfr_install_with(&cli_with(&[], ""), &env_of(&vars))?;
+        let err = install_tool_if_missing(&target, &install).unwrap_err();
+        assert!(format!("{err:#}").contains("Checksum mismatch"));
+        assert!(!target.exists());
 
-        anyhow::bail!("Failed to download CFR. You can use --cfr to specify local cfr.jar");
+        let _ = std::fs::remove_dir_all(base);
+        Ok(())
     }
 
-    Ok(())
+    #[test]
+    fn installed_cfr_jars_are_detected_in_order() -> Result<()> {
+        let base = std::env::temp_dir().join(format!(
+            "class_finder_config_test_{}_cfr_candidates",
+            std::process::id()
+        ));
+        let path = |rel: &str| base.join(rel).to_string_lossy().to_string();
+        let (cf_home, home, java_home, sdkman) =
+            (path("cf"), path("home"), path("jdk"), path("sdkman"));
+        let vars = [
+            ("CLASS_FINDER_HOME", cf_home.as_str()),
+            ("HOME", home.as_str()),
+            ("JAVA_HOME", java_home.as_str()),
+            ("SDKMAN_DIR", sdkman.as_str()),
+        ];
+        let env = env_of(&vars);
+        let cli = cli_with(&[], "");
+        let touch = |rel: &str| -> Result<PathBuf> {
+            let file = base.join(rel);
+            std::fs::create_dir_all(file.parent().expect("parent"))?;
+            std::fs::write(&file, "stub")?;
+            Ok(file)
+        };
+
+        let current = "sdkman/candidates/cfr/current";
+        touch(&format!("{current}/cfr-0.151.jar"))?;
+        let sdkman_jar = touch(&format!("{current}/cfr-0.152.jar"))?;
+        assert_eq!(existing_cfr_path_with(&cli, &env)?, Some(sdkman_jar));
+        let java_home_jar = touch("jdk/lib/cfr.jar")?;
+        assert_eq!(existing_cfr_path_with(&cli, &env)?, Some(java_home_jar));
+        let legacy = touch("home/.class-finder/tools/cfr.jar")?;
+        assert_eq!(existing_cfr_path_with(&cli, &env)?, Some(legacy));
+
+        let report = doctor_report_with(&cli_with(&["--cfr", "/flag/cfr.jar"], ""), &env)?;
+        assert_eq!(report.cfr.chosen, Some(PathBuf::from("/flag/cfr.jar")));
+        let sources: Vec<&str> = report
+            .cfr
+            .candidates
+            .iter()
+            .map(|c| c.source.as_str())
+            .collect();
+        assert_eq!(
+            sources[..5],
+            [
+                "--cfr",
+                "CLASS_FINDER_HOME",
+                "legacy",
+                "JAVA_HOME",
+                "sdkman"
+            ]
+        );
+        assert!(!report.cfr.candidates[0].exists);
+        assert!(report.cfr.candidates[2].exists);
+
+        let _ = std::fs::remove_dir_all(base);
+        Ok(())
+    }
+
+    #[test]
+    fn config_path_is_overridable() {
+        let env = env_of(&[("CLASS_FINDER_CONFIG", "/etc/class-finder.toml")]);
+        assert_eq!(
+            config_file_path_with(&env),
+            Some(PathBuf::from("/etc/class-finder.toml"))
+        );
+        if let Some(path) = config_file_path_with(&env_of(&[])) {
+            assert!(path.ends_with(".config/class-finder/config.toml"));
+        }
+    }
 }
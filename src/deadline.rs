@@ -0,0 +1,142 @@
+//! The global `--timeout` bound on a whole command.
+//!
+//! [`set`] starts the clock once per process. Scanning and probing loops call
+//! [`check`] between jars, and decompiler and javap processes get at most
+//! [`cap`] of their own timeout, so a command stops soon after the deadline
+//! with a [`DeadlineExceeded`] naming the [`Phase`] it was in. Work that goes
+//! through a write buffer is flushed on the way out as usual.
+
+use serde::Serialize;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::{Duration, Instant};
+
+static DEADLINE: OnceLock<(Instant, Duration)> = OnceLock::new();
+static PHASE: AtomicU8 = AtomicU8::new(Phase::Startup as u8);
+
+/// What a command was doing, as reported when the deadline passes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(u8)]
+pub enum Phase {
+    Startup,
+    Scan,
+    Probe,
+    Decompile,
+    Backfill,
+    Warmup,
+    Index,
+}
+
+impl Phase {
+    const ALL: [Phase; 7] = [
+        Phase::Startup,
+        Phase::Scan,
+        Phase::Probe,
+        Phase::Decompile,
+        Phase::Backfill,
+        Phase::Warmup,
+        Phase::Index,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Phase::Startup => "startup",
+            Phase::Scan => "scan",
+            Phase::Probe => "probe",
+            Phase::Decompile => "decompile",
+            Phase::Backfill => "backfill",
+            Phase::Warmup => "warmup",
+            Phase::Index => "index",
+        }
+    }
+}
+
+/// Returned (wrapped in `anyhow::Error`) once the `--timeout` deadline has
+/// passed.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeadlineExceeded {
+    pub phase: Phase,
+    pub timeout_secs: u64,
+}
+
+impl std::fmt::Display for DeadlineExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Operation timed out after {}s during {}",
+            self.timeout_secs,
+            self.phase.as_str()
+        )
+    }
+}
+
+impl std::error::Error for DeadlineExceeded {}
+
+/// Starts the deadline `timeout` from now. Later calls are ignored.
+pub fn set(timeout: Duration) {
+    let _ = DEADLINE.set((Instant::now() + timeout, timeout));
+}
+
+/// Records the phase the command is in.
+pub fn enter(phase: Phase) {
+    PHASE.store(phase as u8, Ordering::Relaxed);
+}
+
+pub fn phase() -> Phase {
+    let raw = PHASE.load(Ordering::Relaxed);
+    Phase::ALL
+        .into_iter()
+        .find(|phase| *phase as u8 == raw)
+        .unwrap_or(Phase::Startup)
+}
+
+/// Time left before the deadline; `None` without `--timeout`.
+pub fn remaining() -> Option<Duration> {
+    DEADLINE
+        .get()
+        .map(|(at, _)| at.saturating_duration_since(Instant::now()))
+}
+
+pub fn expired() -> bool {
+    remaining().is_some_and(|left| left.is_zero())
+}
+
+/// `timeout`, shortened to what is left before the deadline.
+pub fn cap(timeout: Duration) -> Duration {
+    remaining().map_or(timeout, |left| timeout.min(left))
+}
+
+/// The error for the current phase.
+pub fn exceeded() -> DeadlineExceeded {
+    DeadlineExceeded {
+        phase: phase(),
+        timeout_secs: DEADLINE.get().map_or(0, |(_, timeout)| timeout.as_secs()),
+    }
+}
+
+/// Fails with [`DeadlineExceeded`] once the deadline has passed.
+pub fn check() -> anyhow::Result<()> {
+    if expired() {
+        return Err(exceeded().into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phases_round_trip_and_the_error_names_the_current_one() {
+        for phase in Phase::ALL {
+            enter(phase);
+            assert_eq!(super::phase(), phase);
+        }
+        enter(Phase::Probe);
+        let err = exceeded();
+        assert_eq!(err.phase, Phase::Probe);
+        assert!(err.to_string().ends_with("during probe"));
+        enter(Phase::Startup);
+    }
+}
@@ -0,0 +1,1177 @@
+//! Decompiler backends and the shared JVM process runner.
+//!
+//! Every backend is a Java tool launched as a child process. This module owns
+//! the process handling (JVM options, timeouts, killing hung or aborted runs) and the
+//! `Decompiler` trait that `find`, `load` and `warmup` program against.
+//!
+//! - `Cfr`: the default backend, prints decompiled classes to stdout
+//! - `Vineflower`: writes `.java` files into an output directory
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use zip::ZipArchive;
+
+use crate::classpath::ClasspathMode;
+use crate::deadline;
+use crate::encoding::{DecodedOutput, FallbackEncoding, decode_output};
+use crate::parse::{
+    DecompiledStream, ParsedClass, SplitStrategy, inner_classes, parse_decompiled_output,
+    parse_decompiled_stream, parsed_class, strip_cfr_noise,
+};
+use crate::postprocess::Postprocessor;
+use crate::source::fqn_from_source_entry;
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    ValueEnum,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum DecompilerKind {
+    #[default]
+    Cfr,
+    Vineflower,
+}
+
+impl DecompilerKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Cfr => "cfr",
+            Self::Vineflower => "vineflower",
+        }
+    }
+}
+
+pub trait Decompiler: Send + Sync {
+    fn kind(&self) -> DecompilerKind;
+
+    /// Non-default backend options, recorded next to each cached class so
+    /// entries produced with unusual flags can be told apart.
+    fn options(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Backend version, if it can be determined. Recorded next to each cached
+    /// class alongside [`Decompiler::options`].
+    fn version(&self) -> Option<String> {
+        None
+    }
+
+    /// The formatter decompiled classes go through before they are hashed
+    /// and cached, if one is configured.
+    fn postprocessor(&self) -> Option<&Postprocessor> {
+        None
+    }
+
+    fn decompile_class(&self, jar_path: &Path, class_name: &str) -> Result<DecodedOutput>;
+
+    /// The JVM run behind [`Decompiler::decompile_class`], for callers that
+    /// start and wait on the process themselves. `None` for backends that do
+    /// not run one JVM per class.
+    fn class_invocation(
+        &self,
+        _jar_path: &Path,
+        _class_name: &str,
+    ) -> Result<Option<JavaInvocation>> {
+        Ok(None)
+    }
+
+    /// Turns the output of a finished [`Decompiler::class_invocation`] into
+    /// what [`Decompiler::decompile_class`] returns.
+    fn invocation_output(
+        &self,
+        invocation: &JavaInvocation,
+        output: std::process::Output,
+    ) -> Result<DecodedOutput> {
+        let _ = output;
+        anyhow::bail!("No decoder for {}", invocation.target)
+    }
+
+    fn decompile_jar(&self, jar_path: &Path) -> Result<DecodedOutput>;
+
+    /// Decompiles a whole jar and hands each class to `on_class` as soon as it
+    /// is available. Backends that write one file per class override this to
+    /// avoid holding the jar's full output in memory.
+    fn decompile_jar_each(
+        &self,
+        jar_path: &Path,
+        on_class: &mut dyn FnMut(ParsedClass),
+    ) -> Result<usize> {
+        let classes = parse_decoded_output(&self.decompile_jar(jar_path)?);
+        let count = classes.len();
+        classes.into_iter().for_each(on_class);
+        Ok(count)
+    }
+
+    /// Decompiles several classes from one jar. Backends that can handle many
+    /// classes in one process override this; the default runs them one by one
+    /// and reports the ones that failed in [`BatchDecompileOutput::failed`].
+    fn decompile_classes(
+        &self,
+        jar_path: &Path,
+        class_names: &[String],
+    ) -> Result<BatchDecompileOutput> {
+        let mut output = DecodedOutput::default();
+        let mut failed = Vec::new();
+        for class_name in class_names {
+            match self.decompile_class(jar_path, class_name) {
+                Ok(decoded) => {
+                    output.text.push_str(&decoded.text);
+                    output.text.push('\n');
+                    output.encoding = output.encoding.or(decoded.encoding);
+                }
+                Err(err) => failed.push((class_name.clone(), format!("{err:#}"))),
+            }
+        }
+        let mut batch = BatchDecompileOutput::from_decoded(&output, class_names);
+        batch
+            .missing
+            .retain(|name| !failed.iter().any(|(failed, _)| failed == name));
+        batch.failed = failed;
+        Ok(batch)
+    }
+}
+
+/// Temporary working directory for backends that write files, removed on drop.
+pub(crate) struct WorkDir {
+    path: PathBuf,
+}
+
+impl WorkDir {
+    pub(crate) fn create(label: &str) -> Result<Self> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let path = std::env::temp_dir().join(format!(
+            "class_finder_decompile_{}_{}_{}",
+            std::process::id(),
+            nanos,
+            label
+        ));
+        std::fs::create_dir_all(&path)
+            .with_context(|| format!("Failed to create directory: {}", path.display()))?;
+        Ok(Self { path })
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+/// One JVM run, described rather than started: `java <java_opts> <args>`,
+/// killed after `timeout`.
+pub struct JavaInvocation {
+    pub java_opts: Vec<String>,
+    pub args: Vec<String>,
+    /// What the run is reported as in errors, e.g. the class name.
+    pub target: String,
+    pub timeout: Duration,
+    /// Scratch space the run reads and writes, removed with the invocation.
+    work_dir: Option<WorkDir>,
+}
+
+impl JavaInvocation {
+    pub(crate) fn new(
+        java_opts: &[String],
+        args: Vec<String>,
+        target: &str,
+        timeout: Duration,
+    ) -> Self {
+        Self {
+            java_opts: java_opts.to_vec(),
+            args,
+            target: target.to_string(),
+            timeout,
+            work_dir: None,
+        }
+    }
+
+    pub(crate) fn with_work_dir(mut self, work_dir: WorkDir) -> Self {
+        self.work_dir = Some(work_dir);
+        self
+    }
+
+    pub fn work_dir(&self) -> Option<&Path> {
+        self.work_dir.as_ref().map(WorkDir::path)
+    }
+
+    /// The command to spawn, without stdio or process-group setup.
+    pub fn command(&self) -> Command {
+        let mut command = tool_base_command("CLASS_FINDER_JAVA", &jdk_tool("java"));
+        command.args(&self.java_opts).args(&self.args);
+        command
+    }
+
+    /// Runs it to completion on this thread.
+    pub fn run(&self) -> Result<std::process::Output> {
+        run_with_timeout(
+            self.command(),
+            "Failed to execute java (ensure JRE/JDK is installed)",
+            &self.target,
+            self.timeout,
+        )
+    }
+}
+
+/// Walks a decompiler output directory in path order and hands every `.java`
+/// file (including those inside `.jar`/`.zip` archives) to `on_class`, one at a
+/// time. Class names come from the relative path, so inner-class files keep
+/// their exact `Outer$Inner` names; member types printed inline are emitted
+/// too (see [`inner_classes`]). Returns the number of classes emitted.
+pub(crate) fn for_each_java_source(
+    dir: &Path,
+    config: &DecompilerConfig,
+    on_class: &mut dyn FnMut(ParsedClass),
+) -> Result<usize> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        for entry in std::fs::read_dir(&current)
+            .with_context(|| format!("Failed to read directory: {}", current.display()))?
+        {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path
+                .extension()
+                .is_some_and(|e| e == "java" || e == "jar" || e == "zip")
+            {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+
+    let mut emitted = HashSet::new();
+    let mut emit = |cls: ParsedClass| {
+        let inner = inner_classes(&cls);
+        for cls in std::iter::once(cls).chain(inner) {
+            if emitted.insert(cls.class_name.clone()) {
+                on_class(cls);
+            }
+        }
+    };
+
+    for path in files {
+        if path.extension().is_some_and(|e| e == "java") {
+            let rel = path
+                .strip_prefix(dir)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .replace('\\', "/");
+            let bytes = std::fs::read(&path)
+                .with_context(|| format!("Failed to read file: {}", path.display()))?;
+            if let Some(cls) = parsed_source(&rel, &bytes, config) {
+                emit(cls);
+            }
+            continue;
+        }
+
+        let file = File::open(&path)
+            .with_context(|| format!("Failed to open archive: {}", path.display()))?;
+        let mut archive = ZipArchive::new(file)
+            .with_context(|| format!("Failed to read archive: {}", path.display()))?;
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i)?;
+            let name = entry.name().to_string();
+            if !name.ends_with(".java") {
+                continue;
+            }
+            let mut bytes = Vec::new();
+            entry.read_to_end(&mut bytes)?;
+            if let Some(cls) = parsed_source(&name, &bytes, config) {
+                emit(cls);
+            }
+        }
+    }
+    Ok(emitted.len())
+}
+
+fn parsed_source(rel_path: &str, bytes: &[u8], config: &DecompilerConfig) -> Option<ParsedClass> {
+    let class_name = fqn_from_source_entry(rel_path)?;
+    let decoded = decode_decompiler_output(bytes, config);
+    Some(ParsedClass {
+        encoding: decoded.encoding.map(|e| e.as_str().to_string()),
+        ..parsed_class(class_name, decoded.text, SplitStrategy::File)
+    })
+}
+
+/// Decodes decompiler output and, unless `keep_warnings` is set, strips CFR's
+/// warning comments so that hashes are computed on the cleaned source.
+pub(crate) fn decode_decompiler_output(bytes: &[u8], config: &DecompilerConfig) -> DecodedOutput {
+    let mut decoded = decode_output(bytes, config.fallback_encoding);
+    if !config.keep_warnings {
+        decoded.text = strip_cfr_noise(&decoded.text);
+    }
+    decoded
+}
+
+/// Streaming counterpart of [`decode_decompiler_output`] followed by
+/// [`parse_decoded_output`].
+pub(crate) fn decompiled_stream<R: BufRead>(
+    reader: R,
+    config: &DecompilerConfig,
+) -> DecompiledStream<R> {
+    parse_decompiled_stream(reader)
+        .with_fallback_encoding(config.fallback_encoding)
+        .with_cfr_noise_stripped(!config.keep_warnings)
+}
+
+/// Splits decoded stdout into classes, tagging each with the fallback
+/// encoding if one was needed.
+pub(crate) fn parse_decoded_output(output: &DecodedOutput) -> Vec<ParsedClass> {
+    let mut classes = parse_decompiled_output(&output.text);
+    if let Some(encoding) = output.encoding {
+        for cls in &mut classes {
+            cls.encoding = Some(encoding.as_str().to_string());
+        }
+    }
+    classes
+}
+
+/// Counting semaphore bounding how many JVMs run at once.
+pub struct ProcessSlots {
+    state: Mutex<SlotState>,
+    released: Condvar,
+}
+
+struct SlotState {
+    limit: usize,
+    in_flight: usize,
+    gauge_path: Option<PathBuf>,
+}
+
+impl ProcessSlots {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            state: Mutex::new(SlotState {
+                limit: limit.max(1),
+                in_flight: 0,
+                gauge_path: None,
+            }),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Changes the limit and, optionally, a file that mirrors the in-flight
+    /// count for other processes (`class-finder stats`).
+    pub fn configure(&self, limit: usize, gauge_path: Option<PathBuf>) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.limit = limit.max(1);
+        state.gauge_path = gauge_path;
+        drop(state);
+        self.released.notify_all();
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .in_flight
+    }
+
+    /// Blocks until a slot is free. The slot is released when the guard drops.
+    pub fn acquire(&self) -> ProcessSlot<'_> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        while state.in_flight >= state.limit {
+            state = self.released.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+        state.in_flight += 1;
+        write_slot_gauge(&state);
+        ProcessSlot { slots: self }
+    }
+}
+
+pub struct ProcessSlot<'a> {
+    slots: &'a ProcessSlots,
+}
+
+impl Drop for ProcessSlot<'_> {
+    fn drop(&mut self) {
+        let mut state = self.slots.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.in_flight -= 1;
+        write_slot_gauge(&state);
+        drop(state);
+        self.slots.released.notify_one();
+    }
+}
+
+fn write_slot_gauge(state: &SlotState) {
+    if let Some(path) = state.gauge_path.as_deref() {
+        let _ = std::fs::write(path, format!("{}\n", state.in_flight));
+    }
+}
+
+/// Default `--jobs`: half the physical cores, at least one. SMT siblings
+/// count as one core; where the topology cannot be read (outside Linux),
+/// logical CPUs stand in.
+pub fn default_java_jobs() -> usize {
+    let logical = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1);
+    // A CPU quota or affinity mask can leave fewer CPUs than cores.
+    let cores = physical_cores_in(Path::new("/sys/devices/system/cpu"))
+        .map_or(logical, |cores| cores.min(logical));
+    (cores / 2).max(1)
+}
+
+/// Distinct cores behind the online CPUs of a sysfs `cpu` directory: CPUs
+/// listing the same hardware threads share one core.
+fn physical_cores_in(cpu_dir: &Path) -> Option<usize> {
+    let mut cores = HashSet::new();
+    for entry in std::fs::read_dir(cpu_dir).ok()?.flatten() {
+        let name = entry.file_name();
+        let is_cpu = name
+            .to_str()
+            .and_then(|name| name.strip_prefix("cpu"))
+            .is_some_and(|id| !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit()));
+        if !is_cpu {
+            continue;
+        }
+        let topology = entry.path().join("topology");
+        let siblings = std::fs::read_to_string(topology.join("core_cpus_list"))
+            .or_else(|_| std::fs::read_to_string(topology.join("thread_siblings_list")));
+        if let Ok(siblings) = siblings {
+            cores.insert(siblings.trim().to_string());
+        }
+    }
+    (!cores.is_empty()).then_some(cores.len())
+}
+
+/// Slots shared by every java/javap invocation in this process, whether it
+/// comes from find, load, warmup or the background warmer.
+pub fn java_process_slots() -> &'static ProcessSlots {
+    static SLOTS: OnceLock<ProcessSlots> = OnceLock::new();
+    SLOTS.get_or_init(|| ProcessSlots::new(default_java_jobs()))
+}
+
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+thread_local! {
+    /// The flag [`with_abort`] set for this thread.
+    static ABORT: RefCell<Option<Arc<AtomicBool>>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` with `abort` watched by every java/javap process it starts from
+/// this thread: once `abort` is raised, a running process is killed with its
+/// process group and no new one is started, both failing with an error.
+pub fn with_abort<T>(abort: Arc<AtomicBool>, f: impl FnOnce() -> T) -> T {
+    struct Restore(Option<Arc<AtomicBool>>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            ABORT.with(|cell| *cell.borrow_mut() = previous);
+        }
+    }
+
+    let _restore = Restore(ABORT.with(|cell| cell.replace(Some(abort))));
+    f()
+}
+
+fn current_abort() -> Option<Arc<AtomicBool>> {
+    ABORT.with(|cell| cell.borrow().clone())
+}
+
+fn aborted(abort: Option<&Arc<AtomicBool>>) -> bool {
+    abort.is_some_and(|abort| abort.load(Ordering::SeqCst))
+}
+
+fn aborted_error(target: &str) -> anyhow::Error {
+    anyhow::anyhow!("Decompilation aborted: {target}")
+}
+
+#[derive(Debug, Clone)]
+pub struct DecompilerConfig {
+    pub class_timeout: Duration,
+    pub jar_timeout: Duration,
+    /// Extra JVM arguments placed before `-jar` (e.g. `-Xmx4g`).
+    pub java_opts: Vec<String>,
+    /// Related jars made visible to single-class and batch decompiles.
+    pub classpath: ClasspathMode,
+    /// Encoding for decompiler output that is not valid UTF-8.
+    pub fallback_encoding: FallbackEncoding,
+    /// Keep CFR's warning comments instead of stripping them.
+    pub keep_warnings: bool,
+    /// Formatter each decompiled class is piped through before caching.
+    pub postprocess: Option<Postprocessor>,
+}
+
+impl Default for DecompilerConfig {
+    fn default() -> Self {
+        Self {
+            class_timeout: Duration::from_secs(60),
+            jar_timeout: Duration::from_secs(300),
+            java_opts: Vec::new(),
+            classpath: ClasspathMode::default(),
+            fallback_encoding: FallbackEncoding::default(),
+            keep_warnings: false,
+            postprocess: None,
+        }
+    }
+}
+
+/// Reads `Implementation-Version` (or `Bundle-Version`) from a jar's manifest.
+pub(crate) fn manifest_version(jar_path: &Path) -> Option<String> {
+    let file = File::open(jar_path).ok()?;
+    let mut archive = ZipArchive::new(file).ok()?;
+    let mut manifest = String::new();
+    archive
+        .by_name("META-INF/MANIFEST.MF")
+        .ok()?
+        .read_to_string(&mut manifest)
+        .ok()?;
+
+    ["Implementation-Version", "Bundle-Version"]
+        .iter()
+        .find_map(|key| {
+            manifest.lines().find_map(|line| {
+                let value = line.strip_prefix(key)?.strip_prefix(':')?.trim();
+                (!value.is_empty()).then(|| value.to_string())
+            })
+        })
+}
+
+/// Splits a JVM options string on whitespace, honoring single and double quotes
+/// and backslash escapes so values like `"-Dname=a b"` stay one argument.
+pub fn split_java_opts(raw: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_token = false;
+    let mut quote: Option<char> = None;
+    let mut chars = raw.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') => match chars.next() {
+                Some(next) => current.push(next),
+                None => current.push('\\'),
+            },
+            (Some(_), c) => current.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_token = true;
+            }
+            (None, '\\') => {
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+                in_token = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_token {
+                    args.push(std::mem::take(&mut current));
+                    in_token = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                in_token = true;
+            }
+        }
+    }
+
+    if in_token {
+        args.push(current);
+    }
+    args
+}
+
+/// Returned (wrapped in `anyhow::Error`) when a decompiler process exceeds its deadline
+/// and has been killed.
+#[derive(Debug, Clone)]
+pub struct DecompileTimeout {
+    pub target: String,
+    pub timeout: Duration,
+}
+
+impl std::fmt::Display for DecompileTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Decompilation timed out after {}ms: {}",
+            self.timeout.as_millis(),
+            self.target
+        )
+    }
+}
+
+impl std::error::Error for DecompileTimeout {}
+
+/// The error for a process killed after `timeout`: [`DecompileTimeout`], or
+/// [`DeadlineExceeded`](crate::deadline::DeadlineExceeded) when it was the
+/// global `--timeout` that ran out.
+pub(crate) fn timeout_error(target: &str, timeout: Duration) -> anyhow::Error {
+    if deadline::expired() {
+        return deadline::exceeded().into();
+    }
+    DecompileTimeout {
+        target: target.to_string(),
+        timeout,
+    }
+    .into()
+}
+
+/// A place a tool was looked for, as listed by `doctor`.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ToolCandidate {
+    /// The flag, env var or install location the path comes from.
+    pub source: String,
+    pub path: PathBuf,
+    pub exists: bool,
+}
+
+impl ToolCandidate {
+    pub fn new(source: impl Into<String>, path: PathBuf) -> Self {
+        let exists = path.is_file();
+        Self {
+            source: source.into(),
+            path,
+            exists,
+        }
+    }
+}
+
+/// `$JAVA_HOME/bin/<name>` when it exists, else the bare `name` for a PATH
+/// lookup.
+pub(crate) fn jdk_tool_with(env: &dyn Fn(&str) -> Option<String>, name: &str) -> String {
+    env("JAVA_HOME")
+        .filter(|home| !home.trim().is_empty())
+        .map(|home| {
+            Path::new(&home)
+                .join("bin")
+                .join(format!("{name}{}", std::env::consts::EXE_SUFFIX))
+        })
+        .filter(|path| path.is_file())
+        .map_or_else(
+            || name.to_string(),
+            |path| path.to_string_lossy().to_string(),
+        )
+}
+
+pub(crate) fn jdk_tool(name: &str) -> String {
+    jdk_tool_with(&|key| std::env::var(key).ok(), name)
+}
+
+/// Where `java` is looked for, in order: `CLASS_FINDER_JAVA`,
+/// `$JAVA_HOME/bin/java`, then each `PATH` entry. The first one that is set
+/// (for the env var) or exists is used.
+pub fn java_candidates_with(env: &dyn Fn(&str) -> Option<String>) -> Vec<ToolCandidate> {
+    let exe = format!("java{}", std::env::consts::EXE_SUFFIX);
+    let mut candidates = Vec::new();
+    if let Some(path) = env("CLASS_FINDER_JAVA") {
+        candidates.push(ToolCandidate::new("CLASS_FINDER_JAVA", PathBuf::from(path)));
+    }
+    if let Some(home) = env("JAVA_HOME").filter(|home| !home.trim().is_empty()) {
+        candidates.push(ToolCandidate::new(
+            "JAVA_HOME",
+            Path::new(&home).join("bin").join(&exe),
+        ));
+    }
+    if let Some(path) = env("PATH") {
+        candidates.extend(
+            std::env::split_paths(&path).map(|dir| ToolCandidate::new("PATH", dir.join(&exe))),
+        );
+    }
+    candidates
+}
+
+pub(crate) fn tool_base_command(env_var: &str, default_bin: &str) -> Command {
+    let bin = std::env::var(env_var).unwrap_or_else(|_| default_bin.to_string());
+
+    #[cfg(windows)]
+    {
+        let lower = bin.to_ascii_lowercase();
+        if lower.ends_with(".cmd") || lower.ends_with(".bat") {
+            let mut command = Command::new("cmd");
+            command.arg("/C").arg(&bin);
+            return command;
+        }
+    }
+
+    Command::new(&bin)
+}
+
+pub(crate) fn java_command(
+    java_opts: &[String],
+    args: &[&str],
+    target: &str,
+    timeout: Duration,
+) -> Result<std::process::Output> {
+    let mut command = tool_base_command("CLASS_FINDER_JAVA", &jdk_tool("java"));
+    command.args(java_opts).args(args);
+    run_with_timeout(
+        command,
+        "Failed to execute java (ensure JRE/JDK is installed)",
+        target,
+        timeout,
+    )
+}
+
+/// Like [`java_command`], but hands stdout to `read_stdout` while the process
+/// runs instead of collecting it, so large output is never held in memory at
+/// once. The returned `Output` has an empty `stdout`.
+pub(crate) fn java_command_streaming(
+    java_opts: &[String],
+    args: &[&str],
+    target: &str,
+    timeout: Duration,
+    read_stdout: &mut dyn FnMut(&mut dyn BufRead) -> Result<()>,
+) -> Result<std::process::Output> {
+    let mut command = tool_base_command("CLASS_FINDER_JAVA", &jdk_tool("java"));
+    command
+        .args(java_opts)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+
+    let _slot = java_process_slots().acquire();
+    let abort = current_abort();
+    if aborted(abort.as_ref()) {
+        return Err(aborted_error(target));
+    }
+    let mut child = command
+        .spawn()
+        .context("Failed to execute java (ensure JRE/JDK is installed)")?;
+    let stderr = spawn_pipe_reader(child.stderr.take());
+
+    // The reader blocks on stdout, so a watchdog enforces the timeout and the
+    // abort flag; killing the process closes the pipe and ends the read.
+    let (done_tx, done_rx) = mpsc::channel::<()>();
+    let timed_out = Arc::new(AtomicBool::new(false));
+    let watchdog = {
+        let timed_out = Arc::clone(&timed_out);
+        let abort = abort.clone();
+        let pid = child.id();
+        let kill_at = Instant::now() + deadline::cap(timeout);
+        std::thread::spawn(move || {
+            loop {
+                let remaining = kill_at.saturating_duration_since(Instant::now());
+                if let Err(RecvTimeoutError::Disconnected) =
+                    done_rx.recv_timeout(remaining.min(WAIT_POLL_INTERVAL))
+                {
+                    return;
+                }
+                if aborted(abort.as_ref()) {
+                    kill_process_group(pid);
+                    return;
+                }
+                if remaining.is_zero() {
+                    timed_out.store(true, Ordering::SeqCst);
+                    kill_process_group(pid);
+                    return;
+                }
+            }
+        })
+    };
+
+    let read = match child.stdout.take() {
+        Some(stdout) => read_stdout(&mut BufReader::new(stdout)),
+        None => Ok(()),
+    };
+    if read.is_err() {
+        kill_process_tree(&mut child);
+    }
+    let status = child.wait().context("Failed to wait for child process");
+    drop(done_tx);
+    let _ = watchdog.join();
+
+    if timed_out.load(Ordering::SeqCst) {
+        return Err(timeout_error(target, timeout));
+    }
+    if aborted(abort.as_ref()) {
+        return Err(aborted_error(target));
+    }
+    read?;
+    Ok(std::process::Output {
+        status: status?,
+        stdout: Vec::new(),
+        stderr: join_pipe_reader(stderr),
+    })
+}
+
+pub(crate) fn javap_command(
+    args: &[&str],
+    target: &str,
+    timeout: Duration,
+) -> Result<std::process::Output> {
+    let mut command = tool_base_command("CLASS_FINDER_JAVAP", &jdk_tool("javap"));
+    command.args(args);
+    run_with_timeout(
+        command,
+        "Failed to execute javap (ensure a JDK is installed)",
+        target,
+        timeout,
+    )
+}
+
+fn run_with_timeout(
+    mut command: Command,
+    spawn_error: &'static str,
+    target: &str,
+    timeout: Duration,
+) -> Result<std::process::Output> {
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    // Put the JVM (and any wrapper script children) into its own process group
+    // so a timeout can take down the whole tree.
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+
+    let _slot = java_process_slots().acquire();
+    let abort = current_abort();
+    if aborted(abort.as_ref()) {
+        return Err(aborted_error(target));
+    }
+    let mut child = command.spawn().context(spawn_error)?;
+    let stdout = spawn_pipe_reader(child.stdout.take());
+    let stderr = spawn_pipe_reader(child.stderr.take());
+
+    let kill_at = Instant::now() + deadline::cap(timeout);
+    let status = loop {
+        if let Some(status) = child
+            .try_wait()
+            .context("Failed to wait for child process")?
+        {
+            break status;
+        }
+        if aborted(abort.as_ref()) {
+            kill_process_tree(&mut child);
+            let _ = child.wait();
+            return Err(aborted_error(target));
+        }
+        if Instant::now() >= kill_at {
+            kill_process_tree(&mut child);
+            let _ = child.wait();
+            return Err(timeout_error(target, timeout));
+        }
+        std::thread::sleep(WAIT_POLL_INTERVAL);
+    };
+
+    Ok(std::process::Output {
+        status,
+        stdout: join_pipe_reader(stdout),
+        stderr: join_pipe_reader(stderr),
+    })
+}
+
+pub(crate) fn spawn_pipe_reader<R: Read + Send + 'static>(
+    pipe: Option<R>,
+) -> Option<JoinHandle<Vec<u8>>> {
+    let mut pipe = pipe?;
+    Some(std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = pipe.read_to_end(&mut buf);
+        buf
+    }))
+}
+
+pub(crate) fn join_pipe_reader(handle: Option<JoinHandle<Vec<u8>>>) -> Vec<u8> {
+    handle.and_then(|h| h.join().ok()).unwrap_or_default()
+}
+
+pub(crate) fn kill_process_tree(child: &mut Child) {
+    kill_process_group(child.id());
+    let _ = child.kill();
+}
+
+pub(crate) fn kill_process_group(pid: u32) {
+    #[cfg(unix)]
+    {
+        if let Ok(pid) = i32::try_from(pid) {
+            // SAFETY: kill(2) with a negative pid signals the process group we created
+            // via process_group(0); it has no memory-safety preconditions.
+            unsafe {
+                libc::kill(-pid, libc::SIGKILL);
+            }
+        }
+    }
+
+    #[cfg(windows)]
+    {
+        let _ = Command::new("taskkill")
+            .args(["/PID", &pid.to_string(), "/T", "/F"])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BatchDecompileOutput {
+    pub classes: Vec<ParsedClass>,
+    /// Requested class names for which CFR produced no output.
+    pub missing: Vec<String>,
+    /// Requested class names whose decompilation failed, with the error.
+    pub failed: Vec<(String, String)>,
+}
+
+impl BatchDecompileOutput {
+    pub fn from_output(output: &str, class_names: &[String]) -> Self {
+        let mut parsed = parse_decompiled_output(output);
+        let mut classes = Vec::with_capacity(class_names.len());
+        let mut missing = Vec::new();
+        for class_name in class_names {
+            match parsed.iter().position(|c| &c.class_name == class_name) {
+                Some(idx) => classes.push(parsed.swap_remove(idx)),
+                None => missing.push(class_name.clone()),
+            }
+        }
+        Self {
+            classes,
+            missing,
+            failed: Vec::new(),
+        }
+    }
+
+    pub fn from_decoded(output: &DecodedOutput, class_names: &[String]) -> Self {
+        let mut batch = Self::from_output(&output.text, class_names);
+        if let Some(encoding) = output.encoding {
+            for cls in &mut batch.classes {
+                cls.encoding = Some(encoding.as_str().to_string());
+            }
+        }
+        batch
+    }
+}
+
+/// Serializes tests that put a fake `java` on `PATH`. A `JAVA_HOME` from
+/// the developer's environment would win over that fake, so it is unset
+/// the first time the lock is taken.
+#[cfg(test)]
+pub(crate) fn java_env_lock() -> &'static std::sync::Mutex<()> {
+    static LOCK: std::sync::OnceLock<std::sync::Mutex<()>> = std::sync::OnceLock::new();
+    LOCK.get_or_init(|| {
+        // SAFETY: test-only; runs once, and tests only touch the environment
+        // through std, which synchronizes access.
+        unsafe { std::env::remove_var("JAVA_HOME") };
+        std::sync::Mutex::new(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::ClassKind;
+
+    #[test]
+    fn java_home_wins_over_path() -> Result<()> {
+        let base = std::env::temp_dir().join(format!(
+            "class_finder_decompiler_test_{}_java_home",
+            std::process::id()
+        ));
+        let java = base
+            .join("jdk/bin")
+            .join(format!("java{}", std::env::consts::EXE_SUFFIX));
+        std::fs::create_dir_all(java.parent().expect("parent"))?;
+        std::fs::write(&java, "stub")?;
+        let java_home = base.join("jdk").to_string_lossy().to_string();
+        let missing_home = base.join("none").to_string_lossy().to_string();
+
+        let env = |key: &str| match key {
+            "JAVA_HOME" => Some(java_home.clone()),
+            "PATH" => Some("/usr/bin".to_string()),
+            _ => None,
+        };
+        assert_eq!(jdk_tool_with(&env, "java"), java.to_string_lossy());
+        assert_eq!(
+            jdk_tool_with(
+                &|key: &str| (key == "JAVA_HOME").then(|| missing_home.clone()),
+                "java"
+            ),
+            "java"
+        );
+        let sources: Vec<String> = java_candidates_with(&env)
+            .into_iter()
+            .map(|c| c.source)
+            .collect();
+        assert_eq!(sources, ["JAVA_HOME", "PATH"]);
+
+        let _ = std::fs::remove_dir_all(base);
+        Ok(())
+    }
+
+    #[test]
+    fn split_java_opts_respects_quotes() {
+        assert_eq!(
+            split_java_opts(r#"-Xmx4g  -XX:+UseG1GC "-Dname=a b" '-Dq="x"' -Desc=c\ d"#),
+            vec![
+                "-Xmx4g",
+                "-XX:+UseG1GC",
+                "-Dname=a b",
+                "-Dq=\"x\"",
+                "-Desc=c d"
+            ]
+        );
+        assert!(split_java_opts("   ").is_empty());
+        assert_eq!(split_java_opts("''"), vec![""]);
+    }
+
+    #[test]
+    fn process_slots_block_until_a_slot_is_released() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let slots = Arc::new(ProcessSlots::new(2));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let handles: Vec<_> = (0..6)
+            .map(|_| {
+                let slots = Arc::clone(&slots);
+                let peak = Arc::clone(&peak);
+                std::thread::spawn(move || {
+                    let _slot = slots.acquire();
+                    peak.fetch_max(slots.in_flight(), Ordering::SeqCst);
+                    std::thread::sleep(Duration::from_millis(20));
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+        assert_eq!(slots.in_flight(), 0);
+    }
+
+    #[test]
+    fn for_each_java_source_names_classes_from_paths() -> Result<()> {
+        let work = WorkDir::create("for_each_test")?;
+        let pkg = work.path().join("org/example");
+        std::fs::create_dir_all(&pkg)?;
+        std::fs::write(pkg.join("B.java"), "package org.example;\ninterface B {}\n")?;
+        std::fs::write(
+            pkg.join("A$Inner.java"),
+            "package org.example;\nrecord Inner(int x) {}\n",
+        )?;
+        std::fs::write(pkg.join("package-info.java"), "package org.example;\n")?;
+        std::fs::write(work.path().join("summary.txt"), "ignored")?;
+
+        let mut names = Vec::new();
+        let count = for_each_java_source(work.path(), &DecompilerConfig::default(), &mut |cls| {
+            names.push((cls.class_name, cls.kind))
+        })?;
+        assert_eq!(count, 3);
+        assert_eq!(
+            names,
+            vec![
+                ("org.example.A$Inner".to_string(), ClassKind::Record),
+                ("org.example.B".to_string(), ClassKind::Interface),
+                (
+                    "org.example.package-info".to_string(),
+                    ClassKind::PackageInfo
+                ),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn batch_output_maps_requested_names_and_reports_missing() {
+        let output = "/*\n * Decompiled with CFR 0.152.\n */\npackage a;\n\npublic class B {\n}\n";
+        let requested = vec!["a.A".to_string(), "a.B".to_string()];
+        let batch = BatchDecompileOutput::from_output(output, &requested);
+        assert_eq!(batch.classes.len(), 1);
+        assert_eq!(batch.classes[0].class_name, "a.B");
+        assert_eq!(batch.missing, vec!["a.A".to_string()]);
+    }
+
+    #[test]
+    fn physical_cores_count_smt_siblings_once() -> Result<()> {
+        let cpu_dir = std::env::temp_dir().join(format!(
+            "class_finder_decompiler_test_{}_cpus",
+            std::process::id()
+        ));
+        for (cpu, siblings) in [(0, "0,2"), (1, "1,3"), (2, "0,2"), (3, "1,3"), (4, "4")] {
+            let topology = cpu_dir.join(format!("cpu{cpu}/topology"));
+            std::fs::create_dir_all(&topology)?;
+            std::fs::write(topology.join("core_cpus_list"), format!("{siblings}\n"))?;
+        }
+        // Not CPUs, and an offline CPU without topology.
+        std::fs::create_dir_all(cpu_dir.join("cpufreq/topology"))?;
+        std::fs::create_dir_all(cpu_dir.join("cpu5"))?;
+        assert_eq!(physical_cores_in(&cpu_dir), Some(3));
+        assert_eq!(physical_cores_in(&cpu_dir.join("missing")), None);
+        assert!(default_java_jobs() >= 1);
+        let _ = std::fs::remove_dir_all(&cpu_dir);
+        Ok(())
+    }
+
+    #[test]
+    fn default_batch_reports_failed_and_missing_classes() -> Result<()> {
+        struct OneByOne;
+
+        impl Decompiler for OneByOne {
+            fn kind(&self) -> DecompilerKind {
+                DecompilerKind::Cfr
+            }
+
+            fn decompile_class(&self, _jar_path: &Path, class_name: &str) -> Result<DecodedOutput> {
+                let text = match class_name {
+                    "a.Broken" => anyhow::bail!("class file is truncated"),
+                    "a.Empty" => String::new(),
+                    _ => format!("package a;\n\npublic class {} {{\n}}\n", &class_name[2..]),
+                };
+                Ok(DecodedOutput {
+                    text,
+                    encoding: None,
+                })
+            }
+
+            fn decompile_jar(&self, _jar_path: &Path) -> Result<DecodedOutput> {
+                anyhow::bail!("not used")
+            }
+        }
+
+        let requested: Vec<String> = ["a.A", "a.Broken", "a.Empty"].map(String::from).to_vec();
+        let batch = OneByOne.decompile_classes(Path::new("a.jar"), &requested)?;
+        assert_eq!(batch.classes.len(), 1);
+        assert_eq!(batch.classes[0].class_name, "a.A");
+        assert_eq!(batch.missing, vec!["a.Empty".to_string()]);
+        assert_eq!(
+            batch.failed,
+            vec![(
+                "a.Broken".to_string(),
+                "class file is truncated".to_string()
+            )]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,507 @@
+//! Structure-level comparison of two versions of a class.
+//!
+//! Compares [`ClassStructure`]s rather than source text, so decompiler noise
+//! such as reordered locals or renamed parameters does not show up. Methods
+//! are matched by name and arity, fields and enum constants by name, nested
+//! types by name.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+
+use crate::structure::{AnnotationStructure, ClassStructure, FieldStructure, MethodStructure};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct StructureDiff {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extends: Option<ValueChange>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub implements_added: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub implements_removed: Vec<String>,
+    /// Signatures of methods only in the newer structure.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub methods_added: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub methods_removed: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub methods_modified: Vec<MemberChange>,
+    /// Fields only in the newer structure, as `modifiers type name`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields_added: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields_removed: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields_modified: Vec<MemberChange>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub constants_added: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub constants_removed: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub nested_added: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub nested_removed: Vec<String>,
+    /// Diffs of nested types present in both versions that changed.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub nested_modified: Vec<StructureDiff>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ValueChange {
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+/// A method or field present in both versions whose declaration changed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MemberChange {
+    pub name: String,
+    pub from: String,
+    pub to: String,
+    pub changes: Vec<ChangeKind>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    /// Type of a field.
+    Type,
+    ReturnType,
+    ParameterTypes,
+    TypeParameters,
+    Modifiers,
+    Annotations,
+    Throws,
+}
+
+impl ChangeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeKind::Type => "type",
+            ChangeKind::ReturnType => "return_type",
+            ChangeKind::ParameterTypes => "parameter_types",
+            ChangeKind::TypeParameters => "type_parameters",
+            ChangeKind::Modifiers => "modifiers",
+            ChangeKind::Annotations => "annotations",
+            ChangeKind::Throws => "throws",
+        }
+    }
+}
+
+impl StructureDiff {
+    pub fn is_empty(&self) -> bool {
+        *self
+            == StructureDiff {
+                name: self.name.clone(),
+                ..StructureDiff::default()
+            }
+    }
+
+    /// Compact, line-per-change summary: `+` added, `-` removed, `~` modified.
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        if self.is_empty() {
+            let _ = writeln!(out, "{}: no structural changes", self.name);
+            return out;
+        }
+        self.write_text(&mut out);
+        out
+    }
+
+    fn write_text(&self, out: &mut String) {
+        let _ = writeln!(out, "{}", self.name);
+        if let Some(change) = &self.extends {
+            let _ = writeln!(
+                out,
+                "  ~ extends {} -> {}",
+                change.from.as_deref().unwrap_or("(none)"),
+                change.to.as_deref().unwrap_or("(none)")
+            );
+        }
+        let lines = [
+            ('+', "implements", &self.implements_added),
+            ('-', "implements", &self.implements_removed),
+            ('+', "constant", &self.constants_added),
+            ('-', "constant", &self.constants_removed),
+            ('+', "field", &self.fields_added),
+            ('-', "field", &self.fields_removed),
+            ('+', "method", &self.methods_added),
+            ('-', "method", &self.methods_removed),
+            ('+', "nested", &self.nested_added),
+            ('-', "nested", &self.nested_removed),
+        ];
+        for (mark, kind, items) in lines {
+            for item in items {
+                let _ = writeln!(out, "  {mark} {kind} {item}");
+            }
+        }
+        for (kind, changes) in [
+            ("field", &self.fields_modified),
+            ("method", &self.methods_modified),
+        ] {
+            for change in changes {
+                let kinds: Vec<&str> = change.changes.iter().map(ChangeKind::as_str).collect();
+                let _ = writeln!(out, "  ~ {kind} {} ({})", change.name, kinds.join(", "));
+                let _ = writeln!(out, "      - {}", change.from);
+                let _ = writeln!(out, "      + {}", change.to);
+            }
+        }
+        for nested in &self.nested_modified {
+            nested.write_text(out);
+        }
+    }
+}
+
+/// What changed from `old` to `new`.
+pub fn diff_structures(old: &ClassStructure, new: &ClassStructure) -> StructureDiff {
+    let mut diff = StructureDiff {
+        name: new.name.clone(),
+        ..StructureDiff::default()
+    };
+
+    if old.extends != new.extends {
+        diff.extends = Some(ValueChange {
+            from: old.extends.clone(),
+            to: new.extends.clone(),
+        });
+    }
+    (diff.implements_added, diff.implements_removed) = added_removed(
+        old.implements.iter().cloned(),
+        new.implements.iter().cloned(),
+    );
+    (diff.constants_added, diff.constants_removed) = added_removed(
+        old.constants.iter().map(|c| c.name.clone()),
+        new.constants.iter().map(|c| c.name.clone()),
+    );
+
+    diff_fields(&old.fields, &new.fields, &mut diff);
+    diff_methods(&old.methods, &new.methods, &mut diff);
+
+    (diff.nested_added, diff.nested_removed) = added_removed(
+        old.nested.iter().map(|n| n.name.clone()),
+        new.nested.iter().map(|n| n.name.clone()),
+    );
+    for new_nested in &new.nested {
+        if let Some(old_nested) = old.nested.iter().find(|n| n.name == new_nested.name) {
+            let nested = diff_structures(old_nested, new_nested);
+            if !nested.is_empty() {
+                diff.nested_modified.push(nested);
+            }
+        }
+    }
+
+    diff
+}
+
+/// Items only in `new` and items only in `old`, each in its own order.
+fn added_removed(
+    old: impl Iterator<Item = String>,
+    new: impl Iterator<Item = String>,
+) -> (Vec<String>, Vec<String>) {
+    let old: Vec<String> = old.collect();
+    let new: Vec<String> = new.collect();
+    let added = new.iter().filter(|n| !old.contains(n)).cloned().collect();
+    let removed = old.iter().filter(|o| !new.contains(o)).cloned().collect();
+    (added, removed)
+}
+
+fn diff_fields(old: &[FieldStructure], new: &[FieldStructure], diff: &mut StructureDiff) {
+    for field in new {
+        let Some(before) = old.iter().find(|f| f.name == field.name) else {
+            diff.fields_added.push(field_signature(field));
+            continue;
+        };
+        let mut changes = Vec::new();
+        if before.type_name != field.type_name {
+            changes.push(ChangeKind::Type);
+        }
+        if sorted(&before.modifiers) != sorted(&field.modifiers) {
+            changes.push(ChangeKind::Modifiers);
+        }
+        if sorted_annotations(&before.annotations) != sorted_annotations(&field.annotations) {
+            changes.push(ChangeKind::Annotations);
+        }
+        if !changes.is_empty() {
+            diff.fields_modified.push(MemberChange {
+                name: field.name.clone(),
+                from: field_signature(before),
+                to: field_signature(field),
+                changes,
+            });
+        }
+    }
+    for field in old {
+        if !new.iter().any(|f| f.name == field.name) {
+            diff.fields_removed.push(field_signature(field));
+        }
+    }
+}
+
+/// Methods sharing a name and arity in the old and the new structure.
+type Overloads<'a> = (Vec<&'a MethodStructure>, Vec<&'a MethodStructure>);
+
+/// Pairs overloads sharing a name and arity: identical parameter types first,
+/// then whatever is left in declaration order. Unpaired methods are added or
+/// removed.
+fn diff_methods(old: &[MethodStructure], new: &[MethodStructure], diff: &mut StructureDiff) {
+    let mut groups: BTreeMap<(&str, usize), Overloads<'_>> = BTreeMap::new();
+    for method in old {
+        groups.entry(method_key(method)).or_default().0.push(method);
+    }
+    for method in new {
+        groups.entry(method_key(method)).or_default().1.push(method);
+    }
+
+    let mut pairs = Vec::new();
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+    for (_, (mut before, mut after)) in groups {
+        before.retain(|old_method| {
+            let Some(pos) = after
+                .iter()
+                .position(|m| parameter_types(m) == parameter_types(old_method))
+            else {
+                return true;
+            };
+            pairs.push((*old_method, after.remove(pos)));
+            false
+        });
+        let paired = before.len().min(after.len());
+        let rest_after = after.split_off(paired);
+        let rest_before = before.split_off(paired);
+        pairs.extend(before.into_iter().zip(after));
+        removed.extend(rest_before);
+        added.extend(rest_after);
+    }
+
+    let order = |m: &MethodStructure| m.range.start_byte;
+    pairs.sort_by_key(|(_, after)| order(after));
+    removed.sort_by_key(|m| order(m));
+    added.sort_by_key(|m| order(m));
+
+    for (before, after) in pairs {
+        let changes = method_changes(before, after);
+        if !changes.is_empty() {
+            diff.methods_modified.push(MemberChange {
+                name: after.name.clone(),
+                from: before.signature.clone(),
+                to: after.signature.clone(),
+                changes,
+            });
+        }
+    }
+    diff.methods_added = added.into_iter().map(|m| m.signature.clone()).collect();
+    diff.methods_removed = removed.into_iter().map(|m| m.signature.clone()).collect();
+}
+
+fn method_changes(before: &MethodStructure, after: &MethodStructure) -> Vec<ChangeKind> {
+    let mut changes = Vec::new();
+    if before.return_type != after.return_type {
+        changes.push(ChangeKind::ReturnType);
+    }
+    if parameter_types(before) != parameter_types(after) {
+        changes.push(ChangeKind::ParameterTypes);
+    }
+    if before.type_parameters != after.type_parameters {
+        changes.push(ChangeKind::TypeParameters);
+    }
+    if sorted(&before.modifiers) != sorted(&after.modifiers) {
+        changes.push(ChangeKind::Modifiers);
+    }
+    if sorted_annotations(&before.annotations) != sorted_annotations(&after.annotations) {
+        changes.push(ChangeKind::Annotations);
+    }
+    if sorted(&before.throws) != sorted(&after.throws) {
+        changes.push(ChangeKind::Throws);
+    }
+    changes
+}
+
+fn method_key(method: &MethodStructure) -> (&str, usize) {
+    (method.name.as_str(), method.parameters.len())
+}
+
+fn parameter_types(method: &MethodStructure) -> Vec<&str> {
+    method
+        .parameters
+        .iter()
+        .map(|p| p.type_name.as_str())
+        .collect()
+}
+
+fn field_signature(field: &FieldStructure) -> String {
+    let mut parts: Vec<&str> = field.modifiers.iter().map(String::as_str).collect();
+    parts.push(&field.type_name);
+    parts.push(&field.name);
+    parts.join(" ")
+}
+
+fn sorted(items: &[String]) -> Vec<&str> {
+    let mut items: Vec<&str> = items.iter().map(String::as_str).collect();
+    items.sort_unstable();
+    items
+}
+
+fn sorted_annotations(annotations: &[AnnotationStructure]) -> Vec<(&str, Option<&str>)> {
+    let mut items: Vec<_> = annotations
+        .iter()
+        .map(|a| (a.name.as_str(), a.arguments.as_deref()))
+        .collect();
+    items.sort_unstable();
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structure::parse_class_structure;
+
+    const V1: &str = r#"
+package org.example;
+
+public class Service extends Base implements Runnable, Cloneable {
+    private int count;
+    protected String name;
+    public static final long TIMEOUT = 10L;
+
+    public Service(String name) {}
+
+    public void run() {}
+
+    public int size() { return count; }
+
+    public void put(String key, int value) {}
+
+    public void put(String key, long value) {}
+
+    @Deprecated
+    public void legacy() {}
+
+    static class Holder {
+        int value;
+    }
+}
+"#;
+
+    const V2: &str = r#"
+package org.example;
+
+public class Service extends AbstractService implements Runnable, AutoCloseable {
+    private long count;
+    protected final String name;
+
+    public Service(String name) {}
+
+    public void run() {}
+
+    public long size() throws java.io.IOException { return count; }
+
+    public void put(String key, int value) {}
+
+    public void put(String key, Object value) {}
+
+    public void close() {}
+
+    static class Holder {
+        int value;
+        int other;
+    }
+}
+"#;
+
+    fn structure(source: &str) -> ClassStructure {
+        parse_class_structure(source).expect("fixture parses")
+    }
+
+    #[test]
+    fn diff_of_identical_structures_is_empty() {
+        let diff = diff_structures(&structure(V1), &structure(V1));
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_text(), "Service: no structural changes\n");
+    }
+
+    #[test]
+    fn class_level_changes_are_reported() {
+        let diff = diff_structures(&structure(V1), &structure(V2));
+        assert_eq!(
+            diff.extends,
+            Some(ValueChange {
+                from: Some("Base".to_string()),
+                to: Some("AbstractService".to_string()),
+            })
+        );
+        assert_eq!(diff.implements_added, vec!["AutoCloseable"]);
+        assert_eq!(diff.implements_removed, vec!["Cloneable"]);
+    }
+
+    #[test]
+    fn methods_are_matched_by_name_and_arity() {
+        let diff = diff_structures(&structure(V1), &structure(V2));
+        assert_eq!(diff.methods_added, vec!["public void close()"]);
+        assert_eq!(
+            diff.methods_removed,
+            vec!["@Deprecated public void legacy()"]
+        );
+
+        let modified: Vec<(&str, &[ChangeKind])> = diff
+            .methods_modified
+            .iter()
+            .map(|m| (m.name.as_str(), m.changes.as_slice()))
+            .collect();
+        assert_eq!(
+            modified,
+            vec![
+                ("size", &[ChangeKind::ReturnType, ChangeKind::Throws][..]),
+                ("put", &[ChangeKind::ParameterTypes][..]),
+            ]
+        );
+        assert_eq!(
+            diff.methods_modified[1].from,
+            "public void put(String key, long value)"
+        );
+        assert_eq!(
+            diff.methods_modified[1].to,
+            "public void put(String key, Object value)"
+        );
+    }
+
+    #[test]
+    fn fields_and_nested_types_are_compared() {
+        let diff = diff_structures(&structure(V1), &structure(V2));
+        assert_eq!(
+            diff.fields_removed,
+            vec!["public static final long TIMEOUT"]
+        );
+        assert!(diff.fields_added.is_empty());
+        let modified: Vec<(&str, &[ChangeKind])> = diff
+            .fields_modified
+            .iter()
+            .map(|f| (f.name.as_str(), f.changes.as_slice()))
+            .collect();
+        assert_eq!(
+            modified,
+            vec![
+                ("count", &[ChangeKind::Type][..]),
+                ("name", &[ChangeKind::Modifiers][..]),
+            ]
+        );
+
+        assert_eq!(diff.nested_modified.len(), 1);
+        assert_eq!(diff.nested_modified[0].name, "Service.Holder");
+        assert_eq!(diff.nested_modified[0].fields_added, vec!["int other"]);
+    }
+
+    #[test]
+    fn text_summary_marks_each_change() {
+        let text = diff_structures(&structure(V1), &structure(V2)).to_text();
+        assert!(text.starts_with("Service\n  ~ extends Base -> AbstractService\n"));
+        assert!(text.contains("  + implements AutoCloseable\n"));
+        assert!(text.contains("  - field public static final long TIMEOUT\n"));
+        assert!(text.contains("  + method public void close()\n"));
+        assert!(text.contains(
+            "  ~ method size (return_type, throws)\n      - public int size()\n      + public long size() throws java.io.IOException\n"
+        ));
+        assert!(text.contains("Service.Holder\n  + field int other\n"));
+    }
+}
@@ -0,0 +1,220 @@
+use anyhow::{Context, Result, bail};
+use sha2::{Digest, Sha256};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::time::Duration;
+
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Downloads `url` to `target`, verifying the SHA-256 digest when one is
+/// pinned. The body goes to a sibling temp file that is renamed into place only
+/// after verification, so an interrupted or tampered download never leaves a
+/// jar behind that would later be executed. `https_proxy` is used for https
+/// URLs when neither `HTTPS_PROXY` nor `https_proxy` is set.
+pub fn download_verified(
+    url: &str,
+    target: &Path,
+    sha256: Option<&str>,
+    https_proxy: Option<&str>,
+) -> Result<()> {
+    let mut builder = ureq::AgentBuilder::new().timeout(DOWNLOAD_TIMEOUT);
+    let env = with_configured_proxy(|key| std::env::var(key).ok(), https_proxy);
+    if let Some(proxy) = proxy_for(url, env) {
+        builder = builder.proxy(ureq::Proxy::new(&proxy).context("Invalid proxy URL")?);
+    }
+    let response = builder
+        .build()
+        .get(url)
+        .call()
+        .with_context(|| format!("Failed to download {url}"))?;
+
+    let mut body = Vec::new();
+    response
+        .into_reader()
+        .read_to_end(&mut body)
+        .with_context(|| format!("Failed to read response body from {url}"))?;
+
+    if let Some(expected) = sha256 {
+        let actual = hex::encode(Sha256::digest(&body));
+        if !actual.eq_ignore_ascii_case(expected) {
+            bail!("Checksum mismatch for {url}: expected {expected}, got {actual}");
+        }
+    }
+
+    let mut temp = target.as_os_str().to_owned();
+    temp.push(format!(".{}.part", std::process::id()));
+    let temp = std::path::PathBuf::from(temp);
+    let written = std::fs::File::create(&temp)
+        .and_then(|mut file| {
+            file.write_all(&body)?;
+            file.sync_all()
+        })
+        .and_then(|_| std::fs::rename(&temp, target));
+    if let Err(err) = written {
+        let _ = std::fs::remove_file(&temp);
+        return Err(err).with_context(|| format!("Failed to write {}", target.display()));
+    }
+    Ok(())
+}
+
+/// Picks the proxy for `url` from `HTTPS_PROXY`/`HTTP_PROXY`/`ALL_PROXY`
+/// (either case), unless the host is excluded by `NO_PROXY`. Loopback hosts
+/// never go through a proxy.
+fn proxy_for(url: &str, env: impl Fn(&str) -> Option<String>) -> Option<String> {
+    let (scheme, rest) = url.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    let host = if host.starts_with('[') {
+        host.split_once(']')
+            .map_or(host, |(h, _)| h.trim_start_matches('['))
+    } else {
+        host.split(':').next().unwrap_or(host)
+    }
+    .to_ascii_lowercase();
+
+    if matches!(host.as_str(), "localhost" | "127.0.0.1" | "::1") {
+        return None;
+    }
+
+    let lookup = |key: &str| {
+        env(key)
+            .or_else(|| env(&key.to_ascii_lowercase()))
+            .filter(|v| !v.trim().is_empty())
+    };
+    if let Some(no_proxy) = lookup("NO_PROXY")
+        && no_proxy.split(',').any(|entry| {
+            let entry = entry.trim().trim_start_matches('.').to_ascii_lowercase();
+            entry == "*"
+                || (!entry.is_empty() && (host == entry || host.ends_with(&format!(".{entry}"))))
+        })
+    {
+        return None;
+    }
+
+    let scheme_key = if scheme.eq_ignore_ascii_case("https") {
+        "HTTPS_PROXY"
+    } else {
+        "HTTP_PROXY"
+    };
+    lookup(scheme_key).or_else(|| lookup("ALL_PROXY"))
+}
+
+/// `env` with `https_proxy` standing in for an unset `HTTPS_PROXY`, so
+/// the environment still wins and `NO_PROXY` still applies.
+fn with_configured_proxy<'a>(
+    env: impl Fn(&str) -> Option<String> + 'a,
+    https_proxy: Option<&'a str>,
+) -> impl Fn(&str) -> Option<String> + 'a {
+    move |key| {
+        env(key).or_else(|| {
+            https_proxy
+                .filter(|_| key == "https_proxy")
+                .map(str::to_string)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::path::PathBuf;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        std::env::temp_dir().join(format!(
+            "class_finder_download_test_{}_{}_{}",
+            std::process::id(),
+            nanos,
+            name
+        ))
+    }
+
+    /// Serves `body` to `requests` sequential connections on a loopback port.
+    fn serve(body: &'static [u8], requests: usize) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().take(requests) {
+                let mut stream = stream.unwrap();
+                let mut buf = [0u8; 4096];
+                let _ = stream.read(&mut buf);
+                let header = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                );
+                let _ = stream.write_all(header.as_bytes());
+                let _ = stream.write_all(body);
+            }
+        });
+        format!("http://127.0.0.1:{}/cfr.jar", addr.port())
+    }
+
+    #[test]
+    fn download_verified_checks_digest_before_writing() -> Result<()> {
+        let base = temp_dir("digest");
+        std::fs::create_dir_all(&base)?;
+        let target = base.join("cfr.jar");
+        let url = serve(b"jar-bytes", 2);
+        let digest = hex::encode(Sha256::digest(b"jar-bytes"));
+
+        let err = download_verified(&url, &target, Some(&"0".repeat(64)), None).unwrap_err();
+        assert!(err.to_string().contains("Checksum mismatch"));
+        assert!(!target.exists());
+
+        download_verified(&url, &target, Some(&digest), None)?;
+        assert_eq!(std::fs::read(&target)?, b"jar-bytes");
+        assert_eq!(std::fs::read_dir(&base)?.count(), 1);
+
+        let _ = std::fs::remove_dir_all(base);
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_for_honors_scheme_and_no_proxy() {
+        let env = |key: &str| match key {
+            "https_proxy" => Some("http://proxy:3128".to_string()),
+            "NO_PROXY" => Some("internal.example, .corp".to_string()),
+            _ => None,
+        };
+
+        assert_eq!(
+            proxy_for("https://github.com/a.jar", env).as_deref(),
+            Some("http://proxy:3128")
+        );
+        assert_eq!(proxy_for("http://github.com/a.jar", env), None);
+        assert_eq!(proxy_for("https://repo.internal.example/a.jar", env), None);
+        assert_eq!(proxy_for("https://mirror.corp:8443/a.jar", env), None);
+        assert_eq!(proxy_for("https://127.0.0.1:8080/a.jar", env), None);
+    }
+
+    #[test]
+    fn configured_proxy_is_a_fallback_for_https() {
+        let configured = Some("http://configured:8080");
+        let unset = with_configured_proxy(
+            |key| (key == "NO_PROXY").then(|| "mirror.corp".to_string()),
+            configured,
+        );
+        assert_eq!(
+            proxy_for("https://github.com/a.jar", &unset).as_deref(),
+            configured
+        );
+        assert_eq!(proxy_for("http://github.com/a.jar", &unset), None);
+        assert_eq!(proxy_for("https://mirror.corp/a.jar", &unset), None);
+
+        let set = with_configured_proxy(
+            |key| (key == "HTTPS_PROXY").then(|| "http://env:3128".to_string()),
+            configured,
+        );
+        assert_eq!(
+            proxy_for("https://github.com/a.jar", &set).as_deref(),
+            Some("http://env:3128")
+        );
+    }
+}
@@ -0,0 +1,81 @@
+//! Decoding of decompiler output that is not valid UTF-8.
+//!
+//! CFR and Vineflower normally write UTF-8, but a JVM running with a legacy
+//! default charset can emit string constants in it instead. Such output is
+//! decoded with a fixed fallback encoding so the cached text, and its content
+//! hash, are the same on every platform.
+
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum FallbackEncoding {
+    /// ISO-8859-1, every byte maps to the code point of the same value
+    #[default]
+    Latin1,
+    /// GBK, for output from JVMs running with a Chinese default charset
+    Gbk,
+}
+
+impl FallbackEncoding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Latin1 => "latin1",
+            Self::Gbk => "gbk",
+        }
+    }
+}
+
+/// Text decoded from decompiler output. `encoding` is set when the bytes were
+/// not valid UTF-8 and the fallback encoding was used instead.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DecodedOutput {
+    pub text: String,
+    pub encoding: Option<FallbackEncoding>,
+}
+
+/// Result-facing warning for output that needed the fallback `encoding`.
+pub fn non_utf8_warning(target: &str, encoding: &str) -> String {
+    format!("decompiler output for {target} was not valid UTF-8; decoded as {encoding}")
+}
+
+/// Decodes `bytes` as UTF-8, or with `fallback` if they are not valid UTF-8.
+/// CRLF line endings are normalized to LF either way.
+pub fn decode_output(bytes: &[u8], fallback: FallbackEncoding) -> DecodedOutput {
+    let (text, encoding) = match std::str::from_utf8(bytes) {
+        Ok(text) => (text.to_string(), None),
+        Err(_) => {
+            let text = match fallback {
+                FallbackEncoding::Latin1 => bytes.iter().map(|&b| char::from(b)).collect(),
+                FallbackEncoding::Gbk => encoding_rs::GBK
+                    .decode_without_bom_handling(bytes)
+                    .0
+                    .into_owned(),
+            };
+            (text, Some(fallback))
+        }
+    };
+    DecodedOutput {
+        text: text.replace("\r\n", "\n"),
+        encoding,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_output_falls_back_only_for_invalid_utf8() {
+        let utf8 = decode_output("caf\u{e9}\r\n".as_bytes(), FallbackEncoding::Gbk);
+        assert_eq!(utf8.text, "caf\u{e9}\n");
+        assert_eq!(utf8.encoding, None);
+
+        let latin1 = decode_output(b"caf\xe9", FallbackEncoding::Latin1);
+        assert_eq!(latin1.text, "caf\u{e9}");
+        assert_eq!(latin1.encoding, Some(FallbackEncoding::Latin1));
+
+        let gbk = decode_output(b"\xd6\xd0\xce\xc4", FallbackEncoding::Gbk);
+        assert_eq!(gbk.text, "\u{4e2d}\u{6587}");
+        assert_eq!(gbk.encoding, Some(FallbackEncoding::Gbk));
+    }
+}
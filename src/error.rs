@@ -0,0 +1,224 @@
+//! Failures callers can tell apart.
+//!
+//! Errors stay `anyhow::Error` throughout; the ones worth telling apart are
+//! raised as a [`ClassFinderError`] inside it. [`ClassFinderError::from_error`]
+//! finds one in an error chain, also recognizing the other typed errors
+//! ([`DecompileTimeout`], [`DeadlineExceeded`], [`FindOptionsError`]), and
+//! falls back to
+//! [`ClassFinderError::Internal`]. Each variant has a stable
+//! [`code`](ClassFinderError::code) and an
+//! [`exit_code`](ClassFinderError::exit_code); both are part of the CLI's
+//! interface and must not change.
+
+use serde::Serialize;
+use std::path::Path;
+
+use crate::deadline::{DeadlineExceeded, Phase};
+use crate::decompiler::DecompileTimeout;
+use crate::find_options::FindOptionsError;
+
+/// Exit status when no jar provides the class.
+pub const NOT_FOUND_EXIT_CODE: i32 = 2;
+
+/// Exit status when `--timeout` ran out, as with coreutils `timeout`.
+pub const TIMEOUT_EXIT_CODE: i32 = 124;
+
+/// Serialized with its code under `error` and its context fields next to
+/// it; the message is left to the caller.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "error", rename_all = "snake_case")]
+pub enum ClassFinderError {
+    NotFound {
+        class_name: String,
+        searched: String,
+    },
+    InvalidInput {
+        #[serde(skip)]
+        reason: String,
+    },
+    CorruptJar {
+        path: String,
+        #[serde(skip)]
+        reason: String,
+    },
+    DecompileFailed {
+        /// The class or jar that was being decompiled.
+        target: String,
+        #[serde(skip)]
+        reason: String,
+    },
+    DecompileTimeout {
+        target: String,
+        timeout_ms: u64,
+    },
+    CacheBusy {
+        path: String,
+        #[serde(skip)]
+        reason: String,
+    },
+    Timeout {
+        phase: Phase,
+        timeout_secs: u64,
+    },
+    Internal {
+        #[serde(skip)]
+        reason: String,
+    },
+}
+
+impl ClassFinderError {
+    pub fn invalid_input(reason: impl Into<String>) -> Self {
+        Self::InvalidInput {
+            reason: reason.into(),
+        }
+    }
+
+    pub fn corrupt_jar(path: &Path, reason: impl std::fmt::Display) -> Self {
+        Self::CorruptJar {
+            path: path.to_string_lossy().to_string(),
+            reason: reason.to_string(),
+        }
+    }
+
+    pub fn decompile_failed(target: impl Into<String>, reason: impl Into<String>) -> Self {
+        Self::DecompileFailed {
+            target: target.into(),
+            reason: reason.into(),
+        }
+    }
+
+    /// The first [`ClassFinderError`] (or other typed error) in `err`'s
+    /// chain, else [`ClassFinderError::Internal`] with the whole chain.
+    pub fn from_error(err: &anyhow::Error) -> Self {
+        for cause in err.chain() {
+            if let Some(err) = cause.downcast_ref::<ClassFinderError>() {
+                return err.clone();
+            }
+            if let Some(err) = cause.downcast_ref::<DeadlineExceeded>() {
+                return Self::Timeout {
+                    phase: err.phase,
+                    timeout_secs: err.timeout_secs,
+                };
+            }
+            if let Some(err) = cause.downcast_ref::<DecompileTimeout>() {
+                return Self::DecompileTimeout {
+                    target: err.target.clone(),
+                    timeout_ms: err.timeout.as_millis() as u64,
+                };
+            }
+            if let Some(err) = cause.downcast_ref::<FindOptionsError>() {
+                return Self::invalid_input(err.to_string());
+            }
+        }
+        Self::Internal {
+            reason: format!("{err:#}"),
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NotFound { .. } => "not_found",
+            Self::InvalidInput { .. } => "invalid_input",
+            Self::CorruptJar { .. } => "corrupt_jar",
+            Self::DecompileFailed { .. } => "decompile_failed",
+            Self::DecompileTimeout { .. } => "decompile_timeout",
+            Self::CacheBusy { .. } => "cache_busy",
+            Self::Timeout { .. } => "timeout",
+            Self::Internal { .. } => "internal",
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Internal { .. } => 1,
+            Self::NotFound { .. } => NOT_FOUND_EXIT_CODE,
+            Self::InvalidInput { .. } => 3,
+            Self::CorruptJar { .. } => 4,
+            Self::DecompileFailed { .. } => 5,
+            Self::DecompileTimeout { .. } => 6,
+            Self::CacheBusy { .. } => 7,
+            Self::Timeout { .. } => TIMEOUT_EXIT_CODE,
+        }
+    }
+}
+
+impl std::fmt::Display for ClassFinderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotFound {
+                class_name,
+                searched,
+            } => write!(f, "Class {class_name} not found (scan dir: {searched})"),
+            Self::InvalidInput { reason } | Self::Internal { reason } => f.write_str(reason),
+            Self::CorruptJar { path, reason } => {
+                write!(f, "Failed to read zip structure: {path}: {reason}")
+            }
+            Self::DecompileFailed { reason, .. } => f.write_str(reason),
+            Self::DecompileTimeout { target, timeout_ms } => {
+                write!(f, "Decompilation timed out after {timeout_ms}ms: {target}")
+            }
+            Self::CacheBusy { path, reason } => write!(f, "Cache {path} is busy: {reason}"),
+            Self::Timeout {
+                phase,
+                timeout_secs,
+            } => write!(
+                f,
+                "Operation timed out after {timeout_secs}s during {}",
+                phase.as_str()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ClassFinderError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+    use std::time::Duration;
+
+    #[test]
+    fn typed_errors_are_found_through_context() {
+        let err = anyhow::Error::new(ClassFinderError::NotFound {
+            class_name: "a.B".to_string(),
+            searched: "/m2".to_string(),
+        })
+        .context("while looking");
+        let found = ClassFinderError::from_error(&err);
+        assert_eq!(found.code(), "not_found");
+        assert_eq!(found.exit_code(), NOT_FOUND_EXIT_CODE);
+        assert_eq!(found.to_string(), "Class a.B not found (scan dir: /m2)");
+        assert_eq!(
+            serde_json::to_value(&found).unwrap(),
+            serde_json::json!({"error": "not_found", "class_name": "a.B", "searched": "/m2"})
+        );
+
+        let timeout: anyhow::Result<()> = Err(DecompileTimeout {
+            target: "a.B".to_string(),
+            timeout: Duration::from_millis(200),
+        }
+        .into());
+        let found = ClassFinderError::from_error(&timeout.context("find").unwrap_err());
+        assert_eq!(
+            found,
+            ClassFinderError::DecompileTimeout {
+                target: "a.B".to_string(),
+                timeout_ms: 200
+            }
+        );
+    }
+
+    #[test]
+    fn untyped_errors_are_internal_with_the_whole_chain() {
+        let err = anyhow::anyhow!("disk on fire").context("Failed to write");
+        let found = ClassFinderError::from_error(&err);
+        assert_eq!(found.code(), "internal");
+        assert_eq!(found.exit_code(), 1);
+        assert_eq!(found.to_string(), "Failed to write: disk on fire");
+        assert_eq!(
+            serde_json::to_value(&found).unwrap(),
+            serde_json::json!({"error": "internal"})
+        );
+    }
+}
@@ -0,0 +1,195 @@
+//! Writing the cached sources of one jar as a package-layout source tree.
+//!
+//! [`export_sources`] takes the class list from the jar itself, reads every
+//! cached source in one transaction and writes `org.example.Foo` to
+//! `<out>/org/example/Foo.java`. Nested types are printed inline by the
+//! decompilers, so by default the outer file carries them; with
+//! [`InnerClassLayout::Separate`] their cached entries are also written as
+//! `Outer$Inner.java`. Classes that are not cached are reported as missing,
+//! or filled in first (sources jar, then decompiler) when a decompiler is
+//! given for completion.
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::cache::{CachedClassSource, ClassContentSource, PersistentCache, class_source_key};
+use crate::catalog;
+use crate::decompiler::{Decompiler, DecompilerKind};
+use crate::parse::{ParsedClass, inner_classes};
+use crate::source;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum InnerClassLayout {
+    /// Nested types stay in their outer class's file.
+    #[default]
+    Merge,
+    /// Nested types also get an `Outer$Inner.java` file of their own.
+    Separate,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportResult {
+    pub jar_path: String,
+    pub out_dir: String,
+    pub files_written: usize,
+    /// Classes taken from the sources jar during completion.
+    pub from_sources_jar: usize,
+    /// Classes decompiled during completion.
+    pub decompiled: usize,
+    /// Classes of the jar without a cached source, left out of the tree.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub missing: Vec<String>,
+    pub duration_ms: u64,
+}
+
+/// Exports the sources cached for `jar_path` by `kind` into `out`. With
+/// `complete`, classes missing from the cache are read from the sources jar
+/// or decompiled, and cached, before the tree is written.
+pub fn export_sources(
+    cache: &PersistentCache,
+    kind: DecompilerKind,
+    jar_path: &Path,
+    out: &Path,
+    inner: InnerClassLayout,
+    complete: Option<&dyn Decompiler>,
+) -> Result<ExportResult> {
+    let start = Instant::now();
+    let jar_key = jar_path.to_string_lossy().to_string();
+    let top_level = catalog::catalog(jar_path)?;
+    let nested = match inner {
+        InnerClassLayout::Merge => Vec::new(),
+        InnerClassLayout::Separate => catalog::nested_classes(jar_path)?,
+    };
+
+    let (mut from_sources_jar, mut decompiled) = (0, 0);
+    if let Some(decompiler) = complete {
+        let cached = cache.cached_class_sources(&jar_key, kind, &top_level)?;
+        let missing: Vec<String> = top_level
+            .iter()
+            .filter(|name| !cached.contains_key(*name))
+            .cloned()
+            .collect();
+        (from_sources_jar, decompiled) = complete_cache(cache, decompiler, jar_path, &missing)?;
+    }
+
+    let wanted: Vec<String> = top_level.into_iter().chain(nested).collect();
+    let sources = cache.cached_class_sources(&jar_key, kind, &wanted)?;
+    let mut files_written = 0;
+    let mut missing = Vec::new();
+    for class_name in &wanted {
+        let Some(cached) = sources.get(class_name) else {
+            missing.push(class_name.clone());
+            continue;
+        };
+        let path = out.join(source_file_path(class_name));
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        }
+        std::fs::write(&path, &cached.content)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        files_written += 1;
+    }
+
+    Ok(ExportResult {
+        jar_path: jar_key,
+        out_dir: out.to_string_lossy().to_string(),
+        files_written,
+        from_sources_jar,
+        decompiled,
+        missing,
+        duration_ms: start.elapsed().as_millis() as u64,
+    })
+}
+
+/// `org/example/Outer$Inner.java` for `org.example.Outer$Inner`.
+pub fn source_file_path(class_name: &str) -> PathBuf {
+    format!("{}.java", class_name.replace('.', "/")).into()
+}
+
+/// Caches `missing` top-level classes and their nested types, preferring the
+/// sources jar over the decompiler. Returns how many came from each.
+fn complete_cache(
+    cache: &PersistentCache,
+    decompiler: &dyn Decompiler,
+    jar_path: &Path,
+    missing: &[String],
+) -> Result<(usize, usize)> {
+    if missing.is_empty() {
+        return Ok((0, 0));
+    }
+    let jar_key = jar_path.to_string_lossy().to_string();
+    let wanted: HashSet<&String> = missing.iter().collect();
+    let mut found = HashSet::new();
+    let mut entries = Vec::new();
+    let add = |mut cls: ParsedClass, source: ClassContentSource, entries: &mut Vec<_>| {
+        let decompiled = source == ClassContentSource::Decompiled;
+        let postprocessed = decompiled
+            && decompiler
+                .postprocessor()
+                .is_some_and(|p| p.apply(&mut cls));
+        let nested = inner_classes(&cls);
+        for cls in std::iter::once(cls).chain(nested) {
+            entries.push((
+                class_source_key(&cls.class_name, &jar_key, decompiler.kind()),
+                CachedClassSource {
+                    content: cls.content,
+                    source: source.clone(),
+                    options: if decompiled {
+                        decompiler.options()
+                    } else {
+                        Vec::new()
+                    },
+                    decompiler_version: decompiled.then(|| decompiler.version()).flatten(),
+                    encoding: cls.encoding,
+                    postprocessed,
+                },
+            ));
+        }
+    };
+
+    for cls in source::read_jar_sources(jar_path).unwrap_or_default() {
+        if wanted.contains(&cls.class_name) && found.insert(cls.class_name.clone()) {
+            add(cls, ClassContentSource::SourcesJar, &mut entries);
+        }
+    }
+    let from_sources_jar = found.len();
+
+    let rest: Vec<String> = missing
+        .iter()
+        .filter(|name| !found.contains(*name))
+        .cloned()
+        .collect();
+    let mut decompiled = 0;
+    if !rest.is_empty() {
+        let batch = decompiler.decompile_classes(jar_path, &rest)?;
+        for cls in batch.classes.into_iter().filter(|cls| !cls.failed) {
+            decompiled += 1;
+            add(cls, ClassContentSource::Decompiled, &mut entries);
+        }
+    }
+    cache.put_class_sources(&entries)?;
+    Ok((from_sources_jar, decompiled))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn source_files_follow_the_package() {
+        assert_eq!(
+            source_file_path("org.example.Foo"),
+            PathBuf::from("org/example/Foo.java")
+        );
+        assert_eq!(
+            source_file_path("org.example.Outer$Inner"),
+            PathBuf::from("org/example/Outer$Inner.java")
+        );
+        assert_eq!(source_file_path("Main"), PathBuf::from("Main.java"));
+    }
+}
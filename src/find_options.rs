@@ -0,0 +1,269 @@
+//! Which versions of a class a find returns.
+//!
+//! [`FindOptions`] is built with `with_*` calls and checked by
+//! [`FindOptions::validate`] before any jar is read, so the CLI and library
+//! callers reject the same combinations with the same [`FindOptionsError`].
+
+use std::path::PathBuf;
+
+use crate::scan::extract_version_from_maven_path;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FindOptions {
+    version: Option<String>,
+    latest: bool,
+    max_versions: Option<usize>,
+    max_matches: Option<usize>,
+    fresh_scan: bool,
+    verify_jars: bool,
+}
+
+/// A combination of [`FindOptions`] that cannot be satisfied. It surfaces
+/// as [`ClassFinderError::InvalidInput`](crate::error::ClassFinderError).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FindOptionsError {
+    EmptyVersion,
+    LatestWithVersion,
+    LatestWithMaxVersions,
+    VersionWithMaxVersions,
+    ZeroMaxVersions,
+    ZeroMaxMatches,
+}
+
+impl FindOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only jars of exactly this version.
+    pub fn with_version(mut self, version: Option<&str>) -> Self {
+        self.version = version.map(str::to_string);
+        self
+    }
+
+    /// Only the newest version.
+    pub fn with_latest(mut self, latest: bool) -> Self {
+        self.latest = latest;
+        self
+    }
+
+    /// Only the newest `max` versions.
+    pub fn with_max_versions(mut self, max: Option<usize>) -> Self {
+        self.max_versions = max;
+        self
+    }
+
+    /// Stops a scan once this many jars hold the class. The jars kept are
+    /// the first ones in scan order, so the result does not depend on which
+    /// probes finish first; registry hits are never cut short.
+    pub fn with_max_matches(mut self, max: Option<usize>) -> Self {
+        self.max_matches = max;
+        self
+    }
+
+    /// Walks the repositories even when a stored jar list of the scanned
+    /// root is still fresh, and stores the new one.
+    pub fn with_fresh_scan(mut self, fresh_scan: bool) -> Self {
+        self.fresh_scan = fresh_scan;
+        self
+    }
+
+    /// Opens every registry hit to check it still holds the class, also
+    /// the jars unchanged since they were cataloged.
+    pub fn with_verify_jars(mut self, verify_jars: bool) -> Self {
+        self.verify_jars = verify_jars;
+        self
+    }
+
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    pub fn latest(&self) -> bool {
+        self.latest
+    }
+
+    pub fn max_versions(&self) -> Option<usize> {
+        self.max_versions
+    }
+
+    pub fn max_matches(&self) -> Option<usize> {
+        self.max_matches
+    }
+
+    pub fn fresh_scan(&self) -> bool {
+        self.fresh_scan
+    }
+
+    pub fn verify_jars(&self) -> bool {
+        self.verify_jars
+    }
+
+    pub fn validate(&self) -> Result<(), FindOptionsError> {
+        if self.version.as_deref().is_some_and(|v| v.trim().is_empty()) {
+            return Err(FindOptionsError::EmptyVersion);
+        }
+        if self.max_versions == Some(0) {
+            return Err(FindOptionsError::ZeroMaxVersions);
+        }
+        if self.max_matches == Some(0) {
+            return Err(FindOptionsError::ZeroMaxMatches);
+        }
+        match (self.version.is_some(), self.latest, self.max_versions) {
+            (true, true, _) => Err(FindOptionsError::LatestWithVersion),
+            (_, true, Some(_)) => Err(FindOptionsError::LatestWithMaxVersions),
+            (true, _, Some(_)) => Err(FindOptionsError::VersionWithMaxVersions),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether a jar of this version can be part of the result at all.
+    pub(crate) fn accepts(&self, jar: &std::path::Path) -> bool {
+        self.version
+            .as_deref()
+            .is_none_or(|v| extract_version_from_maven_path(jar).as_deref() == Some(v))
+    }
+
+    /// Drops the jars of all but the newest versions asked for; `jars` is in
+    /// version order.
+    pub(crate) fn select(&self, jars: &mut Vec<PathBuf>) {
+        let keep = match (self.latest, self.max_versions) {
+            (true, _) => 1,
+            (false, Some(max)) => max,
+            (false, None) => return,
+        };
+        let mut versions: Vec<Option<String>> = jars
+            .iter()
+            .map(|jar| extract_version_from_maven_path(jar))
+            .collect();
+        versions.dedup();
+        let Some(oldest_kept) = versions
+            .len()
+            .checked_sub(keep)
+            .map(|i| versions[i].clone())
+        else {
+            return;
+        };
+        jars.retain(|jar| extract_version_from_maven_path(jar) >= oldest_kept);
+    }
+}
+
+impl std::fmt::Display for FindOptionsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::EmptyVersion => "--version must not be empty",
+            Self::LatestWithVersion => "--latest cannot be combined with --version",
+            Self::LatestWithMaxVersions => "--latest cannot be combined with --max-versions",
+            Self::VersionWithMaxVersions => "--version cannot be combined with --max-versions",
+            Self::ZeroMaxVersions => "--max-versions must be at least 1",
+            Self::ZeroMaxMatches => "--max-matches must be at least 1",
+        })
+    }
+}
+
+impl std::error::Error for FindOptionsError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jars(versions: &[&str]) -> Vec<PathBuf> {
+        versions
+            .iter()
+            .map(|v| PathBuf::from(format!("/m2/org/example/demo/{v}/demo-{v}.jar")))
+            .collect()
+    }
+
+    #[test]
+    fn validation_matrix() {
+        let version = || FindOptions::new().with_version(Some("1.0"));
+        let cases = [
+            (FindOptions::new(), Ok(())),
+            (version(), Ok(())),
+            (FindOptions::new().with_latest(true), Ok(())),
+            (FindOptions::new().with_max_versions(Some(2)), Ok(())),
+            (
+                FindOptions::new().with_version(Some(" ")),
+                Err(FindOptionsError::EmptyVersion),
+            ),
+            (
+                version().with_latest(true),
+                Err(FindOptionsError::LatestWithVersion),
+            ),
+            (
+                FindOptions::new()
+                    .with_latest(true)
+                    .with_max_versions(Some(2)),
+                Err(FindOptionsError::LatestWithMaxVersions),
+            ),
+            (
+                version().with_max_versions(Some(2)),
+                Err(FindOptionsError::VersionWithMaxVersions),
+            ),
+            (
+                FindOptions::new().with_max_versions(Some(0)),
+                Err(FindOptionsError::ZeroMaxVersions),
+            ),
+            (
+                version().with_latest(true).with_max_versions(Some(0)),
+                Err(FindOptionsError::ZeroMaxVersions),
+            ),
+            (
+                FindOptions::new()
+                    .with_latest(true)
+                    .with_max_matches(Some(5)),
+                Ok(()),
+            ),
+            (
+                FindOptions::new().with_max_matches(Some(0)),
+                Err(FindOptionsError::ZeroMaxMatches),
+            ),
+        ];
+        for (options, expected) in cases {
+            assert_eq!(options.validate(), expected, "{options:?}");
+        }
+    }
+
+    #[test]
+    fn errors_are_invalid_input() {
+        use crate::error::ClassFinderError;
+
+        let err = anyhow::Error::new(FindOptionsError::LatestWithVersion);
+        let found = ClassFinderError::from_error(&err);
+        assert_eq!(found.code(), "invalid_input");
+        assert_eq!(
+            found.to_string(),
+            "--latest cannot be combined with --version"
+        );
+    }
+
+    #[test]
+    fn select_keeps_the_newest_versions() {
+        let mut all = jars(&["1.0", "2.0", "2.0", "3.0"]);
+        FindOptions::new().select(&mut all);
+        assert_eq!(all.len(), 4);
+
+        FindOptions::new()
+            .with_max_versions(Some(2))
+            .select(&mut all);
+        assert_eq!(all, jars(&["2.0", "2.0", "3.0"]));
+
+        FindOptions::new().with_latest(true).select(&mut all);
+        assert_eq!(all, jars(&["3.0"]));
+
+        let mut few = jars(&["1.0"]);
+        FindOptions::new()
+            .with_max_versions(Some(5))
+            .select(&mut few);
+        assert_eq!(few, jars(&["1.0"]));
+    }
+
+    #[test]
+    fn accepts_only_the_requested_version() {
+        let all = jars(&["1.0", "2.0"]);
+        let options = FindOptions::new().with_version(Some("2.0"));
+        assert!(!options.accepts(&all[0]));
+        assert!(options.accepts(&all[1]));
+        assert!(FindOptions::new().accepts(&all[0]));
+    }
+}
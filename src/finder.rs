@@ -0,0 +1,2603 @@
+//! The library entry point: finding, loading, warming and indexing classes.
+//!
+//! [`ClassFinder`] owns what the CLI commands share — the cache, the class
+//! registry, hotspot tracking, the decompiler and the repositories to search
+//! — so the same operations can be embedded without spawning the binary.
+//! Each operation goes through its own write buffer and flushes it before
+//! returning, unless one long-lived buffer is set with
+//! [`ClassFinder::with_write_buffer`].
+//!
+//! `find` runs in two phases: [`ClassFinder::match_class`] finds the jars
+//! providing the class without reading any content, and
+//! [`ClassFinder::find_matched`] reads or decompiles each match.
+//! [`ClassFinder::find`] does both.
+
+use anyhow::{Context, Result};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::buffer::{
+    BufferConfig, BufferStats, FlushReport, Mutation, PendingWrite, Table, WriteBuffer,
+};
+use crate::cache::{
+    BackgroundWarmupStats, CacheStats, ClassContentSource, PersistentCache, class_source_key,
+    content_hash, loaded_jar_key,
+};
+use crate::catalog;
+use crate::deadline::{self, DeadlineExceeded, Phase};
+use crate::decompiler::{Decompiler, DecompilerKind};
+use crate::encoding::{DecodedOutput, non_utf8_warning};
+use crate::error::ClassFinderError;
+use crate::find_options::FindOptions;
+use crate::hotspot::{
+    DEFAULT_WARMUP_THRESHOLD, HotspotTracker, MAX_TRACKED_CLASSES_PER_JAR, WarmupRequest,
+};
+use crate::incremental::IncrementalIndexer;
+use crate::interrupt;
+use crate::javap::Javap;
+use crate::parse::{ParsedClass, hash_content, is_failed_decompilation, parse_decompiled_output};
+use crate::probe::{find_class_fqns_in_jar, jar_contains_class};
+use crate::progress::{NoProgress, ProgressEvent, ProgressSink};
+use crate::registry::ClassRegistry;
+use crate::repository::{Repository, RepositoryLayout, repository_for};
+use crate::scan::{
+    ExclusionCount, JarExclusions, class_name_to_class_path, extract_version_from_maven_path,
+    infer_scan_path, infer_search_paths, resolve_jar_reference, scan_jars,
+};
+use crate::scan_cache::ScanCache;
+use crate::session::ReadSession;
+use crate::source;
+use crate::structure::{ClassStructure, parse_compilation_unit};
+use crate::warmup::{
+    QueuedJar, Warmer, WarmerConfig, WarmupMode, WarmupOrder, WarmupPriority, WarmupQueue,
+    WarmupTask, order_jars,
+};
+
+/// Finds, loads, warms up and indexes classes against one cache.
+pub struct ClassFinder {
+    cache: PersistentCache,
+    registry: ClassRegistry,
+    hotspot: HotspotTracker,
+    queue: WarmupQueue,
+    decompiler: Option<Arc<dyn Decompiler>>,
+    javap: Option<Javap>,
+    /// Root for hotspot group rankings.
+    m2_repo: PathBuf,
+    /// In search order. Also where `--group` directories and `--from-file`
+    /// coordinates are looked up.
+    repositories: Vec<Repository>,
+    exclusions: JarExclusions,
+    buffer_config: BufferConfig,
+    buffer: Option<WriteBuffer>,
+    cache_package_info: bool,
+    cache_structures: bool,
+    progress: Arc<dyn ProgressSink>,
+    scan_cache: ScanCache,
+}
+
+impl ClassFinder {
+    /// Opens (creating it when missing) the cache at `db_path`, searching
+    /// only the local repository at `m2_repo`. Matching and indexing need
+    /// nothing else; reading class content needs
+    /// [`ClassFinder::with_decompiler`].
+    pub fn open(db_path: PathBuf, m2_repo: PathBuf) -> Result<Self> {
+        let cache = PersistentCache::open(db_path)?;
+        Ok(Self {
+            registry: ClassRegistry::new(cache.db()),
+            hotspot: HotspotTracker::new(cache.db(), DEFAULT_WARMUP_THRESHOLD),
+            queue: WarmupQueue::new(cache.db()),
+            scan_cache: ScanCache::new(cache.db()),
+            cache,
+            decompiler: None,
+            javap: None,
+            repositories: vec![Repository::local(m2_repo.clone())],
+            m2_repo,
+            exclusions: JarExclusions::default(),
+            buffer_config: BufferConfig::default(),
+            buffer: None,
+            cache_package_info: false,
+            cache_structures: false,
+            progress: Arc::new(NoProgress),
+        })
+    }
+
+    pub fn with_decompiler(mut self, decompiler: Arc<dyn Decompiler>) -> Self {
+        self.decompiler = Some(decompiler);
+        self
+    }
+
+    /// Repositories to search, in priority order, replacing the local one.
+    pub fn with_repositories(mut self, repositories: Vec<Repository>) -> Self {
+        self.repositories = repositories;
+        self
+    }
+
+    /// Falls back to a javap listing for classes the decompiler fails on.
+    pub fn with_javap(mut self, javap: Javap) -> Self {
+        self.javap = Some(javap);
+        self
+    }
+
+    /// Jars left out of `find` matches and warmup targets.
+    pub fn with_exclusions(mut self, exclusions: JarExclusions) -> Self {
+        self.exclusions = exclusions;
+        self
+    }
+
+    pub fn with_warmup_threshold(mut self, threshold: u32) -> Self {
+        self.hotspot = HotspotTracker::new(self.cache.db(), threshold);
+        self
+    }
+
+    /// Configuration of the write buffer each operation opens.
+    pub fn with_buffer_config(mut self, config: BufferConfig) -> Self {
+        self.buffer_config = config;
+        self
+    }
+
+    /// Keeps one write buffer open for the finder's lifetime instead of one
+    /// per operation. Operations still flush it before returning.
+    pub fn with_write_buffer(mut self, config: BufferConfig) -> Self {
+        self.buffer_config = config;
+        self.buffer = Some(WriteBuffer::new(
+            self.cache.db(),
+            config,
+            self.cache.pending_gauge_path(),
+        ));
+        self
+    }
+
+    /// Also caches `package-info` and `module-info` sources.
+    pub fn with_cache_package_info(mut self, enabled: bool) -> Self {
+        self.cache_package_info = enabled;
+        self
+    }
+
+    /// Parses and caches the structure of every class a load or warmup adds.
+    pub fn with_cache_structures(mut self, enabled: bool) -> Self {
+        self.cache_structures = enabled;
+        self
+    }
+
+    /// Receives the progress events of loads, warmups and indexing.
+    pub fn with_progress(mut self, progress: Arc<dyn ProgressSink>) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// How long the jar list of a scanned root is reused; zero walks on
+    /// every scan.
+    pub fn with_scan_cache_max_age(mut self, max_age: Duration) -> Self {
+        self.scan_cache = self.scan_cache.with_max_age(max_age);
+        self
+    }
+
+    pub fn cache(&self) -> &PersistentCache {
+        &self.cache
+    }
+
+    pub fn registry(&self) -> &ClassRegistry {
+        &self.registry
+    }
+
+    pub fn hotspot(&self) -> &HotspotTracker {
+        &self.hotspot
+    }
+
+    pub fn scan_cache(&self) -> &ScanCache {
+        &self.scan_cache
+    }
+
+    /// A session over this finder's cache for [`ClassFinder::match_class_in`]
+    /// and [`ClassFinder::find_matched_in`].
+    pub fn read_session(&self) -> ReadSession<'_> {
+        ReadSession::new(self.cache.env())
+    }
+
+    pub fn decompiler(&self) -> Option<&Arc<dyn Decompiler>> {
+        self.decompiler.as_ref()
+    }
+
+    fn require_decompiler(&self) -> Result<&dyn Decompiler> {
+        self.decompiler.as_deref().ok_or_else(|| {
+            ClassFinderError::invalid_input("No decompiler configured; see with_decompiler").into()
+        })
+    }
+
+    pub fn repositories(&self) -> &[Repository] {
+        &self.repositories
+    }
+
+    /// Finds `class_name` (fully qualified or simple) in every matching jar
+    /// `options` selects. No match is a [`ClassFinderError::NotFound`];
+    /// invalid options are a
+    /// [`FindOptionsError`](crate::find_options::FindOptionsError).
+    pub fn find(&self, class_name: &str, options: &FindOptions) -> Result<FindResult> {
+        let mut session = self.read_session();
+        let found = self.match_class_in(&mut session, class_name, options)?;
+        self.find_matched_in(&mut session, found)
+    }
+
+    /// The matching phase of [`ClassFinder::find`]. No match is an empty
+    /// [`ClassMatch::jars`] rather than an error.
+    pub fn match_class(&self, class_name: &str, options: &FindOptions) -> Result<ClassMatch> {
+        self.match_class_in(&mut self.read_session(), class_name, options)
+    }
+
+    /// [`ClassFinder::match_class`], reading the registry through `session`.
+    pub fn match_class_in(
+        &self,
+        session: &mut ReadSession<'_>,
+        class_name: &str,
+        options: &FindOptions,
+    ) -> Result<ClassMatch> {
+        let _span = tracing::info_span!("find", class_name).entered();
+        match_class_jars(
+            &self.registry,
+            session,
+            &self.scan_cache,
+            &self.repositories,
+            &self.exclusions,
+            class_name,
+            options,
+        )
+    }
+
+    /// Caches the sources of every class in `jar_path`, from its sources jar
+    /// where there is one and from the decompiler otherwise, then marks the
+    /// jar warmed.
+    pub fn load_jar(&self, jar_path: &Path) -> Result<LoadResult> {
+        let decompiler = self.require_decompiler()?;
+        let output = self.with_buffer(|buffer| {
+            load_jar(
+                &self.cache,
+                &self.registry,
+                buffer,
+                decompiler,
+                jar_path,
+                WarmupMode::AllClasses,
+                self.cache_package_info,
+            )
+        })?;
+        if self.cache_structures {
+            cache_jar_structures(&self.cache, &output.jar_path, decompiler.kind())?;
+        }
+        if !output.skipped {
+            self.progress.event(ProgressEvent::jar_decompiled(
+                &output.jar_path,
+                output.classes_loaded,
+                Duration::from_millis(output.duration_ms),
+            ));
+            let _ = self
+                .hotspot
+                .mark_warmed(&output.jar_path, output.classes_loaded as u32);
+        }
+        Ok(output)
+    }
+
+    /// Loads the jars `params` select, calling `report` after each one and
+    /// periodically with a summary.
+    pub fn warmup(
+        &self,
+        params: WarmupParams<'_>,
+        report: &mut (dyn FnMut(&WarmupProgress<'_>) + Send),
+    ) -> Result<WarmupResult> {
+        let decompiler = self.require_decompiler()?;
+        let output =
+            self.with_buffer(|buffer| warmup_targets(self, decompiler, buffer, params, report))?;
+        for (jar_key, class_count) in &output.loaded_jars {
+            let _ = self.hotspot.mark_warmed(jar_key, *class_count);
+            if self.cache_structures {
+                cache_jar_structures(&self.cache, jar_key, decompiler.kind())?;
+            }
+        }
+        Ok(output)
+    }
+
+    /// Catalogs every jar under `root` that is not cataloged yet.
+    pub fn index(&self, root: PathBuf) -> Result<IndexResult> {
+        index_repo(&self.registry, root, self.progress.as_ref())
+    }
+
+    /// Catalogs only the jars under `root` that are new or modified since the
+    /// last incremental run, per the mtime table, and reports the jars that
+    /// disappeared, deleting their data with `purge_removed`.
+    pub fn index_incremental(&self, root: PathBuf, purge_removed: bool) -> Result<IndexResult> {
+        index_repo_incremental(
+            &self.cache,
+            &self.registry,
+            root,
+            self.repositories.iter().map(|r| r.path.clone()).collect(),
+            purge_removed,
+            Arc::clone(&self.progress),
+        )
+    }
+
+    pub fn stats(&self) -> Result<CacheStats> {
+        self.cache.stats()
+    }
+
+    /// Runs `f` on the long-lived buffer, or on one opened for the call, and
+    /// flushes it.
+    fn with_buffer<R>(&self, f: impl FnOnce(&WriteBuffer) -> Result<R>) -> Result<R> {
+        let flushed = |report: FlushReport| {
+            self.progress.event(ProgressEvent::FlushCompleted {
+                written: report.written,
+                duration_ms: report.duration.as_millis() as u64,
+            });
+        };
+        if let Some(buffer) = &self.buffer {
+            let result = f(buffer)?;
+            flushed(buffer.flush()?);
+            return Ok(result);
+        }
+        let mut buffer = WriteBuffer::new(
+            self.cache.db(),
+            self.buffer_config,
+            self.cache.pending_gauge_path(),
+        );
+        let result = f(&buffer)?;
+        flushed(buffer.flush()?);
+        buffer.shutdown_and_flush()?;
+        Ok(result)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct FindVersion {
+    pub version: Option<String>,
+    pub jar_path: String,
+    /// Name of the repository the jar lives in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+    pub content_hash: String,
+    pub content: String,
+    pub cache_hit: bool,
+    pub source: String,
+    pub lookup_source: String,
+    /// Set when `content` is a javap listing rather than Java source.
+    pub fallback: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub decompiler_options: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decompiler_version: Option<String>,
+    /// Wall time of the decompile call for fresh results, to compare one-shot
+    /// CFR with `--cfr-server`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decompile_ms: Option<u64>,
+    /// Fallback encoding used because the decompiler output was not UTF-8.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+    /// Rewritten by the configured `postprocess` command.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub postprocessed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub structure: Option<ClassStructure>,
+    /// Javadoc of the class, with `--with-javadoc`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub javadoc: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FindResult {
+    pub class_name: String,
+    pub scanned_root: String,
+    pub matched_jars: usize,
+    pub duration_ms: u64,
+    pub versions: Vec<FindVersion>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LoadResult {
+    pub jar_path: String,
+    pub classes_loaded: usize,
+    pub classes_failed: usize,
+    /// Classes already cached with the same content, so not written again.
+    pub classes_skipped_cached: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decompiler_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+    pub skipped: bool,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WarmupResult {
+    pub targets: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub classes_failed: usize,
+    pub duration_ms: u64,
+    /// What stopped the run early: `max-duration`, `max-classes`, or
+    /// `interrupted` (Ctrl-C).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stopped_by: Option<&'static str>,
+    /// Targets not started because the run stopped early.
+    pub not_started: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub excluded: Vec<ExclusionCount>,
+    pub loads: Vec<LoadResult>,
+    pub loaded_jars: Vec<(String, u32)>,
+    pub buffer: BufferStats,
+}
+
+/// Emitted by `warmup_targets` after each jar, and every
+/// `WARMUP_SUMMARY_INTERVAL` as a summary of what is left.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum WarmupProgress<'a> {
+    Jar {
+        index: usize,
+        total: usize,
+        jar_path: &'a str,
+        ok: bool,
+        classes_loaded: usize,
+        skipped: bool,
+        duration_ms: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    Summary {
+        completed: usize,
+        remaining: usize,
+        succeeded: usize,
+        failed: usize,
+        elapsed_ms: u64,
+    },
+}
+
+pub const WARMUP_SUMMARY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Share of a jar's classes that must already be cached for `load_jar` to
+/// decompile only the rest instead of running the whole jar.
+const CACHED_COVERAGE_THRESHOLD: f64 = 0.5;
+
+#[derive(Debug, Serialize)]
+pub struct IndexResult {
+    pub root: String,
+    pub scanned_jars: usize,
+    pub cataloged_jars_new: usize,
+    pub indexed_classes: usize,
+    pub duration_ms: u64,
+    pub failed_jars: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changed_jars: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unchanged_jars: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refreshed_jars: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub removed_jars: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub purged_jars: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unsettled_jars: Option<usize>,
+}
+
+/// Jars a scan with [`FindOptions::with_max_matches`] probes at a time.
+const PROBE_CHUNK_SIZE: usize = 64;
+
+/// Scans one repository for the jars `probe` accepts: a Maven one from the
+/// narrowest package directories outwards before the whole root; a flat one
+/// only as a whole. `scan` lists the jars under a directory.
+fn scan_repository_for_class(
+    repository: &Repository,
+    class_name: &str,
+    max_matches: Option<usize>,
+    scan: &mut dyn FnMut(&Path) -> Result<Vec<PathBuf>>,
+    probe: &(dyn Fn(&Path) -> bool + Sync),
+) -> Result<(Vec<PathBuf>, PathBuf)> {
+    let root = repository.path.as_path();
+    let search_paths = match repository.layout {
+        RepositoryLayout::Maven => infer_search_paths(root, class_name),
+        RepositoryLayout::Flat => vec![root.to_path_buf()],
+    };
+    for candidate_root in &search_paths {
+        tracing::info!("find scan root: {}", candidate_root.display());
+        let matched = jars_containing_class(scan(candidate_root)?, max_matches, probe)?;
+        if !matched.is_empty() {
+            return Ok((matched, candidate_root.clone()));
+        }
+    }
+    if search_paths.first().map(PathBuf::as_path) != Some(root) {
+        tracing::info!("find fallback scan root: {}", root.display());
+        return Ok((
+            jars_containing_class(scan(root)?, max_matches, probe)?,
+            root.to_path_buf(),
+        ));
+    }
+    Ok((Vec::new(), root.to_path_buf()))
+}
+
+/// The `jars` that `probe` accepts. With `max_matches` they are
+/// probed newest version first, in parallel a chunk at a time, and probing
+/// stops after the chunk that reaches the limit; the first `max_matches`
+/// hits in that order are kept, whichever probes finished first.
+fn jars_containing_class(
+    mut jars: Vec<PathBuf>,
+    max_matches: Option<usize>,
+    probe: &(dyn Fn(&Path) -> bool + Sync),
+) -> Result<Vec<PathBuf>> {
+    deadline::enter(Phase::Probe);
+    let limit = max_matches.unwrap_or(usize::MAX);
+    let chunk_size = match max_matches {
+        Some(_) => {
+            // The walk's order varies from run to run.
+            jars.sort_by_cached_key(|jar| {
+                (
+                    std::cmp::Reverse(extract_version_from_maven_path(jar)),
+                    jar.clone(),
+                )
+            });
+            PROBE_CHUNK_SIZE
+        }
+        None => jars.len().max(1),
+    };
+    let mut matched = Vec::new();
+    for chunk in jars.chunks(chunk_size) {
+        if matched.len() >= limit {
+            break;
+        }
+        let hits: Vec<PathBuf> = chunk
+            .par_iter()
+            .filter(|jar| !deadline::expired() && probe(jar))
+            .cloned()
+            .collect();
+        deadline::check()?;
+        matched.extend(hits);
+    }
+    matched.truncate(limit);
+    Ok(matched)
+}
+
+pub fn repository_roots(repositories: &[Repository]) -> String {
+    repositories
+        .iter()
+        .map(|r| r.path.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Jars providing a class, in version order, found without reading any
+/// class content.
+#[derive(Clone)]
+pub struct ClassMatch {
+    /// Fully qualified, also when a simple name was looked up.
+    pub class_name: String,
+    pub jars: Vec<PathBuf>,
+    pub scan_root: PathBuf,
+    /// `registry` or `scan`.
+    pub lookup_source: String,
+    /// When matching began, so `find` reports the time of both phases.
+    started: Instant,
+}
+
+impl ClassMatch {
+    pub(crate) fn into_result(self, versions: Vec<FindVersion>) -> FindResult {
+        FindResult {
+            class_name: self.class_name,
+            scanned_root: self.scan_root.to_string_lossy().to_string(),
+            matched_jars: self.jars.len(),
+            duration_ms: self.started.elapsed().as_millis() as u64,
+            versions,
+        }
+    }
+}
+
+/// The matching phase of `find`: registry hits, else a scan of each
+/// repository in order; a simple name is resolved by scanning all of them.
+/// Scans list jars through `scan_cache`. No match is an empty
+/// [`ClassMatch::jars`] rather than an error.
+pub fn match_class_jars(
+    registry: &ClassRegistry,
+    session: &mut ReadSession<'_>,
+    scan_cache: &ScanCache,
+    repositories: &[Repository],
+    exclusions: &JarExclusions,
+    class_name: &str,
+    options: &FindOptions,
+) -> Result<ClassMatch> {
+    options.validate()?;
+    let started = Instant::now();
+    let primary_root = repositories
+        .first()
+        .map(|r| r.path.clone())
+        .context("No repository to search")?;
+    let roots: Vec<PathBuf> = repositories.iter().map(|r| r.path.clone()).collect();
+    let (class_name, mut jars_matched, scan_root, lookup_source) = if class_name.contains('.') {
+        let class_path = class_name_to_class_path(class_name);
+        let mut registry_hits: Vec<PathBuf> = registry
+            .get_artifacts_in(session, class_name)?
+            .into_iter()
+            .map(PathBuf::from)
+            .filter(|p| p.exists())
+            .collect();
+
+        registry_hits.retain(|p| options.accepts(p));
+        let current = registry.rule_out_in(session, &class_path, &mut registry_hits)?;
+
+        // A jar with the size and mtime it was cataloged with still holds
+        // what the registry lists for it; only the others are opened.
+        let trusted = if options.verify_jars() {
+            HashSet::new()
+        } else {
+            current
+        };
+        deadline::enter(Phase::Probe);
+        registry_hits.retain(|jar| {
+            trusted.contains(jar)
+                || (!deadline::expired() && jar_contains_class(jar, &class_path).unwrap_or(false))
+        });
+        deadline::check()?;
+
+        if !registry_hits.is_empty() {
+            (
+                class_name.to_string(),
+                registry_hits,
+                infer_scan_path(&primary_root, class_name),
+                "registry".to_string(),
+            )
+        } else {
+            let mut matched: Vec<PathBuf> = Vec::new();
+            let mut used_scan_root = primary_root.clone();
+            // Jars the result would drop are not probed, so they never count
+            // towards `max_matches`.
+            let probe = |jar: &Path| {
+                options.accepts(jar)
+                    && !exclusions.excludes(&roots, jar)
+                    && jar_contains_class(jar, &class_path).unwrap_or(false)
+            };
+
+            // Cataloged jars whose class filter rules the class out are
+            // never opened.
+            let mut scan = |dir: &Path| -> Result<Vec<PathBuf>> {
+                let mut jars = scan_cache.jars(session, dir, options.fresh_scan())?;
+                let before = jars.len();
+                registry.rule_out_in(session, &class_path, &mut jars)?;
+                let ruled_out = before - jars.len();
+                if ruled_out > 0 {
+                    tracing::debug!(
+                        "class filters ruled out {ruled_out} jar(s) under {}",
+                        dir.display()
+                    );
+                }
+                Ok(jars)
+            };
+
+            // Repositories are in priority order; the first one holding the
+            // class wins.
+            for repository in repositories {
+                let (jars, root) = scan_repository_for_class(
+                    repository,
+                    class_name,
+                    options.max_matches(),
+                    &mut scan,
+                    &probe,
+                )?;
+                used_scan_root = root;
+                if !jars.is_empty() {
+                    matched = jars;
+                    break;
+                }
+            }
+
+            (
+                class_name.to_string(),
+                matched,
+                used_scan_root,
+                "scan".to_string(),
+            )
+        }
+    } else {
+        let mut jars = Vec::new();
+        for repository in repositories {
+            jars.extend(scan_cache.jars(session, &repository.path, options.fresh_scan())?);
+        }
+
+        deadline::enter(Phase::Probe);
+        let mut fqn_to_jars: HashMap<String, Vec<PathBuf>> = HashMap::new();
+        for jar in jars.iter() {
+            deadline::check()?;
+            let fqns = find_class_fqns_in_jar(jar, class_name).unwrap_or_default();
+            for fqn in fqns.into_iter().take(1) {
+                fqn_to_jars.entry(fqn).or_default().push(jar.clone());
+            }
+        }
+
+        let (best_fqn, best_jars) = fqn_to_jars
+            .into_iter()
+            .max_by(|(a_name, a_jars), (b_name, b_jars)| {
+                a_jars
+                    .len()
+                    .cmp(&b_jars.len())
+                    .then_with(|| a_name.cmp(b_name))
+            })
+            .unwrap_or_else(|| (class_name.to_string(), Vec::new()));
+
+        (best_fqn, best_jars, primary_root, "scan".to_string())
+    };
+
+    jars_matched.retain(|p| options.accepts(p));
+    for count in exclusions.apply(&roots, &mut jars_matched) {
+        if count.removed > 0 {
+            tracing::info!(
+                "--{} {} removed {} jar(s)",
+                count.option,
+                count.pattern,
+                count.removed
+            );
+        }
+    }
+
+    jars_matched.sort_by(|a, b| {
+        extract_version_from_maven_path(a).cmp(&extract_version_from_maven_path(b))
+    });
+    options.select(&mut jars_matched);
+
+    Ok(ClassMatch {
+        class_name,
+        jars: jars_matched,
+        scan_root,
+        lookup_source,
+        started,
+    })
+}
+
+impl ClassFinder {
+    /// The decompiling phase of [`ClassFinder::find`]: each matched jar's
+    /// cached source, else its sources jar, else the decompiler, falling back
+    /// to javap when configured.
+    pub fn find_matched(&self, found: ClassMatch) -> Result<FindResult> {
+        self.find_matched_in(&mut self.read_session(), found)
+    }
+
+    /// [`ClassFinder::find_matched`], reading the cache through `session`,
+    /// which is released before each decompiler run.
+    pub fn find_matched_in(
+        &self,
+        session: &mut ReadSession<'_>,
+        found: ClassMatch,
+    ) -> Result<FindResult> {
+        let _span = tracing::info_span!("find", class_name = %found.class_name).entered();
+        self.check_matched(&found)?;
+        let decompiler = self.require_decompiler()?;
+        let mut versions = Vec::new();
+        for jar_path in &found.jars {
+            let version = match self.stored_version_in(session, &found, jar_path)? {
+                Some(version) => version,
+                None => {
+                    session.release();
+                    deadline::enter(Phase::Decompile);
+                    let decompile_start = Instant::now();
+                    let decompiled = decompiler.decompile_class(jar_path, &found.class_name);
+                    self.decompiled_version(
+                        &found,
+                        jar_path,
+                        decompiled,
+                        decompile_start.elapsed(),
+                    )?
+                }
+            };
+            versions.push(version);
+        }
+        tracing::debug!(
+            transactions = session.transactions(),
+            "find read transactions for {} jar(s)",
+            found.jars.len()
+        );
+        Ok(found.into_result(versions))
+    }
+
+    /// Fails when nothing matched or there is no decompiler to read with.
+    pub(crate) fn check_matched(&self, found: &ClassMatch) -> Result<()> {
+        if found.jars.is_empty() {
+            return Err(ClassFinderError::NotFound {
+                class_name: found.class_name.clone(),
+                searched: repository_roots(&self.repositories),
+            }
+            .into());
+        }
+        self.require_decompiler()?;
+        tracing::debug!(
+            lookup = %found.lookup_source,
+            jars = found.jars.len(),
+            "resolved {}",
+            found.class_name
+        );
+        Ok(())
+    }
+
+    /// The class from `jar_path` without running anything: its cached source,
+    /// else its sources jar. `None` when it has to be decompiled.
+    pub(crate) fn stored_version_in(
+        &self,
+        session: &mut ReadSession<'_>,
+        found: &ClassMatch,
+        jar_path: &Path,
+    ) -> Result<Option<FindVersion>> {
+        let decompiler = self.require_decompiler()?;
+        let class_name = &found.class_name;
+        let jar_key = jar_path.to_string_lossy().to_string();
+        let cache_key = class_source_key(class_name, &jar_key, decompiler.kind());
+        let repository = repository_for(&self.repositories, jar_path).map(|r| r.name.clone());
+
+        if let Some(cached) = self.cache.get_class_source_in(session, &cache_key)?
+            && !(cached.source == ClassContentSource::Decompiled
+                && is_failed_decompilation(&cached.content))
+        {
+            return Ok(Some(FindVersion {
+                version: extract_version_from_maven_path(jar_path),
+                jar_path: jar_key,
+                repository,
+                content_hash: hash_content(&cached.content),
+                content: cached.content,
+                cache_hit: true,
+                source: cached.source.as_str().to_string(),
+                lookup_source: found.lookup_source.clone(),
+                fallback: false,
+                decompiler_options: cached.options,
+                decompiler_version: cached.decompiler_version,
+                decompile_ms: None,
+                warning: cached
+                    .encoding
+                    .as_deref()
+                    .map(|e| non_utf8_warning(class_name, e)),
+                encoding: cached.encoding,
+                postprocessed: cached.postprocessed,
+                structure: None,
+                javadoc: None,
+            }));
+        }
+        let Some(content) = source::read_class_source(jar_path, class_name).unwrap_or(None) else {
+            return Ok(None);
+        };
+        Ok(Some(FindVersion {
+            version: extract_version_from_maven_path(jar_path),
+            jar_path: jar_key,
+            repository,
+            content_hash: hash_content(&content),
+            content,
+            cache_hit: false,
+            source: ClassContentSource::SourcesJar.as_str().to_string(),
+            lookup_source: found.lookup_source.clone(),
+            fallback: false,
+            decompiler_options: Vec::new(),
+            decompiler_version: None,
+            decompile_ms: None,
+            warning: None,
+            encoding: None,
+            postprocessed: false,
+            structure: None,
+            javadoc: None,
+        }))
+    }
+
+    /// The class from `jar_path` as the decompiler returned it after
+    /// `elapsed`, or as a javap listing when that failed and javap is
+    /// configured.
+    pub(crate) fn decompiled_version(
+        &self,
+        found: &ClassMatch,
+        jar_path: &Path,
+        decompiled: Result<DecodedOutput>,
+        elapsed: Duration,
+    ) -> Result<FindVersion> {
+        let decompiler = self.require_decompiler()?;
+        let class_name = &found.class_name;
+        let decompiled = decompiled.and_then(|decompiled| {
+            let parsed = parse_decompiled_output(&decompiled.text);
+            let content = match parsed.into_iter().find(|c| c.class_name == *class_name) {
+                Some(cls) if cls.failed => {
+                    return Err(ClassFinderError::decompile_failed(
+                        class_name,
+                        format!("Decompiler could not decompile {class_name}"),
+                    )
+                    .into());
+                }
+                Some(cls) => cls.content,
+                None => decompiled.text,
+            };
+            Ok((content, decompiled.encoding))
+        });
+        let mut encoding = None;
+        let mut postprocessed = false;
+        let (content, content_source) = match decompiled {
+            Ok((content, used)) => {
+                encoding = used.map(|e| e.as_str().to_string());
+                // The same bytes `load` and `warmup` cache for this class.
+                let content = match decompiler
+                    .postprocessor()
+                    .and_then(|p| p.run(class_name, &content))
+                {
+                    Some(formatted) => {
+                        postprocessed = true;
+                        formatted
+                    }
+                    None => content,
+                };
+                (content, ClassContentSource::Decompiled)
+            }
+            Err(err) => {
+                let Some(javap) = self
+                    .javap
+                    .as_ref()
+                    .filter(|_| !err.is::<DeadlineExceeded>())
+                else {
+                    return Err(err);
+                };
+                tracing::warn!("decompile failed for {class_name}, falling back to javap: {err}");
+                let listing = javap
+                    .disassemble_class(jar_path, class_name)
+                    .with_context(|| format!("{err}"))?;
+                (listing, ClassContentSource::Javap)
+            }
+        };
+        let decompiled = content_source == ClassContentSource::Decompiled;
+        Ok(FindVersion {
+            version: extract_version_from_maven_path(jar_path),
+            jar_path: jar_path.to_string_lossy().to_string(),
+            repository: repository_for(&self.repositories, jar_path).map(|r| r.name.clone()),
+            content_hash: hash_content(&content),
+            content,
+            cache_hit: false,
+            source: content_source.as_str().to_string(),
+            lookup_source: found.lookup_source.clone(),
+            fallback: content_source == ClassContentSource::Javap,
+            decompiler_options: if decompiled {
+                decompiler.options()
+            } else {
+                Vec::new()
+            },
+            decompiler_version: if decompiled {
+                decompiler.version()
+            } else {
+                None
+            },
+            decompile_ms: Some(elapsed.as_millis() as u64),
+            warning: encoding.as_deref().map(|e| non_utf8_warning(class_name, e)),
+            encoding,
+            postprocessed,
+            structure: None,
+            javadoc: None,
+        })
+    }
+
+    /// Loads the jars whose class [`ClassFinder::find_matched`] had to read
+    /// or decompile, so later finds of their other classes hit the cache.
+    /// Failures are only logged.
+    pub fn backfill(&self, result: &FindResult) {
+        let Some(decompiler) = self.decompiler.as_deref() else {
+            return;
+        };
+        let mut target_jars = Vec::new();
+        let mut seen = HashSet::new();
+
+        for version in &result.versions {
+            if version.cache_hit {
+                continue;
+            }
+            if seen.insert(version.jar_path.clone()) {
+                target_jars.push(PathBuf::from(&version.jar_path));
+            }
+        }
+
+        if target_jars.is_empty() {
+            return;
+        }
+
+        let _span = tracing::info_span!("backfill", jars = target_jars.len()).entered();
+        tracing::info!(
+            "find backfill write buffer: batch_size={} flush_ms={}",
+            self.buffer_config.batch_size,
+            self.buffer_config.flush_interval_ms
+        );
+        let mut buffer = WriteBuffer::new(
+            self.cache.db(),
+            self.buffer_config,
+            self.cache.pending_gauge_path(),
+        );
+
+        deadline::enter(Phase::Backfill);
+        for jar_path in target_jars {
+            // The output is already written; running out of time only cuts the
+            // backfill short, and what was loaded is still flushed below.
+            if deadline::expired() {
+                tracing::warn!(
+                    "--timeout reached, stopping the backfill before {}",
+                    jar_path.display()
+                );
+                break;
+            }
+            tracing::info!("find backfill enqueue jar: {}", jar_path.display());
+            match load_jar(
+                &self.cache,
+                &self.registry,
+                &buffer,
+                decompiler,
+                &jar_path,
+                WarmupMode::AllClasses,
+                self.cache_package_info,
+            ) {
+                Ok(output) => {
+                    if !output.skipped {
+                        // Only mark the jar warmed once its sources are committed.
+                        if let Err(err) = buffer.flush() {
+                            tracing::warn!(
+                                "find backfill flush failed: {} ({err})",
+                                output.jar_path
+                            );
+                            continue;
+                        }
+                        let _ = self
+                            .hotspot
+                            .mark_warmed(&output.jar_path, output.classes_loaded as u32);
+                    }
+                }
+                Err(err) => {
+                    tracing::warn!("find backfill failed for {}: {err}", jar_path.display())
+                }
+            }
+        }
+
+        if let Err(err) = buffer.shutdown_and_flush() {
+            tracing::warn!("find backfill flush failed: {err}");
+        }
+    }
+
+    /// Counts the find for every jar it matched, and for the class in each of
+    /// them, in one transaction committed after the output is written. Returns
+    /// the warmups the updated jar counts call for.
+    pub fn record_accesses(&self, result: &FindResult) -> Vec<(String, WarmupRequest)> {
+        let mut seen = HashSet::new();
+        let jar_keys: Vec<&str> = result
+            .versions
+            .iter()
+            .map(|version| version.jar_path.as_str())
+            .filter(|jar_key| seen.insert(*jar_key))
+            .collect();
+        if jar_keys.is_empty() {
+            return Vec::new();
+        }
+        let plan = match self.hotspot.plan_accesses(jar_keys.iter().copied()) {
+            Ok(plan) => plan,
+            Err(err) => {
+                tracing::warn!("hotspot update failed: {err}");
+                return Vec::new();
+            }
+        };
+        let mut accesses = plan.mutations;
+        accesses.extend(
+            jar_keys
+                .iter()
+                .map(|jar_key| HotspotTracker::class_access(&result.class_name, jar_key)),
+        );
+        let mut buffer = WriteBuffer::new(
+            self.cache.db(),
+            self.buffer_config,
+            self.cache.pending_gauge_path(),
+        );
+        if let Err(err) = buffer
+            .mutate_group(accesses)
+            .and_then(|()| buffer.shutdown_and_flush())
+        {
+            tracing::warn!("hotspot update failed: {err}");
+        }
+        plan.requests
+    }
+
+    /// Runs the warmups the accesses recorded by `find` call for on a [`Warmer`],
+    /// giving them `budget` to finish.
+    /// Tasks are also added to the persisted warmup queue and removed once their
+    /// jar is warmed, so the ones that do not finish are left for
+    /// `warmup --resume`.
+    pub fn background_warmup(&self, requests: Vec<(String, WarmupRequest)>, budget: Duration) {
+        let Some(decompiler) = self.decompiler.clone() else {
+            return;
+        };
+        let tasks: Vec<WarmupTask> = requests
+            .into_iter()
+            .map(|(jar_key, request)| WarmupTask {
+                exclude_fqns: if request.mode == WarmupMode::TopLevelOnly {
+                    cached_hot_classes(&self.cache, &self.hotspot, decompiler.as_ref(), &jar_key)
+                } else {
+                    HashSet::new()
+                },
+                jar_path: PathBuf::from(jar_key),
+                priority: request.priority,
+                mode: request.mode,
+            })
+            .collect();
+        if tasks.is_empty() {
+            return;
+        }
+        let _span = tracing::info_span!("background_warmup", jars = tasks.len()).entered();
+        let queue = &self.queue;
+        let queued: Vec<QueuedJar> = tasks
+            .iter()
+            .map(|task| QueuedJar {
+                jar_path: task.jar_path.clone(),
+                priority: task.priority,
+                mode: task.mode,
+            })
+            .collect();
+        if let Err(err) = queue.push_all(&queued) {
+            tracing::warn!("warmup queue update failed: {err}");
+        }
+
+        let mut buffer = WriteBuffer::new(
+            self.cache.db(),
+            self.buffer_config,
+            self.cache.pending_gauge_path(),
+        );
+        let Some(handle) = buffer.handle() else {
+            return;
+        };
+        let config = WarmerConfig {
+            cache_package_info: self.cache_package_info,
+            ..WarmerConfig::default()
+        };
+        let mut warmer = match Warmer::new(decompiler, handle, Some(self.hotspot.clone()), config) {
+            Ok(warmer) => warmer,
+            Err(err) => {
+                tracing::warn!("background warmup unavailable: {err}");
+                return;
+            }
+        };
+        let mut run = BackgroundWarmupStats::default();
+        for task in tasks {
+            tracing::info!(
+                "background warmup enqueue jar: {} ({:?})",
+                task.jar_path.display(),
+                task.mode
+            );
+            if warmer.submit(task).is_ok() {
+                run.submitted_tasks += 1;
+            }
+        }
+
+        // Ctrl-C cancels the queued tasks but still waits for the running ones,
+        // so their sources are committed below.
+        interrupt::install();
+        deadline::enter(Phase::Warmup);
+        let stop_at = Instant::now() + deadline::cap(budget);
+        let finished = loop {
+            let remaining = stop_at.saturating_duration_since(Instant::now());
+            if warmer.drain_within(remaining.min(Duration::from_millis(50))) {
+                break true;
+            }
+            if interrupt::requested() {
+                warmer.cancel();
+                break true;
+            }
+            if remaining.is_zero() {
+                break false;
+            }
+        };
+        let stats = warmer.stats();
+        run.completed_tasks = stats.completed_tasks.load(Ordering::Relaxed);
+        run.failed_tasks = stats.failed_tasks.load(Ordering::Relaxed);
+        run.failed_classes = stats.failed_classes.load(Ordering::Relaxed);
+        run.unfinished_tasks = run
+            .submitted_tasks
+            .saturating_sub(run.completed_tasks + run.failed_tasks);
+        if !finished {
+            tracing::info!(
+                "background warmup budget of {}ms used up, {} task(s) left for a later run",
+                budget.as_millis(),
+                run.unfinished_tasks
+            );
+            // The running tasks' decompilers are killed rather than left
+            // behind; their jars stay queued.
+            warmer.abort();
+        }
+        if let Err(err) = buffer.shutdown_and_flush() {
+            tracing::warn!("background warmup flush failed: {err}");
+        }
+        for jar in &queued {
+            let jar_key = jar.jar_path.to_string_lossy();
+            let warmed = self
+                .hotspot
+                .get_hotspot(&jar_key)
+                .is_ok_and(|h| h.is_some_and(|h| h.warmed));
+            if warmed {
+                let _ = queue.remove(&jar.jar_path);
+            }
+        }
+        if let Err(err) = self.cache.record_background_warmup(&run) {
+            tracing::warn!("background warmup stats not saved: {err}");
+        }
+    }
+}
+
+/// Accessed classes of `jar_key` that are already cached, which a top-level
+/// warmup of the jar does not need to decompile again. Full warmups keep no
+/// exclusions so they still record the jar as loaded.
+fn cached_hot_classes(
+    cache: &PersistentCache,
+    hotspot: &HotspotTracker,
+    decompiler: &dyn Decompiler,
+    jar_key: &str,
+) -> HashSet<String> {
+    let accessed: Vec<String> = hotspot
+        .top_classes(Some(jar_key), MAX_TRACKED_CLASSES_PER_JAR as usize)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|class| class.class_name)
+        .collect();
+    if accessed.is_empty() {
+        return HashSet::new();
+    }
+    cache
+        .cached_class_hashes(jar_key, decompiler.kind(), &accessed)
+        .map(|cached| cached.into_keys().collect())
+        .unwrap_or_default()
+}
+
+fn load_jar(
+    cache: &PersistentCache,
+    registry: &ClassRegistry,
+    buffer: &WriteBuffer,
+    decompiler: &dyn Decompiler,
+    jar_path: &Path,
+    mode: WarmupMode,
+    cache_package_info: bool,
+) -> Result<LoadResult> {
+    let jar_key = jar_path.to_string_lossy().to_string();
+    let start = Instant::now();
+
+    let cataloged_classes = catalog::catalog(jar_path).unwrap_or_default();
+    if !registry.is_cataloged(&jar_key).unwrap_or(false) && !cataloged_classes.is_empty() {
+        buffer.mutate_group(ClassRegistry::catalog_mutations(
+            &jar_key,
+            &cataloged_classes,
+        ))?;
+    }
+
+    let loaded_key = loaded_jar_key(&jar_key, decompiler.kind());
+    if cache
+        .loaded_mode(&loaded_key)?
+        .is_some_and(|loaded| loaded.covers(mode))
+    {
+        return Ok(LoadResult {
+            jar_path: jar_key,
+            classes_loaded: 0,
+            classes_failed: 0,
+            classes_skipped_cached: 0,
+            decompiler_version: None,
+            warning: None,
+            skipped: true,
+            duration_ms: 0,
+        });
+    }
+
+    // Classes cached earlier (e.g. by `find` backfill) are not written again
+    // when their content is unchanged.
+    let wanted: Vec<String> = cataloged_classes
+        .iter()
+        .filter(|class_name| mode.includes(class_name, cache_package_info))
+        .cloned()
+        .collect();
+    let existing = cache.cached_class_hashes(&jar_key, decompiler.kind(), &wanted)?;
+    let unchanged = |cls: &ParsedClass| {
+        existing
+            .get(&cls.class_name)
+            .is_some_and(|hash| *hash == content_hash(&cls.content))
+    };
+
+    // The jar's sources and its loaded marker are queued as one group, so a
+    // jar is never marked loaded with only part of its classes committed.
+    let mut writes = Vec::new();
+    let mut cached_classes = HashSet::new();
+    let mut classes_loaded = 0usize;
+    let mut classes_skipped_cached = 0usize;
+
+    let skipped_kind = |cls: &ParsedClass| !mode.includes_class(cls, cache_package_info);
+    for cls in source::read_jar_sources(jar_path).unwrap_or_default() {
+        if skipped_kind(&cls) {
+            continue;
+        }
+        cached_classes.insert(cls.class_name.clone());
+        if unchanged(&cls) {
+            classes_skipped_cached += 1;
+            continue;
+        }
+        let key = class_source_key(&cls.class_name, &jar_key, decompiler.kind());
+        writes.push(Mutation::Put(PendingWrite {
+            key,
+            content: cls.content,
+            source: ClassContentSource::SourcesJar,
+            options: Vec::new(),
+            decompiler_version: None,
+            encoding: None,
+            postprocessed: false,
+        }));
+        classes_loaded += 1;
+    }
+
+    // With sources, or with most classes already cached, only the remaining
+    // classes are decompiled one by one instead of running the whole jar.
+    let mostly_cached = !wanted.is_empty()
+        && existing.len() as f64 / wanted.len() as f64 >= CACHED_COVERAGE_THRESHOLD;
+    let missing: Option<Vec<String>> = (!cached_classes.is_empty() || mostly_cached).then(|| {
+        wanted
+            .iter()
+            .filter(|class_name| !cached_classes.contains(*class_name))
+            .filter(|class_name| {
+                let cached = existing.contains_key(*class_name);
+                classes_skipped_cached += usize::from(cached);
+                !cached
+            })
+            .cloned()
+            .collect()
+    });
+    let options = decompiler.options();
+    let decompiler_version = decompiler.version();
+    let mut classes_failed = 0usize;
+    let mut transcoded: Option<(usize, String)> = None;
+    let mut store = |mut cls: ParsedClass| {
+        if cached_classes.contains(&cls.class_name) || skipped_kind(&cls) {
+            return;
+        }
+        // Leave failure stubs uncached so `find` retries the class on its own.
+        if cls.failed {
+            classes_failed += 1;
+            return;
+        }
+        cached_classes.insert(cls.class_name.clone());
+        // Before comparing, so a formatted class cached by `find` is unchanged.
+        let postprocessed = decompiler
+            .postprocessor()
+            .is_some_and(|p| p.apply(&mut cls));
+        if unchanged(&cls) {
+            classes_skipped_cached += 1;
+            return;
+        }
+        if let Some(encoding) = &cls.encoding {
+            transcoded.get_or_insert_with(|| (0, encoding.clone())).0 += 1;
+        }
+        let key = class_source_key(&cls.class_name, &jar_key, decompiler.kind());
+        writes.push(Mutation::Put(PendingWrite {
+            key,
+            content: cls.content,
+            source: ClassContentSource::Decompiled,
+            options: options.clone(),
+            decompiler_version: decompiler_version.clone(),
+            encoding: cls.encoding,
+            postprocessed,
+        }));
+        classes_loaded += 1;
+    };
+
+    match missing {
+        None => {
+            decompiler.decompile_jar_each(jar_path, &mut store)?;
+        }
+        Some(missing) if missing.is_empty() => {}
+        Some(missing) => {
+            let batch = decompiler.decompile_classes(jar_path, &missing)?;
+            batch.classes.into_iter().for_each(&mut store);
+            for (class_name, err) in &batch.failed {
+                tracing::warn!("failed to decompile {class_name}: {err}");
+            }
+            classes_failed += batch.failed.len();
+        }
+    }
+    writes.push(Mutation::PutValue {
+        table: Table::Jars,
+        key: loaded_key,
+        value: mode.as_str().to_string(),
+    });
+    buffer.mutate_group(writes)?;
+
+    Ok(LoadResult {
+        jar_path: jar_key,
+        classes_loaded,
+        classes_failed,
+        classes_skipped_cached,
+        decompiler_version,
+        warning: transcoded.map(|(count, encoding)| {
+            let classes = if count == 1 { "class" } else { "classes" };
+            non_utf8_warning(&format!("{count} {classes}"), &encoding)
+        }),
+        skipped: false,
+        duration_ms: start.elapsed().as_millis() as u64,
+    })
+}
+
+/// Which jars [`ClassFinder::warmup`] loads, and the budgets that stop it.
+/// Targets come from the first of `hot`, `group`, `from_file` and
+/// `jar_path` that is set, plus the queue left by earlier runs with `resume`.
+pub struct WarmupParams<'a> {
+    pub jar_path: Option<&'a Path>,
+    pub hot: bool,
+    pub group_level: bool,
+    pub group: Option<&'a str>,
+    pub from_file: Option<&'a Path>,
+    pub top: usize,
+    pub limit: Option<usize>,
+    pub resume: bool,
+    pub mode: WarmupMode,
+    pub max_duration: Option<Duration>,
+    pub max_classes: Option<usize>,
+    pub jobs: usize,
+    pub order: Option<WarmupOrder>,
+}
+
+impl Default for WarmupParams<'_> {
+    fn default() -> Self {
+        Self {
+            jar_path: None,
+            hot: false,
+            group_level: false,
+            group: None,
+            from_file: None,
+            top: 20,
+            limit: None,
+            resume: false,
+            mode: WarmupMode::AllClasses,
+            max_duration: None,
+            max_classes: None,
+            jobs: 1,
+            order: None,
+        }
+    }
+}
+
+fn warmup_targets(
+    finder: &ClassFinder,
+    decompiler: &dyn Decompiler,
+    buffer: &WriteBuffer,
+    params: WarmupParams<'_>,
+    report: &mut (dyn FnMut(&WarmupProgress<'_>) + Send),
+) -> Result<WarmupResult> {
+    let start = Instant::now();
+    let selected: Vec<PathBuf> = if params.hot && params.group_level {
+        let mut jars = Vec::new();
+        for group in finder
+            .hotspot
+            .top_groups(&finder.m2_repo, false, usize::MAX)?
+            .into_iter()
+            .filter(|group| group.unwarmed_jars > 0)
+            .take(params.top)
+        {
+            for dir in group_dirs(&finder.repositories, &group.group_id) {
+                jars.extend(scan_jars(&dir)?);
+            }
+        }
+        jars
+    } else if params.hot {
+        finder
+            .hotspot
+            .top_unwarmed_jars(params.top)?
+            .into_iter()
+            .map(PathBuf::from)
+            .collect()
+    } else if let Some(group) = params.group {
+        let mut jars = Vec::new();
+        for dir in group_dirs(&finder.repositories, group) {
+            jars.extend(scan_jars(&dir)?);
+        }
+        jars
+    } else if let Some(list) = params.from_file {
+        read_target_list(&finder.repositories, list)?
+    } else if let Some(jar_path) = params.jar_path {
+        vec![jar_path.to_path_buf()]
+    } else if params.resume {
+        Vec::new()
+    } else {
+        return Err(ClassFinderError::invalid_input(
+            "warmup requires jar_path, or use --hot / --group / --from-file / --resume",
+        )
+        .into());
+    };
+    // Spend a time or class budget on the jars that pay off soonest. A
+    // `--from-file` list keeps its own order unless `--order` is given.
+    let order = match params.order {
+        Some(order) => Some(order),
+        None if params.from_file.is_some() && !params.hot && params.group.is_none() => None,
+        None => Some(WarmupOrder::default()),
+    };
+    let mut selected = match order {
+        Some(order) => order_jars(selected, order, |jar| access_count(&finder.hotspot, jar)),
+        None => selected,
+    };
+    let roots: Vec<PathBuf> = finder.repositories.iter().map(|r| r.path.clone()).collect();
+    let excluded = finder.exclusions.apply(&roots, &mut selected);
+    for count in &excluded {
+        tracing::info!(
+            "--{} {} removed {} jar(s)",
+            count.option,
+            count.pattern,
+            count.removed
+        );
+    }
+
+    // Jars left over from an interrupted run go first, then the new targets.
+    let resumed = if params.resume {
+        finder.queue.pending()?
+    } else {
+        Vec::new()
+    };
+    let priority = if params.hot {
+        WarmupPriority::High
+    } else {
+        WarmupPriority::Normal
+    };
+    let mut seen = HashSet::new();
+    let mut targets: Vec<QueuedJar> = resumed
+        .into_iter()
+        .chain(selected.into_iter().map(|jar_path| QueuedJar {
+            jar_path,
+            priority,
+            mode: params.mode,
+        }))
+        .filter(|target| seen.insert(target.jar_path.clone()))
+        .collect();
+    if let Some(limit) = params.limit {
+        targets.truncate(limit);
+    }
+    finder.queue.push_all(&targets)?;
+
+    let total = targets.len();
+    let next = AtomicUsize::new(0);
+    let run = Mutex::new(WarmupRun {
+        loads: Vec::new(),
+        loaded_jars: Vec::new(),
+        succeeded: 0,
+        failed: 0,
+        classes_failed: 0,
+        classes_loaded: 0,
+        completed: 0,
+        started: 0,
+        stopped_by: None,
+        last_summary: Instant::now(),
+    });
+    let report = Mutex::new(report);
+    deadline::enter(Phase::Warmup);
+
+    // Each worker claims the next target in order, so budgets and Ctrl-C
+    // are checked between jars just like with a single worker.
+    let worker = || -> Result<()> {
+        loop {
+            let idx = {
+                let mut run = lock(&run);
+                if run.stopped_by.is_none() {
+                    if interrupt::requested() {
+                        run.stopped_by = Some("interrupted");
+                    } else if deadline::expired() {
+                        run.stopped_by = Some("timeout");
+                    } else if params
+                        .max_duration
+                        .is_some_and(|max| start.elapsed() >= max)
+                    {
+                        run.stopped_by = Some("max-duration");
+                    } else if params
+                        .max_classes
+                        .is_some_and(|max| run.classes_loaded >= max)
+                    {
+                        run.stopped_by = Some("max-classes");
+                    }
+                }
+                if run.stopped_by.is_some() {
+                    return Ok(());
+                }
+                let idx = next.fetch_add(1, Ordering::Relaxed);
+                if idx >= total {
+                    return Ok(());
+                }
+                run.started += 1;
+                idx
+            };
+
+            let target = &targets[idx];
+            let jar = &target.jar_path;
+            let jar_start = Instant::now();
+            let jar_key = jar.to_string_lossy();
+            let outcome = load_jar(
+                &finder.cache,
+                &finder.registry,
+                buffer,
+                decompiler,
+                jar,
+                target.mode,
+                finder.cache_package_info,
+            );
+            // Queued behind the jar's own writes, so the entry only disappears
+            // once they are committed.
+            buffer.mutate(Mutation::Delete {
+                table: Table::WarmupQueue,
+                key: jar_key.to_string(),
+            })?;
+
+            let duration = jar_start.elapsed();
+            if let Ok(load) = &outcome
+                && !load.skipped
+            {
+                finder.progress.event(ProgressEvent::jar_decompiled(
+                    &load.jar_path,
+                    load.classes_loaded,
+                    duration,
+                ));
+            }
+            finder.progress.event(ProgressEvent::WarmupTaskFinished {
+                index: idx + 1,
+                total,
+                jar_path: jar_key.to_string(),
+                ok: outcome.is_ok(),
+                classes_loaded: outcome.as_ref().map_or(0, |load| load.classes_loaded),
+                duration_ms: duration.as_millis() as u64,
+            });
+            let mut report = lock(&report);
+            report(&WarmupProgress::Jar {
+                index: idx + 1,
+                total,
+                jar_path: &jar_key,
+                ok: outcome.is_ok(),
+                classes_loaded: outcome.as_ref().map_or(0, |load| load.classes_loaded),
+                skipped: outcome.as_ref().is_ok_and(|load| load.skipped),
+                duration_ms: duration.as_millis() as u64,
+                error: outcome.as_ref().err().map(|err| format!("{err:#}")),
+            });
+            let mut run = lock(&run);
+            run.completed += 1;
+            match outcome {
+                Ok(load) => {
+                    run.succeeded += 1;
+                    run.classes_failed += load.classes_failed;
+                    run.classes_loaded += load.classes_loaded;
+                    if !load.skipped {
+                        let loaded = (load.jar_path.clone(), load.classes_loaded as u32);
+                        run.loaded_jars.push((idx, loaded));
+                    }
+                    run.loads.push((idx, load));
+                }
+                Err(_) => {
+                    run.failed += 1;
+                }
+            }
+
+            let remaining = total - run.completed;
+            if remaining > 0 && run.last_summary.elapsed() >= WARMUP_SUMMARY_INTERVAL {
+                run.last_summary = Instant::now();
+                report(&WarmupProgress::Summary {
+                    completed: run.completed,
+                    remaining,
+                    succeeded: run.succeeded,
+                    failed: run.failed,
+                    elapsed_ms: start.elapsed().as_millis() as u64,
+                });
+            }
+        }
+    };
+
+    let jobs = params.jobs.clamp(1, total.max(1));
+    if jobs == 1 {
+        worker()?;
+    } else {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(jobs).build()?;
+        let errors = Mutex::new(Vec::new());
+        pool.scope(|scope| {
+            for _ in 0..jobs {
+                scope.spawn(|_| {
+                    if let Err(err) = worker() {
+                        lock(&errors).push(err);
+                    }
+                });
+            }
+        });
+        if let Some(err) = errors.into_inner().unwrap_or_default().into_iter().next() {
+            return Err(err);
+        }
+    }
+
+    let mut run = run
+        .into_inner()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    run.loads.sort_by_key(|(idx, _)| *idx);
+    run.loaded_jars.sort_by_key(|(idx, _)| *idx);
+    Ok(WarmupResult {
+        targets: total,
+        succeeded: run.succeeded,
+        failed: run.failed,
+        classes_failed: run.classes_failed,
+        duration_ms: start.elapsed().as_millis() as u64,
+        stopped_by: run.stopped_by,
+        // Left in the warmup queue for `warmup --resume`.
+        not_started: total - run.started,
+        excluded,
+        loads: run.loads.into_iter().map(|(_, load)| load).collect(),
+        loaded_jars: run
+            .loaded_jars
+            .into_iter()
+            .map(|(_, loaded)| loaded)
+            .collect(),
+        buffer: buffer.stats(),
+    })
+}
+
+/// Existing directories of `group` in the Maven-layout repositories.
+fn group_dirs(repositories: &[Repository], group: &str) -> Vec<PathBuf> {
+    repositories
+        .iter()
+        .filter(|r| r.layout == RepositoryLayout::Maven)
+        .map(|r| r.path.join(group.replace('.', "/")))
+        .filter(|dir| dir.exists())
+        .collect()
+}
+
+/// [`resolve_jar_reference`] against the first Maven-layout repository that
+/// has the jar, or the first one when none does.
+fn resolve_jar_in_repositories(repositories: &[Repository], reference: &str) -> PathBuf {
+    let candidates: Vec<PathBuf> = repositories
+        .iter()
+        .filter(|r| r.layout == RepositoryLayout::Maven)
+        .map(|r| resolve_jar_reference(&r.path, reference))
+        .collect();
+    candidates
+        .iter()
+        .find(|jar| jar.is_file())
+        .or(candidates.first())
+        .cloned()
+        .unwrap_or_else(|| PathBuf::from(reference))
+}
+
+/// Jars listed in `list` (`-` for stdin), one path or Maven coordinate per
+/// line. Blank lines and `#` comments are ignored; jars that do not exist are
+/// skipped with a warning.
+fn read_target_list(repositories: &[Repository], list: &Path) -> Result<Vec<PathBuf>> {
+    let content = if list == Path::new("-") {
+        std::io::read_to_string(std::io::stdin()).context("Failed to read targets from stdin")?
+    } else {
+        std::fs::read_to_string(list)
+            .with_context(|| format!("Failed to read target list: {}", list.display()))?
+    };
+    let mut jars = Vec::new();
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let jar = resolve_jar_in_repositories(repositories, line);
+        if jar.is_file() {
+            jars.push(jar);
+        } else {
+            tracing::info!(
+                "warmup target not found, skipped: {line} ({})",
+                jar.display()
+            );
+        }
+    }
+    Ok(jars)
+}
+
+/// Tallies of a `warmup` run, shared by its workers. Loads carry their target
+/// index so the result lists them in target order.
+struct WarmupRun {
+    loads: Vec<(usize, LoadResult)>,
+    loaded_jars: Vec<(usize, (String, u32))>,
+    succeeded: usize,
+    failed: usize,
+    classes_failed: usize,
+    classes_loaded: usize,
+    completed: usize,
+    started: usize,
+    stopped_by: Option<&'static str>,
+    last_summary: Instant,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn access_count(hotspot: &HotspotTracker, jar: &Path) -> u32 {
+    hotspot
+        .get_hotspot(&jar.to_string_lossy())
+        .ok()
+        .flatten()
+        .map_or(0, |h| h.access_count)
+}
+
+fn index_repo(
+    registry: &ClassRegistry,
+    root: PathBuf,
+    progress: &dyn ProgressSink,
+) -> Result<IndexResult> {
+    let start = Instant::now();
+    progress.event(ProgressEvent::ScanStarted {
+        root: root.to_string_lossy().to_string(),
+    });
+    let jars = scan_jars(&root)?;
+    let mut cataloged_jars_new = 0usize;
+    let mut indexed_classes = 0usize;
+    let mut failed_jars = 0usize;
+
+    // Each jar's classes are committed as it is cataloged, so a timeout keeps
+    // what was indexed so far.
+    deadline::enter(Phase::Index);
+    for jar_path in jars.iter() {
+        deadline::check()?;
+        let jar_key = jar_path.to_string_lossy().to_string();
+        if registry.is_cataloged(&jar_key).unwrap_or(false) {
+            continue;
+        }
+
+        match catalog::catalog(jar_path) {
+            Ok(classes) => {
+                indexed_classes += classes.len();
+                let _ = registry.update_registry_and_mark_cataloged(&jar_key, &classes);
+                cataloged_jars_new += 1;
+                progress.event(ProgressEvent::JarCataloged {
+                    jar_path: jar_key,
+                    classes: classes.len(),
+                });
+            }
+            Err(_) => {
+                failed_jars += 1;
+            }
+        }
+    }
+
+    Ok(IndexResult {
+        root: root.to_string_lossy().to_string(),
+        scanned_jars: jars.len(),
+        cataloged_jars_new,
+        indexed_classes,
+        duration_ms: start.elapsed().as_millis() as u64,
+        failed_jars,
+        changed_jars: None,
+        unchanged_jars: None,
+        refreshed_jars: None,
+        removed_jars: None,
+        purged_jars: None,
+        unsettled_jars: None,
+    })
+}
+
+/// `index --incremental`: catalogs only the jars under `root` that are new
+/// or modified since the last incremental run, per the mtime table, and
+/// reports the jars that disappeared, deleting their data with
+/// `purge_removed`. Stored scans outside `scan_roots` are dropped.
+fn index_repo_incremental(
+    cache: &PersistentCache,
+    registry: &ClassRegistry,
+    root: PathBuf,
+    scan_roots: Vec<PathBuf>,
+    purge_removed: bool,
+    progress: Arc<dyn ProgressSink>,
+) -> Result<IndexResult> {
+    let start = Instant::now();
+    let result = IncrementalIndexer::new(cache.db(), root)
+        .with_scan_roots(scan_roots)
+        .with_purge_removed(purge_removed)
+        .with_progress(progress)
+        .run_once(registry)?;
+    Ok(IndexResult {
+        root: result.root,
+        scanned_jars: result.scanned_jars,
+        cataloged_jars_new: result.cataloged_jars_new,
+        indexed_classes: result.indexed_classes,
+        duration_ms: start.elapsed().as_millis() as u64,
+        failed_jars: result.failed_jars,
+        changed_jars: Some(result.changed_jars),
+        unchanged_jars: Some(result.unchanged_jars),
+        refreshed_jars: Some(result.refreshed_jars),
+        removed_jars: Some(result.removed_jars),
+        purged_jars: Some(result.purged_jars),
+        unsettled_jars: Some(result.unsettled_jars),
+    })
+}
+
+/// Parses and caches the structure of every class cached for the jar by
+/// `decompiler` that has none yet. Returns how many were added.
+fn cache_jar_structures(
+    cache: &PersistentCache,
+    jar_key: &str,
+    decompiler: DecompilerKind,
+) -> Result<usize> {
+    let mut added = 0;
+    for class_name in catalog::catalog(Path::new(jar_key)).unwrap_or_default() {
+        let key = class_source_key(&class_name, jar_key, decompiler);
+        let Some(cached) = cache.get_class_source(&key)? else {
+            continue;
+        };
+        let content_hash = hash_content(&cached.content);
+        if cache.get_compilation_unit(&content_hash)?.is_some() {
+            continue;
+        }
+        if let Some(unit) = parse_compilation_unit(&cached.content) {
+            cache.put_compilation_unit(&content_hash, &unit)?;
+            added += 1;
+        }
+    }
+    Ok(added)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::find_options::FindOptions;
+    use std::io::Write;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    /// Decompiles every class to an empty declaration and records which
+    /// classes it was asked for; classes in `failing` fail.
+    #[derive(Default)]
+    struct MockDecompiler {
+        calls: Mutex<Vec<String>>,
+        failing: Vec<String>,
+        postprocess: Option<crate::postprocess::Postprocessor>,
+    }
+
+    impl MockDecompiler {
+        fn failing(class_name: &str) -> Self {
+            Self {
+                failing: vec![class_name.to_string()],
+                ..Self::default()
+            }
+        }
+
+        fn calls(&self) -> Vec<String> {
+            lock(&self.calls).clone()
+        }
+
+        /// As CFR prints it, the same alone and within a whole jar.
+        fn source(class_name: &str) -> String {
+            let (package, name) = class_name.rsplit_once('.').unwrap_or(("", class_name));
+            format!(
+                "/*\n * Decompiled with CFR 0.152.\n */\npackage {package};\n\npublic class {name} {{\n}}\n"
+            )
+        }
+    }
+
+    impl Decompiler for MockDecompiler {
+        fn kind(&self) -> DecompilerKind {
+            DecompilerKind::Cfr
+        }
+
+        fn postprocessor(&self) -> Option<&crate::postprocess::Postprocessor> {
+            self.postprocess.as_ref()
+        }
+
+        fn decompile_class(&self, _jar_path: &Path, class_name: &str) -> Result<DecodedOutput> {
+            lock(&self.calls).push(class_name.to_string());
+            if self.failing.iter().any(|c| c == class_name) {
+                anyhow::bail!("mock cannot decompile {class_name}");
+            }
+            Ok(DecodedOutput {
+                text: Self::source(class_name),
+                encoding: None,
+            })
+        }
+
+        fn decompile_jar(&self, jar_path: &Path) -> Result<DecodedOutput> {
+            let text = catalog::catalog(jar_path)?
+                .iter()
+                .map(|class_name| {
+                    lock(&self.calls).push(class_name.clone());
+                    Self::source(class_name)
+                })
+                .collect();
+            Ok(DecodedOutput {
+                text,
+                encoding: None,
+            })
+        }
+    }
+
+    struct Fixture {
+        base: PathBuf,
+        m2: PathBuf,
+        decompiler: Arc<MockDecompiler>,
+        finder: ClassFinder,
+    }
+
+    impl Fixture {
+        fn new(name: &str) -> Result<Self> {
+            Self::with_decompiler(name, MockDecompiler::default())
+        }
+
+        fn with_decompiler(name: &str, decompiler: MockDecompiler) -> Result<Self> {
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos();
+            let base = std::env::temp_dir().join(format!(
+                "class_finder_finder_test_{}_{}_{}",
+                std::process::id(),
+                nanos,
+                name
+            ));
+            let m2 = base.join("m2");
+            std::fs::create_dir_all(&m2)?;
+            let decompiler = Arc::new(decompiler);
+            let finder = ClassFinder::open(base.join("db.lmdb"), m2.clone())?
+                .with_decompiler(Arc::clone(&decompiler) as Arc<dyn Decompiler>);
+            Ok(Self {
+                base,
+                m2,
+                decompiler,
+                finder,
+            })
+        }
+
+        /// Writes `m2/<rel>` with an empty entry per class.
+        fn jar(&self, rel: &str, classes: &[&str]) -> Result<PathBuf> {
+            let path = self.m2.join(rel);
+            write_zip(
+                &path,
+                &classes
+                    .iter()
+                    .map(|c| (format!("{}.class", c.replace('.', "/")), String::new()))
+                    .collect::<Vec<_>>(),
+            )?;
+            Ok(path)
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.base);
+        }
+    }
+
+    fn write_zip(path: &Path, entries: &[(String, String)]) -> Result<()> {
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(path)?);
+        for (name, content) in entries {
+            zip.start_file(name.as_str(), zip::write::FileOptions::default())?;
+            zip.write_all(content.as_bytes())?;
+        }
+        zip.finish()?;
+        Ok(())
+    }
+
+    fn code(err: &anyhow::Error) -> &'static str {
+        ClassFinderError::from_error(err).code()
+    }
+
+    #[test]
+    fn registry_hits_skip_scanning_and_stale_entries() -> Result<()> {
+        let fx = Fixture::new("registry")?;
+        let jar = fx.jar("org/example/demo/1.0/demo-1.0.jar", &["org.example.A"])?;
+        let gone = fx.jar("org/example/demo/0.9/demo-0.9.jar", &["org.example.A"])?;
+        fx.finder.index(fx.m2.clone())?;
+        std::fs::remove_file(&gone)?;
+
+        let found = fx.finder.find("org.example.A", &FindOptions::new())?;
+        assert_eq!(found.matched_jars, 1);
+        assert_eq!(found.versions[0].jar_path, jar.to_string_lossy());
+        assert_eq!(found.versions[0].lookup_source, "registry");
+        assert_eq!(found.versions[0].source, "decompiled");
+        assert!(!found.versions[0].cache_hit);
+        assert!(found.versions[0].decompile_ms.is_some());
+        assert_eq!(fx.decompiler.calls(), ["org.example.A"]);
+        Ok(())
+    }
+
+    #[test]
+    fn unindexed_classes_are_scanned_from_the_narrowest_package_directory() -> Result<()> {
+        let fx = Fixture::new("tiered")?;
+        fx.jar("org/example/demo/1.0/demo-1.0.jar", &["org.example.pkg.A"])?;
+        fx.jar("org/other/lib/1.0/lib-1.0.jar", &["org.other.B"])?;
+
+        let found = fx
+            .finder
+            .match_class("org.example.pkg.A", &FindOptions::new())?;
+        assert_eq!(found.lookup_source, "scan");
+        assert_eq!(found.scan_root, fx.m2.join("org/example"));
+        assert_eq!(found.jars.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn classes_outside_their_package_directory_fall_back_to_the_whole_root() -> Result<()> {
+        let fx = Fixture::new("fallback")?;
+        fx.jar("org/example/demo/1.0/demo-1.0.jar", &["org.example.A"])?;
+        let shaded = fx.jar("com/vendor/shaded/2.0/shaded-2.0.jar", &["org.example.B"])?;
+
+        let found = fx.finder.find("org.example.B", &FindOptions::new())?;
+        assert_eq!(found.scanned_root, fx.m2.to_string_lossy());
+        assert_eq!(found.versions[0].jar_path, shaded.to_string_lossy());
+        assert_eq!(found.versions[0].lookup_source, "scan");
+        Ok(())
+    }
+
+    #[test]
+    fn the_first_repository_holding_the_class_wins() -> Result<()> {
+        let fx = Fixture::new("repositories")?;
+        let mirror = fx.base.join("mirror");
+        let mirrored = mirror.join("org/example/demo/1.0/demo-1.0.jar");
+        write_zip(
+            &mirrored,
+            &[("org/example/A.class".to_string(), String::new())],
+        )?;
+        fx.jar("org/example/demo/2.0/demo-2.0.jar", &["org.example.A"])?;
+        let repositories = vec![
+            Repository {
+                name: "mirror".to_string(),
+                ..Repository::local(mirror)
+            },
+            Repository::local(fx.m2.clone()),
+        ];
+        let finder = ClassFinder::open(fx.base.join("mirror.lmdb"), fx.m2.clone())?
+            .with_repositories(repositories);
+
+        let found = finder.match_class("org.example.A", &FindOptions::new())?;
+        assert_eq!(found.jars, [mirrored]);
+        Ok(())
+    }
+
+    #[test]
+    fn simple_names_resolve_to_the_most_common_qualified_name() -> Result<()> {
+        let fx = Fixture::new("simple")?;
+        fx.jar("org/example/demo/1.0/demo-1.0.jar", &["org.example.A"])?;
+        fx.jar("org/example/demo/2.0/demo-2.0.jar", &["org.example.A"])?;
+        fx.jar("com/other/lib/1.0/lib-1.0.jar", &["com.other.A"])?;
+
+        let found = fx.finder.find("A", &FindOptions::new())?;
+        assert_eq!(found.class_name, "org.example.A");
+        assert_eq!(found.matched_jars, 2);
+        let versions: Vec<_> = found
+            .versions
+            .iter()
+            .map(|v| v.version.as_deref().unwrap())
+            .collect();
+        assert_eq!(versions, ["1.0", "2.0"]);
+        assert_eq!(found.versions[0].lookup_source, "scan");
+        Ok(())
+    }
+
+    #[test]
+    fn version_selection_applies_to_registry_hits_and_scans() -> Result<()> {
+        let fx = Fixture::new("versions")?;
+        for version in ["1.0", "2.0", "3.0"] {
+            fx.jar(
+                &format!("org/example/demo/{version}/demo-{version}.jar"),
+                &["org.example.A"],
+            )?;
+        }
+        let versions = |options: &FindOptions| -> Result<Vec<String>> {
+            Ok(fx
+                .finder
+                .match_class("org.example.A", options)?
+                .jars
+                .iter()
+                .filter_map(|jar| extract_version_from_maven_path(jar))
+                .collect())
+        };
+
+        for indexed in [false, true] {
+            if indexed {
+                fx.finder.index(fx.m2.clone())?;
+            }
+            let only = FindOptions::new().with_version(Some("2.0"));
+            assert_eq!(versions(&only)?, ["2.0"], "indexed: {indexed}");
+            let latest = FindOptions::new().with_latest(true);
+            assert_eq!(versions(&latest)?, ["3.0"], "indexed: {indexed}");
+            let newest = FindOptions::new().with_max_versions(Some(2));
+            assert_eq!(versions(&newest)?, ["2.0", "3.0"], "indexed: {indexed}");
+        }
+
+        let missing = FindOptions::new().with_version(Some("9.9"));
+        let err = fx.finder.find("org.example.A", &missing).unwrap_err();
+        assert_eq!(code(&err), "not_found");
+        let invalid = FindOptions::new()
+            .with_version(Some("1.0"))
+            .with_latest(true);
+        let err = fx.finder.find("org.example.A", &invalid).unwrap_err();
+        assert_eq!(code(&err), "invalid_input");
+        assert!(fx.decompiler.calls().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn no_match_is_empty_when_matching_and_not_found_when_finding() -> Result<()> {
+        let fx = Fixture::new("missing")?;
+        fx.jar("org/example/demo/1.0/demo-1.0.jar", &["org.example.A"])?;
+
+        let matched = fx
+            .finder
+            .match_class("org.example.Missing", &FindOptions::new())?;
+        assert!(matched.jars.is_empty());
+        let err = fx.finder.find_matched(matched).unwrap_err();
+        assert_eq!(
+            ClassFinderError::from_error(&err),
+            ClassFinderError::NotFound {
+                class_name: "org.example.Missing".to_string(),
+                searched: fx.m2.display().to_string(),
+            }
+        );
+        assert_eq!(
+            code(&fx.finder.find("Missing", &FindOptions::new()).unwrap_err()),
+            "not_found"
+        );
+        assert!(fx.decompiler.calls().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn matching_needs_no_decompiler_but_reading_does() -> Result<()> {
+        let fx = Fixture::new("no_decompiler")?;
+        fx.jar("org/example/demo/1.0/demo-1.0.jar", &["org.example.A"])?;
+        let finder = ClassFinder::open(fx.base.join("bare.lmdb"), fx.m2.clone())?;
+
+        let matched = finder.match_class("org.example.A", &FindOptions::new())?;
+        assert_eq!(matched.jars.len(), 1);
+        let err = finder.find_matched(matched).unwrap_err();
+        assert_eq!(code(&err), "invalid_input");
+        Ok(())
+    }
+
+    #[test]
+    fn cached_and_sources_jar_content_skips_the_decompiler() -> Result<()> {
+        let fx = Fixture::new("stored")?;
+        let jar = fx.jar(
+            "org/example/demo/1.0/demo-1.0.jar",
+            &["org.example.A", "org.example.B"],
+        )?;
+        write_zip(
+            &fx.m2.join("org/example/demo/1.0/demo-1.0-sources.jar"),
+            &[(
+                "org/example/A.java".to_string(),
+                "package org.example;\n// from sources\npublic class A {}\n".to_string(),
+            )],
+        )?;
+
+        let from_sources = fx.finder.find("org.example.A", &FindOptions::new())?;
+        assert_eq!(from_sources.versions[0].source, "sources-jar");
+        assert!(from_sources.versions[0].content.contains("from sources"));
+        assert!(fx.decompiler.calls().is_empty());
+
+        let loaded = fx.finder.load_jar(&jar)?;
+        assert_eq!(loaded.classes_loaded, 2);
+        let calls = fx.decompiler.calls().len();
+        let cached = fx.finder.find("org.example.B", &FindOptions::new())?;
+        assert!(cached.versions[0].cache_hit);
+        assert_eq!(cached.versions[0].decompile_ms, None);
+        assert_eq!(fx.decompiler.calls().len(), calls);
+        Ok(())
+    }
+
+    #[test]
+    fn decompiler_failures_surface_without_a_javap_fallback() -> Result<()> {
+        let fx = Fixture::with_decompiler("failing", MockDecompiler::failing("org.example.A"))?;
+        fx.jar("org/example/demo/1.0/demo-1.0.jar", &["org.example.A"])?;
+
+        let err = fx
+            .finder
+            .find("org.example.A", &FindOptions::new())
+            .unwrap_err();
+        assert!(err.to_string().contains("mock cannot decompile"), "{err}");
+        assert_eq!(fx.decompiler.calls(), ["org.example.A"]);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn postprocessed_misses_and_cache_hits_have_the_same_bytes() -> Result<()> {
+        use crate::postprocess::Postprocessor;
+
+        let upper = |script: &str| {
+            Postprocessor::new(
+                vec!["sh".to_string(), "-c".to_string(), script.to_string()],
+                Duration::from_secs(10),
+            )
+        };
+        let fx = Fixture::with_decompiler(
+            "postprocess",
+            MockDecompiler {
+                postprocess: upper("tr a-z A-Z"),
+                ..MockDecompiler::default()
+            },
+        )?;
+        let jar = fx.jar(
+            "org/example/demo/1.0/demo-1.0.jar",
+            &["org.example.A", "org.example.B"],
+        )?;
+
+        let miss = fx.finder.find("org.example.A", &FindOptions::new())?;
+        let miss = &miss.versions[0];
+        assert!(miss.postprocessed);
+        assert!(miss.content.contains("PACKAGE ORG.EXAMPLE;"));
+        assert_eq!(miss.content_hash, hash_content(&miss.content));
+
+        fx.finder.load_jar(&jar)?;
+        let hit = fx.finder.find("org.example.A", &FindOptions::new())?;
+        let hit = &hit.versions[0];
+        assert!(hit.cache_hit);
+        assert!(hit.postprocessed);
+        assert_eq!(hit.content, miss.content);
+        assert_eq!(hit.content_hash, miss.content_hash);
+
+        let failing = Fixture::with_decompiler(
+            "postprocess_failing",
+            MockDecompiler {
+                postprocess: upper("exit 1"),
+                ..MockDecompiler::default()
+            },
+        )?;
+        failing.jar("org/example/demo/1.0/demo-1.0.jar", &["org.example.A"])?;
+        let raw = failing.finder.find("org.example.A", &FindOptions::new())?;
+        assert!(!raw.versions[0].postprocessed);
+        assert!(raw.versions[0].content.contains("package org.example;"));
+        Ok(())
+    }
+
+    #[test]
+    fn progress_events_come_from_indexing_and_parallel_warmups() -> Result<()> {
+        use crate::progress::ChannelProgress;
+
+        let fx = Fixture::new("progress")?;
+        for version in ["1.0", "2.0", "3.0"] {
+            fx.jar(
+                &format!("org/example/demo/{version}/demo-{version}.jar"),
+                &["org.example.A", "org.example.B"],
+            )?;
+        }
+        let (sink, events) = ChannelProgress::new();
+        let finder = ClassFinder::open(fx.base.join("progress.lmdb"), fx.m2.clone())?
+            .with_decompiler(Arc::clone(&fx.decompiler) as Arc<dyn Decompiler>)
+            .with_progress(Arc::new(sink));
+
+        finder.index(fx.m2.clone())?;
+        let indexed: Vec<ProgressEvent> = events.try_iter().collect();
+        assert_eq!(
+            indexed[0],
+            ProgressEvent::ScanStarted {
+                root: fx.m2.to_string_lossy().to_string()
+            }
+        );
+        assert_eq!(indexed.len(), 4, "{indexed:?}");
+        assert!(
+            indexed[1..]
+                .iter()
+                .all(|event| matches!(event, ProgressEvent::JarCataloged { classes: 2, .. }))
+        );
+
+        let params = WarmupParams {
+            group: Some("org.example"),
+            jobs: 3,
+            ..WarmupParams::default()
+        };
+        let result = finder.warmup(params, &mut |_| {})?;
+        assert_eq!(result.succeeded, 3);
+        let warmed: Vec<ProgressEvent> = events.try_iter().collect();
+        let count =
+            |wanted: fn(&ProgressEvent) -> bool| warmed.iter().filter(|e| wanted(e)).count();
+        assert_eq!(
+            count(|e| matches!(e, ProgressEvent::JarDecompiled { classes: 2, .. })),
+            3
+        );
+        let mut finished: Vec<usize> = warmed
+            .iter()
+            .filter_map(|event| match event {
+                ProgressEvent::WarmupTaskFinished {
+                    index,
+                    total: 3,
+                    ok: true,
+                    ..
+                } => Some(*index),
+                _ => None,
+            })
+            .collect();
+        finished.sort();
+        assert_eq!(finished, [1, 2, 3]);
+        assert!(matches!(
+            warmed.last(),
+            Some(ProgressEvent::FlushCompleted { written, .. }) if *written > 0
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn limited_scans_stop_probing_and_keep_the_newest_matches() -> Result<()> {
+        let fx = Fixture::new("max_matches")?;
+        let dir = fx.m2.join("org/example/demo");
+        let total = 300;
+        for i in 0..total {
+            let version = format!("1.{i:03}");
+            fx.jar(
+                &format!("org/example/demo/{version}/demo-{version}.jar"),
+                &["org.example.A"],
+            )?;
+        }
+        let probes = AtomicUsize::new(0);
+        let probe = |_: &Path| {
+            probes.fetch_add(1, Ordering::Relaxed);
+            true
+        };
+
+        let limited = jars_containing_class(scan_jars(&dir)?, Some(3), &probe)?;
+        assert!(probes.load(Ordering::Relaxed) <= PROBE_CHUNK_SIZE);
+        let versions: Vec<_> = limited
+            .iter()
+            .filter_map(|jar| extract_version_from_maven_path(jar))
+            .collect();
+        assert_eq!(versions, ["1.299", "1.298", "1.297"]);
+        assert_eq!(
+            jars_containing_class(scan_jars(&dir)?, Some(3), &probe)?,
+            limited
+        );
+
+        probes.store(0, Ordering::Relaxed);
+        assert_eq!(
+            jars_containing_class(scan_jars(&dir)?, None, &probe)?.len(),
+            total
+        );
+        assert_eq!(probes.load(Ordering::Relaxed), total);
+
+        let found = fx.finder.match_class(
+            "org.example.A",
+            &FindOptions::new().with_max_matches(Some(2)),
+        )?;
+        assert_eq!(found.lookup_source, "scan");
+        let versions: Vec<_> = found
+            .jars
+            .iter()
+            .filter_map(|jar| extract_version_from_maven_path(jar))
+            .collect();
+        assert_eq!(versions, ["1.298", "1.299"]);
+        Ok(())
+    }
+
+    #[test]
+    fn a_find_reads_through_one_transaction_released_for_decompiling() -> Result<()> {
+        let fx = Fixture::new("read_session")?;
+        for i in 1..=10 {
+            let jar = fx.jar(
+                &format!("org/example/demo/2.{i:02}/demo-2.{i:02}.jar"),
+                &["org.example.A"],
+            )?;
+            fx.finder.load_jar(&jar)?;
+        }
+        fx.finder.index(fx.m2.clone())?;
+
+        let mut session = fx.finder.read_session();
+        let found = fx
+            .finder
+            .match_class_in(&mut session, "org.example.A", &FindOptions::new())?;
+        assert_eq!(found.lookup_source, "registry");
+        let result = fx.finder.find_matched_in(&mut session, found)?;
+        assert_eq!(result.versions.len(), 10);
+        assert!(result.versions.iter().all(|v| v.cache_hit));
+        assert_eq!(session.transactions(), 1);
+        drop(session);
+
+        // The oldest version is not cached: its decompile runs without the
+        // transaction, and the remaining reads begin a new one.
+        fx.jar("org/example/demo/1.00/demo-1.00.jar", &["org.example.A"])?;
+        fx.finder.index(fx.m2.clone())?;
+        let calls = fx.decompiler.calls().len();
+        let mut session = fx.finder.read_session();
+        let found = fx
+            .finder
+            .match_class_in(&mut session, "org.example.A", &FindOptions::new())?;
+        let result = fx.finder.find_matched_in(&mut session, found)?;
+        assert_eq!(result.versions.len(), 11);
+        assert!(!result.versions[0].cache_hit);
+        assert_eq!(fx.decompiler.calls().len(), calls + 1);
+        assert_eq!(session.transactions(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn repeated_scans_reuse_the_stored_jar_list() -> Result<()> {
+        let fx = Fixture::new("scan_cache")?;
+        fx.jar("org/example/demo/1.0/demo-1.0.jar", &["org.example.A"])?;
+        let options = FindOptions::new();
+
+        let first = fx.finder.match_class("org.example.A", &options)?;
+        assert_eq!(first.lookup_source, "scan");
+        let walks = fx.finder.scan_cache().walks();
+        assert!(walks > 0);
+        let second = fx.finder.match_class("org.example.A", &options)?;
+        assert_eq!(second.jars, first.jars);
+        assert_eq!(fx.finder.scan_cache().walks(), walks);
+        assert!(
+            fx.finder
+                .match_class("Missing", &options)
+                .is_ok_and(|m| m.jars.is_empty())
+        );
+        fx.finder.match_class("A", &options)?;
+        let walks = fx.finder.scan_cache().walks();
+        fx.finder.match_class("A", &options)?;
+        assert_eq!(fx.finder.scan_cache().walks(), walks);
+
+        // A new version deep in the tree stays hidden until a fresh scan or
+        // an incremental index drops the stored list.
+        fx.jar("org/example/demo/2.0/demo-2.0.jar", &["org.example.A"])?;
+        assert_eq!(
+            fx.finder.match_class("org.example.A", &options)?.jars.len(),
+            1
+        );
+        let fresh = fx
+            .finder
+            .match_class("org.example.A", &options.clone().with_fresh_scan(true))?;
+        assert_eq!(fresh.jars.len(), 2);
+        assert!(fx.finder.scan_cache().walks() > walks);
+
+        fx.jar("org/example/demo/3.0/demo-3.0.jar", &["org.example.B"])?;
+        let walks = fx.finder.scan_cache().walks();
+        IncrementalIndexer::new(fx.finder.cache().db(), fx.m2.clone())
+            .run_once(fx.finder.registry())?;
+        let missing = fx.finder.match_class("org.example.Missing", &options)?;
+        assert!(missing.jars.is_empty());
+        assert!(fx.finder.scan_cache().walks() > walks);
+        Ok(())
+    }
+
+    #[test]
+    fn class_filters_skip_cataloged_jars_but_not_changed_ones() -> Result<()> {
+        let fx = Fixture::new("class_filters")?;
+        let old = fx.jar("org/example/demo/1.0/demo-1.0.jar", &["org.example.A"])?;
+        let new = fx.jar("org/example/demo/2.0/demo-2.0.jar", &["org.example.A"])?;
+        fx.finder.index(fx.m2.clone())?;
+
+        let mut candidates = vec![old.clone(), new.clone()];
+        let mut session = fx.finder.read_session();
+        fx.finder
+            .registry()
+            .rule_out_in(&mut session, "org/example/C.class", &mut candidates)?;
+        drop(session);
+        assert!(candidates.len() < 2);
+
+        // Rewritten after cataloging: its filter no longer describes it.
+        fx.jar(
+            "org/example/demo/1.0/demo-1.0.jar",
+            &["org.example.A", "org.example.C"],
+        )?;
+        let found = fx
+            .finder
+            .match_class("org.example.C", &FindOptions::new())?;
+        assert_eq!(found.lookup_source, "scan");
+        assert_eq!(found.jars, [old]);
+        let found = fx
+            .finder
+            .match_class("org.example.A", &FindOptions::new())?;
+        assert_eq!(found.lookup_source, "registry");
+        assert_eq!(found.jars.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn registry_hits_of_unchanged_jars_are_trusted_unless_verify_jars() -> Result<()> {
+        let fx = Fixture::new("trusted_hits")?;
+        let jar = fx.jar("org/example/demo/1.0/demo-1.0.jar", &["org.example.A"])?;
+        // A registry entry the jar does not back shows whether it was opened.
+        fx.finder.registry().update_registry_and_mark_cataloged(
+            &jar.to_string_lossy(),
+            &["org.example.Ghost".to_string()],
+        )?;
+
+        let trusted = fx
+            .finder
+            .match_class("org.example.Ghost", &FindOptions::new())?;
+        assert_eq!(trusted.lookup_source, "registry");
+        assert_eq!(trusted.jars, [jar]);
+        let verified = fx.finder.match_class(
+            "org.example.Ghost",
+            &FindOptions::new().with_verify_jars(true),
+        )?;
+        assert!(verified.jars.is_empty());
+
+        // Changed since it was cataloged: opened again.
+        fx.jar(
+            "org/example/demo/1.0/demo-1.0.jar",
+            &["org.example.A", "org.example.B"],
+        )?;
+        let changed = fx
+            .finder
+            .match_class("org.example.Ghost", &FindOptions::new())?;
+        assert!(changed.jars.is_empty());
+        Ok(())
+    }
+}
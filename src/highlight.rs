@@ -0,0 +1,295 @@
+//! ANSI colors for `find` output on a terminal.
+//!
+//! [`highlight_java`] runs a small lexer over the source (comments, string
+//! and char literals including text blocks, numbers, annotations, keywords)
+//! and wraps each token in an escape sequence; every other byte is copied
+//! as is, so stripping the escapes gives back the input. Whether to color at
+//! all is [`ColorChoice::enabled`]'s call; output written with `-o` is never
+//! colored.
+
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// `Auto` colors only when stdout is a terminal.
+    pub fn enabled(self, stdout_is_terminal: bool) -> bool {
+        match self {
+            ColorChoice::Auto => stdout_is_terminal,
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Keyword,
+    String,
+    Comment,
+    Number,
+    Annotation,
+    Good,
+    Notice,
+    Emphasis,
+}
+
+impl Style {
+    fn code(self) -> &'static str {
+        match self {
+            Style::Keyword => "1;35",
+            Style::String => "32",
+            Style::Comment => "90",
+            Style::Number => "36",
+            Style::Annotation => "33",
+            Style::Good => "32",
+            Style::Notice => "33",
+            Style::Emphasis => "1",
+        }
+    }
+}
+
+const RESET: &str = "\x1b[0m";
+
+/// `text` wrapped in the escape sequence for `style`.
+pub fn paint(style: Style, text: &str) -> String {
+    format!("\x1b[{}m{text}{RESET}", style.code())
+}
+
+const KEYWORDS: &[&str] = &[
+    "abstract",
+    "assert",
+    "boolean",
+    "break",
+    "byte",
+    "case",
+    "catch",
+    "char",
+    "class",
+    "const",
+    "continue",
+    "default",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "extends",
+    "false",
+    "final",
+    "finally",
+    "float",
+    "for",
+    "goto",
+    "if",
+    "implements",
+    "import",
+    "instanceof",
+    "int",
+    "interface",
+    "long",
+    "native",
+    "new",
+    "null",
+    "package",
+    "permits",
+    "private",
+    "protected",
+    "public",
+    "record",
+    "return",
+    "sealed",
+    "short",
+    "static",
+    "strictfp",
+    "super",
+    "switch",
+    "synchronized",
+    "this",
+    "throw",
+    "throws",
+    "transient",
+    "true",
+    "try",
+    "var",
+    "void",
+    "volatile",
+    "while",
+    "yield",
+];
+
+/// `source` with ANSI colors around Java tokens.
+pub fn highlight_java(source: &str) -> String {
+    let bytes = source.as_bytes();
+    let mut out = String::with_capacity(source.len() + source.len() / 4);
+    let mut plain_start = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let (style, end) = match bytes[i] {
+            b'/' if bytes.get(i + 1) == Some(&b'/') => (Style::Comment, line_end(bytes, i)),
+            b'/' if bytes.get(i + 1) == Some(&b'*') => (
+                Style::Comment,
+                find(bytes, i + 2, b"*/").map_or(bytes.len(), |at| at + 2),
+            ),
+            b'"' if bytes[i..].starts_with(b"\"\"\"") => (
+                Style::String,
+                find_unescaped(bytes, i + 3, b"\"\"\"").map_or(bytes.len(), |at| at + 3),
+            ),
+            b'"' | b'\'' => (Style::String, quoted_end(bytes, i)),
+            b'@' if bytes.get(i + 1).is_some_and(|b| is_ident_start(*b)) => {
+                (Style::Annotation, annotation_end(bytes, i + 1))
+            }
+            b if b.is_ascii_digit() && (i == 0 || !is_ident_part(bytes[i - 1])) => {
+                (Style::Number, number_end(bytes, i))
+            }
+            b if is_ident_start(b) && (i == 0 || !is_ident_part(bytes[i - 1])) => {
+                let end = ident_end(bytes, i);
+                if KEYWORDS.contains(&&source[i..end]) {
+                    (Style::Keyword, end)
+                } else {
+                    i = end;
+                    continue;
+                }
+            }
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        out.push_str(&source[plain_start..i]);
+        out.push_str(&paint(style, &source[i..end]));
+        i = end;
+        plain_start = end;
+    }
+    out.push_str(&source[plain_start..]);
+    out
+}
+
+fn is_ident_start(b: u8) -> bool {
+    b.is_ascii_alphabetic() || b == b'_' || b == b'$'
+}
+
+fn is_ident_part(b: u8) -> bool {
+    is_ident_start(b) || b.is_ascii_digit() || b >= 0x80
+}
+
+fn ident_end(bytes: &[u8], mut i: usize) -> usize {
+    while i < bytes.len() && is_ident_part(bytes[i]) {
+        i += 1;
+    }
+    i
+}
+
+/// `@a.b.C` is one annotation; a trailing dot is not part of it.
+fn annotation_end(bytes: &[u8], mut i: usize) -> usize {
+    loop {
+        i = ident_end(bytes, i);
+        if bytes.get(i) == Some(&b'.') && bytes.get(i + 1).is_some_and(|b| is_ident_start(*b)) {
+            i += 1;
+        } else {
+            return i;
+        }
+    }
+}
+
+fn number_end(bytes: &[u8], mut i: usize) -> usize {
+    while i < bytes.len()
+        && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_' || bytes[i] == b'.')
+    {
+        i += 1;
+    }
+    i
+}
+
+fn line_end(bytes: &[u8], i: usize) -> usize {
+    bytes[i..]
+        .iter()
+        .position(|b| *b == b'\n')
+        .map_or(bytes.len(), |at| i + at)
+}
+
+fn find(bytes: &[u8], from: usize, needle: &[u8]) -> Option<usize> {
+    bytes
+        .get(from..)?
+        .windows(needle.len())
+        .position(|w| w == needle)
+        .map(|at| from + at)
+}
+
+fn find_unescaped(bytes: &[u8], mut from: usize, needle: &[u8]) -> Option<usize> {
+    while from < bytes.len() {
+        if bytes[from] == b'\\' {
+            from += 2;
+        } else if bytes[from..].starts_with(needle) {
+            return Some(from);
+        } else {
+            from += 1;
+        }
+    }
+    None
+}
+
+/// End of a `"..."` or `'...'` literal; an unterminated one stops at the
+/// end of the line.
+fn quoted_end(bytes: &[u8], start: usize) -> usize {
+    let quote = bytes[start];
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'\n' => return i,
+            b if b == quote => return i + 1,
+            _ => i += 1,
+        }
+    }
+    bytes.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip(colored: &str) -> String {
+        let mut out = String::new();
+        let mut rest = colored;
+        while let Some(at) = rest.find('\x1b') {
+            out.push_str(&rest[..at]);
+            let end = rest[at..].find('m').expect("escape end");
+            rest = &rest[at + end + 1..];
+        }
+        out.push_str(rest);
+        out
+    }
+
+    #[test]
+    fn highlighting_only_adds_escapes() {
+        let source = "package a.b;\n\n/** Doc. */\n@Deprecated\npublic final class C2 {\n    \
+                      // int x = 1;\n    String s = \"a\\\"b // no\";\n    char c = '\\'';\n    \
+                      String t = \"\"\"\n      x \\\"\"\" y\n      \"\"\";\n    long n = 0x1F_00L;\n    \
+                      int classes = 3; /* open";
+        let colored = highlight_java(source);
+        assert_eq!(strip(&colored), source);
+        assert!(colored.contains(&paint(Style::Keyword, "public")));
+        assert!(colored.contains(&paint(Style::Annotation, "@Deprecated")));
+        assert!(colored.contains(&paint(Style::Comment, "// int x = 1;")));
+        assert!(colored.contains(&paint(Style::String, "\"a\\\"b // no\"")));
+        assert!(colored.contains(&paint(Style::String, "'\\''")));
+        assert!(colored.contains(&paint(Style::Number, "0x1F_00L")));
+        assert!(colored.contains(&paint(Style::Comment, "/* open")));
+        assert!(colored.contains(&format!("{} classes = ", paint(Style::Keyword, "int"))));
+        assert!(!colored.contains(&paint(Style::Number, "2")));
+    }
+
+    #[test]
+    fn auto_follows_the_terminal() {
+        assert!(ColorChoice::Auto.enabled(true));
+        assert!(!ColorChoice::Auto.enabled(false));
+        assert!(ColorChoice::Always.enabled(false));
+        assert!(!ColorChoice::Never.enabled(true));
+    }
+}
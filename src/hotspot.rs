@@ -2,20 +2,124 @@ use anyhow::Result;
 use heed::types::Str;
 use heed::{Database, Env};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::hash::BuildHasher;
+use std::path::Path;
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use crate::cache::JAR_HOTSPOT_DB;
+use crate::buffer::Mutation;
+use crate::cache::{CLASS_HOTSPOT_DB, JAR_HOTSPOT_DB};
+use crate::scan::maven_coordinates;
 use crate::warmup::{WarmupMode, WarmupPriority};
 
 type StrDb = Database<Str, Str>;
 
+/// Accesses after which a jar is warmed in full.
+pub const DEFAULT_WARMUP_THRESHOLD: u32 = 2;
+
+/// Classes of one jar with their own row in `CLASS_HOTSPOT_DB`. Past this, a
+/// newly accessed class only replaces the jar's least accessed one now and
+/// then, so a jar scanned class by class cannot grow the table unbounded.
+pub const MAX_TRACKED_CLASSES_PER_JAR: u32 = 256;
+
+/// Access timestamps kept per jar for [`ranking_score`].
+pub const RECENT_ACCESS_SLOTS: usize = 16;
+
+/// Accesses older than this no longer count as recent.
+const RECENT_WINDOW_SECS: u64 = 30 * DAY_SECS;
+
+/// Score of a distinct recent day, in accesses.
+const DAY_WEIGHT: u64 = 10;
+
+const DAY_SECS: u64 = 86400;
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct JarHotspot {
     pub access_count: u32,
     pub last_access: u64,
     pub warmed: bool,
     pub class_count: u32,
+    /// Unix time of the last warmup, 0 for rows written before it was kept.
+    #[serde(default)]
+    pub warmed_at: u64,
+    /// Classes of the jar with a row in `CLASS_HOTSPOT_DB`.
+    #[serde(default)]
+    pub tracked_classes: u32,
+    /// Times of the last [`RECENT_ACCESS_SLOTS`] accesses, oldest first.
+    #[serde(default)]
+    pub recent_accesses: Vec<u64>,
+}
+
+impl JarHotspot {
+    fn record(&mut self, count: u32, at: u64) {
+        self.access_count = self.access_count.saturating_add(count);
+        self.last_access = self.last_access.max(at);
+        let added = (count as usize).min(RECENT_ACCESS_SLOTS);
+        self.recent_accesses.extend(std::iter::repeat_n(at, added));
+        self.recent_accesses.sort_unstable();
+        let excess = self
+            .recent_accesses
+            .len()
+            .saturating_sub(RECENT_ACCESS_SLOTS);
+        self.recent_accesses.drain(..excess);
+    }
+}
+
+/// Ranks a jar by its accesses plus [`DAY_WEIGHT`] for each distinct day of
+/// the last 30 it was accessed on, so a jar used a few times every day
+/// outranks one hit many times in a single session.
+pub fn ranking_score(access_count: u32, recent_accesses: &[u64], now: u64) -> u64 {
+    let days: HashSet<u64> = recent_accesses
+        .iter()
+        .filter(|at| now.saturating_sub(**at) < RECENT_WINDOW_SECS)
+        .map(|at| at / DAY_SECS)
+        .collect();
+    days.len() as u64 * DAY_WEIGHT + u64::from(access_count)
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClassHotspot {
+    pub access_count: u32,
+    pub last_access: u64,
+}
+
+/// Rows dropped by [`HotspotTracker::reset`] or [`HotspotTracker::trim`].
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HotspotRemoval {
+    pub jars: usize,
+    pub classes: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JarAccess {
+    pub jar_path: String,
+    pub score: u64,
+    pub access_count: u32,
+    pub last_access: u64,
+    pub warmed: bool,
+    pub tracked_classes: u32,
+}
+
+/// Hotspot rows of the jars of one Maven group, or of one artifact of it.
+#[derive(Debug, Clone, Serialize)]
+pub struct GroupAccess {
+    pub group_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artifact_id: Option<String>,
+    pub score: u64,
+    pub access_count: u64,
+    pub warmed_jars: u32,
+    pub unwarmed_jars: u32,
+    pub class_count: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClassAccess {
+    pub class_name: String,
+    pub jar_path: String,
+    pub access_count: u32,
+    pub last_access: u64,
 }
 
 #[derive(Debug, Clone)]
@@ -24,6 +128,14 @@ pub struct WarmupRequest {
     pub mode: WarmupMode,
 }
 
+/// Jar accesses folded into one increment per jar, ready to be queued on a
+/// write buffer, and the warmups the folded counts call for.
+#[derive(Debug, Clone, Default)]
+pub struct AccessPlan {
+    pub mutations: Vec<Mutation>,
+    pub requests: Vec<(String, WarmupRequest)>,
+}
+
 #[derive(Debug, Clone)]
 pub struct HotspotTracker {
     db: Arc<Env>,
@@ -39,38 +151,82 @@ impl HotspotTracker {
     }
 
     pub fn record_access(&self, jar_key: &str) -> Result<Option<WarmupRequest>> {
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
-
         let mut hotspot = self.get_hotspot(jar_key)?.unwrap_or_default();
-        hotspot.access_count = hotspot.access_count.saturating_add(1);
-        hotspot.last_access = now;
+        let request = self.warmup_request(&hotspot, 1);
+        hotspot.record(1, unix_now());
+        self.put_hotspot(jar_key, &hotspot)?;
+        Ok(request)
+    }
 
-        let mut request = None;
-        if !hotspot.warmed {
-            if hotspot.access_count >= self.warmup_threshold {
-                request = Some(WarmupRequest {
-                    priority: WarmupPriority::High,
-                    mode: WarmupMode::AllClasses,
-                });
-            } else if hotspot.access_count == 1 {
-                request = Some(WarmupRequest {
-                    priority: WarmupPriority::Normal,
-                    mode: WarmupMode::TopLevelOnly,
-                });
+    /// Folds `jar_keys`, one entry per access, into one
+    /// [`Mutation::RecordJarAccess`] per jar stamped with the current time, so
+    /// a command commits all its accesses with its other buffered writes.
+    /// Only reads the table: the warmups are decided on the stored counts
+    /// plus the folded ones, so a threshold crossed by several accesses at
+    /// once is still seen.
+    pub fn plan_accesses<'a>(
+        &self,
+        jar_keys: impl IntoIterator<Item = &'a str>,
+    ) -> Result<AccessPlan> {
+        let mut counts: Vec<(&str, u32)> = Vec::new();
+        for jar_key in jar_keys {
+            match counts.iter_mut().find(|(key, _)| *key == jar_key) {
+                Some((_, count)) => *count = count.saturating_add(1),
+                None => counts.push((jar_key, 1)),
             }
         }
+        if counts.is_empty() {
+            return Ok(AccessPlan::default());
+        }
 
-        self.put_hotspot(jar_key, &hotspot)?;
-        Ok(request)
+        let at = unix_now();
+        let rtxn = self.db.read_txn()?;
+        let table = open_named_db(&self.db, &rtxn, JAR_HOTSPOT_DB)?;
+        let mut plan = AccessPlan::default();
+        for (jar_key, count) in counts {
+            let hotspot = table
+                .get(&rtxn, jar_key)?
+                .and_then(|v| serde_json::from_str::<JarHotspot>(v).ok())
+                .unwrap_or_default();
+            if let Some(request) = self.warmup_request(&hotspot, count) {
+                plan.requests.push((jar_key.to_string(), request));
+            }
+            plan.mutations.push(Mutation::RecordJarAccess {
+                jar_key: jar_key.to_string(),
+                count,
+                at,
+            });
+        }
+        Ok(plan)
+    }
+
+    /// The warmup `added` more accesses to a jar at `hotspot` call for: a full
+    /// one once the threshold is reached, a top-level one on the first access
+    /// otherwise, and none once the jar is warmed.
+    fn warmup_request(&self, hotspot: &JarHotspot, added: u32) -> Option<WarmupRequest> {
+        if hotspot.warmed || added == 0 {
+            return None;
+        }
+        if hotspot.access_count.saturating_add(added) >= self.warmup_threshold {
+            Some(WarmupRequest {
+                priority: WarmupPriority::High,
+                mode: WarmupMode::AllClasses,
+            })
+        } else if hotspot.access_count == 0 {
+            Some(WarmupRequest {
+                priority: WarmupPriority::Normal,
+                mode: WarmupMode::TopLevelOnly,
+            })
+        } else {
+            None
+        }
     }
 
     pub fn mark_warmed(&self, jar_key: &str, class_count: u32) -> Result<()> {
         let mut hotspot = self.get_hotspot(jar_key)?.unwrap_or_default();
         hotspot.warmed = true;
         hotspot.class_count = class_count;
+        hotspot.warmed_at = unix_now();
         self.put_hotspot(jar_key, &hotspot)?;
         Ok(())
     }
@@ -94,14 +250,212 @@ impl HotspotTracker {
         Ok(())
     }
 
+    /// A find of `class_name` in `jar_key`, to be queued on a write buffer.
+    pub fn class_access(class_name: &str, jar_key: &str) -> Mutation {
+        Mutation::RecordClassAccess {
+            class_name: class_name.to_string(),
+            jar_key: jar_key.to_string(),
+            at: unix_now(),
+        }
+    }
+
+    /// Jars by [`ranking_score`], most recent first on ties.
+    pub fn top_jars(&self, top: usize) -> Result<Vec<JarAccess>> {
+        let now = unix_now();
+        let rtxn = self.db.read_txn()?;
+        let table = open_named_db(&self.db, &rtxn, JAR_HOTSPOT_DB)?;
+        let mut entries = Vec::new();
+        for item in table.iter(&rtxn)? {
+            let (k, v) = item?;
+            let Ok(h) = serde_json::from_str::<JarHotspot>(v) else {
+                continue;
+            };
+            if h.access_count == 0 {
+                continue;
+            }
+            entries.push(JarAccess {
+                jar_path: k.to_string(),
+                score: ranking_score(h.access_count, &h.recent_accesses, now),
+                access_count: h.access_count,
+                last_access: h.last_access,
+                warmed: h.warmed,
+                tracked_classes: h.tracked_classes,
+            });
+        }
+        entries.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then_with(|| b.last_access.cmp(&a.last_access))
+                .then_with(|| a.jar_path.cmp(&b.jar_path))
+        });
+        entries.truncate(top);
+        Ok(entries)
+    }
+
+    /// Jars under `m2_repo` summed per `groupId`, or per `groupId:artifactId`
+    /// with `by_artifact`, highest summed [`ranking_score`] first. Jars outside
+    /// the repository are left out.
+    pub fn top_groups(
+        &self,
+        m2_repo: &Path,
+        by_artifact: bool,
+        top: usize,
+    ) -> Result<Vec<GroupAccess>> {
+        let now = unix_now();
+        let rtxn = self.db.read_txn()?;
+        let table = open_named_db(&self.db, &rtxn, JAR_HOTSPOT_DB)?;
+        let mut groups: HashMap<(String, Option<String>), GroupAccess> = HashMap::new();
+        for item in table.iter(&rtxn)? {
+            let (k, v) = item?;
+            let Ok(h) = serde_json::from_str::<JarHotspot>(v) else {
+                continue;
+            };
+            let Some((group_id, artifact_id)) = maven_coordinates(m2_repo, Path::new(k)) else {
+                continue;
+            };
+            let artifact_id = by_artifact.then_some(artifact_id);
+            let entry = groups
+                .entry((group_id.clone(), artifact_id.clone()))
+                .or_insert_with(|| GroupAccess {
+                    group_id,
+                    artifact_id,
+                    score: 0,
+                    access_count: 0,
+                    warmed_jars: 0,
+                    unwarmed_jars: 0,
+                    class_count: 0,
+                });
+            entry.score += ranking_score(h.access_count, &h.recent_accesses, now);
+            entry.access_count += u64::from(h.access_count);
+            entry.class_count += u64::from(h.class_count);
+            if h.warmed {
+                entry.warmed_jars += 1;
+            } else {
+                entry.unwarmed_jars += 1;
+            }
+        }
+        let mut entries: Vec<GroupAccess> = groups.into_values().collect();
+        entries.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then_with(|| b.access_count.cmp(&a.access_count))
+                .then_with(|| a.group_id.cmp(&b.group_id))
+                .then_with(|| a.artifact_id.cmp(&b.artifact_id))
+        });
+        entries.truncate(top);
+        Ok(entries)
+    }
+
+    /// Most accessed classes, of every jar or only of `jar_key`.
+    pub fn top_classes(&self, jar_key: Option<&str>, top: usize) -> Result<Vec<ClassAccess>> {
+        let rtxn = self.db.read_txn()?;
+        let Some(table) = self
+            .db
+            .open_database::<Str, Str>(&rtxn, Some(CLASS_HOTSPOT_DB))?
+        else {
+            return Ok(Vec::new());
+        };
+        let mut entries = Vec::new();
+        for item in table.iter(&rtxn)? {
+            let (k, v) = item?;
+            let Some((class_name, jar_path)) = k.split_once("::") else {
+                continue;
+            };
+            if jar_key.is_some_and(|jar_key| jar_key != jar_path) {
+                continue;
+            }
+            let Ok(h) = serde_json::from_str::<ClassHotspot>(v) else {
+                continue;
+            };
+            entries.push(ClassAccess {
+                class_name: class_name.to_string(),
+                jar_path: jar_path.to_string(),
+                access_count: h.access_count,
+                last_access: h.last_access,
+            });
+        }
+        entries.sort_by(|a, b| {
+            b.access_count
+                .cmp(&a.access_count)
+                .then_with(|| b.last_access.cmp(&a.last_access))
+                .then_with(|| a.class_name.cmp(&b.class_name))
+                .then_with(|| a.jar_path.cmp(&b.jar_path))
+        });
+        entries.truncate(top);
+        Ok(entries)
+    }
+
+    /// Deletes the hotspot rows of every jar, or only of `jar_key`, with the
+    /// jars' class rows. A reset jar is warmed again on its next accesses.
+    pub fn reset(&self, jar_key: Option<&str>) -> Result<HotspotRemoval> {
+        let mut wtxn = self.db.write_txn()?;
+        let (jars, classes) = hotspot_tables(&self.db, &mut wtxn)?;
+        let removal = match jar_key {
+            None => HotspotRemoval {
+                jars: clear_table(&jars, &mut wtxn)?,
+                classes: clear_table(&classes, &mut wtxn)?,
+            },
+            Some(jar_key) => HotspotRemoval {
+                jars: usize::from(jars.delete(&mut wtxn, jar_key)?),
+                classes: delete_class_rows(&classes, &mut wtxn, |_, jar, _| jar == jar_key)?
+                    .values()
+                    .sum(),
+            },
+        };
+        wtxn.commit()?;
+        Ok(removal)
+    }
+
+    /// Deletes the jar and class rows last accessed more than `older_than`
+    /// ago, along with the class rows of the jars deleted. Jar rows that were
+    /// only warmed, never accessed, are kept.
+    pub fn trim(&self, older_than: Duration) -> Result<HotspotRemoval> {
+        let cutoff = unix_now().saturating_sub(older_than.as_secs());
+        let mut wtxn = self.db.write_txn()?;
+        let (jars, classes) = hotspot_tables(&self.db, &mut wtxn)?;
+
+        let mut stale_jars = HashSet::new();
+        let mut kept_jars = HashMap::new();
+        for item in jars.iter(&wtxn)? {
+            let (k, v) = item?;
+            let Ok(h) = serde_json::from_str::<JarHotspot>(v) else {
+                continue;
+            };
+            if h.access_count > 0 && h.last_access < cutoff {
+                stale_jars.insert(k.to_string());
+            } else {
+                kept_jars.insert(k.to_string(), h);
+            }
+        }
+        let removed = delete_class_rows(&classes, &mut wtxn, |_, jar_key, h| {
+            h.last_access < cutoff || stale_jars.contains(jar_key)
+        })?;
+
+        for jar_key in &stale_jars {
+            jars.delete(&mut wtxn, jar_key)?;
+        }
+        for (jar_key, count) in &removed {
+            if let Some(mut h) = kept_jars.remove(jar_key) {
+                h.tracked_classes = h.tracked_classes.saturating_sub(*count as u32);
+                jars.put(&mut wtxn, jar_key, &serde_json::to_string(&h)?)?;
+            }
+        }
+        wtxn.commit()?;
+        Ok(HotspotRemoval {
+            jars: stale_jars.len(),
+            classes: removed.values().sum(),
+        })
+    }
+
     pub fn top_unwarmed_jars(&self, top: usize) -> Result<Vec<String>> {
         if top == 0 {
             return Ok(Vec::new());
         }
 
+        let now = unix_now();
         let rtxn = self.db.read_txn()?;
         let table = open_named_db(&self.db, &rtxn, JAR_HOTSPOT_DB)?;
-        let mut entries: Vec<(u32, u64, String)> = Vec::new();
+        let mut entries: Vec<(u64, u64, String)> = Vec::new();
         for item in table.iter(&rtxn)? {
             let (k, v) = item?;
             let jar_key = k.to_string();
@@ -111,7 +465,8 @@ impl HotspotTracker {
             if h.warmed || h.access_count == 0 {
                 continue;
             }
-            entries.push((h.access_count, h.last_access, jar_key));
+            let score = ranking_score(h.access_count, &h.recent_accesses, now);
+            entries.push((score, h.last_access, jar_key));
         }
 
         entries.sort_by(|a, b| {
@@ -123,11 +478,158 @@ impl HotspotTracker {
     }
 }
 
+pub fn class_hotspot_key(class_name: &str, jar_key: &str) -> String {
+    format!("{class_name}::{jar_key}")
+}
+
+/// Adds `count` accesses to `jar_key`, in the write transaction of a buffer
+/// flush. The last access only moves forward, whatever order concurrent
+/// commands commit in.
+pub(crate) fn record_jar_access(
+    jars: &StrDb,
+    wtxn: &mut heed::RwTxn<'_>,
+    jar_key: &str,
+    count: u32,
+    at: u64,
+) -> Result<()> {
+    let mut hotspot = jars
+        .get(wtxn, jar_key)?
+        .and_then(|v| serde_json::from_str::<JarHotspot>(v).ok())
+        .unwrap_or_default();
+    hotspot.record(count, at);
+    jars.put(wtxn, jar_key, &serde_json::to_string(&hotspot)?)?;
+    Ok(())
+}
+
+/// Counts an access to `class_name` in `jar_key`, in the write transaction of
+/// a buffer flush. A class the jar has no room for is admitted with a chance
+/// of one in [`MAX_TRACKED_CLASSES_PER_JAR`], replacing the least accessed
+/// one, so classes that keep being asked for eventually get tracked.
+pub(crate) fn record_class_access(
+    classes: &StrDb,
+    jars: &StrDb,
+    wtxn: &mut heed::RwTxn<'_>,
+    class_name: &str,
+    jar_key: &str,
+    at: u64,
+) -> Result<()> {
+    let key = class_hotspot_key(class_name, jar_key);
+    if let Some(mut hotspot) = classes
+        .get(wtxn, &key)?
+        .and_then(|v| serde_json::from_str::<ClassHotspot>(v).ok())
+    {
+        hotspot.access_count = hotspot.access_count.saturating_add(1);
+        hotspot.last_access = at;
+        classes.put(wtxn, &key, &serde_json::to_string(&hotspot)?)?;
+        return Ok(());
+    }
+
+    let mut jar = jars
+        .get(wtxn, jar_key)?
+        .and_then(|v| serde_json::from_str::<JarHotspot>(v).ok())
+        .unwrap_or_default();
+    if jar.tracked_classes >= MAX_TRACKED_CLASSES_PER_JAR {
+        let admit = std::collections::hash_map::RandomState::new()
+            .hash_one(&key)
+            .is_multiple_of(u64::from(MAX_TRACKED_CLASSES_PER_JAR));
+        if !admit {
+            return Ok(());
+        }
+        match least_accessed_class(classes, wtxn, jar_key)? {
+            Some(demoted) => {
+                classes.delete(wtxn, &demoted)?;
+            }
+            None => jar.tracked_classes = 0,
+        }
+    }
+    if jar.tracked_classes < MAX_TRACKED_CLASSES_PER_JAR {
+        jar.tracked_classes += 1;
+    }
+    let hotspot = ClassHotspot {
+        access_count: 1,
+        last_access: at,
+    };
+    classes.put(wtxn, &key, &serde_json::to_string(&hotspot)?)?;
+    jars.put(wtxn, jar_key, &serde_json::to_string(&jar)?)?;
+    Ok(())
+}
+
+fn hotspot_tables(env: &Env, wtxn: &mut heed::RwTxn<'_>) -> Result<(StrDb, StrDb)> {
+    let jars = env.create_database::<Str, Str>(wtxn, Some(JAR_HOTSPOT_DB))?;
+    let classes = env.create_database::<Str, Str>(wtxn, Some(CLASS_HOTSPOT_DB))?;
+    Ok((jars, classes))
+}
+
+fn clear_table(table: &StrDb, wtxn: &mut heed::RwTxn<'_>) -> Result<usize> {
+    let rows = table.len(wtxn)? as usize;
+    table.clear(wtxn)?;
+    Ok(rows)
+}
+
+/// Deletes the class rows `remove` picks, given the class, its jar and its
+/// counts. Returns how many were deleted per jar.
+fn delete_class_rows(
+    classes: &StrDb,
+    wtxn: &mut heed::RwTxn<'_>,
+    remove: impl Fn(&str, &str, &ClassHotspot) -> bool,
+) -> Result<HashMap<String, usize>> {
+    let mut removed: HashMap<String, usize> = HashMap::new();
+    let mut entries = classes.iter_mut(wtxn)?;
+    while let Some(entry) = entries.next() {
+        let (k, v) = entry?;
+        let Some((class_name, jar_key)) = k.split_once("::") else {
+            continue;
+        };
+        let hotspot = serde_json::from_str::<ClassHotspot>(v).unwrap_or_default();
+        if !remove(class_name, jar_key, &hotspot) {
+            continue;
+        }
+        *removed.entry(jar_key.to_string()).or_default() += 1;
+        // SAFETY: `k` and `v` are not used after the deletion.
+        unsafe { entries.del_current()? };
+    }
+    Ok(removed)
+}
+
+/// Key of the tracked class of `jar_key` with the fewest accesses, the
+/// longest unused one on ties.
+fn least_accessed_class(
+    classes: &StrDb,
+    wtxn: &heed::RwTxn<'_>,
+    jar_key: &str,
+) -> Result<Option<String>> {
+    let suffix = format!("::{jar_key}");
+    let mut least: Option<(u32, u64, String)> = None;
+    for item in classes.iter(wtxn)? {
+        let (k, v) = item?;
+        if !k.ends_with(&suffix) {
+            continue;
+        }
+        let Ok(h) = serde_json::from_str::<ClassHotspot>(v) else {
+            continue;
+        };
+        if least
+            .as_ref()
+            .is_none_or(|(count, at, _)| (h.access_count, h.last_access) < (*count, *at))
+        {
+            least = Some((h.access_count, h.last_access, k.to_string()));
+        }
+    }
+    Ok(least.map(|(_, _, key)| key))
+}
+
 fn open_named_db(env: &Env, rtxn: &heed::RoTxn<'_>, name: &str) -> Result<StrDb> {
     env.open_database::<Str, Str>(rtxn, Some(name))?
         .ok_or_else(|| anyhow::anyhow!("Database not found: {name}"))
 }
 
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -176,4 +678,264 @@ mod tests {
         assert!(third.is_none());
         Ok(())
     }
+
+    #[test]
+    fn planned_accesses_are_folded_and_cross_the_threshold_together() -> Result<()> {
+        use crate::buffer::{BufferConfig, WriteBuffer};
+
+        let db_path = temp_db_path("hotspot_plan");
+        let cache = PersistentCache::open(db_path)?;
+        let tracker = HotspotTracker::new(cache.db(), 3);
+        tracker.record_access("a.jar")?;
+
+        let plan = tracker.plan_accesses(["a.jar", "b.jar", "a.jar"])?;
+        assert_eq!(plan.mutations.len(), 2);
+        assert!(matches!(
+            plan.requests.as_slice(),
+            [
+                (
+                    a,
+                    WarmupRequest {
+                        priority: WarmupPriority::High,
+                        mode: WarmupMode::AllClasses
+                    }
+                ),
+                (
+                    b,
+                    WarmupRequest {
+                        priority: WarmupPriority::Normal,
+                        mode: WarmupMode::TopLevelOnly
+                    }
+                ),
+            ] if a == "a.jar" && b == "b.jar"
+        ));
+        assert_eq!(tracker.get_hotspot("a.jar")?.unwrap().access_count, 1);
+
+        let mut buffer = WriteBuffer::new(
+            cache.db(),
+            BufferConfig::default(),
+            cache.pending_gauge_path(),
+        );
+        buffer.mutate_group(plan.mutations)?;
+        buffer.shutdown_and_flush()?;
+        let a = tracker.get_hotspot("a.jar")?.unwrap();
+        assert_eq!(a.access_count, 3);
+        assert!(a.last_access > 0);
+        assert_eq!(tracker.get_hotspot("b.jar")?.unwrap().access_count, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn steady_daily_use_outranks_a_single_burst() -> Result<()> {
+        let db_path = temp_db_path("hotspot_recency");
+        let cache = PersistentCache::open(db_path)?;
+        let tracker = HotspotTracker::new(cache.db(), 1000);
+        let now = unix_now();
+
+        let mut bursty = JarHotspot::default();
+        for minute in 0..50 {
+            bursty.record(1, now - 3600 + minute * 60);
+        }
+        let mut steady = JarHotspot::default();
+        for day in 0..8 {
+            steady.record(1, now - day * DAY_SECS);
+        }
+        let mut stale = JarHotspot::default();
+        for day in 0..30 {
+            stale.record(1, now - (60 + day) * DAY_SECS);
+        }
+        assert_eq!(bursty.recent_accesses.len(), RECENT_ACCESS_SLOTS);
+        tracker.put_hotspot("bursty.jar", &bursty)?;
+        tracker.put_hotspot("steady.jar", &steady)?;
+        tracker.put_hotspot("stale.jar", &stale)?;
+
+        assert_eq!(
+            tracker.top_unwarmed_jars(3)?,
+            vec!["steady.jar", "bursty.jar", "stale.jar"]
+        );
+        let top = tracker.top_jars(3)?;
+        assert_eq!(top[0].jar_path, "steady.jar");
+        assert!(top[0].score > top[1].score);
+        Ok(())
+    }
+
+    #[test]
+    fn groups_sum_the_hotspot_rows_of_their_jars() -> Result<()> {
+        let db_path = temp_db_path("hotspot_groups");
+        let cache = PersistentCache::open(db_path)?;
+        let tracker = HotspotTracker::new(cache.db(), 1000);
+        let m2 = Path::new("/m2");
+        let jar = |group: &str, artifact: &str| {
+            format!(
+                "/m2/{}/{artifact}/1.0/{artifact}-1.0.jar",
+                group.replace('.', "/")
+            )
+        };
+        for _ in 0..3 {
+            tracker.record_access(&jar("com.fasterxml.jackson.core", "jackson-core"))?;
+        }
+        tracker.record_access(&jar("com.fasterxml.jackson.core", "jackson-databind"))?;
+        tracker.mark_warmed(&jar("com.fasterxml.jackson.core", "jackson-databind"), 40)?;
+        tracker.record_access(&jar("org.slf4j", "slf4j-api"))?;
+        tracker.record_access("/elsewhere/lib.jar")?;
+
+        let groups = tracker.top_groups(m2, false, 10)?;
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].group_id, "com.fasterxml.jackson.core");
+        assert_eq!(groups[0].artifact_id, None);
+        assert_eq!(groups[0].access_count, 4);
+        assert_eq!(groups[0].warmed_jars, 1);
+        assert_eq!(groups[0].unwarmed_jars, 1);
+        assert_eq!(groups[0].class_count, 40);
+        assert_eq!(groups[1].group_id, "org.slf4j");
+
+        let artifacts = tracker.top_groups(m2, true, 10)?;
+        assert_eq!(artifacts.len(), 3);
+        assert_eq!(artifacts[0].artifact_id.as_deref(), Some("jackson-core"));
+        Ok(())
+    }
+
+    #[test]
+    fn hotspot_rows_without_recent_accesses_still_load() {
+        let row = r#"{"access_count":3,"last_access":5,"warmed":false,"class_count":0}"#;
+        let hotspot: JarHotspot = serde_json::from_str(row).unwrap();
+        assert!(hotspot.recent_accesses.is_empty());
+        assert_eq!(
+            ranking_score(hotspot.access_count, &hotspot.recent_accesses, 5),
+            3
+        );
+    }
+
+    #[test]
+    fn threshold_of_zero_or_one_warms_in_full_on_first_access() -> Result<()> {
+        let db_path = temp_db_path("hotspot_threshold_one");
+        let cache = PersistentCache::open(db_path)?;
+        for threshold in [0, 1] {
+            let tracker = HotspotTracker::new(cache.db(), threshold);
+            let jar = format!("t{threshold}.jar");
+            let first = tracker.record_access(&jar)?;
+            assert!(matches!(
+                first,
+                Some(WarmupRequest {
+                    priority: WarmupPriority::High,
+                    mode: WarmupMode::AllClasses
+                })
+            ));
+            tracker.mark_warmed(&jar, 1)?;
+            assert!(tracker.record_access(&jar)?.is_none());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn class_accesses_are_counted_and_capped_per_jar() -> Result<()> {
+        use crate::buffer::{BufferConfig, WriteBuffer};
+
+        let db_path = temp_db_path("class_hotspot");
+        let cache = PersistentCache::open(db_path)?;
+        let tracker = HotspotTracker::new(cache.db(), 2);
+        let mut buffer = WriteBuffer::new(
+            cache.db(),
+            BufferConfig::default(),
+            cache.pending_gauge_path(),
+        );
+        buffer.mutate(HotspotTracker::class_access("a.Hot", "a.jar"))?;
+        buffer.mutate(HotspotTracker::class_access("a.Hot", "a.jar"))?;
+        buffer.mutate(HotspotTracker::class_access("b.Other", "b.jar"))?;
+        for idx in 0..MAX_TRACKED_CLASSES_PER_JAR + 20 {
+            buffer.mutate(HotspotTracker::class_access(&format!("a.C{idx}"), "a.jar"))?;
+        }
+        buffer.shutdown_and_flush()?;
+
+        let top = tracker.top_classes(None, 2)?;
+        assert_eq!(top[0].class_name, "a.Hot");
+        assert_eq!(top[0].access_count, 2);
+        assert_eq!(top.len(), 2);
+
+        let other = tracker.top_classes(Some("b.jar"), usize::MAX)?;
+        assert_eq!(other.len(), 1);
+        assert_eq!(other[0].class_name, "b.Other");
+
+        let tracked = tracker.top_classes(Some("a.jar"), usize::MAX)?;
+        assert_eq!(tracked.len(), MAX_TRACKED_CLASSES_PER_JAR as usize);
+        assert!(tracked.iter().any(|class| class.class_name == "a.Hot"));
+        let jar = tracker.get_hotspot("a.jar")?.unwrap();
+        assert_eq!(jar.tracked_classes, MAX_TRACKED_CLASSES_PER_JAR);
+        Ok(())
+    }
+
+    #[test]
+    fn reset_and_trim_only_touch_hotspot_rows() -> Result<()> {
+        use crate::buffer::{BufferConfig, Mutation, WriteBuffer};
+        use crate::cache::{CachedClassSource, ClassContentSource};
+
+        let db_path = temp_db_path("hotspot_reset_trim");
+        let cache = PersistentCache::open(db_path)?;
+        let tracker = HotspotTracker::new(cache.db(), 2);
+        let source = CachedClassSource {
+            content: "class A {}".to_string(),
+            source: ClassContentSource::Decompiled,
+            options: Vec::new(),
+            decompiler_version: None,
+            encoding: None,
+            postprocessed: false,
+        };
+        cache.put_class_sources(&[("a.A::a.jar::cfr".to_string(), source)])?;
+        cache.mark_jar_loaded("a.jar")?;
+
+        let old = unix_now() - 40 * 86400;
+        for jar in ["a.jar", "b.jar", "c.jar"] {
+            tracker.record_access(jar)?;
+        }
+        tracker.put_hotspot(
+            "c.jar",
+            &JarHotspot {
+                access_count: 3,
+                last_access: old,
+                ..JarHotspot::default()
+            },
+        )?;
+        tracker.put_hotspot("warmed.jar", &JarHotspot::default())?;
+        let mut buffer = WriteBuffer::new(
+            cache.db(),
+            BufferConfig::default(),
+            cache.pending_gauge_path(),
+        );
+        buffer.mutate(HotspotTracker::class_access("a.A", "a.jar"))?;
+        for (class_name, jar_key) in [("b.Old", "b.jar"), ("c.C", "c.jar")] {
+            buffer.mutate(Mutation::RecordClassAccess {
+                class_name: class_name.to_string(),
+                jar_key: jar_key.to_string(),
+                at: old,
+            })?;
+        }
+        buffer.mutate(HotspotTracker::class_access("b.New", "b.jar"))?;
+        buffer.shutdown_and_flush()?;
+
+        let trimmed = tracker.trim(Duration::from_secs(30 * 86400))?;
+        assert_eq!(trimmed.jars, 1);
+        assert_eq!(trimmed.classes, 2);
+        assert!(tracker.get_hotspot("c.jar")?.is_none());
+        assert!(tracker.get_hotspot("warmed.jar")?.is_some());
+        assert_eq!(tracker.get_hotspot("b.jar")?.unwrap().tracked_classes, 1);
+        let b_classes = tracker.top_classes(Some("b.jar"), usize::MAX)?;
+        assert_eq!(b_classes.len(), 1);
+        assert_eq!(b_classes[0].class_name, "b.New");
+
+        let reset = tracker.reset(Some("a.jar"))?;
+        assert_eq!(reset.jars, 1);
+        assert_eq!(reset.classes, 1);
+        assert!(tracker.get_hotspot("a.jar")?.is_none());
+        assert!(tracker.get_hotspot("b.jar")?.is_some());
+
+        let reset = tracker.reset(None)?;
+        assert_eq!(reset.jars, 2);
+        assert_eq!(reset.classes, 1);
+        assert!(tracker.top_jars(usize::MAX)?.is_empty());
+        assert!(tracker.top_classes(None, usize::MAX)?.is_empty());
+
+        assert!(cache.get_class_source("a.A::a.jar::cfr")?.is_some());
+        assert!(cache.is_jar_loaded("a.jar")?);
+        Ok(())
+    }
 }
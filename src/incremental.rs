@@ -1,110 +1,352 @@
 use anyhow::Result;
 use heed::Env;
 use heed::types::Str;
-use std::path::PathBuf;
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::sync::mpsc::{self, RecvTimeoutError};
 use std::thread::JoinHandle;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use crate::cache::JAR_MTIME_DB;
+use crate::cache::{JAR_MTIME_DB, delete_jar_data};
 use crate::catalog;
+use crate::interrupt;
+use crate::progress::{NoProgress, ProgressEvent, ProgressSink};
 use crate::registry::ClassRegistry;
 use crate::scan::scan_jars;
+use crate::scan_cache::invalidate_scans;
 
 #[derive(Debug, Clone, Copy)]
 pub struct IncrementalConfig {
     pub interval: Duration,
+    /// How long an artifact directory must be quiet before
+    /// [`IncrementalIndexer::watch`] indexes its jars.
+    pub debounce: Duration,
 }
 
 impl Default for IncrementalConfig {
     fn default() -> Self {
         Self {
             interval: Duration::from_secs(300),
+            debounce: Duration::from_secs(2),
         }
     }
 }
 
+const WATCH_TICK: Duration = Duration::from_millis(200);
+
 #[derive(Debug, serde::Serialize)]
 pub struct IncrementalIndexResult {
     pub root: String,
     pub scanned_jars: usize,
     pub changed_jars: usize,
+    pub unchanged_jars: usize,
+    /// Changed jars cataloged for the first time.
+    pub cataloged_jars_new: usize,
+    /// Changed jars that were cataloged before, whose registry entries were
+    /// replaced.
+    pub refreshed_jars: usize,
     pub indexed_classes: usize,
     pub failed_jars: usize,
+    /// Jars with a recorded mtime under the root that no longer exist.
+    pub removed_jars: usize,
+    /// Removed jars whose registry entries and cached data were deleted.
+    pub purged_jars: usize,
+    /// Jars skipped because they still look like in-progress downloads;
+    /// they are picked up by a later run.
+    pub unsettled_jars: usize,
+}
+
+/// Jars found by [`IncrementalIndexer::scan_changes`].
+#[derive(Debug, Default)]
+pub struct JarChanges {
+    pub scanned: usize,
+    /// New jars, or jars modified since the last scan.
+    pub changed: Vec<PathBuf>,
+    /// Jars scanned before that are gone. Their mtime rows are kept until
+    /// they are purged, so they keep being reported.
+    pub removed: Vec<PathBuf>,
+    /// Jars with an in-progress download marker next to them
+    /// (`DOWNLOAD_MARKER_SUFFIXES`) or modified within the settle window.
+    /// Their mtime is not recorded, so a later scan reports them as changed.
+    pub unsettled: Vec<PathBuf>,
+    /// Size and mtime of each changed jar as scanned, checked again around
+    /// cataloging it.
+    pub observed: HashMap<PathBuf, JarStamp>,
 }
 
+/// Files Maven leaves next to an artifact while (or after failing) to
+/// download it.
+const DOWNLOAD_MARKER_SUFFIXES: [&str; 2] = [".part", ".lastUpdated"];
+
 #[derive(Clone)]
 pub struct IncrementalIndexer {
     db: Arc<Env>,
     root: PathBuf,
+    scan_roots: Vec<PathBuf>,
+    purge_removed: bool,
+    settle_window: Duration,
+    progress: Arc<dyn ProgressSink>,
 }
 
 impl IncrementalIndexer {
     pub fn new(db: Arc<Env>, root: PathBuf) -> Self {
-        Self { db, root }
+        Self {
+            db,
+            scan_roots: vec![root.clone()],
+            root,
+            purge_removed: false,
+            settle_window: Duration::from_secs(2),
+            progress: Arc::new(NoProgress),
+        }
     }
 
-    pub fn scan_changes(&self) -> Result<(usize, Vec<PathBuf>)> {
+    /// Jars modified less than `settle_window` ago are treated as still being
+    /// written and left for a later run.
+    pub fn with_settle_window(mut self, settle_window: Duration) -> Self {
+        self.settle_window = settle_window;
+        self
+    }
+
+    /// The configured repository roots; stored jar lists of roots outside
+    /// them are dropped with those above changed jars. Defaults to the
+    /// indexed root.
+    pub fn with_scan_roots(mut self, scan_roots: Vec<PathBuf>) -> Self {
+        self.scan_roots = scan_roots;
+        self
+    }
+
+    /// Deletes the registry entries and cached data of removed jars in
+    /// [`Self::run_once`].
+    pub fn with_purge_removed(mut self, purge_removed: bool) -> Self {
+        self.purge_removed = purge_removed;
+        self
+    }
+
+    /// Receives a `ScanStarted` per scan and a `JarCataloged` per jar.
+    pub fn with_progress(mut self, progress: Arc<dyn ProgressSink>) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    pub fn scan_changes(&self) -> Result<JarChanges> {
+        self.progress.event(ProgressEvent::ScanStarted {
+            root: self.root.to_string_lossy().to_string(),
+        });
         let jars = scan_jars(&self.root)?;
         let mut wtxn = self.db.write_txn()?;
         let table = self
             .db
             .create_database::<Str, Str>(&mut wtxn, Some(JAR_MTIME_DB))?;
-        let mut changed = Vec::new();
+        let mut changes = JarChanges {
+            scanned: jars.len(),
+            ..JarChanges::default()
+        };
         for jar_path in &jars {
+            let stamp = JarStamp::read(jar_path).unwrap_or_default();
+            if !self.is_settled(jar_path, stamp) {
+                changes.unsettled.push(jar_path.clone());
+                continue;
+            }
+
             let jar_key = jar_path.to_string_lossy().to_string();
-            let mtime = jar_path
-                .metadata()
-                .and_then(|m| m.modified())
-                .unwrap_or(SystemTime::UNIX_EPOCH);
-            let nanos = mtime
-                .duration_since(UNIX_EPOCH)
-                .unwrap_or_default()
-                .as_nanos();
-            let nanos_u64 = u64::try_from(nanos).unwrap_or(u64::MAX);
-
-            let old = table
-                .get(&wtxn, jar_key.as_str())?
-                .and_then(|v| v.parse::<u64>().ok())
-                .unwrap_or(0);
-            if old < nanos_u64 {
-                changed.push(jar_path.clone());
+            if stamp.changed_since(table.get(&wtxn, jar_key.as_str())?) {
+                changes.changed.push(jar_path.clone());
+                changes.observed.insert(jar_path.clone(), stamp);
+            }
+
+            let value = stamp.encode();
+            table.put(&mut wtxn, jar_key.as_str(), value.as_str())?;
+        }
+
+        let found: HashSet<&Path> = jars.iter().map(PathBuf::as_path).collect();
+        for item in table.iter(&wtxn)? {
+            let (k, _) = item?;
+            let jar_path = Path::new(k);
+            if jar_path.starts_with(&self.root) && !found.contains(jar_path) {
+                changes.removed.push(jar_path.to_path_buf());
             }
+        }
+        wtxn.commit()?;
+        Ok(changes)
+    }
 
-            let value = nanos_u64.to_string();
+    /// Sorts `jar_paths`, reported by a filesystem watcher, into changed and
+    /// removed jars like [`Self::scan_changes`] does, without walking the root.
+    pub fn changes_for(&self, jar_paths: &[PathBuf]) -> Result<JarChanges> {
+        let mut wtxn = self.db.write_txn()?;
+        let table = self
+            .db
+            .create_database::<Str, Str>(&mut wtxn, Some(JAR_MTIME_DB))?;
+        let mut changes = JarChanges::default();
+        for jar_path in jar_paths {
+            let jar_key = jar_path.to_string_lossy().to_string();
+            let old = table.get(&wtxn, jar_key.as_str())?.map(str::to_string);
+            let Some(stamp) = JarStamp::read(jar_path) else {
+                if old.is_some() {
+                    changes.removed.push(jar_path.clone());
+                }
+                continue;
+            };
+            changes.scanned += 1;
+            if !self.is_settled(jar_path, stamp) {
+                changes.unsettled.push(jar_path.clone());
+                continue;
+            }
+            if stamp.changed_since(old.as_deref()) {
+                changes.changed.push(jar_path.clone());
+                changes.observed.insert(jar_path.clone(), stamp);
+            }
+            let value = stamp.encode();
             table.put(&mut wtxn, jar_key.as_str(), value.as_str())?;
         }
         wtxn.commit()?;
-        Ok((jars.len(), changed))
+        Ok(changes)
     }
 
     pub fn run_once(&self, registry: &ClassRegistry) -> Result<IncrementalIndexResult> {
-        let (scanned_jars, changed) = self.scan_changes()?;
+        let changes = self.scan_changes()?;
+        self.apply_changes(registry, changes)
+    }
+
+    /// Catalogs changed jars into `registry` and purges removed ones when
+    /// [`Self::with_purge_removed`] is set. A changed jar whose size or mtime
+    /// differs from what the scan observed, before or after reading it, is
+    /// still being written: it is counted as unsettled and its mtime row is
+    /// reset so the next scan picks it up again. The stored jar lists of
+    /// roots above changed or removed jars, or outside the scan roots, are
+    /// dropped (see scan_cache.rs).
+    pub fn apply_changes(
+        &self,
+        registry: &ClassRegistry,
+        changes: JarChanges,
+    ) -> Result<IncrementalIndexResult> {
+        let JarChanges {
+            scanned: scanned_jars,
+            changed,
+            removed,
+            mut unsettled,
+            observed,
+        } = changes;
+        let skipped_unsettled = unsettled.len();
         let mut indexed_classes = 0usize;
         let mut failed_jars = 0usize;
+        let mut cataloged_jars_new = 0usize;
+        let mut refreshed = Vec::new();
 
         for jar_path in changed.iter() {
+            let stable = || {
+                observed
+                    .get(jar_path)
+                    .is_none_or(|seen| JarStamp::read(jar_path) == Some(*seen))
+            };
+            if !stable() {
+                unsettled.push(jar_path.clone());
+                continue;
+            }
             let jar_key = jar_path.to_string_lossy().to_string();
             match catalog::catalog(jar_path) {
+                Ok(_) if !stable() => {
+                    unsettled.push(jar_path.clone());
+                }
                 Ok(classes) => {
                     indexed_classes += classes.len();
-                    let _ = registry.update_registry_and_mark_cataloged(&jar_key, &classes);
+                    self.progress.event(ProgressEvent::JarCataloged {
+                        jar_path: jar_key.clone(),
+                        classes: classes.len(),
+                    });
+                    if registry.is_cataloged(&jar_key).unwrap_or(false) {
+                        refreshed.push((jar_key, classes));
+                    } else {
+                        let _ = registry.update_registry_and_mark_cataloged(&jar_key, &classes);
+                        cataloged_jars_new += 1;
+                    }
                 }
                 Err(_) => {
                     failed_jars += 1;
                 }
             }
         }
+        registry.refresh_jars(&refreshed)?;
+        self.forget_mtimes(&unsettled[skipped_unsettled..])?;
 
+        let mut purged_jars = 0usize;
+        if self.purge_removed && !removed.is_empty() {
+            let jar_keys: Vec<String> = removed
+                .iter()
+                .map(|jar| jar.to_string_lossy().to_string())
+                .collect();
+            registry.remove_jars(&jar_keys)?;
+            delete_jar_data(&self.db, &jar_keys)?;
+            purged_jars = jar_keys.len();
+        }
+
+        // Jars added or removed below a scanned root are invisible in its
+        // mtime, so its stored jar list has to go. Unsettled jars count:
+        // they are already on disk.
+        let touched = [
+            changed.as_slice(),
+            &removed,
+            &unsettled[..skipped_unsettled],
+        ]
+        .concat();
+        let dropped = invalidate_scans(&self.db, &touched, &self.scan_roots)?;
+        if dropped > 0 {
+            tracing::debug!("dropped {dropped} stale stored scan(s)");
+        }
+
+        let changed_jars = changed.len() - (unsettled.len() - skipped_unsettled);
         Ok(IncrementalIndexResult {
             root: self.root.to_string_lossy().to_string(),
             scanned_jars,
-            changed_jars: changed.len(),
+            changed_jars,
+            unchanged_jars: scanned_jars - changed.len() - skipped_unsettled,
+            cataloged_jars_new,
+            refreshed_jars: refreshed.len(),
             indexed_classes,
             failed_jars,
+            removed_jars: removed.len(),
+            purged_jars,
+            unsettled_jars: unsettled.len(),
         })
     }
 
+    /// Whether `jar_path` looks fully written: no in-progress download marker
+    /// next to it and an mtime at least the settle window old.
+    fn is_settled(&self, jar_path: &Path, stamp: JarStamp) -> bool {
+        let Some(file_name) = jar_path.file_name() else {
+            return true;
+        };
+        let has_marker = DOWNLOAD_MARKER_SUFFIXES.iter().any(|suffix| {
+            let mut marker = file_name.to_os_string();
+            marker.push(suffix);
+            jar_path.with_file_name(marker).exists()
+        });
+        if has_marker {
+            return false;
+        }
+        let age = now_nanos().saturating_sub(stamp.mtime_nanos);
+        Duration::from_nanos(age) >= self.settle_window
+    }
+
+    /// Resets the mtime rows of `jars` so the next scan sees them as changed.
+    fn forget_mtimes(&self, jars: &[PathBuf]) -> Result<()> {
+        if jars.is_empty() {
+            return Ok(());
+        }
+        let mut wtxn = self.db.write_txn()?;
+        let table = self
+            .db
+            .create_database::<Str, Str>(&mut wtxn, Some(JAR_MTIME_DB))?;
+        for jar_path in jars {
+            let jar_key = jar_path.to_string_lossy().to_string();
+            table.put(&mut wtxn, jar_key.as_str(), "0")?;
+        }
+        wtxn.commit()?;
+        Ok(())
+    }
+
     pub fn spawn(self, registry: ClassRegistry, config: IncrementalConfig) -> JoinHandle<()> {
         std::thread::spawn(move || {
             loop {
@@ -113,6 +355,203 @@ impl IncrementalIndexer {
             }
         })
     }
+
+    /// Keeps `registry` up to date until Ctrl-C: one [`Self::run_once`] to
+    /// catch up, then filesystem events under the root, debounced per
+    /// artifact directory. Falls back to polling every `config.interval`
+    /// when the watcher cannot be set up or reports an error.
+    pub fn watch(
+        &self,
+        registry: &ClassRegistry,
+        config: IncrementalConfig,
+        mut on_update: impl FnMut(WatchMode, &IncrementalIndexResult),
+    ) -> Result<()> {
+        on_update(WatchMode::Initial, &self.run_once(registry)?);
+        if let Err(err) = self.watch_events(registry, config, &mut on_update) {
+            tracing::warn!(
+                "filesystem events unavailable ({err:#}); polling every {}s",
+                config.interval.as_secs()
+            );
+            self.poll(registry, config, &mut on_update)?;
+        }
+        Ok(())
+    }
+
+    fn watch_events(
+        &self,
+        registry: &ClassRegistry,
+        config: IncrementalConfig,
+        on_update: &mut impl FnMut(WatchMode, &IncrementalIndexResult),
+    ) -> Result<()> {
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(tx)?;
+        watcher.watch(&self.root, RecursiveMode::Recursive)?;
+
+        let mut debouncer = ArtifactDebouncer::default();
+        while !interrupt::requested() {
+            match rx.recv_timeout(WATCH_TICK) {
+                Ok(event) => {
+                    let event = event?;
+                    if matches!(
+                        event.kind,
+                        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+                    ) {
+                        let now = Instant::now();
+                        for path in event.paths {
+                            // A directory moved into place reports no events
+                            // for the jars inside it.
+                            if path.is_dir() {
+                                for jar in scan_jars(&path).unwrap_or_default() {
+                                    debouncer.add(jar, now);
+                                }
+                            } else {
+                                debouncer.add(path, now);
+                            }
+                        }
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    anyhow::bail!("watcher stopped");
+                }
+            }
+            let jars = debouncer.take_settled(Instant::now(), config.debounce);
+            if !jars.is_empty() {
+                tracing::debug!(jars = jars.len(), "settled filesystem events");
+                let changes = self.changes_for(&jars)?;
+                let retry = changes.unsettled.clone();
+                on_update(WatchMode::Events, &self.apply_changes(registry, changes)?);
+                let now = Instant::now();
+                for jar in retry {
+                    debouncer.add(jar, now);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn poll(
+        &self,
+        registry: &ClassRegistry,
+        config: IncrementalConfig,
+        on_update: &mut impl FnMut(WatchMode, &IncrementalIndexResult),
+    ) -> Result<()> {
+        let mut next = Instant::now() + config.interval;
+        while !interrupt::requested() {
+            if Instant::now() < next {
+                std::thread::sleep(WATCH_TICK);
+                continue;
+            }
+            on_update(WatchMode::Polling, &self.run_once(registry)?);
+            next = Instant::now() + config.interval;
+        }
+        Ok(())
+    }
+}
+
+/// How [`IncrementalIndexer::watch`] found the changes it reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WatchMode {
+    Initial,
+    Events,
+    Polling,
+}
+
+/// Collects the jar paths of filesystem events per artifact directory and
+/// releases a directory once it has been quiet for the debounce window, so a
+/// Maven download (jar, pom, checksums, `_remote.repositories`) is handled once.
+#[derive(Debug, Default)]
+struct ArtifactDebouncer {
+    pending: HashMap<PathBuf, (Instant, BTreeSet<PathBuf>)>,
+}
+
+impl ArtifactDebouncer {
+    fn add(&mut self, path: PathBuf, now: Instant) {
+        let Some(dir) = path.parent().map(Path::to_path_buf) else {
+            return;
+        };
+        let entry = self
+            .pending
+            .entry(dir)
+            .or_insert_with(|| (now, BTreeSet::new()));
+        entry.0 = now;
+        if path.extension().is_some_and(|ext| ext == "jar") {
+            entry.1.insert(path);
+        }
+    }
+
+    fn take_settled(&mut self, now: Instant, debounce: Duration) -> Vec<PathBuf> {
+        let settled: Vec<PathBuf> = self
+            .pending
+            .iter()
+            .filter(|(_, (last, _))| now.duration_since(*last) >= debounce)
+            .map(|(dir, _)| dir.clone())
+            .collect();
+        let mut jars = Vec::new();
+        for dir in settled {
+            if let Some((_, paths)) = self.pending.remove(&dir) {
+                jars.extend(paths);
+            }
+        }
+        jars.sort();
+        jars
+    }
+}
+
+/// Size and modification time of a jar when it was scanned. The mtime table
+/// stores it as `mtime_nanos:size`, so a jar replaced by one with the same
+/// mtime but a different size (mtime-preserving copies, some NFS mounts) is
+/// still seen as changed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct JarStamp {
+    pub mtime_nanos: u64,
+    pub size: u64,
+}
+
+impl JarStamp {
+    /// `None` when the jar does not exist (any more).
+    pub fn read(jar_path: &Path) -> Option<Self> {
+        let metadata = jar_path.metadata().ok()?;
+        let mtime = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+        Some(Self {
+            mtime_nanos: nanos_since_epoch(mtime),
+            size: metadata.len(),
+        })
+    }
+
+    /// The mtime table value for this stamp.
+    pub fn encode(&self) -> String {
+        format!("{}:{}", self.mtime_nanos, self.size)
+    }
+
+    /// Whether a jar with this stamp changed since `recorded`, its mtime
+    /// table value: a newer mtime, or a different size at any mtime. Values
+    /// written before sizes were recorded hold only the mtime and compare on
+    /// it alone; the next scan rewrites them with the size.
+    pub fn changed_since(&self, recorded: Option<&str>) -> bool {
+        let Some(recorded) = recorded else {
+            return true;
+        };
+        let (mtime, size) = match recorded.split_once(':') {
+            Some((mtime, size)) => (mtime, size.parse::<u64>().ok()),
+            None => (recorded, None),
+        };
+        let mtime = mtime.parse::<u64>().unwrap_or(0);
+        mtime < self.mtime_nanos || size.is_some_and(|size| size != self.size)
+    }
+}
+
+fn nanos_since_epoch(time: SystemTime) -> u64 {
+    let nanos = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    u64::try_from(nanos).unwrap_or(u64::MAX)
+}
+
+fn now_nanos() -> u64 {
+    nanos_since_epoch(SystemTime::now())
 }
 
 #[cfg(test)]
@@ -150,20 +589,366 @@ mod tests {
         std::fs::write(&jar, b"x")?;
 
         let cache = PersistentCache::open(temp_db_path("incremental_changes"))?;
-        let indexer = IncrementalIndexer::new(cache.db(), m2.clone());
+        let indexer =
+            IncrementalIndexer::new(cache.db(), m2.clone()).with_settle_window(Duration::ZERO);
 
-        let (_, changed1) = indexer.scan_changes()?;
+        let changed1 = indexer.scan_changes()?.changed;
         assert_eq!(changed1.len(), 1);
 
-        let (_, changed2) = indexer.scan_changes()?;
+        let changed2 = indexer.scan_changes()?.changed;
         assert_eq!(changed2.len(), 0);
 
         std::thread::sleep(Duration::from_millis(2));
         std::fs::write(&jar, b"y")?;
-        let (_, changed3) = indexer.scan_changes()?;
+        let changed3 = indexer.scan_changes()?.changed;
         assert_eq!(changed3.len(), 1);
 
         let _ = std::fs::remove_dir_all(base);
         Ok(())
     }
+
+    #[test]
+    fn run_once_indexes_only_changed_jars_into_the_registry() -> Result<()> {
+        use std::io::Write;
+
+        let base = std::env::temp_dir().join(format!(
+            "class-finder-incremental-run-{}-{}",
+            std::process::id(),
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        let m2 = base.join("repository");
+        std::fs::create_dir_all(&m2)?;
+        let jar = m2.join("demo.jar");
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&jar)?);
+        zip.start_file("org/example/A.class", zip::write::FileOptions::default())?;
+        zip.write_all(b"")?;
+        zip.finish()?;
+
+        let cache = PersistentCache::open(temp_db_path("incremental_run_once"))?;
+        let registry = ClassRegistry::new(cache.db());
+        let indexer =
+            IncrementalIndexer::new(cache.db(), m2.clone()).with_settle_window(Duration::ZERO);
+
+        let first = indexer.run_once(&registry)?;
+        assert_eq!(first.scanned_jars, 1);
+        assert_eq!(first.changed_jars, 1);
+        assert_eq!(first.cataloged_jars_new, 1);
+        assert_eq!(first.indexed_classes, 1);
+        let jar_key = jar.to_string_lossy().to_string();
+        assert_eq!(
+            registry.get_artifacts("org.example.A")?,
+            vec![jar_key.clone()]
+        );
+        assert!(registry.is_cataloged(&jar_key)?);
+
+        let second = indexer.run_once(&registry)?;
+        assert_eq!(second.changed_jars, 0);
+        assert_eq!(second.unchanged_jars, 1);
+        assert_eq!(second.indexed_classes, 0);
+
+        std::thread::sleep(Duration::from_millis(2));
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&jar)?);
+        zip.start_file("org/example/B.class", zip::write::FileOptions::default())?;
+        zip.write_all(b"")?;
+        zip.finish()?;
+        let third = indexer.run_once(&registry)?;
+        assert_eq!(third.changed_jars, 1);
+        assert_eq!(third.refreshed_jars, 1);
+        assert_eq!(third.cataloged_jars_new, 0);
+        assert!(registry.get_artifacts("org.example.A")?.is_empty());
+        assert_eq!(registry.get_artifacts("org.example.B")?, vec![jar_key]);
+
+        let _ = std::fs::remove_dir_all(base);
+        Ok(())
+    }
+
+    #[test]
+    fn removed_jars_are_reported_and_purged_on_request() -> Result<()> {
+        use crate::cache::{CachedClassSource, ClassContentSource, class_source_key};
+        use crate::decompiler::DecompilerKind;
+        use crate::hotspot::HotspotTracker;
+        use std::io::Write;
+
+        let base = std::env::temp_dir().join(format!(
+            "class-finder-incremental-removed-{}-{}",
+            std::process::id(),
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        let m2 = base.join("repository");
+        std::fs::create_dir_all(&m2)?;
+        let mut jars = Vec::new();
+        for name in ["kept", "gone"] {
+            let jar = m2.join(format!("{name}.jar"));
+            let mut zip = zip::ZipWriter::new(std::fs::File::create(&jar)?);
+            zip.start_file(
+                format!("org/{name}/A.class"),
+                zip::write::FileOptions::default(),
+            )?;
+            zip.write_all(b"")?;
+            zip.finish()?;
+            jars.push(jar.to_string_lossy().to_string());
+        }
+        let (kept, gone) = (&jars[0], &jars[1]);
+
+        let cache = PersistentCache::open(temp_db_path("incremental_removed"))?;
+        let registry = ClassRegistry::new(cache.db());
+        let indexer =
+            IncrementalIndexer::new(cache.db(), m2.clone()).with_settle_window(Duration::ZERO);
+        indexer.run_once(&registry)?;
+        for jar in [kept, gone] {
+            let source = CachedClassSource {
+                content: "class A {}".to_string(),
+                source: ClassContentSource::Decompiled,
+                options: Vec::new(),
+                decompiler_version: None,
+                encoding: None,
+                postprocessed: false,
+            };
+            let class_name = if jar == kept {
+                "org.kept.A"
+            } else {
+                "org.gone.A"
+            };
+            cache.put_class_sources(&[(
+                class_source_key(class_name, jar, DecompilerKind::Vineflower),
+                source,
+            )])?;
+            cache.mark_jar_loaded(jar)?;
+            HotspotTracker::new(cache.db(), 2).record_access(jar)?;
+        }
+
+        std::fs::remove_file(gone)?;
+        let reported = indexer.run_once(&registry)?;
+        assert_eq!(reported.removed_jars, 1);
+        assert_eq!(reported.purged_jars, 0);
+        assert_eq!(registry.get_artifacts("org.gone.A")?, vec![gone.clone()]);
+
+        let purged = indexer
+            .clone()
+            .with_purge_removed(true)
+            .run_once(&registry)?;
+        assert_eq!(purged.removed_jars, 1);
+        assert_eq!(purged.purged_jars, 1);
+        assert!(registry.get_artifacts("org.gone.A")?.is_empty());
+        assert!(!registry.is_cataloged(gone)?);
+        assert!(!cache.is_jar_loaded(gone)?);
+        let gone_key = class_source_key("org.gone.A", gone, DecompilerKind::Vineflower);
+        assert!(cache.get_class_source(&gone_key)?.is_none());
+        let hotspot = HotspotTracker::new(cache.db(), 2);
+        assert!(hotspot.get_hotspot(gone)?.is_none());
+        assert!(hotspot.get_hotspot(kept)?.is_some());
+
+        assert_eq!(registry.get_artifacts("org.kept.A")?, vec![kept.clone()]);
+        assert!(cache.is_jar_loaded(kept)?);
+        let kept_key = class_source_key("org.kept.A", kept, DecompilerKind::Vineflower);
+        assert!(cache.get_class_source(&kept_key)?.is_some());
+        assert_eq!(indexer.run_once(&registry)?.removed_jars, 0);
+
+        let _ = std::fs::remove_dir_all(base);
+        Ok(())
+    }
+
+    #[test]
+    fn debouncer_releases_an_artifact_directory_once_it_is_quiet() {
+        let dir = PathBuf::from("/m2/org/demo/lib/1.0");
+        let other = PathBuf::from("/m2/org/demo/other/1.0");
+        let start = Instant::now();
+        let debounce = Duration::from_secs(2);
+        let mut debouncer = ArtifactDebouncer::default();
+        debouncer.add(dir.join("lib-1.0.jar.part"), start);
+        debouncer.add(dir.join("lib-1.0.jar"), start);
+        debouncer.add(other.join("other-1.0.jar"), start);
+        debouncer.add(
+            dir.join("lib-1.0.jar.sha1"),
+            start + Duration::from_millis(1500),
+        );
+
+        assert!(
+            debouncer
+                .take_settled(start + Duration::from_secs(1), debounce)
+                .is_empty()
+        );
+        assert_eq!(
+            debouncer.take_settled(start + Duration::from_secs(2), debounce),
+            vec![other.join("other-1.0.jar")]
+        );
+        assert_eq!(
+            debouncer.take_settled(start + Duration::from_secs(4), debounce),
+            vec![dir.join("lib-1.0.jar")]
+        );
+        assert!(
+            debouncer
+                .take_settled(start + Duration::from_secs(10), debounce)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn changes_for_sorts_watched_paths_into_changed_and_removed_jars() -> Result<()> {
+        use std::io::Write;
+
+        let base = std::env::temp_dir().join(format!(
+            "class-finder-incremental-watched-{}-{}",
+            std::process::id(),
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        std::fs::create_dir_all(&base)?;
+        let write_jar = |name: &str| -> Result<PathBuf> {
+            let jar = base.join(format!("{name}.jar"));
+            let mut zip = zip::ZipWriter::new(std::fs::File::create(&jar)?);
+            zip.start_file(
+                format!("org/{name}/A.class"),
+                zip::write::FileOptions::default(),
+            )?;
+            zip.write_all(b"")?;
+            zip.finish()?;
+            Ok(jar)
+        };
+        let old = write_jar("old")?;
+
+        let cache = PersistentCache::open(temp_db_path("incremental_watched"))?;
+        let registry = ClassRegistry::new(cache.db());
+        let indexer = IncrementalIndexer::new(cache.db(), base.clone())
+            .with_settle_window(Duration::ZERO)
+            .with_purge_removed(true);
+        indexer.run_once(&registry)?;
+
+        let new = write_jar("new")?;
+        std::fs::remove_file(&old)?;
+        let never_seen = base.join("never-seen.jar");
+        let changes = indexer.changes_for(&[new.clone(), old.clone(), never_seen])?;
+        assert_eq!(changes.scanned, 1);
+        assert_eq!(changes.changed, vec![new.clone()]);
+        assert_eq!(changes.removed, vec![old.clone()]);
+
+        let result = indexer.apply_changes(&registry, changes)?;
+        assert_eq!(result.cataloged_jars_new, 1);
+        assert_eq!(result.purged_jars, 1);
+        let new_key = new.to_string_lossy().to_string();
+        assert_eq!(registry.get_artifacts("org.new.A")?, vec![new_key]);
+        assert!(registry.get_artifacts("org.old.A")?.is_empty());
+
+        let again = indexer.run_once(&registry)?;
+        assert_eq!(again.changed_jars, 0);
+        assert_eq!(again.removed_jars, 0);
+
+        let _ = std::fs::remove_dir_all(base);
+        Ok(())
+    }
+
+    #[test]
+    fn jars_still_being_downloaded_are_left_for_a_later_scan() -> Result<()> {
+        use std::io::Write;
+
+        let base = std::env::temp_dir().join(format!(
+            "class-finder-incremental-growing-{}-{}",
+            std::process::id(),
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        let m2 = base.join("repository");
+        std::fs::create_dir_all(&m2)?;
+        let full = base.join("full.jar");
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&full)?);
+        zip.start_file("org/example/A.class", zip::write::FileOptions::default())?;
+        zip.write_all(b"")?;
+        zip.finish()?;
+        let bytes = std::fs::read(&full)?;
+        let (head, tail) = bytes.split_at(bytes.len() / 2);
+
+        let jar = m2.join("demo.jar");
+        let marker = m2.join("demo.jar.part");
+        std::fs::write(&jar, head)?;
+        std::fs::write(&marker, b"")?;
+
+        let cache = PersistentCache::open(temp_db_path("incremental_growing"))?;
+        let registry = ClassRegistry::new(cache.db());
+        let settled =
+            IncrementalIndexer::new(cache.db(), m2.clone()).with_settle_window(Duration::ZERO);
+
+        let downloading = settled.run_once(&registry)?;
+        assert_eq!(downloading.unsettled_jars, 1);
+        assert_eq!(downloading.changed_jars, 0);
+        assert_eq!(downloading.failed_jars, 0);
+
+        std::fs::remove_file(&marker)?;
+        let recent = IncrementalIndexer::new(cache.db(), m2.clone())
+            .with_settle_window(Duration::from_secs(3600))
+            .run_once(&registry)?;
+        assert_eq!(recent.unsettled_jars, 1);
+        assert_eq!(recent.changed_jars, 0);
+
+        let changes = settled.scan_changes()?;
+        assert_eq!(changes.changed, vec![jar.clone()]);
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&jar)?
+            .write_all(tail)?;
+        let grown = settled.apply_changes(&registry, changes)?;
+        assert_eq!(grown.unsettled_jars, 1);
+        assert_eq!(grown.changed_jars, 0);
+        assert_eq!(grown.failed_jars, 0);
+        assert!(registry.get_artifacts("org.example.A")?.is_empty());
+
+        let complete = settled.run_once(&registry)?;
+        assert_eq!(complete.unsettled_jars, 0);
+        assert_eq!(complete.cataloged_jars_new, 1);
+        let jar_key = jar.to_string_lossy().to_string();
+        assert_eq!(registry.get_artifacts("org.example.A")?, vec![jar_key]);
+
+        let _ = std::fs::remove_dir_all(base);
+        Ok(())
+    }
+
+    #[test]
+    fn same_mtime_with_a_different_size_counts_as_changed() -> Result<()> {
+        let base = std::env::temp_dir().join(format!(
+            "class-finder-incremental-size-{}-{}",
+            std::process::id(),
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        std::fs::create_dir_all(&base)?;
+        let jar = base.join("a.jar");
+        std::fs::write(&jar, b"x")?;
+        let mtime = jar.metadata()?.modified()?;
+
+        let cache = PersistentCache::open(temp_db_path("incremental_size"))?;
+        let indexer =
+            IncrementalIndexer::new(cache.db(), base.clone()).with_settle_window(Duration::ZERO);
+        assert_eq!(indexer.scan_changes()?.changed, vec![jar.clone()]);
+
+        std::fs::write(&jar, b"replaced")?;
+        std::fs::File::options()
+            .write(true)
+            .open(&jar)?
+            .set_modified(mtime)?;
+        assert_eq!(indexer.scan_changes()?.changed, vec![jar.clone()]);
+        assert!(indexer.scan_changes()?.changed.is_empty());
+
+        let stamp = JarStamp::read(&jar).unwrap();
+        let legacy = stamp.mtime_nanos.to_string();
+        assert!(!stamp.changed_since(Some(&legacy)));
+        assert!(stamp.changed_since(Some("0")));
+        assert!(stamp.changed_since(None));
+        let resized = format!("{}:{}", stamp.mtime_nanos, stamp.size + 1);
+        assert!(stamp.changed_since(Some(&resized)));
+        assert!(!stamp.changed_since(Some(&stamp.encode())));
+
+        let _ = std::fs::remove_dir_all(base);
+        Ok(())
+    }
 }
@@ -0,0 +1,36 @@
+//! Ctrl-C handling for long-running commands.
+//!
+//! The first Ctrl-C only sets a flag that `warmup` and the background warmup
+//! of `find` poll, so they can stop after the current jars and still flush
+//! the write buffer. A second Ctrl-C exits immediately.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Installs the Ctrl-C handler. A no-op on platforms without POSIX signals,
+/// where Ctrl-C keeps terminating the process.
+pub fn install() {
+    #[cfg(unix)]
+    {
+        let handler: extern "C" fn(libc::c_int) = on_sigint;
+        // SAFETY: the handler only touches an atomic and calls `_exit`, both
+        // async-signal-safe.
+        unsafe {
+            libc::signal(libc::SIGINT, handler as libc::sighandler_t);
+        }
+    }
+}
+
+/// Whether Ctrl-C was pressed since [`install`].
+pub fn requested() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+#[cfg(unix)]
+extern "C" fn on_sigint(_signal: libc::c_int) {
+    if INTERRUPTED.swap(true, Ordering::Relaxed) {
+        // SAFETY: `_exit` is async-signal-safe.
+        unsafe { libc::_exit(130) };
+    }
+}
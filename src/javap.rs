@@ -0,0 +1,128 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::decompiler::javap_command;
+use crate::error::ClassFinderError;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum JavapView {
+    /// `javap -p -c`: member signatures plus disassembled bytecode
+    #[default]
+    Bytecode,
+    /// `javap -p`: member signatures only
+    Signatures,
+}
+
+/// Last-resort view of a class when every decompiler fails on it. The output is
+/// a bytecode listing, not Java source.
+#[derive(Debug, Clone)]
+pub struct Javap {
+    view: JavapView,
+    timeout: Duration,
+}
+
+impl Javap {
+    pub fn new(view: JavapView, timeout: Duration) -> Self {
+        Self { view, timeout }
+    }
+
+    pub fn disassemble_class(&self, jar_path: &Path, class_name: &str) -> Result<String> {
+        let jar = jar_path.to_str().context("jar path is not valid UTF-8")?;
+        let mut args = vec!["-p"];
+        if self.view == JavapView::Bytecode {
+            args.push("-c");
+        }
+        args.extend(["-classpath", jar, class_name]);
+
+        let output = javap_command(&args, class_name, self.timeout)?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(ClassFinderError::decompile_failed(
+                class_name,
+                format!("javap failed: {}", stderr.trim()),
+            )
+            .into());
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).replace("\r\n", "\n"))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::decompiler::java_env_lock;
+    use std::path::PathBuf;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        std::env::temp_dir().join(format!(
+            "class_finder_javap_test_{}_{}_{}",
+            std::process::id(),
+            nanos,
+            name
+        ))
+    }
+
+    fn write_executable(path: &Path, content: &str) -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, content)?;
+        let mut perms = std::fs::metadata(path)?.permissions();
+        perms.set_mode(0o755);
+        std::fs::set_permissions(path, perms)?;
+        Ok(())
+    }
+
+    #[test]
+    fn disassemble_class_passes_view_flags_and_classpath() -> Result<()> {
+        let _guard = java_env_lock().lock().expect("PATH test lock poisoned");
+        let base = temp_dir("disassemble");
+        let jar = base.join("demo.jar");
+        let fake_bin = base.join("bin");
+        std::fs::create_dir_all(&base)?;
+        std::fs::write(&jar, "stub")?;
+        write_executable(
+            &fake_bin.join("javap"),
+            r#"#!/bin/sh
+echo "args: $*"
+"#,
+        )?;
+
+        let old_path = std::env::var("PATH").unwrap_or_default();
+        let new_path = format!("{}:{}", fake_bin.to_string_lossy(), old_path);
+        // SAFETY: Guarded by java_env_lock and restored before returning.
+        unsafe { std::env::set_var("PATH", &new_path) };
+
+        let result: Result<()> = {
+            let timeout = Duration::from_secs(10);
+            let jar_arg = jar.to_string_lossy();
+            let bytecode =
+                Javap::new(JavapView::Bytecode, timeout).disassemble_class(&jar, "a.B")?;
+            assert_eq!(
+                bytecode.trim(),
+                format!("args: -p -c -classpath {jar_arg} a.B")
+            );
+            let signatures =
+                Javap::new(JavapView::Signatures, timeout).disassemble_class(&jar, "a.B")?;
+            assert_eq!(
+                signatures.trim(),
+                format!("args: -p -classpath {jar_arg} a.B")
+            );
+            Ok(())
+        };
+
+        // SAFETY: Guarded by java_env_lock and restored before returning.
+        unsafe { std::env::set_var("PATH", old_path) };
+        let _ = std::fs::remove_dir_all(base);
+        result
+    }
+}
@@ -5,31 +5,86 @@
 //! ## Architecture
 //!
 //! - **cache**: Persistent storage using LMDB for extracted/decompiled sources and metadata
+//! - **finder**: `ClassFinder`, the embeddable find/load/warmup/index API the CLI is built on
+//! - **async_finder**: `AsyncClassFinder`, a tokio front end for `ClassFinder` (`async` feature)
+//! - **find_options**: `FindOptions`, the validated version selection of a find
+//! - **session**: `ReadSession`, one read transaction shared by the lookups of a find
 //! - **registry**: Class-to-JAR mapping index for fast lookups
+//! - **bloom**: `ClassFilter`, the per-jar bloom filter that rules jars out of probes without opening them
 //! - **scan**: JAR file discovery in Maven repository structure
+//! - **scan_cache**: Jar lists of scanned roots, reused while fresh so repeated finds skip the walk
+//! - **repository**: Named jar roots with search priorities
 //! - **probe**: JAR inspection utilities for class existence checks
+//! - **listing**: Process-wide LRU of jar entry listings shared by probing, cataloging, pom and sources lookups
 //! - **catalog**: JAR indexing to extract class lists
+//! - **classpath**: Extra classpath (sibling jars, pom dependencies) for single-class decompiles
+//! - **decompiler**: Decompiler backend trait and shared JVM process handling
 //! - **cfr**: CFR decompiler integration
+//! - **cfr_server**: Persistent CFR helper process for single-class decompiles
+//! - **vineflower**: Vineflower decompiler integration
+//! - **download**: Checksum-verified downloads of decompiler jars
+//! - **javap**: javap fallback for classes no decompiler can handle
+//! - **encoding**: Deterministic decoding of decompiler output that is not valid UTF-8
+//! - **postprocess**: External formatter decompiled classes are piped through before caching
 //! - **parse**: Decompiled output parsing and class extraction
 //! - **source**: Source JAR discovery and Java source extraction
 //! - **buffer**: Write buffering for batch database operations
 //! - **warmup**: Background preloading of frequently accessed JARs
 //! - **hotspot**: Access tracking and warmup prioritization
+//! - **progress**: `ProgressSink`, the progress events of indexing, loads and warmups
+//! - **interrupt**: Ctrl-C handling that lets long-running commands stop cleanly
+//! - **deadline**: The global `--timeout` and the phase a command was in when it ran out
+//! - **logging**: Verbosity and format of the diagnostics on stderr
+//! - **error**: `ClassFinderError`, the failures callers can tell apart, with stable codes and exit codes
+//! - **output**: Schema-versioned JSON rendering shared by every command
+//! - **highlight**: Java syntax highlighting and colors for terminal output
 //! - **incremental**: Incremental indexing based on file modification times
 //! - **structure**: Java class structure extraction using tree-sitter AST parsing
+//! - **diff**: Structure-level comparison of two versions of a class
+//! - **export**: Writing the cached sources of a jar as a package-layout tree
+//! - **stub**: Body-less Java skeletons rendered from a class structure
+//! - **snapshot**: Policy for republishing the read-only db snapshot
 
+#[cfg(feature = "async")]
+pub mod async_finder;
+pub mod bloom;
 pub mod buffer;
 pub mod cache;
 pub mod catalog;
 pub mod cfr;
+pub mod cfr_server;
+pub mod classpath;
 pub mod cli;
 pub mod config;
+pub mod deadline;
+pub mod decompiler;
+pub mod diff;
+pub mod download;
+pub mod encoding;
+pub mod error;
+pub mod export;
+pub mod find_options;
+pub mod finder;
+pub mod highlight;
 pub mod hotspot;
 pub mod incremental;
+pub mod interrupt;
+pub mod javap;
+pub mod listing;
+pub mod logging;
+pub mod output;
 pub mod parse;
+pub mod postprocess;
 pub mod probe;
+pub mod progress;
 pub mod registry;
+pub mod repository;
 pub mod scan;
+pub mod scan_cache;
+pub mod session;
+pub mod snapshot;
 pub mod source;
 pub mod structure;
+pub mod stub;
+pub mod vineflower;
 pub mod warmup;
@@ -0,0 +1,281 @@
+//! Entry names of jars, read from the zip central directory once and shared
+//! by cataloging, probing, simple-name lookups and embedded-pom lookups.
+//!
+//! [`jar_listings`] is the process-wide cache: least recently used listings
+//! are evicted past its capacity (config key `listing_cache_size`, default
+//! [`DEFAULT_LISTING_CACHE_SIZE`] jars; 0 disables it), and a listing is
+//! read again when the jar's size or mtime changed since it was taken.
+
+use anyhow::{Context, Result};
+use memmap2::Mmap;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use zip::ZipArchive;
+
+use crate::error::ClassFinderError;
+use crate::incremental::JarStamp;
+
+pub const DEFAULT_LISTING_CACHE_SIZE: usize = 128;
+
+/// The entry names of one jar, sorted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JarListing {
+    names: Vec<String>,
+}
+
+impl JarListing {
+    /// Reads the central directory of `jar_path`.
+    pub fn read(jar_path: &Path) -> Result<Self> {
+        let file = File::open(jar_path)
+            .with_context(|| format!("Failed to open jar: {}", jar_path.display()))?;
+        // SAFETY: The file is opened read-only and remains valid for the lifetime of the mmap.
+        let mmap = unsafe {
+            Mmap::map(&file).with_context(|| format!("mmap failed: {}", jar_path.display()))?
+        };
+        let archive = ZipArchive::new(Cursor::new(&mmap[..]))
+            .map_err(|err| ClassFinderError::corrupt_jar(jar_path, err))?;
+        let mut names: Vec<String> = archive.file_names().map(str::to_string).collect();
+        names.sort_unstable();
+        names.dedup();
+        Ok(Self { names })
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.names
+            .binary_search_by(|probe| probe.as_str().cmp(name))
+            .is_ok()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.names.iter().map(String::as_str)
+    }
+}
+
+struct Cached {
+    stamp: JarStamp,
+    listing: Arc<JarListing>,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct CacheState {
+    capacity: usize,
+    entries: HashMap<PathBuf, Cached>,
+    /// `last_used` ticks to jars, oldest first.
+    recency: BTreeMap<u64, PathBuf>,
+    tick: u64,
+}
+
+impl CacheState {
+    fn touch(&mut self, jar_path: &Path) -> Option<&Cached> {
+        self.tick += 1;
+        let tick = self.tick;
+        let cached = self.entries.get_mut(jar_path)?;
+        self.recency.remove(&cached.last_used);
+        cached.last_used = tick;
+        self.recency.insert(tick, jar_path.to_path_buf());
+        Some(cached)
+    }
+
+    fn insert(&mut self, jar_path: &Path, stamp: JarStamp, listing: Arc<JarListing>) {
+        self.tick += 1;
+        let last_used = self.tick;
+        if let Some(old) = self.entries.insert(
+            jar_path.to_path_buf(),
+            Cached {
+                stamp,
+                listing,
+                last_used,
+            },
+        ) {
+            self.recency.remove(&old.last_used);
+        }
+        self.recency.insert(last_used, jar_path.to_path_buf());
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        while self.entries.len() > self.capacity {
+            let Some((_, jar_path)) = self.recency.pop_first() else {
+                break;
+            };
+            self.entries.remove(&jar_path);
+        }
+    }
+}
+
+/// Listings by jar path, least recently used evicted first.
+pub struct ListingCache {
+    state: Mutex<CacheState>,
+    reads: AtomicUsize,
+}
+
+impl ListingCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            state: Mutex::new(CacheState {
+                capacity,
+                ..CacheState::default()
+            }),
+            reads: AtomicUsize::new(0),
+        }
+    }
+
+    /// Jars kept at most; shrinking evicts right away.
+    pub fn configure(&self, capacity: usize) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.capacity = capacity;
+        state.evict();
+    }
+
+    /// The listing of `jar_path`, read unless a cached one was taken from
+    /// the jar as it is now.
+    pub fn get(&self, jar_path: &Path) -> Result<Arc<JarListing>> {
+        let stamp = JarStamp::read(jar_path);
+        if let Some(stamp) = stamp {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(cached) = state.touch(jar_path)
+                && cached.stamp == stamp
+            {
+                return Ok(Arc::clone(&cached.listing));
+            }
+        }
+        // Read outside the lock, so other jars are listed in parallel.
+        self.reads.fetch_add(1, Ordering::Relaxed);
+        let listing = Arc::new(JarListing::read(jar_path)?);
+        if let Some(stamp) = stamp {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            if state.capacity > 0 {
+                state.insert(jar_path, stamp, Arc::clone(&listing));
+            }
+        }
+        Ok(listing)
+    }
+
+    /// Central directories read so far.
+    pub fn reads(&self) -> usize {
+        self.reads.load(Ordering::Relaxed)
+    }
+
+    pub fn len(&self) -> usize {
+        self.state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entries
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The listings shared by every jar lookup in this process.
+pub fn jar_listings() -> &'static ListingCache {
+    static LISTINGS: OnceLock<ListingCache> = OnceLock::new();
+    LISTINGS.get_or_init(|| ListingCache::new(DEFAULT_LISTING_CACHE_SIZE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!(
+            "class_finder_listing_test_{}_{}_{}",
+            std::process::id(),
+            nanos,
+            name
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_jar(path: &Path, names: &[&str]) {
+        let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+        for name in names {
+            zip.start_file(*name, zip::write::FileOptions::default())
+                .unwrap();
+            zip.write_all(b"").unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn listings_are_sorted_and_searchable() -> Result<()> {
+        let dir = temp_dir("sorted");
+        let jar = dir.join("a.jar");
+        write_jar(&jar, &["b/B.class", "META-INF/MANIFEST.MF", "a/A.class"]);
+        let listing = JarListing::read(&jar)?;
+        assert_eq!(
+            listing.names().collect::<Vec<_>>(),
+            ["META-INF/MANIFEST.MF", "a/A.class", "b/B.class"]
+        );
+        assert!(listing.contains("a/A.class"));
+        assert!(!listing.contains("a/B.class"));
+        let _ = std::fs::remove_dir_all(&dir);
+        Ok(())
+    }
+
+    #[test]
+    fn cached_listings_are_reused_until_the_jar_changes() -> Result<()> {
+        let dir = temp_dir("reuse");
+        let jar = dir.join("a.jar");
+        write_jar(&jar, &["a/A.class"]);
+        let cache = ListingCache::new(4);
+        for _ in 0..3 {
+            assert!(cache.get(&jar)?.contains("a/A.class"));
+        }
+        assert_eq!(cache.reads(), 1);
+
+        write_jar(&jar, &["a/A.class", "a/B.class"]);
+        assert!(cache.get(&jar)?.contains("a/B.class"));
+        assert_eq!(cache.reads(), 2);
+        assert_eq!(cache.len(), 1);
+        let _ = std::fs::remove_dir_all(&dir);
+        Ok(())
+    }
+
+    #[test]
+    fn least_recently_used_listings_are_evicted() -> Result<()> {
+        let dir = temp_dir("evict");
+        let jars: Vec<PathBuf> = (0..3)
+            .map(|i| {
+                let jar = dir.join(format!("{i}.jar"));
+                write_jar(&jar, &["a/A.class"]);
+                jar
+            })
+            .collect();
+        let cache = ListingCache::new(2);
+        cache.get(&jars[0])?;
+        cache.get(&jars[1])?;
+        cache.get(&jars[0])?;
+        cache.get(&jars[2])?;
+        assert_eq!(cache.reads(), 3);
+        // jars[1] was the least recently used one.
+        cache.get(&jars[0])?;
+        cache.get(&jars[2])?;
+        assert_eq!(cache.reads(), 3);
+        cache.get(&jars[1])?;
+        assert_eq!(cache.reads(), 4);
+
+        cache.configure(0);
+        assert!(cache.is_empty());
+        cache.get(&jars[1])?;
+        cache.get(&jars[1])?;
+        assert_eq!(cache.reads(), 6);
+        assert!(cache.is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+        Ok(())
+    }
+}
@@ -0,0 +1,137 @@
+//! Diagnostics on stderr through `tracing`.
+//!
+//! `-v`/`-vv` lower the level to debug/trace and `--quiet` raises it to
+//! errors only. The text format keeps the `[class-finder] ` prefix of info
+//! messages (and `warning: ` for warnings) so the default output reads as it
+//! always has; `--log-format json` writes one JSON object per event, with
+//! the enclosing spans, for log ingestion from `watch`.
+
+use clap::ValueEnum;
+use std::fmt;
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::registry::LookupSpan;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// Info by default, one step more detail per `-v`, errors only when quiet.
+pub fn level(verbose: u8, quiet: bool) -> LevelFilter {
+    match (quiet, verbose) {
+        (true, _) => LevelFilter::ERROR,
+        (false, 0) => LevelFilter::INFO,
+        (false, 1) => LevelFilter::DEBUG,
+        (false, _) => LevelFilter::TRACE,
+    }
+}
+
+/// Installs the stderr subscriber. A second call is a no-op.
+pub fn init(level: LevelFilter, format: LogFormat) {
+    let builder = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(std::io::stderr);
+    let _ = match format {
+        LogFormat::Text => builder.event_format(TextFormat).try_init(),
+        LogFormat::Json => builder
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .try_init(),
+    };
+}
+
+/// One line per event: a level prefix and the message. Spans are left out.
+struct TextFormat;
+
+impl<S, N> FormatEvent<S, N> for TextFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let prefix = match *event.metadata().level() {
+            Level::ERROR => "error: ",
+            Level::WARN => "warning: ",
+            _ => "[class-finder] ",
+        };
+        writer.write_str(prefix)?;
+        ctx.field_format().format_fields(writer.by_ref(), event)?;
+        writeln!(writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn capture(level: LevelFilter, format: LogFormat) -> String {
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let builder = tracing_subscriber::fmt()
+            .with_max_level(level)
+            .with_writer(move || writer.clone());
+        let emit = || {
+            let _span = tracing::info_span!("find", class_name = "a.B").entered();
+            tracing::info!("find scan root: /m2");
+            tracing::warn!("hotspot update failed: locked");
+            tracing::debug!("cache miss");
+        };
+        match format {
+            LogFormat::Text => {
+                tracing::subscriber::with_default(builder.event_format(TextFormat).finish(), emit)
+            }
+            LogFormat::Json => tracing::subscriber::with_default(builder.json().finish(), emit),
+        }
+        String::from_utf8(captured.0.lock().unwrap().clone()).unwrap()
+    }
+
+    #[test]
+    fn text_format_keeps_the_plain_prefixes() {
+        assert_eq!(
+            capture(level(0, false), LogFormat::Text),
+            "[class-finder] find scan root: /m2\nwarning: hotspot update failed: locked\n"
+        );
+        assert!(capture(level(1, false), LogFormat::Text).ends_with("[class-finder] cache miss\n"));
+        assert_eq!(capture(level(2, true), LogFormat::Text), "");
+    }
+
+    #[test]
+    fn json_format_writes_one_object_per_event() {
+        let out = capture(level(0, false), LogFormat::Json);
+        let events: Vec<serde_json::Value> = out
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("json line"))
+            .collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["level"], "INFO");
+        assert_eq!(events[0]["fields"]["message"], "find scan root: /m2");
+        assert_eq!(events[0]["span"]["class_name"], "a.B");
+    }
+}
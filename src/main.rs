@@ -1,153 +1,628 @@
 use anyhow::{Context, Result};
-use clap::Parser;
-use class_finder::buffer::{BufferConfig, PendingWrite, WriteBuffer};
-use class_finder::cache::{ClassContentSource, PersistentCache, ReadOnlyCache};
-use class_finder::catalog;
-use class_finder::cfr::Cfr;
-use class_finder::cli::{Cli, Commands, OutputFormat};
-use class_finder::config::{clear_db, resolve_cfr_path, resolve_db_path, resolve_m2_repo};
+use clap::{CommandFactory, Parser};
+use class_finder::cache::{
+    ClassContentSource, ClearTarget, PersistentCache, ReadOnlyCache, StructureScan, clear_tables,
+};
+use class_finder::cli::{
+    Cli, Commands, ConfigAction, DiffFormat, HotAction, OutputFormat, ProgressFormat,
+};
+use class_finder::config::{
+    clear_db, configured_cfr_version, doctor_report, load_file_config, resolve_buffer_config,
+    resolve_db_path, resolve_decompiler, resolve_decompiler_config, resolve_decompiler_kind,
+    resolve_effective_config, resolve_existing_db_path, resolve_jar_exclusions, resolve_java_jobs,
+    resolve_listing_cache_size, resolve_m2_repo, resolve_repositories, resolve_scan_cache_max_age,
+    resolve_warmup_threshold,
+};
+use class_finder::deadline;
+use class_finder::decompiler::{DecompilerKind, java_process_slots};
+use class_finder::diff::{StructureDiff, diff_structures};
+use class_finder::error::{ClassFinderError, NOT_FOUND_EXIT_CODE, TIMEOUT_EXIT_CODE};
+use class_finder::export::export_sources;
+use class_finder::find_options::FindOptions;
+use class_finder::finder::{
+    ClassFinder, FindResult, FindVersion, WarmupParams, WarmupProgress, repository_roots,
+};
+use class_finder::highlight::{Style, highlight_java, paint};
 use class_finder::hotspot::HotspotTracker;
-use class_finder::parse::{hash_content, parse_decompiled_output};
-use class_finder::probe::{find_class_fqns_in_jar, jar_contains_class};
-use class_finder::registry::ClassRegistry;
-use class_finder::scan::{
-    class_name_to_class_path, extract_version_from_maven_path, infer_scan_path, infer_search_paths,
-    scan_jars,
+use class_finder::incremental::{
+    IncrementalConfig, IncrementalIndexResult, IncrementalIndexer, WatchMode,
 };
+use class_finder::interrupt;
+use class_finder::javap::Javap;
+use class_finder::listing::jar_listings;
+use class_finder::logging;
+use class_finder::output::Schema;
+use class_finder::parse::hash_content;
+use class_finder::progress::{ProgressSink, StderrProgress};
+use class_finder::registry::ClassRegistry;
+use class_finder::repository::named;
+use class_finder::scan::{JarExclusions, extract_version_from_maven_path};
+use class_finder::scan_cache::invalidate_scans;
+use class_finder::snapshot::spawn_publisher;
 use class_finder::source;
-use class_finder::structure::{ClassStructure, parse_class_structure};
-use rayon::prelude::*;
+use class_finder::structure::{
+    ClassStructure, CompilationUnit, ConstantValue, MethodQuery, parse_compilation_unit,
+};
+use class_finder::stub::render_compilation_unit_stub;
 use serde::Serialize;
-use std::collections::{HashMap, HashSet};
+use std::collections::BTreeMap;
+use std::io::IsTerminal;
 use std::path::{Path, PathBuf};
-use std::time::Instant;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+fn main() {
+    let mut schema = Schema::default();
+    let mut json_errors = false;
+    let result = parse_cli().and_then(|cli| {
+        json_errors = reports_errors_as_json(&cli);
+        schema = Schema::resolve(cli.compat)?;
+        run(cli, schema)
+    });
+    match result {
+        Ok(0) => {}
+        Ok(code) => std::process::exit(code),
+        Err(err) => exit_with_error(&err, schema, json_errors),
+    }
+}
 
-fn main() -> Result<()> {
-    let cli = parse_cli()?;
+/// What the failing command had found so far, reported with its error.
+static PARTIAL_RESULTS: Mutex<Option<serde_json::Value>> = Mutex::new(None);
 
+fn record_partial_results(partial: impl Serialize) {
+    if let Ok(value) = serde_json::to_value(partial) {
+        *PARTIAL_RESULTS.lock().unwrap_or_else(|e| e.into_inner()) = Some(value);
+    }
+}
+
+/// Logs `err` and, for commands printing JSON and for timeouts, prints it as
+/// a JSON object with its code on stdout, then exits with the code of its
+/// [`ClassFinderError`].
+fn exit_with_error(err: &anyhow::Error, schema: Schema, json: bool) -> ! {
+    let error = ClassFinderError::from_error(err);
+    tracing::error!(code = error.code(), "{err:#}");
+    if json || matches!(error, ClassFinderError::Timeout { .. }) {
+        let partial = PARTIAL_RESULTS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        if let Ok(json) = schema.error_json(&error, partial.as_ref()) {
+            println!("{json}");
+        }
+    }
+    std::process::exit(error.exit_code());
+}
+
+/// Whether the command's output is JSON, so its failures are too. Every
+/// command prints JSON except `find` and `diff` in their other formats.
+fn reports_errors_as_json(cli: &Cli) -> bool {
+    match &cli.command {
+        Commands::Find {
+            format,
+            code_only,
+            constants,
+            exists,
+            count,
+            ..
+        } => {
+            matches!(format, OutputFormat::Json | OutputFormat::Structure)
+                && !(*code_only || *constants || *exists || *count)
+        }
+        Commands::Diff { format, .. } => *format == DiffFormat::Json,
+        _ => true,
+    }
+}
+
+/// Runs the command and returns its exit status, so everything it opened is
+/// dropped (and flushed) before `main` exits with a non-zero one.
+fn run(cli: Cli, schema: Schema) -> Result<i32> {
     match cli.command.clone() {
-        Commands::Clear => {
+        Commands::Clear {
+            all: _,
+            sources,
+            registry,
+            hotspot,
+            loaded_flags,
+            mtimes,
+        } => {
             let db_path = resolve_db_path(&cli)?;
-            clear_db(&db_path)?;
+            let targets: Vec<ClearTarget> = [
+                (sources, ClearTarget::Sources),
+                (registry, ClearTarget::Registry),
+                (hotspot, ClearTarget::Hotspot),
+                (loaded_flags, ClearTarget::LoadedFlags),
+                (mtimes, ClearTarget::Mtimes),
+            ]
+            .into_iter()
+            .filter_map(|(selected, target)| selected.then_some(target))
+            .collect();
+            if targets.is_empty() {
+                clear_db(&db_path)?;
+            } else {
+                let cache = PersistentCache::open(db_path)?;
+                let output = ClearOutput {
+                    cleared: clear_tables(&cache.db(), &targets)?,
+                };
+                // Clearing jar data also drops the jar lists of roots that
+                // are no longer configured.
+                if targets
+                    .iter()
+                    .any(|t| matches!(t, ClearTarget::Registry | ClearTarget::Mtimes))
+                {
+                    let roots: Vec<PathBuf> = resolve_repositories(&cli)?
+                        .into_iter()
+                        .map(|r| r.path)
+                        .collect();
+                    invalidate_scans(&cache.db(), &[], &roots)?;
+                }
+                println!("{}", schema.to_json(&output)?);
+            }
         }
-        Commands::Index { path } => {
-            let db_path = resolve_db_path(&cli)?;
+        Commands::Config {
+            action: ConfigAction::Show,
+        } => {
+            let config = resolve_effective_config(&cli)?;
+            println!("{}", schema.to_json(&config)?);
+        }
+        Commands::Index {
+            path,
+            repo,
+            incremental,
+            purge_removed,
+        } => {
+            if purge_removed && !incremental {
+                return Err(ClassFinderError::invalid_input(
+                    "--purge-removed requires --incremental",
+                )
+                .into());
+            }
             let output = {
-                let cache = PersistentCache::open(db_path.clone())?;
-                let registry = ClassRegistry::new(cache.db());
-                let root = path.unwrap_or(resolve_m2_repo(&cli)?);
-                index_repo(&registry, root)?
+                let finder = open_finder(&cli)?;
+                let root = match (path, repo) {
+                    (Some(path), _) => path,
+                    (None, Some(name)) => named(finder.repositories(), &name)?.path.clone(),
+                    (None, None) => resolve_m2_repo(&cli)?,
+                };
+                if incremental {
+                    finder.index_incremental(root, purge_removed)?
+                } else {
+                    finder.index(root)?
+                }
             };
-            println!("{}", serde_json::to_string_pretty(&output)?);
+            println!("{}", schema.to_json(&output)?);
         }
-        Commands::Stats => {
+        Commands::Watch {
+            path,
+            repo,
+            interval,
+            debounce,
+            purge_removed,
+        } => {
             let db_path = resolve_db_path(&cli)?;
+            let cache = Arc::new(PersistentCache::open(db_path)?);
+            let registry = ClassRegistry::new(cache.db());
+            let repositories = resolve_repositories(&cli)?;
+            let root = match (path, repo) {
+                (Some(path), _) => path,
+                (None, Some(name)) => {
+                    let repository = named(&repositories, &name)?;
+                    if repository.readonly {
+                        anyhow::bail!(
+                            "Repository {name:?} is read-only; there is nothing to watch"
+                        );
+                    }
+                    repository.path.clone()
+                }
+                (None, None) => resolve_m2_repo(&cli)?,
+            };
+            interrupt::install();
+            let _span = tracing::info_span!("watch", root = %root.display()).entered();
+            let publisher = spawn_publisher(Arc::clone(&cache), cli.file_config.snapshot, None);
+            let mut indexer = IncrementalIndexer::new(cache.db(), root)
+                .with_scan_roots(repositories.iter().map(|r| r.path.clone()).collect())
+                .with_purge_removed(purge_removed);
+            if let Some(progress) = progress_sink(&cli) {
+                indexer = indexer.with_progress(progress);
+            }
+            indexer.watch(
+                &registry,
+                IncrementalConfig { interval, debounce },
+                |mode, result| {
+                    let update = WatchUpdate { mode, result };
+                    if let Ok(line) = schema.to_json_line(&update) {
+                        println!("{line}");
+                    }
+                },
+            )?;
+            if let Some(publisher) = publisher {
+                let _ = publisher.join();
+            }
+        }
+        Commands::ExportSources {
+            jar_path,
+            out,
+            complete,
+            inner,
+        } => {
+            let decompiler = if complete {
+                Some(resolve_decompiler(&cli)?)
+            } else {
+                None
+            };
+            let cache = PersistentCache::open(resolve_db_path(&cli)?)?;
+            configure_java_processes(&cli, &cache);
+            let result = export_sources(
+                &cache,
+                resolve_decompiler_kind(&cli),
+                &jar_path,
+                &out,
+                inner,
+                decompiler.as_deref(),
+            )?;
+            println!("{}", schema.to_json(&result)?);
+        }
+        Commands::Doctor => {
+            let report = doctor_report(&cli)?;
+            println!("{}", schema.to_json(&report)?);
+        }
+        Commands::Snapshot => {
+            let db_path = resolve_existing_db_path(&cli)?;
+            let cache = PersistentCache::open(db_path)?;
+            let record = cache.publish_snapshot(None)?;
+            println!("{}", schema.to_json(&record)?);
+        }
+        Commands::Stats => {
+            let db_path = resolve_existing_db_path(&cli)?;
             let cache = ReadOnlyCache::open(db_path)?;
-            let stats = cache.stats()?;
-            println!("{}", serde_json::to_string_pretty(&stats)?);
+            let mut stats = cache.stats()?;
+            stats.cfr_version = configured_cfr_version(&cli);
+            stats.write_buffer_config = Some(resolve_buffer_config(&cli)?);
+            stats.warmup_threshold = resolve_warmup_threshold(&cli)?;
+            stats.group_top = HotspotTracker::new(cache.db(), stats.warmup_threshold).top_groups(
+                &resolve_m2_repo(&cli)?,
+                false,
+                10,
+            )?;
+            println!("{}", schema.to_json(&stats)?);
         }
-        Commands::Load { jar_path } => {
-            let cfr = Cfr::new(resolve_cfr_path(&cli)?);
-            let db_path = resolve_db_path(&cli)?;
-            let output = {
-                let cache = PersistentCache::open(db_path.clone())?;
-                let registry = ClassRegistry::new(cache.db());
-                let hotspot = HotspotTracker::new(cache.db(), 2);
-                let mut buffer = WriteBuffer::new(
-                    cache.db(),
-                    BufferConfig::default(),
-                    cache.pending_gauge_path(),
-                );
-                let output = load_jar(&cache, &registry, &buffer, &cfr, &jar_path)?;
-                buffer.shutdown_and_flush()?;
-                if !output.skipped {
-                    cache.mark_jar_loaded(&output.jar_path)?;
-                    let _ = hotspot.mark_warmed(&output.jar_path, output.classes_loaded as u32);
-                }
-                output
+        Commands::Diff {
+            class_name,
+            from,
+            to,
+            format,
+        } => {
+            let finder = open_finder(&cli)?.with_decompiler(resolve_decompiler(&cli)?);
+            let class_name = normalize_class_name(&class_name);
+            let (from, old) = diff_side(&finder, &class_name, &from)?;
+            let (to, new) = diff_side(&finder, &class_name, &to)?;
+            let output = DiffOutput {
+                class_name,
+                from,
+                to,
+                diff: diff_structures(&old, &new),
             };
-            println!("{}", serde_json::to_string_pretty(&output)?);
+            match format {
+                DiffFormat::Json => println!("{}", schema.to_json(&output)?),
+                DiffFormat::Text => {
+                    println!(
+                        "{} {} -> {}",
+                        output.class_name, output.from.version, output.to.version
+                    );
+                    print!("{}", output.diff.to_text());
+                }
+            }
+        }
+        Commands::Load { jar_path } => {
+            let output = open_finder(&cli)?
+                .with_decompiler(resolve_decompiler(&cli)?)
+                .load_jar(&jar_path)?;
+            println!("{}", schema.to_json(&output)?);
         }
         Commands::Warmup {
             jar_path,
             hot,
+            group_level,
             group,
+            from_file,
             top,
             limit,
+            resume,
+            mode,
+            max_duration,
+            max_classes,
+            exclude_group,
+            exclude_artifact,
+            exclude_jar_glob,
+            jobs,
+            order,
+            format,
         } => {
-            let cfr = Cfr::new(resolve_cfr_path(&cli)?);
-            let db_path = resolve_db_path(&cli)?;
+            let decompiler = resolve_decompiler(&cli)?;
+            interrupt::install();
             let output = {
-                let cache = PersistentCache::open(db_path.clone())?;
-                let registry = ClassRegistry::new(cache.db());
-                let hotspot = HotspotTracker::new(cache.db(), 2);
-                let mut buffer = WriteBuffer::new(
-                    cache.db(),
-                    BufferConfig::default(),
-                    cache.pending_gauge_path(),
-                );
-                let m2_repo = resolve_m2_repo(&cli)?;
-                let deps = WarmupDeps {
-                    cache: &cache,
-                    registry: &registry,
-                    hotspot: &hotspot,
-                    buffer: &buffer,
-                    cfr: &cfr,
-                    m2_repo: &m2_repo,
-                };
+                let exclusions = resolve_jar_exclusions(
+                    &cli,
+                    &exclude_group,
+                    &exclude_artifact,
+                    &exclude_jar_glob,
+                )?;
+                let finder = open_finder(&cli)?
+                    .with_decompiler(decompiler)
+                    .with_exclusions(exclusions);
                 let params = WarmupParams {
                     jar_path: jar_path.as_deref(),
                     hot,
+                    group_level,
                     group: group.as_deref(),
+                    from_file: from_file.as_deref(),
                     top,
                     limit,
+                    resume,
+                    mode,
+                    max_duration,
+                    max_classes,
+                    jobs,
+                    order,
                 };
-                let output = warmup_targets(&deps, params)?;
-                buffer.shutdown_and_flush()?;
-                for (jar_key, class_count) in &output.loaded_jars {
-                    cache.mark_jar_loaded(jar_key)?;
-                    let _ = hotspot.mark_warmed(jar_key, *class_count);
+                finder.warmup(params, &mut |progress| {
+                    report_warmup_progress(schema, format, progress)
+                })?
+            };
+            match format {
+                ProgressFormat::Text => println!("{}", schema.to_json(&output)?),
+                ProgressFormat::Jsonl => println!("{}", schema.to_json_line(&output)?),
+            }
+            // The result already says why it stopped.
+            if output.stopped_by == Some("timeout") {
+                return Ok(TIMEOUT_EXIT_CODE);
+            }
+        }
+        Commands::Hot {
+            action: Some(action),
+            ..
+        } => {
+            let db_path = resolve_db_path(&cli)?;
+            let cache = PersistentCache::open(db_path)?;
+            let hotspot = HotspotTracker::new(cache.db(), resolve_warmup_threshold(&cli)?);
+            let removal = match action {
+                HotAction::Reset { jar } => hotspot.reset(
+                    jar.as_deref()
+                        .map(|jar| jar.to_string_lossy().to_string())
+                        .as_deref(),
+                )?,
+                HotAction::Trim { older_than } => hotspot.trim(older_than)?,
+            };
+            println!("{}", schema.to_json(&removal)?);
+        }
+        Commands::Hot {
+            action: None,
+            classes,
+            jar,
+            by_group,
+            by_artifact,
+            top,
+        } => {
+            let db_path = resolve_existing_db_path(&cli)?;
+            let cache = ReadOnlyCache::open(db_path)?;
+            let hotspot = HotspotTracker::new(cache.db(), resolve_warmup_threshold(&cli)?);
+            let jar_key = jar.as_deref().map(|jar| jar.to_string_lossy().to_string());
+            if classes || jar_key.is_some() {
+                let entries = hotspot.top_classes(jar_key.as_deref(), top)?;
+                println!("{}", serde_json::to_string_pretty(&entries)?);
+            } else if by_group || by_artifact {
+                let m2_repo = resolve_m2_repo(&cli)?;
+                let entries = hotspot.top_groups(&m2_repo, by_artifact, top)?;
+                println!("{}", serde_json::to_string_pretty(&entries)?);
+            } else {
+                let entries = hotspot.top_jars(top)?;
+                println!("{}", serde_json::to_string_pretty(&entries)?);
+            }
+        }
+        Commands::Find {
+            with_method: Some(query),
+            group,
+            format,
+            output,
+            ..
+        } => {
+            let db_path = resolve_db_path(&cli)?;
+            let cache = PersistentCache::open(db_path)?;
+            let m2_repo = resolve_m2_repo(&cli)?;
+            let result = find_methods(
+                &cache,
+                resolve_decompiler_kind(&cli),
+                &m2_repo,
+                &query,
+                group.as_deref(),
+            )?;
+            let content = match format {
+                OutputFormat::Json => schema.to_json(&result)?,
+                OutputFormat::Text => result
+                    .matches
+                    .iter()
+                    .map(|m| format!("{} {} ({})\n", m.class_name, m.signature, m.jar_path))
+                    .collect(),
+                OutputFormat::Code
+                | OutputFormat::Structure
+                | OutputFormat::Constants
+                | OutputFormat::Stub
+                | OutputFormat::Paths => {
+                    return Err(ClassFinderError::invalid_input(
+                        "--with-method supports --format json or text",
+                    )
+                    .into());
                 }
-                output
             };
-            println!("{}", serde_json::to_string_pretty(&output)?);
+            write_output(&content, output.as_deref())?;
         }
         Commands::Find {
             class_name,
             format,
             code_only,
+            constants,
+            exists,
+            count,
+            exclude_group,
+            exclude_artifact,
+            exclude_jar_glob,
             version,
+            latest,
+            max_versions,
+            max_matches,
+            fresh_scan,
+            verify_jars,
             output,
+            no_fallback,
+            fallback_view,
+            background_warmup_ms,
+            with_javadoc,
+            ..
         } => {
-            let cfr = Cfr::new(resolve_cfr_path(&cli)?);
-            let db_path = resolve_db_path(&cli)?;
-            let cache = PersistentCache::open(db_path)?;
-            let registry = ClassRegistry::new(cache.db());
+            let Some(class_name) = class_name else {
+                return Err(ClassFinderError::invalid_input(
+                    "find needs a class name or --with-method",
+                )
+                .into());
+            };
             let effective_format = if code_only {
                 OutputFormat::Code
+            } else if constants {
+                OutputFormat::Constants
             } else {
                 format
             };
             let class_name = normalize_class_name(&class_name);
-            let m2_repo = resolve_m2_repo(&cli)?;
-            let deps = FindDeps {
-                cache: &cache,
-                registry: &registry,
-                cfr: &cfr,
-                m2_repo: &m2_repo,
-            };
-            let result = find_class(&deps, &class_name, version)?;
-            write_find_output(&result, effective_format, output.as_deref())?;
-            backfill_find_cache(&cache, &registry, &cfr, &result);
+            let finder = open_finder(&cli)?.with_exclusions(JarExclusions::new(
+                &exclude_group,
+                &exclude_artifact,
+                &exclude_jar_glob,
+            )?);
+            let options = FindOptions::new()
+                .with_version(version.as_deref())
+                .with_latest(latest)
+                .with_max_versions(max_versions)
+                // One hit answers --exists; there is no need to probe further.
+                .with_max_matches(max_matches.or(exists.then_some(1)))
+                .with_fresh_scan(fresh_scan)
+                .with_verify_jars(verify_jars);
+            let found = finder.match_class(&class_name, &options)?;
+            // These only need the matching phase: no cache content is read
+            // and nothing is decompiled.
+            if exists || count || effective_format == OutputFormat::Paths {
+                if count {
+                    write_output(&format!("{}\n", found.jars.len()), output.as_deref())?;
+                    return Ok(0);
+                }
+                if found.jars.is_empty() {
+                    tracing::info!(
+                        "Class {} not found (scan dir: {})",
+                        found.class_name,
+                        repository_roots(finder.repositories())
+                    );
+                    return Ok(NOT_FOUND_EXIT_CODE);
+                }
+                if exists {
+                    return Ok(0);
+                }
+                let paths: String = found
+                    .jars
+                    .iter()
+                    .map(|jar| Ok(format!("{}\n", std::path::absolute(jar)?.display())))
+                    .collect::<std::io::Result<_>>()?;
+                write_output(&paths, output.as_deref())?;
+                return Ok(0);
+            }
+            let mut finder = finder.with_decompiler(resolve_decompiler(&cli)?);
+            if !no_fallback {
+                let timeout = resolve_decompiler_config(&cli).class_timeout;
+                finder = finder.with_javap(Javap::new(fallback_view, timeout));
+            }
+            if !found.jars.is_empty() {
+                // Still listed if every decompile below fails.
+                record_partial_results(PartialFind {
+                    class_name: &found.class_name,
+                    scanned_root: &found.scan_root,
+                    matched_jars: &found.jars,
+                });
+            }
+            let mut result = finder.find_matched(found)?;
+            if with_javadoc {
+                attach_javadoc(&mut result);
+            }
+            let color = output.is_none() && cli.color.enabled(std::io::stdout().is_terminal());
+            write_find_output(
+                finder.cache(),
+                &result,
+                effective_format,
+                output.as_deref(),
+                with_javadoc,
+                color,
+                schema,
+            )?;
+            let requests = finder.record_accesses(&result);
+            // Without a warmup budget the matched jars are backfilled here;
+            // `--background-warmup-ms 0` does neither.
+            match background_warmup_ms {
+                None => finder.backfill(&result),
+                Some(0) => {}
+                Some(budget) => {
+                    finder.background_warmup(requests, Duration::from_millis(budget));
+                }
+            }
         }
     }
 
-    Ok(())
+    Ok(0)
 }
 
 fn parse_cli() -> Result<Cli> {
     let args: Vec<String> = std::env::args().collect();
-    Ok(Cli::parse_from(rewrite_args_for_implicit_find(args)))
+    let mut cli = Cli::parse_from(rewrite_args_for_implicit_find(args));
+    // Output meant for pipelines keeps stderr quiet unless asked otherwise.
+    let quiet = cli.quiet
+        || (cli.verbose == 0
+            && matches!(
+                cli.command,
+                Commands::Find {
+                    format: OutputFormat::Paths,
+                    ..
+                } | Commands::Find { exists: true, .. }
+                    | Commands::Find { count: true, .. }
+            ));
+    logging::init(logging::level(cli.verbose, quiet), cli.log_format);
+    if let Some(secs) = cli.timeout {
+        deadline::set(Duration::from_secs(secs));
+    }
+    cli.file_config = load_file_config()?;
+    jar_listings().configure(resolve_listing_cache_size(&cli));
+    Ok(cli)
+}
+
+/// A [`ClassFinder`] over the configured cache and repositories, without a
+/// decompiler.
+fn open_finder(cli: &Cli) -> Result<ClassFinder> {
+    let mut finder = ClassFinder::open(resolve_db_path(cli)?, resolve_m2_repo(cli)?)?
+        .with_repositories(resolve_repositories(cli)?)
+        .with_buffer_config(resolve_buffer_config(cli)?)
+        .with_warmup_threshold(resolve_warmup_threshold(cli)?)
+        .with_scan_cache_max_age(resolve_scan_cache_max_age(cli))
+        .with_cache_package_info(cli.cache_package_info)
+        .with_cache_structures(cli.cache_structures);
+    if let Some(progress) = progress_sink(cli) {
+        finder = finder.with_progress(progress);
+    }
+    configure_java_processes(cli, finder.cache());
+    Ok(finder)
+}
+
+/// `--progress`: events as JSON lines on stderr.
+fn progress_sink(cli: &Cli) -> Option<Arc<dyn ProgressSink>> {
+    cli.progress
+        .then(|| Arc::new(StderrProgress) as Arc<dyn ProgressSink>)
+}
+
+fn configure_java_processes(cli: &Cli, cache: &PersistentCache) {
+    java_process_slots().configure(resolve_java_jobs(cli), Some(cache.java_gauge_path()));
 }
 
 fn rewrite_args_for_implicit_find(mut args: Vec<String>) -> Vec<String> {
@@ -155,7 +630,29 @@ fn rewrite_args_for_implicit_find(mut args: Vec<String>) -> Vec<String> {
         return args;
     }
 
-    let subcommands = ["find", "load", "warmup", "index", "stats", "clear", "help"];
+    let subcommands = [
+        "find",
+        "diff",
+        "load",
+        "export-sources",
+        "warmup",
+        "index",
+        "watch",
+        "stats",
+        "snapshot",
+        "doctor",
+        "hot",
+        "clear",
+        "config",
+        "help",
+    ];
+    let command = Cli::command();
+    let global_flags = FlagNames::of(&command);
+    let global_value_flags = FlagNames::taking_values(&command);
+    let find_flags = command
+        .find_subcommand("find")
+        .map(FlagNames::of)
+        .unwrap_or_default();
 
     let mut idx = 1usize;
     while idx < args.len() {
@@ -165,14 +662,18 @@ fn rewrite_args_for_implicit_find(mut args: Vec<String>) -> Vec<String> {
             break;
         }
 
-        if a == "--m2" || a == "--cfr" || a == "--db" {
-            idx += 2;
+        if global_value_flags.matches(a) {
+            // `--name value` and `-s value` take the next argument too.
+            let inline = a.contains('=') || (!a.starts_with("--") && a.len() > 2);
+            idx += if inline { 1 } else { 2 };
             continue;
         }
 
-        if a.starts_with("--m2=") || a.starts_with("--cfr=") || a.starts_with("--db=") {
-            idx += 1;
-            continue;
+        // A flag only `find` has means the find starts here, before its
+        // class name, and clap can take it from there.
+        if find_flags.matches(a) && !global_flags.matches(a) {
+            args.insert(idx, "find".to_string());
+            return args;
         }
 
         if a.starts_with('-') {
@@ -193,518 +694,183 @@ fn rewrite_args_for_implicit_find(mut args: Vec<String>) -> Vec<String> {
     args
 }
 
-fn normalize_class_name(raw: &str) -> String {
-    let mut s = raw.trim();
-    if let Some(rest) = s.strip_prefix("import") {
-        s = rest.trim();
-    }
-    if s.ends_with(';') {
-        s = s.trim_end_matches(';').trim();
-    }
-    s.chars().filter(|c| !c.is_whitespace()).collect()
-}
-
-#[derive(Debug, Serialize)]
-struct FindVersion {
-    version: Option<String>,
-    jar_path: String,
-    content_hash: String,
-    content: String,
-    cache_hit: bool,
-    source: String,
-    lookup_source: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    structure: Option<ClassStructure>,
-}
-
-#[derive(Debug, Serialize)]
-struct FindResult {
-    class_name: String,
-    scanned_root: String,
-    matched_jars: usize,
-    duration_ms: u64,
-    versions: Vec<FindVersion>,
-}
-
-#[derive(Debug, Serialize)]
-struct LoadResult {
-    jar_path: String,
-    classes_loaded: usize,
-    skipped: bool,
-    duration_ms: u64,
-}
-
-#[derive(Debug, Serialize)]
-struct WarmupResult {
-    targets: usize,
-    succeeded: usize,
-    failed: usize,
-    duration_ms: u64,
-    loads: Vec<LoadResult>,
-    loaded_jars: Vec<(String, u32)>,
+/// Long and short names of a command's options.
+#[derive(Default)]
+struct FlagNames {
+    long: Vec<String>,
+    short: Vec<char>,
 }
 
-#[derive(Debug, Serialize)]
-struct IndexResult {
-    root: String,
-    scanned_jars: usize,
-    cataloged_jars_new: usize,
-    indexed_classes: usize,
-    duration_ms: u64,
-    failed_jars: usize,
-}
-
-struct FindDeps<'a> {
-    cache: &'a PersistentCache,
-    registry: &'a ClassRegistry,
-    cfr: &'a Cfr,
-    m2_repo: &'a Path,
-}
-
-fn find_class(
-    deps: &FindDeps<'_>,
-    class_name: &str,
-    version_filter: Option<String>,
-) -> Result<FindResult> {
-    let start = Instant::now();
-    let m2_repo = deps.m2_repo;
-    let (resolved_class_name, mut matched, scan_root, miss_source) = if class_name.contains('.') {
-        let search_paths = infer_search_paths(m2_repo, class_name);
-        let scan_root = search_paths
-            .first()
-            .cloned()
-            .unwrap_or_else(|| infer_scan_path(m2_repo, class_name));
-        let class_path = class_name_to_class_path(class_name);
-        let mut registry_hits: Vec<PathBuf> = deps
-            .registry
-            .get_artifacts(class_name)?
-            .into_iter()
-            .map(PathBuf::from)
-            .filter(|p| p.exists())
-            .collect();
-
-        if let Some(v) = version_filter.clone() {
-            registry_hits
-                .retain(|p| extract_version_from_maven_path(p).as_deref() == Some(v.as_str()));
-        }
-
-        registry_hits.retain(|jar| jar_contains_class(jar, &class_path).unwrap_or(false));
-
-        if !registry_hits.is_empty() {
-            (
-                class_name.to_string(),
-                registry_hits,
-                scan_root,
-                "registry".to_string(),
-            )
-        } else {
-            let mut matched: Vec<PathBuf> = Vec::new();
-            let mut used_scan_root = scan_root.clone();
-
-            for candidate_root in search_paths.iter() {
-                eprintln!(
-                    "[class-finder] find scan root: {}",
-                    candidate_root.display()
-                );
-                let jars = scan_jars(candidate_root)?;
-                matched = jars
-                    .par_iter()
-                    .filter_map(|jar| match jar_contains_class(jar, &class_path) {
-                        Ok(true) => Some(jar.clone()),
-                        Ok(false) => None,
-                        Err(_) => None,
-                    })
-                    .collect();
-                if !matched.is_empty() {
-                    used_scan_root = candidate_root.clone();
-                    break;
-                }
-            }
-
-            if matched.is_empty() && scan_root.as_path() != m2_repo {
-                eprintln!(
-                    "[class-finder] find fallback scan root: {}",
-                    m2_repo.display()
-                );
-                let jars = scan_jars(m2_repo)?;
-                matched = jars
-                    .par_iter()
-                    .filter_map(|jar| match jar_contains_class(jar, &class_path) {
-                        Ok(true) => Some(jar.clone()),
-                        Ok(false) => None,
-                        Err(_) => None,
-                    })
-                    .collect();
-                used_scan_root = m2_repo.to_path_buf();
-            }
-
-            (
-                class_name.to_string(),
-                matched,
-                used_scan_root,
-                "scan".to_string(),
-            )
-        }
-    } else {
-        let scan_root = m2_repo.to_path_buf();
-        let jars = scan_jars(&scan_root)?;
-
-        let mut fqn_to_jars: HashMap<String, Vec<PathBuf>> = HashMap::new();
-        for jar in jars.iter() {
-            let fqns = find_class_fqns_in_jar(jar, class_name).unwrap_or_default();
-            for fqn in fqns.into_iter().take(1) {
-                fqn_to_jars.entry(fqn).or_default().push(jar.clone());
-            }
-        }
-
-        let (best_fqn, best_jars) = fqn_to_jars
-            .into_iter()
-            .max_by(|(a_name, a_jars), (b_name, b_jars)| {
-                a_jars
-                    .len()
-                    .cmp(&b_jars.len())
-                    .then_with(|| a_name.cmp(b_name))
-            })
-            .with_context(|| {
-                format!(
-                    "Class {class_name} not found (scan dir: {})",
-                    scan_root.display()
-                )
-            })?;
-
-        (best_fqn, best_jars, scan_root, "scan".to_string())
-    };
-
-    if let Some(v) = version_filter.clone() {
-        matched.retain(|p| extract_version_from_maven_path(p).as_deref() == Some(v.as_str()));
+impl FlagNames {
+    fn of(command: &clap::Command) -> Self {
+        Self::filtered(command, |_| true)
     }
 
-    matched.sort_by(|a, b| {
-        extract_version_from_maven_path(a).cmp(&extract_version_from_maven_path(b))
-    });
-
-    if matched.is_empty() {
-        anyhow::bail!(
-            "Class {resolved_class_name} not found (scan dir: {})",
-            scan_root.display()
-        );
+    /// Names of the options that take a value.
+    fn taking_values(command: &clap::Command) -> Self {
+        Self::filtered(command, |arg| arg.get_action().takes_values())
     }
 
-    let mut versions = Vec::new();
-
-    for jar_path in matched.iter() {
-        let jar_key = jar_path.to_string_lossy().to_string();
-        let cache_key = format!("{resolved_class_name}::{jar_key}");
-
-        if let Some(cached) = deps.cache.get_class_source(&cache_key)? {
-            let content_hash = hash_content(&cached.content);
-            versions.push(FindVersion {
-                version: extract_version_from_maven_path(jar_path),
-                jar_path: jar_key,
-                content_hash,
-                content: cached.content,
-                cache_hit: true,
-                source: cached.source.as_str().to_string(),
-                lookup_source: miss_source.clone(),
-                structure: None,
-            });
-            continue;
+    fn filtered(command: &clap::Command, keep: impl Fn(&clap::Arg) -> bool) -> Self {
+        let mut names = Self::default();
+        for arg in command.get_arguments().filter(|arg| keep(arg)) {
+            names.long.extend(
+                arg.get_long_and_visible_aliases()
+                    .into_iter()
+                    .flatten()
+                    .map(String::from),
+            );
+            names
+                .short
+                .extend(arg.get_short_and_visible_aliases().into_iter().flatten());
         }
-
-        let (content, content_source) = if let Some(content) =
-            source::read_class_source(jar_path, &resolved_class_name).unwrap_or(None)
-        {
-            (content, ClassContentSource::SourcesJar)
-        } else {
-            let decompiled = deps.cfr.decompile_class(jar_path, &resolved_class_name)?;
-            let parsed = parse_decompiled_output(&decompiled);
-            let content = parsed
-                .iter()
-                .find(|c| c.class_name == resolved_class_name)
-                .map(|c| c.content.clone())
-                .unwrap_or(decompiled);
-            (content, ClassContentSource::Decompiled)
-        };
-        let content_hash = hash_content(&content);
-        versions.push(FindVersion {
-            version: extract_version_from_maven_path(jar_path),
-            jar_path: jar_key,
-            content_hash,
-            content,
-            cache_hit: false,
-            source: content_source.as_str().to_string(),
-            lookup_source: miss_source.clone(),
-            structure: None,
-        });
+        names
     }
 
-    Ok(FindResult {
-        class_name: resolved_class_name,
-        scanned_root: scan_root.to_string_lossy().to_string(),
-        matched_jars: matched.len(),
-        duration_ms: start.elapsed().as_millis() as u64,
-        versions,
-    })
-}
-
-fn backfill_find_cache(
-    cache: &PersistentCache,
-    registry: &ClassRegistry,
-    cfr: &Cfr,
-    result: &FindResult,
-) {
-    let mut target_jars = Vec::new();
-    let mut seen = HashSet::new();
-
-    for version in &result.versions {
-        if version.cache_hit {
-            continue;
-        }
-        if seen.insert(version.jar_path.clone()) {
-            target_jars.push(PathBuf::from(&version.jar_path));
-        }
-    }
-
-    if target_jars.is_empty() {
-        return;
-    }
-
-    let mut buffer = WriteBuffer::new(
-        cache.db(),
-        BufferConfig::default(),
-        cache.pending_gauge_path(),
-    );
-    let hotspot = HotspotTracker::new(cache.db(), 2);
-
-    for jar_path in target_jars {
-        eprintln!(
-            "[class-finder] find backfill enqueue jar: {}",
-            jar_path.display()
-        );
-        match load_jar(cache, registry, &buffer, cfr, &jar_path) {
-            Ok(output) => {
-                if !output.skipped {
-                    if let Err(err) = cache.mark_jar_loaded(&output.jar_path) {
-                        eprintln!(
-                            "[class-finder] find backfill mark loaded failed: {} ({err})",
-                            output.jar_path
-                        );
-                    }
-                    let _ = hotspot.mark_warmed(&output.jar_path, output.classes_loaded as u32);
-                }
-            }
-            Err(err) => eprintln!(
-                "[class-finder] find backfill failed for {}: {err}",
-                jar_path.display()
-            ),
+    /// `--name`, `--name=value`, `-s` or `-svalue`.
+    fn matches(&self, arg: &str) -> bool {
+        if let Some(long) = arg.strip_prefix("--") {
+            let name = long.split_once('=').map_or(long, |(name, _)| name);
+            return self.long.iter().any(|l| l == name);
         }
-    }
-
-    if let Err(err) = buffer.shutdown_and_flush() {
-        eprintln!("[class-finder] find backfill flush failed: {err}");
+        arg.strip_prefix('-')
+            .and_then(|short| short.chars().next())
+            .is_some_and(|c| self.short.contains(&c))
     }
 }
 
-fn load_jar(
-    cache: &PersistentCache,
-    registry: &ClassRegistry,
-    buffer: &WriteBuffer,
-    cfr: &Cfr,
-    jar_path: &Path,
-) -> Result<LoadResult> {
-    let jar_key = jar_path.to_string_lossy().to_string();
-    let start = Instant::now();
-
-    let cataloged_classes = catalog::catalog(jar_path).unwrap_or_default();
-    if !registry.is_cataloged(&jar_key).unwrap_or(false) && !cataloged_classes.is_empty() {
-        let _ = registry.update_registry_and_mark_cataloged(&jar_key, &cataloged_classes);
+fn normalize_class_name(raw: &str) -> String {
+    let mut s = raw.trim();
+    if let Some(rest) = s.strip_prefix("import") {
+        s = rest.trim();
     }
-
-    if cache.is_jar_loaded(&jar_key)? {
-        return Ok(LoadResult {
-            jar_path: jar_key,
-            classes_loaded: 0,
-            skipped: true,
-            duration_ms: 0,
-        });
+    if s.ends_with(';') {
+        s = s.trim_end_matches(';').trim();
     }
+    s.chars().filter(|c| !c.is_whitespace()).collect()
+}
 
-    let mut cached_classes = HashSet::new();
-    let mut classes_loaded = 0usize;
-
-    for cls in source::read_jar_sources(jar_path).unwrap_or_default() {
-        cached_classes.insert(cls.class_name.clone());
-        let key = format!("{}::{jar_key}", cls.class_name);
-        let _ = buffer.enqueue(PendingWrite {
-            key,
-            content: cls.content,
-            source: ClassContentSource::SourcesJar,
-        });
-        classes_loaded += 1;
+fn report_warmup_progress(schema: Schema, format: ProgressFormat, progress: &WarmupProgress<'_>) {
+    if format == ProgressFormat::Jsonl {
+        if let Ok(line) = schema.to_json_line(progress) {
+            println!("{line}");
+        }
+        return;
     }
-
-    let needs_decompile = cached_classes.is_empty()
-        || cataloged_classes
-            .iter()
-            .any(|class_name| !cached_classes.contains(class_name));
-
-    if needs_decompile {
-        let decompiled = cfr.decompile_jar(jar_path)?;
-        let classes = parse_decompiled_output(&decompiled);
-
-        for cls in classes {
-            if cached_classes.contains(&cls.class_name) {
-                continue;
-            }
-            cached_classes.insert(cls.class_name.clone());
-            let key = format!("{}::{jar_key}", cls.class_name);
-            let _ = buffer.enqueue(PendingWrite {
-                key,
-                content: cls.content,
-                source: ClassContentSource::Decompiled,
-            });
-            classes_loaded += 1;
+    match progress {
+        WarmupProgress::Jar {
+            index,
+            total,
+            jar_path,
+            ok,
+            classes_loaded,
+            skipped,
+            duration_ms,
+            error,
+        } => {
+            let status = match (ok, skipped) {
+                (false, _) => format!("failed: {}", error.as_deref().unwrap_or_default()),
+                (true, true) => "already loaded".to_string(),
+                (true, false) => format!("{classes_loaded} classes"),
+            };
+            tracing::info!("warmup {index}/{total} {jar_path} ({status}, {duration_ms}ms)");
         }
+        WarmupProgress::Summary {
+            completed,
+            remaining,
+            succeeded,
+            failed,
+            elapsed_ms,
+        } => tracing::info!(
+            "warmup progress: {completed} done ({succeeded} ok, {failed} failed), {remaining} remaining, {}s elapsed",
+            elapsed_ms / 1000
+        ),
     }
-
-    Ok(LoadResult {
-        jar_path: jar_key,
-        classes_loaded,
-        skipped: false,
-        duration_ms: start.elapsed().as_millis() as u64,
-    })
 }
 
-struct WarmupDeps<'a> {
-    cache: &'a PersistentCache,
-    registry: &'a ClassRegistry,
-    hotspot: &'a HotspotTracker,
-    buffer: &'a WriteBuffer,
-    cfr: &'a Cfr,
-    m2_repo: &'a Path,
+#[derive(Serialize)]
+struct PartialFind<'a> {
+    class_name: &'a str,
+    scanned_root: &'a Path,
+    matched_jars: &'a [PathBuf],
 }
 
-struct WarmupParams<'a> {
-    jar_path: Option<&'a Path>,
-    hot: bool,
-    group: Option<&'a str>,
-    top: usize,
-    limit: Option<usize>,
+/// Entries removed per table by a selective `clear`.
+#[derive(Debug, Serialize)]
+struct ClearOutput {
+    cleared: BTreeMap<&'static str, u64>,
 }
 
-fn warmup_targets(deps: &WarmupDeps<'_>, params: WarmupParams<'_>) -> Result<WarmupResult> {
-    let start = Instant::now();
-    let mut targets: Vec<PathBuf> = if params.hot {
-        deps.hotspot
-            .top_unwarmed_jars(params.top)?
-            .into_iter()
-            .map(PathBuf::from)
-            .collect()
-    } else if let Some(group) = params.group {
-        let dir = deps.m2_repo.join(group.replace('.', "/"));
-        if dir.exists() {
-            scan_jars(&dir)?
-        } else {
-            Vec::new()
-        }
-    } else if let Some(jar_path) = params.jar_path {
-        vec![jar_path.to_path_buf()]
-    } else {
-        anyhow::bail!("warmup requires jar_path, or use --hot / --group");
-    };
-
-    if let Some(limit) = params.limit {
-        targets.truncate(limit);
-    }
-
-    let mut loads = Vec::new();
-    let mut loaded_jars: Vec<(String, u32)> = Vec::new();
-    let mut succeeded = 0usize;
-    let mut failed = 0usize;
-
-    for jar in targets.iter() {
-        match load_jar(deps.cache, deps.registry, deps.buffer, deps.cfr, jar) {
-            Ok(load) => {
-                succeeded += 1;
-                if !load.skipped {
-                    loaded_jars.push((load.jar_path.clone(), load.classes_loaded as u32));
-                }
-                loads.push(load);
-            }
-            Err(_) => {
-                failed += 1;
-            }
-        }
-    }
-
-    Ok(WarmupResult {
-        targets: targets.len(),
-        succeeded,
-        failed,
-        duration_ms: start.elapsed().as_millis() as u64,
-        loads,
-        loaded_jars,
-    })
+/// One line of `watch` output: the result of a catch-up scan, a debounced
+/// burst of filesystem events, or a polling pass.
+#[derive(serde::Serialize)]
+struct WatchUpdate<'a> {
+    mode: WatchMode,
+    #[serde(flatten)]
+    result: &'a IncrementalIndexResult,
 }
 
-fn index_repo(registry: &ClassRegistry, root: PathBuf) -> Result<IndexResult> {
-    let start = Instant::now();
-    let jars = scan_jars(&root)?;
-    let mut cataloged_jars_new = 0usize;
-    let mut indexed_classes = 0usize;
-    let mut failed_jars = 0usize;
-
-    for jar_path in jars.iter() {
-        let jar_key = jar_path.to_string_lossy().to_string();
-        if registry.is_cataloged(&jar_key).unwrap_or(false) {
-            continue;
-        }
-
-        match catalog::catalog(jar_path) {
-            Ok(classes) => {
-                indexed_classes += classes.len();
-                let _ = registry.update_registry_and_mark_cataloged(&jar_key, &classes);
-                cataloged_jars_new += 1;
-            }
-            Err(_) => {
-                failed_jars += 1;
-            }
-        }
+/// Sets `javadoc` on the versions whose content is Java source with a
+/// Javadoc comment on the class; decompiler output rarely has one.
+fn attach_javadoc(result: &mut FindResult) {
+    let simple_name = result
+        .class_name
+        .rsplit('.')
+        .next()
+        .unwrap_or(&result.class_name)
+        .to_string();
+    for version in result.versions.iter_mut().filter(|v| !v.fallback) {
+        version.javadoc = parse_compilation_unit(&version.content)
+            .and_then(|unit| unit.select(&simple_name).primary.javadoc);
     }
-
-    Ok(IndexResult {
-        root: root.to_string_lossy().to_string(),
-        scanned_jars: jars.len(),
-        cataloged_jars_new,
-        indexed_classes,
-        duration_ms: start.elapsed().as_millis() as u64,
-        failed_jars,
-    })
 }
 
 fn write_find_output(
+    cache: &PersistentCache,
     result: &FindResult,
     format: OutputFormat,
     output: Option<&Path>,
+    with_javadoc: bool,
+    color: bool,
+    schema: Schema,
 ) -> Result<()> {
     let content = match format {
-        OutputFormat::Json => serde_json::to_string_pretty(result)?,
+        OutputFormat::Json => schema.to_json(result)?,
         OutputFormat::Text => {
             let mut out = String::new();
             out.push_str(&format!("class_name: {}\n", result.class_name));
             out.push_str(&format!("matched_jars: {}\n", result.matched_jars));
             out.push_str(&format!("duration_ms: {}\n", result.duration_ms));
             for v in &result.versions {
+                let version = format!("{:?}", v.version);
+                let cache_hit = v.cache_hit.to_string();
+                let (version, cache_hit) = if color {
+                    let hit_style = if v.cache_hit {
+                        Style::Good
+                    } else {
+                        Style::Notice
+                    };
+                    (
+                        paint(Style::Emphasis, &version),
+                        paint(hit_style, &cache_hit),
+                    )
+                } else {
+                    (version, cache_hit)
+                };
                 out.push_str(&format!(
-                    "- version: {:?}, source: {}, lookup_source: {}, cache_hit: {}, jar: {}\n",
-                    v.version, v.source, v.lookup_source, v.cache_hit, v.jar_path
+                    "- version: {version}, source: {}, lookup_source: {}, cache_hit: {cache_hit}, jar: {}\n",
+                    v.source, v.lookup_source, v.jar_path
                 ));
             }
             out
         }
         OutputFormat::Code => {
             let chosen = choose_default_version(&result.versions)?;
-            chosen.content.clone()
+            if color {
+                highlight_java(&chosen.content)
+            } else {
+                chosen.content.clone()
+            }
         }
         OutputFormat::Structure => {
             #[derive(Serialize)]
@@ -712,8 +878,19 @@ fn write_find_output(
                 version: &'a Option<String>,
                 jar_path: &'a str,
                 structure_source: String,
+                /// Whether the structure was parsed from the version's
+                /// `content`, which Code format prints, so its line and byte
+                /// ranges index into it. False when a sources jar was read
+                /// instead.
+                content_matches: bool,
+                /// Whether the structure came from the structure cache rather
+                /// than a fresh parse.
+                structure_cached: bool,
                 #[serde(skip_serializing_if = "Option::is_none")]
                 structure: Option<ClassStructure>,
+                /// Other top-level types declared in the same source.
+                #[serde(skip_serializing_if = "Vec::is_empty")]
+                other_types: Vec<ClassStructure>,
             }
             #[derive(Serialize)]
             struct StructureOutput<'a> {
@@ -728,11 +905,25 @@ fn write_find_output(
                 .map(|v| {
                     let (structure_content, structure_source) =
                         preferred_structure_content(v, &result.class_name);
+                    let (unit, structure_cached) =
+                        cached_compilation_unit(cache, &structure_content, &result.class_name);
+                    let (structure, other_types) = match unit {
+                        Some(mut unit) => {
+                            if !with_javadoc {
+                                unit.strip_javadoc();
+                            }
+                            (Some(unit.primary), unit.others)
+                        }
+                        None => (None, Vec::new()),
+                    };
                     StructureVersion {
                         version: &v.version,
                         jar_path: &v.jar_path,
                         structure_source,
-                        structure: parse_class_structure(&structure_content),
+                        content_matches: structure_content == v.content,
+                        structure_cached,
+                        structure,
+                        other_types,
                     }
                 })
                 .collect();
@@ -742,10 +933,73 @@ fn write_find_output(
                 duration_ms: result.duration_ms,
                 versions,
             };
-            serde_json::to_string_pretty(&out)?
+            schema.to_json(&out)?
+        }
+        // Answered from the matched jars before anything is decompiled.
+        OutputFormat::Paths => unreachable!("--format paths is written by the matching phase"),
+        OutputFormat::Stub => {
+            let mut stubs = Vec::new();
+            for v in &result.versions {
+                let (content, _) = preferred_structure_content(v, &result.class_name);
+                let Some(mut unit) = cached_compilation_unit(cache, &content, &result.class_name).0
+                else {
+                    continue;
+                };
+                if !with_javadoc {
+                    unit.strip_javadoc();
+                }
+                let stub = render_compilation_unit_stub(&unit);
+                if result.versions.len() > 1 {
+                    stubs.push(format!("// {}\n{stub}", v.jar_path));
+                } else {
+                    stubs.push(stub);
+                }
+            }
+            if stubs.is_empty() {
+                anyhow::bail!("Failed to parse the structure of {}", result.class_name);
+            }
+            stubs.join("\n")
+        }
+        OutputFormat::Constants => {
+            #[derive(Serialize)]
+            struct ConstantsVersion<'a> {
+                version: &'a Option<String>,
+                jar_path: &'a str,
+                constants: BTreeMap<String, ConstantValue>,
+            }
+            #[derive(Serialize)]
+            struct ConstantsOutput<'a> {
+                class_name: &'a str,
+                versions: Vec<ConstantsVersion<'a>>,
+            }
+            let versions = result
+                .versions
+                .iter()
+                .map(|v| {
+                    let (content, _) = preferred_structure_content(v, &result.class_name);
+                    let (unit, _) = cached_compilation_unit(cache, &content, &result.class_name);
+                    ConstantsVersion {
+                        version: &v.version,
+                        jar_path: &v.jar_path,
+                        constants: unit
+                            .map(|unit| unit.primary.all_constant_values())
+                            .unwrap_or_default(),
+                    }
+                })
+                .collect();
+            let out = ConstantsOutput {
+                class_name: &result.class_name,
+                versions,
+            };
+            schema.to_json(&out)?
         }
     };
 
+    write_output(&content, output)
+}
+
+/// Writes `content` to `output`, creating its parent directory, or prints it.
+fn write_output(content: &str, output: Option<&Path>) -> Result<()> {
     if let Some(path) = output {
         if let Some(parent) = path.parent()
             && !parent.as_os_str().is_empty()
@@ -763,7 +1017,145 @@ fn write_find_output(
     Ok(())
 }
 
+#[derive(Debug, Serialize)]
+struct MethodSearchResult {
+    method: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group: Option<String>,
+    #[serde(flatten)]
+    scan: StructureScan,
+    duration_ms: u64,
+    matches: Vec<MethodMatch>,
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+struct MethodMatch {
+    class_name: String,
+    jar_path: String,
+    version: Option<String>,
+    signature: String,
+}
+
+/// Classes with a method matching `query` among the cached structures. With
+/// `group`, only jars of that group are searched, and cached sources whose
+/// structure was never parsed are parsed on the way.
+fn find_methods(
+    cache: &PersistentCache,
+    decompiler: DecompilerKind,
+    m2_repo: &Path,
+    query: &str,
+    group: Option<&str>,
+) -> Result<MethodSearchResult> {
+    let start = Instant::now();
+    let method_query = MethodQuery::parse(query)?;
+    let group_dir = group.map(|group| m2_repo.join(group.replace('.', "/")));
+    let in_scope = |jar_key: &str| {
+        group_dir
+            .as_deref()
+            .is_none_or(|dir| Path::new(jar_key).starts_with(dir))
+    };
+
+    let mut matches = Vec::new();
+    let scan = cache.for_each_compilation_unit(
+        decompiler,
+        in_scope,
+        group.is_some(),
+        |_, jar_key, unit| {
+            let found = unit
+                .types()
+                .flat_map(|structure| method_query.find_in(structure));
+            for (owner, method) in found {
+                let class_name = if owner.package.is_empty() {
+                    owner.name.clone()
+                } else {
+                    format!("{}.{}", owner.package, owner.name)
+                };
+                matches.push(MethodMatch {
+                    class_name,
+                    jar_path: jar_key.to_string(),
+                    version: extract_version_from_maven_path(Path::new(jar_key)),
+                    signature: method.signature.clone(),
+                });
+            }
+        },
+    )?;
+    matches.sort();
+    matches.dedup();
+
+    Ok(MethodSearchResult {
+        method: query.to_string(),
+        group: group.map(str::to_string),
+        scan,
+        duration_ms: start.elapsed().as_millis() as u64,
+        matches,
+    })
+}
+
+#[derive(Debug, Serialize)]
+struct DiffOutput {
+    class_name: String,
+    from: DiffSide,
+    to: DiffSide,
+    diff: StructureDiff,
+}
+
+#[derive(Debug, Serialize)]
+struct DiffSide {
+    version: String,
+    jar_path: String,
+    structure_source: String,
+}
+
+/// Finds `version` of the class and parses its structure, preferring a
+/// sources jar like `--format structure`.
+fn diff_side(
+    finder: &ClassFinder,
+    class_name: &str,
+    version: &str,
+) -> Result<(DiffSide, ClassStructure)> {
+    let result = finder.find(class_name, &FindOptions::new().with_version(Some(version)))?;
+    let found = result
+        .versions
+        .iter()
+        .find(|v| v.version.as_deref() == Some(version))
+        .with_context(|| format!("Version {version} of {class_name} not found"))?;
+    let (content, structure_source) = preferred_structure_content(found, class_name);
+    let (unit, _) = cached_compilation_unit(finder.cache(), &content, class_name);
+    let structure = unit
+        .map(|unit| unit.primary)
+        .with_context(|| format!("Failed to parse the structure of {class_name} {version}"))?;
+    let side = DiffSide {
+        version: version.to_string(),
+        jar_path: found.jar_path.clone(),
+        structure_source,
+    };
+    Ok((side, structure))
+}
+
+/// Structures of `content`, read from the structure cache when it was parsed
+/// before and stored there otherwise, with the type of `class_name` as the
+/// primary one. The flag tells whether it was a hit.
+fn cached_compilation_unit(
+    cache: &PersistentCache,
+    content: &str,
+    class_name: &str,
+) -> (Option<CompilationUnit>, bool) {
+    let simple_name = class_name.rsplit('.').next().unwrap_or(class_name);
+    let content_hash = hash_content(content);
+    if let Ok(Some(unit)) = cache.get_compilation_unit(&content_hash) {
+        return (Some(unit.select(simple_name)), true);
+    }
+    let unit = parse_compilation_unit(content);
+    if let Some(unit) = &unit {
+        let _ = cache.put_compilation_unit(&content_hash, unit);
+    }
+    (unit.map(|unit| unit.select(simple_name)), false)
+}
+
 fn preferred_structure_content(version: &FindVersion, class_name: &str) -> (String, String) {
+    if version.source == ClassContentSource::SourcesJar.as_str() {
+        return (version.content.clone(), version.source.clone());
+    }
     let jar_path = Path::new(&version.jar_path);
     if let Ok(Some(content)) = source::read_class_source(jar_path, class_name) {
         return (content, ClassContentSource::SourcesJar.as_str().to_string());
@@ -820,38 +1212,120 @@ mod tests {
         );
     }
 
+    fn rewrite(args: &[&str]) -> Vec<String> {
+        rewrite_args_for_implicit_find(
+            std::iter::once("class-finder")
+                .chain(args.iter().copied())
+                .map(String::from)
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn rewrite_args_for_implicit_find_accepts_find_flags_before_the_class() {
+        assert_eq!(
+            rewrite(&["-f", "code", "org.example.Foo"]),
+            ["class-finder", "find", "-f", "code", "org.example.Foo"]
+        );
+        assert_eq!(
+            rewrite(&[
+                "--db",
+                "/tmp/cf.lmdb",
+                "--version",
+                "1.2",
+                "org.example.Foo"
+            ]),
+            [
+                "class-finder",
+                "--db",
+                "/tmp/cf.lmdb",
+                "find",
+                "--version",
+                "1.2",
+                "org.example.Foo"
+            ]
+        );
+        assert_eq!(
+            rewrite(&["--code-only", "org.example.Foo"]),
+            ["class-finder", "find", "--code-only", "org.example.Foo"]
+        );
+        assert_eq!(
+            rewrite(&["--format=code", "org.example.Foo"]),
+            ["class-finder", "find", "--format=code", "org.example.Foo"]
+        );
+        // `-v` before the subcommand stays the global verbosity flag.
+        assert_eq!(
+            rewrite(&["-v", "org.example.Foo"]),
+            ["class-finder", "-v", "find", "org.example.Foo"]
+        );
+        for args in [
+            ["-f", "code", "org.example.Foo"],
+            ["--version", "1.2", "org.example.Foo"],
+        ] {
+            let cli = Cli::try_parse_from(rewrite(&args)).expect("parses");
+            assert!(matches!(cli.command, Commands::Find { .. }));
+        }
+    }
+
     #[test]
     fn choose_default_version_prefers_latest_entry_with_version() {
         let versions = vec![
             FindVersion {
                 version: None,
                 jar_path: "a.jar".to_string(),
+                repository: None,
                 content_hash: "h1".to_string(),
                 content: "A".to_string(),
                 cache_hit: true,
                 source: "decompiled".to_string(),
                 lookup_source: "cache".to_string(),
+                fallback: false,
+                decompiler_options: Vec::new(),
+                decompiler_version: None,
+                decompile_ms: None,
+                encoding: None,
+                postprocessed: false,
+                warning: None,
                 structure: None,
+                javadoc: None,
             },
             FindVersion {
                 version: Some("1.0.0".to_string()),
                 jar_path: "b.jar".to_string(),
+                repository: None,
                 content_hash: "h2".to_string(),
                 content: "B".to_string(),
                 cache_hit: false,
                 source: "decompiled".to_string(),
                 lookup_source: "scan".to_string(),
+                fallback: false,
+                decompiler_options: Vec::new(),
+                decompiler_version: None,
+                decompile_ms: None,
+                encoding: None,
+                postprocessed: false,
+                warning: None,
                 structure: None,
+                javadoc: None,
             },
             FindVersion {
                 version: Some("1.1.0".to_string()),
                 jar_path: "c.jar".to_string(),
+                repository: None,
                 content_hash: "h3".to_string(),
                 content: "C".to_string(),
                 cache_hit: false,
                 source: "decompiled".to_string(),
                 lookup_source: "registry".to_string(),
+                fallback: false,
+                decompiler_options: Vec::new(),
+                decompiler_version: None,
+                decompile_ms: None,
+                encoding: None,
+                postprocessed: false,
+                warning: None,
                 structure: None,
+                javadoc: None,
             },
         ];
 
@@ -896,12 +1370,21 @@ mod tests {
         let version = FindVersion {
             version: Some("1.0".to_string()),
             jar_path: jar.to_string_lossy().to_string(),
+            repository: None,
             content_hash: "cached".to_string(),
             content: "package org.example; public class A {}".to_string(),
             cache_hit: true,
             source: ClassContentSource::Decompiled.as_str().to_string(),
             lookup_source: "registry".to_string(),
+            fallback: false,
+            decompiler_options: Vec::new(),
+            decompiler_version: None,
+            decompile_ms: None,
+            encoding: None,
+            postprocessed: false,
+            warning: None,
             structure: None,
+            javadoc: None,
         };
 
         let (content, source) = preferred_structure_content(&version, "org.example.A");
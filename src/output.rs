@@ -0,0 +1,204 @@
+//! JSON rendering shared by every command.
+//!
+//! Each JSON object a command prints carries a top-level `schema_version`,
+//! [`SCHEMA_VERSION`], which is bumped whenever the shape of an output
+//! changes. `--compat N` renders the shape of an older version instead, for
+//! the versions from [`OLDEST_COMPAT_VERSION`] on; version 1 is the shape
+//! before the field existed. Commands that print a bare list (`hot`) have no
+//! object to stamp and are left as they are.
+//!
+//! Failures render through [`Schema::error_json`]: since version 3 as
+//! `{"error": {"code", "message", "details"}}`, before that with the code
+//! and context fields at the top level.
+
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::error::ClassFinderError;
+
+/// Version of the JSON shapes this build prints by default.
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// Oldest version `--compat` can still render.
+pub const OLDEST_COMPAT_VERSION: u32 = 1;
+
+/// Which schema version outputs are rendered in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Schema {
+    version: u32,
+}
+
+impl Default for Schema {
+    fn default() -> Self {
+        Self {
+            version: SCHEMA_VERSION,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Versioned<'a, T: ?Sized> {
+    schema_version: u32,
+    #[serde(flatten)]
+    body: &'a T,
+}
+
+#[derive(Serialize)]
+struct ErrorReport {
+    error: ErrorBody,
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    code: &'static str,
+    message: String,
+    details: Map<String, Value>,
+}
+
+/// The error shape of version 2: code and context fields at the top level.
+#[derive(Serialize)]
+struct FlatErrorReport<'a> {
+    #[serde(flatten)]
+    error: &'a ClassFinderError,
+    message: String,
+}
+
+impl Schema {
+    /// The current version, or `compat` when it is one this build renders.
+    pub fn resolve(compat: Option<u32>) -> Result<Self> {
+        let Some(version) = compat else {
+            return Ok(Self::default());
+        };
+        if !(OLDEST_COMPAT_VERSION..=SCHEMA_VERSION).contains(&version) {
+            return Err(ClassFinderError::invalid_input(format!(
+                "--compat {version} is not supported; this version renders schema versions {OLDEST_COMPAT_VERSION} to {SCHEMA_VERSION}"
+            ))
+            .into());
+        }
+        Ok(Self { version })
+    }
+
+    pub fn version(self) -> u32 {
+        self.version
+    }
+
+    /// `value`, which must serialize as an object, pretty-printed and stamped.
+    pub fn to_json<T: Serialize + ?Sized>(self, value: &T) -> Result<String> {
+        Ok(if self.version >= 2 {
+            serde_json::to_string_pretty(&self.versioned(value))?
+        } else {
+            serde_json::to_string_pretty(value)?
+        })
+    }
+
+    /// Like [`Self::to_json`] on a single line, for JSON Lines output.
+    pub fn to_json_line<T: Serialize + ?Sized>(self, value: &T) -> Result<String> {
+        Ok(if self.version >= 2 {
+            serde_json::to_string(&self.versioned(value))?
+        } else {
+            serde_json::to_string(value)?
+        })
+    }
+
+    /// A failure as JSON, with what the command had found before it failed,
+    /// if anything, as `details.partial`.
+    pub fn error_json(self, error: &ClassFinderError, partial: Option<&Value>) -> Result<String> {
+        let message = error.to_string();
+        if self.version >= 3 {
+            let mut details = match serde_json::to_value(error)? {
+                Value::Object(fields) => fields,
+                _ => Map::new(),
+            };
+            details.remove("error");
+            if let Some(partial) = partial {
+                details.insert("partial".to_string(), partial.clone());
+            }
+            return self.to_json(&ErrorReport {
+                error: ErrorBody {
+                    code: error.code(),
+                    message,
+                    details,
+                },
+            });
+        }
+        self.to_json(&FlatErrorReport { error, message })
+    }
+
+    fn versioned<T: Serialize + ?Sized>(self, body: &T) -> Versioned<'_, T> {
+        Versioned {
+            schema_version: self.version,
+            body,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Sample {
+        name: &'static str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        note: Option<&'static str>,
+        count: u64,
+    }
+
+    #[test]
+    fn current_schema_stamps_the_version_first() -> Result<()> {
+        let sample = Sample {
+            name: "a",
+            note: None,
+            count: 2,
+        };
+        assert_eq!(
+            Schema::default().to_json_line(&sample)?,
+            format!(r#"{{"schema_version":{SCHEMA_VERSION},"name":"a","count":2}}"#)
+        );
+        assert_eq!(
+            Schema::resolve(Some(1))?.to_json_line(&sample)?,
+            r#"{"name":"a","count":2}"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn errors_nest_under_error_since_version_3() -> Result<()> {
+        let error = ClassFinderError::NotFound {
+            class_name: "a.B".to_string(),
+            searched: "/m2".to_string(),
+        };
+        let partial = serde_json::json!({"matched_jars": []});
+        let current: Value =
+            serde_json::from_str(&Schema::default().error_json(&error, Some(&partial))?)?;
+        assert_eq!(
+            current,
+            serde_json::json!({
+                "schema_version": SCHEMA_VERSION,
+                "error": {
+                    "code": "not_found",
+                    "message": "Class a.B not found (scan dir: /m2)",
+                    "details": {"class_name": "a.B", "searched": "/m2", "partial": partial},
+                },
+            })
+        );
+        let flat: Value =
+            serde_json::from_str(&Schema::resolve(Some(2))?.error_json(&error, None)?)?;
+        assert_eq!(flat["error"], "not_found");
+        assert_eq!(flat["class_name"], "a.B");
+        assert_eq!(flat["schema_version"], 2);
+        Ok(())
+    }
+
+    #[test]
+    fn compat_is_limited_to_the_supported_versions() {
+        assert_eq!(Schema::resolve(None).unwrap().version(), SCHEMA_VERSION);
+        assert_eq!(
+            Schema::resolve(Some(SCHEMA_VERSION)).unwrap(),
+            Schema::default()
+        );
+        assert!(Schema::resolve(Some(0)).is_err());
+        assert!(Schema::resolve(Some(SCHEMA_VERSION + 1)).is_err());
+    }
+}
@@ -1,28 +1,275 @@
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::collections::{HashSet, VecDeque};
+use std::io::BufRead;
+
+use crate::encoding::{FallbackEncoding, decode_output};
+use crate::structure::nested_types;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ParsedClass {
     pub class_name: String,
     pub content: String,
     pub content_hash: String,
+    /// The decompiler emitted a failure stub instead of real code.
+    #[serde(default)]
+    pub failed: bool,
+    /// Fallback encoding used because the decompiler output was not UTF-8.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+    /// How this class was separated from the rest of the decompiler output.
+    #[serde(default)]
+    pub split: SplitStrategy,
+    /// Package of the class, empty for the default package.
+    #[serde(default)]
+    pub package: String,
+    /// Name without package or enclosing types (`Inner` for `a.Outer$Inner`).
+    #[serde(default)]
+    pub simple_name: String,
+    #[serde(default)]
+    pub kind: ClassKind,
+}
+
+impl ParsedClass {
+    /// Member type, i.e. one with a `$` in its binary name.
+    pub fn is_nested(&self) -> bool {
+        let binary = self
+            .class_name
+            .strip_prefix(&self.package)
+            .unwrap_or(&self.class_name);
+        binary.contains('$')
+    }
+}
+
+/// What a source file declares. `package-info` and `module-info` carry
+/// annotations and module metadata rather than a type.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClassKind {
+    #[default]
+    Class,
+    Interface,
+    Enum,
+    Record,
+    Annotation,
+    PackageInfo,
+    ModuleInfo,
 }
 
+impl ClassKind {
+    /// `PackageInfo` or `ModuleInfo` for `<package>.package-info` and
+    /// `module-info`, `None` for any other name.
+    pub fn for_info_file(class_name: &str) -> Option<Self> {
+        match class_name.rsplit('.').next().unwrap_or(class_name) {
+            "package-info" => Some(Self::PackageInfo),
+            "module-info" => Some(Self::ModuleInfo),
+            _ => None,
+        }
+    }
+
+    pub fn is_type(&self) -> bool {
+        !matches!(self, Self::PackageInfo | Self::ModuleInfo)
+    }
+
+    fn from_keyword(keyword: &str) -> Self {
+        match keyword {
+            "interface" => Self::Interface,
+            "enum" => Self::Enum,
+            "record" => Self::Record,
+            "@interface" => Self::Annotation,
+            _ => Self::Class,
+        }
+    }
+}
+
+/// Splits a binary class name into its package and simple name.
+pub fn split_class_name(class_name: &str) -> (String, String) {
+    let top_level = class_name.split('$').next().unwrap_or(class_name);
+    let package = top_level
+        .rsplit_once('.')
+        .map_or("", |(package, _)| package);
+    let simple_name = class_name.rsplit(['.', '$']).next().unwrap_or(class_name);
+    (package.to_string(), simple_name.to_string())
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SplitStrategy {
+    /// Split on a decompiler file header (see `DECOMPILER_MARKERS`).
+    #[default]
+    Header,
+    /// Split on `package`/`import`/type declarations between top-level types,
+    /// for output without headers.
+    Declarations,
+    /// Read from its own file, so no splitting was needed.
+    File,
+}
+
+/// What CFR leaves in place of code it could not decompile. CFR still exits
+/// successfully, so these are the only sign the output is a stub. The thrown
+/// exception is all that remains when comments are turned off.
+const CFR_FAILURE_MARKERS: [&str; 5] = [
+    "Exception decompiling",
+    "This method has failed to decompile",
+    "unable to decompile",
+    "exceeds decompiler limits",
+    "throw new IllegalStateException(\"Decompilation failed\");",
+];
+
+/// Comment lines CFR adds about how it decompiled a class. They carry no
+/// source information; a line ending in `:` is followed by a list of names.
+const CFR_NOISE_PREFIXES: [&str; 5] = [
+    "WARNING - ",
+    "Could not load the following classes:",
+    "Enabled ",
+    "Lifted jumps to return sites",
+    "Converted monitor instructions to comments",
+];
+
+fn is_cfr_noise(text: &str) -> bool {
+    CFR_NOISE_PREFIXES
+        .iter()
+        .any(|prefix| text.starts_with(prefix))
+}
+
+/// Removes CFR's warning and bookkeeping comments (see [`CFR_NOISE_PREFIXES`])
+/// from decompiled source. Block comments left empty are dropped entirely;
+/// other comments, including file headers and failure stubs, are kept.
+pub fn strip_cfr_noise(content: &str) -> String {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let mut out = String::with_capacity(content.len());
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        let trimmed = line.trim();
+
+        if trimmed.starts_with("/*") && !trimmed.starts_with("/**") {
+            // One-line comment, possibly followed by code.
+            if let Some(close) = trimmed[2..].find("*/").map(|c| c + 2) {
+                if is_cfr_noise(trimmed[2..close].trim()) {
+                    let rest = trimmed[close + 2..].trim_start();
+                    if !rest.is_empty() {
+                        let indent = &line[..line.len() - line.trim_start().len()];
+                        out.push_str(indent);
+                        out.push_str(rest);
+                        out.push('\n');
+                    }
+                } else {
+                    out.push_str(line);
+                }
+                i += 1;
+                continue;
+            }
+            if let Some(end) = (i + 1..lines.len()).find(|&j| lines[j].trim() == "*/") {
+                push_block_without_noise(&lines[i..=end], &mut out);
+                i = end + 1;
+                continue;
+            }
+        }
+
+        if trimmed.starts_with("//") && is_cfr_noise(comment_text(trimmed)) {
+            let listing = trimmed.ends_with(':');
+            i += 1;
+            while listing
+                && i < lines.len()
+                && lines[i].trim().starts_with("//")
+                && !comment_text(lines[i].trim()).is_empty()
+            {
+                i += 1;
+            }
+            continue;
+        }
+
+        out.push_str(line);
+        i += 1;
+    }
+    out
+}
+
+/// Copies a multi-line `/* ... */` block, leaving out noise lines and the
+/// lists that follow them. Nothing is written if only blank lines remain.
+fn push_block_without_noise(block: &[&str], out: &mut String) {
+    let (open, body, close) = (block[0], &block[1..block.len() - 1], block[block.len() - 1]);
+    let mut kept: Vec<&str> = Vec::with_capacity(body.len());
+    let mut k = 0;
+    while k < body.len() {
+        let text = comment_text(body[k].trim());
+        if !is_cfr_noise(text) {
+            kept.push(body[k]);
+            k += 1;
+            continue;
+        }
+        k += 1;
+        if text.ends_with(':') {
+            while k < body.len() && !comment_text(body[k].trim()).is_empty() {
+                k += 1;
+            }
+        }
+    }
+    if kept.len() == body.len() {
+        block.iter().for_each(|line| out.push_str(line));
+        return;
+    }
+
+    while kept
+        .last()
+        .is_some_and(|line| comment_text(line.trim()).is_empty())
+    {
+        kept.pop();
+    }
+    if kept.is_empty() && comment_text(open.trim()).is_empty() {
+        return;
+    }
+    out.push_str(open);
+    kept.iter().for_each(|line| out.push_str(line));
+    out.push_str(close);
+}
+
+/// Comment text with the `/*`, `//` or leading `*` marker removed.
+fn comment_text(line: &str) -> &str {
+    line.trim_start_matches("/*")
+        .trim_start_matches("//")
+        .trim_start_matches('*')
+        .trim()
+}
+
+/// Whether decompiled source contains a decompiler failure stub.
+pub fn is_failed_decompilation(content: &str) -> bool {
+    CFR_FAILURE_MARKERS
+        .iter()
+        .any(|marker| content.contains(marker))
+}
+
+/// File headers emitted by the supported decompiler backends. Each one starts a
+/// new class in concatenated output.
+const DECOMPILER_MARKERS: [&str; 2] = [
+    "/*\n * Decompiled with CFR",
+    "/*\n * Decompiled with Vineflower",
+];
+
+/// Splits concatenated decompiler output into classes. Output with decompiler
+/// headers is split on them; otherwise file boundaries are inferred from the
+/// declarations between top-level types.
 pub fn parse_decompiled_output(content: &str) -> Vec<ParsedClass> {
     let normalized = content.replace("\r\n", "\n");
-    let marker = "/*\n * Decompiled with CFR";
-
-    let mut starts: Vec<usize> = normalized.match_indices(marker).map(|(i, _)| i).collect();
-    if starts.is_empty() {
-        if let Some(name) = extract_class_name(&normalized) {
-            let content_hash = hash_content(&normalized);
-            return vec![ParsedClass {
-                class_name: name,
-                content: normalized,
-                content_hash,
-            }];
-        }
-        return Vec::new();
+
+    let mut starts: Vec<usize> = DECOMPILER_MARKERS
+        .iter()
+        .flat_map(|marker| normalized.match_indices(marker).map(|(i, _)| i))
+        .collect();
+    let split = if starts.is_empty() {
+        starts = declaration_boundaries(&normalized);
+        SplitStrategy::Declarations
+    } else {
+        SplitStrategy::Header
+    };
+
+    if starts.len() == 1 && split == SplitStrategy::Declarations {
+        // A single file is kept untrimmed so its hash matches earlier runs.
+        return extract_class_name(&normalized)
+            .map(|name| with_inner_classes(vec![parsed_class(name, normalized, split)]))
+            .unwrap_or_default();
     }
 
     starts.sort_unstable();
@@ -36,24 +283,408 @@ pub fn parse_decompiled_output(content: &str) -> Vec<ParsedClass> {
         }
 
         if let Some(class_name) = extract_class_name(&class_content) {
-            let content_hash = hash_content(&class_content);
-            results.push(ParsedClass {
-                class_name,
-                content: class_content,
-                content_hash,
-            });
+            results.push(parsed_class(class_name, class_content, split));
+        }
+    }
+
+    with_inner_classes(results)
+}
+
+/// Parses decompiler output from `reader` one class at a time, without holding
+/// the whole output in memory. Splits the same way as
+/// [`parse_decompiled_output`], except that in output without headers a type
+/// declaration only starts a new file until the first `package` line is seen.
+pub fn parse_decompiled_stream<R: BufRead>(reader: R) -> DecompiledStream<R> {
+    DecompiledStream {
+        reader,
+        fallback: FallbackEncoding::default(),
+        strip_noise: false,
+        line: Vec::new(),
+        prev_line_start: 0,
+        chunk: String::new(),
+        chunk_encoding: None,
+        closed: None,
+        tail_encoding: None,
+        scanner: BraceScanner::default(),
+        in_type: false,
+        seen_package: false,
+        header: false,
+        chunks: 0,
+        seen: HashSet::new(),
+        pending: VecDeque::new(),
+        done: false,
+    }
+}
+
+/// Iterator returned by [`parse_decompiled_stream`]. Member types follow their
+/// outer class; names that were already yielded are skipped.
+pub struct DecompiledStream<R> {
+    reader: R,
+    fallback: FallbackEncoding,
+    strip_noise: bool,
+    line: Vec<u8>,
+    prev_line_start: usize,
+    chunk: String,
+    chunk_encoding: Option<FallbackEncoding>,
+    /// End of the last top-level type in `chunk`, with the chunk's encoding
+    /// at that point.
+    closed: Option<(usize, Option<FallbackEncoding>)>,
+    tail_encoding: Option<FallbackEncoding>,
+    scanner: BraceScanner,
+    in_type: bool,
+    seen_package: bool,
+    header: bool,
+    chunks: usize,
+    seen: HashSet<String>,
+    pending: VecDeque<ParsedClass>,
+    done: bool,
+}
+
+impl<R: BufRead> DecompiledStream<R> {
+    /// Encoding used for lines that are not valid UTF-8.
+    pub fn with_fallback_encoding(mut self, fallback: FallbackEncoding) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    /// Removes CFR warning comments from each class, see [`strip_cfr_noise`].
+    pub fn with_cfr_noise_stripped(mut self, strip: bool) -> Self {
+        self.strip_noise = strip;
+        self
+    }
+
+    fn push_line(&mut self) {
+        let decoded = decode_output(&self.line, self.fallback);
+        let line = decoded.text.as_str();
+        let trimmed = line.trim();
+
+        let after_open = self.chunk[self.prev_line_start..].trim_end_matches('\n');
+        if after_open.ends_with("/*") && is_header_line(line) {
+            let start = self.prev_line_start + after_open.len() - 2;
+            let tail = self.chunk.split_off(start);
+            if self.header {
+                let chunk = std::mem::replace(&mut self.chunk, tail);
+                self.finish_chunk(chunk, self.chunk_encoding, true);
+            } else {
+                // Anything before the first header is not part of a class.
+                self.chunk = tail;
+            }
+            self.header = true;
+            self.chunk_encoding = None;
+            self.closed = None;
+        } else if !self.header && self.scanner.at_top_level() {
+            let declares_type = is_type_declaration(trimmed);
+            if let Some((end, encoding)) = self.closed
+                && ((declares_type && !self.seen_package)
+                    || trimmed.starts_with("package ")
+                    || trimmed.starts_with("import "))
+            {
+                let tail = self.chunk.split_off(end);
+                let chunk = std::mem::replace(&mut self.chunk, tail);
+                self.finish_chunk(chunk, encoding, true);
+                self.chunk_encoding = self.tail_encoding;
+                self.closed = None;
+            }
+            self.in_type |= declares_type;
+        }
+        self.seen_package |= line.starts_with("package ");
+
+        if decoded.encoding.is_some() {
+            self.chunk_encoding = decoded.encoding;
+            self.tail_encoding = decoded.encoding;
+        }
+        let depth_before = self.scanner.depth;
+        self.scanner.scan(line);
+        self.prev_line_start = self.chunk.len();
+        self.chunk.push_str(line);
+        if self.in_type && self.scanner.depth == 0 && (depth_before > 0 || line.contains('}')) {
+            self.in_type = false;
+            self.closed = Some((self.chunk.len(), self.chunk_encoding));
+            self.tail_encoding = None;
         }
     }
 
-    results
+    fn finish_chunk(&mut self, chunk: String, encoding: Option<FallbackEncoding>, trim: bool) {
+        self.chunks += 1;
+        self.prev_line_start = 0;
+        let mut content = if self.strip_noise {
+            strip_cfr_noise(&chunk)
+        } else {
+            chunk
+        };
+        if trim {
+            content = content.trim().to_string();
+        }
+        if content.is_empty() {
+            return;
+        }
+        let Some(class_name) = extract_class_name(&content) else {
+            return;
+        };
+        let split = if self.header {
+            SplitStrategy::Header
+        } else {
+            SplitStrategy::Declarations
+        };
+        let cls = ParsedClass {
+            encoding: encoding.map(|e| e.as_str().to_string()),
+            ..parsed_class(class_name, content, split)
+        };
+        let inner = inner_classes(&cls);
+        for cls in std::iter::once(cls).chain(inner) {
+            if self.seen.insert(cls.class_name.clone()) {
+                self.pending.push_back(cls);
+            }
+        }
+    }
 }
 
+impl<R: BufRead> Iterator for DecompiledStream<R> {
+    type Item = Result<ParsedClass>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(cls) = self.pending.pop_front() {
+                return Some(Ok(cls));
+            }
+            if self.done {
+                return None;
+            }
+            self.line.clear();
+            match self.reader.read_until(b'\n', &mut self.line) {
+                Ok(0) => {
+                    self.done = true;
+                    let chunk = std::mem::take(&mut self.chunk);
+                    // Like parse_decompiled_output, a single headerless file
+                    // is kept untrimmed.
+                    let trim = self.header || self.chunks > 0;
+                    self.finish_chunk(chunk, self.chunk_encoding, trim);
+                }
+                Ok(_) => self.push_line(),
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err.into()));
+                }
+            }
+        }
+    }
+}
+
+fn is_header_line(line: &str) -> bool {
+    DECOMPILER_MARKERS
+        .iter()
+        .filter_map(|marker| marker.strip_prefix("/*\n"))
+        .any(|rest| line.starts_with(rest))
+}
+
+/// Appends the member types found inside `classes`, skipping names that the
+/// output already has an entry for.
+fn with_inner_classes(mut classes: Vec<ParsedClass>) -> Vec<ParsedClass> {
+    let mut seen: HashSet<String> = classes.iter().map(|c| c.class_name.clone()).collect();
+    let inner: Vec<ParsedClass> = classes
+        .iter()
+        .flat_map(inner_classes)
+        .filter(|cls| seen.insert(cls.class_name.clone()))
+        .collect();
+    classes.extend(inner);
+    classes
+}
+
+/// Member types declared inside `outer` (CFR and Vineflower print them inline),
+/// as entries named with their binary names (`pkg.Outer$Inner`) whose content
+/// is the nested type's own source. Local and anonymous classes are skipped.
+pub fn inner_classes(outer: &ParsedClass) -> Vec<ParsedClass> {
+    let declarations = outer
+        .content
+        .lines()
+        .filter(|line| extract_type_name_from_line(line.trim()).is_some())
+        .count();
+    if declarations < 2 {
+        return Vec::new();
+    }
+
+    nested_types(&outer.content)
+        .into_iter()
+        .map(|nested| {
+            let class_name = if outer.package.is_empty() {
+                nested.binary_name
+            } else {
+                format!("{}.{}", outer.package, nested.binary_name)
+            };
+            ParsedClass {
+                encoding: outer.encoding.clone(),
+                ..parsed_class(class_name, nested.source, outer.split)
+            }
+        })
+        .collect()
+}
+
+pub(crate) fn parsed_class(
+    class_name: String,
+    content: String,
+    split: SplitStrategy,
+) -> ParsedClass {
+    let content_hash = hash_content(&content);
+    let failed = is_failed_decompilation(&content);
+    let kind = ClassKind::for_info_file(&class_name)
+        .or_else(|| declared_type(&content).map(|(_, kind)| kind))
+        .unwrap_or_default();
+    let (package, simple_name) = split_class_name(&class_name);
+    ParsedClass {
+        package,
+        simple_name,
+        kind,
+        class_name,
+        content,
+        content_hash,
+        failed,
+        encoding: None,
+        split,
+    }
+}
+
+/// Start offsets of the files in output without decompiler headers. A file
+/// ends with the closing brace of a top-level type; the next one starts on the
+/// following line if a `package` or `import` comes before any other code, so
+/// comments and annotations in between go with it. Type declarations only
+/// start a file in default-package output, since a file may hold several
+/// top-level types.
+fn declaration_boundaries(content: &str) -> Vec<usize> {
+    let default_package = !content.lines().any(|l| l.starts_with("package "));
+    let mut starts = vec![0];
+    let mut scanner = BraceScanner::default();
+    let mut in_type = false;
+    let mut closed_at = None;
+    let mut offset = 0;
+
+    for line in content.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if scanner.at_top_level() {
+            let declares_type = is_type_declaration(trimmed);
+            if let Some(end) = closed_at
+                && ((declares_type && default_package)
+                    || trimmed.starts_with("package ")
+                    || trimmed.starts_with("import "))
+            {
+                starts.push(end);
+                closed_at = None;
+            }
+            in_type |= declares_type;
+        }
+
+        let depth_before = scanner.depth;
+        scanner.scan(line);
+        offset += line.len();
+        if in_type && scanner.depth == 0 && (depth_before > 0 || line.contains('}')) {
+            in_type = false;
+            closed_at = Some(offset);
+        }
+    }
+
+    starts
+}
+
+fn is_type_declaration(line: &str) -> bool {
+    !is_comment_line(line) && extract_type_name_from_line(line).is_some()
+}
+
+/// Tracks brace depth across lines, ignoring braces in comments and literals.
+#[derive(Default)]
+struct BraceScanner {
+    depth: usize,
+    in_block_comment: bool,
+    in_text_block: bool,
+}
+
+impl BraceScanner {
+    fn at_top_level(&self) -> bool {
+        self.depth == 0 && !self.in_block_comment && !self.in_text_block
+    }
+
+    fn scan(&mut self, line: &str) {
+        // Only ASCII bytes matter here, and UTF-8 continuation bytes never are.
+        let bytes = line.as_bytes();
+        let mut i = 0;
+        while i < bytes.len() {
+            let rest = &bytes[i..];
+            if self.in_block_comment {
+                if rest.starts_with(b"*/") {
+                    self.in_block_comment = false;
+                    i += 2;
+                } else {
+                    i += 1;
+                }
+                continue;
+            }
+            if self.in_text_block {
+                if rest[0] == b'\\' {
+                    i += 2;
+                } else if rest.starts_with(b"\"\"\"") {
+                    self.in_text_block = false;
+                    i += 3;
+                } else {
+                    i += 1;
+                }
+                continue;
+            }
+            match rest[0] {
+                b'/' if rest.get(1) == Some(&b'/') => return,
+                b'/' if rest.get(1) == Some(&b'*') => {
+                    self.in_block_comment = true;
+                    i += 2;
+                }
+                b'"' if rest.starts_with(b"\"\"\"") => {
+                    self.in_text_block = true;
+                    i += 3;
+                }
+                b'"' | b'\'' => i += literal_len(rest),
+                b'{' => {
+                    self.depth += 1;
+                    i += 1;
+                }
+                b'}' => {
+                    self.depth = self.depth.saturating_sub(1);
+                    i += 1;
+                }
+                _ => i += 1,
+            }
+        }
+    }
+}
+
+/// Length of the string or char literal at the start of `bytes`, up to the
+/// closing quote or the end of the line.
+fn literal_len(bytes: &[u8]) -> usize {
+    let quote = bytes[0];
+    let mut i = 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'\n' => return i,
+            b if b == quote => return i + 1,
+            _ => i += 1,
+        }
+    }
+    bytes.len()
+}
+
+/// Fully-qualified name of the type declared in `content`. Files without a
+/// type are named `<package>.package-info`, or `module-info` if they declare a
+/// module.
 pub fn extract_class_name(content: &str) -> Option<String> {
     let mut package: Option<String> = None;
     let mut type_name: Option<String> = None;
+    let mut declares_module = false;
+    let mut scanner = BraceScanner::default();
 
     for line in content.lines() {
+        let top_level = scanner.at_top_level();
+        scanner.scan(line);
         let line = line.trim();
+        if !top_level || is_comment_line(line) {
+            continue;
+        }
+        declares_module |= line.starts_with("module ") || line.starts_with("open module ");
 
         if package.is_none() && line.starts_with("package ") {
             let pkg = line
@@ -77,29 +708,138 @@ pub fn extract_class_name(content: &str) -> Option<String> {
         }
     }
 
-    let type_name = type_name?;
-    Some(match package {
-        Some(pkg) => format!("{pkg}.{type_name}"),
-        None => type_name,
-    })
+    match (package, type_name) {
+        (Some(pkg), Some(type_name)) => Some(format!("{pkg}.{type_name}")),
+        (None, Some(type_name)) => Some(type_name),
+        (_, None) if declares_module => Some("module-info".to_string()),
+        (Some(pkg), None) => Some(format!("{pkg}.package-info")),
+        (None, None) => None,
+    }
 }
 
+fn is_comment_line(line: &str) -> bool {
+    ["//", "/*", "*"].iter().any(|p| line.starts_with(p))
+}
+
+const TYPE_MODIFIERS: [&str; 9] = [
+    "public",
+    "protected",
+    "private",
+    "abstract",
+    "static",
+    "final",
+    "sealed",
+    "non-sealed",
+    "strictfp",
+];
+
+const TYPE_KEYWORDS: [&str; 5] = ["class", "interface", "enum", "record", "@interface"];
+
 fn extract_type_name_from_line(line: &str) -> Option<String> {
-    let keywords = ["class ", "interface ", "enum ", "record ", "@interface "];
+    type_declaration(line).map(|(name, _)| name)
+}
+
+/// Name and kind of the type declared on `line`, which must read as
+/// annotations and modifiers followed by a type keyword and the name. Comments
+/// and string literals are ignored, so `// class Foo` and `@A("class Bar")`
+/// declare nothing.
+fn type_declaration(line: &str) -> Option<(String, ClassKind)> {
+    let code = code_without_annotations(line);
+    let mut tokens = code.split_whitespace();
+    loop {
+        let token = tokens.next()?;
+        if TYPE_MODIFIERS.contains(&token) {
+            continue;
+        }
+        if !TYPE_KEYWORDS.contains(&token) {
+            return None;
+        }
+        let name: String = tokens
+            .next()?
+            .chars()
+            .take_while(|&c| c.is_alphanumeric() || c == '_' || c == '$')
+            .collect();
+        return (!name.is_empty()).then(|| (name, ClassKind::from_keyword(token)));
+    }
+}
+
+/// First type declared at brace depth zero in `content`.
+fn declared_type(content: &str) -> Option<(String, ClassKind)> {
+    let mut scanner = BraceScanner::default();
+    content.lines().find_map(|line| {
+        let top_level = scanner.at_top_level();
+        scanner.scan(line);
+        let line = line.trim();
+        (top_level && !is_comment_line(line))
+            .then(|| type_declaration(line))
+            .flatten()
+    })
+}
 
-    for kw in keywords {
-        if let Some(pos) = line.find(kw) {
-            let after = &line[pos + kw.len()..];
-            let token = after.split_whitespace().next()?;
-            let token = token.trim_end_matches('{').trim();
-            let token = token.split('<').next().unwrap_or(token);
-            if !token.is_empty() {
-                return Some(token.to_string());
+/// `line` with comments, string and char literals, and annotations (with
+/// their arguments) removed. `@interface` is kept since it declares a type.
+fn code_without_annotations(line: &str) -> String {
+    let bytes = line.as_bytes();
+    let mut code = String::with_capacity(line.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let rest = &bytes[i..];
+        match rest[0] {
+            b'/' if rest.get(1) == Some(&b'/') => break,
+            b'/' if rest.get(1) == Some(&b'*') => {
+                i += line[i + 2..].find("*/").map_or(bytes.len(), |end| end + 4);
+                code.push(' ');
+            }
+            b'"' | b'\'' => {
+                i += literal_len(rest);
+                code.push(' ');
+            }
+            b'@' if !rest.starts_with(b"@interface") => {
+                i += 1;
+                while i < bytes.len()
+                    && (bytes[i].is_ascii_alphanumeric() || matches!(bytes[i], b'_' | b'.' | b'$'))
+                {
+                    i += 1;
+                }
+                if line[i..].trim_start().starts_with('(') {
+                    i = skip_parenthesized(line, i);
+                }
+                code.push(' ');
+            }
+            _ => {
+                let ch = line[i..].chars().next().unwrap_or(' ');
+                code.push(ch);
+                i += ch.len_utf8();
             }
         }
     }
+    code
+}
 
-    None
+/// Offset just past the parenthesized group that starts at or after `start`,
+/// or the end of the line if it is not closed there.
+fn skip_parenthesized(line: &str, start: usize) -> usize {
+    let bytes = line.as_bytes();
+    let mut depth = 0usize;
+    let mut i = start;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' | b'\'' => {
+                i += literal_len(&bytes[i..]);
+                continue;
+            }
+            b'(' => depth += 1,
+            b')' => {
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    return i + 1;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    bytes.len()
 }
 
 pub fn hash_content(content: &str) -> String {
@@ -135,6 +875,482 @@ public class ArrayUtils {
         assert_eq!(parsed.len(), 2);
         assert_eq!(parsed[0].class_name, "org.apache.commons.lang3.StringUtils");
         assert_eq!(parsed[1].class_name, "org.apache.commons.lang3.ArrayUtils");
+        assert_eq!(parsed[0].split, SplitStrategy::Header);
+    }
+
+    #[test]
+    fn parse_decompiled_output_splits_vineflower_frames() {
+        let input = "/*\n * Decompiled with Vineflower.\n */\npackage a;\n\npublic class A {\n}\n/*\n * Decompiled with Vineflower.\n */\npackage a;\n\npublic interface B {\n}\n";
+
+        let parsed = parse_decompiled_output(input);
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].class_name, "a.A");
+        assert_eq!(parsed[1].class_name, "a.B");
+    }
+
+    #[test]
+    fn parse_decompiled_output_flags_failure_stubs() {
+        let input = r#"/*
+ * Decompiled with CFR 0.152.
+ */
+package a;
+
+public class Good {
+}
+/*
+ * Decompiled with CFR 0.152.
+ */
+package a;
+
+public class Broken {
+    public void run() {
+        /*
+         * This method has failed to decompile. When submitting a bug report, please provide this stack trace.
+         */
+        throw new IllegalStateException("Decompilation failed");
+    }
+}
+"#;
+
+        let parsed = parse_decompiled_output(input);
+        assert_eq!(parsed.len(), 2);
+        assert!(!parsed[0].failed);
+        assert!(parsed[1].failed);
+        assert_eq!(parsed[1].class_name, "a.Broken");
+    }
+
+    /// Vineflower-style concatenated output: no file headers, license comments
+    /// and annotations before each type, braces inside literals and comments.
+    const HEADERLESS_OUTPUT: &str = r#"package org.example.a;
+
+import java.util.List;
+
+public class First {
+    private final String open = "{ not a block";
+    private final char close = '}';
+
+    /* a } in a comment */
+    public List<String> names() {
+        return List.of();
+    }
+}
+/*
+ * Licensed under the Apache License, Version 2.0.
+ */
+package org.example.b;
+
+@Deprecated
+public interface Second {
+    // }
+    String TEMPLATE = """
+        { "unbalanced": [
+        """;
+}
+package org.example.b;
+
+public enum Third { A, B; }
+"#;
+
+    #[test]
+    fn parse_decompiled_output_splits_headerless_output_on_declarations() {
+        let parsed = parse_decompiled_output(HEADERLESS_OUTPUT);
+        let names: Vec<&str> = parsed.iter().map(|c| c.class_name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "org.example.a.First",
+                "org.example.b.Second",
+                "org.example.b.Third"
+            ]
+        );
+        assert!(
+            parsed
+                .iter()
+                .all(|c| c.split == SplitStrategy::Declarations)
+        );
+        assert!(parsed[0].content.ends_with("}"));
+        assert!(parsed[1].content.starts_with("/*\n * Licensed"));
+        assert!(parsed[1].content.contains("@Deprecated"));
+        assert!(parsed[1].content.ends_with("\"\"\";\n}"));
+        assert_eq!(
+            parsed[2].content,
+            "package org.example.b;\n\npublic enum Third { A, B; }"
+        );
+    }
+
+    #[test]
+    fn parse_decompiled_output_keeps_single_headerless_file_intact() {
+        let input =
+            "package a;\n\n@SuppressWarnings({\"x\"})\npublic class A {\n}\n\nclass Helper {\n}\n";
+        let parsed = parse_decompiled_output(input);
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].class_name, "a.A");
+        assert!(parsed[0].content.contains("class Helper"));
+
+        let default_package = "public class A {\n}\npublic class B {\n}\n";
+        let names: Vec<String> = parse_decompiled_output(default_package)
+            .into_iter()
+            .map(|c| c.class_name)
+            .collect();
+        assert_eq!(names, vec!["A", "B"]);
+
+        let single = "package a;\n\npublic class A {\n}\n";
+        let parsed = parse_decompiled_output(single);
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].content, single);
+        assert_eq!(parsed[0].split, SplitStrategy::Declarations);
+    }
+
+    #[test]
+    fn parse_decompiled_output_emits_inline_inner_classes() {
+        let input = r#"/*
+ * Decompiled with CFR 0.152.
+ */
+package a;
+
+public class Outer {
+    private final Runnable task = new Runnable() {
+        public void run() {}
+    };
+
+    public static class Inner {
+        private class Deep {
+        }
+    }
+}
+/*
+ * Decompiled with CFR 0.152.
+ */
+package a;
+
+static class Outer$Inner {
+}
+"#;
+
+        let parsed = parse_decompiled_output(input);
+        let names: Vec<&str> = parsed.iter().map(|c| c.class_name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["a.Outer", "a.Outer$Inner", "a.Outer$Inner$Deep"]
+        );
+        assert!(parsed[0].content.contains("public static class Inner {"));
+        assert!(parsed[1].content.contains("static class Outer$Inner {"));
+        assert_eq!(parsed[2].content, "private class Deep {\n        }");
+        assert_eq!(parsed[2].split, SplitStrategy::Header);
+
+        let single = "package a;\n\npublic class Outer {\n    interface Callback {}\n}\n";
+        let parsed = parse_decompiled_output(single);
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].content, single);
+        assert_eq!(parsed[1].class_name, "a.Outer$Callback");
+        assert_eq!(parsed[1].content, "interface Callback {}");
+    }
+
+    #[test]
+    fn strip_cfr_noise_removes_warning_comments_only() {
+        let input = r#"/*
+ * Decompiled with CFR 0.152.
+ * 
+ * Could not load the following classes:
+ *  org.missing.Base
+ *  org.missing.Other
+ */
+package a;
+
+import org.missing.Base;
+
+// Could not load the following classes:
+//  org.missing.Iface
+public class A
+extends Base {
+    /*
+     * WARNING - Removed try catching itself - possible behaviour change.
+     */
+    public void run() {
+        /* WARNING - void declaration */ int x = 1;
+        /* keep me */
+    }
+
+    /*
+     * Enabled aggressive block sorting
+     * Lifted jumps to return sites
+     */
+    public void other() {
+    }
+}
+"#;
+        let expected = r#"/*
+ * Decompiled with CFR 0.152.
+ */
+package a;
+
+import org.missing.Base;
+
+public class A
+extends Base {
+    public void run() {
+        int x = 1;
+        /* keep me */
+    }
+
+    public void other() {
+    }
+}
+"#;
+        let stripped = strip_cfr_noise(input);
+        assert_eq!(stripped, expected);
+        assert_eq!(strip_cfr_noise(expected), expected);
+
+        let parsed = parse_decompiled_output(&stripped);
+        assert_eq!(parsed[0].class_name, "a.A");
+        assert_eq!(parsed[0].content_hash, hash_content(&parsed[0].content));
+    }
+
+    #[test]
+    fn is_failed_decompilation_detects_stubs_without_comments() {
+        let stub = "public void big() {\n    throw new IllegalStateException(\"Decompilation failed\");\n}\n";
+        assert!(is_failed_decompilation(stub));
+        assert!(is_failed_decompilation(
+            "/*\n * The procedure exceeds decompiler limits.\n */"
+        ));
+        assert!(!is_failed_decompilation(
+            "throw new IllegalStateException(\"Decompilation failed: \" + reason);"
+        ));
+    }
+
+    #[test]
+    fn parse_decompiled_output_names_package_and_module_info() {
+        let input = r#"/*
+ * Decompiled with CFR 0.152.
+ */
+/**
+ * Utilities; see the class docs for each enum type.
+ */
+@Deprecated
+package a.b;
+/*
+ * Decompiled with CFR 0.152.
+ */
+open module a.b {
+    exports a.b;
+}
+/*
+ * Decompiled with CFR 0.152.
+ */
+package a.b;
+
+public class C {
+}
+"#;
+
+        let parsed = parse_decompiled_output(input);
+        let names: Vec<(&str, ClassKind)> = parsed
+            .iter()
+            .map(|c| (c.class_name.as_str(), c.kind))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("a.b.package-info", ClassKind::PackageInfo),
+                ("module-info", ClassKind::ModuleInfo),
+                ("a.b.C", ClassKind::Class),
+            ]
+        );
+    }
+
+    #[test]
+    fn extract_class_name_ignores_comments_and_annotation_strings() {
+        let input = r#"
+package a.b;
+// public class Foo
+/* class Baz */
+@MyAnnotation(value = "class Bar", other = 'c')
+public class Real {
+}
+"#;
+        assert_eq!(extract_class_name(input).as_deref(), Some("a.b.Real"));
+
+        let inline = "package a.b;\n@A(\"enum E\") /* interface I */ final class Real {}\n";
+        assert_eq!(extract_class_name(inline).as_deref(), Some("a.b.Real"));
+    }
+
+    #[test]
+    fn extract_class_name_understands_sealed_and_record_modifiers() {
+        let sealed = "package a;\npublic sealed interface Shape permits Circle, Square {\n}\n";
+        assert_eq!(extract_class_name(sealed).as_deref(), Some("a.Shape"));
+
+        let non_sealed = "package a;\npublic non-sealed class Square extends Shape {\n}\n";
+        assert_eq!(extract_class_name(non_sealed).as_deref(), Some("a.Square"));
+
+        let record = "package a;\npublic final record Point(int x, int y) {\n}\n";
+        assert_eq!(extract_class_name(record).as_deref(), Some("a.Point"));
+
+        let annotation = "package a;\npublic @interface Marker {\n}\n";
+        assert_eq!(extract_class_name(annotation).as_deref(), Some("a.Marker"));
+    }
+
+    #[test]
+    fn extract_class_name_prefers_top_level_declaration() {
+        let input = r#"
+package a.b;
+
+/**
+ * Holds a nested class Helper.
+ */
+@Deprecated
+public class Outer {
+    static class Helper {
+        String s = "class Fake";
+    }
+}
+"#;
+        assert_eq!(extract_class_name(input).as_deref(), Some("a.b.Outer"));
+
+        let nested_first = "package a;\n{\n  class Inner {}\n}\nclass Top {}\n";
+        assert_eq!(extract_class_name(nested_first).as_deref(), Some("a.Top"));
+    }
+
+    #[test]
+    fn parsed_class_records_package_simple_name_and_kind() {
+        let outer = parsed_class(
+            "a.b.Outer".to_string(),
+            "package a.b;\npublic enum Outer {\n    A;\n    public static record Inner(int x) {}\n}\n"
+                .to_string(),
+            SplitStrategy::File,
+        );
+        assert_eq!(outer.package, "a.b");
+        assert_eq!(outer.simple_name, "Outer");
+        assert_eq!(outer.kind, ClassKind::Enum);
+        assert!(!outer.is_nested());
+
+        let inner = inner_classes(&outer);
+        assert_eq!(inner.len(), 1);
+        assert_eq!(inner[0].class_name, "a.b.Outer$Inner");
+        assert_eq!(inner[0].package, "a.b");
+        assert_eq!(inner[0].simple_name, "Inner");
+        assert_eq!(inner[0].kind, ClassKind::Record);
+        assert!(inner[0].is_nested());
+
+        assert_eq!(
+            split_class_name("Top$Nested"),
+            (String::new(), "Nested".to_string())
+        );
+    }
+
+    #[test]
+    fn parsed_class_serde_round_trip() {
+        let cls = parsed_class(
+            "a.Marker".to_string(),
+            "package a;\npublic @interface Marker {}\n".to_string(),
+            SplitStrategy::Declarations,
+        );
+        let json = serde_json::to_string(&cls).unwrap();
+        assert!(json.contains("\"kind\":\"annotation\""));
+        let back: ParsedClass = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.class_name, "a.Marker");
+        assert_eq!(back.package, "a");
+        assert_eq!(back.simple_name, "Marker");
+        assert_eq!(back.kind, ClassKind::Annotation);
+        assert_eq!(back.split, SplitStrategy::Declarations);
+
+        let legacy: ParsedClass = serde_json::from_str(
+            r#"{"class_name":"a.B","content":"class B {}","content_hash":"x"}"#,
+        )
+        .unwrap();
+        assert_eq!(legacy.kind, ClassKind::Class);
+        assert!(legacy.package.is_empty());
+    }
+
+    // The stream yields member types right after their outer class, so
+    // entries are compared in name order.
+    fn stream_entries(input: &str) -> Vec<(String, String, SplitStrategy)> {
+        let mut entries: Vec<_> = parse_decompiled_stream(input.as_bytes())
+            .map(|cls| {
+                let cls = cls.unwrap();
+                (cls.class_name, cls.content_hash, cls.split)
+            })
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries
+    }
+
+    fn output_entries(input: &str) -> Vec<(String, String, SplitStrategy)> {
+        let mut entries: Vec<_> = parse_decompiled_output(input)
+            .into_iter()
+            .map(|cls| (cls.class_name, cls.content_hash, cls.split))
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries
+    }
+
+    #[test]
+    fn parse_decompiled_stream_matches_whole_output_parsing() {
+        let header = "junk\n/*\n * Decompiled with CFR 0.152.\n */\npackage a;\n\npublic class A {\n    static class In {}\n}\n/*\n * Decompiled with CFR 0.152.\n */\npackage a;\n\npublic interface B {\n}\n";
+        let single = "package a;\n\npublic class Only {\n}\n\n";
+        for input in [header, HEADERLESS_OUTPUT, single] {
+            assert_eq!(stream_entries(input), output_entries(input), "{input}");
+        }
+        assert_eq!(
+            stream_entries(&header.replace('\n', "\r\n")),
+            output_entries(header)
+        );
+    }
+
+    #[test]
+    fn parse_decompiled_stream_yields_before_reading_everything() {
+        struct Counting<'a> {
+            inner: &'a [u8],
+            read: std::rc::Rc<std::cell::Cell<usize>>,
+        }
+        impl std::io::Read for Counting<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let n = self.inner.read(buf)?;
+                self.read.set(self.read.get() + n);
+                Ok(n)
+            }
+        }
+
+        let body = "    public int value() {\n        return 42;\n    }\n".repeat(20);
+        let input: String = (0..4000)
+            .map(|i| {
+                format!(
+                    "/*\n * Decompiled with CFR 0.152.\n */\npackage big;\n\npublic class C{i} {{\n{body}}}\n"
+                )
+            })
+            .collect();
+        assert!(input.len() > 4 * 1024 * 1024);
+
+        let read = std::rc::Rc::new(std::cell::Cell::new(0));
+        let reader = std::io::BufReader::new(Counting {
+            inner: input.as_bytes(),
+            read: read.clone(),
+        });
+        let mut stream = parse_decompiled_stream(reader);
+        let first = stream.next().unwrap().unwrap();
+        assert_eq!(first.class_name, "big.C0");
+        assert!(read.get() < 64 * 1024, "read {} bytes", read.get());
+
+        let rest: Vec<ParsedClass> = stream.map(Result::unwrap).collect();
+        assert_eq!(rest.len(), 3999);
+        assert_eq!(rest[3998].class_name, "big.C3999");
+        assert_eq!(read.get(), input.len());
+    }
+
+    #[test]
+    fn parse_decompiled_stream_decodes_and_strips_per_class() {
+        let mut input = b"/*\n * Decompiled with CFR 0.152.\n */\npackage a;\n\npublic class A {\n    String s = \"caf\xe9\";\n}\n".to_vec();
+        input.extend_from_slice(
+            b"/*\n * Decompiled with CFR 0.152.\n */\npackage a;\n\npublic class B {\n    /*\n     * WARNING - void declaration\n     */\n    void m() {}\n}\n",
+        );
+        let parsed: Vec<ParsedClass> = parse_decompiled_stream(&input[..])
+            .with_cfr_noise_stripped(true)
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(parsed.len(), 2);
+        assert!(parsed[0].content.contains("caf\u{e9}"));
+        assert_eq!(parsed[0].encoding.as_deref(), Some("latin1"));
+        assert_eq!(parsed[1].encoding, None);
+        assert!(!parsed[1].content.contains("WARNING"));
     }
 
     #[test]
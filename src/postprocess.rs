@@ -0,0 +1,199 @@
+//! External formatter run over decompiled classes before they are hashed
+//! and cached (config key `postprocess`, e.g. `["google-java-format", "-"]`).
+//!
+//! The command reads one class on stdin and writes the replacement to
+//! stdout. A run that fails, times out or prints nothing leaves the class as
+//! the decompiler produced it; each cached class records whether it was
+//! post-processed.
+
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::decompiler::{join_pipe_reader, kill_process_tree, spawn_pipe_reader};
+use crate::parse::{ParsedClass, hash_content};
+
+pub const DEFAULT_POSTPROCESS_TIMEOUT: Duration = Duration::from_secs(10);
+
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Postprocessor {
+    /// Program and arguments; never empty.
+    command: Vec<String>,
+    timeout: Duration,
+}
+
+impl Postprocessor {
+    /// `None` for an empty command, which disables post-processing.
+    pub fn new(command: Vec<String>, timeout: Duration) -> Option<Self> {
+        (!command.is_empty()).then_some(Self { command, timeout })
+    }
+
+    pub fn command(&self) -> &[String] {
+        &self.command
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// `content` as the command rewrote it, or `None` (with a warning) when
+    /// the raw source has to be kept.
+    pub fn run(&self, class_name: &str, content: &str) -> Option<String> {
+        match self.try_run(content) {
+            Ok(out) if !out.trim().is_empty() => Some(out),
+            Ok(_) => {
+                tracing::warn!(
+                    "postprocess printed nothing for {class_name}; keeping the decompiled source"
+                );
+                None
+            }
+            Err(err) => {
+                tracing::warn!(
+                    "postprocess failed for {class_name}; keeping the decompiled source: {err:#}"
+                );
+                None
+            }
+        }
+    }
+
+    /// Rewrites `cls` in place. Returns whether the command's output was
+    /// used.
+    pub fn apply(&self, cls: &mut ParsedClass) -> bool {
+        if cls.failed {
+            return false;
+        }
+        let Some(content) = self.run(&cls.class_name, &cls.content) else {
+            return false;
+        };
+        cls.content_hash = hash_content(&content);
+        cls.content = content;
+        true
+    }
+
+    fn try_run(&self, content: &str) -> Result<String> {
+        let program = &self.command[0];
+        let mut command = Command::new(program);
+        command
+            .args(&self.command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            command.process_group(0);
+        }
+        let mut child = command
+            .spawn()
+            .with_context(|| format!("Failed to execute {program}"))?;
+        let stdout = spawn_pipe_reader(child.stdout.take());
+        let stderr = spawn_pipe_reader(child.stderr.take());
+        // Written from a thread so a command that prints before it has read
+        // all of its input cannot block on a full pipe.
+        let stdin = child.stdin.take().map(|mut stdin| {
+            let input = content.to_string();
+            std::thread::spawn(move || {
+                let _ = stdin.write_all(input.as_bytes());
+            })
+        });
+
+        let kill_at = Instant::now() + self.timeout;
+        let status = loop {
+            if let Some(status) = child
+                .try_wait()
+                .context("Failed to wait for child process")?
+            {
+                break status;
+            }
+            if Instant::now() >= kill_at {
+                kill_process_tree(&mut child);
+                let _ = child.wait();
+                anyhow::bail!("{program} timed out after {}s", self.timeout.as_secs());
+            }
+            std::thread::sleep(WAIT_POLL_INTERVAL);
+        };
+        if let Some(stdin) = stdin {
+            let _ = stdin.join();
+        }
+        let stdout = join_pipe_reader(stdout);
+        if !status.success() {
+            let stderr = join_pipe_reader(stderr);
+            anyhow::bail!(
+                "{program} exited with {status}: {}",
+                String::from_utf8_lossy(&stderr).trim()
+            );
+        }
+        String::from_utf8(stdout).with_context(|| format!("{program} printed invalid UTF-8"))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::parse::{SplitStrategy, parsed_class};
+
+    fn sh(script: &str, timeout: Duration) -> Postprocessor {
+        Postprocessor::new(
+            vec!["sh".to_string(), "-c".to_string(), script.to_string()],
+            timeout,
+        )
+        .unwrap()
+    }
+
+    fn class(content: &str) -> ParsedClass {
+        parsed_class(
+            "org.example.A".to_string(),
+            content.to_string(),
+            SplitStrategy::File,
+        )
+    }
+
+    #[test]
+    fn empty_command_disables_postprocessing() {
+        assert_eq!(Postprocessor::new(Vec::new(), Duration::from_secs(1)), None);
+    }
+
+    #[test]
+    fn output_replaces_the_class_and_its_hash() {
+        let upper = sh("tr a-z A-Z", Duration::from_secs(10));
+        let mut cls = class("class a {}\n");
+        assert!(upper.apply(&mut cls));
+        assert_eq!(cls.content, "CLASS A {}\n");
+        assert_eq!(cls.content_hash, hash_content("CLASS A {}\n"));
+    }
+
+    #[test]
+    fn failures_keep_the_raw_source() {
+        for failing in [
+            sh(
+                "cat >/dev/null; echo broken >&2; exit 3",
+                Duration::from_secs(10),
+            ),
+            sh("cat >/dev/null", Duration::from_secs(10)),
+            sh("sleep 5", Duration::from_millis(200)),
+            Postprocessor::new(
+                vec!["class-finder-no-such-formatter".to_string()],
+                Duration::from_secs(1),
+            )
+            .unwrap(),
+        ] {
+            let mut cls = class("class A {}\n");
+            let hash = cls.content_hash.clone();
+            assert!(!failing.apply(&mut cls), "{failing:?}");
+            assert_eq!(cls.content, "class A {}\n");
+            assert_eq!(cls.content_hash, hash);
+        }
+    }
+
+    #[test]
+    fn failure_stubs_are_left_alone() {
+        let upper = sh("tr a-z A-Z", Duration::from_secs(10));
+        let mut cls = class("class a {}\n");
+        cls.failed = true;
+        assert!(!upper.apply(&mut cls));
+        assert_eq!(cls.content, "class a {}\n");
+    }
+}
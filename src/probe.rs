@@ -1,38 +1,18 @@
-use anyhow::{Context, Result};
-use memmap2::Mmap;
-use std::fs::File;
-use std::io::Cursor;
+use anyhow::Result;
 use std::path::Path;
-use zip::ZipArchive;
+
+use crate::listing::jar_listings;
 
 pub fn jar_contains_class(jar_path: &Path, class_path: &str) -> Result<bool> {
-    let file = File::open(jar_path)
-        .with_context(|| format!("Failed to open jar: {}", jar_path.display()))?;
-    // SAFETY: The file is opened read-only and remains valid for the lifetime of the mmap.
-    let mmap = unsafe {
-        Mmap::map(&file).with_context(|| format!("mmap failed: {}", jar_path.display()))?
-    };
-    let mut archive = ZipArchive::new(Cursor::new(&mmap[..]))
-        .with_context(|| format!("Failed to read zip structure: {}", jar_path.display()))?;
-    Ok(archive.by_name(class_path).is_ok())
+    Ok(jar_listings().get(jar_path)?.contains(class_path))
 }
 
 pub fn find_class_fqns_in_jar(jar_path: &Path, simple_class_name: &str) -> Result<Vec<String>> {
-    let file = File::open(jar_path)
-        .with_context(|| format!("Failed to open jar: {}", jar_path.display()))?;
-    // SAFETY: The file is opened read-only and remains valid for the lifetime of the mmap.
-    let mmap = unsafe {
-        Mmap::map(&file).with_context(|| format!("mmap failed: {}", jar_path.display()))?
-    };
-    let mut archive = ZipArchive::new(Cursor::new(&mmap[..]))
-        .with_context(|| format!("Failed to read zip structure: {}", jar_path.display()))?;
-
+    let listing = jar_listings().get(jar_path)?;
     let wanted_suffix = format!("/{simple_class_name}.class");
     let mut results = Vec::new();
 
-    for i in 0..archive.len() {
-        let entry = archive.by_index(i)?;
-        let name = entry.name();
+    for name in listing.names() {
         if !name.ends_with(".class") {
             continue;
         }
@@ -0,0 +1,150 @@
+//! Progress events of long-running operations, for embedders that want to
+//! follow an index or warmup without scraping stderr.
+//!
+//! [`ClassFinder::with_progress`](crate::finder::ClassFinder::with_progress)
+//! and [`IncrementalIndexer::with_progress`](crate::incremental::IncrementalIndexer::with_progress)
+//! take an `Arc<dyn ProgressSink>`; both default to [`NoProgress`]. Warmup
+//! workers report from their own threads, so sinks are `Send + Sync` and
+//! events arrive in completion order.
+
+use serde::Serialize;
+use std::io::Write;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum ProgressEvent {
+    /// Jar discovery under `root` began.
+    ScanStarted { root: String },
+    /// A jar's class list was added to (or refreshed in) the registry.
+    JarCataloged { jar_path: String, classes: usize },
+    /// A jar's sources were cached; `classes` counts the ones written.
+    JarDecompiled {
+        jar_path: String,
+        classes: usize,
+        duration_ms: u64,
+    },
+    /// The write buffer committed everything an operation queued.
+    FlushCompleted { written: usize, duration_ms: u64 },
+    /// A warmup worker finished its `index`-th of `total` jars.
+    WarmupTaskFinished {
+        index: usize,
+        total: usize,
+        jar_path: String,
+        ok: bool,
+        classes_loaded: usize,
+        duration_ms: u64,
+    },
+}
+
+impl ProgressEvent {
+    pub(crate) fn jar_decompiled(jar_path: &str, classes: usize, duration: Duration) -> Self {
+        Self::JarDecompiled {
+            jar_path: jar_path.to_string(),
+            classes,
+            duration_ms: duration.as_millis() as u64,
+        }
+    }
+}
+
+pub trait ProgressSink: Send + Sync {
+    fn event(&self, event: ProgressEvent);
+}
+
+/// Drops every event.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoProgress;
+
+impl ProgressSink for NoProgress {
+    fn event(&self, _event: ProgressEvent) {}
+}
+
+/// Prints each event as a JSON line on stderr (`--progress`).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StderrProgress;
+
+impl ProgressSink for StderrProgress {
+    fn event(&self, event: ProgressEvent) {
+        if let Ok(line) = serde_json::to_string(&event) {
+            // One locked write per line, so lines from workers never interleave.
+            let _ = writeln!(std::io::stderr().lock(), "{line}");
+        }
+    }
+}
+
+/// Forwards events to a [`Receiver`], e.g. one a server streams to its
+/// clients. Events sent after the receiver is dropped are discarded.
+#[derive(Debug, Clone)]
+pub struct ChannelProgress {
+    tx: Sender<ProgressEvent>,
+}
+
+impl ChannelProgress {
+    pub fn new() -> (Self, Receiver<ProgressEvent>) {
+        let (tx, rx) = mpsc::channel();
+        (Self { tx }, rx)
+    }
+}
+
+impl ProgressSink for ChannelProgress {
+    fn event(&self, event: ProgressEvent) {
+        let _ = self.tx.send(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn events_serialize_with_a_kebab_case_tag() {
+        let event = ProgressEvent::jar_decompiled("/m2/a.jar", 3, Duration::from_millis(1500));
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({
+                "event": "jar-decompiled",
+                "jar_path": "/m2/a.jar",
+                "classes": 3,
+                "duration_ms": 1500,
+            })
+        );
+    }
+
+    #[test]
+    fn channel_sink_collects_events_from_many_threads() {
+        let (sink, rx) = ChannelProgress::new();
+        let sink: Arc<dyn ProgressSink> = Arc::new(sink);
+        std::thread::scope(|scope| {
+            for i in 0..4 {
+                let sink = Arc::clone(&sink);
+                scope.spawn(move || {
+                    sink.event(ProgressEvent::JarCataloged {
+                        jar_path: format!("/m2/{i}.jar"),
+                        classes: i,
+                    })
+                });
+            }
+        });
+        drop(sink);
+        let mut classes: Vec<usize> = rx
+            .iter()
+            .map(|event| match event {
+                ProgressEvent::JarCataloged { classes, .. } => classes,
+                other => panic!("unexpected {other:?}"),
+            })
+            .collect();
+        classes.sort();
+        assert_eq!(classes, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn a_dropped_receiver_does_not_fail_the_sender() {
+        let (sink, rx) = ChannelProgress::new();
+        drop(rx);
+        sink.event(ProgressEvent::ScanStarted {
+            root: "/m2".to_string(),
+        });
+    }
+}
@@ -1,9 +1,16 @@
 use anyhow::{Context, Result};
 use heed::types::Str;
 use heed::{Database, Env};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+use crate::bloom::ClassFilter;
+use crate::buffer::{Mutation, Table};
 use crate::cache::{ARTIFACT_MANIFEST_DB, CLASS_REGISTRY_DB};
+use crate::incremental::JarStamp;
+use crate::session::ReadSession;
 
 type StrDb = Database<Str, Str>;
 
@@ -23,12 +30,18 @@ impl ClassRegistry {
     }
 
     pub fn get_artifacts(&self, fqn: &str) -> Result<Vec<String>> {
-        let rtxn = self.db.read_txn()?;
-        let table = open_named_db(&self.db, &rtxn, CLASS_REGISTRY_DB)?;
-        let Some(value) = table.get(&rtxn, fqn)? else {
+        self.get_artifacts_in(&mut ReadSession::new(&self.db), fqn)
+    }
+
+    pub fn get_artifacts_in(
+        &self,
+        session: &mut ReadSession<'_>,
+        fqn: &str,
+    ) -> Result<Vec<String>> {
+        let Some(value) = session.get(CLASS_REGISTRY_DB, fqn)? else {
             return Ok(Vec::new());
         };
-        serde_json::from_str(value)
+        serde_json::from_str(&value)
             .with_context(|| format!("Failed to parse artifact list for class: {}", fqn))
     }
 
@@ -51,15 +64,7 @@ impl ClassRegistry {
             let mut updated = 0usize;
 
             for class in classes {
-                let mut paths: Vec<String> = registry
-                    .get(&wtxn, class.as_str())?
-                    .and_then(|v| serde_json::from_str::<Vec<String>>(v).ok())
-                    .unwrap_or_default();
-
-                if !paths.iter().any(|p| p == jar_key) {
-                    paths.push(jar_key.to_string());
-                    let json = serde_json::to_string(&paths)?;
-                    registry.put(&mut wtxn, class.as_str(), json.as_str())?;
+                if add_artifact(&registry, &mut wtxn, class, jar_key)? {
                     updated += 1;
                 }
             }
@@ -67,7 +72,7 @@ impl ClassRegistry {
             let manifest = self
                 .db
                 .create_database::<Str, Str>(&mut wtxn, Some(ARTIFACT_MANIFEST_DB))?;
-            manifest.put(&mut wtxn, jar_key, "1")?;
+            manifest.put(&mut wtxn, jar_key, &manifest_value(jar_key, classes))?;
 
             updated
         };
@@ -75,6 +80,148 @@ impl ClassRegistry {
         Ok(updated)
     }
 
+    /// Catalogs jars that may have been cataloged before with other classes:
+    /// each jar is dropped from the classes it no longer contains, then added
+    /// to `classes` like [`Self::update_registry_and_mark_cataloged`]. The
+    /// registry is scanned once for all `jars`, in one transaction.
+    pub fn refresh_jars(&self, jars: &[(String, Vec<String>)]) -> Result<usize> {
+        if jars.is_empty() {
+            return Ok(0);
+        }
+        let wanted: HashMap<&str, HashSet<&str>> = jars
+            .iter()
+            .map(|(jar_key, classes)| {
+                (
+                    jar_key.as_str(),
+                    classes.iter().map(String::as_str).collect(),
+                )
+            })
+            .collect();
+
+        let mut wtxn = self.db.write_txn()?;
+        let registry = self
+            .db
+            .create_database::<Str, Str>(&mut wtxn, Some(CLASS_REGISTRY_DB))?;
+        drop_stale_artifacts(&registry, &mut wtxn, &wanted)?;
+
+        let manifest = self
+            .db
+            .create_database::<Str, Str>(&mut wtxn, Some(ARTIFACT_MANIFEST_DB))?;
+        let mut updated = 0usize;
+        for (jar_key, classes) in jars {
+            for class in classes {
+                if add_artifact(&registry, &mut wtxn, class, jar_key)? {
+                    updated += 1;
+                }
+            }
+            manifest.put(&mut wtxn, jar_key, &manifest_value(jar_key, classes))?;
+        }
+        wtxn.commit()?;
+        Ok(updated)
+    }
+
+    /// Removes `jar_keys` from the registry and the manifest, for jars that
+    /// no longer exist. Returns how many classes listed one of them.
+    pub fn remove_jars(&self, jar_keys: &[String]) -> Result<usize> {
+        if jar_keys.is_empty() {
+            return Ok(0);
+        }
+        let wanted: HashMap<&str, HashSet<&str>> = jar_keys
+            .iter()
+            .map(|jar_key| (jar_key.as_str(), HashSet::new()))
+            .collect();
+        let mut wtxn = self.db.write_txn()?;
+        let registry = self
+            .db
+            .create_database::<Str, Str>(&mut wtxn, Some(CLASS_REGISTRY_DB))?;
+        let updated = drop_stale_artifacts(&registry, &mut wtxn, &wanted)?;
+        let manifest = self
+            .db
+            .create_database::<Str, Str>(&mut wtxn, Some(ARTIFACT_MANIFEST_DB))?;
+        for jar_key in jar_keys {
+            manifest.delete(&mut wtxn, jar_key)?;
+        }
+        wtxn.commit()?;
+        Ok(updated)
+    }
+
+    /// Buffered equivalent of [`Self::update_registry_and_mark_cataloged`],
+    /// to be queued with [`crate::buffer::WriteBuffer::mutate_group`].
+    pub fn catalog_mutations(jar_key: &str, classes: &[String]) -> Vec<Mutation> {
+        classes
+            .iter()
+            .map(|class| Mutation::AddArtifact {
+                class_name: class.clone(),
+                jar_key: jar_key.to_string(),
+            })
+            .chain(std::iter::once(Mutation::PutValue {
+                table: Table::ArtifactManifest,
+                key: jar_key.to_string(),
+                value: manifest_value(jar_key, classes),
+            }))
+            .collect()
+    }
+
+    /// The manifest row of a cataloged jar, when the jar on disk is still
+    /// the one it was cataloged from.
+    fn current_entry_in(
+        &self,
+        session: &mut ReadSession<'_>,
+        jar: &Path,
+    ) -> Result<Option<ManifestEntry>> {
+        let Some(value) = session.get(ARTIFACT_MANIFEST_DB, &jar.to_string_lossy())? else {
+            return Ok(None);
+        };
+        let Ok(entry) = serde_json::from_str::<ManifestEntry>(&value) else {
+            return Ok(None);
+        };
+        let current = JarStamp::read(jar).is_some_and(|stamp| stamp.encode() == entry.stamp);
+        Ok(current.then_some(entry))
+    }
+
+    /// The class filter of a cataloged jar, when the jar on disk is still the
+    /// one it was built from.
+    pub fn class_filter_in(
+        &self,
+        session: &mut ReadSession<'_>,
+        jar: &Path,
+    ) -> Result<Option<ClassFilter>> {
+        Ok(self
+            .current_entry_in(session, jar)?
+            .and_then(|entry| ClassFilter::decode(&entry.filter)))
+    }
+
+    /// Drops the `jars` whose class filter rules out `class_path`, without
+    /// opening them; jars without a usable filter are kept. Member types
+    /// (`$` in the path) are not cataloged, so they rule nothing out.
+    /// Returns the kept jars that have the size and mtime they were
+    /// cataloged with, so their registry entries can be trusted without
+    /// opening them; jars cataloged before stamps were recorded are not.
+    pub fn rule_out_in(
+        &self,
+        session: &mut ReadSession<'_>,
+        class_path: &str,
+        jars: &mut Vec<PathBuf>,
+    ) -> Result<HashSet<PathBuf>> {
+        let member_type = class_path.contains('$');
+        let mut current = HashSet::new();
+        let mut kept = Vec::with_capacity(jars.len());
+        for jar in jars.drain(..) {
+            if let Some(entry) = self.current_entry_in(session, &jar)? {
+                let ruled_out = !member_type
+                    && ClassFilter::decode(&entry.filter)
+                        .is_some_and(|filter| !filter.might_contain(class_path));
+                if ruled_out {
+                    continue;
+                }
+                current.insert(jar.clone());
+            }
+            kept.push(jar);
+        }
+        *jars = kept;
+        Ok(current)
+    }
+
     pub fn indexed_classes(&self) -> Result<u64> {
         let rtxn = self.db.read_txn()?;
         let table = open_named_db(&self.db, &rtxn, CLASS_REGISTRY_DB)?;
@@ -104,6 +251,82 @@ impl ReadOnlyClassRegistry {
     }
 }
 
+/// Manifest row of a cataloged jar: its [`JarStamp`] when cataloged and a
+/// [`ClassFilter`] over its classes. Rows written before filters were kept
+/// hold a bare `1`.
+#[derive(Serialize, Deserialize)]
+struct ManifestEntry {
+    stamp: String,
+    filter: String,
+}
+
+/// The manifest row for `jar_key`; a bare `1` when the jar cannot be read,
+/// since a filter could never be checked against it.
+fn manifest_value(jar_key: &str, classes: &[String]) -> String {
+    let Some(stamp) = JarStamp::read(Path::new(jar_key)) else {
+        return "1".to_string();
+    };
+    let entry = ManifestEntry {
+        stamp: stamp.encode(),
+        filter: ClassFilter::for_classes(classes).encode(),
+    };
+    serde_json::to_string(&entry).unwrap_or_else(|_| "1".to_string())
+}
+
+/// Adds `jar_key` to the artifact list of `class`; false if it was listed.
+pub(crate) fn add_artifact(
+    registry: &StrDb,
+    wtxn: &mut heed::RwTxn<'_>,
+    class: &str,
+    jar_key: &str,
+) -> Result<bool> {
+    let mut paths: Vec<String> = registry
+        .get(wtxn, class)?
+        .and_then(|v| serde_json::from_str::<Vec<String>>(v).ok())
+        .unwrap_or_default();
+    if paths.iter().any(|p| p == jar_key) {
+        return Ok(false);
+    }
+    paths.push(jar_key.to_string());
+    let json = serde_json::to_string(&paths)?;
+    registry.put(wtxn, class, json.as_str())?;
+    Ok(true)
+}
+
+/// Drops each jar in `wanted` from the classes outside its set. Returns how
+/// many classes were updated.
+fn drop_stale_artifacts(
+    registry: &StrDb,
+    wtxn: &mut heed::RwTxn<'_>,
+    wanted: &HashMap<&str, HashSet<&str>>,
+) -> Result<usize> {
+    let mut stale: Vec<(String, Vec<String>)> = Vec::new();
+    for item in registry.iter(wtxn)? {
+        let (class, v) = item?;
+        let Ok(mut paths) = serde_json::from_str::<Vec<String>>(v) else {
+            continue;
+        };
+        let before = paths.len();
+        paths.retain(|path| {
+            wanted
+                .get(path.as_str())
+                .is_none_or(|classes| classes.contains(class))
+        });
+        if paths.len() != before {
+            stale.push((class.to_string(), paths));
+        }
+    }
+    let updated = stale.len();
+    for (class, paths) in stale {
+        if paths.is_empty() {
+            registry.delete(wtxn, &class)?;
+        } else {
+            registry.put(wtxn, &class, &serde_json::to_string(&paths)?)?;
+        }
+    }
+    Ok(updated)
+}
+
 fn open_named_db(env: &Env, rtxn: &heed::RoTxn<'_>, name: &str) -> Result<StrDb> {
     env.open_database::<Str, Str>(rtxn, Some(name))?
         .with_context(|| format!("Database not found: {name}"))
@@ -162,4 +385,150 @@ mod tests {
         let _ = std::fs::remove_file(db_path);
         Ok(())
     }
+
+    #[test]
+    fn catalog_mutations_match_the_direct_update() -> Result<()> {
+        let db_path = temp_db_path("registry_buffered");
+        let cache = PersistentCache::open(db_path.clone())?;
+        let registry = ClassRegistry::new(cache.db());
+        registry.update_registry_and_mark_cataloged("jar1", &["a.A".to_string()])?;
+
+        let mut buffer = crate::buffer::WriteBuffer::new(
+            cache.db(),
+            crate::buffer::BufferConfig::default(),
+            cache.pending_gauge_path(),
+        );
+        let classes = vec!["a.A".to_string(), "a.B".to_string()];
+        buffer.mutate_group(ClassRegistry::catalog_mutations("jar2", &classes))?;
+        buffer.mutate_group(ClassRegistry::catalog_mutations("jar2", &classes))?;
+        buffer.shutdown_and_flush()?;
+
+        assert!(registry.is_cataloged("jar2")?);
+        assert_eq!(
+            registry.get_artifacts("a.A")?,
+            vec!["jar1".to_string(), "jar2".to_string()]
+        );
+        assert_eq!(registry.get_artifacts("a.B")?, vec!["jar2".to_string()]);
+
+        drop(buffer);
+        drop(registry);
+        drop(cache);
+        let _ = std::fs::remove_file(db_path);
+        Ok(())
+    }
+
+    #[test]
+    fn refresh_jars_drops_classes_a_jar_no_longer_has() -> Result<()> {
+        let db_path = temp_db_path("registry_refresh");
+        let cache = PersistentCache::open(db_path)?;
+        let registry = ClassRegistry::new(cache.db());
+
+        registry
+            .update_registry_and_mark_cataloged("jar1", &["a.A".to_string(), "a.B".to_string()])?;
+        registry.update_registry_and_mark_cataloged("jar2", &["a.B".to_string()])?;
+
+        registry.refresh_jars(&[(
+            "jar1".to_string(),
+            vec!["a.A".to_string(), "a.C".to_string()],
+        )])?;
+        assert_eq!(registry.get_artifacts("a.A")?, vec!["jar1".to_string()]);
+        assert_eq!(registry.get_artifacts("a.B")?, vec!["jar2".to_string()]);
+        assert_eq!(registry.get_artifacts("a.C")?, vec!["jar1".to_string()]);
+
+        registry.refresh_jars(&[("jar2".to_string(), Vec::new())])?;
+        assert!(registry.get_artifacts("a.B")?.is_empty());
+        assert_eq!(registry.indexed_classes()?, 2);
+        Ok(())
+    }
+
+    fn write_jar(path: &Path, classes: &[String]) -> Result<()> {
+        use std::io::Write;
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(path)?);
+        for class in classes {
+            zip.start_file(
+                crate::scan::class_name_to_class_path(class),
+                zip::write::FileOptions::default(),
+            )?;
+            zip.write_all(b"")?;
+        }
+        zip.finish()?;
+        Ok(())
+    }
+
+    #[test]
+    fn class_filters_never_rule_out_a_jar_holding_the_class() -> Result<()> {
+        use crate::scan::class_name_to_class_path;
+
+        let db_path = temp_db_path("registry_filters");
+        let dir = db_path.with_extension("jars");
+        std::fs::create_dir_all(&dir)?;
+        let cache = PersistentCache::open(db_path.clone())?;
+        let registry = ClassRegistry::new(cache.db());
+        let mut jars = Vec::new();
+        for j in 0..20 {
+            let jar = dir.join(format!("lib{j}.jar"));
+            let classes: Vec<String> = (0..150)
+                .map(|c| format!("org.lib{j}.pkg{}.Type{c}", c % 7))
+                .collect();
+            write_jar(&jar, &classes)?;
+            let cataloged = crate::catalog::catalog(&jar)?;
+            registry.update_registry_and_mark_cataloged(&jar.to_string_lossy(), &cataloged)?;
+            jars.push((jar, classes));
+        }
+        let all: Vec<PathBuf> = jars.iter().map(|(jar, _)| jar.clone()).collect();
+
+        let mut session = ReadSession::new(cache.env());
+        for (jar, classes) in &jars {
+            for class in classes {
+                let mut candidates = vec![jar.clone()];
+                let current = registry.rule_out_in(
+                    &mut session,
+                    &class_name_to_class_path(class),
+                    &mut candidates,
+                )?;
+                assert!(
+                    candidates == [jar.clone()],
+                    "{class} ruled out of {}",
+                    jar.display()
+                );
+                assert!(current.contains(jar));
+            }
+        }
+        let mut candidates = all.clone();
+        registry.rule_out_in(&mut session, "org/missing/Type.class", &mut candidates)?;
+        assert!(candidates.len() <= 2, "{} kept", candidates.len());
+
+        // Member types are not cataloged, and a jar changed since it was
+        // cataloged keeps its stale filter out of the decision.
+        let mut candidates = all.clone();
+        registry.rule_out_in(
+            &mut session,
+            "org/missing/Type$Inner.class",
+            &mut candidates,
+        )?;
+        assert_eq!(candidates, all);
+        let (changed, _) = &jars[0];
+        write_jar(changed, &["org.missing.Type".to_string()])?;
+        let mut candidates = all.clone();
+        let current =
+            registry.rule_out_in(&mut session, "org/missing/Type.class", &mut candidates)?;
+        assert!(candidates.contains(changed));
+        assert!(!current.contains(changed));
+
+        // Rows from before filters were kept rule nothing out.
+        registry.update_registry_and_mark_cataloged("legacy.jar", &[])?;
+        let mut candidates = vec![PathBuf::from("legacy.jar")];
+        session.release();
+        let current =
+            registry.rule_out_in(&mut session, "org/missing/Type.class", &mut candidates)?;
+        assert_eq!(candidates.len(), 1);
+        assert!(current.is_empty());
+
+        drop(session);
+        drop(registry);
+        drop(cache);
+        let _ = std::fs::remove_dir_all(&dir);
+        let _ = std::fs::remove_file(db_path);
+        Ok(())
+    }
 }
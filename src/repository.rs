@@ -0,0 +1,143 @@
+//! Named jar roots searched by `find` and targeted by `index`/`watch --repo`.
+//!
+//! The Maven repository from `--m2` is always present as [`LOCAL_REPOSITORY`];
+//! more come from `[[repository]]` tables in the config file. Jar paths in
+//! the registry and cache stay absolute, so a repository can be renamed or
+//! dropped from the config without invalidating anything.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Name of the repository made from `--m2` / `CLASS_FINDER_M2`.
+pub const LOCAL_REPOSITORY: &str = "local";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RepositoryLayout {
+    /// `<group path>/<artifact>/<version>/<file>.jar`; lookups start in the
+    /// directories named after the class's package.
+    #[default]
+    Maven,
+    /// Jars anywhere below the root; lookups scan all of it.
+    Flat,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Repository {
+    pub name: String,
+    pub path: PathBuf,
+    #[serde(default)]
+    pub layout: RepositoryLayout,
+    /// Higher is searched first; a class found in one repository is not
+    /// looked up in those after it.
+    #[serde(default)]
+    pub priority: i32,
+    /// Content that never changes, such as a mounted mirror snapshot;
+    /// `watch` refuses it.
+    #[serde(default)]
+    pub readonly: bool,
+}
+
+impl Repository {
+    pub fn local(path: PathBuf) -> Self {
+        Self {
+            name: LOCAL_REPOSITORY.to_string(),
+            path,
+            layout: RepositoryLayout::Maven,
+            priority: 0,
+            readonly: false,
+        }
+    }
+
+    pub fn contains(&self, jar_path: &Path) -> bool {
+        jar_path.starts_with(&self.path)
+    }
+}
+
+/// `configured` followed by `local` unless one of them already has its name
+/// or path, ordered by descending priority (ties keep that order). Names must
+/// be unique.
+pub fn merge_repositories(configured: &[Repository], local: Repository) -> Result<Vec<Repository>> {
+    let mut repositories = configured.to_vec();
+    for (i, repo) in repositories.iter().enumerate() {
+        if repositories[..i].iter().any(|r| r.name == repo.name) {
+            anyhow::bail!("repository {:?} is defined more than once", repo.name);
+        }
+    }
+    if !repositories
+        .iter()
+        .any(|r| r.name == local.name || r.path == local.path)
+    {
+        repositories.push(local);
+    }
+    repositories.sort_by_key(|r| std::cmp::Reverse(r.priority));
+    Ok(repositories)
+}
+
+/// The repository whose root holds `jar_path`, the deepest one when roots
+/// are nested.
+pub fn repository_for<'a>(
+    repositories: &'a [Repository],
+    jar_path: &Path,
+) -> Option<&'a Repository> {
+    repositories
+        .iter()
+        .filter(|r| r.contains(jar_path))
+        .max_by_key(|r| r.path.components().count())
+}
+
+pub fn named<'a>(repositories: &'a [Repository], name: &str) -> Result<&'a Repository> {
+    repositories.iter().find(|r| r.name == name).ok_or_else(|| {
+        let names: Vec<&str> = repositories.iter().map(|r| r.name.as_str()).collect();
+        anyhow::anyhow!(
+            "Unknown repository {name:?}; configured: {}",
+            names.join(", ")
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repo(name: &str, path: &str, priority: i32) -> Repository {
+        Repository {
+            name: name.to_string(),
+            path: PathBuf::from(path),
+            layout: RepositoryLayout::Maven,
+            priority,
+            readonly: false,
+        }
+    }
+
+    #[test]
+    fn merge_orders_by_priority_and_keeps_local_once() -> Result<()> {
+        let local = Repository::local(PathBuf::from("/m2"));
+        let merged = merge_repositories(
+            &[repo("mirror", "/mirror", -1), repo("corp", "/corp", 10)],
+            local.clone(),
+        )?;
+        let names: Vec<&str> = merged.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["corp", "local", "mirror"]);
+
+        let merged = merge_repositories(&[repo("home", "/m2", 5)], local.clone())?;
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].name, "home");
+
+        assert!(merge_repositories(&[repo("a", "/a", 0), repo("a", "/b", 0)], local).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn jars_belong_to_the_deepest_root() {
+        let repos = [repo("outer", "/data", 0), repo("inner", "/data/mirror", 0)];
+        let owner = |jar: &str| repository_for(&repos, Path::new(jar)).map(|r| r.name.as_str());
+        assert_eq!(owner("/data/mirror/a/1.0/a-1.0.jar"), Some("inner"));
+        assert_eq!(owner("/data/x/1.0/x-1.0.jar"), Some("outer"));
+        assert_eq!(owner("/datamirror/x.jar"), None);
+        assert!(named(&repos, "inner").is_ok());
+        assert!(named(&repos, "missing").is_err());
+    }
+}
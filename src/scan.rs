@@ -1,8 +1,12 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use globset::{Glob, GlobMatcher};
 use ignore::WalkBuilder;
+use serde::Serialize;
 use std::path::{Path, PathBuf};
 use std::sync::mpsc;
 
+use crate::deadline::{self, Phase};
+
 pub fn default_m2_repository() -> Result<PathBuf> {
     let home =
         dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Failed to resolve home directory"))?;
@@ -39,7 +43,11 @@ pub fn infer_scan_path(m2_repo: &Path, class_name: &str) -> PathBuf {
         .unwrap_or_else(|| m2_repo.to_path_buf())
 }
 
+/// Every `.jar` under `base_path`. Stops with
+/// [`DeadlineExceeded`](crate::deadline::DeadlineExceeded) once the global
+/// `--timeout` has passed.
 pub fn scan_jars(base_path: &Path) -> Result<Vec<PathBuf>> {
+    deadline::enter(Phase::Scan);
     let (tx, rx) = mpsc::channel();
 
     let walker = WalkBuilder::new(base_path)
@@ -52,6 +60,9 @@ pub fn scan_jars(base_path: &Path) -> Result<Vec<PathBuf>> {
     walker.run(|| {
         let tx = tx.clone();
         Box::new(move |entry| {
+            if deadline::expired() {
+                return ignore::WalkState::Quit;
+            }
             if let Ok(entry) = entry {
                 let path = entry.path();
                 if path.extension().is_some_and(|e| e == "jar") {
@@ -63,6 +74,7 @@ pub fn scan_jars(base_path: &Path) -> Result<Vec<PathBuf>> {
     });
 
     drop(tx);
+    deadline::check()?;
     Ok(rx.iter().collect())
 }
 
@@ -77,6 +89,147 @@ pub fn extract_version_from_maven_path(jar_path: &Path) -> Option<String> {
         .map(|s| s.to_string_lossy().to_string())
 }
 
+/// `groupId` and `artifactId` of a jar laid out as
+/// `<m2>/<group path>/<artifact>/<version>/<file>.jar`.
+pub fn maven_coordinates(m2_repo: &Path, jar_path: &Path) -> Option<(String, String)> {
+    let rel = jar_path.strip_prefix(m2_repo).ok()?;
+    let parts: Vec<String> = rel
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect();
+    if parts.len() < 4 {
+        return None;
+    }
+    let artifact = parts[parts.len() - 3].clone();
+    Some((parts[..parts.len() - 3].join("."), artifact))
+}
+
+/// Jar path for one line of a target list: a path, or Maven coordinates as
+/// `group:artifact:version`, `group:artifact:packaging:version` or
+/// `group:artifact:packaging:classifier:version`.
+pub fn resolve_jar_reference(m2_repo: &Path, reference: &str) -> PathBuf {
+    let parts: Vec<&str> = reference.split(':').collect();
+    let looks_like_gav = (3..=5).contains(&parts.len())
+        && parts
+            .iter()
+            .all(|p| !p.is_empty() && !p.contains(['/', '\\']));
+    if !looks_like_gav {
+        return PathBuf::from(reference);
+    }
+    let (group, artifact, version) = (parts[0], parts[1], parts[parts.len() - 1]);
+    let file = match parts.len() {
+        5 => format!("{artifact}-{version}-{}.jar", parts[3]),
+        _ => format!("{artifact}-{version}.jar"),
+    };
+    m2_repo
+        .join(group.replace('.', "/"))
+        .join(artifact)
+        .join(version)
+        .join(file)
+}
+
+#[derive(Debug)]
+enum JarExclusion {
+    /// A `groupId` and its subgroups.
+    Group(String),
+    /// An `artifactId`, or `groupId:artifactId`.
+    Artifact(String),
+    Glob(String, GlobMatcher),
+}
+
+/// Jars removed from a bulk target list by one exclusion.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExclusionCount {
+    pub option: &'static str,
+    pub pattern: String,
+    pub removed: usize,
+}
+
+/// Exclusions applied to bulk target lists (`warmup --group`, `--hot`).
+#[derive(Debug, Default)]
+pub struct JarExclusions {
+    rules: Vec<JarExclusion>,
+}
+
+impl JarExclusions {
+    pub fn new(groups: &[String], artifacts: &[String], jar_globs: &[String]) -> Result<Self> {
+        let mut rules: Vec<JarExclusion> = groups
+            .iter()
+            .map(|g| JarExclusion::Group(g.clone()))
+            .chain(artifacts.iter().map(|a| JarExclusion::Artifact(a.clone())))
+            .collect();
+        for pattern in jar_globs {
+            let matcher = Glob::new(pattern)
+                .with_context(|| format!("invalid --exclude-jar-glob pattern: {pattern}"))?
+                .compile_matcher();
+            rules.push(JarExclusion::Glob(pattern.clone(), matcher));
+        }
+        Ok(Self { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Drops the excluded jars from `jars`, reading Maven coordinates
+    /// relative to the first of `roots` that holds each jar. Each removed jar
+    /// is counted against the first exclusion that matches it.
+    pub fn apply(&self, roots: &[PathBuf], jars: &mut Vec<PathBuf>) -> Vec<ExclusionCount> {
+        let mut counts: Vec<ExclusionCount> = self
+            .rules
+            .iter()
+            .map(|rule| {
+                let (option, pattern) = match rule {
+                    JarExclusion::Group(g) => ("exclude-group", g),
+                    JarExclusion::Artifact(a) => ("exclude-artifact", a),
+                    JarExclusion::Glob(p, _) => ("exclude-jar-glob", p),
+                };
+                ExclusionCount {
+                    option,
+                    pattern: pattern.clone(),
+                    removed: 0,
+                }
+            })
+            .collect();
+        if self.rules.is_empty() {
+            return counts;
+        }
+        jars.retain(|jar| {
+            let hit = self.matching_rule(roots, jar);
+            if let Some(idx) = hit {
+                counts[idx].removed += 1;
+            }
+            hit.is_none()
+        });
+        counts
+    }
+
+    /// Whether [`Self::apply`] would drop `jar`.
+    pub fn excludes(&self, roots: &[PathBuf], jar: &Path) -> bool {
+        !self.rules.is_empty() && self.matching_rule(roots, jar).is_some()
+    }
+
+    fn matching_rule(&self, roots: &[PathBuf], jar: &Path) -> Option<usize> {
+        let coordinates = roots.iter().find_map(|root| maven_coordinates(root, jar));
+        self.rules
+            .iter()
+            .position(|rule| match (rule, &coordinates) {
+                (JarExclusion::Group(g), Some((group, _))) => {
+                    group == g
+                        || group
+                            .strip_prefix(g.as_str())
+                            .is_some_and(|r| r.starts_with('.'))
+                }
+                (JarExclusion::Artifact(a), Some((group, artifact))) => match a.split_once(':') {
+                    Some((g, a)) => g == group && a == artifact,
+                    None => a == artifact,
+                },
+                (JarExclusion::Glob(_, matcher), _) => matcher.is_match(jar),
+                _ => false,
+            })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -120,6 +273,68 @@ mod tests {
         assert_eq!(paths, vec![narrow, wide, widest]);
     }
 
+    #[test]
+    fn jar_exclusions_match_coordinates_and_globs() {
+        let m2 = PathBuf::from("/m2");
+        let jar = |rel: &str| m2.join(rel);
+        let mut jars = vec![
+            jar("com/mycorp/app/1.0/app-1.0.jar"),
+            jar("com/mycorp/gen/protos/1.0/protos-1.0.jar"),
+            jar("com/mycorpx/lib/1.0/lib-1.0.jar"),
+            jar("org/example/client/2.0/client-2.0.jar"),
+            jar("org/example/client-shaded/2.0/client-shaded-2.0.jar"),
+        ];
+        let exclusions = JarExclusions::new(
+            &["com.mycorp.gen".to_string()],
+            &["org.example:client".to_string()],
+            &["**/*-shaded-*.jar".to_string()],
+        )
+        .unwrap();
+
+        let counts = exclusions.apply(std::slice::from_ref(&m2), &mut jars);
+        assert_eq!(
+            jars,
+            vec![
+                jar("com/mycorp/app/1.0/app-1.0.jar"),
+                jar("com/mycorpx/lib/1.0/lib-1.0.jar"),
+            ]
+        );
+        let removed: Vec<_> = counts.iter().map(|c| (c.option, c.removed)).collect();
+        assert_eq!(
+            removed,
+            [
+                ("exclude-group", 1),
+                ("exclude-artifact", 1),
+                ("exclude-jar-glob", 1)
+            ]
+        );
+        assert_eq!(
+            maven_coordinates(&m2, &jar("com/mycorp/app/1.0/app-1.0.jar")),
+            Some(("com.mycorp".to_string(), "app".to_string()))
+        );
+    }
+
+    #[test]
+    fn resolve_jar_reference_maps_coordinates_under_m2() {
+        let m2 = PathBuf::from("/m2");
+        assert_eq!(
+            resolve_jar_reference(&m2, "org.example:demo:1.0"),
+            m2.join("org/example/demo/1.0/demo-1.0.jar")
+        );
+        assert_eq!(
+            resolve_jar_reference(&m2, "org.example:demo:jar:1.0"),
+            m2.join("org/example/demo/1.0/demo-1.0.jar")
+        );
+        assert_eq!(
+            resolve_jar_reference(&m2, "org.example:demo:jar:tests:1.0"),
+            m2.join("org/example/demo/1.0/demo-1.0-tests.jar")
+        );
+        assert_eq!(
+            resolve_jar_reference(&m2, "/libs/demo-1.0.jar"),
+            PathBuf::from("/libs/demo-1.0.jar")
+        );
+    }
+
     #[test]
     fn infer_search_paths_falls_back_to_repo_root_for_short_names() {
         let base = temp_dir("class-finder-search-paths-short");
@@ -0,0 +1,266 @@
+//! Jar lists of scanned roots, kept in `SCAN_CACHE_DB` so a find that misses
+//! the registry does not walk the same tree on every run.
+//!
+//! An entry is reused while it is younger than the max age (config key
+//! `scan_cache_max_age`, default [`DEFAULT_SCAN_CACHE_MAX_AGE`]) and the
+//! root directory's mtime is the one recorded with it. Jars added deeper in
+//! the tree leave the root's mtime alone, so the incremental indexer drops
+//! the entries above the jars it sees change ([`invalidate_scans`]), along
+//! with those of roots no longer configured; `clear --registry` drops the
+//! latter too, and `clear --mtimes` or a full clear drops every entry.
+//! `find --fresh-scan` walks anyway and replaces the entry.
+
+use anyhow::Result;
+use heed::Env;
+use heed::types::Str;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::cache::SCAN_CACHE_DB;
+use crate::scan::scan_jars;
+use crate::session::ReadSession;
+
+pub const DEFAULT_SCAN_CACHE_MAX_AGE: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ScanEntry {
+    /// Seconds since the epoch.
+    scanned_at: u64,
+    /// Nanoseconds since the epoch, when the platform reports one.
+    root_mtime: Option<u64>,
+    jars: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct ScanCache {
+    db: Arc<Env>,
+    max_age: Duration,
+    walks: Arc<AtomicUsize>,
+}
+
+impl ScanCache {
+    pub fn new(db: Arc<Env>) -> Self {
+        Self {
+            db,
+            max_age: DEFAULT_SCAN_CACHE_MAX_AGE,
+            walks: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// How long a jar list is reused; zero disables the cache.
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    pub fn max_age(&self) -> Duration {
+        self.max_age
+    }
+
+    /// Directory walks this cache (and its clones) ran so far.
+    pub fn walks(&self) -> usize {
+        self.walks.load(Ordering::Relaxed)
+    }
+
+    /// Every jar under `root`: the stored list while it is fresh, else a
+    /// walk whose result replaces it. `fresh` skips the stored list.
+    /// A walk releases `session` first, since it can take a while and is
+    /// followed by a write.
+    pub fn jars(
+        &self,
+        session: &mut ReadSession<'_>,
+        root: &Path,
+        fresh: bool,
+    ) -> Result<Vec<PathBuf>> {
+        let key = root.to_string_lossy();
+        let root_mtime = dir_mtime(root);
+        if !fresh
+            && !self.max_age.is_zero()
+            && let Some(entry) = session
+                .get(SCAN_CACHE_DB, &key)?
+                .and_then(|raw| serde_json::from_str::<ScanEntry>(&raw).ok())
+        {
+            let age = Duration::from_secs(now_secs().saturating_sub(entry.scanned_at));
+            if age < self.max_age && entry.root_mtime == root_mtime {
+                tracing::info!(
+                    "scan cache hit: {} ({} jars, scanned {}s ago)",
+                    root.display(),
+                    entry.jars.len(),
+                    age.as_secs()
+                );
+                return Ok(entry.jars.into_iter().map(PathBuf::from).collect());
+            }
+        }
+
+        session.release();
+        self.walks.fetch_add(1, Ordering::Relaxed);
+        tracing::info!("scan cache miss, walking {}", root.display());
+        let jars = scan_jars(root)?;
+        if !self.max_age.is_zero() {
+            self.store(&key, root_mtime, &jars)?;
+        }
+        Ok(jars)
+    }
+
+    fn store(&self, key: &str, root_mtime: Option<u64>, jars: &[PathBuf]) -> Result<()> {
+        let entry = ScanEntry {
+            scanned_at: now_secs(),
+            root_mtime,
+            jars: jars
+                .iter()
+                .map(|jar| jar.to_string_lossy().to_string())
+                .collect(),
+        };
+        let mut wtxn = self.db.write_txn()?;
+        let table = self
+            .db
+            .create_database::<Str, Str>(&mut wtxn, Some(SCAN_CACHE_DB))?;
+        table.put(&mut wtxn, key, &serde_json::to_string(&entry)?)?;
+        wtxn.commit()?;
+        Ok(())
+    }
+}
+
+/// Drops the stored jar lists of every root above one of `jars`, so the
+/// next find walks them again, and of every root outside the configured
+/// `roots`. Returns how many were dropped.
+pub fn invalidate_scans(env: &Env, jars: &[PathBuf], roots: &[PathBuf]) -> Result<usize> {
+    let mut wtxn = env.write_txn()?;
+    let table = env.create_database::<Str, Str>(&mut wtxn, Some(SCAN_CACHE_DB))?;
+    let stale: Vec<String> = table
+        .iter(&wtxn)?
+        .filter_map(|row| row.ok())
+        .map(|(root, _)| root.to_string())
+        .filter(|root| {
+            jars.iter().any(|jar| jar.starts_with(root))
+                || !roots
+                    .iter()
+                    .any(|configured| Path::new(root).starts_with(configured))
+        })
+        .collect();
+    if stale.is_empty() {
+        return Ok(0);
+    }
+    for root in &stale {
+        table.delete(&mut wtxn, root)?;
+    }
+    wtxn.commit()?;
+    Ok(stale.len())
+}
+
+fn dir_mtime(dir: &Path) -> Option<u64> {
+    let modified = std::fs::metadata(dir).ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_nanos() as u64)
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::PersistentCache;
+    use std::fs::File;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        std::env::temp_dir().join(format!(
+            "class_finder_scan_cache_test_{}_{}_{}",
+            std::process::id(),
+            nanos,
+            name
+        ))
+    }
+
+    fn touch_jar(path: &Path) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, b"").unwrap();
+    }
+
+    #[test]
+    fn fresh_lists_are_reused_until_invalidated() -> Result<()> {
+        let root = temp_path("root");
+        let jar = root.join("org/example/a/1.0/a-1.0.jar");
+        touch_jar(&jar);
+        let db_path = temp_path("db.lmdb");
+        let cache = PersistentCache::open(db_path.clone())?;
+        let scans = ScanCache::new(cache.db());
+        let env = cache.db();
+        let mut session = ReadSession::new(&env);
+        let roots = vec![root.clone()];
+
+        assert_eq!(scans.jars(&mut session, &root, false)?, vec![jar.clone()]);
+        assert_eq!(scans.jars(&mut session, &root, false)?, vec![jar.clone()]);
+        assert_eq!(scans.walks(), 1);
+
+        // A jar deep in the tree leaves the root's mtime alone.
+        let added = root.join("org/example/a/2.0/a-2.0.jar");
+        touch_jar(&added);
+        assert_eq!(scans.jars(&mut session, &root, false)?.len(), 1);
+        assert_eq!(scans.jars(&mut session, &root, true)?.len(), 2);
+        assert_eq!(scans.walks(), 2);
+
+        let newer = root.join("org/example/a/3.0/a-3.0.jar");
+        touch_jar(&newer);
+        session.release();
+        assert_eq!(invalidate_scans(&env, &[newer], &roots)?, 1);
+        assert_eq!(scans.jars(&mut session, &root, false)?.len(), 3);
+        assert_eq!(scans.walks(), 3);
+
+        // A new entry in the root itself changes its mtime; it is set
+        // explicitly, as the filesystem's granularity may hide the change.
+        touch_jar(&root.join("top.jar"));
+        let modified = std::fs::metadata(&root)?.modified()?;
+        File::open(&root)?.set_modified(modified + Duration::from_secs(1))?;
+        assert_eq!(scans.jars(&mut session, &root, false)?.len(), 4);
+        assert_eq!(scans.walks(), 4);
+
+        // Roots that are no longer configured are dropped.
+        session.release();
+        let elsewhere = temp_path("elsewhere");
+        assert_eq!(invalidate_scans(&env, &[], &roots)?, 0);
+        assert_eq!(invalidate_scans(&env, &[], &[elsewhere])?, 1);
+        assert_eq!(scans.jars(&mut session, &root, false)?.len(), 4);
+        assert_eq!(scans.walks(), 5);
+
+        drop(session);
+        drop(cache);
+        let _ = std::fs::remove_dir_all(&root);
+        let _ = std::fs::remove_file(&db_path);
+        Ok(())
+    }
+
+    #[test]
+    fn zero_max_age_always_walks() -> Result<()> {
+        let root = temp_path("uncached");
+        touch_jar(&root.join("a.jar"));
+        let db_path = temp_path("uncached.lmdb");
+        let cache = PersistentCache::open(db_path.clone())?;
+        let scans = ScanCache::new(cache.db()).with_max_age(Duration::ZERO);
+        let env = cache.db();
+        let roots = vec![root.clone()];
+        let mut session = ReadSession::new(&env);
+        for _ in 0..3 {
+            assert_eq!(scans.jars(&mut session, &root, false)?.len(), 1);
+        }
+        assert_eq!(scans.walks(), 3);
+        session.release();
+        assert_eq!(invalidate_scans(&env, &[root.join("a.jar")], &roots)?, 0);
+
+        drop(session);
+        drop(cache);
+        let _ = std::fs::remove_dir_all(&root);
+        let _ = std::fs::remove_file(&db_path);
+        Ok(())
+    }
+}
@@ -0,0 +1,126 @@
+//! One read transaction shared by the registry and cache lookups of a
+//! `find`, instead of one per lookup.
+//!
+//! LMDB gives each thread a single reader slot, so while a [`ReadSession`]
+//! holds its transaction every read on that thread has to go through it.
+//! [`ReadSession::release`] ends the transaction and the next read begins a
+//! fresh one, which also sees whatever was committed in between. Release it
+//! before anything slow such as a decompiler run: an open reader keeps
+//! writers from reusing the pages it can still see.
+
+use anyhow::{Context, Result};
+use heed::types::Str;
+use heed::{Database, Env, RoTxn, WithTls};
+use std::collections::HashMap;
+
+type StrDb = Database<Str, Str>;
+
+pub struct ReadSession<'e> {
+    env: &'e Env,
+    txn: Option<RoTxn<'e, WithTls>>,
+    /// Handles opened in the current transaction, by table name.
+    tables: HashMap<&'static str, StrDb>,
+    transactions: usize,
+}
+
+impl<'e> ReadSession<'e> {
+    /// Begins no transaction until the first read.
+    pub fn new(env: &'e Env) -> Self {
+        Self {
+            env,
+            txn: None,
+            tables: HashMap::new(),
+            transactions: 0,
+        }
+    }
+
+    pub fn get(&mut self, table: &'static str, key: &str) -> Result<Option<String>> {
+        let txn = match &mut self.txn {
+            Some(txn) => txn,
+            slot => {
+                self.transactions += 1;
+                slot.insert(self.env.read_txn()?)
+            }
+        };
+        let db = match self.tables.get(table) {
+            Some(db) => *db,
+            None => {
+                let db = self
+                    .env
+                    .open_database::<Str, Str>(txn, Some(table))?
+                    .with_context(|| format!("Database not found: {table}"))?;
+                *self.tables.entry(table).or_insert(db)
+            }
+        };
+        Ok(db.get(txn, key)?.map(str::to_string))
+    }
+
+    /// Ends the current transaction, if any.
+    pub fn release(&mut self) {
+        self.txn = None;
+        self.tables.clear();
+    }
+
+    /// Read transactions begun so far.
+    pub fn transactions(&self) -> usize {
+        self.transactions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::{CLASS_REGISTRY_DB, PersistentCache};
+    use crate::registry::ClassRegistry;
+
+    fn temp_db_path(name: &str) -> std::path::PathBuf {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        std::env::temp_dir().join(format!(
+            "class_finder_session_test_{}_{}_{}.lmdb",
+            std::process::id(),
+            nanos,
+            name
+        ))
+    }
+
+    #[test]
+    fn reads_share_one_transaction_until_released() -> Result<()> {
+        let path = temp_db_path("shared");
+        let cache = PersistentCache::open(path.clone())?;
+        let registry = ClassRegistry::new(cache.db());
+        registry.update_registry_and_mark_cataloged("a.jar", &["a.A".to_string()])?;
+
+        let env = cache.db();
+        let mut session = ReadSession::new(&env);
+        assert_eq!(session.transactions(), 0);
+        for _ in 0..10 {
+            assert_eq!(registry.get_artifacts_in(&mut session, "a.A")?, ["a.jar"]);
+            assert!(session.get(CLASS_REGISTRY_DB, "a.Missing")?.is_none());
+        }
+        assert_eq!(session.transactions(), 1);
+
+        // Commits made while the session is open stay invisible until it is
+        // released.
+        std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    registry.update_registry_and_mark_cataloged("b.jar", &["b.B".to_string()])
+                })
+                .join()
+                .unwrap()
+        })?;
+        assert!(registry.get_artifacts_in(&mut session, "b.B")?.is_empty());
+        session.release();
+        assert_eq!(registry.get_artifacts_in(&mut session, "b.B")?, ["b.jar"]);
+        assert_eq!(session.transactions(), 2);
+
+        drop(session);
+        drop(registry);
+        drop(cache);
+        let _ = std::fs::remove_file(&path);
+        Ok(())
+    }
+}
@@ -0,0 +1,168 @@
+//! When long-running commands republish `<db>.snapshot`.
+//!
+//! A [`SnapshotPolicy`] comes from the `[snapshot]` table of the config file.
+//! [`spawn_publisher`] checks it in a background thread and calls
+//! [`PersistentCache::publish_snapshot`] whenever it triggers, until Ctrl-C.
+
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::buffer::WriteBufferHandle;
+use crate::cache::{PersistentCache, SnapshotRecord};
+use crate::hotspot::unix_now;
+use crate::interrupt;
+
+const CHECK_TICK: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SnapshotPolicy {
+    /// Republish once this many seconds have passed since the last snapshot.
+    pub interval_secs: Option<u64>,
+    /// Republish once this many class sources, registry entries or
+    /// structures were added since the last snapshot.
+    pub min_new_entries: Option<u64>,
+}
+
+impl SnapshotPolicy {
+    pub fn is_enabled(&self) -> bool {
+        self.interval_secs.is_some() || self.min_new_entries.is_some()
+    }
+
+    /// Whether either trigger fires for a db holding `entries` rows at unix
+    /// time `now`. Without a previous snapshot, any enabled policy fires.
+    pub fn due(&self, last: Option<&SnapshotRecord>, entries: u64, now: u64) -> bool {
+        let Some(last) = last else {
+            return self.is_enabled();
+        };
+        self.interval_secs
+            .is_some_and(|secs| now.saturating_sub(last.published_at) >= secs)
+            || self
+                .min_new_entries
+                .is_some_and(|min| entries.saturating_sub(last.entries) >= min)
+    }
+}
+
+/// Publishes snapshots of `cache` per `policy` until Ctrl-C, flushing
+/// `buffer` before each copy. `None` when the policy is disabled.
+/// Failures are logged as warnings and retried at the next check.
+pub fn spawn_publisher(
+    cache: Arc<PersistentCache>,
+    policy: SnapshotPolicy,
+    buffer: Option<WriteBufferHandle>,
+) -> Option<JoinHandle<()>> {
+    if !policy.is_enabled() {
+        return None;
+    }
+    Some(std::thread::spawn(move || {
+        let mut last = cache.last_snapshot().ok().flatten();
+        while !interrupt::requested() {
+            let due = cache
+                .snapshot_entries()
+                .is_ok_and(|entries| policy.due(last.as_ref(), entries, unix_now()));
+            if due {
+                match cache.publish_snapshot(buffer.as_ref()) {
+                    Ok(record) => last = Some(record),
+                    Err(err) => tracing::warn!("snapshot publication failed: {err:#}"),
+                }
+            }
+            std::thread::sleep(CHECK_TICK);
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use heed::EnvOpenOptions;
+    use heed::types::Str;
+    use std::path::PathBuf;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        std::env::temp_dir().join(format!(
+            "class_finder_snapshot_test_{}_{}_{}",
+            std::process::id(),
+            nanos,
+            name
+        ))
+    }
+
+    fn record(published_at: u64, entries: u64) -> SnapshotRecord {
+        SnapshotRecord {
+            path: String::new(),
+            published_at,
+            entries,
+            duration_ms: 0,
+        }
+    }
+
+    #[test]
+    fn policy_triggers_on_age_or_new_entries() {
+        let policy = SnapshotPolicy {
+            interval_secs: Some(60),
+            min_new_entries: Some(100),
+        };
+        let last = record(1_000, 500);
+        assert!(policy.due(None, 0, 1_000));
+        assert!(!policy.due(Some(&last), 599, 1_059));
+        assert!(policy.due(Some(&last), 599, 1_060));
+        assert!(policy.due(Some(&last), 600, 1_001));
+        assert!(!SnapshotPolicy::default().due(None, 1_000, 1_000));
+
+        let entries_only = SnapshotPolicy {
+            min_new_entries: Some(1),
+            ..SnapshotPolicy::default()
+        };
+        assert!(!entries_only.due(Some(&last), 500, u64::MAX));
+    }
+
+    #[test]
+    fn published_snapshot_holds_flushed_writes() -> Result<()> {
+        use crate::buffer::{BufferConfig, Mutation, Table, WriteBuffer};
+
+        let base = temp_dir("publish");
+        let cache = PersistentCache::open(base.join("db.lmdb"))?;
+        let buffer = WriteBuffer::new(
+            cache.db(),
+            BufferConfig {
+                flush_interval_ms: 60_000,
+                ..BufferConfig::default()
+            },
+            cache.pending_gauge_path(),
+        );
+        buffer.mutate(Mutation::PutValue {
+            table: Table::ClassRegistry,
+            key: "org.example.A".to_string(),
+            value: "[\"/m2/a.jar\"]".to_string(),
+        })?;
+
+        let record = cache.publish_snapshot(buffer.handle().as_ref())?;
+        assert_eq!(record.entries, 1);
+        assert_eq!(cache.last_snapshot()?, Some(record.clone()));
+        assert_eq!(cache.stats()?.last_snapshot, Some(record));
+
+        let snapshot = unsafe {
+            EnvOpenOptions::new()
+                .max_dbs(32)
+                .flags(heed::EnvFlags::NO_SUB_DIR | heed::EnvFlags::READ_ONLY)
+                .open(cache.snapshot_path())?
+        };
+        let rtxn = snapshot.read_txn()?;
+        let registry = snapshot
+            .open_database::<Str, Str>(&rtxn, Some(crate::cache::CLASS_REGISTRY_DB))?
+            .expect("registry table");
+        assert!(registry.get(&rtxn, "org.example.A")?.is_some());
+        drop(rtxn);
+        drop(buffer);
+        let _ = std::fs::remove_dir_all(base);
+        Ok(())
+    }
+}
@@ -6,7 +6,8 @@ use std::io::{Cursor, Read};
 use std::path::{Path, PathBuf};
 use zip::ZipArchive;
 
-use crate::parse::{ParsedClass, extract_class_name, hash_content};
+use crate::listing::jar_listings;
+use crate::parse::{ParsedClass, SplitStrategy, extract_class_name, parsed_class};
 
 pub fn sources_jar_path(jar_path: &Path) -> Option<PathBuf> {
     if jar_path.extension().is_none_or(|ext| ext != "jar") {
@@ -34,6 +35,17 @@ pub fn read_class_source(jar_path: &Path, class_name: &str) -> Result<Option<Str
         return Ok(None);
     };
 
+    // Sources jars often lack a class; the shared listing answers that
+    // without opening the archive.
+    let entry_path = source_entry_path(class_name);
+    if !jar_listings()
+        .get(&sources_jar)
+        .with_context(|| format!("Failed to read sources jar: {}", sources_jar.display()))?
+        .contains(&entry_path)
+    {
+        return Ok(None);
+    }
+
     let file = File::open(&sources_jar)
         .with_context(|| format!("Failed to open sources jar: {}", sources_jar.display()))?;
     // SAFETY: The file is opened read-only and remains valid for the lifetime of the mmap.
@@ -42,7 +54,6 @@ pub fn read_class_source(jar_path: &Path, class_name: &str) -> Result<Option<Str
     let mut archive = ZipArchive::new(Cursor::new(&mmap[..]))
         .with_context(|| format!("Failed to read sources jar: {}", sources_jar.display()))?;
 
-    let entry_path = source_entry_path(class_name);
     match archive.by_name(&entry_path) {
         Ok(mut entry) => read_entry_to_string(&mut entry).map(Some),
         Err(_) => Ok(None),
@@ -70,7 +81,7 @@ pub fn read_sources_jar(sources_jar: &Path) -> Result<Vec<ParsedClass>> {
     for idx in 0..archive.len() {
         let mut entry = archive.by_index(idx)?;
         let name = entry.name().to_string();
-        if !name.ends_with(".java") || is_special_java_entry(&name) {
+        if !name.ends_with(".java") {
             continue;
         }
 
@@ -84,11 +95,9 @@ pub fn read_sources_jar(sources_jar: &Path) -> Result<Vec<ParsedClass>> {
             continue;
         }
 
-        let content_hash = hash_content(&content);
         classes.push(ParsedClass {
-            class_name,
-            content,
-            content_hash,
+            failed: false,
+            ..parsed_class(class_name, content, SplitStrategy::File)
         });
     }
 
@@ -100,18 +109,13 @@ fn source_entry_path(class_name: &str) -> String {
     format!("{}.java", top_level.replace('.', "/"))
 }
 
-fn fqn_from_source_entry(entry_name: &str) -> Option<String> {
+/// `org/example/A.java` -> `org.example.A`; `package-info.java` and
+/// `module-info.java` keep their file names (`org.example.package-info`).
+pub(crate) fn fqn_from_source_entry(entry_name: &str) -> Option<String> {
     let class_path = entry_name.strip_suffix(".java")?;
-    if is_special_java_entry(entry_name) {
-        return None;
-    }
     Some(class_path.replace(['/', '\\'], "."))
 }
 
-fn is_special_java_entry(entry_name: &str) -> bool {
-    entry_name.ends_with("package-info.java") || entry_name.ends_with("module-info.java")
-}
-
 fn read_entry_to_string(entry: &mut zip::read::ZipFile<'_>) -> Result<String> {
     let mut bytes = Vec::new();
     entry.read_to_end(&mut bytes)?;
@@ -1,32 +1,358 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use tree_sitter::Parser;
 
-#[derive(Debug, Clone, Serialize)]
+/// The top-level types of one source file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompilationUnit {
+    /// The public type, or the first one when none is public. It also holds
+    /// the file's imports and comments.
+    pub primary: ClassStructure,
+    /// The other top-level types, e.g. package-private helpers, in
+    /// declaration order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub others: Vec<ClassStructure>,
+}
+
+impl CompilationUnit {
+    /// The primary type followed by the others.
+    pub fn types(&self) -> impl Iterator<Item = &ClassStructure> {
+        std::iter::once(&self.primary).chain(&self.others)
+    }
+
+    /// Makes the top-level type named `simple_name` the primary one, for a
+    /// lookup of a secondary type whose source is the whole file. The
+    /// others stay in declaration order; without a match nothing changes.
+    pub fn select(mut self, simple_name: &str) -> Self {
+        let Some(pos) = self.others.iter().position(|t| t.name == simple_name) else {
+            return self;
+        };
+        let mut selected = self.others.remove(pos);
+        selected.imports = std::mem::take(&mut self.primary.imports);
+        selected.comments = std::mem::take(&mut self.primary.comments);
+        self.others
+            .push(std::mem::replace(&mut self.primary, selected));
+        self.others.sort_by_key(|t| t.range.start_byte);
+        self
+    }
+
+    pub fn strip_javadoc(&mut self) {
+        self.primary.strip_javadoc();
+        for other in &mut self.others {
+            other.strip_javadoc();
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct ClassStructure {
+    /// Simple name of a top-level type, `Outer.Inner` for a nested one.
+    pub name: String,
     pub package: String,
     pub imports: Vec<String>,
     pub class_declaration: String,
+    pub kind: TypeKind,
+    /// Keyword modifiers of the declaration as written, e.g. `public`,
+    /// `abstract`, `sealed`; annotations are listed under `annotations`.
+    pub modifiers: Vec<String>,
+    /// Type parameters with their bounds, e.g. `T extends Comparable<T>`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub type_parameters: Vec<String>,
+    /// Components of a record, from its header.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub record_components: Vec<ParameterStructure>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
+    /// Direct super-interfaces: the `implements` list of a class, enum or
+    /// record, the `extends` list of an interface.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub implements: Vec<String>,
+    /// Subtypes allowed by a sealed type.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub permits: Vec<String>,
+    /// Annotations on the type declaration itself.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub annotations: Vec<AnnotationStructure>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub class_comment: Option<String>,
+    /// The `/** ... */` comment right before the declaration, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub javadoc: Option<String>,
+    #[serde(flatten)]
+    pub range: SourceRange,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub comments: Vec<String>,
-    pub fields: Vec<MemberStructure>,
-    pub methods: Vec<MemberStructure>,
+    pub fields: Vec<FieldStructure>,
+    /// Constants of an enum, in declaration order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub constants: Vec<EnumConstantStructure>,
+    /// `static final` fields (and interface fields) initialized with a
+    /// literal, by name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub constant_values: BTreeMap<String, ConstantValue>,
+    pub methods: Vec<MethodStructure>,
+    /// Member types (static nested, inner, interfaces, enums, records and
+    /// annotations) declared in the body, each with its own members.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub nested: Vec<ClassStructure>,
+}
+
+impl ClassStructure {
+    /// `constant_values` of the type and its nested types, the latter keyed
+    /// by their name relative to this type, e.g. `Inner.NAME`.
+    pub fn all_constant_values(&self) -> BTreeMap<String, ConstantValue> {
+        let mut values = self.constant_values.clone();
+        for nested in &self.nested {
+            let relative = nested
+                .name
+                .strip_prefix(&format!("{}.", self.name))
+                .unwrap_or(&nested.name);
+            for (name, value) in nested.all_constant_values() {
+                values.insert(format!("{relative}.{name}"), value);
+            }
+        }
+        values
+    }
+
+    /// Drops the `javadoc` of the type, its members and nested types, for
+    /// output that should stay compact.
+    pub fn strip_javadoc(&mut self) {
+        self.javadoc = None;
+        for field in &mut self.fields {
+            field.javadoc = None;
+        }
+        for constant in &mut self.constants {
+            constant.javadoc = None;
+        }
+        for method in &mut self.methods {
+            method.javadoc = None;
+        }
+        for nested in &mut self.nested {
+            nested.strip_javadoc();
+        }
+    }
+}
+
+/// Which kind of type declaration a [`ClassStructure`] comes from.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TypeKind {
+    #[default]
+    Class,
+    Interface,
+    Enum,
+    Record,
+    /// An `@interface` declaration.
+    Annotation,
+}
+
+impl TypeKind {
+    fn of(node: &tree_sitter::Node) -> Self {
+        match node.kind() {
+            "interface_declaration" => Self::Interface,
+            "enum_declaration" => Self::Enum,
+            "record_declaration" => Self::Record,
+            "annotation_type_declaration" => Self::Annotation,
+            _ => Self::Class,
+        }
+    }
 }
 
-#[derive(Debug, Clone, Serialize)]
-pub struct MemberStructure {
+/// One declarator of a field or interface constant declaration; `int a, b
+/// = 2;` gives two entries that share `declaration` and `comment`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldStructure {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_name: String,
+    pub modifiers: Vec<String>,
+    pub annotations: Vec<AnnotationStructure>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub initializer: Option<String>,
     pub declaration: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub comment: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub javadoc: Option<String>,
+    #[serde(flatten)]
+    pub range: SourceRange,
 }
 
-impl MemberStructure {
+impl FieldStructure {
     pub fn contains(&self, needle: &str) -> bool {
         self.declaration.contains(needle)
     }
 }
 
+/// The literal a constant field is initialized with, as written: numbers keep
+/// their suffix (`10L`), strings and chars their quotes, and a concatenation
+/// of string literals stays as source text, with single spaces around `+`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConstantValue {
+    #[serde(rename = "type")]
+    pub type_name: String,
+    pub value: String,
+}
+
+/// An annotation as written: `@Foo` has no arguments, `@Foo("x")` has `"x"`
+/// and `@Foo(a = 1, b = 2)` has `a = 1, b = 2`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnnotationStructure {
+    /// Name without the `@`, qualified if the source qualifies it.
+    pub name: String,
+    /// Text between the parentheses, whitespace-normalized.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub arguments: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnumConstantStructure {
+    pub name: String,
+    /// Constructor arguments, as written.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub arguments: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub javadoc: Option<String>,
+    #[serde(flatten)]
+    pub range: SourceRange,
+}
+
+/// A method, constructor (including a record's compact constructor, which has
+/// no parameter list) or annotation element, split into the parts of its
+/// declaration. `signature` is the whole declaration without the body.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MethodStructure {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub type_parameters: Vec<String>,
+    /// `None` for constructors.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub return_type: Option<String>,
+    pub parameters: Vec<ParameterStructure>,
+    pub modifiers: Vec<String>,
+    pub annotations: Vec<AnnotationStructure>,
+    pub throws: Vec<String>,
+    pub is_constructor: bool,
+    pub signature: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub javadoc: Option<String>,
+    #[serde(flatten)]
+    pub range: SourceRange,
+}
+
+impl MethodStructure {
+    pub fn contains(&self, needle: &str) -> bool {
+        self.signature.contains(needle)
+    }
+}
+
+/// A method to look for, written `name` (any parameters) or `name(T1, T2)`.
+/// Parameter types compare without package qualifiers and whitespace, and
+/// `*` in one matches any run of characters, so `parse(*)` is any
+/// one-parameter `parse`. Constructors are looked up by the simple class name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MethodQuery {
+    pub name: String,
+    /// `None` when the query has no parameter list.
+    pub parameter_types: Option<Vec<String>>,
+}
+
+impl MethodQuery {
+    pub fn parse(query: &str) -> anyhow::Result<Self> {
+        let query = query.trim();
+        let (name, parameter_types) = match query.split_once('(') {
+            None => (query, None),
+            Some((name, rest)) => {
+                let params = rest
+                    .strip_suffix(')')
+                    .ok_or_else(|| anyhow::anyhow!("Unclosed parameter list in {query:?}"))?;
+                let types = split_top_level(params, ',')
+                    .into_iter()
+                    .map(|t| simple_type(t.trim()))
+                    .filter(|t| !t.is_empty())
+                    .collect();
+                (name.trim(), Some(types))
+            }
+        };
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+        {
+            anyhow::bail!("Invalid method name in {query:?}");
+        }
+        Ok(Self {
+            name: name.to_string(),
+            parameter_types,
+        })
+    }
+
+    pub fn matches(&self, method: &MethodStructure) -> bool {
+        if method.name != self.name {
+            return false;
+        }
+        let Some(types) = &self.parameter_types else {
+            return true;
+        };
+        types.len() == method.parameters.len()
+            && types
+                .iter()
+                .zip(&method.parameters)
+                .all(|(pattern, p)| wildcard_match(pattern, &simple_type(&p.type_name)))
+    }
+
+    /// Matching methods of `structure` and its nested types, each with the
+    /// type declaring it.
+    pub fn find_in<'a>(
+        &self,
+        structure: &'a ClassStructure,
+    ) -> Vec<(&'a ClassStructure, &'a MethodStructure)> {
+        let mut found: Vec<_> = structure
+            .methods
+            .iter()
+            .filter(|m| self.matches(m))
+            .map(|m| (structure, m))
+            .collect();
+        for nested in &structure.nested {
+            found.extend(self.find_in(nested));
+        }
+        found
+    }
+}
+
+/// Where a declaration sits in the parsed source: 1-based, inclusive lines and
+/// the byte range `start_byte..end_byte` of the same string, from the
+/// declaration's first modifier or annotation to its closing brace or `;`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceRange {
+    pub start_line: usize,
+    pub end_line: usize,
+    pub start_byte: usize,
+    pub end_byte: usize,
+}
+
+impl SourceRange {
+    fn of(node: &tree_sitter::Node) -> Self {
+        Self {
+            start_line: node.start_position().row + 1,
+            end_line: node.end_position().row + 1,
+            start_byte: node.start_byte(),
+            end_byte: node.end_byte(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParameterStructure {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_name: String,
+}
+
 #[derive(Debug, Clone)]
 struct SourceComment {
     start_byte: usize,
@@ -47,6 +373,28 @@ impl SourceComments {
         Self { comments }
     }
 
+    /// The comment directly before `node` (only whitespace in between) when
+    /// it is a Javadoc comment.
+    fn javadoc(
+        &self,
+        node: &tree_sitter::Node<'_>,
+        source: &str,
+        stop_at_byte: usize,
+    ) -> Option<String> {
+        let node_start = node.start_byte();
+        let comment =
+            self.comments.iter().rev().find(|comment| {
+                comment.end_byte <= node_start && comment.start_byte >= stop_at_byte
+            })?;
+        if has_non_comment_code(&source[comment.end_byte..node_start])
+            || !comment.text.starts_with("/**")
+            || comment.text == "/**/"
+        {
+            return None;
+        }
+        Some(comment.text.clone())
+    }
+
     fn all_texts(&self) -> Vec<String> {
         self.comments
             .iter()
@@ -87,7 +435,17 @@ impl SourceComments {
     }
 }
 
+/// Structure of the primary top-level type of `source`; see
+/// [`parse_compilation_unit`].
 pub fn parse_class_structure(source: &str) -> Option<ClassStructure> {
+    parse_compilation_unit(source).map(|unit| unit.primary)
+}
+
+/// Structures of every top-level type in `source`. The primary one is the
+/// first `public` type, or the first type when none is public; a source
+/// with no type declaration (`package-info`) gives an unnamed primary
+/// holding just the package and imports.
+pub fn parse_compilation_unit(source: &str) -> Option<CompilationUnit> {
     if source.trim().is_empty() {
         return None;
     }
@@ -103,10 +461,7 @@ pub fn parse_class_structure(source: &str) -> Option<ClassStructure> {
 
     let mut package = String::new();
     let mut imports = Vec::new();
-    let mut class_declaration = String::new();
-    let mut class_comment = None;
-    let mut fields = Vec::new();
-    let mut methods = Vec::new();
+    let mut declarations = Vec::new();
 
     let mut cursor = root.walk();
     for child in root.children(&mut cursor) {
@@ -119,28 +474,141 @@ pub fn parse_class_structure(source: &str) -> Option<ClassStructure> {
                     imports.push(imp);
                 }
             }
-            "class_declaration"
-            | "interface_declaration"
-            | "enum_declaration"
-            | "record_declaration"
-            | "annotation_type_declaration" => {
-                class_declaration = extract_class_declaration(&child, bytes);
-                class_comment = comments.leading_comment(&child, source, 0);
-                extract_members(&child, source, bytes, &comments, &mut fields, &mut methods);
-            }
+            kind if TYPE_DECLARATION_KINDS.contains(&kind) => declarations.push(child),
             _ => {}
         }
     }
 
-    Some(ClassStructure {
-        package,
-        imports,
-        class_declaration,
-        class_comment,
-        comments: comments.all_texts(),
+    let primary_index = declarations
+        .iter()
+        .position(|node| split_modifiers(node, bytes).0.iter().any(|m| m == "public"))
+        .unwrap_or(0);
+    let mut types: Vec<ClassStructure> = declarations
+        .iter()
+        .map(|node| {
+            let name = type_name(node, bytes).to_string();
+            extract_type(node, name, &package, 0, source, &comments)
+        })
+        .collect();
+    let mut primary = if types.is_empty() {
+        ClassStructure {
+            package,
+            ..ClassStructure::default()
+        }
+    } else {
+        types.remove(primary_index)
+    };
+    primary.imports = imports;
+    primary.comments = comments.all_texts();
+
+    Some(CompilationUnit {
+        primary,
+        others: types,
+    })
+}
+
+/// Structure of the type declared by `node`, with its member types nested
+/// under it. Imports and the file's comment list are left empty: they belong
+/// to the primary top-level structure.
+fn extract_type(
+    node: &tree_sitter::Node,
+    name: String,
+    package: &str,
+    scope_start: usize,
+    source_text: &str,
+    comments: &SourceComments,
+) -> ClassStructure {
+    let source = source_text.as_bytes();
+    let supertypes = extract_supertypes(node, source);
+    let mut fields = Vec::new();
+    let mut constants = Vec::new();
+    let mut constant_values = BTreeMap::new();
+    let mut methods = Vec::new();
+    extract_members(
+        node,
+        source_text,
+        comments,
+        &mut fields,
+        &mut constants,
+        &mut constant_values,
+        &mut methods,
+    );
+    let (modifiers, annotations) = split_modifiers(node, source);
+    ClassStructure {
+        class_declaration: extract_class_declaration(node, source),
+        kind: TypeKind::of(node),
+        modifiers,
+        type_parameters: extract_type_parameters(node, source),
+        record_components: extract_record_components(node, source),
+        extends: supertypes.extends,
+        implements: supertypes.implements,
+        permits: supertypes.permits,
+        annotations,
+        class_comment: comments.leading_comment(node, source_text, scope_start),
+        javadoc: comments.javadoc(node, source_text, scope_start),
+        range: SourceRange::of(node),
+        nested: extract_nested(node, &name, package, source_text, comments),
+        name,
+        package: package.to_string(),
+        imports: Vec::new(),
+        comments: Vec::new(),
         fields,
+        constants,
+        constant_values,
         methods,
-    })
+    }
+}
+
+/// Structures of the member types declared in `node`'s body, recursively,
+/// named `prefix.Simple`.
+fn extract_nested(
+    node: &tree_sitter::Node,
+    prefix: &str,
+    package: &str,
+    source_text: &str,
+    comments: &SourceComments,
+) -> Vec<ClassStructure> {
+    let source = source_text.as_bytes();
+    member_type_declarations(node)
+        .into_iter()
+        .map(|(member, scope_start)| {
+            let name = format!("{prefix}.{}", type_name(&member, source));
+            extract_type(&member, name, package, scope_start, source_text, comments)
+        })
+        .collect()
+}
+
+/// Type declarations among the members of `node`'s body, including those
+/// after the constants of an enum, each with the start of the body that
+/// holds it.
+fn member_type_declarations<'a>(
+    node: &tree_sitter::Node<'a>,
+) -> Vec<(tree_sitter::Node<'a>, usize)> {
+    let Some(body) = find_body(node) else {
+        return Vec::new();
+    };
+    let mut members = Vec::new();
+    let mut cursor = body.walk();
+    for child in body.named_children(&mut cursor) {
+        if child.kind() == "enum_body_declarations" {
+            let mut inner = child.walk();
+            members.extend(
+                child
+                    .named_children(&mut inner)
+                    .map(|member| (member, child.start_byte())),
+            );
+        } else {
+            members.push((child, body.start_byte()));
+        }
+    }
+    members.retain(|(member, _)| TYPE_DECLARATION_KINDS.contains(&member.kind()));
+    members
+}
+
+fn type_name<'a>(node: &tree_sitter::Node, source: &'a [u8]) -> &'a str {
+    node.child_by_field_name("name")
+        .map(|name| node_text(&name, source))
+        .unwrap_or("")
 }
 
 fn extract_package(node: &tree_sitter::Node, source: &[u8]) -> String {
@@ -194,90 +662,313 @@ fn extract_class_declaration(node: &tree_sitter::Node, source: &[u8]) -> String
     result.trim().to_string()
 }
 
+#[derive(Debug, Default)]
+struct Supertypes {
+    extends: Option<String>,
+    implements: Vec<String>,
+    permits: Vec<String>,
+}
+
+/// Types named by the `superclass`, `super_interfaces`, `extends_interfaces`
+/// and `permits` clauses of a type declaration.
+fn extract_supertypes(node: &tree_sitter::Node, source: &[u8]) -> Supertypes {
+    let type_list = |clause: &tree_sitter::Node| -> Vec<String> {
+        let mut types = Vec::new();
+        let mut cursor = clause.walk();
+        for list in clause.named_children(&mut cursor) {
+            let mut inner = list.walk();
+            types.extend(
+                list.named_children(&mut inner)
+                    .map(|ty| normalize_whitespace(node_text(&ty, source))),
+            );
+        }
+        types
+    };
+
+    let mut supertypes = Supertypes::default();
+    let mut cursor = node.walk();
+    for clause in node.named_children(&mut cursor) {
+        match clause.kind() {
+            "superclass" => {
+                supertypes.extends = clause
+                    .named_child(0)
+                    .map(|ty| normalize_whitespace(node_text(&ty, source)));
+            }
+            "super_interfaces" | "extends_interfaces" => {
+                supertypes.implements.extend(type_list(&clause));
+            }
+            "permits" => supertypes.permits = type_list(&clause),
+            _ => {}
+        }
+    }
+    supertypes
+}
+
 fn extract_members(
     node: &tree_sitter::Node,
     source_text: &str,
-    source: &[u8],
     comments: &SourceComments,
-    fields: &mut Vec<MemberStructure>,
-    methods: &mut Vec<MemberStructure>,
+    fields: &mut Vec<FieldStructure>,
+    constants: &mut Vec<EnumConstantStructure>,
+    constant_values: &mut BTreeMap<String, ConstantValue>,
+    methods: &mut Vec<MethodStructure>,
 ) {
+    let source = source_text.as_bytes();
     let body = find_body(node);
     let body = match body {
         Some(b) => b,
         None => return,
     };
 
+    let mut members = Vec::new();
     let mut cursor = body.walk();
     for child in body.children(&mut cursor) {
+        if child.kind() == "enum_body_declarations" {
+            let mut inner = child.walk();
+            members.extend(
+                child
+                    .children(&mut inner)
+                    .map(|member| (member, child.start_byte())),
+            );
+        } else {
+            members.push((child, body.start_byte()));
+        }
+    }
+
+    for (child, scope_start) in members {
+        let comment = || comments.leading_comment(&child, source_text, scope_start);
+        let javadoc = || comments.javadoc(&child, source_text, scope_start);
         match child.kind() {
-            "field_declaration" => {
-                fields.push(MemberStructure {
-                    declaration: normalize_whitespace(node_text(&child, source)),
-                    comment: comments.leading_comment(&child, source_text, body.start_byte()),
-                });
+            "field_declaration" | "constant_declaration" => {
+                let start = fields.len();
+                fields.extend(extract_fields(&child, source, comment(), javadoc()));
+                let implicitly_constant = child.kind() == "constant_declaration";
+                let mut cursor = child.walk();
+                let declarators = child.children_by_field_name("declarator", &mut cursor);
+                for (field, declarator) in fields[start..].iter().zip(declarators) {
+                    let is_static_final = implicitly_constant
+                        || ["static", "final"]
+                            .iter()
+                            .all(|m| field.modifiers.iter().any(|f| f == m));
+                    let Some(value) = declarator.child_by_field_name("value") else {
+                        continue;
+                    };
+                    if is_static_final && is_constant_literal(&value, source) {
+                        constant_values.insert(
+                            field.name.clone(),
+                            ConstantValue {
+                                type_name: field.type_name.clone(),
+                                value: constant_text(&value, source),
+                            },
+                        );
+                    }
+                }
             }
-            "method_declaration" | "constructor_declaration" => {
-                if let Some(sig) = extract_method_signature(&child, source) {
-                    methods.push(MemberStructure {
-                        declaration: sig,
-                        comment: comments.leading_comment(&child, source_text, body.start_byte()),
+            "method_declaration"
+            | "constructor_declaration"
+            | "compact_constructor_declaration" => {
+                if let Some(method) = extract_method(&child, source) {
+                    methods.push(MethodStructure {
+                        comment: comment(),
+                        javadoc: javadoc(),
+                        ..method
                     });
                 }
             }
             "annotation_type_element_declaration" => {
-                let text = normalize_whitespace(node_text(&child, source));
-                methods.push(MemberStructure {
-                    declaration: text,
-                    comment: comments.leading_comment(&child, source_text, body.start_byte()),
-                });
-            }
-            "constant_declaration" => {
-                fields.push(MemberStructure {
-                    declaration: normalize_whitespace(node_text(&child, source)),
-                    comment: comments.leading_comment(&child, source_text, body.start_byte()),
-                });
+                if let Some(method) = extract_method(&child, source) {
+                    methods.push(MethodStructure {
+                        signature: normalize_whitespace(node_text(&child, source)),
+                        comment: comment(),
+                        javadoc: javadoc(),
+                        ..method
+                    });
+                }
             }
             "enum_constant" => {
-                fields.push(MemberStructure {
-                    declaration: normalize_whitespace(node_text(&child, source)),
-                    comment: comments.leading_comment(&child, source_text, body.start_byte()),
+                let mut arguments = Vec::new();
+                if let Some(args) = child.child_by_field_name("arguments") {
+                    let mut inner = args.walk();
+                    arguments.extend(
+                        args.named_children(&mut inner)
+                            .filter(|arg| !arg.is_extra())
+                            .map(|arg| normalize_whitespace(node_text(&arg, source))),
+                    );
+                }
+                constants.push(EnumConstantStructure {
+                    name: type_name(&child, source).to_string(),
+                    arguments,
+                    comment: comment(),
+                    javadoc: javadoc(),
+                    range: SourceRange::of(&child),
                 });
             }
-            "enum_body_declarations" => {
-                let mut inner_cursor = child.walk();
-                for inner in child.children(&mut inner_cursor) {
-                    match inner.kind() {
-                        "field_declaration" => {
-                            fields.push(MemberStructure {
-                                declaration: normalize_whitespace(node_text(&inner, source)),
-                                comment: comments.leading_comment(
-                                    &inner,
-                                    source_text,
-                                    child.start_byte(),
-                                ),
-                            });
-                        }
-                        "method_declaration" | "constructor_declaration" => {
-                            if let Some(sig) = extract_method_signature(&inner, source) {
-                                methods.push(MemberStructure {
-                                    declaration: sig,
-                                    comment: comments.leading_comment(
-                                        &inner,
-                                        source_text,
-                                        child.start_byte(),
-                                    ),
-                                });
-                            }
-                        }
-                        _ => {}
-                    }
+            _ => {}
+        }
+    }
+}
+
+/// One entry per declarator of a field or constant declaration. Array
+/// dimensions written after a declarator's name (`int a[]`) are moved onto
+/// its type.
+fn extract_fields(
+    node: &tree_sitter::Node,
+    source: &[u8],
+    comment: Option<String>,
+    javadoc: Option<String>,
+) -> Vec<FieldStructure> {
+    let Some(ty) = node.child_by_field_name("type") else {
+        return Vec::new();
+    };
+    let type_name = normalize_whitespace(node_text(&ty, source));
+    let declaration = normalize_whitespace(node_text(node, source));
+    let (modifiers, annotations) = split_modifiers(node, source);
+
+    let mut fields = Vec::new();
+    let mut cursor = node.walk();
+    for declarator in node.children_by_field_name("declarator", &mut cursor) {
+        let Some(name) = declarator.child_by_field_name("name") else {
+            continue;
+        };
+        let mut field_type = type_name.clone();
+        if let Some(dims) = declarator.child_by_field_name("dimensions") {
+            field_type.push_str(&normalize_whitespace(node_text(&dims, source)));
+        }
+        fields.push(FieldStructure {
+            name: node_text(&name, source).to_string(),
+            type_name: field_type,
+            modifiers: modifiers.clone(),
+            annotations: annotations.clone(),
+            initializer: declarator
+                .child_by_field_name("value")
+                .map(|value| normalize_whitespace(node_text(&value, source))),
+            declaration: declaration.clone(),
+            comment: comment.clone(),
+            javadoc: javadoc.clone(),
+            range: SourceRange::of(node),
+        });
+    }
+    fields
+}
+
+/// Whether `node` is a numeric, string, char or boolean literal, a signed
+/// number, or a concatenation of string literals, possibly parenthesized.
+fn is_constant_literal(node: &tree_sitter::Node, source: &[u8]) -> bool {
+    match node.kind() {
+        kind if is_numeric_literal(kind) => true,
+        "character_literal" | "string_literal" | "true" | "false" => true,
+        "unary_expression" => {
+            node.child_by_field_name("operator")
+                .is_some_and(|op| matches!(node_text(&op, source), "-" | "+"))
+                && node
+                    .child_by_field_name("operand")
+                    .is_some_and(|operand| is_numeric_literal(operand.kind()))
+        }
+        "binary_expression" => is_string_concatenation(node, source),
+        "parenthesized_expression" => node
+            .named_child(0)
+            .is_some_and(|inner| is_constant_literal(&inner, source)),
+        _ => false,
+    }
+}
+
+/// Source text of a node accepted by [`is_constant_literal`], with the
+/// literals kept verbatim and single spaces around `+`.
+fn constant_text(node: &tree_sitter::Node, source: &[u8]) -> String {
+    match node.kind() {
+        "binary_expression" => {
+            let side = |name| {
+                node.child_by_field_name(name)
+                    .map(|operand| constant_text(&operand, source))
+                    .unwrap_or_default()
+            };
+            format!("{} + {}", side("left"), side("right"))
+        }
+        "parenthesized_expression" | "unary_expression" => {
+            let mut text = String::new();
+            let mut cursor = node.walk();
+            for child in node.children(&mut cursor) {
+                if !child.is_extra() {
+                    text.push_str(&constant_text(&child, source));
                 }
             }
-            "record_declaration_body" => {}
-            _ => {}
+            text
+        }
+        _ => node_text(node, source).to_string(),
+    }
+}
+
+fn is_numeric_literal(kind: &str) -> bool {
+    matches!(
+        kind,
+        "decimal_integer_literal"
+            | "hex_integer_literal"
+            | "octal_integer_literal"
+            | "binary_integer_literal"
+            | "decimal_floating_point_literal"
+            | "hex_floating_point_literal"
+    )
+}
+
+fn is_string_concatenation(node: &tree_sitter::Node, source: &[u8]) -> bool {
+    match node.kind() {
+        "string_literal" => true,
+        "parenthesized_expression" => node
+            .named_child(0)
+            .is_some_and(|inner| is_string_concatenation(&inner, source)),
+        "binary_expression" => {
+            node.child_by_field_name("operator")
+                .is_some_and(|op| node_text(&op, source) == "+")
+                && ["left", "right"].iter().all(|side| {
+                    node.child_by_field_name(side)
+                        .is_some_and(|operand| is_string_concatenation(&operand, source))
+                })
+        }
+        _ => false,
+    }
+}
+
+/// Keywords and annotations of `node`'s `modifiers` child, apart.
+fn split_modifiers(
+    node: &tree_sitter::Node,
+    source: &[u8],
+) -> (Vec<String>, Vec<AnnotationStructure>) {
+    let mut modifiers = Vec::new();
+    let mut annotations = Vec::new();
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        if child.kind() != "modifiers" {
+            continue;
+        }
+        let mut inner = child.walk();
+        for modifier in child.children(&mut inner) {
+            match modifier.kind() {
+                "marker_annotation" | "annotation" => {
+                    annotations.extend(extract_annotation(&modifier, source));
+                }
+                "line_comment" | "block_comment" => {}
+                _ => modifiers.push(normalize_whitespace(node_text(&modifier, source))),
+            }
         }
     }
+    (modifiers, annotations)
+}
+
+fn extract_annotation(node: &tree_sitter::Node, source: &[u8]) -> Option<AnnotationStructure> {
+    let name = node.child_by_field_name("name")?;
+    let arguments = node.child_by_field_name("arguments").and_then(|args| {
+        let text = node_text(&args, source);
+        let inner = text
+            .strip_prefix('(')
+            .and_then(|t| t.strip_suffix(')'))
+            .unwrap_or(text);
+        let inner = normalize_whitespace(inner);
+        (!inner.is_empty()).then_some(inner)
+    });
+    Some(AnnotationStructure {
+        name: normalize_whitespace(node_text(&name, source)),
+        arguments,
+    })
 }
 
 fn find_body<'a>(node: &tree_sitter::Node<'a>) -> Option<tree_sitter::Node<'a>> {
@@ -313,61 +1004,334 @@ fn extract_method_signature(node: &tree_sitter::Node, source: &[u8]) -> Option<S
         }
     }
 
-    let sig = result.trim().to_string();
+    let sig = normalize_whitespace(&result);
     if sig.is_empty() { None } else { Some(sig) }
 }
 
-fn node_text<'a>(node: &tree_sitter::Node, source: &'a [u8]) -> &'a str {
-    node.utf8_text(source).unwrap_or("")
-}
-
-fn normalize_whitespace(s: &str) -> String {
-    s.split_whitespace().collect::<Vec<_>>().join(" ")
-}
-
-fn needs_no_leading_space(kind: &str) -> bool {
-    matches!(
-        kind,
-        "type_parameters" | "formal_parameters" | "type_arguments"
-    )
-}
-
-fn collect_comments(
-    node: &tree_sitter::Node<'_>,
-    source: &[u8],
-    comments: &mut Vec<SourceComment>,
-) {
-    if matches!(node.kind(), "block_comment" | "line_comment") {
-        comments.push(SourceComment {
-            start_byte: node.start_byte(),
-            end_byte: node.end_byte(),
-            text: node_text(node, source).trim().to_string(),
-        });
-        return;
-    }
+/// Splits a method, constructor or annotation element declaration into its
+/// parts, using the grammar's named children.
+fn extract_method(node: &tree_sitter::Node, source: &[u8]) -> Option<MethodStructure> {
+    let signature = extract_method_signature(node, source)?;
+    let is_constructor = matches!(
+        node.kind(),
+        "constructor_declaration" | "compact_constructor_declaration"
+    );
+    let return_type = if is_constructor {
+        None
+    } else {
+        node.child_by_field_name("type").map(|ty| {
+            let mut ty = normalize_whitespace(node_text(&ty, source));
+            if let Some(dims) = node.child_by_field_name("dimensions") {
+                ty.push_str(&normalize_whitespace(node_text(&dims, source)));
+            }
+            ty
+        })
+    };
 
+    let (modifiers, annotations) = split_modifiers(node, source);
+    let mut throws = Vec::new();
     let mut cursor = node.walk();
     for child in node.children(&mut cursor) {
-        collect_comments(&child, source, comments);
+        if child.kind() == "throws" {
+            let mut inner = child.walk();
+            throws.extend(
+                child
+                    .named_children(&mut inner)
+                    .map(|ty| normalize_whitespace(node_text(&ty, source))),
+            );
+        }
+    }
+
+    let mut parameters = Vec::new();
+    if let Some(formal) = node.child_by_field_name("parameters") {
+        let mut inner = formal.walk();
+        for param in formal.named_children(&mut inner) {
+            if let Some(param) = extract_parameter(&param, source) {
+                parameters.push(param);
+            }
+        }
     }
+
+    Some(MethodStructure {
+        name: type_name(node, source).to_string(),
+        type_parameters: extract_type_parameters(node, source),
+        return_type,
+        parameters,
+        modifiers,
+        annotations,
+        throws,
+        is_constructor,
+        signature,
+        comment: None,
+        javadoc: None,
+        range: SourceRange::of(node),
+    })
 }
 
-fn has_non_comment_code(segment: &str) -> bool {
-    !segment.trim().is_empty()
+/// Each type parameter of a generic type or method, bounds included.
+fn extract_type_parameters(node: &tree_sitter::Node, source: &[u8]) -> Vec<String> {
+    let mut cursor = node.walk();
+    let Some(params) = node
+        .named_children(&mut cursor)
+        .find(|child| child.kind() == "type_parameters")
+    else {
+        return Vec::new();
+    };
+    let mut inner = params.walk();
+    params
+        .named_children(&mut inner)
+        .map(|param| normalize_whitespace(node_text(&param, source)))
+        .collect()
 }
 
-fn starts_line_comment(source: &str, comment_start: usize) -> bool {
-    let line_start = source[..comment_start]
-        .rfind('\n')
-        .map(|pos| pos + 1)
-        .unwrap_or(0);
-    source[line_start..comment_start].trim().is_empty()
+fn extract_record_components(node: &tree_sitter::Node, source: &[u8]) -> Vec<ParameterStructure> {
+    if node.kind() != "record_declaration" {
+        return Vec::new();
+    }
+    let Some(params) = node.child_by_field_name("parameters") else {
+        return Vec::new();
+    };
+    let mut cursor = params.walk();
+    params
+        .named_children(&mut cursor)
+        .filter_map(|param| extract_parameter(&param, source))
+        .collect()
 }
 
-#[cfg(test)]
+fn extract_parameter(node: &tree_sitter::Node, source: &[u8]) -> Option<ParameterStructure> {
+    match node.kind() {
+        "formal_parameter" => {
+            let mut type_name =
+                normalize_whitespace(node_text(&node.child_by_field_name("type")?, source));
+            if let Some(dims) = node.child_by_field_name("dimensions") {
+                type_name.push_str(&normalize_whitespace(node_text(&dims, source)));
+            }
+            Some(ParameterStructure {
+                name: node_text(&node.child_by_field_name("name")?, source).to_string(),
+                type_name,
+            })
+        }
+        // `String... args`: the type and the declarator are plain children.
+        "spread_parameter" => {
+            let mut type_name = None;
+            let mut name = None;
+            let mut cursor = node.walk();
+            for child in node.named_children(&mut cursor) {
+                match child.kind() {
+                    "modifiers" => {}
+                    "variable_declarator" => {
+                        name = child
+                            .child_by_field_name("name")
+                            .map(|n| node_text(&n, source).to_string());
+                    }
+                    _ if type_name.is_none() => {
+                        type_name = Some(format!(
+                            "{}...",
+                            normalize_whitespace(node_text(&child, source))
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+            Some(ParameterStructure {
+                name: name?,
+                type_name: type_name?,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// A member type declared inside another type's body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NestedType {
+    /// Simple names from the top-level type down, joined with `$`.
+    pub binary_name: String,
+    pub source: String,
+}
+
+const TYPE_DECLARATION_KINDS: [&str; 5] = [
+    "class_declaration",
+    "interface_declaration",
+    "enum_declaration",
+    "record_declaration",
+    "annotation_type_declaration",
+];
+
+/// Member types of every top-level type in `source`, at any depth, outermost
+/// first. Local and anonymous classes are not members and are skipped.
+pub fn nested_types(source: &str) -> Vec<NestedType> {
+    let mut parser = Parser::new();
+    if parser
+        .set_language(&tree_sitter_java::LANGUAGE.into())
+        .is_err()
+    {
+        return Vec::new();
+    }
+    let Some(tree) = parser.parse(source, None) else {
+        return Vec::new();
+    };
+    let root = tree.root_node();
+    let bytes = source.as_bytes();
+
+    let mut nested = Vec::new();
+    let mut cursor = root.walk();
+    for child in root.children(&mut cursor) {
+        if TYPE_DECLARATION_KINDS.contains(&child.kind())
+            && let Some(name) = child.child_by_field_name("name")
+        {
+            collect_nested_types(&child, node_text(&name, bytes), bytes, &mut nested);
+        }
+    }
+    nested
+}
+
+fn collect_nested_types(
+    node: &tree_sitter::Node,
+    prefix: &str,
+    source: &[u8],
+    nested: &mut Vec<NestedType>,
+) {
+    for (member, _) in member_type_declarations(node) {
+        let Some(name) = member.child_by_field_name("name") else {
+            continue;
+        };
+        let binary_name = format!("{prefix}${}", node_text(&name, source));
+        nested.push(NestedType {
+            binary_name: binary_name.clone(),
+            source: node_text(&member, source).to_string(),
+        });
+        collect_nested_types(&member, &binary_name, source, nested);
+    }
+}
+
+/// Splits on `separator` outside of `<...>`, so `Map<K, V>, int` gives two.
+fn split_top_level(s: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth = depth.saturating_sub(1),
+            c if c == separator && depth == 0 => {
+                parts.push(&s[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+/// `java.util.Map<java.lang.String, Object>` as `Map<String,Object>`: no
+/// whitespace, every dotted name reduced to its last segment.
+fn simple_type(type_name: &str) -> String {
+    let mut out = String::new();
+    let mut name = String::new();
+    let flush = |name: &mut String, out: &mut String| {
+        let (base, varargs) = match name.strip_suffix("...") {
+            Some(base) => (base, "..."),
+            None => (name.as_str(), ""),
+        };
+        out.push_str(base.rsplit('.').next().unwrap_or(base));
+        out.push_str(varargs);
+        name.clear();
+    };
+    for c in type_name.chars() {
+        if c.is_alphanumeric() || c == '_' || c == '$' || c == '.' {
+            name.push(c);
+        } else {
+            flush(&mut name, &mut out);
+            if !c.is_whitespace() {
+                out.push(c);
+            }
+        }
+    }
+    flush(&mut name, &mut out);
+    out
+}
+
+/// Whether `text` matches `pattern`, where `*` stands for any run of
+/// characters and everything else is literal.
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts: Vec<&str> = parts.collect();
+    let Some(last) = parts.pop() else {
+        return rest.is_empty();
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(pos) => rest = &rest[pos + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+fn node_text<'a>(node: &tree_sitter::Node, source: &'a [u8]) -> &'a str {
+    node.utf8_text(source).unwrap_or("")
+}
+
+fn normalize_whitespace(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn needs_no_leading_space(kind: &str) -> bool {
+    matches!(
+        kind,
+        "type_parameters" | "formal_parameters" | "type_arguments"
+    )
+}
+
+fn collect_comments(
+    node: &tree_sitter::Node<'_>,
+    source: &[u8],
+    comments: &mut Vec<SourceComment>,
+) {
+    if matches!(node.kind(), "block_comment" | "line_comment") {
+        comments.push(SourceComment {
+            start_byte: node.start_byte(),
+            end_byte: node.end_byte(),
+            text: node_text(node, source).trim().to_string(),
+        });
+        return;
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_comments(&child, source, comments);
+    }
+}
+
+fn has_non_comment_code(segment: &str) -> bool {
+    !segment.trim().is_empty()
+}
+
+fn starts_line_comment(source: &str, comment_start: usize) -> bool {
+    let line_start = source[..comment_start]
+        .rfind('\n')
+        .map(|pos| pos + 1)
+        .unwrap_or(0);
+    source[line_start..comment_start].trim().is_empty()
+}
+
+#[cfg(test)]
 mod tests {
     use super::*;
 
+    fn annotation(name: &str, arguments: Option<&str>) -> AnnotationStructure {
+        AnnotationStructure {
+            name: name.to_string(),
+            arguments: arguments.map(str::to_string),
+        }
+    }
+
     #[test]
     fn parse_simple_class() {
         let source = r#"
@@ -401,6 +1365,8 @@ public class Foo extends Bar implements Baz {
                 .class_declaration
                 .contains("public class Foo extends Bar implements Baz")
         );
+        assert_eq!(result.extends.as_deref(), Some("Bar"));
+        assert_eq!(result.implements, vec!["Baz"]);
         assert_eq!(result.fields.len(), 2);
         assert!(result.fields[0].contains("private String name"));
         assert!(result.fields[1].contains("private int count"));
@@ -408,6 +1374,25 @@ public class Foo extends Bar implements Baz {
         assert!(result.methods[0].contains("public Foo(String name)"));
         assert!(result.methods[1].contains("public String getName()"));
         assert!(result.methods[2].contains("public void setName(String name)"));
+
+        let ctor = &result.methods[0];
+        assert!(ctor.is_constructor);
+        assert_eq!(ctor.name, "Foo");
+        assert_eq!(ctor.return_type, None);
+        assert_eq!(ctor.modifiers, vec!["public"]);
+        assert_eq!(
+            ctor.parameters,
+            vec![ParameterStructure {
+                name: "name".to_string(),
+                type_name: "String".to_string(),
+            }]
+        );
+        let getter = &result.methods[1];
+        assert!(!getter.is_constructor);
+        assert_eq!(getter.name, "getName");
+        assert_eq!(getter.return_type.as_deref(), Some("String"));
+        assert!(getter.parameters.is_empty());
+        assert_eq!(result.methods[2].return_type.as_deref(), Some("void"));
     }
 
     #[test]
@@ -430,6 +1415,12 @@ public interface Service<T> {
         assert_eq!(result.methods.len(), 2);
         assert!(result.methods[0].contains("T find(String id)"));
         assert!(result.methods[1].contains("void save(T entity)"));
+        assert_eq!(result.type_parameters, vec!["T"]);
+        assert_eq!(result.methods[0].name, "find");
+        assert_eq!(result.methods[0].return_type.as_deref(), Some("T"));
+        assert!(result.methods[0].modifiers.is_empty());
+        assert_eq!(result.methods[1].parameters[0].type_name, "T");
+        assert_eq!(result.methods[1].parameters[0].name, "entity");
     }
 
     #[test]
@@ -451,7 +1442,9 @@ public enum Color {
 "#;
         let result = parse_class_structure(source).unwrap();
         assert!(result.class_declaration.contains("public enum Color"));
-        assert!(result.fields.iter().any(|f| f.contains("RED")));
+        let constants: Vec<&str> = result.constants.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(constants, vec!["RED", "GREEN", "BLUE"]);
+        assert_eq!(result.fields.len(), 1);
         assert!(
             result
                 .fields
@@ -484,6 +1477,10 @@ public @interface Component {
         assert_eq!(result.imports.len(), 5);
         assert!(result.class_declaration.contains("@interface Component"));
         assert!(result.methods.iter().any(|m| m.contains("value()")));
+        assert_eq!(result.methods[0].signature, "String value() default \"\";");
+        assert_eq!(result.methods[0].name, "value");
+        assert_eq!(result.methods[0].return_type.as_deref(), Some("String"));
+        assert!(result.methods[0].parameters.is_empty());
     }
 
     #[test]
@@ -513,6 +1510,66 @@ public abstract class AbstractRepository<T, ID> implements Repository<T, ID> {
         );
         assert!(result.fields.iter().any(|f| f.contains("EntityManager em")));
         assert_eq!(result.methods.len(), 2);
+        assert_eq!(
+            result.methods[0].annotations,
+            vec![annotation("Override", None)]
+        );
+        assert_eq!(result.methods[0].modifiers, vec!["public"]);
+        assert_eq!(result.methods[0].parameters[0].type_name, "ID");
+        assert!(result.methods[1].annotations.is_empty());
+        assert_eq!(result.methods[1].modifiers, vec!["protected", "abstract"]);
+        assert_eq!(result.methods[1].return_type.as_deref(), Some("Class<T>"));
+    }
+
+    #[test]
+    fn parse_method_throws_varargs_and_arrays() {
+        let source = r#"
+package org.example;
+
+public class Io {
+    @Deprecated
+    @SuppressWarnings("unchecked")
+    public static <T> List<T> read(final Path path, String[] names, int... sizes)
+            throws IOException, InterruptedException {
+        return null;
+    }
+
+    protected Io(Map<String, List<Integer>> index) throws Exception {}
+}
+"#;
+        let result = parse_class_structure(source).unwrap();
+        let read = &result.methods[0];
+        assert_eq!(read.name, "read");
+        assert_eq!(read.return_type.as_deref(), Some("List<T>"));
+        assert_eq!(read.modifiers, vec!["public", "static"]);
+        assert_eq!(
+            read.annotations,
+            vec![
+                annotation("Deprecated", None),
+                annotation("SuppressWarnings", Some("\"unchecked\"")),
+            ]
+        );
+        assert_eq!(read.throws, vec!["IOException", "InterruptedException"]);
+        let params: Vec<(&str, &str)> = read
+            .parameters
+            .iter()
+            .map(|p| (p.name.as_str(), p.type_name.as_str()))
+            .collect();
+        assert_eq!(
+            params,
+            vec![("path", "Path"), ("names", "String[]"), ("sizes", "int...")]
+        );
+        assert!(read.signature.starts_with("@Deprecated @SuppressWarnings"));
+        assert!(
+            read.signature
+                .ends_with("throws IOException, InterruptedException")
+        );
+
+        let ctor = &result.methods[1];
+        assert!(ctor.is_constructor);
+        assert_eq!(ctor.name, "Io");
+        assert_eq!(ctor.throws, vec!["Exception"]);
+        assert_eq!(ctor.parameters[0].type_name, "Map<String, List<Integer>>");
     }
 
     #[test]
@@ -584,6 +1641,713 @@ public class Commented {
         );
     }
 
+    #[test]
+    fn parse_supertypes_with_generics() {
+        let source = r#"
+package org.example;
+
+public sealed class Table<K, V> extends AbstractMap<K, List<V>>
+        implements Serializable, Comparable<Table<K, V>> permits HashTable, TreeTable {
+    interface View<T> extends Iterable<T>, AutoCloseable {}
+    enum Kind implements Supplier<String> { A; public String get() { return ""; } }
+    record Cell(int row) implements Comparable<Cell> {}
+}
+"#;
+        let result = parse_class_structure(source).unwrap();
+        assert_eq!(result.extends.as_deref(), Some("AbstractMap<K, List<V>>"));
+        assert_eq!(
+            result.implements,
+            vec!["Serializable", "Comparable<Table<K, V>>"]
+        );
+        assert_eq!(result.permits, vec!["HashTable", "TreeTable"]);
+        assert!(
+            result
+                .class_declaration
+                .starts_with("public sealed class Table<K, V> extends AbstractMap<K, List<V>>")
+        );
+
+        let view = &result.nested[0];
+        assert_eq!(view.extends, None);
+        assert_eq!(view.implements, vec!["Iterable<T>", "AutoCloseable"]);
+        assert_eq!(result.nested[1].implements, vec!["Supplier<String>"]);
+        assert_eq!(result.nested[2].implements, vec!["Comparable<Cell>"]);
+        assert!(result.nested[2].permits.is_empty());
+    }
+
+    #[test]
+    fn parse_marker_single_value_and_normal_annotations() {
+        let source = r#"
+package org.example;
+
+@Entity
+@Table(name = "orders",
+       indexes = { @Index(columnList = "created") })
+@org.springframework.stereotype.Component("orderRepo")
+public class Order {
+    @Id
+    @GeneratedValue(strategy = GenerationType.IDENTITY)
+    private Long id;
+
+    @Transactional(readOnly = true)
+    public Order find(@PathVariable("id") Long id) {
+        return null;
+    }
+
+    @Embeddable
+    static class Key {}
+}
+"#;
+        let result = parse_class_structure(source).unwrap();
+        assert_eq!(
+            result.annotations,
+            vec![
+                annotation("Entity", None),
+                annotation(
+                    "Table",
+                    Some("name = \"orders\", indexes = { @Index(columnList = \"created\") }")
+                ),
+                annotation(
+                    "org.springframework.stereotype.Component",
+                    Some("\"orderRepo\"")
+                ),
+            ]
+        );
+        assert_eq!(
+            result.fields[0].annotations,
+            vec![
+                annotation("Id", None),
+                annotation("GeneratedValue", Some("strategy = GenerationType.IDENTITY")),
+            ]
+        );
+        assert_eq!(
+            result.methods[0].annotations,
+            vec![annotation("Transactional", Some("readOnly = true"))]
+        );
+        assert_eq!(result.methods[0].parameters[0].type_name, "Long");
+        assert_eq!(
+            result.nested[0].annotations,
+            vec![annotation("Embeddable", None)]
+        );
+        assert!(result.class_declaration.contains("public class Order"));
+    }
+
+    #[test]
+    fn javadoc_is_taken_only_from_doc_comments_right_before_a_declaration() {
+        let source = r#"
+package org.example;
+
+/**
+ * Widget doc.
+ */
+@Deprecated
+public class Widget {
+    /** Size doc. */
+    private int size;
+
+    // Plain comment.
+    private int plain;
+
+    /** Detached doc. */
+
+    int gap;
+
+    /** Orphan. */ int before; /* not doc */ int after;
+
+    /** Build doc. */
+    public Widget build() { return this; }
+
+    enum Kind {
+        /** Small doc. */
+        SMALL
+    }
+}
+"#;
+        let mut result = parse_class_structure(source).unwrap();
+        assert!(
+            result
+                .javadoc
+                .as_deref()
+                .unwrap_or_default()
+                .contains("Widget doc.")
+        );
+        let javadocs: Vec<(&str, Option<&str>)> = result
+            .fields
+            .iter()
+            .map(|f| (f.name.as_str(), f.javadoc.as_deref()))
+            .collect();
+        assert_eq!(
+            javadocs,
+            vec![
+                ("size", Some("/** Size doc. */")),
+                ("plain", None),
+                ("gap", Some("/** Detached doc. */")),
+                ("before", Some("/** Orphan. */")),
+                ("after", None),
+            ]
+        );
+        assert_eq!(
+            result.fields[1].comment.as_deref(),
+            Some("// Plain comment.")
+        );
+        assert_eq!(
+            result.methods[0].javadoc.as_deref(),
+            Some("/** Build doc. */")
+        );
+        assert_eq!(
+            result.nested[0].constants[0].javadoc.as_deref(),
+            Some("/** Small doc. */")
+        );
+
+        result.strip_javadoc();
+        assert!(result.javadoc.is_none());
+        assert!(result.fields.iter().all(|f| f.javadoc.is_none()));
+        assert!(result.methods[0].javadoc.is_none());
+        assert!(result.nested[0].constants[0].javadoc.is_none());
+        assert!(result.class_comment.is_some());
+    }
+
+    #[test]
+    fn parse_generic_record_with_compact_constructor() {
+        let source = r#"
+package org.example;
+
+public record Range<T extends Comparable<T>>(T low, T high, List<String> tags)
+        implements Serializable {
+    public Range {
+        Objects.requireNonNull(low);
+    }
+
+    private static final long serialVersionUID = 1L;
+
+    public static <T extends Comparable<T>> Range<T> of(T low, T high) {
+        return new Range<>(low, high, List.of());
+    }
+
+    public boolean contains(T value) {
+        return low.compareTo(value) <= 0 && high.compareTo(value) >= 0;
+    }
+}
+"#;
+        let result = parse_class_structure(source).unwrap();
+        assert_eq!(result.name, "Range");
+        assert_eq!(result.type_parameters, vec!["T extends Comparable<T>"]);
+        let components: Vec<(&str, &str)> = result
+            .record_components
+            .iter()
+            .map(|c| (c.name.as_str(), c.type_name.as_str()))
+            .collect();
+        assert_eq!(
+            components,
+            vec![("low", "T"), ("high", "T"), ("tags", "List<String>")]
+        );
+        assert_eq!(result.implements, vec!["Serializable"]);
+        assert_eq!(result.fields[0].name, "serialVersionUID");
+
+        let names: Vec<&str> = result.methods.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, vec!["Range", "of", "contains"]);
+        let compact = &result.methods[0];
+        assert!(compact.is_constructor);
+        assert!(compact.parameters.is_empty());
+        assert_eq!(compact.signature, "public Range");
+        let of = &result.methods[1];
+        assert_eq!(of.type_parameters, vec!["T extends Comparable<T>"]);
+        assert_eq!(of.modifiers, vec!["public", "static"]);
+        assert_eq!(of.return_type.as_deref(), Some("Range<T>"));
+        assert_eq!(of.parameters.len(), 2);
+        assert!(result.methods[2].type_parameters.is_empty());
+    }
+
+    #[test]
+    fn ranges_slice_the_source_back_to_each_declaration() {
+        let source = "package org.example;\n\n/** Doc. */\n@Service\npublic class Ranged {\n    private int a, b = 2;\n\n    @Override\n    public String toString() {\n        return \"é\";\n    }\n\n    enum Kind { ONE }\n}\n";
+        let result = parse_class_structure(source).unwrap();
+        let slice = |range: &SourceRange| &source[range.start_byte..range.end_byte];
+        let lines = |range: &SourceRange| {
+            source
+                .lines()
+                .skip(range.start_line - 1)
+                .take(range.end_line - range.start_line + 1)
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+
+        assert_eq!((result.range.start_line, result.range.end_line), (4, 14));
+        assert!(slice(&result.range).starts_with("@Service\npublic class Ranged {"));
+        assert!(slice(&result.range).ends_with('}'));
+
+        let method = &result.methods[0];
+        assert_eq!((method.range.start_line, method.range.end_line), (8, 11));
+        assert_eq!(
+            slice(&method.range),
+            "@Override\n    public String toString() {\n        return \"é\";\n    }"
+        );
+        assert_eq!(
+            lines(&method.range),
+            "    @Override\n    public String toString() {\n        return \"é\";\n    }"
+        );
+
+        assert_eq!(result.fields.len(), 2);
+        for field in &result.fields {
+            assert_eq!(slice(&field.range), "private int a, b = 2;");
+            assert_eq!(field.range.start_line, 6);
+        }
+        let kind = &result.nested[0];
+        assert_eq!(slice(&kind.range), "enum Kind { ONE }");
+        assert_eq!(slice(&kind.constants[0].range), "ONE");
+        assert_eq!(kind.range.start_line, 13);
+    }
+
+    #[test]
+    fn parse_fields_into_declarators() {
+        let source = r#"
+package org.example;
+
+public class Settings {
+    @Value("${timeout}")
+    @Nullable
+    private final Duration timeout = Duration.ofSeconds(30);
+
+    int a, b = 2, matrix[][];
+
+    /** Shared. */
+    static final List<String> NAMES = List.of("a", "b");
+}
+"#;
+        let result = parse_class_structure(source).unwrap();
+        let fields: Vec<(&str, &str, Option<&str>)> = result
+            .fields
+            .iter()
+            .map(|f| {
+                (
+                    f.name.as_str(),
+                    f.type_name.as_str(),
+                    f.initializer.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                ("timeout", "Duration", Some("Duration.ofSeconds(30)")),
+                ("a", "int", None),
+                ("b", "int", Some("2")),
+                ("matrix", "int[][]", None),
+                ("NAMES", "List<String>", Some("List.of(\"a\", \"b\")")),
+            ]
+        );
+        let timeout = &result.fields[0];
+        assert_eq!(timeout.modifiers, vec!["private", "final"]);
+        assert_eq!(
+            timeout.annotations,
+            vec![
+                annotation("Value", Some("\"${timeout}\"")),
+                annotation("Nullable", None),
+            ]
+        );
+        assert!(result.fields[1].modifiers.is_empty());
+        assert_eq!(result.fields[2].declaration, "int a, b = 2, matrix[][];");
+        assert_eq!(result.fields[4].modifiers, vec!["static", "final"]);
+        assert!(
+            result.fields[4]
+                .comment
+                .as_deref()
+                .unwrap_or_default()
+                .contains("Shared")
+        );
+    }
+
+    #[test]
+    fn parse_constant_interface_and_enum_constants_with_arguments() {
+        let source = r#"
+package org.example;
+
+public interface Limits {
+    int MAX = 10, MIN = 1;
+    String NAME = "limits";
+}
+"#;
+        let result = parse_class_structure(source).unwrap();
+        let names: Vec<&str> = result.fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["MAX", "MIN", "NAME"]);
+        assert_eq!(result.fields[1].type_name, "int");
+        assert_eq!(result.fields[1].initializer.as_deref(), Some("1"));
+        assert!(result.constants.is_empty());
+
+        let source = r#"
+package org.example;
+
+public enum Unit {
+    /** Base unit. */
+    BYTE(1, "B"),
+    @Deprecated
+    KILO(1024L * 1, "KiB") {
+        @Override
+        String label() { return "kilo"; }
+    },
+    NONE;
+
+    private final long factor;
+
+    Unit(long factor, String suffix) {
+        this.factor = factor;
+    }
+
+    Unit() {
+        this(0, "");
+    }
+}
+"#;
+        let result = parse_class_structure(source).unwrap();
+        let constants: Vec<(&str, Vec<&str>)> = result
+            .constants
+            .iter()
+            .map(|c| {
+                (
+                    c.name.as_str(),
+                    c.arguments.iter().map(String::as_str).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            constants,
+            vec![
+                ("BYTE", vec!["1", "\"B\""]),
+                ("KILO", vec!["1024L * 1", "\"KiB\""]),
+                ("NONE", vec![]),
+            ]
+        );
+        assert!(
+            result.constants[0]
+                .comment
+                .as_deref()
+                .unwrap_or_default()
+                .contains("Base unit")
+        );
+        assert_eq!(result.fields.len(), 1);
+        assert_eq!(result.fields[0].name, "factor");
+        assert_eq!(result.fields[0].type_name, "long");
+        assert_eq!(result.methods.len(), 2);
+        assert!(result.methods.iter().all(|m| m.is_constructor));
+    }
+
+    #[test]
+    fn parse_nested_enum_and_static_class() {
+        let source = r#"
+package org.example;
+
+public class Order {
+    private Status status;
+
+    /** Lifecycle of an order. */
+    public enum Status {
+        NEW,
+        SHIPPED;
+
+        public boolean isFinal() {
+            return this == SHIPPED;
+        }
+    }
+
+    public static class Builder {
+        private Status status = Status.NEW;
+
+        public Builder status(Status status) {
+            this.status = status;
+            return this;
+        }
+
+        interface Step {
+            Builder next();
+        }
+    }
+
+    public Status getStatus() {
+        return status;
+    }
+}
+"#;
+        let result = parse_class_structure(source).unwrap();
+        assert_eq!(result.name, "Order");
+        assert_eq!(result.fields.len(), 1);
+        assert_eq!(result.methods.len(), 1);
+        assert!(result.methods[0].contains("public Status getStatus()"));
+
+        let names: Vec<&str> = result.nested.iter().map(|n| n.name.as_str()).collect();
+        assert_eq!(names, vec!["Order.Status", "Order.Builder"]);
+
+        let status = &result.nested[0];
+        assert_eq!(status.package, "org.example");
+        assert_eq!(status.class_declaration, "public enum Status");
+        assert!(
+            status
+                .class_comment
+                .as_deref()
+                .unwrap_or_default()
+                .contains("Lifecycle of an order")
+        );
+        assert_eq!(status.constants[1].name, "SHIPPED");
+        assert!(status.methods[0].contains("public boolean isFinal()"));
+
+        let builder = &result.nested[1];
+        assert_eq!(builder.class_declaration, "public static class Builder");
+        assert!(builder.fields[0].contains("private Status status"));
+        assert!(builder.methods[0].contains("public Builder status(Status status)"));
+        assert_eq!(builder.nested.len(), 1);
+        assert_eq!(builder.nested[0].name, "Order.Builder.Step");
+        assert!(builder.nested[0].methods[0].contains("Builder next()"));
+        assert!(builder.nested[0].nested.is_empty());
+    }
+
+    #[test]
+    fn nested_types_uses_binary_names_and_skips_local_classes() {
+        let source = r#"package a;
+
+public class Outer {
+    static class Inner {
+        interface Deep {}
+    }
+
+    enum Mode {
+        ON { void flip() {} };
+
+        @interface Marker {}
+
+        void flip() {}
+    }
+
+    void run() {
+        class Local {}
+        Runnable r = new Runnable() { public void run() {} };
+    }
+}
+
+class Helper {
+    record Point(int x) {}
+}
+"#;
+        let nested = nested_types(source);
+        let names: Vec<&str> = nested.iter().map(|n| n.binary_name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "Outer$Inner",
+                "Outer$Inner$Deep",
+                "Outer$Mode",
+                "Outer$Mode$Marker",
+                "Helper$Point"
+            ]
+        );
+        assert!(nested[0].source.starts_with("static class Inner {"));
+        assert_eq!(nested[1].source, "interface Deep {}");
+    }
+
+    #[test]
+    fn method_query_matches_parameter_types_loosely() -> anyhow::Result<()> {
+        let source = r#"
+package org.example;
+
+public class Codec {
+    public Codec(String name) {}
+    public Object parse(java.lang.String text) { return null; }
+    public Object parse(String text, int offset) { return null; }
+    public void serialize(Object value, com.fasterxml.jackson.core.JsonGenerator gen) {}
+    public void each(java.util.Map<String, java.util.List<Object>> map, String... rest) {}
+
+    static class Inner {
+        Inner(int size) {}
+        Object parse(byte[] data) { return null; }
+    }
+}
+"#;
+        let structure = parse_class_structure(source).expect("structure");
+        let found = |query: &str| -> anyhow::Result<Vec<String>> {
+            Ok(MethodQuery::parse(query)?
+                .find_in(&structure)
+                .into_iter()
+                .map(|(owner, method)| format!("{}#{}", owner.name, method.signature))
+                .collect())
+        };
+
+        assert_eq!(
+            found("parse(String)")?,
+            vec!["Codec#public Object parse(java.lang.String text)"]
+        );
+        assert_eq!(found("parse")?.len(), 3);
+        assert_eq!(
+            found("parse(*)")?,
+            vec![
+                "Codec#public Object parse(java.lang.String text)",
+                "Codec.Inner#Object parse(byte[] data)"
+            ]
+        );
+        assert_eq!(found("parse(String, *)")?.len(), 1);
+        assert_eq!(found("serialize(Object, JsonGenerator)")?.len(), 1);
+        assert_eq!(found("serialize(Object, Json*)")?.len(), 1);
+        assert_eq!(found("each(Map<String, List<*>>, String...)")?.len(), 1);
+        assert_eq!(
+            found("Codec(String)")?,
+            vec!["Codec#public Codec(String name)"]
+        );
+        assert_eq!(found("Inner(int)")?, vec!["Codec.Inner#Inner(int size)"]);
+        assert!(found("parse()")?.is_empty());
+        assert!(MethodQuery::parse("parse(String").is_err());
+        assert!(MethodQuery::parse("a.b(String)").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn static_final_literal_fields_are_listed_as_constant_values() {
+        let source = r#"
+package org.example;
+
+public class Protocol {
+    public static final long DEFAULT_TIMEOUT = 30_000L;
+    static final int MAGIC = 0xCAFEBABE, NEGATIVE = -1;
+    private static final double RATIO = (0.75);
+    public static final String PREFIX = "x-  "
+        + ("proto");
+    static final char SEPARATOR = ':';
+    static final boolean ENABLED = true;
+    static final String BLOCK = """
+        hi
+        """;
+    static final String NAME = "a" + 1;
+    static final Object LOCK = new Object();
+    static final int DERIVED = MAGIC + 1;
+    final int instance = 1;
+    static int mutable = 2;
+
+    interface Keys {
+        String HEADER = "X-Key";
+    }
+}
+"#;
+        let structure = parse_class_structure(source).expect("structure");
+        let values: Vec<(&str, &str, &str)> = structure
+            .constant_values
+            .iter()
+            .map(|(name, c)| (name.as_str(), c.type_name.as_str(), c.value.as_str()))
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                ("BLOCK", "String", "\"\"\"\n        hi\n        \"\"\""),
+                ("DEFAULT_TIMEOUT", "long", "30_000L"),
+                ("ENABLED", "boolean", "true"),
+                ("MAGIC", "int", "0xCAFEBABE"),
+                ("NEGATIVE", "int", "-1"),
+                ("PREFIX", "String", "\"x-  \" + (\"proto\")"),
+                ("RATIO", "double", "(0.75)"),
+                ("SEPARATOR", "char", "':'"),
+            ]
+        );
+
+        let keys = &structure.nested[0];
+        assert_eq!(
+            keys.constant_values.get("HEADER"),
+            Some(&ConstantValue {
+                type_name: "String".to_string(),
+                value: "\"X-Key\"".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn secondary_top_level_types_are_kept_apart() {
+        let source = r#"
+package org.example;
+
+import java.util.List;
+
+class Helper {
+    int count;
+    void help() {}
+}
+
+public class Main {
+    public void run() {}
+}
+
+interface Callback {
+    void done();
+}
+"#;
+        let unit = parse_compilation_unit(source).expect("unit");
+        assert_eq!(unit.primary.name, "Main");
+        assert_eq!(unit.primary.imports, vec!["java.util.List"]);
+        let method_names: Vec<&str> = unit
+            .primary
+            .methods
+            .iter()
+            .map(|m| m.name.as_str())
+            .collect();
+        assert_eq!(method_names, vec!["run"]);
+        assert!(unit.primary.fields.is_empty());
+        let others: Vec<&str> = unit.others.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(others, vec!["Helper", "Callback"]);
+        assert_eq!(unit.others[0].package, "org.example");
+        assert_eq!(unit.others[0].fields[0].name, "count");
+
+        let helper = unit.select("Helper");
+        assert_eq!(helper.primary.name, "Helper");
+        assert_eq!(helper.primary.imports, vec!["java.util.List"]);
+        let others: Vec<&str> = helper.others.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(others, vec!["Main", "Callback"]);
+        assert!(helper.others[0].imports.is_empty());
+
+        let main = parse_class_structure(source).expect("structure");
+        assert_eq!(main.name, "Main");
+    }
+
+    #[test]
+    fn type_kind_and_modifiers_are_extracted() {
+        let source = r#"
+package org.example;
+
+@Deprecated
+public abstract sealed class Shape permits Shape.Circle {
+    static final class Circle extends Shape {}
+    private interface Visitor {}
+    protected enum Mode { ON }
+    public record Point(int x, int y) {}
+    @interface Marker {}
+    non-sealed static class Open {}
+}
+"#;
+        let structure = parse_class_structure(source).expect("structure");
+        assert_eq!(structure.kind, TypeKind::Class);
+        assert_eq!(structure.modifiers, vec!["public", "abstract", "sealed"]);
+        assert_eq!(structure.annotations[0].name, "Deprecated");
+        let nested: Vec<(&str, TypeKind, Vec<&str>)> = structure
+            .nested
+            .iter()
+            .map(|n| {
+                let modifiers = n.modifiers.iter().map(String::as_str).collect();
+                (n.name.as_str(), n.kind, modifiers)
+            })
+            .collect();
+        assert_eq!(
+            nested,
+            vec![
+                ("Shape.Circle", TypeKind::Class, vec!["static", "final"]),
+                ("Shape.Visitor", TypeKind::Interface, vec!["private"]),
+                ("Shape.Mode", TypeKind::Enum, vec!["protected"]),
+                ("Shape.Point", TypeKind::Record, vec!["public"]),
+                ("Shape.Marker", TypeKind::Annotation, vec![]),
+                ("Shape.Open", TypeKind::Class, vec!["non-sealed", "static"]),
+            ]
+        );
+
+        let json = serde_json::to_value(&structure).expect("json");
+        assert_eq!(json["kind"], "class");
+        assert_eq!(json["nested"][4]["kind"], "annotation");
+        let text = serde_json::to_string(&structure).expect("json");
+        let position = |key: &str| text.find(&format!("\"{key}\":"));
+        assert!(position("class_declaration") < position("kind"));
+        assert!(position("kind") < position("modifiers"));
+        assert!(position("modifiers") < position("permits"));
+    }
+
     #[test]
     fn parse_empty_source_returns_none() {
         assert!(parse_class_structure("").is_none());
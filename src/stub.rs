@@ -0,0 +1,262 @@
+//! Java skeletons rendered from a [`ClassStructure`].
+//!
+//! The stub keeps the package, imports, declarations, annotations, fields and
+//! nested types, and drops every body: methods end in `;`, constructors get an
+//! empty `{}` so the stub still parses, and only fields with a literal
+//! constant value keep their initializer.
+
+use crate::structure::{
+    AnnotationStructure, ClassStructure, CompilationUnit, FieldStructure, MethodStructure,
+};
+
+const INDENT: &str = "    ";
+
+/// Stub source for `structure`. Members are listed as enum constants, fields,
+/// methods, then nested types, each group in declaration order.
+pub fn render_stub(structure: &ClassStructure) -> String {
+    let mut out = String::new();
+    if !structure.package.is_empty() {
+        out.push_str(&format!("package {};\n\n", structure.package));
+    }
+    for import in &structure.imports {
+        out.push_str(&format!("import {import};\n"));
+    }
+    if !structure.imports.is_empty() {
+        out.push('\n');
+    }
+    render_type(structure, 0, &mut out);
+    out
+}
+
+/// [`render_stub`] of the primary type followed by the other top-level types
+/// of the unit, under one package and import list.
+pub fn render_compilation_unit_stub(unit: &CompilationUnit) -> String {
+    let mut out = render_stub(&unit.primary);
+    for other in &unit.others {
+        out.push('\n');
+        render_type(other, 0, &mut out);
+    }
+    out
+}
+
+fn render_type(structure: &ClassStructure, depth: usize, out: &mut String) {
+    let indent = INDENT.repeat(depth);
+    let member_indent = INDENT.repeat(depth + 1);
+    push_javadoc(structure.javadoc.as_deref(), &indent, out);
+    out.push_str(&format!(
+        "{indent}{} {{\n",
+        normalize_whitespace(&structure.class_declaration)
+    ));
+
+    let mut sections: Vec<String> = Vec::new();
+    if !structure.constants.is_empty() {
+        let constants: Vec<String> = structure
+            .constants
+            .iter()
+            .map(|constant| {
+                let mut text = String::new();
+                push_javadoc(constant.javadoc.as_deref(), &member_indent, &mut text);
+                text.push_str(&member_indent);
+                text.push_str(&constant.name);
+                if !constant.arguments.is_empty() {
+                    text.push_str(&format!("({})", constant.arguments.join(", ")));
+                }
+                text
+            })
+            .collect();
+        sections.push(format!("{};\n", constants.join(",\n")));
+    }
+
+    let mut members = String::new();
+    for field in &structure.fields {
+        push_javadoc(field.javadoc.as_deref(), &member_indent, &mut members);
+        members.push_str(&format!(
+            "{member_indent}{}\n",
+            render_field(structure, field)
+        ));
+    }
+    if !members.is_empty() {
+        sections.push(std::mem::take(&mut members));
+    }
+    for method in &structure.methods {
+        push_javadoc(method.javadoc.as_deref(), &member_indent, &mut members);
+        members.push_str(&format!("{member_indent}{}\n", render_method(method)));
+    }
+    if !members.is_empty() {
+        sections.push(members);
+    }
+    for nested in &structure.nested {
+        let mut text = String::new();
+        render_type(nested, depth + 1, &mut text);
+        sections.push(text);
+    }
+
+    out.push_str(&sections.join("\n"));
+    out.push_str(&format!("{indent}}}\n"));
+}
+
+fn render_field(owner: &ClassStructure, field: &FieldStructure) -> String {
+    let mut parts: Vec<String> = field.annotations.iter().map(render_annotation).collect();
+    parts.extend(field.modifiers.iter().cloned());
+    parts.push(field.type_name.clone());
+    parts.push(field.name.clone());
+    let mut text = parts.join(" ");
+    if let Some(constant) = owner.constant_values.get(&field.name) {
+        text.push_str(" = ");
+        text.push_str(&constant.value);
+    }
+    text.push(';');
+    text
+}
+
+fn render_method(method: &MethodStructure) -> String {
+    let signature = method.signature.trim_end_matches(';').trim_end();
+    if method.is_constructor {
+        format!("{signature} {{}}")
+    } else {
+        format!("{signature};")
+    }
+}
+
+fn render_annotation(annotation: &AnnotationStructure) -> String {
+    match &annotation.arguments {
+        Some(arguments) => format!("@{}({arguments})", annotation.name),
+        None => format!("@{}", annotation.name),
+    }
+}
+
+/// Re-indents a `/** ... */` comment to `indent`, one ` * ` line per line.
+fn push_javadoc(javadoc: Option<&str>, indent: &str, out: &mut String) {
+    let Some(javadoc) = javadoc else {
+        return;
+    };
+    for (i, line) in javadoc.lines().enumerate() {
+        let line = line.trim();
+        if i > 0 && line.starts_with('*') {
+            out.push_str(&format!("{indent} {line}\n"));
+        } else {
+            out.push_str(&format!("{indent}{line}\n"));
+        }
+    }
+}
+
+fn normalize_whitespace(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structure::parse_class_structure;
+
+    const SOURCE: &str = r#"
+package org.example;
+
+import java.util.List;
+import java.util.Map;
+
+/**
+ * A service.
+ */
+@Deprecated
+public class Service<T> extends Base implements Runnable {
+    public static final long TIMEOUT = 30L;
+    private final Map<String, List<T>> cache = new java.util.HashMap<>();
+    @Inject protected Helper helper;
+
+    public Service(String name) {
+        super(name);
+    }
+
+    /** Runs it. */
+    @Override
+    public void run() {
+        System.out.println("run");
+    }
+
+    public <R> R apply(java.util.function.Function<T, R> fn) throws Exception {
+        return fn.apply(null);
+    }
+
+    enum Mode {
+        ON("on") { void flip() {} },
+        OFF("off");
+
+        Mode(String label) {}
+
+        void flip() {}
+    }
+
+    @interface Marker {
+        String value() default "";
+    }
+
+    record Point(int x, int y) {
+        Point {
+            if (x < 0) throw new IllegalArgumentException();
+        }
+    }
+}
+"#;
+
+    fn member_set(structure: &ClassStructure) -> Vec<String> {
+        let mut members = Vec::new();
+        members.push(format!("type {}", structure.name));
+        members.extend(
+            structure
+                .fields
+                .iter()
+                .map(|f| format!("field {} {}", f.type_name, f.name)),
+        );
+        members.extend(
+            structure
+                .constants
+                .iter()
+                .map(|c| format!("constant {}({})", c.name, c.arguments.join(","))),
+        );
+        members.extend(structure.methods.iter().map(|m| {
+            let params: Vec<&str> = m.parameters.iter().map(|p| p.type_name.as_str()).collect();
+            format!("method {}({})", m.name, params.join(","))
+        }));
+        members.extend(
+            structure
+                .constant_values
+                .iter()
+                .map(|(k, v)| format!("value {k}={}", v.value)),
+        );
+        for nested in &structure.nested {
+            members.extend(member_set(nested));
+        }
+        members
+    }
+
+    #[test]
+    fn stub_has_no_bodies() {
+        let stub = render_stub(&parse_class_structure(SOURCE).expect("structure"));
+        assert!(stub.starts_with("package org.example;\n\nimport java.util.List;\n"));
+        assert!(stub.contains("/**\n * A service.\n */\n@Deprecated public class Service<T>"));
+        assert!(stub.contains("    public static final long TIMEOUT = 30L;\n"));
+        assert!(stub.contains("    private final Map<String, List<T>> cache;\n"));
+        assert!(stub.contains("    @Inject protected Helper helper;\n"));
+        assert!(stub.contains("    public Service(String name) {}\n"));
+        assert!(stub.contains("    /** Runs it. */\n    @Override public void run();\n"));
+        assert!(stub.contains("        ON(\"on\"),\n        OFF(\"off\");\n"));
+        assert!(stub.contains("        String value() default \"\";\n"));
+        assert!(!stub.contains("println"));
+        assert!(!stub.contains("HashMap"));
+        assert!(!stub.contains("IllegalArgumentException"));
+    }
+
+    #[test]
+    fn stub_reparses_to_the_same_members() {
+        let structure = parse_class_structure(SOURCE).expect("structure");
+        let stub = render_stub(&structure);
+        let reparsed = parse_class_structure(&stub).expect("stub parses");
+        assert_eq!(member_set(&reparsed), member_set(&structure));
+        assert_eq!(reparsed.imports, structure.imports);
+        assert_eq!(reparsed.extends, structure.extends);
+        assert_eq!(reparsed.implements, structure.implements);
+        assert_eq!(reparsed.annotations, structure.annotations);
+        assert_eq!(render_stub(&reparsed), stub);
+    }
+}
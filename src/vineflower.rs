@@ -0,0 +1,405 @@
+use anyhow::{Context, Result, bail};
+use memmap2::Mmap;
+use std::fs::File;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use zip::ZipArchive;
+
+use crate::classpath::decompile_classpath;
+use crate::decompiler::{
+    Decompiler, DecompilerConfig, DecompilerKind, JavaInvocation, WorkDir, for_each_java_source,
+    java_command, manifest_version,
+};
+use crate::encoding::DecodedOutput;
+use crate::error::ClassFinderError;
+use crate::parse::ParsedClass;
+use crate::postprocess::Postprocessor;
+
+/// Header prepended to every file Vineflower writes, so the concatenated output
+/// can be split by `parse_decompiled_output` like CFR's.
+pub const VINEFLOWER_HEADER: &str = "/*\n * Decompiled with Vineflower.\n */\n";
+
+#[derive(Debug, Clone)]
+pub struct Vineflower {
+    vineflower_jar: PathBuf,
+    config: DecompilerConfig,
+    version: OnceLock<Option<String>>,
+}
+
+impl Vineflower {
+    pub fn new(vineflower_jar: PathBuf) -> Self {
+        Self::with_config(vineflower_jar, DecompilerConfig::default())
+    }
+
+    pub fn with_config(vineflower_jar: PathBuf, config: DecompilerConfig) -> Self {
+        Self {
+            vineflower_jar,
+            config,
+            version: OnceLock::new(),
+        }
+    }
+
+    fn args(&self, inputs: &[&str], out_dir: &Path) -> Result<Vec<String>> {
+        let mut args = vec![
+            "-jar".to_string(),
+            self.vineflower_jar
+                .to_str()
+                .context("vineflower.jar path is not valid UTF-8")?
+                .to_string(),
+            "-log=ERROR".to_string(),
+        ];
+        args.extend(inputs.iter().map(|input| input.to_string()));
+        args.push(
+            out_dir
+                .to_str()
+                .context("output path is not valid UTF-8")?
+                .to_string(),
+        );
+        Ok(args)
+    }
+
+    fn run(
+        &self,
+        inputs: &[&str],
+        out_dir: &Path,
+        target: &str,
+        timeout: std::time::Duration,
+    ) -> Result<()> {
+        let args = self.args(inputs, out_dir)?;
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let output = java_command(&self.config.java_opts, &args, target, timeout)?;
+        check_status(&output, target)
+    }
+}
+
+fn check_status(output: &std::process::Output, target: &str) -> Result<()> {
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(ClassFinderError::decompile_failed(
+            target,
+            format!("Vineflower decompilation failed: {}", stderr.trim()),
+        )
+        .into());
+    }
+    Ok(())
+}
+
+impl Decompiler for Vineflower {
+    fn kind(&self) -> DecompilerKind {
+        DecompilerKind::Vineflower
+    }
+
+    fn version(&self) -> Option<String> {
+        self.version
+            .get_or_init(|| manifest_version(&self.vineflower_jar))
+            .clone()
+    }
+
+    fn postprocessor(&self) -> Option<&Postprocessor> {
+        self.config.postprocess.as_ref()
+    }
+
+    fn decompile_class(&self, jar_path: &Path, class_name: &str) -> Result<DecodedOutput> {
+        let invocation = self
+            .class_invocation(jar_path, class_name)?
+            .context("Vineflower always runs one JVM per class")?;
+        let output = invocation.run()?;
+        self.invocation_output(&invocation, output)
+    }
+
+    /// Extracts the class and its inner classes into a work directory that
+    /// Vineflower decompiles into `out`.
+    fn class_invocation(
+        &self,
+        jar_path: &Path,
+        class_name: &str,
+    ) -> Result<Option<JavaInvocation>> {
+        let work = WorkDir::create("class")?;
+        let input_dir = work.path().join("in");
+        let out_dir = work.path().join("out");
+        std::fs::create_dir_all(&out_dir)?;
+
+        let extracted = extract_class_entries(jar_path, class_name, &input_dir)?;
+        if extracted == 0 {
+            bail!(
+                "Class {class_name} not found in jar: {}",
+                jar_path.display()
+            );
+        }
+
+        let classpath = decompile_classpath(jar_path, self.config.classpath)?;
+        let libraries: Vec<String> = std::env::split_paths(&classpath)
+            .map(|entry| format!("-e={}", entry.to_string_lossy()))
+            .collect();
+        let input = input_dir
+            .to_str()
+            .context("input path is not valid UTF-8")?;
+        let mut inputs: Vec<&str> = libraries.iter().map(String::as_str).collect();
+        inputs.push(input);
+        let args = self.args(&inputs, &out_dir)?;
+        Ok(Some(
+            JavaInvocation::new(
+                &self.config.java_opts,
+                args,
+                class_name,
+                self.config.class_timeout,
+            )
+            .with_work_dir(work),
+        ))
+    }
+
+    fn invocation_output(
+        &self,
+        invocation: &JavaInvocation,
+        output: std::process::Output,
+    ) -> Result<DecodedOutput> {
+        check_status(&output, &invocation.target)?;
+        let work_dir = invocation
+            .work_dir()
+            .context("Vineflower run without a work directory")?;
+        frame_sources(&work_dir.join("out"), &self.config)
+    }
+
+    fn decompile_jar(&self, jar_path: &Path) -> Result<DecodedOutput> {
+        let work = WorkDir::create("jar")?;
+        let out_dir = work.path().join("out");
+        std::fs::create_dir_all(&out_dir)?;
+
+        let jar = jar_path.to_str().context("jar path is not valid UTF-8")?;
+        self.run(&[jar], &out_dir, jar, self.config.jar_timeout)?;
+
+        frame_sources(&out_dir, &self.config)
+    }
+
+    fn decompile_jar_each(
+        &self,
+        jar_path: &Path,
+        on_class: &mut dyn FnMut(ParsedClass),
+    ) -> Result<usize> {
+        let work = WorkDir::create("jar")?;
+        let out_dir = work.path().join("out");
+        std::fs::create_dir_all(&out_dir)?;
+
+        let jar = jar_path.to_str().context("jar path is not valid UTF-8")?;
+        self.run(&[jar], &out_dir, jar, self.config.jar_timeout)?;
+
+        for_each_java_source(&out_dir, &self.config, on_class)
+    }
+}
+
+/// Copies `pkg/Name.class` and its `pkg/Name$*.class` inner classes out of the jar.
+fn extract_class_entries(jar_path: &Path, class_name: &str, target_dir: &Path) -> Result<usize> {
+    let file = File::open(jar_path)
+        .with_context(|| format!("Failed to open jar: {}", jar_path.display()))?;
+    // SAFETY: The file is opened read-only and remains valid for the lifetime of the mmap.
+    let mmap = unsafe { Mmap::map(&file) }
+        .with_context(|| format!("mmap jar failed: {}", jar_path.display()))?;
+    let mut archive = ZipArchive::new(Cursor::new(&mmap[..]))
+        .with_context(|| format!("Failed to parse zip(jar): {}", jar_path.display()))?;
+
+    let base = class_name.replace('.', "/");
+    let exact = format!("{base}.class");
+    let inner_prefix = format!("{base}$");
+
+    let mut extracted = 0usize;
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let name = entry.name().to_string();
+        if name != exact && !(name.starts_with(&inner_prefix) && name.ends_with(".class")) {
+            continue;
+        }
+
+        let target = target_dir.join(&name);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut bytes = Vec::new();
+        entry.read_to_end(&mut bytes)?;
+        std::fs::write(&target, bytes)?;
+        extracted += 1;
+    }
+    Ok(extracted)
+}
+
+/// Joins every source under `out_dir` into one string, each behind
+/// [`VINEFLOWER_HEADER`]. The fallback encoding is reported if any file
+/// needed it.
+fn frame_sources(out_dir: &Path, config: &DecompilerConfig) -> Result<DecodedOutput> {
+    let mut out = DecodedOutput::default();
+    let count = for_each_java_source(out_dir, config, &mut |cls| {
+        out.text.push_str(VINEFLOWER_HEADER);
+        out.text.push_str(cls.content.trim_end());
+        out.text.push('\n');
+        if cls.encoding.is_some() {
+            out.encoding = Some(config.fallback_encoding);
+        }
+    })?;
+    if count == 0 {
+        bail!("Vineflower produced no output");
+    }
+    Ok(out)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::decompiler::java_env_lock;
+    use crate::parse::parse_decompiled_output;
+    use std::io::Write;
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use zip::write::FileOptions;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        std::env::temp_dir().join(format!(
+            "class_finder_vineflower_test_{}_{}_{}",
+            std::process::id(),
+            nanos,
+            name
+        ))
+    }
+
+    fn write_file(path: &Path, content: &str) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+
+    fn make_executable(path: &Path) -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+        let mut perms = std::fs::metadata(path)?.permissions();
+        perms.set_mode(0o755);
+        std::fs::set_permissions(path, perms)?;
+        Ok(())
+    }
+
+    fn write_jar(path: &Path, entries: &[(&str, &[u8])]) -> Result<()> {
+        let file = File::create(path)?;
+        let mut zip = zip::ZipWriter::new(file);
+        let options = FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        for (name, content) in entries {
+            zip.start_file(*name, options)?;
+            zip.write_all(content)?;
+        }
+        zip.finish()?;
+        Ok(())
+    }
+
+    #[test]
+    fn decompile_class_extracts_entry_and_frames_output() -> Result<()> {
+        let _guard = java_env_lock().lock().expect("PATH test lock poisoned");
+        let base = temp_dir("decompile_class");
+        let fake_vf = base.join("vineflower.jar");
+        let jar = base.join("repo/demo.jar");
+        let fake_bin = base.join("bin");
+        let fake_java = fake_bin.join("java");
+
+        write_file(&fake_vf, "stub")?;
+        std::fs::create_dir_all(base.join("repo"))?;
+        write_jar(
+            &jar,
+            &[
+                ("org/example/A.class", b""),
+                ("org/example/A$Inner.class", b""),
+                ("org/example/B.class", b""),
+            ],
+        )?;
+        write_file(
+            &fake_java,
+            r#"#!/bin/sh
+set -e
+# -jar <vineflower.jar> -log=ERROR -e=<jar> <in> <out>
+in="$5"
+out="$6"
+[ -f "$in/org/example/A.class" ] || { echo "missing input" >&2; exit 1; }
+[ -f "$in/org/example/A\$Inner.class" ] || { echo "missing inner" >&2; exit 1; }
+[ ! -f "$in/org/example/B.class" ] || { echo "unexpected sibling" >&2; exit 1; }
+mkdir -p "$out/org/example"
+printf 'package org.example;\n\npublic class A {\n}\n' > "$out/org/example/A.java"
+"#,
+        )?;
+        make_executable(&fake_java)?;
+
+        let old_path = std::env::var("PATH").unwrap_or_default();
+        let new_path = format!("{}:{}", fake_bin.to_string_lossy(), old_path);
+        // SAFETY: Guarded by java_env_lock and restored before returning.
+        unsafe { std::env::set_var("PATH", &new_path) };
+
+        let result: Result<()> = {
+            let vineflower = Vineflower::new(fake_vf);
+            let out = vineflower.decompile_class(&jar, "org.example.A")?.text;
+            assert!(out.starts_with(VINEFLOWER_HEADER));
+            let parsed = parse_decompiled_output(&out);
+            assert_eq!(parsed.len(), 1);
+            assert_eq!(parsed[0].class_name, "org.example.A");
+            Ok(())
+        };
+
+        // SAFETY: Guarded by java_env_lock and restored before returning.
+        unsafe { std::env::set_var("PATH", old_path) };
+        let _ = std::fs::remove_dir_all(base);
+        result
+    }
+
+    #[test]
+    fn decompile_jar_reads_archive_output() -> Result<()> {
+        let _guard = java_env_lock().lock().expect("PATH test lock poisoned");
+        let base = temp_dir("decompile_jar");
+        let fake_vf = base.join("vineflower.jar");
+        let jar = base.join("demo.jar");
+        let fake_bin = base.join("bin");
+        let fake_java = fake_bin.join("java");
+        let prepared = base.join("prepared.jar");
+
+        write_file(&fake_vf, "stub")?;
+        write_jar(&jar, &[("org/example/A.class", b"")])?;
+        write_jar(
+            &prepared,
+            &[
+                (
+                    "org/example/A.java",
+                    b"package org.example;\npublic class A {}\n",
+                ),
+                (
+                    "org/example/B.java",
+                    b"package org.example;\npublic class B {}\n",
+                ),
+            ],
+        )?;
+        write_file(
+            &fake_java,
+            &format!(
+                "#!/bin/sh\nset -e\ncp \"{}\" \"$5/demo.jar\"\n",
+                prepared.to_string_lossy()
+            ),
+        )?;
+        make_executable(&fake_java)?;
+
+        let old_path = std::env::var("PATH").unwrap_or_default();
+        let new_path = format!("{}:{}", fake_bin.to_string_lossy(), old_path);
+        // SAFETY: Guarded by java_env_lock and restored before returning.
+        unsafe { std::env::set_var("PATH", &new_path) };
+
+        let result: Result<()> = {
+            let vineflower = Vineflower::new(fake_vf);
+            let out = vineflower.decompile_jar(&jar)?.text;
+            let names: Vec<String> = parse_decompiled_output(&out)
+                .into_iter()
+                .map(|c| c.class_name)
+                .collect();
+            assert_eq!(names, vec!["org.example.A", "org.example.B"]);
+            Ok(())
+        };
+
+        // SAFETY: Guarded by java_env_lock and restored before returning.
+        unsafe { std::env::set_var("PATH", old_path) };
+        let _ = std::fs::remove_dir_all(base);
+        result
+    }
+}
@@ -11,38 +11,140 @@
 //! should be warmed based on access patterns.
 
 use anyhow::Result;
+use clap::ValueEnum;
+use heed::types::Str;
+use heed::{Database, Env};
 use rayon::ThreadPool;
+use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
-use std::collections::{BinaryHeap, HashSet};
+use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering as AtomicOrdering};
 use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
 use std::thread::JoinHandle;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use crate::buffer::{PendingWrite, WriteBufferHandle};
-use crate::cache::ClassContentSource;
+use crate::buffer::{Mutation, PendingWrite, Table, WriteBufferHandle};
+use crate::cache::{ClassContentSource, WARMUP_QUEUE_DB, class_source_key, loaded_jar_key};
 use crate::catalog;
-use crate::cfr::Cfr;
+use crate::decompiler::{Decompiler, java_process_slots, with_abort};
 use crate::hotspot::HotspotTracker;
-use crate::parse::parse_decompiled_output;
+use crate::parse::{ClassKind, ParsedClass};
+use crate::registry::ClassRegistry;
 use crate::source;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
 pub enum WarmupMode {
+    /// Top-level types only, without nested classes or package/module info
+    #[value(name = "top-level")]
     TopLevelOnly,
+    /// Every class the decompiler produces
+    #[value(name = "all")]
     AllClasses,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+impl WarmupMode {
+    /// Value recorded in `JARS_DB` for a jar loaded in this mode.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::TopLevelOnly => "top-level",
+            Self::AllClasses => "all",
+        }
+    }
+
+    /// Mode of a `JARS_DB` value. Markers written before modes were recorded
+    /// (`"1"`) stand for a full load.
+    pub fn from_marker(value: &str) -> Self {
+        if value == Self::TopLevelOnly.as_str() {
+            Self::TopLevelOnly
+        } else {
+            Self::AllClasses
+        }
+    }
+
+    /// Whether a jar loaded in this mode already holds everything a load in
+    /// `other` would cache.
+    pub fn covers(self, other: Self) -> bool {
+        self == Self::AllClasses || self == other
+    }
+
+    /// Whether this mode caches the class named `class_name`.
+    pub fn includes(self, class_name: &str, cache_package_info: bool) -> bool {
+        let info_file = ClassKind::for_info_file(class_name).is_some();
+        match self {
+            Self::AllClasses => cache_package_info || !info_file,
+            Self::TopLevelOnly => !info_file && !class_name.contains('$'),
+        }
+    }
+
+    /// Like [`Self::includes`], using the kind the parser found in the source.
+    pub fn includes_class(self, cls: &ParsedClass, cache_package_info: bool) -> bool {
+        match self {
+            Self::AllClasses => cache_package_info || cls.kind.is_type(),
+            Self::TopLevelOnly => cls.kind.is_type() && !cls.is_nested(),
+        }
+    }
+}
+
+/// Order in which a bulk warmup processes its targets.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum WarmupOrder {
+    /// Accesses per MiB of jar, so small, frequently used jars come first
+    #[default]
+    Score,
+    /// Smallest jars first
+    Size,
+    /// Most accessed jars first, the smallest first among equals
+    Hotness,
+    /// By jar path
+    Path,
+}
+
+/// Accesses per MiB of jar (scaled by 1000). Jars under 1 MiB count as 1 MiB,
+/// and a jar never accessed scores like one accessed once.
+pub fn warmup_score(access_count: u32, size_bytes: u64) -> u64 {
+    (u64::from(access_count) + 1) * 1000 / (size_bytes / (1024 * 1024) + 1)
+}
+
+/// Sorts `jars` by `order`, looking up each jar's access count with
+/// `access_count`. Ties fall back to size, then path.
+pub fn order_jars(
+    jars: Vec<PathBuf>,
+    order: WarmupOrder,
+    access_count: impl Fn(&Path) -> u32,
+) -> Vec<PathBuf> {
+    let mut keyed: Vec<(u32, u64, PathBuf)> = jars
+        .into_iter()
+        .map(|jar| {
+            let size = jar.metadata().map_or(u64::MAX, |m| m.len());
+            (access_count(&jar), size, jar)
+        })
+        .collect();
+    keyed.sort_by(|a, b| {
+        let by_size = || a.1.cmp(&b.1).then_with(|| a.2.cmp(&b.2));
+        match order {
+            WarmupOrder::Score => warmup_score(b.0, b.1)
+                .cmp(&warmup_score(a.0, a.1))
+                .then_with(by_size),
+            WarmupOrder::Size => by_size(),
+            WarmupOrder::Hotness => b.0.cmp(&a.0).then_with(by_size),
+            WarmupOrder::Path => a.2.cmp(&b.2),
+        }
+    });
+    keyed.into_iter().map(|(_, _, jar)| jar).collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum WarmupPriority {
     Low = 0,
     Normal = 1,
     High = 2,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct WarmupTask {
     pub jar_path: PathBuf,
     pub priority: WarmupPriority,
@@ -50,10 +152,139 @@ pub struct WarmupTask {
     pub exclude_fqns: HashSet<String>,
 }
 
+impl WarmupTask {
+    /// One task doing the work of both: the higher priority, the mode that
+    /// covers the other, and only the classes both leave out.
+    fn merge(&self, other: &WarmupTask) -> WarmupTask {
+        WarmupTask {
+            jar_path: self.jar_path.clone(),
+            priority: self.priority.max(other.priority),
+            mode: if self.mode.covers(other.mode) {
+                self.mode
+            } else {
+                other.mode
+            },
+            exclude_fqns: self
+                .exclude_fqns
+                .intersection(&other.exclude_fqns)
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+/// A warmup target waiting in the persisted [`WarmupQueue`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueuedJar {
+    pub jar_path: PathBuf,
+    pub priority: WarmupPriority,
+    pub mode: WarmupMode,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct QueueRow {
+    priority: WarmupPriority,
+    mode: WarmupMode,
+    seq: u64,
+}
+
+/// Warmup targets persisted in `WARMUP_QUEUE_DB` (keyed by jar path), so a
+/// run that is interrupted can be resumed with `warmup --resume`.
+#[derive(Debug, Clone)]
+pub struct WarmupQueue {
+    db: Arc<Env>,
+}
+
+impl WarmupQueue {
+    pub fn new(db: Arc<Env>) -> Self {
+        Self { db }
+    }
+
+    /// Queues `jars` behind the entries already queued. A jar that is already
+    /// queued keeps its place and takes the higher of the two priorities.
+    pub fn push_all(&self, jars: &[QueuedJar]) -> Result<()> {
+        if jars.is_empty() {
+            return Ok(());
+        }
+        let mut wtxn = self.db.write_txn()?;
+        let table: Database<Str, Str> =
+            self.db.create_database(&mut wtxn, Some(WARMUP_QUEUE_DB))?;
+        let mut next_seq = 0u64;
+        for item in table.iter(&wtxn)? {
+            let (_, v) = item?;
+            if let Ok(row) = serde_json::from_str::<QueueRow>(v) {
+                next_seq = next_seq.max(row.seq + 1);
+            }
+        }
+        for jar in jars {
+            let key = jar.jar_path.to_string_lossy();
+            let existing = table
+                .get(&wtxn, &key)?
+                .and_then(|v| serde_json::from_str::<QueueRow>(v).ok());
+            let row = match existing {
+                Some(row) => QueueRow {
+                    priority: row.priority.max(jar.priority),
+                    ..row
+                },
+                None => {
+                    next_seq += 1;
+                    QueueRow {
+                        priority: jar.priority,
+                        mode: jar.mode,
+                        seq: next_seq - 1,
+                    }
+                }
+            };
+            table.put(&mut wtxn, &key, &serde_json::to_string(&row)?)?;
+        }
+        wtxn.commit()?;
+        Ok(())
+    }
+
+    /// Queued jars, highest priority first and in queue order within a
+    /// priority.
+    pub fn pending(&self) -> Result<Vec<QueuedJar>> {
+        let rtxn = self.db.read_txn()?;
+        let Some(table) = self
+            .db
+            .open_database::<Str, Str>(&rtxn, Some(WARMUP_QUEUE_DB))?
+        else {
+            return Ok(Vec::new());
+        };
+        let mut rows = Vec::new();
+        for item in table.iter(&rtxn)? {
+            let (k, v) = item?;
+            if let Ok(row) = serde_json::from_str::<QueueRow>(v) {
+                rows.push((PathBuf::from(k), row));
+            }
+        }
+        rows.sort_by(|(_, a), (_, b)| b.priority.cmp(&a.priority).then(a.seq.cmp(&b.seq)));
+        Ok(rows
+            .into_iter()
+            .map(|(jar_path, row)| QueuedJar {
+                jar_path,
+                priority: row.priority,
+                mode: row.mode,
+            })
+            .collect())
+    }
+
+    pub fn remove(&self, jar_path: &Path) -> Result<()> {
+        let mut wtxn = self.db.write_txn()?;
+        let table: Database<Str, Str> =
+            self.db.create_database(&mut wtxn, Some(WARMUP_QUEUE_DB))?;
+        table.delete(&mut wtxn, &jar_path.to_string_lossy())?;
+        wtxn.commit()?;
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct WarmerConfig {
     pub max_concurrent: usize,
     pub poll_interval_ms: u64,
+    /// Also cache `package-info` and `module-info` sources.
+    pub cache_package_info: bool,
 }
 
 impl Default for WarmerConfig {
@@ -61,6 +292,7 @@ impl Default for WarmerConfig {
         Self {
             max_concurrent: 2,
             poll_interval_ms: 50,
+            cache_package_info: false,
         }
     }
 }
@@ -71,6 +303,11 @@ pub struct WarmerStats {
     pub running_tasks: Arc<AtomicUsize>,
     pub completed_tasks: Arc<AtomicU64>,
     pub failed_tasks: Arc<AtomicU64>,
+    pub failed_classes: Arc<AtomicU64>,
+    /// Tasks dropped from the queue by [`Warmer::cancel`] before they started.
+    pub cancelled_tasks: Arc<AtomicU64>,
+    /// Re-submissions that raised the priority of a task still queued.
+    pub upgraded_tasks: Arc<AtomicU64>,
 }
 
 impl WarmerStats {
@@ -80,29 +317,59 @@ impl WarmerStats {
             running_tasks: Arc::new(AtomicUsize::new(0)),
             completed_tasks: Arc::new(AtomicU64::new(0)),
             failed_tasks: Arc::new(AtomicU64::new(0)),
+            failed_classes: Arc::new(AtomicU64::new(0)),
+            cancelled_tasks: Arc::new(AtomicU64::new(0)),
+            upgraded_tasks: Arc::new(AtomicU64::new(0)),
         }
     }
+
+    /// JVMs currently running in this process, across the warmer and any
+    /// other decompile work (see [`java_process_slots`]).
+    pub fn java_processes_in_flight(&self) -> usize {
+        java_process_slots().in_flight()
+    }
+}
+
+/// How far a [`Warmer`] is asked to stop.
+#[derive(Clone, Default)]
+struct StopFlags {
+    /// Queued tasks are dropped.
+    cancelled: Arc<AtomicBool>,
+    /// The decompiler processes of running tasks are killed too (see
+    /// [`with_abort`]).
+    aborted: Arc<AtomicBool>,
 }
 
 pub struct Warmer {
     tx: Option<Sender<WarmupTask>>,
     stats: WarmerStats,
+    stop: StopFlags,
     handle: Option<JoinHandle<()>>,
 }
 
 impl Warmer {
     pub fn new(
-        cfr: Cfr,
+        decompiler: Arc<dyn Decompiler>,
         buffer: WriteBufferHandle,
         hotspot: Option<HotspotTracker>,
         config: WarmerConfig,
     ) -> Result<Self> {
         let (tx, rx) = std::sync::mpsc::channel::<WarmupTask>();
         let stats = WarmerStats::new();
-        let handle = spawn_warmer(rx, cfr, buffer, hotspot, config, stats.clone());
+        let stop = StopFlags::default();
+        let handle = spawn_warmer(
+            rx,
+            decompiler,
+            buffer,
+            hotspot,
+            config,
+            stats.clone(),
+            stop.clone(),
+        );
         Ok(Self {
             tx: Some(tx),
             stats,
+            stop,
             handle: Some(handle),
         })
     }
@@ -128,18 +395,67 @@ impl Warmer {
         }
         Ok(())
     }
+
+    /// Stops after the running tasks: queued tasks are dropped and counted in
+    /// [`WarmerStats::cancelled_tasks`], and this returns once the running
+    /// ones have finished and queued their writes.
+    pub fn cancel(&mut self) {
+        self.stop.cancelled.store(true, AtomicOrdering::Relaxed);
+        self.tx.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+
+    /// Like [`Self::cancel`], but the running tasks are cut short as well:
+    /// their decompiler processes are killed and the tasks fail, so this
+    /// returns without waiting for them to finish.
+    pub fn abort(&mut self) {
+        self.stop.aborted.store(true, AtomicOrdering::SeqCst);
+        self.cancel();
+    }
+
+    /// Stops accepting tasks and waits up to `budget` for the submitted ones
+    /// to finish. Returns false if some were still queued or running; those
+    /// keep going until [`Self::cancel`] or [`Self::abort`], or until the
+    /// warmer is dropped, which aborts them.
+    pub fn drain_within(&mut self, budget: Duration) -> bool {
+        self.tx.take();
+        let deadline = Instant::now() + budget;
+        while self.handle.as_ref().is_some_and(|h| !h.is_finished()) {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+        true
+    }
+}
+
+impl Drop for Warmer {
+    fn drop(&mut self) {
+        if self.handle.is_some() {
+            self.abort();
+        }
+    }
 }
 
+/// A task waiting in the warmer. Within a priority, tasks with a higher
+/// [`warmup_score`] run first, then in submission order.
 #[derive(Debug)]
 struct QueuedTask {
     priority: WarmupPriority,
+    score: u64,
     seq: u64,
     task: WarmupTask,
 }
 
 impl PartialEq for QueuedTask {
     fn eq(&self, other: &Self) -> bool {
-        self.priority == other.priority && self.seq == other.seq
+        self.priority == other.priority && self.score == other.score && self.seq == other.seq
     }
 }
 
@@ -155,17 +471,19 @@ impl Ord for QueuedTask {
     fn cmp(&self, other: &Self) -> Ordering {
         self.priority
             .cmp(&other.priority)
+            .then_with(|| self.score.cmp(&other.score))
             .then_with(|| other.seq.cmp(&self.seq))
     }
 }
 
 fn spawn_warmer(
     rx: Receiver<WarmupTask>,
-    cfr: Cfr,
+    decompiler: Arc<dyn Decompiler>,
     buffer: WriteBufferHandle,
     hotspot: Option<HotspotTracker>,
     config: WarmerConfig,
     stats: WarmerStats,
+    stop: StopFlags,
 ) -> JoinHandle<()> {
     std::thread::spawn(move || {
         let pool = rayon::ThreadPoolBuilder::new()
@@ -173,6 +491,10 @@ fn spawn_warmer(
             .build()
             .unwrap();
         let mut queue: BinaryHeap<QueuedTask> = BinaryHeap::new();
+        // The live heap entry of each queued jar, by `seq`. A re-submission
+        // merges into it by pushing a replacement and leaving the old entry to
+        // be skipped when popped.
+        let mut queued: HashMap<PathBuf, (u64, WarmupTask)> = HashMap::new();
         let mut in_flight: HashSet<PathBuf> = HashSet::new();
         let (done_tx, done_rx) = std::sync::mpsc::channel::<PathBuf>();
         let next_seq = AtomicU64::new(0);
@@ -185,12 +507,34 @@ fn spawn_warmer(
 
             match rx.recv_timeout(Duration::from_millis(config.poll_interval_ms)) {
                 Ok(task) => {
-                    let seq = next_seq.fetch_add(1, AtomicOrdering::Relaxed);
-                    queue.push(QueuedTask {
-                        priority: task.priority,
-                        seq,
-                        task,
-                    });
+                    let task = match queued.get(&task.jar_path) {
+                        Some((_, existing)) => {
+                            // Folded into the queued task, so not pending on its own.
+                            stats.pending_tasks.fetch_sub(1, AtomicOrdering::Relaxed);
+                            let merged = existing.merge(&task);
+                            if merged.priority > existing.priority {
+                                stats.upgraded_tasks.fetch_add(1, AtomicOrdering::Relaxed);
+                            }
+                            (merged != *existing).then_some(merged)
+                        }
+                        None => Some(task),
+                    };
+                    if let Some(task) = task {
+                        let seq = next_seq.fetch_add(1, AtomicOrdering::Relaxed);
+                        queued.insert(task.jar_path.clone(), (seq, task.clone()));
+                        let access_count = hotspot
+                            .as_ref()
+                            .and_then(|h| h.get_hotspot(&task.jar_path.to_string_lossy()).ok())
+                            .flatten()
+                            .map_or(0, |h| h.access_count);
+                        let size = task.jar_path.metadata().map_or(u64::MAX, |m| m.len());
+                        queue.push(QueuedTask {
+                            priority: task.priority,
+                            score: warmup_score(access_count, size),
+                            seq,
+                            task,
+                        });
+                    }
                 }
                 Err(RecvTimeoutError::Timeout) => {}
                 Err(RecvTimeoutError::Disconnected) => {
@@ -198,8 +542,33 @@ fn spawn_warmer(
                 }
             }
 
+            if stop.cancelled.load(AtomicOrdering::Relaxed) {
+                let dropped = queued.len() + rx.try_iter().count();
+                queue.clear();
+                queued.clear();
+                stats
+                    .pending_tasks
+                    .fetch_sub(dropped, AtomicOrdering::Relaxed);
+                stats
+                    .cancelled_tasks
+                    .fetch_add(dropped as u64, AtomicOrdering::Relaxed);
+                if stats.running_tasks.load(AtomicOrdering::Relaxed) == 0 {
+                    break;
+                }
+                continue;
+            }
+
             while stats.running_tasks.load(AtomicOrdering::Relaxed) < config.max_concurrent.max(1) {
-                let Some(queued) = queue.pop() else { break };
+                let Some(next) = queue.pop() else { break };
+                if queued
+                    .get(&next.task.jar_path)
+                    .is_none_or(|(seq, _)| *seq != next.seq)
+                {
+                    // Replaced by a merged entry.
+                    continue;
+                }
+                queued.remove(&next.task.jar_path);
+                let queued = next;
                 if in_flight.contains(&queued.task.jar_path) {
                     stats.pending_tasks.fetch_sub(1, AtomicOrdering::Relaxed);
                     continue;
@@ -209,7 +578,7 @@ fn spawn_warmer(
                 stats.pending_tasks.fetch_sub(1, AtomicOrdering::Relaxed);
                 stats.running_tasks.fetch_add(1, AtomicOrdering::Relaxed);
 
-                let cfr = cfr.clone();
+                let decompiler = Arc::clone(&decompiler);
                 let buffer = buffer.clone();
                 let stats = stats.clone();
                 let done_tx = done_tx.clone();
@@ -217,13 +586,28 @@ fn spawn_warmer(
                 let jar_path = queued.task.jar_path.clone();
                 let mode = queued.task.mode;
                 let exclude_fqns = queued.task.exclude_fqns.clone();
+                let cache_package_info = config.cache_package_info;
+                let aborted = Arc::clone(&stop.aborted);
 
                 spawn_on_pool(&pool, move || {
-                    let outcome =
-                        warmup_jar(&cfr, &buffer, jar_path.as_path(), mode, &exclude_fqns);
+                    let outcome = with_abort(aborted, || {
+                        warmup_jar(
+                            decompiler.as_ref(),
+                            &buffer,
+                            jar_path.as_path(),
+                            mode,
+                            &exclude_fqns,
+                            cache_package_info,
+                        )
+                    });
+                    // Record the jar as warmed only once its sources are committed.
+                    let outcome = outcome.and_then(|counts| buffer.flush().map(|_| counts));
                     match outcome {
-                        Ok(class_count) => {
+                        Ok((class_count, failed_count)) => {
                             stats.completed_tasks.fetch_add(1, AtomicOrdering::Relaxed);
+                            stats
+                                .failed_classes
+                                .fetch_add(failed_count as u64, AtomicOrdering::Relaxed);
                             if let Some(hotspot) = hotspot.as_ref() {
                                 let jar_key = jar_path.to_string_lossy().to_string();
                                 let _ = hotspot.mark_warmed(&jar_key, class_count as u32);
@@ -254,78 +638,139 @@ fn spawn_on_pool(pool: &ThreadPool, f: impl FnOnce() + Send + 'static) {
 }
 
 fn warmup_jar(
-    cfr: &Cfr,
+    decompiler: &dyn Decompiler,
     buffer: &WriteBufferHandle,
     jar_path: &Path,
     mode: WarmupMode,
     exclude_fqns: &HashSet<String>,
-) -> Result<usize> {
+    cache_package_info: bool,
+) -> Result<(usize, usize)> {
     let jar_key = jar_path.to_string_lossy().to_string();
     let cataloged_classes = catalog::catalog(jar_path).unwrap_or_default();
+    if !cataloged_classes.is_empty() {
+        buffer.mutate_group(ClassRegistry::catalog_mutations(
+            &jar_key,
+            &cataloged_classes,
+        ))?;
+    }
+    // Queued as one group, like `load`, so the loaded marker of a full warmup
+    // never commits without the sources.
+    let mut writes = Vec::new();
     let mut cached_classes = HashSet::new();
     let mut class_count = 0usize;
 
     for cls in source::read_jar_sources(jar_path).unwrap_or_default() {
-        if exclude_fqns.contains(&cls.class_name) {
-            continue;
-        }
-        if mode == WarmupMode::TopLevelOnly && cls.class_name.contains('$') {
-            continue;
-        }
-        if cls.class_name.ends_with("package-info") || cls.class_name.ends_with("module-info") {
+        if exclude_fqns.contains(&cls.class_name) || !mode.includes_class(&cls, cache_package_info)
+        {
             continue;
         }
 
         cached_classes.insert(cls.class_name.clone());
-        let key = format!("{}::{jar_key}", cls.class_name);
-        let _ = buffer.enqueue(PendingWrite {
+        let key = class_source_key(&cls.class_name, &jar_key, decompiler.kind());
+        writes.push(Mutation::Put(PendingWrite {
             key,
             content: cls.content,
             source: ClassContentSource::SourcesJar,
-        });
+            options: Vec::new(),
+            decompiler_version: None,
+            encoding: None,
+            postprocessed: false,
+        }));
         class_count += 1;
     }
 
-    let needs_decompile = cached_classes.is_empty()
-        || cataloged_classes
+    let missing: Option<Vec<String>> = (!cached_classes.is_empty()).then(|| {
+        cataloged_classes
             .iter()
-            .any(|class_name| !cached_classes.contains(class_name));
+            .filter(|class_name| {
+                !cached_classes.contains(*class_name)
+                    && !exclude_fqns.contains(*class_name)
+                    && mode.includes(class_name, cache_package_info)
+            })
+            .cloned()
+            .collect()
+    });
+    let options = decompiler.options();
+    let decompiler_version = decompiler.version();
+    let mut failed_count = 0usize;
+    let mut store = |mut cls: ParsedClass| {
+        if exclude_fqns.contains(&cls.class_name) || !mode.includes_class(&cls, cache_package_info)
+        {
+            return;
+        }
+        if cached_classes.contains(&cls.class_name) {
+            return;
+        }
+        if cls.failed {
+            failed_count += 1;
+            return;
+        }
+        cached_classes.insert(cls.class_name.clone());
+        let postprocessed = decompiler
+            .postprocessor()
+            .is_some_and(|p| p.apply(&mut cls));
 
-    if needs_decompile {
-        let decompiled = cfr.decompile_jar(jar_path)?;
-        let classes = parse_decompiled_output(&decompiled);
+        let key = class_source_key(&cls.class_name, &jar_key, decompiler.kind());
+        writes.push(Mutation::Put(PendingWrite {
+            key,
+            content: cls.content,
+            source: ClassContentSource::Decompiled,
+            options: options.clone(),
+            decompiler_version: decompiler_version.clone(),
+            encoding: cls.encoding,
+            postprocessed,
+        }));
+        class_count += 1;
+    };
 
-        for cls in classes {
-            if exclude_fqns.contains(&cls.class_name) {
-                continue;
-            }
-            if cached_classes.contains(&cls.class_name) {
-                continue;
-            }
-            if mode == WarmupMode::TopLevelOnly && cls.class_name.contains('$') {
-                continue;
-            }
-            if cls.class_name.ends_with("package-info") || cls.class_name.ends_with("module-info") {
-                continue;
+    match missing {
+        None => {
+            decompiler.decompile_jar_each(jar_path, &mut store)?;
+        }
+        Some(missing) => {
+            let batch = decompiler.decompile_classes(jar_path, &missing)?;
+            batch.classes.into_iter().for_each(&mut store);
+            for (class_name, err) in &batch.failed {
+                tracing::warn!("failed to decompile {class_name}: {err}");
             }
-
-            cached_classes.insert(cls.class_name.clone());
-            let key = format!("{}::{jar_key}", cls.class_name);
-            let _ = buffer.enqueue(PendingWrite {
-                key,
-                content: cls.content,
-                source: ClassContentSource::Decompiled,
-            });
-            class_count += 1;
+            failed_count += batch.failed.len();
         }
     }
+    // A top-level run could overwrite the marker of an earlier full load,
+    // which this handle cannot read, so only full runs record one.
+    if mode == WarmupMode::AllClasses && exclude_fqns.is_empty() {
+        writes.push(Mutation::PutValue {
+            table: Table::Jars,
+            key: loaded_jar_key(&jar_key, decompiler.kind()),
+            value: mode.as_str().to_string(),
+        });
+    }
+    buffer.mutate_group(writes)?;
 
-    Ok(class_count)
+    Ok((class_count, failed_count))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::buffer::{BufferConfig, WriteBuffer};
+    use crate::cache::PersistentCache;
+    use crate::decompiler::DecompilerKind;
+    use crate::encoding::DecodedOutput;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn temp_db_path(name: &str) -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        std::env::temp_dir().join(format!(
+            "class_finder_test_{}_{}_{}.lmdb",
+            std::process::id(),
+            nanos,
+            name
+        ))
+    }
 
     #[test]
     fn queued_task_orders_by_priority_then_fifo() {
@@ -339,16 +784,19 @@ mod tests {
         let mut heap = BinaryHeap::new();
         heap.push(QueuedTask {
             priority: WarmupPriority::Normal,
+            score: 0,
             seq: 0,
             task: dummy.clone(),
         });
         heap.push(QueuedTask {
             priority: WarmupPriority::High,
+            score: 0,
             seq: 2,
             task: dummy.clone(),
         });
         heap.push(QueuedTask {
             priority: WarmupPriority::High,
+            score: 0,
             seq: 1,
             task: dummy.clone(),
         });
@@ -365,4 +813,263 @@ mod tests {
         assert_eq!(third.priority, WarmupPriority::Normal);
         assert_eq!(third.seq, 0);
     }
+
+    #[test]
+    fn queued_task_prefers_higher_scores_within_a_priority() {
+        let task = |name: &str| WarmupTask {
+            jar_path: PathBuf::from(name),
+            priority: WarmupPriority::Normal,
+            mode: WarmupMode::AllClasses,
+            exclude_fqns: HashSet::new(),
+        };
+        let small = warmup_score(1, 100 * 1024);
+        let large = warmup_score(1, 80 * 1024 * 1024);
+        assert!(small > large);
+
+        let mut heap = BinaryHeap::new();
+        for (seq, (name, priority, score)) in [
+            ("large.jar", WarmupPriority::Normal, large),
+            ("small.jar", WarmupPriority::Normal, small),
+            ("hot-large.jar", WarmupPriority::High, large),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            heap.push(QueuedTask {
+                priority,
+                score,
+                seq: seq as u64,
+                task: task(name),
+            });
+        }
+        let order: Vec<_> = std::iter::from_fn(|| heap.pop())
+            .map(|queued| queued.task.jar_path)
+            .collect();
+        assert_eq!(
+            order,
+            ["hot-large.jar", "small.jar", "large.jar"].map(PathBuf::from)
+        );
+    }
+
+    #[test]
+    fn order_jars_schedules_by_size_hotness_or_path() -> Result<()> {
+        let dir = temp_db_path("order_jars");
+        std::fs::create_dir_all(&dir)?;
+        let jar = |name: &str, size: usize| -> Result<PathBuf> {
+            let path = dir.join(name);
+            std::fs::write(&path, vec![0u8; size])?;
+            Ok(path)
+        };
+        let mib = 1024 * 1024;
+        let big = jar("a-big.jar", 3 * mib)?;
+        let small = jar("b-small.jar", 1024)?;
+        let medium = jar("c-medium.jar", mib)?;
+        let jars = vec![big.clone(), small.clone(), medium.clone()];
+        // The big jar is accessed most, but not enough to outweigh its size.
+        let access_count = |path: &Path| if path == big { 3 } else { 1 };
+
+        let scheduled = |order| order_jars(jars.clone(), order, access_count);
+        assert_eq!(
+            scheduled(WarmupOrder::Score),
+            [small.clone(), medium.clone(), big.clone()]
+        );
+        assert_eq!(
+            scheduled(WarmupOrder::Size),
+            [small.clone(), medium.clone(), big.clone()]
+        );
+        assert_eq!(
+            scheduled(WarmupOrder::Hotness),
+            [big.clone(), small.clone(), medium.clone()]
+        );
+        assert_eq!(scheduled(WarmupOrder::Path), [big, small, medium]);
+
+        let _ = std::fs::remove_dir_all(dir);
+        Ok(())
+    }
+
+    #[test]
+    fn warmup_queue_persists_targets_in_priority_order() -> Result<()> {
+        let db_path = temp_db_path("warmup_queue");
+        let cache = PersistentCache::open(db_path.clone())?;
+        let queue = WarmupQueue::new(cache.db());
+        let jar = |name: &str, priority| QueuedJar {
+            jar_path: PathBuf::from(name),
+            priority,
+            mode: WarmupMode::AllClasses,
+        };
+
+        queue.push_all(&[
+            jar("a.jar", WarmupPriority::Normal),
+            jar("b.jar", WarmupPriority::Normal),
+        ])?;
+        queue.push_all(&[
+            jar("c.jar", WarmupPriority::High),
+            jar("b.jar", WarmupPriority::High),
+            jar("a.jar", WarmupPriority::Low),
+        ])?;
+        let order: Vec<_> = queue
+            .pending()?
+            .into_iter()
+            .map(|q| (q.jar_path, q.priority))
+            .collect();
+        assert_eq!(
+            order,
+            [
+                (PathBuf::from("b.jar"), WarmupPriority::High),
+                (PathBuf::from("c.jar"), WarmupPriority::High),
+                (PathBuf::from("a.jar"), WarmupPriority::Normal),
+            ]
+        );
+
+        queue.remove(Path::new("b.jar"))?;
+        assert_eq!(queue.pending()?.len(), 2);
+        assert_eq!(cache.stats()?.warmup_pending_tasks, 2);
+
+        drop(queue);
+        drop(cache);
+        let _ = std::fs::remove_file(db_path);
+        Ok(())
+    }
+
+    /// Takes `delay` per jar and records the jars it was asked for.
+    struct SlowDecompiler {
+        delay: Duration,
+        jars: std::sync::Mutex<Vec<PathBuf>>,
+    }
+
+    impl SlowDecompiler {
+        fn new(delay: Duration) -> Arc<Self> {
+            Arc::new(Self {
+                delay,
+                jars: std::sync::Mutex::new(Vec::new()),
+            })
+        }
+    }
+
+    impl Decompiler for SlowDecompiler {
+        fn kind(&self) -> DecompilerKind {
+            DecompilerKind::Cfr
+        }
+
+        fn decompile_class(&self, _jar_path: &Path, _class_name: &str) -> Result<DecodedOutput> {
+            Ok(DecodedOutput::default())
+        }
+
+        fn decompile_jar(&self, jar_path: &Path) -> Result<DecodedOutput> {
+            self.jars.lock().unwrap().push(jar_path.to_path_buf());
+            std::thread::sleep(self.delay);
+            Ok(DecodedOutput::default())
+        }
+    }
+
+    #[test]
+    fn cancel_drops_queued_tasks_and_waits_for_running_ones() -> Result<()> {
+        let db_path = temp_db_path("warmer_cancel");
+        let cache = PersistentCache::open(db_path.clone())?;
+        let mut buffer = WriteBuffer::new(
+            cache.db(),
+            BufferConfig::default(),
+            cache.pending_gauge_path(),
+        );
+        let mut warmer = Warmer::new(
+            SlowDecompiler::new(Duration::from_millis(100)),
+            buffer.handle().expect("buffer is running"),
+            None,
+            WarmerConfig::default(),
+        )?;
+        for i in 0..20 {
+            warmer.submit(WarmupTask {
+                jar_path: PathBuf::from(format!("slow-{i}.jar")),
+                priority: WarmupPriority::Normal,
+                mode: WarmupMode::AllClasses,
+                exclude_fqns: HashSet::new(),
+            })?;
+        }
+        std::thread::sleep(Duration::from_millis(150));
+        warmer.cancel();
+
+        let stats = warmer.stats();
+        let completed = stats.completed_tasks.load(AtomicOrdering::Relaxed);
+        let failed = stats.failed_tasks.load(AtomicOrdering::Relaxed);
+        let cancelled = stats.cancelled_tasks.load(AtomicOrdering::Relaxed);
+        assert!(completed >= 1, "running tasks finish");
+        assert!(cancelled >= 1, "queued tasks are dropped");
+        assert_eq!(completed + failed + cancelled, 20);
+        assert_eq!(stats.pending_tasks.load(AtomicOrdering::Relaxed), 0);
+        assert_eq!(stats.running_tasks.load(AtomicOrdering::Relaxed), 0);
+
+        buffer.shutdown_and_flush()?;
+        drop(buffer);
+        drop(cache);
+        let _ = std::fs::remove_file(db_path);
+        Ok(())
+    }
+
+    #[test]
+    fn resubmitting_a_queued_jar_upgrades_it_instead_of_running_it_twice() -> Result<()> {
+        let db_path = temp_db_path("warmer_upgrade");
+        let cache = PersistentCache::open(db_path.clone())?;
+        let mut buffer = WriteBuffer::new(
+            cache.db(),
+            BufferConfig::default(),
+            cache.pending_gauge_path(),
+        );
+        let decompiler = SlowDecompiler::new(Duration::from_millis(150));
+        let mut warmer = Warmer::new(
+            decompiler.clone(),
+            buffer.handle().expect("buffer is running"),
+            None,
+            WarmerConfig {
+                max_concurrent: 1,
+                ..WarmerConfig::default()
+            },
+        )?;
+        let task = |name: &str, priority| WarmupTask {
+            jar_path: PathBuf::from(name),
+            priority,
+            mode: WarmupMode::AllClasses,
+            exclude_fqns: HashSet::new(),
+        };
+        // The blocker keeps the only slot busy while the others queue up.
+        warmer.submit(task("blocker.jar", WarmupPriority::High))?;
+        std::thread::sleep(Duration::from_millis(20));
+        warmer.submit(task("other.jar", WarmupPriority::Normal))?;
+        warmer.submit(task("hot.jar", WarmupPriority::Normal))?;
+        warmer.submit(task("hot.jar", WarmupPriority::High))?;
+        warmer.shutdown_and_drain()?;
+
+        let ran = decompiler.jars.lock().unwrap().clone();
+        assert_eq!(
+            ran,
+            ["blocker.jar", "hot.jar", "other.jar"].map(PathBuf::from)
+        );
+        let stats = warmer.stats();
+        assert_eq!(stats.upgraded_tasks.load(AtomicOrdering::Relaxed), 1);
+        assert_eq!(stats.completed_tasks.load(AtomicOrdering::Relaxed), 3);
+        assert_eq!(stats.pending_tasks.load(AtomicOrdering::Relaxed), 0);
+
+        buffer.shutdown_and_flush()?;
+        drop(buffer);
+        drop(cache);
+        let _ = std::fs::remove_file(db_path);
+        Ok(())
+    }
+
+    #[test]
+    fn warmup_mode_filters_and_covers() {
+        let top = WarmupMode::TopLevelOnly;
+        let all = WarmupMode::AllClasses;
+        assert!(top.includes("a.Outer", true));
+        assert!(!top.includes("a.Outer$Inner", false));
+        assert!(!top.includes("a.package-info", true));
+        assert!(all.includes("a.Outer$Inner", false));
+        assert!(all.includes("a.package-info", true));
+        assert!(!all.includes("a.package-info", false));
+
+        assert!(all.covers(top) && all.covers(all) && top.covers(top));
+        assert!(!top.covers(all));
+        assert_eq!(WarmupMode::from_marker("1"), all);
+        assert_eq!(WarmupMode::from_marker(top.as_str()), top);
+        assert_eq!(WarmupMode::from_marker(all.as_str()), all);
+    }
 }
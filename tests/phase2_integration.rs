@@ -72,8 +72,11 @@ fn run_json(bin: &str, args: &[&str], envs: &[(&str, &str)]) -> anyhow::Result<V
     }
     let out = cmd.output()?;
     if !out.status.success() {
+        let code = serde_json::from_slice::<Value>(&out.stdout)
+            .ok()
+            .and_then(|report| report["error"]["code"].as_str().map(String::from));
         return Err(anyhow::anyhow!(
-            "command failed: status={:?}, stderr={}",
+            "command failed: status={:?}, code={code:?}, stderr={}",
             out.status.code(),
             String::from_utf8_lossy(&out.stderr)
         ));
@@ -81,6 +84,28 @@ fn run_json(bin: &str, args: &[&str], envs: &[(&str, &str)]) -> anyhow::Result<V
     Ok(serde_json::from_slice(&out.stdout)?)
 }
 
+/// Runs a command expected to fail and returns its exit status and the
+/// `error` object it printed, after checking the shape shared by all errors.
+fn run_json_error(
+    bin: &str,
+    args: &[&str],
+    envs: &[(&str, &str)],
+) -> anyhow::Result<(Option<i32>, Value)> {
+    let mut cmd = Command::new(bin);
+    cmd.args(args);
+    for (k, v) in envs {
+        cmd.env(k, v);
+    }
+    let out = cmd.output()?;
+    anyhow::ensure!(!out.status.success(), "command succeeded: {args:?}");
+    let mut report: Value = serde_json::from_slice(&out.stdout)?;
+    let error = report["error"].take();
+    anyhow::ensure!(error["code"].is_string(), "no error code: {report}");
+    anyhow::ensure!(error["message"].is_string(), "no error message: {error}");
+    anyhow::ensure!(error["details"].is_object(), "no error details: {error}");
+    Ok((out.status.code(), error))
+}
+
 #[test]
 fn phase2_three_layer_flow_works() -> anyhow::Result<()> {
     let base = temp_dir("phase2_flow");
@@ -320,7 +345,9 @@ fi
         &envs,
     )?;
     assert!(
-        load["skipped"] == Value::Bool(true) || load["classes_loaded"].as_u64().unwrap_or(0) > 0
+        load["skipped"] == Value::Bool(true)
+            || load["classes_loaded"].as_u64().unwrap_or(0) > 0
+            || load["classes_skipped_cached"].as_u64().unwrap_or(0) > 0
     );
 
     let third = run_json(
@@ -394,6 +421,447 @@ fi
     Ok(())
 }
 
+#[test]
+fn structure_output_reports_javadoc_on_request_and_ranges_into_code() -> anyhow::Result<()> {
+    let base = temp_dir("javadoc_flag");
+    let m2 = base.join("m2");
+    let db = base.join("db.lmdb");
+    let fake_cfr = base.join("cfr.jar");
+    write_file(&fake_cfr, "stub")?;
+
+    let jar = m2.join("org/example/demo/1.0/demo-1.0.jar");
+    write_jar(&jar, &[("org/example/pkg/A.class", b"")])?;
+    let sources_jar = m2.join("org/example/demo/1.0/demo-1.0-sources.jar");
+    write_jar(
+        &sources_jar,
+        &[(
+            "org/example/pkg/A.java",
+            b"package org.example.pkg;\n\n/** Entry point. */\npublic class A {\n    /**\n     * Runs it.\n     */\n    public void run() {}\n}\n",
+        )],
+    )?;
+
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let (m2, db, fake_cfr) = (
+        m2.to_string_lossy().to_string(),
+        db.to_string_lossy().to_string(),
+        fake_cfr.to_string_lossy().to_string(),
+    );
+    let find_args = |extra: &[&'static str]| {
+        let mut args = vec![
+            "--m2",
+            m2.as_str(),
+            "--db",
+            db.as_str(),
+            "--cfr",
+            fake_cfr.as_str(),
+            "find",
+            "org.example.pkg.A",
+        ];
+        args.extend_from_slice(extra);
+        args
+    };
+    let run = |extra: &[&'static str]| run_json(bin, &find_args(extra), &[]);
+    let run_code = |extra: &[&'static str]| -> anyhow::Result<String> {
+        let output = Command::new(bin).args(find_args(extra)).output()?;
+        anyhow::ensure!(output.status.success(), "find --format code failed");
+        Ok(String::from_utf8(output.stdout)?)
+    };
+
+    let compact = run(&["--format", "structure"])?;
+    let structure = &compact["versions"][0]["structure"];
+    assert!(structure["javadoc"].is_null());
+    assert!(structure["methods"][0]["javadoc"].is_null());
+
+    let with_javadoc = run(&["--format", "structure", "--with-javadoc"])?;
+    let structure = &with_javadoc["versions"][0]["structure"];
+    assert_eq!(structure["javadoc"], "/** Entry point. */");
+    assert!(
+        structure["methods"][0]["javadoc"]
+            .as_str()
+            .unwrap_or_default()
+            .contains("Runs it.")
+    );
+
+    let code = run_code(&["--format", "code"])?;
+    let method = &structure["methods"][0];
+    let start = method["start_byte"].as_u64().unwrap_or_default() as usize;
+    let end = method["end_byte"].as_u64().unwrap_or_default() as usize;
+    assert_eq!(&code[start..end], "public void run() {}");
+    assert_eq!(method["start_line"], 8);
+    assert_eq!(with_javadoc["versions"][0]["content_matches"], true);
+
+    let json = run(&["--with-javadoc"])?;
+    assert_eq!(json["versions"][0]["javadoc"], "/** Entry point. */");
+    assert!(run(&[])?["versions"][0].get("javadoc").is_none());
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[test]
+fn second_structure_output_reads_the_structure_cache() -> anyhow::Result<()> {
+    let base = temp_dir("structure_cache");
+    let m2 = base.join("m2");
+    let db = base.join("db.lmdb");
+    let fake_cfr = base.join("cfr.jar");
+    write_file(&fake_cfr, "stub")?;
+
+    let jar = m2.join("org/example/demo/1.0/demo-1.0.jar");
+    write_jar(&jar, &[("org/example/pkg/A.class", b"")])?;
+    let sources_jar = m2.join("org/example/demo/1.0/demo-1.0-sources.jar");
+    write_jar(
+        &sources_jar,
+        &[(
+            "org/example/pkg/A.java",
+            b"package org.example.pkg;\n\n/** Entry point. */\npublic class A {\n    public void run() {}\n}\n",
+        )],
+    )?;
+
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let m2 = m2.to_string_lossy().to_string();
+    let db = db.to_string_lossy().to_string();
+    let fake_cfr = fake_cfr.to_string_lossy().to_string();
+    let globals = [
+        "--m2",
+        m2.as_str(),
+        "--db",
+        db.as_str(),
+        "--cfr",
+        fake_cfr.as_str(),
+    ];
+    let find = |extra: &[&str]| {
+        let mut args = globals.to_vec();
+        args.extend_from_slice(&["find", "org.example.pkg.A", "--format", "structure"]);
+        args.extend_from_slice(extra);
+        run_json(bin, &args, &[])
+    };
+
+    let first = find(&[])?;
+    assert_eq!(first["versions"][0]["structure_cached"], false);
+    assert_eq!(first["versions"][0]["structure"]["name"], "A");
+    assert_eq!(first["versions"][0]["structure"]["kind"], "class");
+    assert_eq!(
+        first["versions"][0]["structure"]["modifiers"],
+        serde_json::json!(["public"])
+    );
+
+    let second = find(&["--with-javadoc"])?;
+    assert_eq!(second["versions"][0]["structure_cached"], true);
+    let structure = &second["versions"][0]["structure"];
+    assert_eq!(structure["name"], "A");
+    assert_eq!(structure["methods"][0]["name"], "run");
+    assert_eq!(structure["javadoc"], "/** Entry point. */");
+
+    let mut stats_args = globals.to_vec();
+    stats_args.push("stats");
+    let stats = run_json(bin, &stats_args, &[])?;
+    assert_eq!(stats["structure_entries"], 1);
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[test]
+fn structure_output_lists_other_top_level_types() -> anyhow::Result<()> {
+    let base = temp_dir("structure_other_types");
+    let m2 = base.join("m2");
+    let db = base.join("db.lmdb");
+    let fake_cfr = base.join("cfr.jar");
+    write_file(&fake_cfr, "stub")?;
+
+    let jar = m2.join("org/example/demo/1.0/demo-1.0.jar");
+    write_jar(&jar, &[("org/example/pkg/A.class", b"")])?;
+    let sources_jar = m2.join("org/example/demo/1.0/demo-1.0-sources.jar");
+    write_jar(
+        &sources_jar,
+        &[(
+            "org/example/pkg/A.java",
+            b"package org.example.pkg;\n\nclass Helper {\n    void help() {}\n}\n\npublic class A {\n    public void run() {}\n}\n",
+        )],
+    )?;
+
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let m2 = m2.to_string_lossy().to_string();
+    let db = db.to_string_lossy().to_string();
+    let fake_cfr = fake_cfr.to_string_lossy().to_string();
+    let json = run_json(
+        bin,
+        &[
+            "--m2",
+            m2.as_str(),
+            "--db",
+            db.as_str(),
+            "--cfr",
+            fake_cfr.as_str(),
+            "find",
+            "org.example.pkg.A",
+            "--format",
+            "structure",
+        ],
+        &[],
+    )?;
+    let version = &json["versions"][0];
+    assert_eq!(version["structure"]["name"], "A");
+    assert_eq!(
+        version["structure"]["methods"].as_array().map(Vec::len),
+        Some(1)
+    );
+    assert_eq!(version["other_types"][0]["name"], "Helper");
+    assert_eq!(version["other_types"][0]["methods"][0]["name"], "help");
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[test]
+fn diff_reports_structural_changes_between_versions() -> anyhow::Result<()> {
+    let base = temp_dir("structure_diff");
+    let m2 = base.join("m2");
+    let db = base.join("db.lmdb");
+    let fake_cfr = base.join("cfr.jar");
+    write_file(&fake_cfr, "stub")?;
+
+    let versions: [(&str, &[u8]); 2] = [
+        (
+            "1.0",
+            b"package org.example.pkg;\n\npublic class A implements Runnable {\n    private int count;\n    public void run() {}\n    public int size() { return count; }\n}\n",
+        ),
+        (
+            "2.0",
+            b"package org.example.pkg;\n\npublic class A implements Runnable {\n    private long count;\n    public void run() {}\n    public long size() { return count; }\n    public void reset() {}\n}\n",
+        ),
+    ];
+    for (version, source) in versions {
+        let dir = m2.join(format!("org/example/demo/{version}"));
+        write_jar(
+            &dir.join(format!("demo-{version}.jar")),
+            &[("org/example/pkg/A.class", b"")],
+        )?;
+        write_jar(
+            &dir.join(format!("demo-{version}-sources.jar")),
+            &[("org/example/pkg/A.java", source)],
+        )?;
+    }
+
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let m2 = m2.to_string_lossy().to_string();
+    let db = db.to_string_lossy().to_string();
+    let fake_cfr = fake_cfr.to_string_lossy().to_string();
+    let args = |extra: &[&'static str]| {
+        let mut args = vec![
+            "--m2",
+            m2.as_str(),
+            "--db",
+            db.as_str(),
+            "--cfr",
+            fake_cfr.as_str(),
+            "diff",
+            "org.example.pkg.A",
+            "1.0",
+            "2.0",
+        ];
+        args.extend_from_slice(extra);
+        args
+    };
+
+    let json = run_json(bin, &args(&[]), &[])?;
+    assert_eq!(json["from"]["version"], "1.0");
+    assert_eq!(json["to"]["version"], "2.0");
+    let diff = &json["diff"];
+    assert_eq!(diff["methods_added"][0], "public void reset()");
+    assert_eq!(diff["methods_modified"][0]["name"], "size");
+    assert_eq!(diff["methods_modified"][0]["changes"][0], "return_type");
+    assert_eq!(diff["fields_modified"][0]["changes"][0], "type");
+    assert!(diff.get("implements_added").is_none());
+
+    let output = Command::new(bin)
+        .args(args(&["--format", "text"]))
+        .output()?;
+    anyhow::ensure!(output.status.success(), "diff --format text failed");
+    let text = String::from_utf8(output.stdout)?;
+    assert!(text.starts_with("org.example.pkg.A 1.0 -> 2.0\nA\n"));
+    assert!(text.contains("  + method public void reset()\n"));
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[test]
+fn find_with_method_searches_cached_structures() -> anyhow::Result<()> {
+    let base = temp_dir("with_method");
+    let m2 = base.join("m2");
+    let db = base.join("db.lmdb");
+    let fake_cfr = base.join("cfr.jar");
+    write_file(&fake_cfr, "stub")?;
+
+    let jar = m2.join("org/example/demo/1.0/demo-1.0.jar");
+    write_jar(&jar, &[("org/example/pkg/Codec.class", b"")])?;
+    let sources_jar = m2.join("org/example/demo/1.0/demo-1.0-sources.jar");
+    write_jar(
+        &sources_jar,
+        &[(
+            "org/example/pkg/Codec.java",
+            b"package org.example.pkg;\n\npublic class Codec {\n    public Codec(String name) {}\n    public Object parse(String text) { return null; }\n    public Object parse(byte[] data) { return null; }\n}\n",
+        )],
+    )?;
+
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let m2 = m2.to_string_lossy().to_string();
+    let db = db.to_string_lossy().to_string();
+    let fake_cfr = fake_cfr.to_string_lossy().to_string();
+    let globals = [
+        "--m2",
+        m2.as_str(),
+        "--db",
+        db.as_str(),
+        "--cfr",
+        fake_cfr.as_str(),
+    ];
+    let run = |extra: &[&str]| {
+        let mut args = globals.to_vec();
+        args.extend_from_slice(extra);
+        run_json(bin, &args, &[])
+    };
+
+    run(&["find", "org.example.pkg.Codec"])?;
+
+    let unparsed = run(&["find", "--with-method", "parse(String)"])?;
+    assert_eq!(unparsed["scanned_classes"], 0);
+    assert_eq!(unparsed["matches"].as_array().map(Vec::len), Some(0));
+
+    let other_group = run(&[
+        "find",
+        "--with-method",
+        "parse(String)",
+        "--group",
+        "com.other",
+    ])?;
+    assert_eq!(other_group["parsed_classes"], 0);
+
+    let grouped = run(&[
+        "find",
+        "--with-method",
+        "parse(String)",
+        "--group",
+        "org.example",
+    ])?;
+    assert_eq!(grouped["parsed_classes"], 1);
+    let matches = grouped["matches"].as_array().cloned().unwrap_or_default();
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0]["class_name"], "org.example.pkg.Codec");
+    assert_eq!(matches[0]["version"], "1.0");
+    assert_eq!(matches[0]["signature"], "public Object parse(String text)");
+    assert!(
+        matches[0]["jar_path"]
+            .as_str()
+            .unwrap_or_default()
+            .ends_with("demo-1.0.jar")
+    );
+
+    let cached = run(&["find", "--with-method", "parse(*)"])?;
+    assert_eq!(cached["parsed_classes"], 0);
+    assert_eq!(cached["matches"].as_array().map(Vec::len), Some(2));
+
+    let constructor = run(&["find", "--with-method", "Codec(String)"])?;
+    assert_eq!(
+        constructor["matches"][0]["signature"],
+        "public Codec(String name)"
+    );
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[test]
+fn find_constants_prints_only_constant_values() -> anyhow::Result<()> {
+    let base = temp_dir("constants");
+    let m2 = base.join("m2");
+    let db = base.join("db.lmdb");
+    let fake_cfr = base.join("cfr.jar");
+    write_file(&fake_cfr, "stub")?;
+
+    let jar = m2.join("org/example/demo/1.0/demo-1.0.jar");
+    write_jar(&jar, &[("org/example/pkg/Protocol.class", b"")])?;
+    let sources_jar = m2.join("org/example/demo/1.0/demo-1.0-sources.jar");
+    write_jar(
+        &sources_jar,
+        &[(
+            "org/example/pkg/Protocol.java",
+            b"package org.example.pkg;\n\npublic class Protocol {\n    public static final long DEFAULT_TIMEOUT = 30L;\n    private final int port = 80;\n    static class Magic {\n        static final int VALUE = 0xCAFE;\n    }\n}\n",
+        )],
+    )?;
+
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let json = run_json(
+        bin,
+        &[
+            "--m2",
+            m2.to_string_lossy().as_ref(),
+            "--db",
+            db.to_string_lossy().as_ref(),
+            "--cfr",
+            fake_cfr.to_string_lossy().as_ref(),
+            "find",
+            "org.example.pkg.Protocol",
+            "--constants",
+        ],
+        &[],
+    )?;
+    let constants = &json["versions"][0]["constants"];
+    assert_eq!(constants["DEFAULT_TIMEOUT"]["type"], "long");
+    assert_eq!(constants["DEFAULT_TIMEOUT"]["value"], "30L");
+    assert_eq!(constants["Magic.VALUE"]["value"], "0xCAFE");
+    assert!(constants.get("port").is_none());
+    assert!(json["versions"][0].get("content").is_none());
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[test]
+fn stub_format_prints_declarations_without_bodies() -> anyhow::Result<()> {
+    let base = temp_dir("stub_format");
+    let m2 = base.join("m2");
+    let db = base.join("db.lmdb");
+    let fake_cfr = base.join("cfr.jar");
+    write_file(&fake_cfr, "stub")?;
+
+    let jar = m2.join("org/example/demo/1.0/demo-1.0.jar");
+    write_jar(&jar, &[("org/example/pkg/A.class", b"")])?;
+    let sources_jar = m2.join("org/example/demo/1.0/demo-1.0-sources.jar");
+    write_jar(
+        &sources_jar,
+        &[(
+            "org/example/pkg/A.java",
+            b"package org.example.pkg;\n\nimport java.util.List;\n\npublic class A {\n    private List<String> names = new java.util.ArrayList<>();\n    public A() { names.add(\"a\"); }\n    public int size() { return names.size(); }\n}\n",
+        )],
+    )?;
+
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let output = Command::new(bin)
+        .args([
+            "--m2",
+            m2.to_string_lossy().as_ref(),
+            "--db",
+            db.to_string_lossy().as_ref(),
+            "--cfr",
+            fake_cfr.to_string_lossy().as_ref(),
+            "find",
+            "org.example.pkg.A",
+            "--format",
+            "stub",
+        ])
+        .output()?;
+    anyhow::ensure!(output.status.success(), "find --format stub failed");
+    assert_eq!(
+        String::from_utf8(output.stdout)?,
+        "package org.example.pkg;\n\nimport java.util.List;\n\npublic class A {\n    private List<String> names;\n\n    public A() {}\n    public int size();\n}\n"
+    );
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
 #[test]
 fn sources_jar_is_preferred_and_cached() -> anyhow::Result<()> {
     let base = temp_dir("sources_jar");
@@ -653,3 +1121,2366 @@ fi
     let _ = std::fs::remove_dir_all(base);
     Ok(())
 }
+
+#[cfg(unix)]
+#[test]
+fn javap_fallback_is_used_when_decompile_fails() -> anyhow::Result<()> {
+    let base = temp_dir("javap_fallback");
+    let m2 = base.join("m2");
+    let db = base.join("db.lmdb");
+    let fake_cfr = base.join("cfr.jar");
+    write_file(&fake_cfr, "stub")?;
+
+    let jar = m2.join("org/example/demo/1.0/demo-1.0.jar");
+    write_jar(&jar, &[("org/example/pkg/A.class", b"")])?;
+
+    let fake_java = base.join("bin/java");
+    write_file(
+        &fake_java,
+        r#"#!/bin/sh
+echo "CFR exploded" >&2
+exit 1
+"#,
+    )?;
+    make_executable(&fake_java)?;
+    let fake_javap = base.join("bin/javap");
+    write_file(
+        &fake_javap,
+        r#"#!/bin/sh
+echo "Compiled from \"A.java\""
+echo "public class org.example.pkg.A {"
+echo "}"
+"#,
+    )?;
+    make_executable(&fake_javap)?;
+
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let java_bin = fake_java.to_string_lossy().to_string();
+    let javap_bin = fake_javap.to_string_lossy().to_string();
+    let envs = [
+        ("CLASS_FINDER_JAVA", java_bin.as_str()),
+        ("CLASS_FINDER_JAVAP", javap_bin.as_str()),
+    ];
+    let m2_arg = m2.to_string_lossy().to_string();
+    let db_arg = db.to_string_lossy().to_string();
+    let cfr_arg = fake_cfr.to_string_lossy().to_string();
+    let args = [
+        "--m2",
+        m2_arg.as_str(),
+        "--db",
+        db_arg.as_str(),
+        "--cfr",
+        cfr_arg.as_str(),
+        "find",
+        "org.example.pkg.A",
+    ];
+
+    let first = run_json(bin, &args, &envs)?;
+    assert_eq!(
+        first["versions"][0]["source"],
+        Value::String("javap".to_string())
+    );
+    assert_eq!(first["versions"][0]["fallback"], Value::Bool(true));
+    assert!(
+        first["versions"][0]["content"]
+            .as_str()
+            .unwrap_or_default()
+            .contains("public class org.example.pkg.A")
+    );
+
+    let second = run_json(bin, &args, &envs)?;
+    assert_eq!(second["versions"][0]["cache_hit"], Value::Bool(false));
+
+    let mut no_fallback = args.to_vec();
+    no_fallback.push("--no-fallback");
+    assert!(run_json(bin, &no_fallback, &envs).is_err());
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn failed_classes_are_reported_and_retried_individually() -> anyhow::Result<()> {
+    let base = temp_dir("failed_classes");
+    let m2 = base.join("m2");
+    let db = base.join("db.lmdb");
+    let fake_cfr = base.join("cfr.jar");
+    write_file(&fake_cfr, "stub")?;
+
+    let jar = m2.join("org/example/demo/1.0/demo-1.0.jar");
+    write_jar(
+        &jar,
+        &[
+            ("org/example/pkg/A.class", b""),
+            ("org/example/pkg/B.class", b""),
+        ],
+    )?;
+
+    let fake_java = base.join("bin/java");
+    write_file(
+        &fake_java,
+        r#"#!/bin/sh
+if [ "$3" = "--version" ]; then
+  echo "CFR 0.152"
+  exit 0
+fi
+if [ "$4" = "--outputdir" ]; then
+  mkdir -p "$5/org/example/pkg"
+  printf 'package org.example.pkg;\npublic class A {}\n' > "$5/org/example/pkg/A.java"
+  printf 'package org.example.pkg;\npublic class B {\n    /*\n     * Exception decompiling\n     */\n}\n' > "$5/org/example/pkg/B.java"
+  exit 0
+fi
+printf '/*\n * Decompiled with CFR 0.152.\n */\npackage org.example.pkg;\npublic class B { int retried; }\n'
+"#,
+    )?;
+    make_executable(&fake_java)?;
+
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let java_bin = fake_java.to_string_lossy().to_string();
+    let envs = [("CLASS_FINDER_JAVA", java_bin.as_str())];
+    let m2_arg = m2.to_string_lossy().to_string();
+    let db_arg = db.to_string_lossy().to_string();
+    let cfr_arg = fake_cfr.to_string_lossy().to_string();
+    let jar_arg = jar.to_string_lossy().to_string();
+    let global = [
+        "--m2",
+        m2_arg.as_str(),
+        "--db",
+        db_arg.as_str(),
+        "--cfr",
+        cfr_arg.as_str(),
+    ];
+
+    let mut load_args = global.to_vec();
+    load_args.extend(["load", jar_arg.as_str()]);
+    let load = run_json(bin, &load_args, &envs)?;
+    assert_eq!(load["classes_loaded"].as_u64(), Some(1));
+    assert_eq!(load["classes_failed"].as_u64(), Some(1));
+    assert_eq!(
+        load["decompiler_version"],
+        Value::String("0.152".to_string())
+    );
+
+    let mut find_args = global.to_vec();
+    find_args.extend(["find", "org.example.pkg.B"]);
+    let found = run_json(bin, &find_args, &envs)?;
+    assert_eq!(found["versions"][0]["cache_hit"], Value::Bool(false));
+    assert!(
+        found["versions"][0]["content"]
+            .as_str()
+            .unwrap_or_default()
+            .contains("int retried;")
+    );
+    assert_eq!(
+        found["versions"][0]["decompiler_version"],
+        Value::String("0.152".to_string())
+    );
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn cfr_server_serves_find_and_falls_back_when_it_dies() -> anyhow::Result<()> {
+    let base = temp_dir("cfr_server");
+    let m2 = base.join("m2");
+    let db = base.join("db.lmdb");
+    let fake_cfr = base.join("cfr.jar");
+    write_file(&fake_cfr, "stub")?;
+
+    let jar = m2.join("org/example/demo/1.0/demo-1.0.jar");
+    write_jar(&jar, &[("org/example/pkg/A.class", b"")])?;
+
+    let fake_java = base.join("bin/java");
+    write_file(
+        &fake_java,
+        r#"#!/bin/sh
+if [ "$1" = "-cp" ]; then
+  [ -n "$FAIL_SERVER" ] && exit 1
+  while IFS= read -r line; do
+    body='package org.example.pkg;
+public class A { int server; }'
+    printf 'OK %d\n%s' ${#body} "$body"
+  done
+  exit 0
+fi
+[ "$3" = "--version" ] && exit 1
+printf 'package org.example.pkg;\npublic class A { int oneshot; }\n'
+"#,
+    )?;
+    make_executable(&fake_java)?;
+
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let java_bin = fake_java.to_string_lossy().to_string();
+    let m2_arg = m2.to_string_lossy().to_string();
+    let db_arg = db.to_string_lossy().to_string();
+    let cfr_arg = fake_cfr.to_string_lossy().to_string();
+    let args = [
+        "--m2",
+        m2_arg.as_str(),
+        "--db",
+        db_arg.as_str(),
+        "--cfr",
+        cfr_arg.as_str(),
+        "--cfr-server",
+        "find",
+        "org.example.pkg.A",
+    ];
+
+    let served = run_json(bin, &args, &[("CLASS_FINDER_JAVA", java_bin.as_str())])?;
+    let content = served["versions"][0]["content"]
+        .as_str()
+        .unwrap_or_default();
+    assert!(content.contains("int server;"));
+    assert!(served["versions"][0]["decompile_ms"].is_u64());
+
+    let _ = std::fs::remove_file(&db);
+    let fallback = run_json(
+        bin,
+        &args,
+        &[
+            ("CLASS_FINDER_JAVA", java_bin.as_str()),
+            ("FAIL_SERVER", "1"),
+        ],
+    )?;
+    let content = fallback["versions"][0]["content"]
+        .as_str()
+        .unwrap_or_default();
+    assert!(content.contains("int oneshot;"));
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn non_utf8_decompiler_output_is_decoded_and_reported() -> anyhow::Result<()> {
+    let base = temp_dir("non_utf8");
+    let m2 = base.join("m2");
+    let db = base.join("db.lmdb");
+    let fake_cfr = base.join("cfr.jar");
+    write_file(&fake_cfr, "stub")?;
+
+    let jar = m2.join("org/example/demo/1.0/demo-1.0.jar");
+    write_jar(
+        &jar,
+        &[
+            ("org/example/pkg/A.class", b""),
+            ("org/example/pkg/B.class", b""),
+        ],
+    )?;
+
+    let fake_java = base.join("bin/java");
+    write_file(
+        &fake_java,
+        r#"#!/bin/sh
+if [ "$4" = "--outputdir" ]; then
+  mkdir -p "$5/org/example/pkg"
+  printf 'package org.example.pkg;\npublic class A { String s = "caf\351"; }\n' > "$5/org/example/pkg/A.java"
+  printf 'package org.example.pkg;\npublic class B {}\n' > "$5/org/example/pkg/B.java"
+  exit 0
+fi
+printf 'package org.example.pkg;\npublic class A { String s = "caf\351"; }\n'
+"#,
+    )?;
+    make_executable(&fake_java)?;
+
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let java_bin = fake_java.to_string_lossy().to_string();
+    let envs = [("CLASS_FINDER_JAVA", java_bin.as_str())];
+    let m2_arg = m2.to_string_lossy().to_string();
+    let db_arg = db.to_string_lossy().to_string();
+    let cfr_arg = fake_cfr.to_string_lossy().to_string();
+    let jar_arg = jar.to_string_lossy().to_string();
+    let global = [
+        "--m2",
+        m2_arg.as_str(),
+        "--db",
+        db_arg.as_str(),
+        "--cfr",
+        cfr_arg.as_str(),
+    ];
+
+    let mut load_args = global.to_vec();
+    load_args.extend(["--fallback-encoding", "latin1", "load", jar_arg.as_str()]);
+    let load = run_json(bin, &load_args, &envs)?;
+    assert_eq!(load["classes_loaded"].as_u64(), Some(2));
+    assert!(
+        load["warning"]
+            .as_str()
+            .unwrap_or_default()
+            .starts_with("decompiler output for 1 class ")
+    );
+
+    let mut find_args = global.to_vec();
+    find_args.extend(["find", "org.example.pkg.A"]);
+    let found = run_json(bin, &find_args, &envs)?;
+    let version = &found["versions"][0];
+    assert_eq!(version["cache_hit"], Value::Bool(true));
+    assert_eq!(version["encoding"], Value::String("latin1".to_string()));
+
+    let fresh_db = base.join("fresh.lmdb").to_string_lossy().to_string();
+    let fresh_args = [
+        "--m2",
+        m2_arg.as_str(),
+        "--db",
+        fresh_db.as_str(),
+        "--cfr",
+        cfr_arg.as_str(),
+        "find",
+        "org.example.pkg.A",
+    ];
+    let found = run_json(bin, &fresh_args, &envs)?;
+    let version = &found["versions"][0];
+    assert_eq!(version["cache_hit"], Value::Bool(false));
+    assert!(
+        version["content"]
+            .as_str()
+            .unwrap_or_default()
+            .contains("\"caf\u{e9}\"")
+    );
+    assert_eq!(version["encoding"], Value::String("latin1".to_string()));
+    assert!(
+        version["warning"]
+            .as_str()
+            .unwrap_or_default()
+            .contains("not valid UTF-8")
+    );
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn package_info_is_cached_only_when_requested() -> anyhow::Result<()> {
+    let base = temp_dir("package_info");
+    let m2 = base.join("m2");
+    let fake_cfr = base.join("cfr.jar");
+    write_file(&fake_cfr, "stub")?;
+
+    let jar = m2.join("org/example/demo/1.0/demo-1.0.jar");
+    write_jar(
+        &jar,
+        &[
+            ("org/example/pkg/A.class", b""),
+            ("org/example/pkg/package-info.class", b""),
+        ],
+    )?;
+
+    let fake_java = base.join("bin/java");
+    write_file(
+        &fake_java,
+        r#"#!/bin/sh
+if [ "$4" = "--outputdir" ]; then
+  mkdir -p "$5/org/example/pkg"
+  printf 'package org.example.pkg;\npublic class A {}\n' > "$5/org/example/pkg/A.java"
+  printf '@Deprecated\npackage org.example.pkg;\n' > "$5/org/example/pkg/package-info.java"
+  exit 0
+fi
+exit 1
+"#,
+    )?;
+    make_executable(&fake_java)?;
+
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let java_bin = fake_java.to_string_lossy().to_string();
+    let envs = [("CLASS_FINDER_JAVA", java_bin.as_str())];
+    let m2_arg = m2.to_string_lossy().to_string();
+    let cfr_arg = fake_cfr.to_string_lossy().to_string();
+    let jar_arg = jar.to_string_lossy().to_string();
+
+    for (db_name, extra, expected) in [
+        ("default.lmdb", None, 1),
+        ("with_info.lmdb", Some("--cache-package-info"), 2),
+    ] {
+        let db_arg = base.join(db_name).to_string_lossy().to_string();
+        let mut args = vec![
+            "--m2",
+            m2_arg.as_str(),
+            "--db",
+            db_arg.as_str(),
+            "--cfr",
+            cfr_arg.as_str(),
+        ];
+        args.extend(extra);
+        args.extend(["load", jar_arg.as_str()]);
+        let load = run_json(bin, &args, &envs)?;
+        assert_eq!(load["classes_loaded"].as_u64(), Some(expected), "{db_name}");
+    }
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn find_runs_hotspot_warmups_in_the_background() -> anyhow::Result<()> {
+    let base = temp_dir("background_warmup");
+    let m2 = base.join("m2");
+    let db = base.join("db.lmdb");
+    let fake_cfr = base.join("cfr.jar");
+    write_file(&fake_cfr, "stub")?;
+
+    let jar = m2.join("org/example/demo/1.0/demo-1.0.jar");
+    write_jar(
+        &jar,
+        &[
+            ("org/example/pkg/A.class", b""),
+            ("org/example/pkg/B.class", b""),
+        ],
+    )?;
+
+    let fake_java = base.join("bin/java");
+    write_file(
+        &fake_java,
+        r#"#!/bin/sh
+if [ "$4" = "--outputdir" ]; then
+  mkdir -p "$5/org/example/pkg"
+  printf 'package org.example.pkg;\npublic class A {}\n' > "$5/org/example/pkg/A.java"
+  printf 'package org.example.pkg;\npublic class B {}\n' > "$5/org/example/pkg/B.java"
+  exit 0
+fi
+printf 'package org.example.pkg;\npublic class A {}\n'
+"#,
+    )?;
+    make_executable(&fake_java)?;
+
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let java_bin = fake_java.to_string_lossy().to_string();
+    let envs = [("CLASS_FINDER_JAVA", java_bin.as_str())];
+    let m2_arg = m2.to_string_lossy().to_string();
+    let db_arg = db.to_string_lossy().to_string();
+    let cfr_arg = fake_cfr.to_string_lossy().to_string();
+    let global = [
+        "--m2",
+        m2_arg.as_str(),
+        "--db",
+        db_arg.as_str(),
+        "--cfr",
+        cfr_arg.as_str(),
+    ];
+
+    let mut find_args = global.to_vec();
+    find_args.extend([
+        "find",
+        "org.example.pkg.A",
+        "--background-warmup-ms",
+        "30000",
+    ]);
+    let found = run_json(bin, &find_args, &envs)?;
+    assert_eq!(found["versions"][0]["cache_hit"], Value::Bool(false));
+
+    let mut stats_args = global.to_vec();
+    stats_args.push("stats");
+    let stats = run_json(bin, &stats_args, &envs)?;
+    assert_eq!(
+        stats["background_warmup"]["submitted_tasks"].as_u64(),
+        Some(1)
+    );
+    assert_eq!(
+        stats["background_warmup"]["completed_tasks"].as_u64(),
+        Some(1)
+    );
+    assert_eq!(stats["warmed_jars"].as_u64(), Some(1));
+    assert_eq!(stats["warmup_pending_tasks"].as_u64(), Some(0));
+    assert_eq!(stats["warmup_threshold"].as_u64(), Some(2));
+    assert_eq!(stats["group_top"][0]["group_id"], "org.example");
+    assert_eq!(stats["group_top"][0]["warmed_jars"].as_u64(), Some(1));
+
+    let mut stats_args = global.to_vec();
+    stats_args.extend(["--warmup-threshold", "1", "stats"]);
+    let stats = run_json(bin, &stats_args, &envs)?;
+    assert_eq!(stats["warmup_threshold"].as_u64(), Some(1));
+    let stats = run_json(
+        bin,
+        &global.iter().copied().chain(["stats"]).collect::<Vec<_>>(),
+        &[
+            ("CLASS_FINDER_JAVA", java_bin.as_str()),
+            ("CLASS_FINDER_WARMUP_THRESHOLD", "0"),
+        ],
+    )?;
+    assert_eq!(stats["warmup_threshold"].as_u64(), Some(1));
+
+    let mut find_args = global.to_vec();
+    find_args.extend(["find", "org.example.pkg.B"]);
+    let found = run_json(bin, &find_args, &envs)?;
+    assert_eq!(found["versions"][0]["cache_hit"], Value::Bool(true));
+    run_json(bin, &find_args, &envs)?;
+
+    let mut hot_args = global.to_vec();
+    hot_args.extend(["hot", "--classes"]);
+    let hot = run_json(bin, &hot_args, &envs)?;
+    let classes = hot.as_array().expect("hot --classes prints an array");
+    assert_eq!(classes.len(), 2);
+    assert_eq!(classes[0]["class_name"], "org.example.pkg.B");
+    assert_eq!(classes[0]["access_count"].as_u64(), Some(2));
+    assert_eq!(classes[1]["class_name"], "org.example.pkg.A");
+    assert_eq!(classes[1]["jar_path"], jar.to_string_lossy().as_ref());
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn background_warmup_budget_kills_running_decompilers() -> anyhow::Result<()> {
+    let base = temp_dir("background_warmup_budget");
+    let m2 = base.join("m2");
+    let db = base.join("db.lmdb");
+    let fake_cfr = base.join("cfr.jar");
+    write_file(&fake_cfr, "stub")?;
+    let jar = m2.join("org/example/demo/1.0/demo-1.0.jar");
+    write_jar(&jar, &[("org/example/pkg/A.class", b"")])?;
+
+    // The warmup run records its pids and hangs, as would a slow JVM.
+    let pids = base.join("pids");
+    let fake_java = base.join("bin/java");
+    write_file(
+        &fake_java,
+        &format!(
+            r#"#!/bin/sh
+if [ "$4" = "--outputdir" ]; then
+  echo $$ >> "{pids}"
+  sleep 30 &
+  echo $! >> "{pids}"
+  wait
+  exit 0
+fi
+printf 'package org.example.pkg;\npublic class A {{}}\n'
+"#,
+            pids = pids.display()
+        ),
+    )?;
+    make_executable(&fake_java)?;
+
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let java_bin = fake_java.to_string_lossy().to_string();
+    let m2_arg = m2.to_string_lossy().to_string();
+    let db_arg = db.to_string_lossy().to_string();
+    let cfr_arg = fake_cfr.to_string_lossy().to_string();
+    let started = std::time::Instant::now();
+    run_json(
+        bin,
+        &[
+            "--m2",
+            m2_arg.as_str(),
+            "--db",
+            db_arg.as_str(),
+            "--cfr",
+            cfr_arg.as_str(),
+            "find",
+            "org.example.pkg.A",
+            "--background-warmup-ms",
+            "300",
+        ],
+        &[("CLASS_FINDER_JAVA", java_bin.as_str())],
+    )?;
+    assert!(started.elapsed() < std::time::Duration::from_secs(20));
+
+    let pids: Vec<String> = std::fs::read_to_string(&pids)?
+        .lines()
+        .map(str::to_string)
+        .collect();
+    assert_eq!(pids.len(), 2, "the warmup started one decompiler");
+    let alive = |pid: &str| {
+        Command::new("kill")
+            .args(["-0", pid])
+            .stderr(std::process::Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    };
+    // Killed processes are reaped by their parents shortly after.
+    let give_up = std::time::Instant::now() + std::time::Duration::from_secs(5);
+    while pids.iter().any(|pid| alive(pid)) && std::time::Instant::now() < give_up {
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+    let survivors: Vec<&String> = pids.iter().filter(|pid| alive(pid)).collect();
+    assert!(survivors.is_empty(), "still running: {survivors:?}");
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn warmup_streams_jsonl_progress_per_jar() -> anyhow::Result<()> {
+    let base = temp_dir("warmup_progress");
+    let m2 = base.join("m2");
+    let db = base.join("db.lmdb");
+    let fake_cfr = base.join("cfr.jar");
+    write_file(&fake_cfr, "stub")?;
+
+    for version in ["1.0", "2.0"] {
+        let jar = m2.join(format!("org/example/demo/{version}/demo-{version}.jar"));
+        write_jar(&jar, &[("org/example/pkg/A.class", b"")])?;
+    }
+
+    let fake_java = base.join("bin/java");
+    write_file(
+        &fake_java,
+        r#"#!/bin/sh
+if [ "$4" = "--outputdir" ]; then
+  mkdir -p "$5/org/example/pkg"
+  printf 'package org.example.pkg;\npublic class A {}\n' > "$5/org/example/pkg/A.java"
+  exit 0
+fi
+exit 1
+"#,
+    )?;
+    make_executable(&fake_java)?;
+
+    let out = Command::new(env!("CARGO_BIN_EXE_class-finder"))
+        .args([
+            "--m2",
+            m2.to_string_lossy().as_ref(),
+            "--db",
+            db.to_string_lossy().as_ref(),
+            "--cfr",
+            fake_cfr.to_string_lossy().as_ref(),
+            "warmup",
+            "--group",
+            "org.example",
+            "--format",
+            "jsonl",
+        ])
+        .env("CLASS_FINDER_JAVA", &fake_java)
+        .output()?;
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+
+    let lines = String::from_utf8(out.stdout)?
+        .lines()
+        .map(serde_json::from_str::<Value>)
+        .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(lines.len(), 3);
+    for (idx, line) in lines[..2].iter().enumerate() {
+        assert_eq!(line["event"], Value::String("jar".to_string()));
+        assert_eq!(line["index"].as_u64(), Some(idx as u64 + 1));
+        assert_eq!(line["total"].as_u64(), Some(2));
+        assert_eq!(line["ok"], Value::Bool(true));
+        assert_eq!(line["classes_loaded"].as_u64(), Some(1));
+    }
+    assert_eq!(lines[2]["targets"].as_u64(), Some(2));
+    assert_eq!(lines[2]["succeeded"].as_u64(), Some(2));
+
+    let java_bin = fake_java.to_string_lossy().to_string();
+    let envs = [("CLASS_FINDER_JAVA", java_bin.as_str())];
+    let m2_arg = m2.to_string_lossy().to_string();
+    let db_arg = db.to_string_lossy().to_string();
+    let cfr_arg = fake_cfr.to_string_lossy().to_string();
+    let global = [
+        "--m2",
+        m2_arg.as_str(),
+        "--db",
+        db_arg.as_str(),
+        "--cfr",
+        cfr_arg.as_str(),
+    ];
+    let mut stats_args = global.to_vec();
+    stats_args.push("stats");
+    let stats = run_json(env!("CARGO_BIN_EXE_class-finder"), &stats_args, &envs)?;
+    assert_eq!(stats["warmup_pending_tasks"].as_u64(), Some(0));
+    assert_eq!(stats["warmed_jars"].as_u64(), Some(2));
+    assert_eq!(stats["warmed_classes"].as_u64(), Some(2));
+    assert!(stats["last_warmup_at"].as_u64().is_some_and(|at| at > 0));
+    assert_eq!(stats["warmup_threshold"].as_u64(), Some(2));
+
+    let mut resume_args = global.to_vec();
+    resume_args.extend(["warmup", "--resume"]);
+    let resumed = run_json(env!("CARGO_BIN_EXE_class-finder"), &resume_args, &envs)?;
+    assert_eq!(resumed["targets"].as_u64(), Some(0));
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[test]
+fn progress_flag_prints_events_on_stderr() -> anyhow::Result<()> {
+    let base = temp_dir("progress_flag");
+    let m2 = base.join("m2");
+    let db = base.join("db.lmdb");
+    for version in ["1.0", "2.0"] {
+        let jar = m2.join(format!("org/example/demo/{version}/demo-{version}.jar"));
+        write_jar(
+            &jar,
+            &[("org/example/A.class", b""), ("org/example/B.class", b"")],
+        )?;
+    }
+
+    let out = Command::new(env!("CARGO_BIN_EXE_class-finder"))
+        .args([
+            "--m2",
+            m2.to_string_lossy().as_ref(),
+            "--db",
+            db.to_string_lossy().as_ref(),
+            "--progress",
+            "index",
+        ])
+        .output()?;
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let result: Value = serde_json::from_slice(&out.stdout)?;
+    assert_eq!(result["cataloged_jars_new"].as_u64(), Some(2));
+
+    let events = String::from_utf8(out.stderr)?
+        .lines()
+        .filter(|line| line.starts_with("{\"event\""))
+        .map(serde_json::from_str::<Value>)
+        .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(events.len(), 3, "{events:?}");
+    assert_eq!(events[0]["event"], "scan-started");
+    assert_eq!(
+        events[0]["root"],
+        Value::String(m2.to_string_lossy().to_string())
+    );
+    for event in &events[1..] {
+        assert_eq!(event["event"], "jar-cataloged");
+        assert_eq!(event["classes"].as_u64(), Some(2));
+    }
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn top_level_warmup_is_redone_by_a_full_warmup() -> anyhow::Result<()> {
+    let base = temp_dir("warmup_mode");
+    let m2 = base.join("m2");
+    let db = base.join("db.lmdb");
+    let fake_cfr = base.join("cfr.jar");
+    write_file(&fake_cfr, "stub")?;
+
+    let jar = m2.join("org/example/demo/1.0/demo-1.0.jar");
+    write_jar(
+        &jar,
+        &[
+            ("org/example/pkg/A.class", b""),
+            ("org/example/pkg/package-info.class", b""),
+        ],
+    )?;
+
+    let fake_java = base.join("bin/java");
+    write_file(
+        &fake_java,
+        r#"#!/bin/sh
+if [ "$4" = "--outputdir" ]; then
+  mkdir -p "$5/org/example/pkg"
+  printf 'package org.example.pkg;\npublic class A {}\n' > "$5/org/example/pkg/A.java"
+  printf '@Deprecated\npackage org.example.pkg;\n' > "$5/org/example/pkg/package-info.java"
+  exit 0
+fi
+if [ "$3" = "--extraclasspath" ] && [ "$5" = "org.example.pkg.package-info" ]; then
+  printf '/*\n * Decompiled with CFR 0.152.\n */\n@Deprecated\npackage org.example.pkg;\n'
+  exit 0
+fi
+exit 1
+"#,
+    )?;
+    make_executable(&fake_java)?;
+
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let java_bin = fake_java.to_string_lossy().to_string();
+    let envs = [("CLASS_FINDER_JAVA", java_bin.as_str())];
+    let m2_arg = m2.to_string_lossy().to_string();
+    let db_arg = db.to_string_lossy().to_string();
+    let cfr_arg = fake_cfr.to_string_lossy().to_string();
+    let jar_arg = jar.to_string_lossy().to_string();
+
+    // The full warmup finds `A` already cached and only decompiles the rest.
+    for (mode, skipped, loaded, skipped_cached) in [
+        ("top-level", false, 1, 0),
+        ("all", false, 1, 1),
+        ("top-level", true, 0, 0),
+    ] {
+        let args = [
+            "--m2",
+            m2_arg.as_str(),
+            "--db",
+            db_arg.as_str(),
+            "--cfr",
+            cfr_arg.as_str(),
+            "--cache-package-info",
+            "warmup",
+            jar_arg.as_str(),
+            "--mode",
+            mode,
+        ];
+        let output = run_json(bin, &args, &envs)?;
+        let load = &output["loads"][0];
+        assert_eq!(load["skipped"], Value::Bool(skipped), "{mode}");
+        assert_eq!(load["classes_loaded"].as_u64(), Some(loaded), "{mode}");
+        assert_eq!(
+            load["classes_skipped_cached"].as_u64(),
+            Some(skipped_cached),
+            "{mode}"
+        );
+    }
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn warmup_stops_at_the_class_budget_and_resumes_later() -> anyhow::Result<()> {
+    let base = temp_dir("warmup_budget");
+    let m2 = base.join("m2");
+    let db = base.join("db.lmdb");
+    let fake_cfr = base.join("cfr.jar");
+    write_file(&fake_cfr, "stub")?;
+
+    let first = m2.join("org/example/first/1.0/first-1.0.jar");
+    let second = m2.join("org/example/second/1.0/second-1.0.jar");
+    write_jar(&first, &[("org/example/pkg/A.class", b"")])?;
+    write_jar(&second, &[("org/example/pkg/A.class", b"")])?;
+
+    let fake_java = base.join("bin/java");
+    write_file(
+        &fake_java,
+        r#"#!/bin/sh
+if [ "$4" = "--outputdir" ]; then
+  mkdir -p "$5/org/example/pkg"
+  printf 'package org.example.pkg;\npublic class A {}\n' > "$5/org/example/pkg/A.java"
+  exit 0
+fi
+exit 1
+"#,
+    )?;
+    make_executable(&fake_java)?;
+
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let java_bin = fake_java.to_string_lossy().to_string();
+    let envs = [("CLASS_FINDER_JAVA", java_bin.as_str())];
+    let m2_arg = m2.to_string_lossy().to_string();
+    let db_arg = db.to_string_lossy().to_string();
+    let cfr_arg = fake_cfr.to_string_lossy().to_string();
+    let second_arg = second.to_string_lossy().to_string();
+    let common = [
+        "--m2",
+        m2_arg.as_str(),
+        "--db",
+        db_arg.as_str(),
+        "--cfr",
+        cfr_arg.as_str(),
+        "warmup",
+    ];
+
+    let mut args = common.to_vec();
+    args.extend(["--group", "org.example", "--max-classes", "1"]);
+    let output = run_json(bin, &args, &envs)?;
+    assert_eq!(output["succeeded"].as_u64(), Some(1));
+    assert_eq!(output["not_started"].as_u64(), Some(1));
+    assert_eq!(output["stopped_by"], "max-classes");
+
+    let mut args = common.to_vec();
+    args.push("--resume");
+    let output = run_json(bin, &args, &envs)?;
+    assert_eq!(output["succeeded"].as_u64(), Some(1));
+    assert_eq!(output["not_started"].as_u64(), Some(0));
+    assert_eq!(output["loads"][0]["jar_path"], second_arg.as_str());
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn warmup_group_leaves_out_excluded_jars() -> anyhow::Result<()> {
+    let base = temp_dir("warmup_exclude");
+    let m2 = base.join("m2");
+    let db = base.join("db.lmdb");
+    let fake_cfr = base.join("cfr.jar");
+    write_file(&fake_cfr, "stub")?;
+
+    let kept = m2.join("org/example/app/1.0/app-1.0.jar");
+    write_jar(&kept, &[("org/example/pkg/A.class", b"")])?;
+    write_jar(
+        &m2.join("org/example/protos/1.0/protos-1.0.jar"),
+        &[("org/example/pkg/A.class", b"")],
+    )?;
+    write_jar(
+        &m2.join("org/example/gen/client/1.0/client-1.0.jar"),
+        &[("org/example/pkg/A.class", b"")],
+    )?;
+
+    let fake_java = base.join("bin/java");
+    write_file(
+        &fake_java,
+        r#"#!/bin/sh
+if [ "$4" = "--outputdir" ]; then
+  mkdir -p "$5/org/example/pkg"
+  printf 'package org.example.pkg;\npublic class A {}\n' > "$5/org/example/pkg/A.java"
+  exit 0
+fi
+exit 1
+"#,
+    )?;
+    make_executable(&fake_java)?;
+
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let java_bin = fake_java.to_string_lossy().to_string();
+    let envs = [("CLASS_FINDER_JAVA", java_bin.as_str())];
+    let m2_arg = m2.to_string_lossy().to_string();
+    let db_arg = db.to_string_lossy().to_string();
+    let cfr_arg = fake_cfr.to_string_lossy().to_string();
+    let args = [
+        "--m2",
+        m2_arg.as_str(),
+        "--db",
+        db_arg.as_str(),
+        "--cfr",
+        cfr_arg.as_str(),
+        "warmup",
+        "--group",
+        "org.example",
+        "--exclude-group",
+        "org.example.gen",
+        "--exclude-artifact",
+        "protos",
+    ];
+    let output = run_json(bin, &args, &envs)?;
+    assert_eq!(output["targets"].as_u64(), Some(1));
+    assert_eq!(
+        output["loads"][0]["jar_path"],
+        kept.to_string_lossy().as_ref()
+    );
+    let removed: Vec<_> = output["excluded"]
+        .as_array()
+        .expect("exclusion counts")
+        .iter()
+        .map(|count| count["removed"].as_u64())
+        .collect();
+    assert_eq!(removed, [Some(1), Some(1)]);
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn warmup_jobs_load_jars_in_parallel() -> anyhow::Result<()> {
+    let base = temp_dir("warmup_jobs");
+    let m2 = base.join("m2");
+    let db = base.join("db.lmdb");
+    let fake_cfr = base.join("cfr.jar");
+    write_file(&fake_cfr, "stub")?;
+
+    let mut jars = Vec::new();
+    for name in ["a", "b", "c", "d"] {
+        let jar = m2.join(format!("org/example/{name}/1.0/{name}-1.0.jar"));
+        write_jar(&jar, &[("org/example/pkg/A.class", b"")])?;
+        jars.push(jar.to_string_lossy().to_string());
+    }
+
+    let fake_java = base.join("bin/java");
+    write_file(
+        &fake_java,
+        r#"#!/bin/sh
+if [ "$4" = "--outputdir" ]; then
+  sleep 0.2
+  mkdir -p "$5/org/example/pkg"
+  printf 'package org.example.pkg;\npublic class A {}\n' > "$5/org/example/pkg/A.java"
+  exit 0
+fi
+exit 1
+"#,
+    )?;
+    make_executable(&fake_java)?;
+
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let java_bin = fake_java.to_string_lossy().to_string();
+    let envs = [("CLASS_FINDER_JAVA", java_bin.as_str())];
+    let m2_arg = m2.to_string_lossy().to_string();
+    let db_arg = db.to_string_lossy().to_string();
+    let cfr_arg = fake_cfr.to_string_lossy().to_string();
+    let global = [
+        "--m2",
+        m2_arg.as_str(),
+        "--db",
+        db_arg.as_str(),
+        "--cfr",
+        cfr_arg.as_str(),
+        "--jobs",
+        "4",
+    ];
+
+    let mut args = global.to_vec();
+    args.extend(["warmup", "--group", "org.example", "--jobs", "4"]);
+    let output = run_json(bin, &args, &envs)?;
+    assert_eq!(output["succeeded"].as_u64(), Some(4));
+    let loaded: Vec<_> = output["loads"]
+        .as_array()
+        .expect("loads")
+        .iter()
+        .map(|load| load["jar_path"].as_str().unwrap_or_default().to_string())
+        .collect();
+    assert_eq!(loaded, jars, "loads are listed in target order");
+
+    let mut stats_args = global.to_vec();
+    stats_args.push("stats");
+    let stats = run_json(bin, &stats_args, &envs)?;
+    assert_eq!(stats["loaded_jars"].as_u64(), Some(4));
+    assert_eq!(stats["warmed_jars"].as_u64(), Some(4));
+    assert_eq!(stats["warmup_pending_tasks"].as_u64(), Some(0));
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn warmup_reads_targets_from_a_file() -> anyhow::Result<()> {
+    let base = temp_dir("warmup_from_file");
+    let m2 = base.join("m2");
+    let db = base.join("db.lmdb");
+    let fake_cfr = base.join("cfr.jar");
+    write_file(&fake_cfr, "stub")?;
+
+    let by_coordinates = m2.join("org/example/demo/1.0/demo-1.0.jar");
+    let by_path = base.join("libs/extra.jar");
+    write_jar(&by_coordinates, &[("org/example/pkg/A.class", b"")])?;
+    write_jar(&by_path, &[("org/example/pkg/A.class", b"")])?;
+    let list = base.join("jars.txt");
+    write_file(
+        &list,
+        &format!(
+            "# project dependencies\norg.example:demo:1.0\n\n{}\norg.example:missing:1.0\n",
+            by_path.display()
+        ),
+    )?;
+
+    let fake_java = base.join("bin/java");
+    write_file(
+        &fake_java,
+        r#"#!/bin/sh
+if [ "$4" = "--outputdir" ]; then
+  mkdir -p "$5/org/example/pkg"
+  printf 'package org.example.pkg;\npublic class A {}\n' > "$5/org/example/pkg/A.java"
+  exit 0
+fi
+exit 1
+"#,
+    )?;
+    make_executable(&fake_java)?;
+
+    let java_bin = fake_java.to_string_lossy().to_string();
+    let envs = [("CLASS_FINDER_JAVA", java_bin.as_str())];
+    let output = run_json(
+        env!("CARGO_BIN_EXE_class-finder"),
+        &[
+            "--m2",
+            m2.to_string_lossy().as_ref(),
+            "--db",
+            db.to_string_lossy().as_ref(),
+            "--cfr",
+            fake_cfr.to_string_lossy().as_ref(),
+            "warmup",
+            "--from-file",
+            list.to_string_lossy().as_ref(),
+        ],
+        &envs,
+    )?;
+    assert_eq!(output["targets"].as_u64(), Some(2));
+    assert_eq!(output["succeeded"].as_u64(), Some(2));
+    assert_eq!(
+        output["loads"][0]["jar_path"],
+        by_coordinates.to_string_lossy().as_ref()
+    );
+    assert_eq!(
+        output["loads"][1]["jar_path"],
+        by_path.to_string_lossy().as_ref()
+    );
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[test]
+fn config_show_applies_flag_env_file_precedence() -> anyhow::Result<()> {
+    let base = temp_dir("config_show");
+    let config = base.join("config.toml");
+    write_file(
+        &config,
+        "m2 = \"/file/m2\"\ndb = \"/file/db.lmdb\"\ndecompiler = \"vineflower\"\nbuffer_batch_size = 300\nwarmup_threshold = 30\n",
+    )?;
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let config_path = config.to_string_lossy().to_string();
+
+    let json = run_json(
+        bin,
+        &["--db", "/flag/db.lmdb", "config", "show"],
+        &[
+            ("CLASS_FINDER_CONFIG", config_path.as_str()),
+            ("CLASS_FINDER_BUFFER_BATCH_SIZE", "200"),
+        ],
+    )?;
+    assert_eq!(json["config_file"], config_path.as_str());
+    assert_eq!(json["m2"], "/file/m2");
+    assert_eq!(json["db"], "/flag/db.lmdb");
+    assert_eq!(json["decompiler"], "vineflower");
+    assert_eq!(json["write_buffer"]["batch_size"], 200);
+    assert_eq!(json["warmup_threshold"], 30);
+
+    write_file(&config, "buffer_batch = 1\n")?;
+    let out = Command::new(bin)
+        .args(["config", "show"])
+        .env("CLASS_FINDER_CONFIG", &config_path)
+        .output()?;
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("Invalid config file"));
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[test]
+fn path_env_vars_replace_the_global_flags() -> anyhow::Result<()> {
+    let base = temp_dir("path_env_vars");
+    let m2 = base.join("m2");
+    let home = base.join("home");
+    let fake_cfr = base.join("cfr.jar");
+    write_file(&fake_cfr, "stub")?;
+    let jar = m2.join("org/example/demo/1.0/demo-1.0.jar");
+    write_jar(&jar, &[("org/example/A.class", b"")])?;
+    write_jar(
+        &m2.join("org/example/demo/1.0/demo-1.0-sources.jar"),
+        &[(
+            "org/example/A.java",
+            b"package org.example;\npublic class A {}\n",
+        )],
+    )?;
+
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let m2 = m2.to_string_lossy().to_string();
+    let home = home.to_string_lossy().to_string();
+    let fake_cfr = fake_cfr.to_string_lossy().to_string();
+    let db = base.join("home").join("db.lmdb");
+    let envs = [
+        ("CLASS_FINDER_M2", m2.as_str()),
+        ("CLASS_FINDER_HOME", home.as_str()),
+        ("CFR_JAR", fake_cfr.as_str()),
+    ];
+
+    let found = run_json(bin, &["find", "org.example.A"], &envs)?;
+    assert_eq!(found["versions"][0]["version"], "1.0");
+    assert!(db.exists());
+
+    let config = run_json(bin, &["config", "show"], &envs)?;
+    assert_eq!(config["m2"], m2.as_str());
+    assert_eq!(config["db"], db.to_string_lossy().as_ref());
+    assert_eq!(config["sources"]["m2"], "env");
+    assert_eq!(config["sources"]["home"], "env");
+    assert_eq!(config["sources"]["cfr"], "env");
+    assert_eq!(
+        config["precedence"],
+        serde_json::json!(["flag", "env", "file", "default"])
+    );
+
+    let missing_db = base.join("missing.lmdb").to_string_lossy().to_string();
+    let out = Command::new(bin)
+        .arg("stats")
+        .envs(envs.iter().copied())
+        .env("CLASS_FINDER_DB", &missing_db)
+        .output()?;
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("CLASS_FINDER_DB points to"));
+
+    let out = Command::new(bin)
+        .args(["find", "org.example.A"])
+        .envs(envs.iter().copied())
+        .env("CLASS_FINDER_M2", base.join("no-m2"))
+        .output()?;
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("CLASS_FINDER_M2 points to"));
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[test]
+fn find_searches_configured_repositories_by_priority() -> anyhow::Result<()> {
+    let base = temp_dir("repositories");
+    let m2 = base.join("m2");
+    let corp = base.join("corp");
+    let db = base.join("db.lmdb");
+    let fake_cfr = base.join("cfr.jar");
+    write_file(&fake_cfr, "stub")?;
+    let source = |class: &str| format!("package org.example;\npublic class {class} {{}}\n");
+    for (root, version) in [(&m2, "1.0"), (&corp, "2.0")] {
+        let dir = root.join(format!("org/example/demo/{version}"));
+        write_jar(
+            &dir.join(format!("demo-{version}.jar")),
+            &[("org/example/A.class", b""), ("org/example/B.class", b"")],
+        )?;
+        write_jar(
+            &dir.join(format!("demo-{version}-sources.jar")),
+            &[
+                ("org/example/A.java", source("A").as_bytes()),
+                ("org/example/B.java", source("B").as_bytes()),
+            ],
+        )?;
+    }
+    write_jar(
+        &m2.join("org/example/solo/1.0/solo-1.0.jar"),
+        &[("org/example/C.class", b"")],
+    )?;
+    write_jar(
+        &m2.join("org/example/solo/1.0/solo-1.0-sources.jar"),
+        &[("org/example/C.java", source("C").as_bytes())],
+    )?;
+    let config = base.join("config.toml");
+    write_file(
+        &config,
+        &format!(
+            "[[repository]]\nname = \"corp\"\npath = {:?}\npriority = 10\nreadonly = true\n",
+            corp.to_string_lossy()
+        ),
+    )?;
+
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let m2 = m2.to_string_lossy().to_string();
+    let db = db.to_string_lossy().to_string();
+    let fake_cfr = fake_cfr.to_string_lossy().to_string();
+    let config = config.to_string_lossy().to_string();
+    let envs = [("CLASS_FINDER_CONFIG", config.as_str())];
+    let run = |args: &[&str]| {
+        let mut all = vec![
+            "--m2",
+            m2.as_str(),
+            "--db",
+            db.as_str(),
+            "--cfr",
+            fake_cfr.as_str(),
+        ];
+        all.extend_from_slice(args);
+        run_json(bin, &all, &envs)
+    };
+
+    let found = run(&["find", "org.example.A"])?;
+    let versions = found["versions"].as_array().cloned().unwrap_or_default();
+    assert_eq!(versions.len(), 1);
+    assert_eq!(versions[0]["version"], "2.0");
+    assert_eq!(versions[0]["repository"], "corp");
+
+    let found = run(&["find", "org.example.C"])?;
+    assert_eq!(found["versions"][0]["repository"], "local");
+
+    let indexed = run(&["index", "--repo", "corp"])?;
+    assert_eq!(indexed["root"], corp.to_string_lossy().as_ref());
+    assert_eq!(indexed["scanned_jars"], 2);
+
+    let found = run(&["find", "org.example.B"])?;
+    assert_eq!(found["versions"][0]["lookup_source"], "registry");
+    assert_eq!(found["versions"][0]["repository"], "corp");
+
+    let out = Command::new(bin)
+        .args([
+            "--m2",
+            m2.as_str(),
+            "--db",
+            db.as_str(),
+            "watch",
+            "--repo",
+            "corp",
+        ])
+        .envs(envs)
+        .output()?;
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("read-only"));
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[test]
+fn no_download_fails_fast_with_install_instructions() -> anyhow::Result<()> {
+    let base = temp_dir("no_download");
+    let m2 = base.join("m2");
+    let home = base.join("home");
+    let config = base.join("config.toml");
+    write_file(
+        &config,
+        "cfr_download_url = \"https://mirror.invalid/cfr.jar\"\ncfr_sha256 = \"skip\"\n",
+    )?;
+    write_jar(
+        &m2.join("org/example/demo/1.0/demo-1.0.jar"),
+        &[("org/example/A.class", b"")],
+    )?;
+
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let out = Command::new(bin)
+        .args(["--no-download", "find", "org.example.A"])
+        .env("CLASS_FINDER_M2", &m2)
+        .env("CLASS_FINDER_HOME", &home)
+        .env("CLASS_FINDER_CONFIG", &config)
+        .env("HOME", &home)
+        .env_remove("CFR_JAR")
+        .env_remove("JAVA_HOME")
+        .output()?;
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("--no-download"), "stderr: {stderr}");
+    assert!(stderr.contains("https://mirror.invalid/cfr.jar"));
+    assert!(stderr.contains("CFR_JAR"));
+    assert!(!home.join("tools").join("cfr.jar").exists());
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[test]
+fn snapshot_command_publishes_a_copy_and_records_it_in_stats() -> anyhow::Result<()> {
+    let base = temp_dir("snapshot_command");
+    let m2 = base.join("m2");
+    write_jar(
+        &m2.join("org/example/demo/1.0/demo-1.0.jar"),
+        &[("org/example/A.class", b"")],
+    )?;
+    let config = base.join("config.toml");
+    write_file(&config, "[snapshot]\ninterval_secs = 600\n")?;
+
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let db = base.join("db.lmdb");
+    let db_arg = db.to_string_lossy().to_string();
+    let m2_arg = m2.to_string_lossy().to_string();
+    let config_arg = config.to_string_lossy().to_string();
+    let envs = [("CLASS_FINDER_CONFIG", config_arg.as_str())];
+    let global = ["--db", db_arg.as_str(), "--m2", m2_arg.as_str()];
+
+    let indexed = run_json(bin, &[&global[..], &["index"]].concat(), &envs)?;
+    assert_eq!(indexed["indexed_classes"], 1);
+
+    let record = run_json(bin, &[&global[..], &["snapshot"]].concat(), &envs)?;
+    let snapshot = base.join("db.lmdb.snapshot");
+    assert_eq!(record["path"], snapshot.to_string_lossy().as_ref());
+    assert_eq!(record["entries"], 1);
+    assert!(snapshot.is_file());
+
+    let stats = run_json(bin, &[&global[..], &["stats"]].concat(), &envs)?;
+    let mut nested = record.clone();
+    nested
+        .as_object_mut()
+        .map(|record| record.remove("schema_version"));
+    assert_eq!(stats["last_snapshot"], nested);
+
+    let config = run_json(bin, &[&global[..], &["config", "show"]].concat(), &envs)?;
+    assert_eq!(config["snapshot"]["interval_secs"], 600);
+    assert!(config["snapshot"]["min_new_entries"].is_null());
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[test]
+fn selective_clear_keeps_unrelated_tables() -> anyhow::Result<()> {
+    let base = temp_dir("selective_clear");
+    let m2 = base.join("m2");
+    let fake_cfr = base.join("cfr.jar");
+    write_file(&fake_cfr, "stub")?;
+    write_jar(
+        &m2.join("org/example/demo/1.0/demo-1.0.jar"),
+        &[("org/example/A.class", b"")],
+    )?;
+    write_jar(
+        &m2.join("org/example/demo/1.0/demo-1.0-sources.jar"),
+        &[(
+            "org/example/A.java",
+            b"package org.example;\npublic class A {}\n",
+        )],
+    )?;
+
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let db = base.join("db.lmdb");
+    let db_arg = db.to_string_lossy().to_string();
+    let m2_arg = m2.to_string_lossy().to_string();
+    let cfr_arg = fake_cfr.to_string_lossy().to_string();
+    let global = [
+        "--db",
+        db_arg.as_str(),
+        "--m2",
+        m2_arg.as_str(),
+        "--cfr",
+        cfr_arg.as_str(),
+    ];
+    let run = |args: &[&str]| run_json(bin, &[&global[..], args].concat(), &[]);
+
+    run(&["index"])?;
+    run(&["find", "org.example.A"])?;
+    let before = run(&["stats"])?;
+    assert_eq!(before["source_entries"], 1);
+    assert_eq!(before["hotspot_jars"], 1);
+
+    let cleared = run(&["clear", "--sources", "--hotspot"])?;
+    assert_eq!(cleared["cleared"]["class_sources"], 1);
+    assert_eq!(cleared["cleared"]["jar_hotspot"], 1);
+    assert!(cleared["cleared"]["class_registry"].is_null());
+
+    let after = run(&["stats"])?;
+    assert_eq!(after["source_entries"], 0);
+    assert_eq!(after["hotspot_jars"], 0);
+    assert_eq!(after["indexed_classes"], before["indexed_classes"]);
+    assert_eq!(after["cataloged_jars"], before["cataloged_jars"]);
+    assert_eq!(after["loaded_jars"], before["loaded_jars"]);
+
+    let cleared = run(&["clear", "--registry"])?;
+    assert_eq!(cleared["cleared"]["class_registry"], 1);
+    assert_eq!(run(&["stats"])?["indexed_classes"], 0);
+
+    let out = Command::new(bin)
+        .args(global)
+        .args(["clear", "--all", "--sources"])
+        .output()?;
+    assert!(!out.status.success());
+
+    Command::new(bin)
+        .args(global)
+        .args(["clear", "--all"])
+        .output()?;
+    assert!(!db.exists());
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[test]
+fn db_works_as_a_file_or_a_directory() -> anyhow::Result<()> {
+    let base = temp_dir("db_layouts");
+    let m2 = base.join("m2");
+    let fake_cfr = base.join("cfr.jar");
+    write_file(&fake_cfr, "stub")?;
+    write_jar(
+        &m2.join("org/example/demo/1.0/demo-1.0.jar"),
+        &[("org/example/A.class", b"")],
+    )?;
+    write_jar(
+        &m2.join("org/example/demo/1.0/demo-1.0-sources.jar"),
+        &[(
+            "org/example/A.java",
+            b"package org.example;\npublic class A {}\n",
+        )],
+    )?;
+
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let m2_arg = m2.to_string_lossy().to_string();
+    let cfr_arg = fake_cfr.to_string_lossy().to_string();
+    let file_db = base.join("file").join("db.lmdb");
+    let dir_db = base.join("dir");
+    let dir_arg = format!("{}{}", dir_db.to_string_lossy(), std::path::MAIN_SEPARATOR);
+
+    for (db_arg, data, lock, snapshot) in [
+        (
+            file_db.to_string_lossy().to_string(),
+            file_db.clone(),
+            base.join("file").join("db.lmdb-lock"),
+            base.join("file").join("db.lmdb.snapshot"),
+        ),
+        (
+            dir_arg,
+            dir_db.join("data.mdb"),
+            dir_db.join("lock.mdb"),
+            dir_db.join("snapshot").join("data.mdb"),
+        ),
+    ] {
+        let global = [
+            "--db",
+            db_arg.as_str(),
+            "--m2",
+            m2_arg.as_str(),
+            "--cfr",
+            cfr_arg.as_str(),
+        ];
+        let run = |args: &[&str]| run_json(bin, &[&global[..], args].concat(), &[]);
+
+        run(&["index"])?;
+        let found = run(&["find", "org.example.A"])?;
+        assert_eq!(found["versions"][0]["version"], "1.0");
+        assert!(data.is_file(), "{}", data.display());
+        assert!(lock.is_file(), "{}", lock.display());
+
+        run(&["snapshot"])?;
+        assert!(snapshot.is_file(), "{}", snapshot.display());
+        let stats = run(&["stats"])?;
+        assert_eq!(stats["source_entries"], 1);
+
+        Command::new(bin).args(global).arg("clear").output()?;
+        assert!(!data.exists());
+        assert!(!lock.exists());
+    }
+    // The directory stays, and is recognized without a trailing separator.
+    assert!(dir_db.is_dir());
+    let dir_arg = dir_db.to_string_lossy().to_string();
+    run_json(bin, &["--db", dir_arg.as_str(), "stats"], &[])?;
+    assert!(dir_db.join("data.mdb").is_file());
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[test]
+fn doctor_lists_tool_candidates_and_the_chosen_ones() -> anyhow::Result<()> {
+    let base = temp_dir("doctor");
+    let cf_home = base.join("cf");
+    let legacy = base.join("home/.class-finder/tools/cfr.jar");
+    let java = base
+        .join("jdk/bin")
+        .join(format!("java{}", std::env::consts::EXE_SUFFIX));
+    write_file(&legacy, "stub")?;
+    write_file(&java, "stub")?;
+
+    let empty_config = base.join("config.toml");
+    write_file(&empty_config, "")?;
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let out = Command::new(bin)
+        .arg("doctor")
+        .env("CLASS_FINDER_HOME", &cf_home)
+        .env("HOME", base.join("home"))
+        .env("JAVA_HOME", base.join("jdk"))
+        .env("CLASS_FINDER_CONFIG", &empty_config)
+        .env_remove("CFR_JAR")
+        .env_remove("CLASS_FINDER_JAVA")
+        .output()?;
+    assert!(out.status.success());
+    let report: Value = serde_json::from_slice(&out.stdout)?;
+    assert_eq!(report["cfr"]["chosen"], legacy.to_string_lossy().as_ref());
+    assert_eq!(
+        report["cfr"]["candidates"][0]["source"],
+        "CLASS_FINDER_HOME"
+    );
+    assert_eq!(report["cfr"]["candidates"][0]["exists"], false);
+    assert_eq!(report["cfr"]["candidates"][1]["source"], "legacy");
+    assert_eq!(report["java"]["chosen"], java.to_string_lossy().as_ref());
+    assert_eq!(report["java"]["candidates"][0]["source"], "JAVA_HOME");
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[test]
+fn verbosity_flags_and_json_log_format_shape_stderr() -> anyhow::Result<()> {
+    let base = temp_dir("logging");
+    let m2 = base.join("m2");
+    let db = base.join("db.lmdb");
+    write_jar(
+        &m2.join("org/example/demo/1.0/demo-1.0.jar"),
+        &[("org/example/A.class", b"")],
+    )?;
+    let config = base.join("config.toml");
+    write_file(&config, "")?;
+    let fake_cfr = base.join("cfr.jar");
+    write_file(&fake_cfr, "stub")?;
+
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let stderr = |flags: &[&str]| -> anyhow::Result<String> {
+        let out = Command::new(bin)
+            .arg("--db")
+            .arg(&db)
+            .args(flags)
+            .args(["find", "org.example.Missing"])
+            .env("CLASS_FINDER_M2", &m2)
+            .env("CLASS_FINDER_CONFIG", &config)
+            .env("CFR_JAR", &fake_cfr)
+            .env("RUST_BACKTRACE", "0")
+            .output()?;
+        assert!(!out.status.success());
+        Ok(String::from_utf8_lossy(&out.stderr).to_string())
+    };
+    let scan_root = format!("find scan root: {}", m2.join("org/example").display());
+
+    let default = stderr(&[])?;
+    assert!(
+        default.contains(&format!("[class-finder] {scan_root}")),
+        "stderr: {default}"
+    );
+    let quiet = stderr(&["--quiet"])?;
+    assert!(!quiet.contains("[class-finder]"), "stderr: {quiet}");
+    assert!(quiet.contains("org.example.Missing not found"));
+
+    let json = stderr(&["--log-format", "json"])?;
+    let event: Value = json
+        .lines()
+        .find_map(|line| serde_json::from_str(line).ok())
+        .expect("a json log line");
+    assert_eq!(event["level"], "INFO");
+    assert_eq!(event["fields"]["message"], scan_root.as_str());
+    assert_eq!(event["span"]["name"], "find");
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[test]
+fn color_flag_highlights_stdout_but_not_output_files() -> anyhow::Result<()> {
+    let base = temp_dir("color");
+    let m2 = base.join("m2");
+    let db = base.join("db.lmdb");
+    let fake_cfr = base.join("cfr.jar");
+    write_file(&fake_cfr, "stub")?;
+    write_jar(
+        &m2.join("org/example/demo/1.0/demo-1.0.jar"),
+        &[("org/example/pkg/A.class", b"")],
+    )?;
+    let source = "package org.example.pkg;\n\n// Entry point.\npublic class A {\n    String name = \"a\";\n}\n";
+    write_jar(
+        &m2.join("org/example/demo/1.0/demo-1.0-sources.jar"),
+        &[("org/example/pkg/A.java", source.as_bytes())],
+    )?;
+
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let run = |flags: &[&str]| -> anyhow::Result<String> {
+        let out = Command::new(bin)
+            .arg("--m2")
+            .arg(&m2)
+            .arg("--db")
+            .arg(&db)
+            .arg("--cfr")
+            .arg(&fake_cfr)
+            .args(flags)
+            .output()?;
+        anyhow::ensure!(
+            out.status.success(),
+            "{}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+        Ok(String::from_utf8(out.stdout)?)
+    };
+
+    let plain_file = base.join("plain.java");
+    let colored_file = base.join("colored.java");
+    let (plain_arg, colored_arg) = (
+        plain_file.to_string_lossy().to_string(),
+        colored_file.to_string_lossy().to_string(),
+    );
+    run(&["find", "org.example.pkg.A", "--code-only", "-o", &plain_arg])?;
+    run(&[
+        "--color",
+        "always",
+        "find",
+        "org.example.pkg.A",
+        "--code-only",
+        "-o",
+        &colored_arg,
+    ])?;
+    assert_eq!(std::fs::read(&plain_file)?, std::fs::read(&colored_file)?);
+    assert_eq!(std::fs::read_to_string(&plain_file)?, source);
+
+    // Piped stdout stays plain unless colors are forced.
+    assert_eq!(run(&["find", "org.example.pkg.A", "--code-only"])?, source);
+    let colored = run(&[
+        "--color",
+        "always",
+        "find",
+        "org.example.pkg.A",
+        "--code-only",
+    ])?;
+    assert!(colored.contains("\x1b[1;35mpublic\x1b[0m"), "{colored:?}");
+    assert!(colored.contains("\x1b[32m\"a\"\x1b[0m"));
+    let text = run(&[
+        "--color",
+        "always",
+        "find",
+        "org.example.pkg.A",
+        "-f",
+        "text",
+    ])?;
+    assert!(text.contains("cache_hit: \x1b["), "{text:?}");
+    let never = run(&[
+        "--color",
+        "never",
+        "find",
+        "org.example.pkg.A",
+        "-f",
+        "text",
+    ])?;
+    assert!(!never.contains('\x1b'));
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[test]
+fn export_sources_writes_a_package_tree_and_completes_from_sources_jar() -> anyhow::Result<()> {
+    let base = temp_dir("export_sources");
+    let m2 = base.join("m2");
+    let db = base.join("db.lmdb");
+    let fake_cfr = base.join("cfr.jar");
+    write_file(&fake_cfr, "stub")?;
+    let jar = m2.join("org/example/demo/1.0/demo-1.0.jar");
+    write_jar(
+        &jar,
+        &[
+            ("org/example/pkg/A.class", b""),
+            ("org/example/pkg/A$Inner.class", b""),
+            ("org/example/pkg/A$1.class", b""),
+            ("org/example/B.class", b""),
+        ],
+    )?;
+    let a_source =
+        "package org.example.pkg;\n\npublic class A {\n    public static class Inner {\n    }\n}\n";
+    write_jar(
+        &m2.join("org/example/demo/1.0/demo-1.0-sources.jar"),
+        &[
+            ("org/example/pkg/A.java", a_source.as_bytes()),
+            (
+                "org/example/B.java",
+                b"package org.example;\n\npublic class B {\n}\n",
+            ),
+        ],
+    )?;
+
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let (db, fake_cfr, jar) = (
+        db.to_string_lossy().to_string(),
+        fake_cfr.to_string_lossy().to_string(),
+        jar.to_string_lossy().to_string(),
+    );
+    let export = |out: &std::path::Path, extra: &[&str]| {
+        let out = out.to_string_lossy().to_string();
+        let mut args = vec![
+            "--db",
+            db.as_str(),
+            "--cfr",
+            fake_cfr.as_str(),
+            "export-sources",
+            jar.as_str(),
+            "--out",
+            out.as_str(),
+        ];
+        args.extend_from_slice(extra);
+        run_json(bin, &args, &[])
+    };
+
+    let cold = export(&base.join("cold"), &[])?;
+    assert_eq!(cold["files_written"], 0);
+    assert_eq!(cold["missing"].as_array().map(Vec::len), Some(2));
+
+    let separate = base.join("separate");
+    let completed = export(&separate, &["--complete", "--inner", "separate"])?;
+    assert_eq!(completed["from_sources_jar"], 2);
+    assert_eq!(completed["decompiled"], 0);
+    assert_eq!(completed["files_written"], 3);
+    assert!(completed.get("missing").is_none());
+    assert_eq!(
+        std::fs::read_to_string(separate.join("org/example/pkg/A.java"))?,
+        a_source
+    );
+    assert!(separate.join("org/example/B.java").is_file());
+    assert!(
+        std::fs::read_to_string(separate.join("org/example/pkg/A$Inner.java"))?
+            .contains("class Inner")
+    );
+
+    let merged = base.join("merged");
+    let warm = export(&merged, &[])?;
+    assert_eq!(warm["files_written"], 2);
+    assert!(merged.join("org/example/pkg/A.java").is_file());
+    assert!(!merged.join("org/example/pkg/A$Inner.java").exists());
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[test]
+fn json_outputs_carry_a_schema_version_unless_compat_asks_for_the_old_shape() -> anyhow::Result<()>
+{
+    let base = temp_dir("schema_version");
+    let m2 = base.join("m2");
+    write_jar(
+        &m2.join("org/example/demo/1.0/demo-1.0.jar"),
+        &[("org/example/pkg/A.class", b"")],
+    )?;
+    write_jar(
+        &m2.join("org/example/demo/1.0/demo-1.0-sources.jar"),
+        &[(
+            "org/example/pkg/A.java",
+            b"package org.example.pkg;\n\npublic class A {}\n",
+        )],
+    )?;
+    let fake_cfr = base.join("cfr.jar");
+    write_file(&fake_cfr, "stub")?;
+
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let (m2, db, fake_cfr) = (
+        m2.to_string_lossy().to_string(),
+        base.join("db.lmdb").to_string_lossy().to_string(),
+        fake_cfr.to_string_lossy().to_string(),
+    );
+    let run = |extra: &[&str]| {
+        let mut args = vec![
+            "--m2",
+            m2.as_str(),
+            "--db",
+            db.as_str(),
+            "--cfr",
+            fake_cfr.as_str(),
+        ];
+        args.extend_from_slice(extra);
+        run_json(bin, &args, &[])
+    };
+
+    for command in [&["index"][..], &["find", "org.example.pkg.A"], &["stats"]] {
+        let current = run(command)?;
+        assert_eq!(current["schema_version"], 3, "{command:?}");
+
+        let old = run(&[&["--compat", "1"][..], command].concat())?;
+        assert!(old.get("schema_version").is_none(), "{command:?}");
+    }
+    let find = run(&["--compat", "2", "find", "org.example.pkg.A"])?;
+    assert_eq!(find["versions"][0]["source"], "sources-jar");
+
+    let out = Command::new(bin)
+        .args(["--compat", "9", "stats"])
+        .output()?;
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("--compat 9"));
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[test]
+fn paths_format_lists_jars_without_a_decompiler_and_exits_2_on_no_match() -> anyhow::Result<()> {
+    let base = temp_dir("paths_format");
+    let m2 = base.join("m2");
+    let old = m2.join("org/example/demo/1.0/demo-1.0.jar");
+    let new = m2.join("org/example/demo/2.0/demo-2.0.jar");
+    write_jar(&new, &[("org/example/pkg/A.class", b"")])?;
+    write_jar(&old, &[("org/example/pkg/A.class", b"")])?;
+    let home = base.join("home");
+
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let run = |args: &[&str]| {
+        Command::new(bin)
+            .arg("--m2")
+            .arg(&m2)
+            .arg("--db")
+            .arg(base.join("db.lmdb"))
+            .arg("--no-download")
+            .args(args)
+            .env("CLASS_FINDER_HOME", &home)
+            .env("HOME", &home)
+            .env_remove("CFR_JAR")
+            .output()
+    };
+
+    let out = run(&["find", "org.example.pkg.A", "--format", "paths"])?;
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert_eq!(
+        String::from_utf8(out.stdout)?,
+        format!("{}\n{}\n", old.display(), new.display())
+    );
+    assert!(
+        out.stderr.is_empty(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+
+    let filtered = run(&["find", "A", "--format", "paths", "--version", "2.0"])?;
+    assert_eq!(
+        String::from_utf8(filtered.stdout)?,
+        format!("{}\n", new.display())
+    );
+    for selection in [&["--latest"][..], &["--max-versions", "1"]] {
+        let newest = run(&[&["find", "A", "--format", "paths"][..], selection].concat())?;
+        assert_eq!(
+            String::from_utf8(newest.stdout)?,
+            format!("{}\n", new.display())
+        );
+    }
+    let conflicting = run(&["find", "A", "--latest", "--version", "2.0"])?;
+    assert_eq!(conflicting.status.code(), Some(3));
+    let error: Value = serde_json::from_slice(&conflicting.stdout)?;
+    assert_eq!(error["error"]["code"], "invalid_input");
+    assert_eq!(
+        error["error"]["message"],
+        "--latest cannot be combined with --version"
+    );
+
+    let missing = run(&["find", "org.example.pkg.Missing", "-f", "paths"])?;
+    assert_eq!(missing.status.code(), Some(2));
+    assert!(missing.stdout.is_empty());
+    assert!(missing.stderr.is_empty());
+    let verbose = run(&["-v", "find", "org.example.pkg.Missing", "-f", "paths"])?;
+    assert_eq!(verbose.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&verbose.stderr).contains("not found"));
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[test]
+fn find_count_and_exists_stop_after_matching() -> anyhow::Result<()> {
+    let base = temp_dir("count_exists");
+    let m2 = base.join("m2");
+    for jar in [
+        "org/example/demo/1.0/demo-1.0.jar",
+        "org/example/demo/2.0/demo-2.0.jar",
+        "com/other/shaded/1.0/shaded-1.0.jar",
+    ] {
+        write_jar(&m2.join(jar), &[("org/example/pkg/A.class", b"")])?;
+    }
+    let home = base.join("home");
+
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let db = base.join("db.lmdb");
+    let run = |args: &[&str]| {
+        Command::new(bin)
+            .arg("--m2")
+            .arg(&m2)
+            .arg("--db")
+            .arg(&db)
+            .arg("--no-download")
+            .args(args)
+            .env("CLASS_FINDER_HOME", &home)
+            .env("HOME", &home)
+            .env_remove("CFR_JAR")
+            .output()
+    };
+    let count = |args: &[&str]| -> anyhow::Result<String> {
+        let out = run(&[&["find", "--count"][..], args].concat())?;
+        anyhow::ensure!(
+            out.status.success(),
+            "{}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+        Ok(String::from_utf8(out.stdout)?)
+    };
+
+    assert_eq!(count(&["A"])?, "3\n");
+    assert_eq!(count(&["org.example.pkg.A", "--version", "2.0"])?, "1\n");
+    assert_eq!(
+        count(&["org.example.pkg.A", "--exclude-group", "com.other"])?,
+        "2\n"
+    );
+    assert_eq!(count(&["org.example.pkg.Missing"])?, "0\n");
+
+    let exists = run(&["find", "org.example.pkg.A", "--exists"])?;
+    assert_eq!(exists.status.code(), Some(0));
+    assert!(exists.stdout.is_empty());
+    assert!(exists.stderr.is_empty());
+    let missing = run(&["find", "org.example.pkg.Missing", "--exists"])?;
+    assert_eq!(missing.status.code(), Some(2));
+    assert!(missing.stdout.is_empty());
+    let excluded = run(&[
+        "find",
+        "org.example.pkg.A",
+        "--exists",
+        "--exclude-jar-glob",
+        "**/*.jar",
+    ])?;
+    assert_eq!(excluded.status.code(), Some(2));
+
+    let stats: Value = serde_json::from_slice(&run(&["stats"])?.stdout)?;
+    assert_eq!(stats["source_entries"], 0);
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[test]
+fn second_scan_reuses_the_stored_jar_list_unless_fresh_scan() -> anyhow::Result<()> {
+    let base = temp_dir("scan_cache");
+    let m2 = base.join("m2");
+    write_jar(
+        &m2.join("org/example/demo/1.0/demo-1.0.jar"),
+        &[("org/example/pkg/A.class", b"")],
+    )?;
+    let home = base.join("home");
+
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let db = base.join("db.lmdb");
+    // `--exists` never adds to the registry, so every run takes the scan
+    // path; `-v` keeps its diagnostics on stderr.
+    let exists = |extra: &[&str]| -> anyhow::Result<String> {
+        let out = Command::new(bin)
+            .arg("--m2")
+            .arg(&m2)
+            .arg("--db")
+            .arg(&db)
+            .args(["-v", "find", "org.example.pkg.A", "--exists"])
+            .args(extra)
+            .env("CLASS_FINDER_HOME", &home)
+            .env("HOME", &home)
+            .output()?;
+        anyhow::ensure!(
+            out.status.success(),
+            "{}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+        Ok(String::from_utf8(out.stderr)?)
+    };
+
+    let cold = exists(&[])?;
+    assert!(cold.contains("scan cache miss"), "{cold}");
+    let warm = exists(&[])?;
+    assert!(warm.contains("scan cache hit"), "{warm}");
+    assert!(!warm.contains("scan cache miss"), "{warm}");
+    let fresh = exists(&["--fresh-scan"])?;
+    assert!(fresh.contains("scan cache miss"), "{fresh}");
+    assert!(!fresh.contains("scan cache hit"), "{fresh}");
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn timeout_stops_a_hung_decompile_and_reports_the_phase() -> anyhow::Result<()> {
+    let base = temp_dir("global_timeout");
+    let m2 = base.join("m2");
+    let db = base.join("db.lmdb");
+    let fake_cfr = base.join("cfr.jar");
+    write_file(&fake_cfr, "stub")?;
+    write_jar(
+        &m2.join("org/example/demo/1.0/demo-1.0.jar"),
+        &[("org/example/pkg/A.class", b"")],
+    )?;
+    let fake_java = base.join("bin/java");
+    write_file(&fake_java, "#!/bin/sh\nsleep 30\n")?;
+    make_executable(&fake_java)?;
+
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let started = std::time::Instant::now();
+    let out = Command::new(bin)
+        .args(["--m2", m2.to_str().unwrap()])
+        .args(["--db", db.to_str().unwrap()])
+        .args(["--cfr", fake_cfr.to_str().unwrap()])
+        .args(["--timeout", "1", "find", "org.example.pkg.A"])
+        .env("CLASS_FINDER_JAVA", &fake_java)
+        .output()?;
+    assert!(started.elapsed() < std::time::Duration::from_secs(20));
+    assert_eq!(out.status.code(), Some(124));
+    let report: Value = serde_json::from_slice(&out.stdout)?;
+    assert_eq!(report["error"]["code"], "timeout");
+    assert_eq!(report["error"]["details"]["phase"], "decompile");
+    assert_eq!(report["error"]["details"]["timeout_secs"], 1);
+    assert!(report["schema_version"].is_u64());
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[test]
+fn failures_carry_a_code_as_json_on_stdout_and_in_the_exit_status() -> anyhow::Result<()> {
+    let base = temp_dir("typed_errors");
+    let m2 = base.join("m2");
+    let db = base.join("db.lmdb");
+    let fake_cfr = base.join("cfr.jar");
+    write_file(&fake_cfr, "stub")?;
+    write_jar(
+        &m2.join("org/example/demo/1.0/demo-1.0.jar"),
+        &[("org/example/pkg/A.class", b"")],
+    )?;
+
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let run = |args: &[&str]| {
+        Command::new(bin)
+            .args(["--m2", m2.to_str().unwrap(), "--db", db.to_str().unwrap()])
+            .args(["--cfr", fake_cfr.to_str().unwrap()])
+            .args(args)
+            .output()
+    };
+
+    let missing = run(&["find", "org.example.pkg.Missing"])?;
+    assert_eq!(missing.status.code(), Some(2));
+    let report: Value = serde_json::from_slice(&missing.stdout)?;
+    assert_eq!(report["error"]["code"], "not_found");
+    assert_eq!(
+        report["error"]["details"]["class_name"],
+        "org.example.pkg.Missing"
+    );
+    assert!(
+        report["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("not found")
+    );
+    assert!(String::from_utf8_lossy(&missing.stderr).contains("not found"));
+
+    let code = run(&["find", "-f", "code", "org.example.pkg.Missing"])?;
+    assert_eq!(code.status.code(), Some(2));
+    assert!(code.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&code.stderr).contains("not found"));
+
+    let compat = run(&["--compat", "99", "find", "org.example.pkg.A"])?;
+    assert_eq!(compat.status.code(), Some(3));
+    let report: Value = serde_json::from_slice(&compat.stdout)?;
+    assert_eq!(report["error"]["code"], "invalid_input");
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn json_errors_share_one_shape_and_keep_partial_results() -> anyhow::Result<()> {
+    let base = temp_dir("json_error_shape");
+    let m2 = base.join("m2");
+    let db = base.join("db.lmdb");
+    let fake_cfr = base.join("cfr.jar");
+    write_file(&fake_cfr, "stub")?;
+    let jar = m2.join("org/example/demo/1.0/demo-1.0.jar");
+    write_jar(&jar, &[("org/example/pkg/A.class", b"")])?;
+    let fake_java = base.join("bin/java");
+    write_file(&fake_java, "#!/bin/sh\necho \"CFR exploded\" >&2\nexit 1\n")?;
+    make_executable(&fake_java)?;
+
+    let bin = env!("CARGO_BIN_EXE_class-finder");
+    let java_bin = fake_java.to_string_lossy().to_string();
+    let envs = [("CLASS_FINDER_JAVA", java_bin.as_str())];
+    let (m2, db, fake_cfr) = (
+        m2.to_string_lossy().to_string(),
+        db.to_string_lossy().to_string(),
+        fake_cfr.to_string_lossy().to_string(),
+    );
+    let args = |extra: &[&str]| -> Vec<String> {
+        ["--m2", &m2, "--db", &db, "--cfr", &fake_cfr]
+            .iter()
+            .chain(extra)
+            .map(|s| s.to_string())
+            .collect()
+    };
+    let run = |extra: &[&str]| {
+        let args = args(extra);
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        run_json_error(bin, &args, &envs)
+    };
+
+    // Every decompile failed, but the matched jars are still reported.
+    let (status, error) = run(&["find", "org.example.pkg.A", "--no-fallback"])?;
+    assert_eq!(status, Some(5));
+    assert_eq!(error["code"], "decompile_failed");
+    assert_eq!(error["details"]["target"], "org.example.pkg.A");
+    let partial = &error["details"]["partial"];
+    assert_eq!(partial["class_name"], "org.example.pkg.A");
+    assert_eq!(
+        partial["matched_jars"],
+        serde_json::json!([jar.to_string_lossy()])
+    );
+
+    // Commands without a --format print JSON, and so do their failures.
+    let (status, error) = run(&[
+        "export-sources",
+        "/nonexistent/missing.jar",
+        "--out",
+        "/nonexistent/out",
+    ])?;
+    assert_eq!(status, Some(1));
+    assert_eq!(error["code"], "internal");
+
+    let (_, error) = run(&["warmup"])?;
+    assert_eq!(error["code"], "invalid_input");
+
+    // The shape before version 3 keeps the code at the top level.
+    let old_args = args(&["--compat", "2", "find", "org.example.pkg.Missing"]);
+    let old = Command::new(bin).args(&old_args).envs(envs).output()?;
+    let old: Value = serde_json::from_slice(&old.stdout)?;
+    assert_eq!(old["error"], "not_found");
+    assert_eq!(old["schema_version"], 2);
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+/// Decompiles every class to an empty declaration, for library-level tests
+/// that do not go through a JVM.
+struct StubDecompiler;
+
+impl StubDecompiler {
+    fn source(class_name: &str) -> String {
+        let (package, name) = class_name.rsplit_once('.').unwrap_or(("", class_name));
+        format!("package {package};\n\npublic class {name} {{\n}}\n")
+    }
+}
+
+impl class_finder::decompiler::Decompiler for StubDecompiler {
+    fn kind(&self) -> class_finder::decompiler::DecompilerKind {
+        class_finder::decompiler::DecompilerKind::Cfr
+    }
+
+    fn decompile_class(
+        &self,
+        _jar_path: &std::path::Path,
+        class_name: &str,
+    ) -> anyhow::Result<class_finder::encoding::DecodedOutput> {
+        Ok(class_finder::encoding::DecodedOutput {
+            text: Self::source(class_name),
+            encoding: None,
+        })
+    }
+
+    fn decompile_jar(
+        &self,
+        jar_path: &std::path::Path,
+    ) -> anyhow::Result<class_finder::encoding::DecodedOutput> {
+        let text = class_finder::catalog::catalog(jar_path)?
+            .iter()
+            .map(|class_name| {
+                format!(
+                    "/*\n * Decompiled with CFR 0.152.\n */\n{}",
+                    Self::source(class_name)
+                )
+            })
+            .collect();
+        Ok(class_finder::encoding::DecodedOutput {
+            text,
+            encoding: None,
+        })
+    }
+}
+
+#[test]
+fn library_finder_finds_loads_and_indexes_without_the_binary() -> anyhow::Result<()> {
+    use class_finder::error::ClassFinderError;
+    use class_finder::find_options::FindOptions;
+    use class_finder::finder::ClassFinder;
+
+    let base = temp_dir("library_finder");
+    let m2 = base.join("m2");
+    let jar = m2.join("org/example/demo/1.0/demo-1.0.jar");
+    write_jar(
+        &jar,
+        &[("org/example/A.class", b""), ("org/example/B.class", b"")],
+    )?;
+    let finder = ClassFinder::open(base.join("db.lmdb"), m2.clone())?
+        .with_decompiler(std::sync::Arc::new(StubDecompiler));
+
+    let index = finder.index(m2.clone())?;
+    assert_eq!(index.cataloged_jars_new, 1);
+    assert_eq!(index.indexed_classes, 2);
+
+    let found = finder.find("org.example.A", &FindOptions::new())?;
+    assert_eq!(found.matched_jars, 1);
+    assert_eq!(found.versions[0].version.as_deref(), Some("1.0"));
+    assert_eq!(found.versions[0].source, "decompiled");
+    assert_eq!(found.versions[0].lookup_source, "registry");
+    assert!(!found.versions[0].cache_hit);
+    assert!(found.versions[0].content.contains("public class A"));
+
+    let load = finder.load_jar(&jar)?;
+    assert_eq!(load.classes_loaded, 2);
+    assert!(!load.skipped);
+    assert!(finder.load_jar(&jar)?.skipped);
+
+    let cached = finder.find(
+        "org.example.B",
+        &FindOptions::new().with_version(Some("1.0")),
+    )?;
+    assert!(cached.versions[0].cache_hit);
+    let stats = finder.stats()?;
+    assert_eq!(stats.source_entries, 2);
+    assert_eq!(stats.loaded_jars, 1);
+
+    let err = finder
+        .find("org.example.Missing", &FindOptions::new())
+        .unwrap_err();
+    assert_eq!(ClassFinderError::from_error(&err).code(), "not_found");
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn async_finder_finds_and_loads_like_the_blocking_one() -> anyhow::Result<()> {
+    use class_finder::async_finder::AsyncClassFinder;
+    use class_finder::error::ClassFinderError;
+    use class_finder::find_options::FindOptions;
+    use class_finder::finder::ClassFinder;
+
+    let base = temp_dir("async_finder");
+    let m2 = base.join("m2");
+    let jar = m2.join("org/example/demo/1.0/demo-1.0.jar");
+    write_jar(
+        &jar,
+        &[("org/example/A.class", b""), ("org/example/B.class", b"")],
+    )?;
+    let finder = AsyncClassFinder::new(
+        ClassFinder::open(base.join("db.lmdb"), m2.clone())?
+            .with_decompiler(std::sync::Arc::new(StubDecompiler)),
+    );
+
+    assert_eq!(finder.index(m2.clone()).await?.indexed_classes, 2);
+    let found = finder.find("A", &FindOptions::new()).await?;
+    assert_eq!(found.class_name, "org.example.A");
+    assert_eq!(found.versions[0].source, "decompiled");
+    assert!(!found.versions[0].cache_hit);
+    assert!(found.versions[0].content.contains("public class A"));
+
+    assert_eq!(finder.load_jar(jar.clone()).await?.classes_loaded, 2);
+    let cached = finder.find("org.example.B", &FindOptions::new()).await?;
+    assert!(cached.versions[0].cache_hit);
+    assert_eq!(finder.stats().await?.source_entries, 2);
+
+    let err = finder
+        .find("org.example.Missing", &FindOptions::new())
+        .await
+        .unwrap_err();
+    assert_eq!(ClassFinderError::from_error(&err).code(), "not_found");
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}
+
+#[test]
+fn library_finder_matches_without_a_decompiler_and_warms_up_groups() -> anyhow::Result<()> {
+    use class_finder::error::ClassFinderError;
+    use class_finder::find_options::FindOptions;
+    use class_finder::finder::{ClassFinder, WarmupParams};
+
+    let base = temp_dir("library_warmup");
+    let m2 = base.join("m2");
+    for version in ["1.0", "2.0"] {
+        write_jar(
+            &m2.join(format!("org/example/demo/{version}/demo-{version}.jar")),
+            &[("org/example/A.class", b"")],
+        )?;
+    }
+    let db = base.join("db.lmdb");
+
+    let finder = ClassFinder::open(db.clone(), m2.clone())?;
+    let matched = finder.match_class("A", &FindOptions::new())?;
+    assert_eq!(matched.class_name, "org.example.A");
+    assert_eq!(matched.jars.len(), 2);
+    assert!(matched.jars[0].ends_with("demo-1.0.jar"));
+    let err = finder.find_matched(matched).unwrap_err();
+    assert_eq!(ClassFinderError::from_error(&err).code(), "invalid_input");
+    drop(finder);
+
+    let finder = ClassFinder::open(db, m2)?.with_decompiler(std::sync::Arc::new(StubDecompiler));
+    let mut events = 0;
+    let params = WarmupParams {
+        group: Some("org.example"),
+        ..WarmupParams::default()
+    };
+    let warmup = finder.warmup(params, &mut |_| events += 1)?;
+    assert_eq!(warmup.targets, 2);
+    assert_eq!(warmup.succeeded, 2);
+    assert_eq!(warmup.loaded_jars.len(), 2);
+    assert_eq!(events, 2);
+    assert!(
+        finder
+            .find(
+                "org.example.A",
+                &FindOptions::new().with_version(Some("2.0"))
+            )?
+            .versions[0]
+            .cache_hit
+    );
+
+    let _ = std::fs::remove_dir_all(base);
+    Ok(())
+}